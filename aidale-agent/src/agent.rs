@@ -0,0 +1,1006 @@
+//! Agent loop.
+
+use crate::checkpoint::{AgentCheckpoint, Checkpointer};
+use crate::conversation::Conversation;
+use crate::memory::Memory;
+use aidale_core::error::AiError;
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_core::types::*;
+use aidale_plugin::tool_result::ToolResultLimiter;
+use aidale_plugin::tool_use::{
+    FunctionTool, PendingToolCall, ToolApprover, ToolErrorPolicy, ToolOutcome, ToolRegistry,
+};
+use futures::channel::mpsc;
+use futures::future::{self, Either};
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+/// Stream type alias for agent events
+pub type AgentEventStream<'a> = dyn Stream<Item = Result<AgentEvent, AiError>> + Send + Unpin + 'a;
+
+type StopPredicate = Arc<dyn Fn(&[AgentStep]) -> bool + Send + Sync>;
+
+/// Predicate over the steps taken so far; the run stops once it returns true
+#[derive(Clone)]
+pub struct StopCondition(StopPredicate);
+
+impl StopCondition {
+    /// Stop when `predicate` returns true after a step
+    pub fn new(predicate: impl Fn(&[AgentStep]) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(predicate))
+    }
+
+    /// Stop after the given tool has been called
+    pub fn tool_called(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self::new(move |steps| {
+            steps
+                .last()
+                .is_some_and(|step| step.tool_calls.iter().any(|call| call.name == name))
+        })
+    }
+
+    /// Stop after `count` steps
+    pub fn step_count(count: usize) -> Self {
+        Self::new(move |steps| steps.len() >= count)
+    }
+
+    fn matches(&self, steps: &[AgentStep]) -> bool {
+        (self.0)(steps)
+    }
+}
+
+impl Debug for StopCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StopCondition(..)")
+    }
+}
+
+/// A tool call made by the model together with its outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInvocation {
+    pub id: String,
+    pub name: String,
+    /// Arguments the tool ran with, after any change by the approver
+    pub arguments: serde_json::Value,
+    /// Tool output, or the error message that was reported to the model
+    pub result: Result<serde_json::Value, String>,
+    /// Number of executions, including retries; 0 if the call was denied
+    pub attempts: u32,
+}
+
+/// One model call and the tool executions it triggered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStep {
+    /// Zero-based step number
+    pub index: usize,
+    /// Assistant message returned by the model
+    pub message: Message,
+    pub tool_calls: Vec<ToolInvocation>,
+    pub finish_reason: FinishReason,
+    pub usage: Usage,
+}
+
+impl AgentStep {
+    /// Text content of the assistant message
+    pub fn text(&self) -> String {
+        self.message
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Why a run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The model answered without calling tools
+    Completed,
+    /// A stop condition matched
+    StopCondition,
+    /// The step limit was reached while the model still wanted tools
+    MaxSteps,
+    /// A tool whose error policy is [`ToolErrorPolicy::Abort`] failed
+    ToolFailed,
+}
+
+/// Result of [`Agent::run`]
+#[derive(Debug, Clone)]
+pub struct AgentRun {
+    /// Text of the final assistant message
+    pub output: String,
+    pub steps: Vec<AgentStep>,
+    /// Full transcript sent to the model, including system prompt and memory
+    pub messages: Vec<Message>,
+    /// Usage summed over all steps
+    pub usage: Usage,
+    pub stop_reason: StopReason,
+}
+
+/// Progress of a streamed agent run, see [`Agent::run_stream`]
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// The model is called for step `index`
+    StepStarted { index: usize },
+    /// A chunk of the model's answer text in step `index`
+    AssistantDelta { index: usize, text: String },
+    /// The model asked for a tool call; it is approved and executed next
+    ToolCallProposed {
+        index: usize,
+        id: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+    /// A tool call of step `index` finished, failed or was denied
+    ToolResult {
+        index: usize,
+        invocation: ToolInvocation,
+    },
+    /// The run is over; always the last event of a successful run
+    Finished { run: AgentRun },
+}
+
+/// Tool-using agent
+pub struct Agent {
+    executor: Arc<RuntimeExecutor>,
+    model: String,
+    system_prompt: Option<String>,
+    tools: Arc<ToolRegistry>,
+    approver: Option<Arc<dyn ToolApprover>>,
+    result_limiter: Option<ToolResultLimiter>,
+    checkpointer: Option<Arc<dyn Checkpointer>>,
+    memory: Option<Arc<dyn Memory>>,
+    max_steps: usize,
+    stop_conditions: Vec<StopCondition>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+}
+
+impl Debug for Agent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Agent")
+            .field("model", &self.model)
+            .field("tools", &self.tools.len())
+            .field("approver", &self.approver.is_some())
+            .field("result_limiter", &self.result_limiter)
+            .field("checkpointer", &self.checkpointer.is_some())
+            .field("memory", &self.memory.is_some())
+            .field("max_steps", &self.max_steps)
+            .field("stop_conditions", &self.stop_conditions.len())
+            .finish()
+    }
+}
+
+impl Agent {
+    /// Create an agent builder
+    pub fn builder(executor: Arc<RuntimeExecutor>, model: impl Into<String>) -> AgentBuilder {
+        AgentBuilder {
+            agent: Agent {
+                executor,
+                model: model.into(),
+                system_prompt: None,
+                tools: Arc::new(ToolRegistry::new()),
+                approver: None,
+                result_limiter: None,
+                checkpointer: None,
+                memory: None,
+                max_steps: 10,
+                stop_conditions: Vec::new(),
+                temperature: None,
+                max_tokens: None,
+            },
+            tools: ToolRegistry::new(),
+        }
+    }
+
+    /// Run the agent on a user input
+    pub async fn run(&self, input: impl Into<String>) -> Result<AgentRun, AiError> {
+        let state = self.start_with_memory(String::new(), input.into()).await?;
+        let run = self.drive(state, None, None).await?;
+        self.remember(run).await
+    }
+
+    /// Run the agent on a user input, streaming its progress
+    ///
+    /// The model's answers are streamed as [`AgentEvent::AssistantDelta`]s and
+    /// the stream ends with [`AgentEvent::Finished`], or with the error that
+    /// stopped the run. Memory is used as in [`Self::run`].
+    pub fn run_stream(&self, input: impl Into<String>) -> Box<AgentEventStream<'_>> {
+        let input = input.into();
+        let (events, mut receiver) = mpsc::unbounded();
+        let run = async move {
+            let state = self.start_with_memory(String::new(), input).await?;
+            let run = self.drive(state, Some(&events), None).await?;
+            self.remember(run).await
+        };
+
+        let stream = async_stream::stream! {
+            futures::pin_mut!(run);
+            // Prefer pending events so they arrive before the run's result
+            let result = loop {
+                match future::select(receiver.next(), &mut run).await {
+                    Either::Left((Some(event), _)) => yield Ok(event),
+                    Either::Left((None, _)) => break run.await,
+                    Either::Right((result, _)) => break result,
+                }
+            };
+            while let Ok(Some(event)) = receiver.try_next() {
+                yield Ok(event);
+            }
+            yield result.map(|run| AgentEvent::Finished { run });
+        };
+        Box::new(Box::pin(stream))
+    }
+
+    /// Run the agent on a user input, checkpointing after every step
+    ///
+    /// Requires a [`Checkpointer`]; an earlier checkpoint of `run_id` is
+    /// replaced. If the run is interrupted, continue it with
+    /// [`Self::resume`]. Memory is used as in [`Self::run`].
+    pub async fn run_checkpointed(
+        &self,
+        run_id: impl Into<String>,
+        input: impl Into<String>,
+    ) -> Result<AgentRun, AiError> {
+        let checkpointer = self.checkpointer()?;
+        let state = self.start_with_memory(run_id.into(), input.into()).await?;
+        checkpointer.save(&state).await?;
+        self.drive_checkpointed(state, checkpointer).await
+    }
+
+    /// Continue an interrupted [`Self::run_checkpointed`] run from its last
+    /// checkpoint
+    ///
+    /// Tool calls that were proposed but not finished are executed first.
+    pub async fn resume(&self, run_id: &str) -> Result<AgentRun, AiError> {
+        let checkpointer = self.checkpointer()?;
+        let state = checkpointer.load(run_id).await?.ok_or_else(|| {
+            AiError::invalid_request(format!("No checkpoint for run '{}'", run_id))
+        })?;
+        tracing::debug!(
+            "Resuming run {} after {} step(s)",
+            run_id,
+            state.step_count()
+        );
+        self.drive_checkpointed(state, checkpointer).await
+    }
+
+    /// Run the agent as the next turn of a conversation
+    ///
+    /// The conversation's messages are used as history instead of the
+    /// agent's memory; the new messages and the usage are recorded in it.
+    pub async fn run_in(
+        &self,
+        conversation: &mut Conversation,
+        input: impl Into<String>,
+    ) -> Result<AgentRun, AiError> {
+        let state = self.start(String::new(), conversation.messages.clone(), input.into());
+        let (run, first_new) = self.drive(state, None, None).await?;
+        conversation.record(&run.messages[first_new..], &run.usage);
+        Ok(run)
+    }
+
+    fn checkpointer(&self) -> Result<&dyn Checkpointer, AiError> {
+        self.checkpointer
+            .as_deref()
+            .ok_or_else(|| AiError::invalid_request("Agent has no checkpointer"))
+    }
+
+    /// Initial state of a run on `input` after `history`
+    fn start(&self, run_id: String, history: Vec<Message>, input: String) -> AgentCheckpoint {
+        let mut messages = Vec::new();
+        if let Some(prompt) = &self.system_prompt {
+            messages.push(Message::system(prompt.clone()));
+        }
+        messages.extend(history);
+        let first_new = messages.len();
+        messages.push(Message::user(input));
+        AgentCheckpoint {
+            run_id,
+            messages,
+            first_new,
+            ..Default::default()
+        }
+    }
+
+    /// Initial state of a run on `input` after the memory's history
+    async fn start_with_memory(
+        &self,
+        run_id: String,
+        input: String,
+    ) -> Result<AgentCheckpoint, AiError> {
+        let history = match &self.memory {
+            Some(memory) => memory.load().await?,
+            None => Vec::new(),
+        };
+        Ok(self.start(run_id, history, input))
+    }
+
+    /// Append the new messages of a run to the memory
+    async fn remember(&self, (run, first_new): (AgentRun, usize)) -> Result<AgentRun, AiError> {
+        if let Some(memory) = &self.memory {
+            memory.append(&run.messages[first_new..]).await?;
+        }
+        Ok(run)
+    }
+
+    async fn drive_checkpointed(
+        &self,
+        state: AgentCheckpoint,
+        checkpointer: &dyn Checkpointer,
+    ) -> Result<AgentRun, AiError> {
+        let run_id = state.run_id.clone();
+        let run = self.drive(state, None, Some(checkpointer)).await?;
+        let run = self.remember(run).await?;
+        checkpointer.delete(&run_id).await?;
+        Ok(run)
+    }
+
+    /// Run the loop from `state`, returning the index of the first new
+    /// message in the transcript
+    ///
+    /// With `events`, model calls are streamed and progress is sent there.
+    /// With `checkpointer`, the state is saved after every model call and
+    /// every round of tool executions.
+    async fn drive(
+        &self,
+        mut state: AgentCheckpoint,
+        events: Option<&mpsc::UnboundedSender<AgentEvent>>,
+        checkpointer: Option<&dyn Checkpointer>,
+    ) -> Result<(AgentRun, usize), AiError> {
+        let emit = |event: AgentEvent| {
+            if let Some(events) = events {
+                // The receiver is only gone once the stream was dropped
+                let _ = events.unbounded_send(event);
+            }
+        };
+        let tools = self.tools.definitions();
+        let mut stop_reason = StopReason::MaxSteps;
+
+        loop {
+            // A resumed run may still have to execute the last step's tools
+            if state.pending_tool_calls.is_empty() {
+                let index = state.steps.len();
+                if index >= self.max_steps {
+                    break;
+                }
+                let mut req =
+                    ChatCompletionRequest::new(self.model.clone(), state.messages.clone());
+                if !tools.is_empty() {
+                    req = req.with_tools(tools.clone());
+                }
+                req.temperature = self.temperature;
+                req.max_tokens = self.max_tokens;
+
+                emit(AgentEvent::StepStarted { index });
+                let (message, finish_reason, step_usage) = match events {
+                    Some(_) => self.stream_step(req, index, &emit).await?,
+                    None => {
+                        let response = self.executor.chat_completion(req).await?;
+                        let choice = response
+                            .choices
+                            .into_iter()
+                            .next()
+                            .ok_or_else(|| AiError::provider("No choices in response"))?;
+                        (choice.message, choice.finish_reason, response.usage)
+                    }
+                };
+                add_usage(&mut state.usage, &step_usage);
+
+                let calls: Vec<_> = message
+                    .content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::ToolCall {
+                            id,
+                            name,
+                            arguments,
+                        } => Some(PendingToolCall {
+                            id: id.clone(),
+                            name: name.clone(),
+                            arguments: arguments.clone(),
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+                state.messages.push(message.clone());
+                for call in &calls {
+                    emit(AgentEvent::ToolCallProposed {
+                        index,
+                        id: call.id.clone(),
+                        name: call.name.clone(),
+                        arguments: call.arguments.clone(),
+                    });
+                }
+                state.steps.push(AgentStep {
+                    index,
+                    message,
+                    tool_calls: Vec::new(),
+                    finish_reason,
+                    usage: step_usage,
+                });
+
+                if calls.is_empty() {
+                    tracing::debug!("Agent step {} finished without tool calls", index);
+                    stop_reason = StopReason::Completed;
+                    break;
+                }
+                state.pending_tool_calls = calls;
+                if let Some(checkpointer) = checkpointer {
+                    checkpointer.save(&state).await?;
+                }
+            }
+
+            // Tool failures and denials are reported to the model so it can
+            // recover
+            let index = state.steps.len() - 1;
+            let calls = std::mem::take(&mut state.pending_tool_calls);
+            let outcomes = futures::future::join_all(calls.iter().map(|call| {
+                self.tools
+                    .execute_approved(call.clone(), self.approver.as_deref())
+            }))
+            .await;
+            let mut tool_calls = Vec::with_capacity(calls.len());
+            for (PendingToolCall { id, name, .. }, outcome) in calls.into_iter().zip(outcomes) {
+                let ToolOutcome {
+                    arguments,
+                    result,
+                    attempts,
+                } = outcome;
+                let result = result.map_err(|e| e.to_string());
+                let mut content = match &result {
+                    Ok(value) => value.clone(),
+                    Err(error) => serde_json::json!({ "error": error }),
+                };
+                // Only the model sees the limited result; the step keeps it whole
+                if let Some(limiter) = &self.result_limiter {
+                    content = limiter.limit(&name, content).await;
+                }
+                state
+                    .messages
+                    .push(Message::tool_result(id.clone(), content));
+                let invocation = ToolInvocation {
+                    id,
+                    name,
+                    arguments,
+                    result,
+                    attempts,
+                };
+                if events.is_some() {
+                    emit(AgentEvent::ToolResult {
+                        index,
+                        invocation: invocation.clone(),
+                    });
+                }
+                tool_calls.push(invocation);
+            }
+            let aborted = tool_calls.iter().any(|call| {
+                // Denied calls never ran and don't count as failures
+                call.result.is_err()
+                    && call.attempts > 0
+                    && self.tools.options(&call.name).on_error == ToolErrorPolicy::Abort
+            });
+
+            tracing::debug!(
+                "Agent step {} finished with {} tool call(s)",
+                index,
+                tool_calls.len()
+            );
+            state.steps[index].tool_calls = tool_calls;
+            if let Some(checkpointer) = checkpointer {
+                checkpointer.save(&state).await?;
+            }
+
+            if aborted {
+                stop_reason = StopReason::ToolFailed;
+                break;
+            }
+            if self.stop_conditions.iter().any(|c| c.matches(&state.steps)) {
+                stop_reason = StopReason::StopCondition;
+                break;
+            }
+        }
+
+        let run = AgentRun {
+            output: state.steps.last().map(AgentStep::text).unwrap_or_default(),
+            steps: state.steps,
+            messages: state.messages,
+            usage: state.usage,
+            stop_reason,
+        };
+        Ok((run, state.first_new))
+    }
+
+    /// Stream one model call, emitting its answer text as it arrives
+    async fn stream_step(
+        &self,
+        req: ChatCompletionRequest,
+        index: usize,
+        emit: &impl Fn(AgentEvent),
+    ) -> Result<(Message, FinishReason, Usage), AiError> {
+        let mut stream = self.executor.stream(req).await?;
+        let mut text = String::new();
+        let mut reasoning = String::new();
+        let mut calls = Vec::new();
+        let mut finish_reason = None;
+        let mut usage = Usage::default();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if let Some(chunk_usage) = chunk.usage {
+                usage = chunk_usage;
+            }
+            for choice in chunk.choices.into_iter().filter(|c| c.index == 0) {
+                if let Some(delta) = choice.delta.content.filter(|d| !d.is_empty()) {
+                    text.push_str(&delta);
+                    emit(AgentEvent::AssistantDelta { index, text: delta });
+                }
+                if let Some(delta) = choice.delta.reasoning {
+                    reasoning.push_str(&delta);
+                }
+                calls.extend(choice.delta.tool_calls.unwrap_or_default());
+                if choice.finish_reason.is_some() {
+                    finish_reason = choice.finish_reason;
+                }
+            }
+        }
+
+        let finish_reason = finish_reason.unwrap_or(if calls.is_empty() {
+            FinishReason::Stop
+        } else {
+            FinishReason::ToolCalls
+        });
+        let mut message = Message::builder(Role::Assistant);
+        if !reasoning.is_empty() {
+            message = message.part(ContentPart::Reasoning { text: reasoning });
+        }
+        if !text.is_empty() || calls.is_empty() {
+            message = message.text(text);
+        }
+        for call in calls {
+            message = message.part(call);
+        }
+        Ok((message.build(), finish_reason, usage))
+    }
+}
+
+fn add_usage(total: &mut Usage, usage: &Usage) {
+    total.prompt_tokens += usage.prompt_tokens;
+    total.completion_tokens += usage.completion_tokens;
+    total.total_tokens += usage.total_tokens;
+}
+
+/// Builder for [`Agent`]
+pub struct AgentBuilder {
+    agent: Agent,
+    tools: ToolRegistry,
+}
+
+impl Debug for AgentBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AgentBuilder")
+            .field("agent", &self.agent)
+            .field("tools", &self.tools.len())
+            .finish()
+    }
+}
+
+impl AgentBuilder {
+    /// Set the system prompt
+    pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.agent.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Add a function tool
+    pub fn tool(mut self, tool: FunctionTool) -> Self {
+        self.tools.register_function(tool);
+        self
+    }
+
+    /// Use a prepared tool registry (replaces tools added with [`Self::tool`])
+    pub fn tools(mut self, registry: ToolRegistry) -> Self {
+        self.tools = registry;
+        self
+    }
+
+    /// Ask `approver` before executing each tool call
+    ///
+    /// The loop waits for the answer. A denied call is not executed and the
+    /// denial is reported to the model as the tool's error.
+    pub fn on_tool_call_pending(mut self, approver: impl ToolApprover + 'static) -> Self {
+        self.agent.approver = Some(Arc::new(approver));
+        self
+    }
+
+    /// Truncate or summarize tool results with `limiter` before they are
+    /// sent back to the model
+    pub fn limit_tool_results(mut self, limiter: ToolResultLimiter) -> Self {
+        self.agent.result_limiter = Some(limiter);
+        self
+    }
+
+    /// Save the state of [`Agent::run_checkpointed`] runs to `checkpointer`
+    pub fn checkpointer(mut self, checkpointer: Arc<dyn Checkpointer>) -> Self {
+        self.agent.checkpointer = Some(checkpointer);
+        self
+    }
+
+    /// Carry conversation history across runs
+    pub fn memory(mut self, memory: Arc<dyn Memory>) -> Self {
+        self.agent.memory = Some(memory);
+        self
+    }
+
+    /// Maximum number of model calls per run (default: 10)
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.agent.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Add a stop condition checked after every step with tool calls
+    pub fn stop_when(mut self, condition: StopCondition) -> Self {
+        self.agent.stop_conditions.push(condition);
+        self
+    }
+
+    /// Set the sampling temperature
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.agent.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the maximum tokens per model call
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.agent.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Build the agent
+    pub fn build(mut self) -> Agent {
+        self.agent.tools = Arc::new(self.tools);
+        self.agent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::BufferMemory;
+    use aidale_core::provider::{ChatCompletionStream, Provider};
+    use async_trait::async_trait;
+
+    /// Calls `add` on the first turn, then answers with the tool result;
+    /// streams the answer word by word
+    #[derive(Debug)]
+    struct ScriptedProvider;
+
+    #[async_trait]
+    impl Provider for ScriptedProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "scripted".to_string(),
+                name: "Scripted".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let last = req.messages.last().unwrap();
+            let (message, finish_reason) = match &last.content[0] {
+                ContentPart::ToolResult { result, .. } => (
+                    Message::assistant(format!("The sum is {}", result)),
+                    FinishReason::Stop,
+                ),
+                _ => (
+                    Message::builder(Role::Assistant)
+                        .tool_call("call_1", "add", serde_json::json!({"a": 2, "b": 3}))
+                        .build(),
+                    FinishReason::ToolCalls,
+                ),
+            };
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message,
+                    finish_reason,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    total_tokens: 10,
+                    ..Usage::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            let response = self.chat_completion(req).await?;
+            let choice = response.choices.into_iter().next().unwrap();
+            let chunk = |content: Option<String>, tool_calls, finish_reason| {
+                Ok(ChatCompletionChunk {
+                    id: response.id.clone(),
+                    model: response.model.clone(),
+                    choices: vec![ChoiceDelta {
+                        index: 0,
+                        delta: MessageDelta {
+                            role: None,
+                            content,
+                            reasoning: None,
+                            tool_calls,
+                        },
+                        finish_reason,
+                    }],
+                    usage: None,
+                    system_fingerprint: None,
+                    service_tier: None,
+                })
+            };
+            let mut chunks = Vec::new();
+            for part in choice.message.content {
+                match part {
+                    ContentPart::Text { text } => chunks.extend(
+                        text.split_inclusive(' ')
+                            .map(|word| chunk(Some(word.to_string()), None, None)),
+                    ),
+                    call => chunks.push(chunk(None, Some(vec![call]), None)),
+                }
+            }
+            chunks.push(chunk(None, None, Some(choice.finish_reason)));
+            Ok(Box::new(futures::stream::iter(chunks)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_stream() {
+        let executor = Arc::new(RuntimeExecutor::builder(ScriptedProvider).finish());
+        let agent = Agent::builder(executor, "test")
+            .tool(FunctionTool::new(
+                "add",
+                "Add two numbers",
+                serde_json::json!({"type": "object"}),
+                |args: serde_json::Value| async move {
+                    Ok(serde_json::json!(
+                        args["a"].as_i64().unwrap() + args["b"].as_i64().unwrap()
+                    ))
+                },
+            ))
+            .build();
+
+        let events: Vec<AgentEvent> = agent
+            .run_stream("What is 2 + 3?")
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        let mut deltas = String::new();
+        let mut kinds = Vec::new();
+        for event in &events {
+            kinds.push(match event {
+                AgentEvent::StepStarted { index } => format!("step {}", index),
+                AgentEvent::AssistantDelta { text, .. } => {
+                    deltas.push_str(text);
+                    "delta".to_string()
+                }
+                AgentEvent::ToolCallProposed { name, .. } => format!("call {}", name),
+                AgentEvent::ToolResult { invocation, .. } => {
+                    format!("result {}", invocation.result.clone().unwrap())
+                }
+                AgentEvent::Finished { .. } => "finished".to_string(),
+            });
+        }
+        kinds.dedup();
+        assert_eq!(
+            kinds,
+            ["step 0", "call add", "result 5", "step 1", "delta", "finished"]
+        );
+        assert_eq!(deltas, "The sum is 5");
+        let AgentEvent::Finished { run } = events.last().unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(run.output, "The sum is 5");
+        assert_eq!(run.steps[0].finish_reason, FinishReason::ToolCalls);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_resume() {
+        use crate::checkpoint::JsonFileCheckpointer;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Fails the save after the tools of the first step ran
+        struct CrashingCheckpointer {
+            inner: Arc<JsonFileCheckpointer>,
+            saves: AtomicUsize,
+        }
+
+        #[async_trait]
+        impl Checkpointer for CrashingCheckpointer {
+            async fn save(&self, checkpoint: &AgentCheckpoint) -> Result<(), AiError> {
+                if self.saves.fetch_add(1, Ordering::SeqCst) == 2 {
+                    return Err(AiError::other("crashed"));
+                }
+                self.inner.save(checkpoint).await
+            }
+
+            async fn load(&self, run_id: &str) -> Result<Option<AgentCheckpoint>, AiError> {
+                self.inner.load(run_id).await
+            }
+
+            async fn delete(&self, run_id: &str) -> Result<(), AiError> {
+                self.inner.delete(run_id).await
+            }
+        }
+
+        let executions = Arc::new(AtomicUsize::new(0));
+        let agent = |checkpointer: Arc<dyn Checkpointer>| {
+            let executions = executions.clone();
+            let executor = Arc::new(RuntimeExecutor::builder(ScriptedProvider).finish());
+            Agent::builder(executor, "test")
+                .tool(FunctionTool::new(
+                    "add",
+                    "Add two numbers",
+                    serde_json::json!({"type": "object"}),
+                    move |args: serde_json::Value| {
+                        executions.fetch_add(1, Ordering::SeqCst);
+                        async move {
+                            Ok(serde_json::json!(
+                                args["a"].as_i64().unwrap() + args["b"].as_i64().unwrap()
+                            ))
+                        }
+                    },
+                ))
+                .checkpointer(checkpointer)
+                .build()
+        };
+        let dir = std::env::temp_dir().join(format!("aidale-checkpoints-{}", std::process::id()));
+        let store = Arc::new(JsonFileCheckpointer::new(&dir));
+
+        let crashing = agent(Arc::new(CrashingCheckpointer {
+            inner: store.clone(),
+            saves: AtomicUsize::new(0),
+        }));
+        assert!(crashing
+            .run_checkpointed("job-1", "What is 2 + 3?")
+            .await
+            .is_err());
+        let checkpoint = store.load("job-1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.step_count(), 1);
+        assert_eq!(checkpoint.pending_tool_calls[0].name, "add");
+
+        let run = agent(store.clone()).resume("job-1").await.unwrap();
+        assert_eq!(run.output, "The sum is 5");
+        assert_eq!(run.steps.len(), 2);
+        assert_eq!(run.steps[0].tool_calls[0].result, Ok(serde_json::json!(5)));
+        // The interrupted execution is repeated
+        assert_eq!(executions.load(Ordering::SeqCst), 2);
+        assert!(store.load("job-1").await.unwrap().is_none());
+        assert!(agent(store).resume("job-1").await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tool_loop() {
+        let executor = Arc::new(RuntimeExecutor::builder(ScriptedProvider).finish());
+        let memory = Arc::new(BufferMemory::new());
+        let agent = Agent::builder(executor, "test")
+            .system_prompt("You add numbers.")
+            .tool(FunctionTool::new(
+                "add",
+                "Add two numbers",
+                serde_json::json!({"type": "object"}),
+                |args: serde_json::Value| async move {
+                    Ok(serde_json::json!(
+                        args["a"].as_i64().unwrap() + args["b"].as_i64().unwrap()
+                    ))
+                },
+            ))
+            .memory(memory.clone())
+            .build();
+
+        let run = agent.run("What is 2 + 3?").await.unwrap();
+        assert_eq!(run.output, "The sum is 5");
+        assert_eq!(run.stop_reason, StopReason::Completed);
+        assert_eq!(run.steps.len(), 2);
+        assert_eq!(run.steps[0].tool_calls[0].result, Ok(serde_json::json!(5)));
+        assert_eq!(run.usage.total_tokens, 20);
+        // user, assistant tool call, tool result, final answer
+        assert_eq!(memory.load().await.unwrap().len(), 4);
+
+        let mut conversation = Conversation::new("c");
+        agent
+            .run_in(&mut conversation, "What is 2 + 3?")
+            .await
+            .unwrap();
+        agent.run_in(&mut conversation, "Again?").await.unwrap();
+        assert_eq!(conversation.messages.len(), 8);
+        assert_eq!(conversation.usage.total_tokens, 40);
+    }
+
+    #[tokio::test]
+    async fn test_tool_approval() {
+        use aidale_plugin::tool_use::{ApprovalChannel, ToolApproval};
+
+        let add = || {
+            FunctionTool::new(
+                "add",
+                "Add two numbers",
+                serde_json::json!({"type": "object"}),
+                |args: serde_json::Value| async move {
+                    Ok(serde_json::json!(
+                        args["a"].as_i64().unwrap() + args["b"].as_i64().unwrap()
+                    ))
+                },
+            )
+        };
+        let executor = Arc::new(RuntimeExecutor::builder(ScriptedProvider).finish());
+
+        let (approver, mut requests) = ApprovalChannel::new(1);
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                assert_eq!(request.call.name, "add");
+                request.modify(serde_json::json!({"a": 2, "b": 40}));
+            }
+        });
+        let agent = Agent::builder(executor.clone(), "test")
+            .tool(add())
+            .on_tool_call_pending(approver)
+            .build();
+        let run = agent.run("What is 2 + 3?").await.unwrap();
+        assert_eq!(run.output, "The sum is 42");
+        assert_eq!(run.steps[0].tool_calls[0].arguments["b"], 40);
+
+        let agent = Agent::builder(executor, "test")
+            .tool(add())
+            .on_tool_call_pending(|_call: PendingToolCall| async {
+                ToolApproval::Deny {
+                    reason: "not now".to_string(),
+                }
+            })
+            .build();
+        let run = agent.run("What is 2 + 3?").await.unwrap();
+        let error = run.steps[0].tool_calls[0].result.clone().unwrap_err();
+        assert!(error.contains("not now"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_failure_aborts() {
+        use aidale_plugin::tool_use::ToolOptions;
+
+        let mut tools = ToolRegistry::new();
+        tools.register_with_options(
+            "add",
+            Arc::new(FunctionTool::new(
+                "add",
+                "Add two numbers",
+                serde_json::json!({"type": "object"}),
+                |_args: serde_json::Value| async move { Err(AiError::provider("overflow")) },
+            )),
+            ToolOptions::default()
+                .with_retries(2, std::time::Duration::ZERO)
+                .with_on_error(ToolErrorPolicy::Abort),
+        );
+        let executor = Arc::new(RuntimeExecutor::builder(ScriptedProvider).finish());
+        let agent = Agent::builder(executor, "test").tools(tools).build();
+
+        let run = agent.run("What is 2 + 3?").await.unwrap();
+        assert_eq!(run.stop_reason, StopReason::ToolFailed);
+        assert_eq!(run.steps.len(), 1);
+        assert_eq!(run.steps[0].tool_calls[0].attempts, 3);
+        assert!(run.steps[0].tool_calls[0].result.is_err());
+    }
+}
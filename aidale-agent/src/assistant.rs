@@ -0,0 +1,515 @@
+//! OpenAI Assistants compatibility.
+//!
+//! An [`AssistantExecutor`] runs conversation turns on an existing OpenAI
+//! assistant through the threads and runs endpoints, for teams whose
+//! assistants are already configured there. Each [`Conversation`] maps to a
+//! thread whose ID is kept in the conversation's metadata, and function
+//! calls are answered from a [`ToolRegistry`]:
+//!
+//! ```ignore
+//! let assistant = AssistantExecutor::new(executor, "asst_abc123").with_tools(registry);
+//! let mut conversation = Conversation::new("user-42");
+//! let run = assistant.run_in(&mut conversation, "Where is my order?").await?;
+//! println!("{}", run.output);
+//! ```
+//!
+//! Requests are sent with [`Provider::api_request`] through the executor's
+//! layers, so retries, logging and rate limits apply as for chat requests.
+//!
+//! [`Provider::api_request`]: aidale_core::provider::Provider::api_request
+
+use crate::agent::{AgentRun, AgentStep, StopReason, ToolInvocation};
+use crate::conversation::Conversation;
+use aidale_core::error::AiError;
+use aidale_core::provider::ApiRequest;
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_core::types::*;
+use aidale_plugin::tool_result::ToolResultLimiter;
+use aidale_plugin::tool_use::{PendingToolCall, ToolApprover, ToolRegistry};
+use serde_json::{json, Value};
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Conversation metadata key holding the thread ID
+pub const THREAD_ID_KEY: &str = "openai_thread_id";
+
+/// Runs conversation turns on an OpenAI assistant
+pub struct AssistantExecutor {
+    executor: Arc<RuntimeExecutor>,
+    assistant_id: String,
+    tools: Arc<ToolRegistry>,
+    approver: Option<Arc<dyn ToolApprover>>,
+    result_limiter: Option<ToolResultLimiter>,
+    poll_interval: Duration,
+    timeout: Duration,
+}
+
+impl Debug for AssistantExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssistantExecutor")
+            .field("assistant_id", &self.assistant_id)
+            .field("tools", &self.tools.len())
+            .field("approver", &self.approver.is_some())
+            .field("result_limiter", &self.result_limiter)
+            .field("poll_interval", &self.poll_interval)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl AssistantExecutor {
+    /// Run turns on the assistant with the given ID
+    pub fn new(executor: Arc<RuntimeExecutor>, assistant_id: impl Into<String>) -> Self {
+        Self {
+            executor,
+            assistant_id: assistant_id.into(),
+            tools: Arc::new(ToolRegistry::new()),
+            approver: None,
+            result_limiter: None,
+            poll_interval: Duration::from_millis(500),
+            timeout: Duration::from_secs(600),
+        }
+    }
+
+    /// Answer function calls from these tools
+    ///
+    /// The tool definitions are sent with every run and replace the tools
+    /// configured on the assistant for that run.
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = Arc::new(tools);
+        self
+    }
+
+    /// Ask `approver` before executing each function call
+    pub fn with_tool_approval(mut self, approver: impl ToolApprover + 'static) -> Self {
+        self.approver = Some(Arc::new(approver));
+        self
+    }
+
+    /// Truncate or summarize function call outputs with `limiter` before
+    /// they are submitted
+    pub fn with_tool_result_limit(mut self, limiter: ToolResultLimiter) -> Self {
+        self.result_limiter = Some(limiter);
+        self
+    }
+
+    /// Set how often a run's status is checked
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Set how long a run may take before it is cancelled
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// ID of the thread backing a conversation, once it has one
+    pub fn thread_id(conversation: &Conversation) -> Option<&str> {
+        conversation
+            .metadata
+            .get(THREAD_ID_KEY)
+            .and_then(Value::as_str)
+    }
+
+    /// Run the assistant as the next turn of a conversation
+    ///
+    /// The first turn creates a thread seeded with the conversation's text
+    /// messages. The new messages and the run's usage are recorded in the
+    /// conversation; usage is reported on the last step.
+    pub async fn run_in(
+        &self,
+        conversation: &mut Conversation,
+        input: impl Into<String>,
+    ) -> Result<AgentRun, AiError> {
+        let thread = match Self::thread_id(conversation) {
+            Some(thread) => thread.to_string(),
+            None => {
+                let thread = self.create_thread(&conversation.messages).await?;
+                conversation
+                    .metadata
+                    .insert(THREAD_ID_KEY.to_string(), json!(thread));
+                thread
+            }
+        };
+
+        let input = input.into();
+        self.send(ApiRequest::post(
+            format!("/threads/{}/messages", thread),
+            json!({"role": "user", "content": input}),
+        ))
+        .await?;
+
+        let mut body = json!({"assistant_id": self.assistant_id});
+        let tools: Vec<_> = self
+            .tools
+            .definitions()
+            .into_iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                        "strict": tool.strict,
+                    },
+                })
+            })
+            .collect();
+        if !tools.is_empty() {
+            body["tools"] = Value::Array(tools);
+        }
+        let run = self
+            .send(ApiRequest::post(format!("/threads/{}/runs", thread), body))
+            .await?;
+        let run_id = run["id"].as_str().unwrap_or_default().to_string();
+
+        let mut messages = vec![Message::user(input)];
+        let mut steps = Vec::new();
+        let started = aidale_core::rt::now();
+        let run = loop {
+            let run = self
+                .send(ApiRequest::get(format!(
+                    "/threads/{}/runs/{}",
+                    thread, run_id
+                )))
+                .await?;
+            match run["status"].as_str().unwrap_or_default() {
+                "completed" => break run,
+                "requires_action" => {
+                    let step = self.answer_tool_calls(&thread, &run).await?;
+                    messages.push(step.message.clone());
+                    messages.extend(step.tool_calls.iter().map(|call| {
+                        Message::tool_result(call.id.clone(), tool_output(&call.result))
+                    }));
+                    steps.push(AgentStep {
+                        index: steps.len(),
+                        ..step
+                    });
+                }
+                "queued" | "in_progress" | "cancelling" => {
+                    if started.elapsed() > self.timeout {
+                        let _ = self
+                            .send(ApiRequest::post(
+                                format!("/threads/{}/runs/{}/cancel", thread, run_id),
+                                json!({}),
+                            ))
+                            .await;
+                        return Err(AiError::timeout(format!(
+                            "Assistant run {} did not finish within {:?}",
+                            run_id, self.timeout
+                        )));
+                    }
+                    aidale_core::rt::sleep(self.poll_interval).await;
+                }
+                status => {
+                    let reason = run["last_error"]["message"]
+                        .as_str()
+                        .or(run["incomplete_details"]["reason"].as_str())
+                        .unwrap_or("no details");
+                    return Err(AiError::provider(format!(
+                        "Assistant run {} {}: {}",
+                        run_id, status, reason
+                    )));
+                }
+            }
+        };
+
+        let usage = Usage {
+            prompt_tokens: run["usage"]["prompt_tokens"].as_u64().unwrap_or(0) as u32,
+            completion_tokens: run["usage"]["completion_tokens"].as_u64().unwrap_or(0) as u32,
+            total_tokens: run["usage"]["total_tokens"].as_u64().unwrap_or(0) as u32,
+            ..Usage::default()
+        };
+        let output = self.run_output(&thread, &run_id).await?;
+        let message = Message::assistant(output.clone());
+        messages.push(message.clone());
+        steps.push(AgentStep {
+            index: steps.len(),
+            message,
+            tool_calls: Vec::new(),
+            finish_reason: FinishReason::Stop,
+            usage: usage.clone(),
+        });
+
+        conversation.record(&messages, &usage);
+        Ok(AgentRun {
+            output,
+            steps,
+            messages: conversation.messages.clone(),
+            usage,
+            stop_reason: StopReason::Completed,
+        })
+    }
+
+    /// Send a request with the Assistants API version header
+    async fn send(&self, req: ApiRequest) -> Result<Value, AiError> {
+        self.executor
+            .raw()
+            .api_request(req.with_header("OpenAI-Beta", "assistants=v2"))
+            .await
+    }
+
+    /// Create a thread holding the text of earlier messages
+    async fn create_thread(&self, history: &[Message]) -> Result<String, AiError> {
+        let messages: Vec<_> = history
+            .iter()
+            .filter_map(|msg| {
+                let role = match msg.role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                    _ => return None,
+                };
+                let text = text_of(msg);
+                (!text.is_empty()).then(|| json!({"role": role, "content": text}))
+            })
+            .collect();
+        let thread = self
+            .send(ApiRequest::post("/threads", json!({"messages": messages})))
+            .await?;
+        thread["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AiError::provider("No thread ID in response"))
+    }
+
+    /// Execute the function calls a run is waiting for and submit the
+    /// results
+    async fn answer_tool_calls(&self, thread: &str, run: &Value) -> Result<AgentStep, AiError> {
+        let calls = run["required_action"]["submit_tool_outputs"]["tool_calls"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut message = Message::builder(Role::Assistant);
+        let mut tool_calls = Vec::new();
+        let mut outputs = Vec::new();
+        for call in calls {
+            let id = call["id"].as_str().unwrap_or_default().to_string();
+            let name = call["function"]["name"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let arguments = call["function"]["arguments"]
+                .as_str()
+                .and_then(|arguments| serde_json::from_str(arguments).ok())
+                .unwrap_or(Value::Null);
+            // Tool failures and denials are reported to the model so it can
+            // recover
+            let call = PendingToolCall {
+                id: id.clone(),
+                name: name.clone(),
+                arguments: arguments.clone(),
+            };
+            let outcome = self
+                .tools
+                .execute_approved(call, self.approver.as_deref())
+                .await;
+            let result = outcome.result.map_err(|e| e.to_string());
+            let mut output = tool_output(&result);
+            if let Some(limiter) = &self.result_limiter {
+                output = limiter.limit(&name, output).await;
+            }
+            outputs.push(json!({
+                "tool_call_id": id,
+                "output": output.to_string(),
+            }));
+            message = message.tool_call(id.clone(), name.clone(), arguments.clone());
+            tool_calls.push(ToolInvocation {
+                id,
+                name,
+                arguments: outcome.arguments,
+                result,
+                attempts: outcome.attempts,
+            });
+        }
+
+        self.send(ApiRequest::post(
+            format!(
+                "/threads/{}/runs/{}/submit_tool_outputs",
+                thread,
+                run["id"].as_str().unwrap_or_default()
+            ),
+            json!({"tool_outputs": outputs}),
+        ))
+        .await?;
+
+        Ok(AgentStep {
+            index: 0,
+            message: message.build(),
+            tool_calls,
+            finish_reason: FinishReason::ToolCalls,
+            usage: Usage::default(),
+        })
+    }
+
+    /// Text of the assistant messages a run added to the thread
+    async fn run_output(&self, thread: &str, run_id: &str) -> Result<String, AiError> {
+        let messages = self
+            .send(ApiRequest::get(format!(
+                "/threads/{}/messages?run_id={}&order=asc",
+                thread, run_id
+            )))
+            .await?;
+        let texts: Vec<_> = messages["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|msg| msg["role"] == "assistant")
+            .flat_map(|msg| msg["content"].as_array().into_iter().flatten())
+            .filter_map(|part| part["text"]["value"].as_str())
+            .collect();
+        Ok(texts.join("\n"))
+    }
+}
+
+fn text_of(msg: &Message) -> String {
+    msg.content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn tool_output(result: &Result<Value, String>) -> Value {
+    match result {
+        Ok(value) => value.clone(),
+        Err(error) => json!({ "error": error }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::{ChatCompletionStream, Provider};
+    use aidale_plugin::tool_use::FunctionTool;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Assistants API that calls `add` once, then answers
+    #[derive(Debug, Default)]
+    struct ScriptedAssistants {
+        requests: Mutex<Vec<String>>,
+        polls: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl Provider for ScriptedAssistants {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "scripted".to_string(),
+                name: "Scripted".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            Err(AiError::unsupported("chat"))
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+
+        async fn api_request(&self, req: ApiRequest) -> Result<Value, AiError> {
+            assert_eq!(req.headers["OpenAI-Beta"], "assistants=v2");
+            self.requests
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", req.method.as_str(), req.path));
+            Ok(match req.path.as_str() {
+                "/threads" => json!({"id": "thread_1"}),
+                "/threads/thread_1/runs" => {
+                    assert_eq!(req.body.unwrap()["tools"][0]["function"]["name"], "add");
+                    json!({"id": "run_1", "status": "queued"})
+                }
+                "/threads/thread_1/runs/run_1" => {
+                    let mut polls = self.polls.lock().unwrap();
+                    *polls += 1;
+                    match *polls {
+                        1 => {
+                            json!({"id": "run_1", "status": "requires_action", "required_action": {
+                                "submit_tool_outputs": {"tool_calls": [{
+                                    "id": "call_1",
+                                    "type": "function",
+                                    "function": {"name": "add", "arguments": "{\"a\":2,\"b\":3}"}
+                                }]}
+                            }})
+                        }
+                        _ => json!({"id": "run_1", "status": "completed", "usage": {
+                            "prompt_tokens": 20, "completion_tokens": 5, "total_tokens": 25
+                        }}),
+                    }
+                }
+                "/threads/thread_1/runs/run_1/submit_tool_outputs" => {
+                    assert_eq!(
+                        req.body.unwrap()["tool_outputs"][0],
+                        json!({"tool_call_id": "call_1", "output": "5"})
+                    );
+                    json!({"id": "run_1", "status": "queued"})
+                }
+                path if path.starts_with("/threads/thread_1/messages?") => json!({"data": [{
+                    "role": "assistant",
+                    "content": [{"type": "text", "text": {"value": "The sum is 5", "annotations": []}}]
+                }]}),
+                _ => json!({"id": "msg_1"}),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_assistant_run() {
+        let api = Arc::new(ScriptedAssistants::default());
+        let executor =
+            Arc::new(RuntimeExecutor::builder(api.clone() as Arc<dyn Provider>).finish());
+        let mut tools = ToolRegistry::new();
+        tools.register_function(FunctionTool::new(
+            "add",
+            "Add two numbers",
+            json!({"type": "object"}),
+            |args: Value| async move {
+                Ok(json!(
+                    args["a"].as_i64().unwrap() + args["b"].as_i64().unwrap()
+                ))
+            },
+        ));
+        let assistant = AssistantExecutor::new(executor, "asst_1")
+            .with_tools(tools)
+            .with_poll_interval(Duration::ZERO);
+
+        let mut conversation = Conversation::new("c");
+        let run = assistant
+            .run_in(&mut conversation, "What is 2 + 3?")
+            .await
+            .unwrap();
+        assert_eq!(run.output, "The sum is 5");
+        assert_eq!(run.steps.len(), 2);
+        assert_eq!(run.steps[0].tool_calls[0].result, Ok(json!(5)));
+        assert_eq!(
+            AssistantExecutor::thread_id(&conversation),
+            Some("thread_1")
+        );
+        // user, assistant tool call, tool result, final answer
+        assert_eq!(conversation.messages.len(), 4);
+        assert_eq!(conversation.usage.total_tokens, 25);
+        assert_eq!(
+            api.requests.lock().unwrap()[..3],
+            [
+                "POST /threads",
+                "POST /threads/thread_1/messages",
+                "POST /threads/thread_1/runs"
+            ]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Agent run checkpoints.
+//!
+//! A long-running agent can lose hours of work to a crash or a deploy. With
+//! a [`Checkpointer`] set, [`Agent::run_checkpointed`] saves the state of the
+//! run after every model call and every round of tool executions, and
+//! [`Agent::resume`] picks an interrupted run up where it stopped:
+//!
+//! ```ignore
+//! let checkpoints = Arc::new(JsonFileCheckpointer::new("./checkpoints"));
+//! let agent = Agent::builder(executor, "gpt-4o")
+//!     .tool(deploy_tool)
+//!     .checkpointer(checkpoints.clone())
+//!     .build();
+//!
+//! let run = if checkpoints.load("job-17").await?.is_some() {
+//!     agent.resume("job-17").await?
+//! } else {
+//!     agent.run_checkpointed("job-17", "Roll out v2 to staging").await?
+//! };
+//! ```
+//!
+//! Tool calls the model made before the interruption but that didn't
+//! finish are executed on resume, so tools may run more than once and should
+//! be idempotent. The checkpoint is deleted once the run ends.
+//!
+//! [`Agent::run_checkpointed`]: crate::Agent::run_checkpointed
+//! [`Agent::resume`]: crate::Agent::resume
+
+use crate::agent::AgentStep;
+use crate::conversation::{json_path, read_json, remove_file, write_json};
+use aidale_core::error::AiError;
+use aidale_core::types::{Message, Usage};
+use aidale_plugin::tool_use::PendingToolCall;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// State of an unfinished agent run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentCheckpoint {
+    pub run_id: String,
+    /// Transcript so far, including system prompt and memory
+    pub messages: Vec<Message>,
+    /// Index of the run's first new message in `messages`
+    pub first_new: usize,
+    /// Completed steps; the last one lacks its tool results while
+    /// `pending_tool_calls` is non-empty
+    pub steps: Vec<AgentStep>,
+    /// Tool calls of the last step that still have to be executed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending_tool_calls: Vec<PendingToolCall>,
+    /// Usage summed over all steps
+    pub usage: Usage,
+}
+
+impl AgentCheckpoint {
+    /// Number of model calls made so far
+    pub fn step_count(&self) -> usize {
+        self.steps.len()
+    }
+}
+
+/// Storage for agent checkpoints, keyed by run ID
+#[async_trait]
+pub trait Checkpointer: Send + Sync {
+    /// Insert or replace the checkpoint of a run
+    async fn save(&self, checkpoint: &AgentCheckpoint) -> Result<(), AiError>;
+
+    /// Load the checkpoint of a run, or `None` if there is none
+    async fn load(&self, run_id: &str) -> Result<Option<AgentCheckpoint>, AiError>;
+
+    /// Delete the checkpoint of a run; deleting an unknown ID is not an error
+    async fn delete(&self, run_id: &str) -> Result<(), AiError>;
+}
+
+/// In-process checkpoints, for tests and runs that only need to survive
+/// transient errors
+#[derive(Debug, Default)]
+pub struct InMemoryCheckpointer {
+    checkpoints: Mutex<HashMap<String, AgentCheckpoint>>,
+}
+
+impl InMemoryCheckpointer {
+    /// Create an empty checkpointer
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Checkpointer for InMemoryCheckpointer {
+    async fn save(&self, checkpoint: &AgentCheckpoint) -> Result<(), AiError> {
+        self.checkpoints
+            .lock()
+            .unwrap()
+            .insert(checkpoint.run_id.clone(), checkpoint.clone());
+        Ok(())
+    }
+
+    async fn load(&self, run_id: &str) -> Result<Option<AgentCheckpoint>, AiError> {
+        Ok(self.checkpoints.lock().unwrap().get(run_id).cloned())
+    }
+
+    async fn delete(&self, run_id: &str) -> Result<(), AiError> {
+        self.checkpoints.lock().unwrap().remove(run_id);
+        Ok(())
+    }
+}
+
+/// Checkpointer keeping each run in `<dir>/<run_id>.json`
+///
+/// Run IDs follow the same rules as [`JsonFileStore`](crate::JsonFileStore)
+/// conversation IDs.
+#[derive(Debug, Clone)]
+pub struct JsonFileCheckpointer {
+    dir: PathBuf,
+}
+
+impl JsonFileCheckpointer {
+    /// Store files in `dir`, which is created on the first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, run_id: &str) -> Result<PathBuf, AiError> {
+        json_path(&self.dir, "run", run_id)
+    }
+}
+
+#[async_trait]
+impl Checkpointer for JsonFileCheckpointer {
+    async fn save(&self, checkpoint: &AgentCheckpoint) -> Result<(), AiError> {
+        write_json(&self.path(&checkpoint.run_id)?, checkpoint).await
+    }
+
+    async fn load(&self, run_id: &str) -> Result<Option<AgentCheckpoint>, AiError> {
+        read_json(&self.path(run_id)?).await
+    }
+
+    async fn delete(&self, run_id: &str) -> Result<(), AiError> {
+        remove_file(&self.path(run_id)?).await
+    }
+}
@@ -0,0 +1,353 @@
+//! Persistent conversations.
+//!
+//! A [`Conversation`] holds the messages of a session, tool calls and tool
+//! results included, together with its usage totals. Pass it to
+//! [`Agent::run_in`](crate::Agent::run_in) for each turn and save it to a
+//! [`ConversationStore`] to resume the session after a restart:
+//!
+//! ```ignore
+//! let store = JsonFileStore::new("./sessions");
+//! let mut conversation = store
+//!     .load("user-42")
+//!     .await?
+//!     .unwrap_or_else(|| Conversation::new("user-42"));
+//!
+//! let run = agent.run_in(&mut conversation, "And tomorrow?").await?;
+//! store.save(&conversation).await?;
+//! ```
+//!
+//! [`JsonFileStore`] keeps one JSON file per conversation; with the `sqlite`
+//! feature, [`SqliteStore`] keeps them in a single database file.
+
+use aidale_core::error::AiError;
+use aidale_core::types::{Message, Usage};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Messages and usage totals of a session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    /// History without the system prompt
+    pub messages: Vec<Message>,
+    /// Usage summed over all recorded turns
+    pub usage: Usage,
+    /// Application data stored with the conversation
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
+}
+
+impl Conversation {
+    /// Create an empty conversation
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Attach application data
+    pub fn with_metadata(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.metadata.insert(key.into(), value);
+        self
+    }
+
+    /// Append the messages and usage of a turn
+    pub fn record(&mut self, messages: &[Message], usage: &Usage) {
+        self.messages.extend_from_slice(messages);
+        self.usage.prompt_tokens += usage.prompt_tokens;
+        self.usage.completion_tokens += usage.completion_tokens;
+        self.usage.total_tokens += usage.total_tokens;
+    }
+}
+
+/// Storage for conversations, keyed by ID
+#[async_trait]
+pub trait ConversationStore: Send + Sync {
+    /// Insert or replace a conversation
+    async fn save(&self, conversation: &Conversation) -> Result<(), AiError>;
+
+    /// Load a conversation, or `None` if it was never saved
+    async fn load(&self, id: &str) -> Result<Option<Conversation>, AiError>;
+
+    /// Delete a conversation; deleting an unknown ID is not an error
+    async fn delete(&self, id: &str) -> Result<(), AiError>;
+
+    /// IDs of all stored conversations, sorted
+    async fn list(&self) -> Result<Vec<String>, AiError>;
+}
+
+/// Store keeping each conversation in `<dir>/<id>.json`
+///
+/// IDs are restricted to ASCII letters, digits, `-`, `_` and `.` (not
+/// leading) so they map to file names safely.
+#[derive(Debug, Clone)]
+pub struct JsonFileStore {
+    dir: PathBuf,
+}
+
+impl JsonFileStore {
+    /// Store files in `dir`, which is created on the first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, AiError> {
+        json_path(&self.dir, "conversation", id)
+    }
+}
+
+/// Path of `<dir>/<id>.json`, rejecting IDs that aren't safe file names
+pub(crate) fn json_path(dir: &Path, kind: &str, id: &str) -> Result<PathBuf, AiError> {
+    let valid = !id.is_empty()
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(AiError::invalid_request(format!(
+            "Invalid {} ID '{}'",
+            kind, id
+        )));
+    }
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+pub(crate) fn io_error(action: &str, path: &Path, e: std::io::Error) -> AiError {
+    AiError::other(format!("Failed to {} {}: {}", action, path.display(), e))
+}
+
+/// Write `value` as JSON to `path`, creating its directory
+///
+/// The data goes to a temporary file first so a crash never leaves a
+/// truncated file behind.
+pub(crate) async fn write_json(path: &Path, value: &impl Serialize) -> Result<(), AiError> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| io_error("create", dir, e))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(value)?)
+        .await
+        .map_err(|e| io_error("write", &tmp, e))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .map_err(|e| io_error("write", path, e))
+}
+
+/// Read JSON from `path`, or `None` if the file doesn't exist
+pub(crate) async fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, AiError> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(io_error("read", path, e)),
+    }
+}
+
+/// Remove `path`; a missing file is not an error
+pub(crate) async fn remove_file(path: &Path) -> Result<(), AiError> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(io_error("delete", path, e)),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl ConversationStore for JsonFileStore {
+    async fn save(&self, conversation: &Conversation) -> Result<(), AiError> {
+        write_json(&self.path(&conversation.id)?, conversation).await
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Conversation>, AiError> {
+        read_json(&self.path(id)?).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), AiError> {
+        remove_file(&self.path(id)?).await
+    }
+
+    async fn list(&self) -> Result<Vec<String>, AiError> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error("read", &self.dir, e)),
+        };
+        let mut ids = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| io_error("read", &self.dir, e))?
+        {
+            let name = entry.file_name();
+            if let Some(id) = name.to_str().and_then(|n| n.strip_suffix(".json")) {
+                ids.push(id.to_string());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{Conversation, ConversationStore};
+    use aidale_core::error::AiError;
+    use async_trait::async_trait;
+    use rusqlite::{Connection, OptionalExtension};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    /// Store keeping conversations as JSON in a SQLite table
+    ///
+    /// Queries run on the blocking thread pool.
+    #[derive(Debug, Clone)]
+    pub struct SqliteStore {
+        conn: Arc<Mutex<Connection>>,
+    }
+
+    fn sqlite_error(e: rusqlite::Error) -> AiError {
+        AiError::other(format!("SQLite error: {}", e))
+    }
+
+    impl SqliteStore {
+        /// Open or create a database file
+        pub fn open(path: impl AsRef<Path>) -> Result<Self, AiError> {
+            Self::init(Connection::open(path).map_err(sqlite_error)?)
+        }
+
+        /// Use a private in-memory database
+        pub fn in_memory() -> Result<Self, AiError> {
+            Self::init(Connection::open_in_memory().map_err(sqlite_error)?)
+        }
+
+        fn init(conn: Connection) -> Result<Self, AiError> {
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS conversations (
+                    id TEXT PRIMARY KEY,
+                    data TEXT NOT NULL
+                )",
+                [],
+            )
+            .map_err(sqlite_error)?;
+            Ok(Self {
+                conn: Arc::new(Mutex::new(conn)),
+            })
+        }
+
+        async fn with_conn<T: Send + 'static>(
+            &self,
+            f: impl FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+        ) -> Result<T, AiError> {
+            let conn = self.conn.clone();
+            tokio::task::spawn_blocking(move || f(&conn.lock().unwrap()))
+                .await
+                .map_err(|e| AiError::other(e.to_string()))?
+                .map_err(sqlite_error)
+        }
+    }
+
+    #[async_trait]
+    impl ConversationStore for SqliteStore {
+        async fn save(&self, conversation: &Conversation) -> Result<(), AiError> {
+            let id = conversation.id.clone();
+            let data = serde_json::to_string(conversation)?;
+            self.with_conn(move |conn| {
+                conn.execute(
+                    "INSERT INTO conversations (id, data) VALUES (?1, ?2)
+                     ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+                    (id, data),
+                )
+                .map(|_| ())
+            })
+            .await
+        }
+
+        async fn load(&self, id: &str) -> Result<Option<Conversation>, AiError> {
+            let id = id.to_string();
+            let data: Option<String> = self
+                .with_conn(move |conn| {
+                    conn.query_row(
+                        "SELECT data FROM conversations WHERE id = ?1",
+                        [id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                })
+                .await?;
+            Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+        }
+
+        async fn delete(&self, id: &str) -> Result<(), AiError> {
+            let id = id.to_string();
+            self.with_conn(move |conn| {
+                conn.execute("DELETE FROM conversations WHERE id = ?1", [id])
+                    .map(|_| ())
+            })
+            .await
+        }
+
+        async fn list(&self) -> Result<Vec<String>, AiError> {
+            self.with_conn(|conn| {
+                let mut stmt = conn.prepare("SELECT id FROM conversations ORDER BY id")?;
+                let ids = stmt.query_map([], |row| row.get(0))?;
+                ids.collect()
+            })
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn round_trip(store: &dyn ConversationStore) {
+        let mut conversation =
+            Conversation::new("session-1").with_metadata("user", serde_json::json!("ada"));
+        conversation.record(
+            &[
+                Message::user("Hi"),
+                Message::tool_result("call_1", serde_json::json!({"temp": 21})),
+            ],
+            &Usage {
+                total_tokens: 7,
+                ..Usage::default()
+            },
+        );
+        store.save(&conversation).await.unwrap();
+        store.save(&Conversation::new("session-0")).await.unwrap();
+
+        let loaded = store.load("session-1").await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded.messages).unwrap(),
+            serde_json::to_value(&conversation.messages).unwrap()
+        );
+        assert_eq!(loaded.usage.total_tokens, 7);
+        assert_eq!(loaded.metadata["user"], "ada");
+        assert_eq!(store.list().await.unwrap(), ["session-0", "session-1"]);
+
+        store.delete("session-1").await.unwrap();
+        store.delete("session-1").await.unwrap();
+        assert!(store.load("session-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stores() {
+        let dir = std::env::temp_dir().join(format!("aidale-conversations-{}", std::process::id()));
+        let store = JsonFileStore::new(&dir);
+        round_trip(&store).await;
+        assert!(store.load("../etc/passwd").await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+
+        #[cfg(feature = "sqlite")]
+        round_trip(&SqliteStore::in_memory().unwrap()).await;
+    }
+}
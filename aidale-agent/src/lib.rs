@@ -0,0 +1,50 @@
+//! # Aidale Agent
+//!
+//! Tool-using agents on top of [`RuntimeExecutor`](aidale_core::RuntimeExecutor).
+//!
+//! An [`Agent`] bundles a model, system prompt, tools and optional memory,
+//! and [`Agent::run`] performs the act/observe loop: call the model, execute
+//! the tool calls it makes, feed the results back, and repeat until the model
+//! answers without tools, a stop condition matches, or the step limit is hit.
+//! With [`AgentBuilder::on_tool_call_pending`] every tool call waits for the
+//! application to approve, deny or modify it first. [`Agent::run_stream`]
+//! reports the same loop as a stream of [`AgentEvent`]s, including the
+//! model's answer text as it is generated, for UIs showing live progress.
+//! Long-running agents can save their state to a
+//! [`Checkpointer`] after every step and [`Agent::resume`] after a restart.
+//!
+//! ```ignore
+//! let agent = Agent::builder(executor, "gpt-4o")
+//!     .system_prompt("You are a helpful weather assistant.")
+//!     .tool(weather_tool)
+//!     .max_steps(5)
+//!     .build();
+//!
+//! let run = agent.run("Do I need an umbrella in Paris?").await?;
+//! println!("{}", run.output);
+//! for step in &run.steps {
+//!     println!("step {}: {} tool call(s)", step.index, step.tool_calls.len());
+//! }
+//! ```
+
+pub mod agent;
+pub mod assistant;
+pub mod checkpoint;
+pub mod conversation;
+pub mod memory;
+pub mod workflow;
+
+// Re-exports
+pub use agent::{
+    Agent, AgentBuilder, AgentEvent, AgentEventStream, AgentRun, AgentStep, StopCondition,
+    StopReason, ToolInvocation,
+};
+pub use assistant::AssistantExecutor;
+pub use checkpoint::{AgentCheckpoint, Checkpointer, InMemoryCheckpointer, JsonFileCheckpointer};
+#[cfg(feature = "sqlite")]
+pub use conversation::SqliteStore;
+pub use conversation::{Conversation, ConversationStore, JsonFileStore};
+pub use memory::{BufferMemory, Memory};
+pub use workflow::{
+    EdgeCondition, Node, NodeStatus, NodeTrace, Workflow, WorkflowBuilder, WorkflowRun,
+};
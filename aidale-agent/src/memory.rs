@@ -0,0 +1,65 @@
+//! Conversation memory for agents.
+
+use aidale_core::error::AiError;
+use aidale_core::types::{Message, Role};
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+/// Conversation history carried across agent runs
+#[async_trait]
+pub trait Memory: Send + Sync {
+    /// Messages to prepend to the next run (after the system prompt)
+    async fn load(&self) -> Result<Vec<Message>, AiError>;
+
+    /// Record the messages produced by a run
+    async fn append(&self, messages: &[Message]) -> Result<(), AiError>;
+
+    /// Forget everything
+    async fn clear(&self) -> Result<(), AiError>;
+}
+
+/// In-process memory keeping the most recent messages
+#[derive(Debug, Default)]
+pub struct BufferMemory {
+    messages: Mutex<Vec<Message>>,
+    max_messages: Option<usize>,
+}
+
+impl BufferMemory {
+    /// Create an unbounded buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `max_messages` messages, dropping the oldest first
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = Some(max_messages);
+        self
+    }
+}
+
+#[async_trait]
+impl Memory for BufferMemory {
+    async fn load(&self) -> Result<Vec<Message>, AiError> {
+        Ok(self.messages.lock().unwrap().clone())
+    }
+
+    async fn append(&self, messages: &[Message]) -> Result<(), AiError> {
+        let mut stored = self.messages.lock().unwrap();
+        stored.extend_from_slice(messages);
+        if let Some(max) = self.max_messages {
+            let mut excess = stored.len().saturating_sub(max);
+            // Don't leave tool results without the call that produced them
+            while matches!(stored.get(excess), Some(m) if m.role == Role::Tool) {
+                excess += 1;
+            }
+            stored.drain(..excess);
+        }
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), AiError> {
+        self.messages.lock().unwrap().clear();
+        Ok(())
+    }
+}
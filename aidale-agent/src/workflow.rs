@@ -0,0 +1,680 @@
+//! DAG workflows.
+//!
+//! A [`Workflow`] is a fixed pipeline of nodes (prompts, tool calls and
+//! plain functions) connected by edges, run on a [`RuntimeExecutor`]. Unlike
+//! an [`Agent`](crate::Agent), the model doesn't decide what happens next:
+//! the graph does, optionally branching on the structured output of an
+//! earlier node.
+//!
+//! ```ignore
+//! let workflow = Workflow::builder(executor)
+//!     .node(
+//!         "classify",
+//!         Node::prompt("gpt-4o-mini", "Classify this ticket: {{input}}")
+//!             .with_schema(json!({
+//!                 "type": "object",
+//!                 "properties": {"team": {"enum": ["billing", "tech"]}},
+//!                 "required": ["team"],
+//!             })),
+//!     )
+//!     .node("billing", Node::prompt("gpt-4o", "Answer as billing support: {{input}}"))
+//!     .node("tech", Node::tool("open_issue", |state| json!({"title": state["input"]})))
+//!     .tools(registry)
+//!     .edge_if("classify", "billing", EdgeCondition::equals("/classify/team", "billing"))
+//!     .edge_if("classify", "tech", EdgeCondition::equals("/classify/team", "tech"))
+//!     .build()?;
+//!
+//! let run = workflow.run("I was charged twice").await?;
+//! println!("{:?}", run.output());
+//! ```
+//!
+//! All nodes share one JSON state object: the run's input is stored under
+//! `"input"` and each node's output under the node's name. Prompt templates
+//! refer to it with `{{path}}` placeholders, where `path` is a dot-separated
+//! path such as `classify.team`.
+//!
+//! Nodes run one at a time in dependency order. A node without incoming
+//! edges always runs; any other node runs if at least one of its incoming
+//! edges comes from a node that ran and has a condition that holds.
+//! Otherwise it is skipped, and so are the nodes only it leads to.
+
+use aidale_core::error::AiError;
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_core::types::*;
+use aidale_plugin::tool_use::ToolRegistry;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// State key holding the run's input
+pub const INPUT_KEY: &str = "input";
+
+type StatePredicate = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+type ArgumentsFn = Arc<dyn Fn(&Value) -> Value + Send + Sync>;
+type NodeFn = Arc<dyn Fn(Value) -> BoxFuture<'static, Result<Value, AiError>> + Send + Sync>;
+
+/// Predicate over the workflow state deciding whether an edge is taken
+#[derive(Clone)]
+pub struct EdgeCondition(StatePredicate);
+
+impl EdgeCondition {
+    /// Take the edge when `predicate` returns true
+    pub fn new(predicate: impl Fn(&Value) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(predicate))
+    }
+
+    /// Take the edge when the state value at the JSON `pointer` (e.g.
+    /// `/classify/team`) equals `value`
+    pub fn equals(pointer: impl Into<String>, value: impl Into<Value>) -> Self {
+        let pointer = pointer.into();
+        let value = value.into();
+        Self::new(move |state| state.pointer(&pointer) == Some(&value))
+    }
+
+    fn matches(&self, state: &Value) -> bool {
+        (self.0)(state)
+    }
+}
+
+impl Debug for EdgeCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EdgeCondition")
+    }
+}
+
+enum NodeKind {
+    Prompt {
+        model: String,
+        system: Option<String>,
+        template: String,
+        schema: Option<Value>,
+    },
+    Tool {
+        name: String,
+        arguments: ArgumentsFn,
+    },
+    Function(NodeFn),
+}
+
+/// A step of a [`Workflow`]
+pub struct Node {
+    kind: NodeKind,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Node");
+        match &self.kind {
+            NodeKind::Prompt { model, schema, .. } => s
+                .field("kind", &"prompt")
+                .field("model", model)
+                .field("structured", &schema.is_some()),
+            NodeKind::Tool { name, .. } => s.field("kind", &"tool").field("tool", name),
+            NodeKind::Function(_) => s.field("kind", &"function"),
+        };
+        s.field("retries", &self.retries).finish()
+    }
+}
+
+impl Node {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+        }
+    }
+
+    /// Send `template`, rendered from the state, to `model`; the output is
+    /// the answer text
+    pub fn prompt(model: impl Into<String>, template: impl Into<String>) -> Self {
+        Self::new(NodeKind::Prompt {
+            model: model.into(),
+            system: None,
+            template: template.into(),
+            schema: None,
+        })
+    }
+
+    /// Execute the workflow's tool `name` with the arguments `arguments`
+    /// builds from the state; the output is the tool result
+    pub fn tool(
+        name: impl Into<String>,
+        arguments: impl Fn(&Value) -> Value + Send + Sync + 'static,
+    ) -> Self {
+        Self::new(NodeKind::Tool {
+            name: name.into(),
+            arguments: Arc::new(arguments),
+        })
+    }
+
+    /// Compute the output from the state with `f`
+    pub fn function<F, Fut>(f: F) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, AiError>> + Send + 'static,
+    {
+        Self::new(NodeKind::Function(Arc::new(move |state| {
+            Box::pin(f(state))
+        })))
+    }
+
+    /// Set the system prompt of a prompt node, rendered like the template
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        if let NodeKind::Prompt { system: s, .. } = &mut self.kind {
+            *s = Some(system.into());
+        }
+        self
+    }
+
+    /// Make a prompt node output an object matching the JSON `schema`
+    /// instead of text, for edges to branch on
+    pub fn with_schema(mut self, schema: Value) -> Self {
+        if let NodeKind::Prompt { schema: s, .. } = &mut self.kind {
+            *s = Some(schema);
+        }
+        self
+    }
+
+    /// Retry a failed node up to `retries` times, waiting `delay` in between
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Edge {
+    from: usize,
+    to: usize,
+    condition: Option<EdgeCondition>,
+}
+
+/// Whether a node ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    Completed,
+    /// None of the node's incoming edges was taken
+    Skipped,
+}
+
+/// Record of one node in a [`WorkflowRun`]
+#[derive(Debug, Clone)]
+pub struct NodeTrace {
+    pub node: String,
+    pub status: NodeStatus,
+    /// Number of executions, including retries; 0 if skipped
+    pub attempts: u32,
+    pub duration: Duration,
+    /// Usage of a prompt node
+    pub usage: Usage,
+}
+
+/// Result of [`Workflow::run`]
+#[derive(Debug, Clone)]
+pub struct WorkflowRun {
+    /// The input and the outputs of all completed nodes, by node name
+    pub state: Value,
+    /// One entry per node, in execution order
+    pub trace: Vec<NodeTrace>,
+    /// Usage summed over all prompt nodes
+    pub usage: Usage,
+}
+
+impl WorkflowRun {
+    /// Output of the last node that ran
+    pub fn output(&self) -> Option<&Value> {
+        self.trace
+            .iter()
+            .rev()
+            .find(|t| t.status == NodeStatus::Completed)
+            .and_then(|t| self.state.get(&t.node))
+    }
+}
+
+/// A validated graph of nodes, see the [module docs](self)
+pub struct Workflow {
+    executor: Arc<RuntimeExecutor>,
+    tools: Arc<ToolRegistry>,
+    names: Vec<String>,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    /// Node indices in dependency order
+    order: Vec<usize>,
+}
+
+impl Debug for Workflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Workflow")
+            .field("nodes", &self.names)
+            .field("edges", &self.edges.len())
+            .field("tools", &self.tools.len())
+            .finish()
+    }
+}
+
+impl Workflow {
+    /// Create a workflow builder
+    pub fn builder(executor: Arc<RuntimeExecutor>) -> WorkflowBuilder {
+        WorkflowBuilder {
+            executor,
+            tools: ToolRegistry::new(),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Run the workflow on `input`
+    ///
+    /// Stops at the first node that still fails after its retries.
+    pub async fn run(&self, input: impl Into<Value>) -> Result<WorkflowRun, AiError> {
+        let mut state = json!({ INPUT_KEY: input.into() });
+        let mut completed = vec![false; self.nodes.len()];
+        let mut trace = Vec::with_capacity(self.nodes.len());
+        let mut usage = Usage::default();
+
+        for &index in &self.order {
+            let name = &self.names[index];
+            let mut incoming = self.edges.iter().filter(|e| e.to == index).peekable();
+            let active = incoming.peek().is_none()
+                || incoming.any(|e| {
+                    completed[e.from] && e.condition.as_ref().map_or(true, |c| c.matches(&state))
+                });
+            if !active {
+                tracing::debug!("Workflow node {} skipped", name);
+                trace.push(NodeTrace {
+                    node: name.clone(),
+                    status: NodeStatus::Skipped,
+                    attempts: 0,
+                    duration: Duration::ZERO,
+                    usage: Usage::default(),
+                });
+                continue;
+            }
+
+            let node = &self.nodes[index];
+            let started = Instant::now();
+            let mut attempts = 0;
+            let (output, node_usage) = loop {
+                attempts += 1;
+                match self.execute(node, &state).await {
+                    Ok(result) => break result,
+                    Err(e) if attempts <= node.retries => {
+                        tracing::warn!(
+                            "Workflow node {} failed (attempt {}): {}",
+                            name,
+                            attempts,
+                            e
+                        );
+                        tokio::time::sleep(node.retry_delay).await;
+                    }
+                    Err(e) => {
+                        return Err(AiError::other(format!(
+                            "Workflow node '{}' failed: {}",
+                            name, e
+                        )))
+                    }
+                }
+            };
+            tracing::debug!("Workflow node {} completed", name);
+
+            add_usage(&mut usage, &node_usage);
+            state[name.as_str()] = output;
+            completed[index] = true;
+            trace.push(NodeTrace {
+                node: name.clone(),
+                status: NodeStatus::Completed,
+                attempts,
+                duration: started.elapsed(),
+                usage: node_usage,
+            });
+        }
+
+        Ok(WorkflowRun {
+            state,
+            trace,
+            usage,
+        })
+    }
+
+    async fn execute(&self, node: &Node, state: &Value) -> Result<(Value, Usage), AiError> {
+        match &node.kind {
+            NodeKind::Prompt {
+                model,
+                system,
+                template,
+                schema,
+            } => {
+                let mut messages = Vec::new();
+                if let Some(system) = system {
+                    messages.push(Message::system(render(system, state)?));
+                }
+                messages.push(Message::user(render(template, state)?));
+                match schema {
+                    Some(schema) => {
+                        let params = ObjectParams::new(messages, schema.clone());
+                        let result = self.executor.generate_object(model.clone(), params).await?;
+                        Ok((result.object, result.usage))
+                    }
+                    None => {
+                        let params = TextParams::new(messages);
+                        let result = self.executor.generate_text(model.clone(), params).await?;
+                        Ok((Value::String(result.content), result.usage))
+                    }
+                }
+            }
+            NodeKind::Tool { name, arguments } => {
+                let output = self.tools.execute(name, &arguments(state)).await?;
+                Ok((output, Usage::default()))
+            }
+            NodeKind::Function(f) => Ok((f(state.clone()).await?, Usage::default())),
+        }
+    }
+}
+
+fn add_usage(total: &mut Usage, usage: &Usage) {
+    total.prompt_tokens += usage.prompt_tokens;
+    total.completion_tokens += usage.completion_tokens;
+    total.total_tokens += usage.total_tokens;
+}
+
+/// Replace `{{path}}` placeholders with state values; strings are inserted
+/// as-is, other values as JSON
+fn render(template: &str, state: &Value) -> Result<String, AiError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let path = rest[start + 2..start + 2 + len].trim();
+        let value = path
+            .split('.')
+            .try_fold(state, |value, key| match value {
+                Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => value.get(key),
+            })
+            .ok_or_else(|| {
+                AiError::invalid_request(format!("Unknown template variable '{}'", path))
+            })?;
+        match value {
+            Value::String(s) => out.push_str(s),
+            other => out.push_str(&other.to_string()),
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Builder for [`Workflow`]
+pub struct WorkflowBuilder {
+    executor: Arc<RuntimeExecutor>,
+    tools: ToolRegistry,
+    nodes: Vec<(String, Node)>,
+    edges: Vec<(String, String, Option<EdgeCondition>)>,
+}
+
+impl Debug for WorkflowBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkflowBuilder")
+            .field("nodes", &self.nodes)
+            .field("edges", &self.edges.len())
+            .finish()
+    }
+}
+
+impl WorkflowBuilder {
+    /// Add a node; its output is stored in the state under `name`
+    pub fn node(mut self, name: impl Into<String>, node: Node) -> Self {
+        self.nodes.push((name.into(), node));
+        self
+    }
+
+    /// Always run `to` after `from`
+    pub fn edge(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.edges.push((from.into(), to.into(), None));
+        self
+    }
+
+    /// Run `to` after `from` if `condition` holds for the state at that point
+    pub fn edge_if(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        condition: EdgeCondition,
+    ) -> Self {
+        self.edges.push((from.into(), to.into(), Some(condition)));
+        self
+    }
+
+    /// Tools available to tool nodes
+    pub fn tools(mut self, registry: ToolRegistry) -> Self {
+        self.tools = registry;
+        self
+    }
+
+    /// Validate the graph
+    ///
+    /// Fails on duplicate or reserved node names, edges to unknown nodes,
+    /// tool nodes naming unregistered tools, and cycles.
+    pub fn build(self) -> Result<Workflow, AiError> {
+        let mut indices = HashMap::new();
+        for (i, (name, node)) in self.nodes.iter().enumerate() {
+            if name == INPUT_KEY || indices.insert(name.clone(), i).is_some() {
+                return Err(AiError::invalid_request(format!(
+                    "Invalid or duplicate workflow node name '{}'",
+                    name
+                )));
+            }
+            if let NodeKind::Tool { name: tool, .. } = &node.kind {
+                if !self.tools.contains(tool) {
+                    return Err(AiError::invalid_request(format!(
+                        "Workflow node '{}' uses unknown tool '{}'",
+                        name, tool
+                    )));
+                }
+            }
+        }
+        let index = |name: &str| {
+            indices.get(name).copied().ok_or_else(|| {
+                AiError::invalid_request(format!("Unknown workflow node '{}'", name))
+            })
+        };
+        let edges = self
+            .edges
+            .into_iter()
+            .map(|(from, to, condition)| {
+                Ok(Edge {
+                    from: index(&from)?,
+                    to: index(&to)?,
+                    condition,
+                })
+            })
+            .collect::<Result<Vec<_>, AiError>>()?;
+
+        // Kahn's algorithm, keeping insertion order among ready nodes
+        let mut pending: Vec<usize> = vec![0; self.nodes.len()];
+        for edge in &edges {
+            pending[edge.to] += 1;
+        }
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut ready: Vec<usize> = (0..self.nodes.len()).filter(|&i| pending[i] == 0).collect();
+        while !ready.is_empty() {
+            let next = ready.remove(0);
+            order.push(next);
+            for edge in edges.iter().filter(|e| e.from == next) {
+                pending[edge.to] -= 1;
+                if pending[edge.to] == 0 {
+                    ready.push(edge.to);
+                }
+            }
+        }
+        if order.len() < self.nodes.len() {
+            return Err(AiError::invalid_request("Workflow graph has a cycle"));
+        }
+
+        let (names, nodes) = self.nodes.into_iter().unzip();
+        Ok(Workflow {
+            executor: self.executor,
+            tools: Arc::new(self.tools),
+            names,
+            nodes,
+            edges,
+            order,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::{ChatCompletionStream, Provider};
+    use aidale_plugin::tool_use::FunctionTool;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Classifies requests that ask for it, echoes everything else
+    #[derive(Debug)]
+    struct EchoProvider;
+
+    #[async_trait]
+    impl Provider for EchoProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "echo".to_string(),
+                name: "Echo".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let prompt = match &req.messages.last().unwrap().content[0] {
+                ContentPart::Text { text } => text.clone(),
+                _ => String::new(),
+            };
+            let answer = if prompt.starts_with("Classify") {
+                json!({"team": "billing"}).to_string()
+            } else {
+                format!("Echo: {}", prompt)
+            };
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(answer),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    total_tokens: 10,
+                    ..Usage::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workflow() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        tools.register_function(FunctionTool::new(
+            "lookup",
+            "Look up an invoice",
+            json!({"type": "object"}),
+            move |args: Value| {
+                let calls = calls.clone();
+                async move {
+                    // Fails once to exercise retries
+                    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        return Err(AiError::other("busy"));
+                    }
+                    Ok(json!({"invoice": args["id"], "amount": 42}))
+                }
+            },
+        ));
+        let executor = Arc::new(RuntimeExecutor::builder(EchoProvider).finish());
+        let workflow = Workflow::builder(executor)
+            .tools(tools)
+            .node(
+                "classify",
+                Node::prompt("test", "Classify: {{input.text}}")
+                    .with_schema(json!({"type": "object"})),
+            )
+            .node(
+                "lookup",
+                Node::tool("lookup", |state| json!({"id": state["input"]["invoice"]}))
+                    .with_retries(1, Duration::ZERO),
+            )
+            .node("tech", Node::prompt("test", "Fix {{input.text}}"))
+            .node(
+                "reply",
+                Node::prompt("test", "Refund {{lookup.amount}} for {{classify.team}}"),
+            )
+            .edge_if(
+                "classify",
+                "lookup",
+                EdgeCondition::equals("/classify/team", "billing"),
+            )
+            .edge_if(
+                "classify",
+                "tech",
+                EdgeCondition::equals("/classify/team", "tech"),
+            )
+            .edge("lookup", "reply")
+            .build()
+            .unwrap();
+
+        let run = workflow
+            .run(json!({"text": "Charged twice", "invoice": "INV-7"}))
+            .await
+            .unwrap();
+        assert_eq!(run.output(), Some(&json!("Echo: Refund 42 for billing")));
+        let trace: Vec<_> = run
+            .trace
+            .iter()
+            .map(|t| (t.node.as_str(), t.status, t.attempts))
+            .collect();
+        assert_eq!(
+            trace,
+            [
+                ("classify", NodeStatus::Completed, 1),
+                ("lookup", NodeStatus::Completed, 2),
+                ("tech", NodeStatus::Skipped, 0),
+                ("reply", NodeStatus::Completed, 1),
+            ]
+        );
+        assert_eq!(run.usage.total_tokens, 20);
+
+        let cyclic = Workflow::builder(Arc::new(RuntimeExecutor::builder(EchoProvider).finish()))
+            .node("a", Node::prompt("test", "a"))
+            .node("b", Node::prompt("test", "b"))
+            .edge("a", "b")
+            .edge("b", "a")
+            .build();
+        assert!(cyclic.is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Text-to-speech and speech-to-text requests.
+//!
+//! [`Provider::speech`] turns text into audio and [`Provider::transcription`]
+//! turns audio into text. Both go through the provider's layers like chat
+//! requests, so voice pipelines get the same retries and logging:
+//!
+//! ```ignore
+//! let transcript = executor
+//!     .transcription(TranscriptionRequest::from_path("whisper-1", "question.m4a")?)
+//!     .await?;
+//! let answer = executor.generate_text(/* ... */).await?;
+//! let speech = executor
+//!     .speech(SpeechRequest::new("tts-1", answer.content, "alloy").with_format(AudioFileFormat::Opus))
+//!     .await?;
+//! std::fs::write(format!("answer.{}", speech.format.extension()), speech.audio)?;
+//! ```
+//!
+//! [`Provider::speech`]: crate::provider::Provider::speech
+//! [`Provider::transcription`]: crate::provider::Provider::transcription
+
+use crate::error::AiError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Container or encoding of an audio file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFileFormat {
+    #[default]
+    Mp3,
+    /// Opus in an Ogg container
+    Opus,
+    Aac,
+    Flac,
+    Wav,
+    /// Raw 16-bit little-endian PCM, 24 kHz mono, without a header
+    Pcm,
+    /// MPEG-4 audio
+    M4a,
+    Webm,
+    /// Ogg with a codec other than Opus, e.g. Vorbis
+    Ogg,
+}
+
+impl AudioFileFormat {
+    /// Format of an audio file from its signature
+    ///
+    /// Raw PCM has no signature and is never detected.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(Self::Wav),
+            [b'f', b'L', b'a', b'C', ..] => Some(Self::Flac),
+            [b'I', b'D', b'3', ..] => Some(Self::Mp3),
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(Self::M4a),
+            [0x1A, 0x45, 0xDF, 0xA3, ..] => Some(Self::Webm),
+            [b'O', b'g', b'g', b'S', ..] => {
+                // The first page holds the codec identification header
+                let opus = bytes.windows(8).take(64).any(|w| w == b"OpusHead");
+                Some(if opus { Self::Opus } else { Self::Ogg })
+            }
+            // ADTS AAC: sync word with layer 0
+            [0xFF, b, ..] if b & 0xF6 == 0xF0 => Some(Self::Aac),
+            // MPEG audio frame sync
+            [0xFF, b, ..] if b & 0xE0 == 0xE0 => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+
+    /// Format of an audio file from its extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "mp3" | "mpga" | "mpeg" => Some(Self::Mp3),
+            "opus" => Some(Self::Opus),
+            "aac" => Some(Self::Aac),
+            "flac" => Some(Self::Flac),
+            "wav" => Some(Self::Wav),
+            "pcm" => Some(Self::Pcm),
+            "m4a" | "mp4" => Some(Self::M4a),
+            "webm" => Some(Self::Webm),
+            "ogg" | "oga" => Some(Self::Ogg),
+            _ => None,
+        }
+    }
+
+    /// Usual file extension
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::Opus => "opus",
+            Self::Aac => "aac",
+            Self::Flac => "flac",
+            Self::Wav => "wav",
+            Self::Pcm => "pcm",
+            Self::M4a => "m4a",
+            Self::Webm => "webm",
+            Self::Ogg => "ogg",
+        }
+    }
+
+    /// MIME type, e.g. for a `Content-Type` header
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Opus => "audio/opus",
+            Self::Aac => "audio/aac",
+            Self::Flac => "audio/flac",
+            Self::Wav => "audio/wav",
+            Self::Pcm => "audio/pcm",
+            Self::M4a => "audio/mp4",
+            Self::Webm => "audio/webm",
+            Self::Ogg => "audio/ogg",
+        }
+    }
+}
+
+impl fmt::Display for AudioFileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Request to synthesize speech from text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeechRequest {
+    pub model: String,
+    pub input: String,
+    /// Provider voice name, e.g. `alloy`
+    pub voice: String,
+    /// Format of the returned audio
+    pub format: AudioFileFormat,
+    /// Playback speed, where 1.0 is normal
+    pub speed: Option<f32>,
+    /// How to speak, e.g. "calm and slow"; not supported by every model
+    pub instructions: Option<String>,
+}
+
+impl SpeechRequest {
+    /// Create a request for MP3 audio
+    pub fn new(
+        model: impl Into<String>,
+        input: impl Into<String>,
+        voice: impl Into<String>,
+    ) -> Self {
+        Self {
+            model: model.into(),
+            input: input.into(),
+            voice: voice.into(),
+            format: AudioFileFormat::default(),
+            speed: None,
+            instructions: None,
+        }
+    }
+
+    /// Set the format of the returned audio
+    pub fn with_format(mut self, format: AudioFileFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the playback speed
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Set instructions on tone and delivery
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+}
+
+/// Synthesized speech
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Speech {
+    pub audio: Vec<u8>,
+    pub format: AudioFileFormat,
+}
+
+/// Request to transcribe audio
+#[derive(Clone, PartialEq)]
+pub struct TranscriptionRequest {
+    pub model: String,
+    pub audio: Vec<u8>,
+    pub format: AudioFileFormat,
+    /// ISO-639-1 language of the audio; detected when not set
+    pub language: Option<String>,
+    /// Text preceding the audio, or spellings of names and terms
+    pub prompt: Option<String>,
+    pub temperature: Option<f32>,
+    /// Return [`TranscriptionSegment`]s with timestamps
+    pub timestamps: bool,
+}
+
+impl fmt::Debug for TranscriptionRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TranscriptionRequest")
+            .field("model", &self.model)
+            .field("audio", &format_args!("{} bytes", self.audio.len()))
+            .field("format", &self.format)
+            .field("language", &self.language)
+            .field("prompt", &self.prompt)
+            .field("temperature", &self.temperature)
+            .field("timestamps", &self.timestamps)
+            .finish()
+    }
+}
+
+impl TranscriptionRequest {
+    /// Create a request for audio in a known format
+    pub fn new(model: impl Into<String>, audio: Vec<u8>, format: AudioFileFormat) -> Self {
+        Self {
+            model: model.into(),
+            audio,
+            format,
+            language: None,
+            prompt: None,
+            temperature: None,
+            timestamps: false,
+        }
+    }
+
+    /// Create a request detecting the format from the file signature
+    pub fn from_bytes(model: impl Into<String>, audio: Vec<u8>) -> Result<Self, AiError> {
+        let format = AudioFileFormat::detect(&audio)
+            .ok_or_else(|| AiError::invalid_request("Unrecognized audio format"))?;
+        Ok(Self::new(model, audio, format))
+    }
+
+    /// Create a request from an audio file
+    ///
+    /// The format is detected from the file signature, falling back to the
+    /// extension.
+    pub fn from_path(model: impl Into<String>, path: impl AsRef<Path>) -> Result<Self, AiError> {
+        let path = path.as_ref();
+        let audio = std::fs::read(path).map_err(|e| {
+            AiError::invalid_request(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let format = AudioFileFormat::detect(&audio)
+            .or_else(|| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .and_then(AudioFileFormat::from_extension)
+            })
+            .ok_or_else(|| {
+                AiError::invalid_request(format!(
+                    "{} is not a supported audio file",
+                    path.display()
+                ))
+            })?;
+        Ok(Self::new(model, audio, format))
+    }
+
+    /// Set the language of the audio
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Set a prompt guiding style and vocabulary
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Set the sampling temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Request segment timestamps
+    pub fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// File name to upload the audio under; some APIs detect the format from it
+    pub fn filename(&self) -> String {
+        format!("audio.{}", self.format.extension())
+    }
+}
+
+/// Transcribed span of audio
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionSegment {
+    /// Start in seconds
+    pub start: f64,
+    /// End in seconds
+    pub end: f64,
+    pub text: String,
+}
+
+/// Text of transcribed audio
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transcription {
+    pub text: String,
+    /// Detected or requested language
+    pub language: Option<String>,
+    /// Length of the audio in seconds
+    pub duration: Option<f64>,
+    /// Timestamped segments, if requested
+    pub segments: Vec<TranscriptionSegment>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_audio_format() {
+        let wav = b"RIFF\x24\x00\x00\x00WAVEfmt ";
+        assert_eq!(AudioFileFormat::detect(wav), Some(AudioFileFormat::Wav));
+        assert_eq!(
+            AudioFileFormat::detect(b"ID3\x04\x00"),
+            Some(AudioFileFormat::Mp3)
+        );
+        assert_eq!(
+            AudioFileFormat::detect(&[0xFF, 0xFB, 0x90, 0x00]),
+            Some(AudioFileFormat::Mp3)
+        );
+        assert_eq!(
+            AudioFileFormat::detect(&[0xFF, 0xF1, 0x50, 0x80]),
+            Some(AudioFileFormat::Aac)
+        );
+        assert_eq!(
+            AudioFileFormat::detect(b"\x00\x00\x00\x20ftypM4A "),
+            Some(AudioFileFormat::M4a)
+        );
+        let opus = b"OggS\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x13OpusHead";
+        assert_eq!(AudioFileFormat::detect(opus), Some(AudioFileFormat::Opus));
+        assert_eq!(AudioFileFormat::detect(&[0, 0, 0, 0]), None);
+
+        assert_eq!(
+            AudioFileFormat::from_extension("MP4"),
+            Some(AudioFileFormat::M4a)
+        );
+        assert!(TranscriptionRequest::from_bytes("whisper-1", vec![0; 16]).is_err());
+        let request = TranscriptionRequest::from_bytes("whisper-1", wav.to_vec()).unwrap();
+        assert_eq!(request.filename(), "audio.wav");
+    }
+}
@@ -0,0 +1,133 @@
+//! Per-request deadlines and token budgets.
+//!
+//! Both are carried on [`RequestContext`](crate::types::RequestContext) and
+//! checked by the runtime before every provider call, so multi-round runs
+//! stop cleanly with [`AiError::DeadlineExceeded`] or
+//! [`AiError::BudgetExhausted`] once a limit is hit.
+
+use crate::error::AiError;
+use crate::rt::{self, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Point in time by which a request must complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Deadline at a specific instant
+    pub fn at(instant: Instant) -> Self {
+        Self(instant)
+    }
+
+    /// Deadline `timeout` from now
+    pub fn after(timeout: Duration) -> Self {
+        Self(rt::now() + timeout)
+    }
+
+    /// The deadline instant
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Time left before the deadline (zero once expired)
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(rt::now())
+    }
+
+    /// Whether the deadline has passed
+    pub fn is_expired(&self) -> bool {
+        rt::now() >= self.0
+    }
+
+    /// Return [`AiError::DeadlineExceeded`] if the deadline has passed
+    pub fn check(&self) -> Result<(), AiError> {
+        if self.is_expired() {
+            Err(AiError::deadline_exceeded("request deadline has passed"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Token budget shared by every request that holds a clone of it
+#[derive(Debug, Clone)]
+pub struct TokenBudget {
+    limit: u64,
+    used: Arc<AtomicU64>,
+}
+
+impl TokenBudget {
+    /// Create a budget of `limit` total tokens
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Total tokens allowed
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Tokens consumed so far
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Tokens left (zero once exhausted)
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used())
+    }
+
+    /// Whether the budget is used up
+    pub fn is_exhausted(&self) -> bool {
+        self.used() >= self.limit
+    }
+
+    /// Record consumed tokens
+    pub fn consume(&self, tokens: u64) {
+        self.used.fetch_add(tokens, Ordering::Relaxed);
+    }
+
+    /// Return [`AiError::BudgetExhausted`] if the budget is used up
+    pub fn check(&self) -> Result<(), AiError> {
+        if self.is_exhausted() {
+            Err(AiError::BudgetExhausted {
+                used: self.used(),
+                limit: self.limit,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_budget() {
+        let budget = TokenBudget::new(100);
+        let shared = budget.clone();
+
+        shared.consume(60);
+        assert_eq!(budget.remaining(), 40);
+        assert!(budget.check().is_ok());
+
+        budget.consume(50);
+        assert!(matches!(
+            shared.check(),
+            Err(AiError::BudgetExhausted {
+                used: 110,
+                limit: 100
+            })
+        ));
+
+        assert!(Deadline::after(Duration::from_secs(60)).check().is_ok());
+        assert!(Deadline::at(rt::now()).check().is_err());
+    }
+}
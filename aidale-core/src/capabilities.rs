@@ -0,0 +1,104 @@
+//! Model capability descriptors.
+//!
+//! Describes limits of well-known models so layers can adapt requests, e.g.
+//! truncating prompts that would exceed the context window.
+
+use serde::{Deserialize, Serialize};
+
+/// Capabilities and limits of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    /// Maximum number of tokens in prompt plus completion
+    pub context_window: u32,
+    /// Maximum number of completion tokens, if limited separately
+    pub max_output_tokens: Option<u32>,
+}
+
+impl ModelCapabilities {
+    /// Create capabilities with a context window
+    pub const fn new(context_window: u32) -> Self {
+        Self {
+            context_window,
+            max_output_tokens: None,
+        }
+    }
+
+    /// Set the maximum number of completion tokens
+    pub const fn with_max_output_tokens(mut self, max_output_tokens: u32) -> Self {
+        self.max_output_tokens = Some(max_output_tokens);
+        self
+    }
+}
+
+/// Known model families, matched by prefix (more specific prefixes first)
+const KNOWN_MODELS: &[(&str, ModelCapabilities)] = &[
+    (
+        "gpt-4o-mini",
+        ModelCapabilities::new(128_000).with_max_output_tokens(16_384),
+    ),
+    (
+        "gpt-4o",
+        ModelCapabilities::new(128_000).with_max_output_tokens(16_384),
+    ),
+    (
+        "gpt-4.1",
+        ModelCapabilities::new(1_047_576).with_max_output_tokens(32_768),
+    ),
+    (
+        "gpt-4-turbo",
+        ModelCapabilities::new(128_000).with_max_output_tokens(4_096),
+    ),
+    ("gpt-4", ModelCapabilities::new(8_192)),
+    (
+        "gpt-3.5-turbo",
+        ModelCapabilities::new(16_385).with_max_output_tokens(4_096),
+    ),
+    (
+        "o1",
+        ModelCapabilities::new(200_000).with_max_output_tokens(100_000),
+    ),
+    (
+        "o3",
+        ModelCapabilities::new(200_000).with_max_output_tokens(100_000),
+    ),
+    (
+        "o4",
+        ModelCapabilities::new(200_000).with_max_output_tokens(100_000),
+    ),
+    (
+        "deepseek-chat",
+        ModelCapabilities::new(65_536).with_max_output_tokens(8_192),
+    ),
+    (
+        "deepseek-reasoner",
+        ModelCapabilities::new(65_536).with_max_output_tokens(8_192),
+    ),
+    ("claude", ModelCapabilities::new(200_000)),
+    (
+        "command-r",
+        ModelCapabilities::new(128_000).with_max_output_tokens(4_096),
+    ),
+    (
+        "command-a",
+        ModelCapabilities::new(256_000).with_max_output_tokens(8_192),
+    ),
+    (
+        "gemini-1.5",
+        ModelCapabilities::new(1_000_000).with_max_output_tokens(8_192),
+    ),
+    (
+        "gemini-2",
+        ModelCapabilities::new(1_000_000).with_max_output_tokens(8_192),
+    ),
+];
+
+/// Look up capabilities of a well-known model.
+///
+/// Provider prefixes such as `openai/gpt-4o` (used by routers) are ignored.
+pub fn model_capabilities(model: &str) -> Option<ModelCapabilities> {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    KNOWN_MODELS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, capabilities)| *capabilities)
+}
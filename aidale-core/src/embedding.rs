@@ -0,0 +1,27 @@
+//! Embedding abstractions.
+//!
+//! Embedders turn text into dense vectors. They are used by semantic
+//! caching, retrieval and other similarity-based features.
+
+use crate::error::AiError;
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+/// Embedding vector type
+pub type Embedding = Vec<f32>;
+
+/// Trait for services that turn text into embedding vectors.
+#[async_trait]
+pub trait Embedder: Send + Sync + Debug + 'static {
+    /// Embed a batch of texts, returning one vector per input in order
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AiError>;
+
+    /// Embed a single text
+    async fn embed_one(&self, text: &str) -> Result<Embedding, AiError> {
+        self.embed(&[text.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AiError::provider("Embedder returned no vectors"))
+    }
+}
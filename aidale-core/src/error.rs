@@ -1,11 +1,78 @@
 //! Error types for AI Core operations.
 
+use std::time::Duration;
+
+/// Structured details of an error returned by a provider API.
+///
+/// Attached to the API-facing variants ([`AiError::Provider`],
+/// [`AiError::Authentication`], [`AiError::RateLimit`],
+/// [`AiError::InvalidRequest`], [`AiError::ModelNotFound`] and
+/// [`AiError::Timeout`]) when the error came from an HTTP response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiErrorDetails {
+    /// Provider ID (e.g. `openai`)
+    pub provider: String,
+    /// HTTP status code, when known
+    pub status: Option<u16>,
+    /// Provider error code (e.g. `insufficient_quota`, `model_not_found`)
+    pub code: Option<String>,
+    /// Provider error type (e.g. `invalid_request_error`)
+    pub error_type: Option<String>,
+    /// Raw error message returned by the provider
+    pub message: Option<String>,
+    /// Request ID reported by the provider (`x-request-id` and similar)
+    pub request_id: Option<String>,
+}
+
+impl ApiErrorDetails {
+    /// Create details for a provider
+    pub fn new(provider: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the HTTP status code
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Set the provider error code
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Set the provider error type
+    pub fn with_error_type(mut self, error_type: impl Into<String>) -> Self {
+        self.error_type = Some(error_type.into());
+        self
+    }
+
+    /// Set the raw provider message
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Set the provider request ID
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+}
+
 /// The main error type for AI operations.
 #[derive(Debug, thiserror::Error)]
 pub enum AiError {
     /// Provider-specific errors
-    #[error("Provider error: {0}")]
-    Provider(String),
+    #[error("Provider error: {message}")]
+    Provider {
+        message: String,
+        details: Option<Box<ApiErrorDetails>>,
+    },
 
     /// Network-related errors
     #[error("Network error: {0}")]
@@ -16,24 +83,55 @@ pub enum AiError {
     Serialization(#[from] serde_json::Error),
 
     /// Authentication errors
-    #[error("Authentication error: {0}")]
-    Authentication(String),
+    #[error("Authentication error: {message}")]
+    Authentication {
+        message: String,
+        details: Option<Box<ApiErrorDetails>>,
+    },
 
     /// Rate limit errors
-    #[error("Rate limit exceeded: {0}")]
-    RateLimit(String),
+    ///
+    /// `retry_after` carries the wait time advertised by the provider
+    /// (`Retry-After` or rate-limit reset headers), when available.
+    #[error("Rate limit exceeded: {message}")]
+    RateLimit {
+        message: String,
+        retry_after: Option<Duration>,
+        details: Option<Box<ApiErrorDetails>>,
+    },
 
     /// Invalid request errors
-    #[error("Invalid request: {0}")]
-    InvalidRequest(String),
+    #[error("Invalid request: {message}")]
+    InvalidRequest {
+        message: String,
+        details: Option<Box<ApiErrorDetails>>,
+    },
 
     /// Model not found errors
-    #[error("Model not found: {0}")]
-    ModelNotFound(String),
+    #[error("Model not found: {message}")]
+    ModelNotFound {
+        message: String,
+        details: Option<Box<ApiErrorDetails>>,
+    },
 
     /// Timeout errors
-    #[error("Request timeout: {0}")]
-    Timeout(String),
+    #[error("Request timeout: {message}")]
+    Timeout {
+        message: String,
+        details: Option<Box<ApiErrorDetails>>,
+    },
+
+    /// The request deadline passed before the provider responded
+    #[error("Deadline exceeded: {0}")]
+    DeadlineExceeded(String),
+
+    /// The token budget of the request or session is used up
+    #[error("Token budget exhausted: {used} of {limit} tokens used")]
+    BudgetExhausted { used: u64, limit: u64 },
+
+    /// Content blocked by moderation or content filters
+    #[error("Content blocked: {}", categories.join(", "))]
+    ContentBlocked { categories: Vec<String> },
 
     /// Plugin errors
     #[error("Plugin error ({plugin}): {message}")]
@@ -63,32 +161,70 @@ pub enum AiError {
 impl AiError {
     /// Create a provider error
     pub fn provider(msg: impl Into<String>) -> Self {
-        Self::Provider(msg.into())
+        Self::Provider {
+            message: msg.into(),
+            details: None,
+        }
     }
 
     /// Create an authentication error
     pub fn authentication(msg: impl Into<String>) -> Self {
-        Self::Authentication(msg.into())
+        Self::Authentication {
+            message: msg.into(),
+            details: None,
+        }
     }
 
     /// Create a rate limit error
     pub fn rate_limit(msg: impl Into<String>) -> Self {
-        Self::RateLimit(msg.into())
+        Self::RateLimit {
+            message: msg.into(),
+            retry_after: None,
+            details: None,
+        }
+    }
+
+    /// Create a rate limit error with the provider's advertised wait time
+    pub fn rate_limit_after(msg: impl Into<String>, retry_after: Option<Duration>) -> Self {
+        Self::RateLimit {
+            message: msg.into(),
+            retry_after,
+            details: None,
+        }
     }
 
     /// Create an invalid request error
     pub fn invalid_request(msg: impl Into<String>) -> Self {
-        Self::InvalidRequest(msg.into())
+        Self::InvalidRequest {
+            message: msg.into(),
+            details: None,
+        }
     }
 
     /// Create a model not found error
     pub fn model_not_found(msg: impl Into<String>) -> Self {
-        Self::ModelNotFound(msg.into())
+        Self::ModelNotFound {
+            message: msg.into(),
+            details: None,
+        }
     }
 
     /// Create a timeout error
     pub fn timeout(msg: impl Into<String>) -> Self {
-        Self::Timeout(msg.into())
+        Self::Timeout {
+            message: msg.into(),
+            details: None,
+        }
+    }
+
+    /// Create a deadline exceeded error
+    pub fn deadline_exceeded(msg: impl Into<String>) -> Self {
+        Self::DeadlineExceeded(msg.into())
+    }
+
+    /// Create a content blocked error
+    pub fn content_blocked(categories: Vec<String>) -> Self {
+        Self::ContentBlocked { categories }
     }
 
     /// Create a plugin error
@@ -127,12 +263,71 @@ impl AiError {
         Self::Other(msg.into())
     }
 
+    /// Attach API error details to an API-facing variant.
+    ///
+    /// Other variants are returned unchanged.
+    pub fn with_details(mut self, api_details: ApiErrorDetails) -> Self {
+        match &mut self {
+            AiError::Provider { details, .. }
+            | AiError::Authentication { details, .. }
+            | AiError::RateLimit { details, .. }
+            | AiError::InvalidRequest { details, .. }
+            | AiError::ModelNotFound { details, .. }
+            | AiError::Timeout { details, .. } => *details = Some(Box::new(api_details)),
+            _ => {}
+        }
+        self
+    }
+
+    /// API error details, if the error came from a provider response
+    pub fn details(&self) -> Option<&ApiErrorDetails> {
+        match self {
+            AiError::Provider { details, .. }
+            | AiError::Authentication { details, .. }
+            | AiError::RateLimit { details, .. }
+            | AiError::InvalidRequest { details, .. }
+            | AiError::ModelNotFound { details, .. }
+            | AiError::Timeout { details, .. } => details.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// HTTP status code of the provider response, if known
+    pub fn status(&self) -> Option<u16> {
+        self.details().and_then(|details| details.status)
+    }
+
+    /// Provider error code (e.g. `insufficient_quota`), if known
+    ///
+    /// Prefer branching on this over matching the `Display` output.
+    pub fn code(&self) -> Option<&str> {
+        self.details().and_then(|details| details.code.as_deref())
+    }
+
+    /// Request ID reported by the provider, if known
+    pub fn request_id(&self) -> Option<&str> {
+        self.details()
+            .and_then(|details| details.request_id.as_deref())
+    }
+
     /// Check if this is a retryable error
+    ///
+    /// Network errors, timeouts, rate limits and provider errors with a 5xx
+    /// status are retryable.
     pub fn is_retryable(&self) -> bool {
-        matches!(
-            self,
-            AiError::Network(_) | AiError::Timeout(_) | AiError::RateLimit(_)
-        )
+        match self {
+            AiError::Network(_) | AiError::Timeout { .. } | AiError::RateLimit { .. } => true,
+            AiError::Provider { .. } => matches!(self.status(), Some(status) if status >= 500),
+            _ => false,
+        }
+    }
+
+    /// Wait time advertised by the provider before retrying, if any
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AiError::RateLimit { retry_after, .. } => *retry_after,
+            _ => None,
+        }
     }
 }
 
@@ -147,3 +342,25 @@ impl From<&str> for AiError {
         Self::Other(s.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_details() {
+        let err = AiError::provider("quota").with_details(
+            ApiErrorDetails::new("openai")
+                .with_status(429)
+                .with_code("insufficient_quota")
+                .with_request_id("req_123"),
+        );
+        assert_eq!(err.code(), Some("insufficient_quota"));
+        assert_eq!(err.request_id(), Some("req_123"));
+        assert!(!err.is_retryable());
+
+        // Details are ignored on variants that do not come from providers
+        let err = AiError::configuration("bad").with_details(ApiErrorDetails::new("openai"));
+        assert!(err.details().is_none());
+    }
+}
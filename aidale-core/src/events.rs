@@ -0,0 +1,100 @@
+//! Layer lifecycle events.
+//!
+//! Retries, cache lookups and model routing happen inside layers, below the
+//! plugin hooks.
+//! Layers report them on the [`EventBus`] carried by
+//! [`ChatCompletionRequest::events`](crate::types::ChatCompletionRequest::events);
+//! the executor connects it to the request's [`RequestContext`] and, once
+//! the provider call returns, delivers the recorded events in order to the
+//! plugins' `on_retry`, `on_cache` and `on_route` hooks. This lets a single telemetry
+//! plugin see attempts, delays and cache hits next to the request lifecycle.
+//!
+//! [`RequestContext`]: crate::types::RequestContext
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A failed attempt that is about to be retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryEvent {
+    /// Retry number, starting at 1
+    pub attempt: u32,
+    /// Maximum number of retries for the request
+    pub max_retries: u32,
+    /// Delay before the retry is issued
+    pub delay: Duration,
+    /// Message of the error that triggered the retry
+    pub error: String,
+}
+
+/// A response cache lookup
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEvent {
+    /// Name of the cache layer
+    pub layer: String,
+    pub hit: bool,
+}
+
+/// A routing decision replacing the requested model
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteEvent {
+    /// Model named in the request
+    pub from: String,
+    /// Model the request was sent to
+    pub to: String,
+    /// Rule that picked the model, e.g. `tokens` or `keyword:prove`
+    pub reason: String,
+}
+
+/// Event emitted by a layer during a provider call
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerEvent {
+    Retry(RetryEvent),
+    Cache(CacheEvent),
+    Route(RouteEvent),
+}
+
+/// Queue of the layer events of a request
+///
+/// Cloning yields a handle to the same queue, so events emitted on a cloned
+/// request (e.g. by a retry) end up in the original request's queue.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    events: Arc<Mutex<Vec<LayerEvent>>>,
+}
+
+impl EventBus {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event
+    pub fn emit(&self, event: LayerEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    /// Remove and return the recorded events, oldest first
+    pub fn take(&self) -> Vec<LayerEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    /// Number of recorded events
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    /// Whether no events are recorded
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("events", &self.len())
+            .finish()
+    }
+}
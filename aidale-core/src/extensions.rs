@@ -0,0 +1,115 @@
+//! Typed extensions shared across plugin hooks.
+//!
+//! [`Extensions`] is a type map in the spirit of `http::Extensions`, except
+//! that it is shared: cloning an `Extensions` yields a handle to the same
+//! underlying map. This lets a plugin stash data in one hook (for example a
+//! tool execution trace) and another plugin read it in a later hook.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+type AnyMap = HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+/// Shared, interior-mutable type map keyed by type.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: Arc<RwLock<AnyMap>>,
+}
+
+impl Extensions {
+    /// Create an empty extensions map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a value, returning the previous value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&self, value: T) -> Option<T> {
+        self.map
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast::<T>().ok().map(|boxed| *boxed))
+    }
+
+    /// Get a clone of the value of type `T`
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.map
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Check whether a value of type `T` is present
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.read().unwrap().contains_key(&TypeId::of::<T>())
+    }
+
+    /// Remove and return the value of type `T`
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<T> {
+        self.map
+            .write()
+            .unwrap()
+            .remove(&TypeId::of::<T>())
+            .and_then(|old| old.downcast::<T>().ok().map(|boxed| *boxed))
+    }
+
+    /// Mutate the value of type `T` in place, inserting `T::default()` first
+    /// if it is missing
+    pub fn update<T, R>(&self, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Default + Send + Sync + 'static,
+    {
+        let mut map = self.map.write().unwrap();
+        let value = map
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()));
+        f(value.downcast_mut::<T>().expect("extension type mismatch"))
+    }
+
+    /// Number of stored values
+    pub fn len(&self) -> usize {
+        self.map.read().unwrap().len()
+    }
+
+    /// Whether the map is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct Trace(Vec<String>);
+
+    #[test]
+    fn test_shared_across_clones() {
+        let ext = Extensions::new();
+        let handle = ext.clone();
+
+        handle.update(|trace: &mut Trace| trace.0.push("search".into()));
+        ext.update(|trace: &mut Trace| trace.0.push("fetch".into()));
+
+        assert_eq!(
+            ext.get::<Trace>(),
+            Some(Trace(vec!["search".into(), "fetch".into()]))
+        );
+        assert_eq!(handle.insert(7u32), None);
+        assert_eq!(ext.remove::<u32>(), Some(7));
+        assert!(!ext.contains::<u32>());
+    }
+}
@@ -0,0 +1,161 @@
+//! Few-shot examples.
+//!
+//! Examples are attached to a request with `TextParams::with_example` (or
+//! the `ObjectParams` equivalent) instead of being hand-assembled into the
+//! conversation. The executor renders them after plugins have transformed
+//! the params, in the [`ExampleStyle`] chosen for its provider:
+//!
+//! ```ignore
+//! let params = TextParams::new(vec![Message::user("I love this phone")])
+//!     .with_example("The battery died in a day", "negative")
+//!     .with_example("Works as advertised", "positive");
+//! ```
+
+use crate::types::{ContentPart, Message, Role};
+use serde::{Deserialize, Serialize};
+
+/// Header of the examples block in [`ExampleStyle::SystemPrompt`]
+const EXAMPLES_HEADER: &str = "Examples of inputs and the expected outputs:";
+
+/// An input and the output the model should give for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Example {
+    pub input: Message,
+    pub output: Message,
+}
+
+impl Example {
+    /// Create a text example
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self {
+            input: Message::user(input),
+            output: Message::assistant(output),
+        }
+    }
+
+    /// Create an example from messages, e.g. with images in the input
+    ///
+    /// The roles are set to user and assistant.
+    pub fn from_messages(mut input: Message, mut output: Message) -> Self {
+        input.role = Role::User;
+        output.role = Role::Assistant;
+        Self { input, output }
+    }
+}
+
+/// How examples are placed in the conversation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExampleStyle {
+    /// Alternating user and assistant turns after the leading system
+    /// messages
+    #[default]
+    Turns,
+    /// A text block appended to the system prompt; non-text parts of the
+    /// examples are dropped
+    SystemPrompt,
+}
+
+impl ExampleStyle {
+    /// Pick a style by provider ID
+    ///
+    /// Chat providers get [`Self::Turns`]. Unknown providers, often local or
+    /// completion-style models that mistake example turns for conversation
+    /// history, get [`Self::SystemPrompt`].
+    pub fn for_provider(provider_id: &str) -> Self {
+        match provider_id {
+            "openai" | "azure" | "deepseek" | "xai" | "groq" | "fireworks" | "together"
+            | "openrouter" | "anthropic" | "bedrock" | "vertex" | "gemini" => Self::Turns,
+            _ => Self::SystemPrompt,
+        }
+    }
+
+    /// Insert `examples` into `messages`
+    pub fn render(&self, mut messages: Vec<Message>, examples: Vec<Example>) -> Vec<Message> {
+        if examples.is_empty() {
+            return messages;
+        }
+        let leading_system = messages
+            .iter()
+            .take_while(|msg| msg.role == Role::System)
+            .count();
+
+        match self {
+            Self::Turns => {
+                let turns = examples
+                    .into_iter()
+                    .flat_map(|example| [example.input, example.output]);
+                messages.splice(leading_system..leading_system, turns);
+            }
+            Self::SystemPrompt => {
+                let mut block = EXAMPLES_HEADER.to_string();
+                for example in &examples {
+                    block.push_str("\n\nInput:\n");
+                    block.push_str(&text_of(&example.input));
+                    block.push_str("\nOutput:\n");
+                    block.push_str(&text_of(&example.output));
+                }
+                match leading_system.checked_sub(1) {
+                    Some(last) => messages[last].content.push(ContentPart::Text {
+                        text: format!("\n\n{}", block),
+                    }),
+                    None => messages.insert(0, Message::system(block)),
+                }
+            }
+        }
+        messages
+    }
+}
+
+fn text_of(message: &Message) -> String {
+    message
+        .content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_examples() {
+        let messages = vec![
+            Message::system("Classify sentiment."),
+            Message::user("Great"),
+        ];
+        let examples = vec![
+            Example::new("Awful", "negative"),
+            Example::new("Fine", "positive"),
+        ];
+
+        let turns = ExampleStyle::Turns.render(messages.clone(), examples.clone());
+        let roles: Vec<_> = turns.iter().map(|msg| msg.role.clone()).collect();
+        assert_eq!(
+            roles,
+            [
+                Role::System,
+                Role::User,
+                Role::Assistant,
+                Role::User,
+                Role::Assistant,
+                Role::User
+            ]
+        );
+        assert_eq!(text_of(&turns[2]), "negative");
+
+        let prompt = ExampleStyle::SystemPrompt.render(messages, examples);
+        assert_eq!(prompt.len(), 2);
+        assert!(text_of(&prompt[0]).contains("Input:\nFine\nOutput:\npositive"));
+
+        let prompt = ExampleStyle::SystemPrompt.render(
+            vec![Message::user("Great")],
+            vec![Example::new("Awful", "negative")],
+        );
+        assert_eq!(prompt[0].role, Role::System);
+    }
+}
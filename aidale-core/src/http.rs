@@ -0,0 +1,258 @@
+//! Pluggable HTTP backend.
+//!
+//! Providers that call their APIs directly send requests through an
+//! [`HttpClient`] instead of a concrete client library. `reqwest::Client`
+//! implements it and is the default; on `wasm32` reqwest uses the browser's
+//! `fetch`, with response bodies streamed from a `ReadableStream`. Runtimes
+//! with their own fetch API, such as Cloudflare Workers, plug in by
+//! implementing the trait:
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! struct WorkerFetch;
+//!
+//! #[async_trait]
+//! impl HttpClient for WorkerFetch {
+//!     async fn send(&self, request: HttpRequest) -> Result<HttpResponse, AiError> {
+//!         // Convert to `worker::Request`, call `Fetch::Request(..).send()`,
+//!         // and wrap the body stream in an `HttpResponse`
+//!     }
+//! }
+//!
+//! let provider = CohereProvider::new(api_key).with_http_backend(Arc::new(WorkerFetch));
+//! ```
+
+use crate::error::AiError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::fmt;
+use std::pin::Pin;
+
+/// HTTP method of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl HttpMethod {
+    /// Method name as sent on the wire
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+        }
+    }
+}
+
+/// Request to send through an [`HttpClient`]
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Bytes>,
+}
+
+impl HttpRequest {
+    /// Create a request without headers or body
+    pub fn new(method: HttpMethod, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Create a GET request
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Get, url)
+    }
+
+    /// Create a POST request
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Post, url)
+    }
+
+    /// Create a PUT request
+    pub fn put(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Put, url)
+    }
+
+    /// Create a DELETE request
+    pub fn delete(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Delete, url)
+    }
+
+    /// Add a header
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add an `Authorization: Bearer` header
+    pub fn with_bearer_auth(self, token: impl fmt::Display) -> Self {
+        self.with_header("authorization", format!("Bearer {}", token))
+    }
+
+    /// Set the body
+    pub fn with_body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+}
+
+/// Stream of response body bytes
+pub type HttpBody = Pin<Box<dyn Stream<Item = Result<Bytes, AiError>> + Send>>;
+
+/// Response of an [`HttpClient`]
+pub struct HttpResponse {
+    pub status: u16,
+    /// Headers, with lowercase names
+    pub headers: HashMap<String, String>,
+    pub body: HttpBody,
+}
+
+impl HttpResponse {
+    /// Create a response; header names are lowercased
+    pub fn new(
+        status: u16,
+        headers: impl IntoIterator<Item = (String, String)>,
+        body: HttpBody,
+    ) -> Self {
+        Self {
+            status,
+            headers: headers
+                .into_iter()
+                .map(|(name, value)| (name.to_ascii_lowercase(), value))
+                .collect(),
+            body,
+        }
+    }
+
+    /// Create a response with a complete body, e.g. in tests
+    pub fn from_bytes(status: u16, body: impl Into<Bytes>) -> Self {
+        let body = body.into();
+        Self::new(
+            status,
+            Vec::new(),
+            Box::pin(futures::stream::once(async move { Ok(body) })),
+        )
+    }
+
+    /// Whether the status is 2xx
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Value of a header, looked up case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Read the whole body
+    pub async fn bytes(mut self) -> Result<Bytes, AiError> {
+        let mut body = Vec::new();
+        while let Some(chunk) = self.body.next().await {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(body.into())
+    }
+
+    /// Read the whole body as UTF-8 text
+    pub async fn text(self) -> Result<String, AiError> {
+        let bytes = self.bytes().await?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| AiError::provider(format!("Response body is not UTF-8: {}", e)))
+    }
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sends HTTP requests for providers
+///
+/// Unsuccessful statuses are returned as responses, not errors; errors are
+/// for requests that got no response at all.
+#[async_trait]
+pub trait HttpClient: Send + Sync + fmt::Debug {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, AiError>;
+}
+
+#[async_trait]
+impl HttpClient for reqwest::Client {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, AiError> {
+        let method = match request.method {
+            HttpMethod::Get => reqwest::Method::GET,
+            HttpMethod::Post => reqwest::Method::POST,
+            HttpMethod::Put => reqwest::Method::PUT,
+            HttpMethod::Delete => reqwest::Method::DELETE,
+        };
+        let mut builder = self.request(method, &request.url);
+        for (name, value) in request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        // wasm32 is single-threaded, so fetch futures never cross threads
+        #[cfg(target_arch = "wasm32")]
+        let response = send_wrapper::SendWrapper::new(builder.send()).await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let response = builder.send().await?;
+
+        let status = response.status().as_u16();
+        let headers: Vec<_> = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = value.to_str().ok()?;
+                Some((name.as_str().to_string(), value.to_string()))
+            })
+            .collect();
+        let body = response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(AiError::from));
+        #[cfg(target_arch = "wasm32")]
+        let body = send_wrapper::SendWrapper::new(body);
+        Ok(HttpResponse::new(status, headers, Box::pin(body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_response() {
+        let mut response = HttpResponse::from_bytes(429, "slow down");
+        response
+            .headers
+            .insert("retry-after".to_string(), "2".to_string());
+        assert!(!response.is_success());
+        assert_eq!(response.header("Retry-After"), Some("2"));
+        assert_eq!(response.text().await.unwrap(), "slow down");
+
+        let request = HttpRequest::post("https://api.example.com")
+            .with_bearer_auth("key")
+            .with_body("{}");
+        assert_eq!(request.method.as_str(), "POST");
+        assert_eq!(request.headers[0].1, "Bearer key");
+    }
+}
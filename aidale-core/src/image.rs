@@ -0,0 +1,196 @@
+//! Preparing local images for vision requests.
+//!
+//! [`load_image`] reads an image file, detects its MIME type from the file
+//! signature, and returns a base64 [`ContentPart::ImageData`] part. Images
+//! above [`ImageLimits`] are downscaled and re-encoded when the `image`
+//! feature is enabled; without it they are rejected.
+//!
+//! The default limits fit every supported provider: Anthropic accepts at
+//! most 5 MB per image and all providers scale larger images down to about
+//! 2048 pixels anyway, so sending more only costs bandwidth.
+
+use crate::error::AiError;
+use crate::types::ContentPart;
+use std::path::Path;
+
+/// Size limits an image must fit before it is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLimits {
+    /// Longest side in pixels
+    pub max_dimension: u32,
+    /// Encoded size in bytes, before base64
+    pub max_bytes: usize,
+}
+
+impl Default for ImageLimits {
+    fn default() -> Self {
+        Self {
+            max_dimension: 2048,
+            max_bytes: 5 * 1024 * 1024,
+        }
+    }
+}
+
+impl ImageLimits {
+    /// Create the default limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the longest side in pixels
+    pub fn with_max_dimension(mut self, max_dimension: u32) -> Self {
+        self.max_dimension = max_dimension;
+        self
+    }
+
+    /// Set the encoded size in bytes
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+/// MIME type of an image from its file signature
+pub fn detect_mime(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// MIME type of an image from its file extension
+fn mime_from_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Read an image file into a base64 image part that fits `limits`
+pub fn load_image(path: impl AsRef<Path>, limits: &ImageLimits) -> Result<ContentPart, AiError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| {
+        AiError::invalid_request(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    let mime_type = detect_mime(&bytes)
+        .or_else(|| mime_from_extension(path))
+        .ok_or_else(|| {
+            AiError::invalid_request(format!("{} is not a supported image", path.display()))
+        })?;
+    let (mime_type, bytes) = fit(mime_type, bytes, limits)?;
+    Ok(ContentPart::image_data(mime_type, &bytes))
+}
+
+/// Downscale and re-encode an image until it fits `limits`
+#[cfg(feature = "image")]
+fn fit(
+    mime_type: &'static str,
+    bytes: Vec<u8>,
+    limits: &ImageLimits,
+) -> Result<(&'static str, Vec<u8>), AiError> {
+    use image::imageops::FilterType;
+    use image::GenericImageView;
+
+    let invalid = |e: image::ImageError| AiError::invalid_request(format!("Invalid image: {}", e));
+    let image = image::load_from_memory(&bytes).map_err(invalid)?;
+    let (width, height) = image.dimensions();
+    if bytes.len() <= limits.max_bytes && width.max(height) <= limits.max_dimension {
+        return Ok((mime_type, bytes));
+    }
+
+    // JPEG is far smaller for photos; PNG keeps transparency
+    let alpha = image.color().has_alpha();
+    let mut side = width.max(height).min(limits.max_dimension);
+    while side >= 16 {
+        let scaled = image.resize(side, side, FilterType::Lanczos3);
+        if alpha {
+            let mut encoded = Vec::new();
+            scaled
+                .write_to(
+                    &mut std::io::Cursor::new(&mut encoded),
+                    image::ImageFormat::Png,
+                )
+                .map_err(invalid)?;
+            if encoded.len() <= limits.max_bytes {
+                return Ok(("image/png", encoded));
+            }
+        } else {
+            let rgb = scaled.to_rgb8();
+            for quality in [85, 70, 55] {
+                let mut encoded = Vec::new();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut encoded, quality)
+                    .encode_image(&rgb)
+                    .map_err(invalid)?;
+                if encoded.len() <= limits.max_bytes {
+                    return Ok(("image/jpeg", encoded));
+                }
+            }
+        }
+        side = side * 3 / 4;
+    }
+    Err(AiError::invalid_request(format!(
+        "Image can't be compressed below {} bytes",
+        limits.max_bytes
+    )))
+}
+
+/// Check an image against the size limit; resizing needs the `image` feature
+#[cfg(not(feature = "image"))]
+fn fit(
+    mime_type: &'static str,
+    bytes: Vec<u8>,
+    limits: &ImageLimits,
+) -> Result<(&'static str, Vec<u8>), AiError> {
+    if bytes.len() > limits.max_bytes {
+        return Err(AiError::invalid_request(format!(
+            "Image is {} bytes, above the limit of {}; enable the `image` feature to downscale it",
+            bytes.len(),
+            limits.max_bytes
+        )));
+    }
+    Ok((mime_type, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_image() {
+        let dir = std::env::temp_dir().join(format!("aidale-image-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // A 1x1 PNG saved with the wrong extension
+        let png = [
+            0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00,
+            0x00, 0x1F, 0x15, 0xC4, 0x89, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x9C, 0x63, 0xF8, 0xCF, 0xC0, 0xF0, 0x1F, 0x00, 0x05, 0x00, 0x01, 0xFF, 0x89, 0x99,
+            0x3D, 0x1D, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
+        ];
+        let path = dir.join("pixel.jpg");
+        std::fs::write(&path, png).unwrap();
+
+        let part = load_image(&path, &ImageLimits::default()).unwrap();
+        let ContentPart::ImageData { mime_type, data } = &part else {
+            panic!("expected image data, got {:?}", part);
+        };
+        assert_eq!(mime_type, "image/png");
+        assert!(data.starts_with("iVBORw0KGgo"));
+        assert_eq!(
+            part.image_url().unwrap(),
+            format!("data:image/png;base64,{}", data)
+        );
+
+        let tiny = ImageLimits::default().with_max_bytes(10);
+        assert!(load_image(&path, &tiny).is_err());
+        assert!(load_image(dir.join("missing.png"), &ImageLimits::default()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
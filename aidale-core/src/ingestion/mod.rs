@@ -0,0 +1,221 @@
+//! Document ingestion for retrieval.
+//!
+//! Splits [`Document`]s into [`Chunk`]s with a [`TextSplitter`], embeds the
+//! chunks in batches and upserts them into a [`VectorStore`]. Each record's
+//! payload holds the document metadata plus `text`, `source`, `document_id`
+//! and `chunk_index`, matching the fields the retrieval plugin reads.
+//!
+//! ```ignore
+//! let ingestor = Ingestor::new(embedder, store)
+//!     .with_splitter(Arc::new(MarkdownSplitter::new(1000, 100)));
+//!
+//! let document = Document::new("guide.md", text).with_metadata("lang", json!("en"));
+//! let chunks = ingestor.ingest(&[document]).await?;
+//! ```
+
+mod splitter;
+
+pub use splitter::{MarkdownSplitter, RecursiveCharacterSplitter, TextSplitter, TokenTextSplitter};
+
+use crate::embedding::Embedder;
+use crate::error::AiError;
+use crate::vector_store::{VectorRecord, VectorStore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Metadata attached to documents and chunks
+pub type Metadata = serde_json::Map<String, serde_json::Value>;
+
+/// A document to ingest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl Document {
+    /// Create a document without metadata
+    pub fn new(id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            text: text.into(),
+            metadata: Metadata::new(),
+        }
+    }
+
+    /// Attach a metadata field, copied to every chunk of the document
+    pub fn with_metadata(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.metadata.insert(key.into(), value);
+        self
+    }
+}
+
+/// A piece of a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    /// `{document_id}#{index}`
+    pub id: String,
+    pub document_id: String,
+    pub index: usize,
+    pub text: String,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl Chunk {
+    /// Create the `index`-th chunk of `document`
+    pub fn new(document: &Document, index: usize, text: String) -> Self {
+        Self {
+            id: format!("{}#{}", document.id, index),
+            document_id: document.id.clone(),
+            index,
+            text,
+            metadata: document.metadata.clone(),
+        }
+    }
+
+    /// Vector store payload for this chunk
+    ///
+    /// `source` defaults to the document ID unless the metadata sets it.
+    pub fn payload(&self) -> serde_json::Value {
+        let mut payload = self.metadata.clone();
+        payload
+            .entry("source")
+            .or_insert_with(|| serde_json::json!(self.document_id));
+        payload.insert("text".to_string(), serde_json::json!(self.text));
+        payload.insert(
+            "document_id".to_string(),
+            serde_json::json!(self.document_id),
+        );
+        payload.insert("chunk_index".to_string(), serde_json::json!(self.index));
+        serde_json::Value::Object(payload)
+    }
+}
+
+/// Splits, embeds and stores documents
+#[derive(Debug, Clone)]
+pub struct Ingestor {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn VectorStore>,
+    splitter: Arc<dyn TextSplitter>,
+    batch_size: usize,
+}
+
+impl Ingestor {
+    /// Create an ingestor using 1000-character chunks with 200 characters
+    /// of overlap
+    pub fn new(embedder: Arc<dyn Embedder>, store: Arc<dyn VectorStore>) -> Self {
+        Self {
+            embedder,
+            store,
+            splitter: Arc::new(RecursiveCharacterSplitter::new(1000, 200)),
+            batch_size: 64,
+        }
+    }
+
+    /// Set the text splitter
+    pub fn with_splitter(mut self, splitter: Arc<dyn TextSplitter>) -> Self {
+        self.splitter = splitter;
+        self
+    }
+
+    /// Set how many chunks are embedded per request
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Split documents into chunks without storing them
+    pub fn split(&self, documents: &[Document]) -> Vec<Chunk> {
+        documents
+            .iter()
+            .flat_map(|doc| self.splitter.split_document(doc))
+            .collect()
+    }
+
+    /// Ingest documents, returning the number of chunks stored
+    pub async fn ingest(&self, documents: &[Document]) -> Result<usize, AiError> {
+        self.ingest_chunks(self.split(documents)).await
+    }
+
+    /// Embed and store already split chunks, returning how many were stored
+    pub async fn ingest_chunks(&self, chunks: Vec<Chunk>) -> Result<usize, AiError> {
+        let total = chunks.len();
+        for batch in chunks.chunks(self.batch_size) {
+            let texts: Vec<String> = batch.iter().map(|chunk| chunk.text.clone()).collect();
+            let vectors = self.embedder.embed(&texts).await?;
+            if vectors.len() != batch.len() {
+                return Err(AiError::provider(format!(
+                    "Embedder returned {} vectors for {} chunks",
+                    vectors.len(),
+                    batch.len()
+                )));
+            }
+
+            let records = batch
+                .iter()
+                .zip(vectors)
+                .map(|(chunk, vector)| VectorRecord::new(chunk.id.clone(), vector, chunk.payload()))
+                .collect();
+            self.store.upsert(records).await?;
+        }
+
+        tracing::debug!("Ingested {} chunks", total);
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::Embedding;
+    use crate::vector_store::{InMemoryVectorStore, VectorQuery};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Embeds text by length and counts embedding calls
+    #[derive(Debug, Default)]
+    struct LengthEmbedder {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Embedder for LengthEmbedder {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AiError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(texts.iter().map(|t| vec![1.0, t.len() as f32]).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingest_batches_and_payload() {
+        let embedder = Arc::new(LengthEmbedder::default());
+        let store = Arc::new(InMemoryVectorStore::new());
+        let ingestor = Ingestor::new(embedder.clone(), store.clone())
+            .with_splitter(Arc::new(RecursiveCharacterSplitter::new(20, 0)))
+            .with_batch_size(2);
+
+        let document = Document::new(
+            "notes",
+            "Alpha beta gamma.\n\nDelta epsilon zeta.\n\nEta theta.",
+        )
+        .with_metadata("lang", serde_json::json!("en"));
+        let stored = ingestor.ingest(&[document]).await.unwrap();
+
+        assert_eq!(stored, 3);
+        assert_eq!(store.len(), 3);
+        assert_eq!(embedder.calls.load(Ordering::SeqCst), 2);
+
+        let hits = store
+            .search(&VectorQuery::new(vec![1.0, 0.0], 3).with_filter("chunk_index", 2.into()))
+            .await
+            .unwrap();
+        let payload = &hits[0].record.payload;
+        assert_eq!(hits[0].record.id, "notes#2");
+        assert_eq!(payload["text"], "Eta theta.");
+        assert_eq!(payload["source"], "notes");
+        assert_eq!(payload["lang"], "en");
+    }
+}
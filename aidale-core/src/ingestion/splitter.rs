@@ -0,0 +1,338 @@
+//! Text splitters.
+//!
+//! [`RecursiveCharacterSplitter`] tries a list of separators from coarsest
+//! (paragraphs) to finest (single characters), splitting further only the
+//! pieces that are still too long, then merges neighbouring pieces back into
+//! chunks of up to `chunk_size` with `chunk_overlap` of shared context.
+//! [`TokenTextSplitter`] does the same measured in tokens, and
+//! [`MarkdownSplitter`] first cuts a document into heading sections.
+
+use super::{Chunk, Document};
+use crate::tokenizer::TokenCounter;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Separators used for plain text, coarsest first
+const TEXT_SEPARATORS: &[&str] = &["\n\n", "\n", ". ", " ", ""];
+
+/// Separators used inside Markdown sections, coarsest first
+const MARKDOWN_SEPARATORS: &[&str] = &["\n```", "\n\n", "\n", ". ", " ", ""];
+
+/// Splits text into chunks for embedding
+pub trait TextSplitter: Send + Sync + Debug + 'static {
+    /// Split text into chunks
+    fn split(&self, text: &str) -> Vec<String>;
+
+    /// Split a document, copying its metadata to every chunk
+    fn split_document(&self, document: &Document) -> Vec<Chunk> {
+        self.split(&document.text)
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| Chunk::new(document, index, text))
+            .collect()
+    }
+}
+
+/// How chunk length is measured
+#[derive(Debug, Clone)]
+enum Length {
+    Chars,
+    Tokens(Arc<dyn TokenCounter>),
+}
+
+impl Length {
+    fn of(&self, text: &str) -> usize {
+        match self {
+            Length::Chars => text.chars().count(),
+            Length::Tokens(counter) => counter.count_tokens(text),
+        }
+    }
+}
+
+/// Recursive splitter measuring chunks in characters
+#[derive(Debug, Clone)]
+pub struct RecursiveCharacterSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+    separators: Vec<String>,
+    length: Length,
+}
+
+impl RecursiveCharacterSplitter {
+    /// Create a splitter producing chunks of up to `chunk_size` characters,
+    /// with `chunk_overlap` characters shared between neighbours
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Self {
+        let chunk_size = chunk_size.max(1);
+        Self {
+            chunk_size,
+            chunk_overlap: chunk_overlap.min(chunk_size - 1),
+            separators: TEXT_SEPARATORS.iter().map(|s| s.to_string()).collect(),
+            length: Length::Chars,
+        }
+    }
+
+    /// Replace the separators, coarsest first
+    ///
+    /// Include `""` last to allow splitting between characters.
+    pub fn with_separators<I, S>(mut self, separators: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.separators = separators.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn split_recursive(&self, text: &str, separators: &[String], out: &mut Vec<String>) {
+        let Some(position) = separators
+            .iter()
+            .position(|sep| sep.is_empty() || text.contains(sep.as_str()))
+        else {
+            push_trimmed(out, text);
+            return;
+        };
+        let rest = &separators[position + 1..];
+
+        let mut fitting = Vec::new();
+        for piece in split_keeping_separator(text, &separators[position]) {
+            if self.length.of(piece) <= self.chunk_size {
+                fitting.push(piece);
+                continue;
+            }
+            if !fitting.is_empty() {
+                self.merge(&fitting, out);
+                fitting.clear();
+            }
+            if rest.is_empty() {
+                push_trimmed(out, piece);
+            } else {
+                self.split_recursive(piece, rest, out);
+            }
+        }
+        if !fitting.is_empty() {
+            self.merge(&fitting, out);
+        }
+    }
+
+    /// Merge small pieces into chunks with a sliding overlap window
+    fn merge(&self, pieces: &[&str], out: &mut Vec<String>) {
+        let mut window: VecDeque<(&str, usize)> = VecDeque::new();
+        let mut total = 0;
+
+        for &piece in pieces {
+            let len = self.length.of(piece);
+            if total + len > self.chunk_size && !window.is_empty() {
+                push_trimmed(out, &window.iter().map(|(p, _)| *p).collect::<String>());
+                while total > self.chunk_overlap || (total + len > self.chunk_size && total > 0) {
+                    let Some((_, dropped)) = window.pop_front() else {
+                        break;
+                    };
+                    total -= dropped;
+                }
+            }
+            window.push_back((piece, len));
+            total += len;
+        }
+
+        if !window.is_empty() {
+            push_trimmed(out, &window.iter().map(|(p, _)| *p).collect::<String>());
+        }
+    }
+}
+
+impl TextSplitter for RecursiveCharacterSplitter {
+    fn split(&self, text: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        self.split_recursive(text, &self.separators, &mut out);
+        out
+    }
+}
+
+/// Recursive splitter measuring chunks in tokens
+#[derive(Debug, Clone)]
+pub struct TokenTextSplitter {
+    inner: RecursiveCharacterSplitter,
+}
+
+impl TokenTextSplitter {
+    /// Create a splitter producing chunks of up to `chunk_tokens` tokens as
+    /// counted by `counter`, with `overlap_tokens` shared between neighbours
+    pub fn new(counter: Arc<dyn TokenCounter>, chunk_tokens: usize, overlap_tokens: usize) -> Self {
+        let mut inner = RecursiveCharacterSplitter::new(chunk_tokens, overlap_tokens);
+        inner.length = Length::Tokens(counter);
+        Self { inner }
+    }
+}
+
+impl TextSplitter for TokenTextSplitter {
+    fn split(&self, text: &str) -> Vec<String> {
+        self.inner.split(text)
+    }
+}
+
+/// Markdown-aware splitter.
+///
+/// Cuts the text at headings (ignoring `#` lines inside code fences), splits
+/// oversized sections recursively and records the heading path of each chunk
+/// under the `headings` metadata field.
+#[derive(Debug, Clone)]
+pub struct MarkdownSplitter {
+    inner: RecursiveCharacterSplitter,
+}
+
+impl MarkdownSplitter {
+    /// Create a splitter producing chunks of up to `chunk_size` characters
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Self {
+        Self::from_splitter(RecursiveCharacterSplitter::new(chunk_size, chunk_overlap))
+    }
+
+    /// Create a splitter producing chunks of up to `chunk_tokens` tokens
+    pub fn with_tokens(
+        counter: Arc<dyn TokenCounter>,
+        chunk_tokens: usize,
+        overlap_tokens: usize,
+    ) -> Self {
+        Self::from_splitter(TokenTextSplitter::new(counter, chunk_tokens, overlap_tokens).inner)
+    }
+
+    fn from_splitter(splitter: RecursiveCharacterSplitter) -> Self {
+        Self {
+            inner: splitter.with_separators(MARKDOWN_SEPARATORS.iter().copied()),
+        }
+    }
+
+    /// Split into `(heading path, section text)` pairs
+    fn sections(text: &str) -> Vec<(Vec<String>, String)> {
+        let mut sections = Vec::new();
+        let mut headings: Vec<(usize, String)> = Vec::new();
+        let mut current = String::new();
+        let mut in_fence = false;
+
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_fence = !in_fence;
+            }
+
+            let heading = (!in_fence).then(|| heading_level(trimmed)).flatten();
+            if let Some(level) = heading {
+                if !current.trim().is_empty() {
+                    let path = headings.iter().map(|(_, h)| h.clone()).collect();
+                    sections.push((path, std::mem::take(&mut current)));
+                }
+                current.clear();
+                headings.retain(|(l, _)| *l < level);
+                headings.push((level, trimmed[level..].trim().to_string()));
+            }
+            current.push_str(line);
+        }
+
+        if !current.trim().is_empty() {
+            let path = headings.iter().map(|(_, h)| h.clone()).collect();
+            sections.push((path, current));
+        }
+        sections
+    }
+}
+
+/// ATX heading level of a line (`## Title` is 2)
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.starts_with(' ') || rest.trim().is_empty())).then_some(level)
+}
+
+impl TextSplitter for MarkdownSplitter {
+    fn split(&self, text: &str) -> Vec<String> {
+        Self::sections(text)
+            .into_iter()
+            .flat_map(|(_, section)| self.inner.split(&section))
+            .collect()
+    }
+
+    fn split_document(&self, document: &Document) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        for (headings, section) in Self::sections(&document.text) {
+            for text in self.inner.split(&section) {
+                let mut chunk = Chunk::new(document, chunks.len(), text);
+                if !headings.is_empty() {
+                    chunk
+                        .metadata
+                        .insert("headings".to_string(), serde_json::json!(headings));
+                }
+                chunks.push(chunk);
+            }
+        }
+        chunks
+    }
+}
+
+/// Split `text` at `separator`, keeping the separator at the start of the
+/// following piece so that joining the pieces restores the text
+fn split_keeping_separator<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    if separator.is_empty() {
+        return text
+            .char_indices()
+            .map(|(i, c)| &text[i..i + c.len_utf8()])
+            .collect();
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, _) in text.match_indices(separator) {
+        if i > start {
+            pieces.push(&text[start..i]);
+            start = i;
+        }
+    }
+    pieces.push(&text[start..]);
+    pieces
+}
+
+fn push_trimmed(out: &mut Vec<String>, text: &str) {
+    let text = text.trim();
+    if !text.is_empty() {
+        out.push(text.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::HeuristicTokenCounter;
+
+    #[test]
+    fn test_recursive_split_prefers_coarse_separators() {
+        let text = "First paragraph here.\n\nSecond paragraph is a bit longer than the first.";
+        let chunks = RecursiveCharacterSplitter::new(30, 0).split(text);
+        assert_eq!(chunks[0], "First paragraph here.");
+        assert!(chunks.iter().all(|c| c.chars().count() <= 30));
+        assert_eq!(chunks.join(" ").split_whitespace().count(), 12);
+
+        let overlapping = RecursiveCharacterSplitter::new(12, 6).split("one two three four five");
+        assert!(overlapping.windows(2).any(|w| {
+            let last = w[0].split(' ').next_back().unwrap();
+            w[1].starts_with(last)
+        }));
+
+        let tokens = TokenTextSplitter::new(Arc::new(HeuristicTokenCounter), 4, 0)
+            .split("alpha beta gamma delta epsilon");
+        assert!(tokens.len() > 1);
+    }
+
+    #[test]
+    fn test_markdown_sections_carry_headings() {
+        let text = "# Guide\nIntro.\n\n## Install\n```sh\n# not a heading\ncargo add aidale\n```\n\n## Usage\nCall it.\n";
+        let document = Document::new("guide.md", text);
+        let chunks = MarkdownSplitter::new(200, 0).split_document(&document);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks[1].metadata["headings"],
+            serde_json::json!(["Guide", "Install"])
+        );
+        assert!(chunks[1].text.contains("# not a heading"));
+        assert_eq!(chunks[2].id, "guide.md#2");
+    }
+}
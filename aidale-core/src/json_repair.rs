@@ -0,0 +1,196 @@
+//! Recovering JSON from model output.
+//!
+//! Models asked for JSON often wrap it in markdown fences or prose, use
+//! single quotes, leave trailing commas, or stop mid-object when they run
+//! out of tokens. [`parse`] tries strict parsing first and falls back to
+//! these repairs, so well-formed output is never altered.
+//!
+//! ```
+//! use aidale_core::json_repair;
+//!
+//! let value = json_repair::parse("```json\n{'name': 'Ada', 'tags': ['math',],\n```").unwrap();
+//! assert_eq!(value["tags"][0], "math");
+//! ```
+
+/// Parse JSON, repairing it if strict parsing fails
+///
+/// On failure the error of the strict attempt is returned.
+pub fn parse(text: &str) -> Result<serde_json::Value, serde_json::Error> {
+    let text = text.trim();
+    serde_json::from_str(text).or_else(|err| {
+        let candidate = extract(strip_fences(text));
+        serde_json::from_str(candidate)
+            .or_else(|_| serde_json::from_str(&repair(candidate)))
+            .map_err(|_| err)
+    })
+}
+
+/// Content of the first markdown code block, or the trimmed text if there is
+/// none
+///
+/// An unterminated block (truncated output) runs to the end of the text.
+pub fn strip_fences(text: &str) -> &str {
+    let Some(start) = text.find("```") else {
+        return text.trim();
+    };
+    // Skip the language tag
+    let body = text[start + 3..].trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+    let end = body.find("```").unwrap_or(body.len());
+    body[..end].trim()
+}
+
+/// The first JSON object or array in the text, dropping surrounding prose
+///
+/// Brackets inside (single- or double-quoted) strings are ignored. Without
+/// its closer (truncated output) the span runs to the end of the text;
+/// without an opener the text is returned unchanged.
+pub fn extract(text: &str) -> &str {
+    let Some(start) = text.find(['{', '[']) else {
+        return text;
+    };
+
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (offset, c) in text[start..].char_indices() {
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == open {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return &text[start..=start + offset];
+                }
+            }
+            _ => {}
+        }
+    }
+    &text[start..]
+}
+
+/// Rewrite almost-JSON into JSON
+///
+/// Converts single-quoted strings to double-quoted ones, escapes raw
+/// newlines in strings, removes trailing commas and closes strings, arrays
+/// and objects left open by truncation. The result is not guaranteed to
+/// parse.
+pub fn repair(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut closers = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+                if c == '\'' {
+                    // `\'` is not a JSON escape
+                    out.pop();
+                }
+                out.push(c);
+                continue;
+            }
+            match c {
+                '\\' => {
+                    escaped = true;
+                    out.push(c);
+                }
+                c if c == open => {
+                    quote = None;
+                    out.push('"');
+                }
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                _ => out.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => {
+                quote = Some(c);
+                out.push('"');
+            }
+            '{' => {
+                closers.push('}');
+                out.push(c);
+            }
+            '[' => {
+                closers.push(']');
+                out.push(c);
+            }
+            '}' | ']' => {
+                trim_trailing_comma(&mut out);
+                if closers.last() == Some(&c) {
+                    closers.pop();
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+
+    // Close whatever truncation left open
+    if quote.is_some() {
+        if escaped {
+            out.pop();
+        }
+        out.push('"');
+    }
+    for closer in closers.into_iter().rev() {
+        trim_trailing_comma(&mut out);
+        if out.ends_with(':') {
+            out.push_str("null");
+        }
+        out.push(closer);
+    }
+    out
+}
+
+fn trim_trailing_comma(out: &mut String) {
+    out.truncate(out.trim_end().len());
+    if out.ends_with(',') {
+        out.pop();
+        out.truncate(out.trim_end().len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repairs() {
+        assert_eq!(parse(r#"{"a": [1, 2]}"#).unwrap(), json!({"a": [1, 2]}));
+        assert_eq!(
+            parse("Sure! Here it is:\n```json\n{\"a\": 1}\n```\nAnything else?").unwrap(),
+            json!({"a": 1})
+        );
+        assert_eq!(
+            parse("{'name': 'O\\'Brien', 'quote': 'say \"hi\"',}").unwrap(),
+            json!({"name": "O'Brien", "quote": "say \"hi\""})
+        );
+        assert_eq!(
+            parse(r#"{"items": [{"id": 1}, {"id": 2, "note": "trunc"#).unwrap(),
+            json!({"items": [{"id": 1}, {"id": 2, "note": "trunc"}]})
+        );
+        assert_eq!(
+            parse(r#"{"a": 1, "b":"#).unwrap(),
+            json!({"a": 1, "b": null})
+        );
+        assert_eq!(parse("[1, 2,").unwrap(), json!([1, 2]));
+        assert!(parse("no json here").is_err());
+    }
+}
@@ -3,8 +3,9 @@
 //! Inspired by OpenDAL's architecture, layers provide a composable way to wrap
 //! providers with cross-cutting concerns like logging, retry, caching, etc.
 
+use crate::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
 use crate::error::AiError;
-use crate::provider::Provider;
+use crate::provider::{ApiRequest, Provider};
 use crate::types::*;
 use async_trait::async_trait;
 use std::sync::Arc;
@@ -22,6 +23,77 @@ pub trait Layer<P: Provider> {
     fn layer(&self, inner: P) -> Self::LayeredProvider;
 }
 
+/// Type-erased provider wrapping function
+type WrapFn = dyn Fn(Arc<dyn Provider>) -> Arc<dyn Provider> + Send + Sync;
+
+/// Type-erased layer.
+///
+/// Every static `Layer<P>` changes the provider type, so a stack of them
+/// cannot be stored in a `Vec` or assembled conditionally. A `BoxedLayer`
+/// wraps type-erased providers (`Arc<dyn Provider>`) instead, which makes
+/// heterogeneous stacks possible at the cost of one dynamic dispatch per
+/// layer and call. Apply it with
+/// [`RuntimeExecutorBuilder::boxed_layer`](crate::runtime::executor::RuntimeExecutorBuilder::boxed_layer).
+///
+/// ```ignore
+/// let mut layers = vec![BoxedLayer::new(LoggingLayer::new())];
+/// if config.retries {
+///     layers.push(BoxedLayer::new(RetryLayer::new()));
+/// }
+/// let executor = RuntimeExecutor::builder(provider)
+///     .boxed_layers(layers)
+///     .finish();
+/// ```
+#[derive(Clone)]
+pub struct BoxedLayer {
+    wrap: Arc<WrapFn>,
+}
+
+impl BoxedLayer {
+    /// Erase a layer
+    pub fn new<L>(layer: L) -> Self
+    where
+        L: Layer<Arc<dyn Provider>> + Send + Sync + 'static,
+    {
+        Self::from_fn(move |inner| Arc::new(layer.layer(inner)))
+    }
+
+    /// Create a layer from a wrapping function
+    pub fn from_fn(
+        wrap: impl Fn(Arc<dyn Provider>) -> Arc<dyn Provider> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            wrap: Arc::new(wrap),
+        }
+    }
+}
+
+impl std::fmt::Debug for BoxedLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxedLayer").finish_non_exhaustive()
+    }
+}
+
+impl Layer<Arc<dyn Provider>> for BoxedLayer {
+    type LayeredProvider = Arc<dyn Provider>;
+
+    fn layer(&self, inner: Arc<dyn Provider>) -> Self::LayeredProvider {
+        (self.wrap)(inner)
+    }
+}
+
+/// Erase a provider's type, without double-wrapping erased providers
+pub(crate) fn erase_provider<P: Provider>(provider: P) -> Arc<dyn Provider> {
+    let provider: Box<dyn std::any::Any> = Box::new(provider);
+    match provider.downcast::<Arc<dyn Provider>>() {
+        Ok(erased) => *erased,
+        Err(provider) => match provider.downcast::<P>() {
+            Ok(provider) => Arc::new(*provider),
+            Err(_) => unreachable!("provider has type P"),
+        },
+    }
+}
+
 /// Helper trait for layered providers.
 ///
 /// This trait provides default forwarding implementations for provider methods,
@@ -55,6 +127,42 @@ pub trait LayeredProvider: Sized + Provider {
     ) -> Result<Box<crate::provider::ChatCompletionStream>, AiError> {
         self.inner().stream_chat_completion(req).await
     }
+
+    /// Default implementation for chat_completion_raw - forwards to inner
+    async fn layered_chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        self.inner().chat_completion_raw(body).await
+    }
+
+    /// Default implementation for api_request - forwards to inner
+    async fn layered_api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        self.inner().api_request(req).await
+    }
+
+    /// Default implementation for speech - forwards to inner
+    async fn layered_speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        self.inner().speech(req).await
+    }
+
+    /// Default implementation for transcription - forwards to inner
+    async fn layered_transcription(
+        &self,
+        req: TranscriptionRequest,
+    ) -> Result<Transcription, AiError> {
+        self.inner().transcription(req).await
+    }
+
+    /// Default implementation for list_models - forwards to inner
+    async fn layered_list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        self.inner().list_models().await
+    }
+
+    /// Default implementation for health_check - forwards to inner
+    async fn layered_health_check(&self) -> Result<(), AiError> {
+        self.inner().health_check().await
+    }
 }
 
 /// Macro to implement Provider trait by forwarding to LayeredProvider methods.
@@ -82,6 +190,44 @@ macro_rules! impl_layered_provider {
             ) -> Result<Box<$crate::provider::ChatCompletionStream>, $crate::error::AiError> {
                 $crate::layer::LayeredProvider::layered_stream_chat_completion(self, req).await
             }
+
+            async fn chat_completion_raw(
+                &self,
+                body: serde_json::Value,
+            ) -> Result<serde_json::Value, $crate::error::AiError> {
+                $crate::layer::LayeredProvider::layered_chat_completion_raw(self, body).await
+            }
+
+            async fn api_request(
+                &self,
+                req: $crate::provider::ApiRequest,
+            ) -> Result<serde_json::Value, $crate::error::AiError> {
+                $crate::layer::LayeredProvider::layered_api_request(self, req).await
+            }
+
+            async fn speech(
+                &self,
+                req: $crate::audio::SpeechRequest,
+            ) -> Result<$crate::audio::Speech, $crate::error::AiError> {
+                $crate::layer::LayeredProvider::layered_speech(self, req).await
+            }
+
+            async fn transcription(
+                &self,
+                req: $crate::audio::TranscriptionRequest,
+            ) -> Result<$crate::audio::Transcription, $crate::error::AiError> {
+                $crate::layer::LayeredProvider::layered_transcription(self, req).await
+            }
+
+            async fn list_models(
+                &self,
+            ) -> Result<Vec<$crate::types::ModelInfo>, $crate::error::AiError> {
+                $crate::layer::LayeredProvider::layered_list_models(self).await
+            }
+
+            async fn health_check(&self) -> Result<(), $crate::error::AiError> {
+                $crate::layer::LayeredProvider::layered_health_check(self).await
+            }
         }
     };
 }
@@ -6,22 +6,69 @@
 //! AI applications with multiple provider support, middleware composition,
 //! and plugin extensibility.
 
+pub mod audio;
+pub mod budget;
+pub mod capabilities;
+pub mod embedding;
 pub mod error;
+pub mod events;
+pub mod extensions;
+pub mod few_shot;
+pub mod http;
+pub mod image;
+pub mod ingestion;
+pub mod json_repair;
 pub mod layer;
+pub mod message;
+pub mod moderation;
+pub mod normalize;
 pub mod plugin;
 pub mod provider;
+pub mod realtime;
+pub mod rerank;
+pub mod rt;
 pub mod runtime;
 pub mod strategy;
+pub mod tokenizer;
 pub mod types;
+pub mod vector_store;
+pub mod wire;
 
 // Re-exports
-pub use error::AiError;
-pub use layer::{Layer, LayeredProvider};
+pub use audio::{
+    AudioFileFormat, Speech, SpeechRequest, Transcription, TranscriptionRequest,
+    TranscriptionSegment,
+};
+pub use budget::{Deadline, TokenBudget};
+pub use capabilities::{model_capabilities, ModelCapabilities};
+pub use embedding::{Embedder, Embedding};
+pub use error::{AiError, ApiErrorDetails};
+pub use events::{CacheEvent, EventBus, LayerEvent, RetryEvent, RouteEvent};
+pub use extensions::Extensions;
+pub use few_shot::{Example, ExampleStyle};
+pub use http::{HttpClient, HttpMethod, HttpRequest, HttpResponse};
+pub use ingestion::{Chunk, Document, Ingestor, TextSplitter};
+pub use layer::{BoxedLayer, Layer, LayeredProvider};
+pub use message::MessageBuilder;
+pub use moderation::{ModerationResult, Moderator};
+pub use normalize::MessageNormalizer;
 pub use plugin::{Plugin, PluginEngine, PluginPhase};
-pub use provider::Provider;
-pub use runtime::RuntimeExecutor;
+pub use provider::{ApiRequest, Provider};
+pub use realtime::{RealtimeConfig, RealtimeEvent, RealtimeProvider, RealtimeSession};
+pub use rerank::{RerankRequest, RerankResult, Reranker};
+pub use runtime::{
+    BatchItem, BatchOutput, BatchRequest, BatchResult, Classification, ClassifyOptions,
+    ConflictPolicy, ContentFilterPolicy, ExtractConflict, ExtractOptions, Extraction,
+    ParamDefaults, RevisedText, RevisionPolicy, RevisionStep, RevisionStepKind, RuntimeExecutor,
+    StreamedText, SummarizeOptions, SummarizeProgress, Summary,
+};
 pub use strategy::{JsonModeStrategy, JsonOutputStrategy, JsonSchemaStrategy};
+pub use tokenizer::{HeuristicTokenCounter, TokenCounter};
 pub use types::*;
+pub use vector_store::{
+    HnswVectorStore, InMemoryVectorStore, ScoredRecord, VectorQuery, VectorRecord, VectorStore,
+};
+pub use wire::{WireBody, WireDirection, WireLog, WireObserver};
 
 /// Result type alias for AI operations
 pub type Result<T> = std::result::Result<T, AiError>;
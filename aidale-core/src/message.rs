@@ -0,0 +1,248 @@
+//! Message construction helpers.
+//!
+//! Provides [`MessageBuilder`] for multi-part messages and the
+//! [`messages!`](crate::messages) macro for concise conversations.
+
+use crate::error::AiError;
+use crate::image::{load_image, ImageLimits};
+use crate::types::{CacheControl, ContentPart, Message, Role};
+use std::path::Path;
+
+/// Builder for messages with multiple content parts.
+///
+/// # Example
+///
+/// ```
+/// use aidale_core::types::{Message, Role};
+///
+/// let msg = Message::builder(Role::User)
+///     .text("What is in this image?")
+///     .image("https://example.com/cat.png")
+///     .name("alice")
+///     .build();
+/// assert_eq!(msg.content.len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    role: Role,
+    content: Vec<ContentPart>,
+    name: Option<String>,
+    cache_control: Option<CacheControl>,
+}
+
+impl MessageBuilder {
+    /// Create a builder for a message with the given role
+    pub fn new(role: Role) -> Self {
+        Self {
+            role,
+            content: Vec::new(),
+            name: None,
+            cache_control: None,
+        }
+    }
+
+    /// Append a text part
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.content.push(ContentPart::Text { text: text.into() });
+        self
+    }
+
+    /// Append the contents of a UTF-8 text file as a text part
+    pub fn text_file(self, path: impl AsRef<Path>) -> Result<Self, AiError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            AiError::invalid_request(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        Ok(self.text(text))
+    }
+
+    /// Append an image part by URL
+    pub fn image(mut self, url: impl Into<String>) -> Self {
+        self.content.push(ContentPart::Image { url: url.into() });
+        self
+    }
+
+    /// Append an inline image part from raw image bytes
+    pub fn image_data(mut self, mime_type: impl Into<String>, bytes: &[u8]) -> Self {
+        self.content.push(ContentPart::image_data(mime_type, bytes));
+        self
+    }
+
+    /// Append an image file as an inline image part, downscaled to the
+    /// default [`ImageLimits`]
+    pub fn image_file(self, path: impl AsRef<Path>) -> Result<Self, AiError> {
+        self.image_file_with_limits(path, &ImageLimits::default())
+    }
+
+    /// Append an image file as an inline image part that fits `limits`
+    pub fn image_file_with_limits(
+        mut self,
+        path: impl AsRef<Path>,
+        limits: &ImageLimits,
+    ) -> Result<Self, AiError> {
+        self.content.push(load_image(path, limits)?);
+        Ok(self)
+    }
+
+    /// Append a document attachment part from raw file bytes
+    pub fn file(mut self, name: impl Into<String>, mime: impl Into<String>, bytes: &[u8]) -> Self {
+        self.content.push(ContentPart::file(name, mime, bytes));
+        self
+    }
+
+    /// Append a local document, such as a PDF, as an attachment part
+    pub fn file_from_path(self, path: impl AsRef<Path>) -> Result<Self, AiError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| {
+            AiError::invalid_request(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mime = document_mime(path, &bytes);
+        Ok(self.file(name, mime, &bytes))
+    }
+
+    /// Append a tool call part
+    pub fn tool_call(
+        mut self,
+        id: impl Into<String>,
+        name: impl Into<String>,
+        arguments: serde_json::Value,
+    ) -> Self {
+        self.content.push(ContentPart::ToolCall {
+            id: id.into(),
+            name: name.into(),
+            arguments,
+        });
+        self
+    }
+
+    /// Append a tool result part
+    pub fn tool_result(mut self, id: impl Into<String>, result: serde_json::Value) -> Self {
+        self.content.push(ContentPart::ToolResult {
+            id: id.into(),
+            result,
+        });
+        self
+    }
+
+    /// Append an arbitrary content part
+    pub fn part(mut self, part: ContentPart) -> Self {
+        self.content.push(part);
+        self
+    }
+
+    /// Set the participant name
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Mark the message as a prompt caching breakpoint
+    pub fn cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Build the message
+    pub fn build(self) -> Message {
+        Message {
+            role: self.role,
+            content: self.content,
+            name: self.name,
+            cache_control: self.cache_control,
+        }
+    }
+}
+
+impl From<MessageBuilder> for Message {
+    fn from(builder: MessageBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// MIME type of a document from its signature or file extension
+fn document_mime(path: &Path, bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(b"%PDF-") {
+        return "application/pdf";
+    }
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Build a `Vec<Message>` from `role: content` pairs.
+///
+/// Roles are `system`, `user`, `assistant`, and `tool`. For `system`, `user`
+/// and `assistant` the content is text; for `tool` it is an `(id, result)`
+/// tuple. A `msg: expr` entry inserts anything convertible into a `Message`,
+/// such as a [`MessageBuilder`].
+///
+/// # Example
+///
+/// ```
+/// use aidale_core::messages;
+///
+/// let messages = messages![
+///     system: "You are a helpful assistant.",
+///     user: "Hi!",
+///     assistant: "Hello! How can I help?",
+///     user: "Tell me a joke.",
+/// ];
+/// assert_eq!(messages.len(), 4);
+/// ```
+#[macro_export]
+macro_rules! messages {
+    ($($role:ident : $content:expr),* $(,)?) => {
+        vec![$($crate::messages!(@message $role $content)),*]
+    };
+    (@message msg $content:expr) => {
+        $crate::types::Message::from($content)
+    };
+    (@message tool $content:expr) => {{
+        let (id, result) = $content;
+        $crate::types::Message::tool_result(id, result)
+    }};
+    (@message $role:ident $content:expr) => {
+        $crate::types::Message::$role($content)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_macro() {
+        let messages = crate::messages![
+            system: "Be brief.",
+            user: "Call the tool.",
+            msg: MessageBuilder::new(Role::Assistant)
+                .tool_call("call_1", "lookup", serde_json::json!({"q": "rust"})),
+            tool: ("call_1", serde_json::json!({"answer": 42})),
+        ];
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(messages[2].role, Role::Assistant);
+        assert_eq!(messages[3].role, Role::Tool);
+        assert!(matches!(
+            &messages[3].content[0],
+            ContentPart::ToolResult { id, .. } if id == "call_1"
+        ));
+    }
+}
@@ -0,0 +1,26 @@
+//! Content moderation abstractions.
+
+use crate::error::AiError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// Result of moderating a piece of content
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModerationResult {
+    /// Whether the content violates any policy
+    pub flagged: bool,
+    /// Names of the violated categories
+    pub categories: Vec<String>,
+    /// Per-category scores, when the moderator provides them
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub scores: HashMap<String, f32>,
+}
+
+/// Trait for content moderation services.
+#[async_trait]
+pub trait Moderator: Send + Sync + Debug + 'static {
+    /// Moderate a piece of text
+    async fn moderate(&self, text: &str) -> Result<ModerationResult, AiError>;
+}
@@ -0,0 +1,240 @@
+//! Message normalization.
+//!
+//! Providers differ in which conversations they accept: Anthropic and
+//! Gemini reject consecutive messages of the same role and conversations
+//! that start with an assistant turn, and every provider rejects tool
+//! results that do not answer a preceding tool call. A [`MessageNormalizer`]
+//! rewrites conversations to fit these constraints before they are sent,
+//! and reports what cannot be fixed as an `InvalidRequest` error instead of
+//! an opaque provider 400.
+//!
+//! The executor picks a normalizer from the provider ID (see
+//! [`MessageNormalizer::for_provider`]); set one explicitly with
+//! `RuntimeExecutorBuilder::message_normalizer`.
+
+use crate::error::AiError;
+use crate::types::{ContentPart, Message, Role};
+use std::collections::HashSet;
+
+/// Text of the user message inserted before a leading assistant message
+const LEADING_USER_TEXT: &str = "(continued)";
+
+/// Rewrites conversations to fit a provider's constraints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageNormalizer {
+    hoist_system: bool,
+    merge_consecutive: bool,
+    leading_user: bool,
+    validate_tool_results: bool,
+}
+
+impl MessageNormalizer {
+    /// Create a normalizer that leaves messages unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Normalizer for OpenAI-compatible chat APIs: tool results are
+    /// validated, everything else is accepted as-is
+    pub fn openai() -> Self {
+        Self::new().with_validate_tool_results(true)
+    }
+
+    /// Normalizer for Anthropic-style APIs (also Gemini): a single leading
+    /// system message, alternating roles starting with the user, and
+    /// validated tool results
+    pub fn anthropic() -> Self {
+        Self {
+            hoist_system: true,
+            merge_consecutive: true,
+            leading_user: true,
+            validate_tool_results: true,
+        }
+    }
+
+    /// Pick a normalizer by provider ID
+    ///
+    /// Unknown providers get [`Self::new`], which changes nothing.
+    pub fn for_provider(provider_id: &str) -> Self {
+        match provider_id {
+            "anthropic" | "bedrock" | "vertex" | "gemini" => Self::anthropic(),
+            "openai" | "azure" | "deepseek" | "xai" | "groq" | "fireworks" | "together"
+            | "openrouter" => Self::openai(),
+            _ => Self::new(),
+        }
+    }
+
+    /// Move all system messages into one message at the start, for APIs with
+    /// a single system field
+    pub fn with_hoist_system(mut self, enabled: bool) -> Self {
+        self.hoist_system = enabled;
+        self
+    }
+
+    /// Merge consecutive messages of the same role and name
+    pub fn with_merge_consecutive(mut self, enabled: bool) -> Self {
+        self.merge_consecutive = enabled;
+        self
+    }
+
+    /// Insert a user message when the first non-system message is not one
+    pub fn with_leading_user(mut self, enabled: bool) -> Self {
+        self.leading_user = enabled;
+        self
+    }
+
+    /// Check that every tool result answers a tool call of the directly
+    /// preceding assistant message, and that every tool call is answered
+    pub fn with_validate_tool_results(mut self, enabled: bool) -> Self {
+        self.validate_tool_results = enabled;
+        self
+    }
+
+    /// Normalize a conversation
+    pub fn normalize(&self, messages: Vec<Message>) -> Result<Vec<Message>, AiError> {
+        let mut messages = if self.hoist_system {
+            hoist_system(messages)
+        } else {
+            messages
+        };
+        if self.leading_user {
+            let first = messages.iter().position(|msg| msg.role != Role::System);
+            if let Some(index) = first.filter(|&index| messages[index].role != Role::User) {
+                messages.insert(index, Message::user(LEADING_USER_TEXT));
+            }
+        }
+        if self.merge_consecutive {
+            messages = merge_consecutive(messages);
+        }
+        if self.validate_tool_results {
+            validate_tool_results(&messages)?;
+        }
+        Ok(messages)
+    }
+}
+
+fn hoist_system(messages: Vec<Message>) -> Vec<Message> {
+    let (system, rest): (Vec<_>, Vec<_>) = messages
+        .into_iter()
+        .partition(|msg| msg.role == Role::System);
+    let system = system.into_iter().reduce(|mut merged, msg| {
+        merged.content.extend(msg.content);
+        merged.cache_control = msg.cache_control.or(merged.cache_control);
+        merged
+    });
+    system.into_iter().chain(rest).collect()
+}
+
+fn merge_consecutive(messages: Vec<Message>) -> Vec<Message> {
+    let mut merged: Vec<Message> = Vec::with_capacity(messages.len());
+    for msg in messages {
+        match merged.last_mut() {
+            Some(last) if last.role == msg.role && last.name == msg.name => {
+                last.content.extend(msg.content);
+                // A breakpoint on the later message covers the merged one
+                last.cache_control = msg.cache_control.or(last.cache_control.take());
+            }
+            _ => merged.push(msg),
+        }
+    }
+    merged
+}
+
+fn validate_tool_results(messages: &[Message]) -> Result<(), AiError> {
+    // Tool calls of the last assistant message that are still unanswered
+    let mut pending: HashSet<&str> = HashSet::new();
+    for msg in messages {
+        let results: Vec<&str> = msg
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::ToolResult { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        for id in &results {
+            if !pending.remove(id) {
+                return Err(AiError::invalid_request(format!(
+                    "Tool result '{}' does not answer a tool call of the preceding assistant message",
+                    id
+                )));
+            }
+        }
+        if !results.is_empty() {
+            continue;
+        }
+
+        unanswered(&pending)?;
+        if msg.role == Role::Assistant {
+            pending.extend(msg.content.iter().filter_map(|part| match part {
+                ContentPart::ToolCall { id, .. } => Some(id.as_str()),
+                _ => None,
+            }));
+        }
+    }
+    unanswered(&pending)
+}
+
+fn unanswered(pending: &HashSet<&str>) -> Result<(), AiError> {
+    match pending.iter().next() {
+        Some(id) => Err(AiError::invalid_request(format!(
+            "Tool call '{}' has no tool result",
+            id
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool_call(id: &str) -> Message {
+        Message::builder(Role::Assistant)
+            .tool_call(id, "search", json!({}))
+            .build()
+    }
+
+    fn tool_result(id: &str) -> Message {
+        Message::tool_result(id, json!("ok"))
+    }
+
+    #[test]
+    fn test_anthropic_normalization() {
+        let messages = vec![
+            Message::assistant("Hello!"),
+            Message::system("Be brief."),
+            Message::user("Hi"),
+            Message::user("Search for cats"),
+            tool_call("1"),
+            tool_result("1"),
+            Message::system("Answer in English."),
+        ];
+        let messages = MessageNormalizer::anthropic().normalize(messages).unwrap();
+        let roles: Vec<_> = messages.iter().map(|msg| msg.role.clone()).collect();
+        assert_eq!(
+            roles,
+            [
+                Role::System,
+                Role::User,
+                Role::Assistant,
+                Role::User,
+                Role::Assistant,
+                Role::Tool
+            ]
+        );
+        assert_eq!(messages[0].content.len(), 2);
+        assert_eq!(messages[3].content.len(), 2);
+
+        let normalizer = MessageNormalizer::openai();
+        assert!(normalizer.normalize(vec![tool_result("1")]).is_err());
+        assert!(normalizer
+            .normalize(vec![tool_call("1"), Message::user("Hi")])
+            .is_err());
+        assert!(normalizer
+            .normalize(vec![tool_call("1"), tool_result("2")])
+            .is_err());
+        assert!(normalizer.normalize(vec![tool_call("1")]).is_err());
+    }
+}
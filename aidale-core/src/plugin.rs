@@ -1,6 +1,7 @@
 //! Plugin system for runtime-level extensibility.
 
 use crate::error::AiError;
+use crate::events::{CacheEvent, LayerEvent, RetryEvent, RouteEvent};
 use crate::provider::TextStream;
 use crate::types::*;
 use async_trait::async_trait;
@@ -85,6 +86,28 @@ pub trait Plugin: Send + Sync + Debug + 'static {
         Ok(result)
     }
 
+    /// Transform object generation parameters
+    ///
+    /// The `generate_object` counterpart of [`Plugin::transform_params`].
+    async fn transform_object_params(
+        &self,
+        params: ObjectParams,
+        _ctx: &RequestContext,
+    ) -> Result<ObjectParams, AiError> {
+        Ok(params)
+    }
+
+    /// Transform object generation result
+    ///
+    /// The `generate_object` counterpart of [`Plugin::transform_result`].
+    async fn transform_object_result(
+        &self,
+        result: ObjectResult,
+        _ctx: &RequestContext,
+    ) -> Result<ObjectResult, AiError> {
+        Ok(result)
+    }
+
     // ==================== Parallel Hooks ====================
     // These hooks execute concurrently and are used for side effects.
 
@@ -102,11 +125,55 @@ pub trait Plugin: Send + Sync + Debug + 'static {
         Ok(())
     }
 
+    /// Hook called when an object request ends successfully
+    async fn on_object_end(
+        &self,
+        _ctx: &RequestContext,
+        _result: &ObjectResult,
+    ) -> Result<(), AiError> {
+        Ok(())
+    }
+
     /// Hook called when an error occurs
     async fn on_error(&self, _error: &AiError, _ctx: &RequestContext) -> Result<(), AiError> {
         Ok(())
     }
 
+    /// Hook called before a streaming request is sent to the provider
+    async fn on_stream_start(&self, _ctx: &RequestContext) -> Result<(), AiError> {
+        Ok(())
+    }
+
+    /// Hook called for every chunk of a streaming response
+    async fn on_chunk(&self, _chunk: &TextChunk, _ctx: &RequestContext) -> Result<(), AiError> {
+        Ok(())
+    }
+
+    /// Hook called when a stream completes, with the aggregated result
+    async fn on_stream_end(
+        &self,
+        _ctx: &RequestContext,
+        _result: &TextResult,
+    ) -> Result<(), AiError> {
+        Ok(())
+    }
+
+    /// Hook called for every retry a layer made during the provider call
+    async fn on_retry(&self, _event: &RetryEvent, _ctx: &RequestContext) -> Result<(), AiError> {
+        Ok(())
+    }
+
+    /// Hook called for every cache lookup a layer made during the provider
+    /// call
+    async fn on_cache(&self, _event: &CacheEvent, _ctx: &RequestContext) -> Result<(), AiError> {
+        Ok(())
+    }
+
+    /// Hook called when a routing layer sent the request to another model
+    async fn on_route(&self, _event: &RouteEvent, _ctx: &RequestContext) -> Result<(), AiError> {
+        Ok(())
+    }
+
     // ==================== Stream Hooks ====================
     // These hooks transform streaming responses.
 
@@ -200,6 +267,30 @@ impl PluginEngine {
         Ok(result)
     }
 
+    /// Run sequential transform_object_params hooks
+    pub async fn transform_object_params(
+        &self,
+        mut params: ObjectParams,
+        ctx: &RequestContext,
+    ) -> Result<ObjectParams, AiError> {
+        for plugin in &self.plugins {
+            params = plugin.transform_object_params(params, ctx).await?;
+        }
+        Ok(params)
+    }
+
+    /// Run sequential transform_object_result hooks
+    pub async fn transform_object_result(
+        &self,
+        mut result: ObjectResult,
+        ctx: &RequestContext,
+    ) -> Result<ObjectResult, AiError> {
+        for plugin in &self.plugins {
+            result = plugin.transform_object_result(result, ctx).await?;
+        }
+        Ok(result)
+    }
+
     // ==================== Parallel Hook Execution ====================
 
     /// Run parallel on_request_start hooks
@@ -234,6 +325,24 @@ impl PluginEngine {
         Ok(())
     }
 
+    /// Run parallel on_object_end hooks
+    pub async fn on_object_end(
+        &self,
+        ctx: &RequestContext,
+        result: &ObjectResult,
+    ) -> Result<(), AiError> {
+        use futures::future::try_join_all;
+
+        let futures = self
+            .plugins
+            .iter()
+            .map(|p| p.on_object_end(ctx, result))
+            .collect::<Vec<_>>();
+
+        try_join_all(futures).await?;
+        Ok(())
+    }
+
     /// Run parallel on_error hooks
     pub async fn on_error(&self, error: &AiError, ctx: &RequestContext) -> Result<(), AiError> {
         use futures::future::try_join_all;
@@ -248,6 +357,78 @@ impl PluginEngine {
         Ok(())
     }
 
+    /// Run parallel on_stream_start hooks
+    pub async fn on_stream_start(&self, ctx: &RequestContext) -> Result<(), AiError> {
+        use futures::future::try_join_all;
+
+        let futures = self
+            .plugins
+            .iter()
+            .map(|p| p.on_stream_start(ctx))
+            .collect::<Vec<_>>();
+
+        try_join_all(futures).await?;
+        Ok(())
+    }
+
+    /// Run parallel on_chunk hooks
+    pub async fn on_chunk(&self, chunk: &TextChunk, ctx: &RequestContext) -> Result<(), AiError> {
+        use futures::future::try_join_all;
+
+        let futures = self
+            .plugins
+            .iter()
+            .map(|p| p.on_chunk(chunk, ctx))
+            .collect::<Vec<_>>();
+
+        try_join_all(futures).await?;
+        Ok(())
+    }
+
+    /// Run parallel on_stream_end hooks
+    pub async fn on_stream_end(
+        &self,
+        ctx: &RequestContext,
+        result: &TextResult,
+    ) -> Result<(), AiError> {
+        use futures::future::try_join_all;
+
+        let futures = self
+            .plugins
+            .iter()
+            .map(|p| p.on_stream_end(ctx, result))
+            .collect::<Vec<_>>();
+
+        try_join_all(futures).await?;
+        Ok(())
+    }
+
+    /// Deliver the layer events recorded on the context to parallel
+    /// on_retry / on_cache / on_route hooks, one event at a time in emission
+    /// order
+    pub async fn on_layer_events(&self, ctx: &RequestContext) -> Result<(), AiError> {
+        use futures::future::try_join_all;
+
+        for event in ctx.events().take() {
+            if let LayerEvent::Route(route) = &event {
+                // Kept for the result metadata
+                ctx.extensions().insert(route.clone());
+            }
+            let futures = self
+                .plugins
+                .iter()
+                .map(|p| match &event {
+                    LayerEvent::Retry(retry) => p.on_retry(retry, ctx),
+                    LayerEvent::Cache(cache) => p.on_cache(cache, ctx),
+                    LayerEvent::Route(route) => p.on_route(route, ctx),
+                })
+                .collect::<Vec<_>>();
+
+            try_join_all(futures).await?;
+        }
+        Ok(())
+    }
+
     // ==================== Stream Hook Execution ====================
 
     /// Apply stream transformations
@@ -1,9 +1,12 @@
 //! Provider trait and core abstractions.
 
+use crate::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
 use crate::error::AiError;
+use crate::http::HttpMethod;
 use crate::types::*;
 use async_trait::async_trait;
 use futures::Stream;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 
@@ -17,6 +20,57 @@ pub type TextStream = dyn Stream<Item = Result<TextChunk, AiError>> + Send + Unp
 /// Stream type alias for objects (legacy, kept for backward compatibility during transition)
 pub type ObjectStream = dyn Stream<Item = Result<serde_json::Value, AiError>> + Send + Unpin;
 
+/// Request to a provider endpoint outside the chat API, such as OpenAI's
+/// assistants and threads
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub method: HttpMethod,
+    /// Path below the API base URL, e.g. `/threads`
+    pub path: String,
+    pub body: Option<serde_json::Value>,
+    /// Extra headers sent with the request
+    pub headers: HashMap<String, String>,
+}
+
+impl ApiRequest {
+    /// Create a request without body or extra headers
+    pub fn new(method: HttpMethod, path: impl Into<String>) -> Self {
+        Self {
+            method,
+            path: path.into(),
+            body: None,
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Create a GET request
+    pub fn get(path: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Get, path)
+    }
+
+    /// Create a POST request with a JSON body
+    pub fn post(path: impl Into<String>, body: serde_json::Value) -> Self {
+        Self::new(HttpMethod::Post, path).with_body(body)
+    }
+
+    /// Create a DELETE request
+    pub fn delete(path: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Delete, path)
+    }
+
+    /// Set the JSON body
+    pub fn with_body(mut self, body: serde_json::Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Add a header
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+}
+
 /// Core provider trait for AI services.
 ///
 /// This trait defines the simplified interface that all AI providers must implement.
@@ -43,6 +97,123 @@ pub trait Provider: Send + Sync + Debug + 'static {
         &self,
         req: ChatCompletionRequest,
     ) -> Result<Box<ChatCompletionStream>, AiError>;
+
+    /// Chat completion with a request body in the provider's own format
+    ///
+    /// An escape hatch for provider features the typed request doesn't
+    /// model yet: the body is sent to the chat endpoint as is and the
+    /// response body returned unparsed. Providers without raw access return
+    /// `Unsupported`.
+    async fn chat_completion_raw(
+        &self,
+        _body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        Err(AiError::unsupported(format!(
+            "{} does not support raw requests",
+            self.info().name
+        )))
+    }
+
+    /// Call another endpoint of the provider's API
+    ///
+    /// Lets APIs this crate doesn't wrap, such as OpenAI's assistants and
+    /// threads, go through the same layers as chat requests. Authentication
+    /// is added by the provider; the JSON response body is returned.
+    /// Providers without such access return `Unsupported`.
+    async fn api_request(&self, _req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        Err(AiError::unsupported(format!(
+            "{} does not support API requests",
+            self.info().name
+        )))
+    }
+
+    /// Synthesize speech from text
+    ///
+    /// Providers without a text-to-speech API return `Unsupported`.
+    async fn speech(&self, _req: SpeechRequest) -> Result<Speech, AiError> {
+        Err(AiError::unsupported(format!(
+            "{} does not support speech synthesis",
+            self.info().name
+        )))
+    }
+
+    /// Transcribe audio to text
+    ///
+    /// Providers without a speech-to-text API return `Unsupported`.
+    async fn transcription(&self, _req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        Err(AiError::unsupported(format!(
+            "{} does not support transcription",
+            self.info().name
+        )))
+    }
+
+    /// List the models available with the configured credentials
+    ///
+    /// Providers without a model listing API return `Unsupported`.
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        Err(AiError::unsupported(format!(
+            "{} does not support listing models",
+            self.info().name
+        )))
+    }
+
+    /// Check that the provider is reachable and the credentials are valid
+    ///
+    /// Defaults to listing models, which is cheap and authenticated on most
+    /// APIs.
+    async fn health_check(&self) -> Result<(), AiError> {
+        self.list_models().await.map(|_| ())
+    }
+}
+
+/// Type-erased providers are providers too, so layers can wrap a provider
+/// whose concrete type is only known at runtime
+#[async_trait]
+impl Provider for Arc<dyn Provider> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        (**self).info()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        (**self).chat_completion(req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        (**self).stream_chat_completion(req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        (**self).chat_completion_raw(body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        (**self).api_request(req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        (**self).speech(req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        (**self).transcription(req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        (**self).list_models().await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        (**self).health_check().await
+    }
 }
 
 /// Helper function to collect a text stream into a result
@@ -53,6 +224,7 @@ pub async fn collect_text_stream(
     use futures::StreamExt;
 
     let mut content = String::new();
+    let mut reasoning: Option<String> = None;
     let mut finish_reason = None;
     let mut usage = None;
     let tool_calls = None;
@@ -60,6 +232,9 @@ pub async fn collect_text_stream(
     while let Some(chunk) = stream.next().await {
         let chunk = chunk?;
         content.push_str(&chunk.delta);
+        if let Some(delta) = &chunk.reasoning {
+            reasoning.get_or_insert_with(String::new).push_str(delta);
+        }
 
         if let Some(reason) = chunk.finish_reason {
             finish_reason = Some(reason);
@@ -73,12 +248,11 @@ pub async fn collect_text_stream(
     Ok(TextResult {
         content,
         finish_reason: finish_reason.unwrap_or(FinishReason::Stop),
-        usage: usage.unwrap_or(Usage {
-            prompt_tokens: 0,
-            completion_tokens: 0,
-            total_tokens: 0,
-        }),
+        usage: usage.unwrap_or_default(),
         model: response.model,
         tool_calls,
+        reasoning,
+        citations: None,
+        metadata: std::collections::HashMap::new(),
     })
 }
@@ -0,0 +1,302 @@
+//! Realtime speech-to-speech sessions.
+//!
+//! A [`RealtimeProvider`] opens a [`RealtimeSession`]: a bidirectional
+//! connection where the application streams microphone audio in and plays
+//! the model's audio as it arrives. Commands go out through a
+//! [`RealtimeSender`]; everything the model does comes back as a stream of
+//! [`RealtimeEvent`]s:
+//!
+//! ```ignore
+//! let session = provider
+//!     .connect(RealtimeConfig::new("gpt-4o-realtime-preview").with_voice("alloy"))
+//!     .await?;
+//! let (sender, mut events) = session.split();
+//!
+//! // Feed PCM16 microphone frames from another task
+//! sender.send_audio(&frame)?;
+//!
+//! while let Some(event) = events.next().await {
+//!     match event? {
+//!         RealtimeEvent::AudioDelta { audio, .. } => speaker.play(&audio),
+//!         // The user started talking over the model
+//!         RealtimeEvent::SpeechStarted => sender.interrupt(speaker.played())?,
+//!         RealtimeEvent::ToolCall { call_id, name, arguments } => {
+//!             let output = registry.execute(&name, &arguments).await?;
+//!             sender.send_tool_result(call_id, output)?;
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! ```
+
+use crate::error::AiError;
+use crate::types::{Tool, Usage};
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::stream::{Stream, StreamExt};
+use std::fmt::{self, Debug};
+use std::time::Duration;
+
+/// Stream type alias for realtime events
+pub type RealtimeEventStream = dyn Stream<Item = Result<RealtimeEvent, AiError>> + Send + Unpin;
+
+/// Encoding of audio sent and received
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFormat {
+    /// 16-bit little-endian PCM, 24 kHz mono
+    #[default]
+    Pcm16,
+    /// G.711 μ-law, 8 kHz, as used in telephony
+    G711Ulaw,
+    /// G.711 A-law, 8 kHz
+    G711Alaw,
+}
+
+/// Session setup
+#[derive(Debug, Clone)]
+pub struct RealtimeConfig {
+    pub model: String,
+    pub instructions: Option<String>,
+    /// Provider voice name, e.g. `alloy`
+    pub voice: Option<String>,
+    pub audio_format: AudioFormat,
+    /// Let the server detect when the user starts and stops speaking
+    ///
+    /// Without it the application commits the audio buffer and requests a
+    /// response itself.
+    pub server_vad: bool,
+    /// Transcribe the user's audio into [`RealtimeEvent::InputTranscript`]
+    pub transcribe_input: bool,
+    pub tools: Vec<Tool>,
+    pub temperature: Option<f32>,
+}
+
+impl RealtimeConfig {
+    /// Configuration for a model with server-side voice activity detection
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            instructions: None,
+            voice: None,
+            audio_format: AudioFormat::default(),
+            server_vad: true,
+            transcribe_input: false,
+            tools: Vec::new(),
+            temperature: None,
+        }
+    }
+
+    /// Set the system instructions
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Set the output voice
+    pub fn with_voice(mut self, voice: impl Into<String>) -> Self {
+        self.voice = Some(voice.into());
+        self
+    }
+
+    /// Set the audio encoding for both directions
+    pub fn with_audio_format(mut self, audio_format: AudioFormat) -> Self {
+        self.audio_format = audio_format;
+        self
+    }
+
+    /// Enable or disable server-side voice activity detection
+    pub fn with_server_vad(mut self, server_vad: bool) -> Self {
+        self.server_vad = server_vad;
+        self
+    }
+
+    /// Enable or disable transcription of the user's audio
+    pub fn with_input_transcription(mut self, transcribe_input: bool) -> Self {
+        self.transcribe_input = transcribe_input;
+        self
+    }
+
+    /// Set the tools the model may call
+    pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Set the sampling temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+}
+
+/// Command sent to a realtime session
+#[derive(Debug, Clone)]
+pub enum RealtimeCommand {
+    /// Change the session setup; the model is fixed once connected
+    UpdateSession(RealtimeConfig),
+    /// Append raw audio in the session's format to the input buffer
+    Audio(Vec<u8>),
+    /// Finish the current user turn (only needed without server VAD)
+    CommitAudio,
+    /// Add a user text message
+    Text(String),
+    /// Answer a tool call
+    ToolResult {
+        call_id: String,
+        output: serde_json::Value,
+    },
+    /// Ask the model to respond (only needed without server VAD)
+    CreateResponse,
+    /// Stop the current response, keeping only the audio the user heard
+    Interrupt {
+        /// How much of the response audio was played
+        played: Duration,
+    },
+}
+
+/// Event received from a realtime session
+#[derive(Debug, Clone)]
+pub enum RealtimeEvent {
+    /// The session is ready
+    SessionCreated { id: String },
+    /// A chunk of response audio in the session's format
+    AudioDelta { item_id: String, audio: Vec<u8> },
+    /// The response audio of an item is complete
+    AudioDone { item_id: String },
+    /// Transcript of the response audio so far
+    TranscriptDelta { text: String },
+    /// Text of a text-only response
+    TextDelta { text: String },
+    /// Transcript of what the user said
+    InputTranscript { text: String },
+    /// The user started speaking; playback should stop
+    SpeechStarted,
+    /// The user stopped speaking
+    SpeechStopped,
+    /// The model wants a tool called
+    ToolCall {
+        call_id: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+    /// A response finished
+    ResponseDone { usage: Option<Usage> },
+    /// The provider reported an error; the session stays open
+    Error { message: String },
+}
+
+/// Sending half of a [`RealtimeSession`]; cheap to clone
+#[derive(Debug, Clone)]
+pub struct RealtimeSender {
+    commands: mpsc::UnboundedSender<RealtimeCommand>,
+}
+
+impl RealtimeSender {
+    /// Send a command
+    pub fn send(&self, command: RealtimeCommand) -> Result<(), AiError> {
+        self.commands
+            .unbounded_send(command)
+            .map_err(|_| AiError::stream("Realtime session is closed"))
+    }
+
+    /// Append a frame of audio
+    pub fn send_audio(&self, audio: &[u8]) -> Result<(), AiError> {
+        self.send(RealtimeCommand::Audio(audio.to_vec()))
+    }
+
+    /// Finish the current user turn
+    pub fn commit_audio(&self) -> Result<(), AiError> {
+        self.send(RealtimeCommand::CommitAudio)
+    }
+
+    /// Add a user text message
+    pub fn send_text(&self, text: impl Into<String>) -> Result<(), AiError> {
+        self.send(RealtimeCommand::Text(text.into()))
+    }
+
+    /// Answer a tool call; the model continues once all calls are answered
+    /// and a response is requested
+    pub fn send_tool_result(
+        &self,
+        call_id: impl Into<String>,
+        output: serde_json::Value,
+    ) -> Result<(), AiError> {
+        self.send(RealtimeCommand::ToolResult {
+            call_id: call_id.into(),
+            output,
+        })
+    }
+
+    /// Ask the model to respond
+    pub fn create_response(&self) -> Result<(), AiError> {
+        self.send(RealtimeCommand::CreateResponse)
+    }
+
+    /// Stop the current response after `played` of its audio was heard
+    pub fn interrupt(&self, played: Duration) -> Result<(), AiError> {
+        self.send(RealtimeCommand::Interrupt { played })
+    }
+
+    /// Change the session setup
+    pub fn update_session(&self, config: RealtimeConfig) -> Result<(), AiError> {
+        self.send(RealtimeCommand::UpdateSession(config))
+    }
+
+    /// Close the session; the event stream ends once the connection closes
+    pub fn close(&self) {
+        self.commands.close_channel();
+    }
+}
+
+/// Open realtime connection
+pub struct RealtimeSession {
+    sender: RealtimeSender,
+    events: Box<RealtimeEventStream>,
+}
+
+impl Debug for RealtimeSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RealtimeSession")
+            .field("sender", &self.sender)
+            .finish()
+    }
+}
+
+impl RealtimeSession {
+    /// Create a session from a command channel and an event stream
+    ///
+    /// Providers spawn a task forwarding commands to the connection and
+    /// connection messages to `events`.
+    pub fn new(
+        commands: mpsc::UnboundedSender<RealtimeCommand>,
+        events: Box<RealtimeEventStream>,
+    ) -> Self {
+        Self {
+            sender: RealtimeSender { commands },
+            events,
+        }
+    }
+
+    /// Sender for commands
+    pub fn sender(&self) -> &RealtimeSender {
+        &self.sender
+    }
+
+    /// Wait for the next event; `None` once the connection is closed
+    pub async fn next_event(&mut self) -> Option<Result<RealtimeEvent, AiError>> {
+        self.events.next().await
+    }
+
+    /// Split into a sender and the event stream, to send from one task
+    /// while receiving in another
+    pub fn split(self) -> (RealtimeSender, Box<RealtimeEventStream>) {
+        (self.sender, self.events)
+    }
+}
+
+/// Trait for services with realtime speech sessions
+#[async_trait]
+pub trait RealtimeProvider: Send + Sync + Debug + 'static {
+    /// Open a session
+    async fn connect(&self, config: RealtimeConfig) -> Result<RealtimeSession, AiError>;
+}
@@ -0,0 +1,53 @@
+//! Reranking abstractions.
+//!
+//! A reranker scores documents against a query with a cross-encoder, which
+//! is slower than comparing embeddings but much more precise. Retrieval
+//! typically fetches a few dozen candidates from a vector store and keeps
+//! the best few after reranking.
+
+use crate::error::AiError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// Documents to order by relevance to a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RerankRequest {
+    pub query: String,
+    pub documents: Vec<String>,
+    /// Only return the most relevant `top_n` documents
+    pub top_n: Option<usize>,
+}
+
+impl RerankRequest {
+    /// Create a request scoring all documents
+    pub fn new(query: impl Into<String>, documents: Vec<String>) -> Self {
+        Self {
+            query: query.into(),
+            documents,
+            top_n: None,
+        }
+    }
+
+    /// Only return the most relevant `top_n` documents
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.top_n = Some(top_n);
+        self
+    }
+}
+
+/// Relevance of one document
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RerankResult {
+    /// Position of the document in the request
+    pub index: usize,
+    /// Relevance score; higher is more relevant
+    pub score: f32,
+}
+
+/// Trait for services that order documents by relevance to a query.
+#[async_trait]
+pub trait Reranker: Send + Sync + Debug + 'static {
+    /// Score the documents, returning results sorted by descending score
+    async fn rerank(&self, req: RerankRequest) -> Result<Vec<RerankResult>, AiError>;
+}
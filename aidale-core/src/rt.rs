@@ -0,0 +1,169 @@
+//! Async runtime shims.
+//!
+//! The core and the built-in layers only need a timer and a way to spawn
+//! background tasks, so they don't depend on a particular executor. The
+//! runtime is picked with a cargo feature:
+//!
+//! - `tokio` (default)
+//! - `async-std`
+//! - `smol`
+//!
+//! If several are enabled, the first in this list wins. On `wasm32`
+//! (browsers, Cloudflare Workers) no feature is needed: timers are backed by
+//! JavaScript's `setTimeout`, tasks by the microtask queue and [`Instant`]
+//! by `performance.now()`.
+//!
+//! ```toml
+//! aidale-core = { version = "0.1", default-features = false, features = ["smol"] }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(any(
+    target_arch = "wasm32",
+    feature = "tokio",
+    feature = "async-std",
+    feature = "smol"
+)))]
+compile_error!("aidale-core needs a runtime: enable the `tokio`, `async-std` or `smol` feature");
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub use web_time::Instant;
+
+/// The time limit of [`timeout`] elapsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Current time as seen by the runtime's timers
+///
+/// With tokio this follows its clock, so time measured by layers advances
+/// with `tokio::time::pause` and `advance` in tests.
+pub fn now() -> Instant {
+    #[cfg(all(not(target_arch = "wasm32"), feature = "tokio"))]
+    {
+        tokio::time::Instant::now().into_std()
+    }
+    #[cfg(not(all(not(target_arch = "wasm32"), feature = "tokio")))]
+    {
+        Instant::now()
+    }
+}
+
+/// Wait for `duration`
+pub async fn sleep(duration: Duration) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+        // wasm32 is single-threaded, so the JS timer never crosses threads
+        send_wrapper::SendWrapper::new(gloo_timers::future::TimeoutFuture::new(millis)).await
+    }
+    #[cfg(all(not(target_arch = "wasm32"), feature = "tokio"))]
+    {
+        tokio::time::sleep(duration).await
+    }
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        not(feature = "tokio"),
+        feature = "async-std"
+    ))]
+    {
+        async_std::task::sleep(duration).await
+    }
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        not(feature = "tokio"),
+        not(feature = "async-std"),
+        feature = "smol"
+    ))]
+    {
+        smol::Timer::after(duration).await;
+    }
+}
+
+/// Run `future`, giving up after `duration`
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+    #[cfg(all(not(target_arch = "wasm32"), feature = "tokio"))]
+    {
+        tokio::time::timeout(duration, future)
+            .await
+            .map_err(|_| Elapsed)
+    }
+    #[cfg(not(all(not(target_arch = "wasm32"), feature = "tokio")))]
+    {
+        use futures::future::{select, Either};
+        let future = std::pin::pin!(future);
+        let timer = std::pin::pin!(sleep(duration));
+        match select(future, timer).await {
+            Either::Left((output, _)) => Ok(output),
+            Either::Right(_) => Err(Elapsed),
+        }
+    }
+}
+
+/// Run `future` in the background
+///
+/// The task is detached: it runs to completion even if nothing waits for it.
+/// With tokio this must be called from within a runtime.
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(target_arch = "wasm32")]
+    {
+        wasm_bindgen_futures::spawn_local(future);
+    }
+    #[cfg(all(not(target_arch = "wasm32"), feature = "tokio"))]
+    {
+        tokio::spawn(future);
+    }
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        not(feature = "tokio"),
+        feature = "async-std"
+    ))]
+    {
+        async_std::task::spawn(future);
+    }
+    #[cfg(all(
+        not(target_arch = "wasm32"),
+        not(feature = "tokio"),
+        not(feature = "async-std"),
+        feature = "smol"
+    ))]
+    {
+        smol::spawn(future).detach();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timers() {
+        let start = now();
+        sleep(Duration::from_secs(5)).await;
+        assert_eq!(now() - start, Duration::from_secs(5));
+
+        assert_eq!(timeout(Duration::from_secs(1), async { 1 }).await, Ok(1));
+        let slow = sleep(Duration::from_secs(2));
+        assert_eq!(timeout(Duration::from_secs(1), slow).await, Err(Elapsed));
+
+        let (tx, rx) = futures::channel::oneshot::channel();
+        spawn(async move {
+            let _ = tx.send(());
+        });
+        assert!(rx.await.is_ok());
+    }
+}
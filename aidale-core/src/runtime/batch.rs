@@ -0,0 +1,314 @@
+//! Batch generation with bounded parallelism.
+//!
+//! [`RuntimeExecutor::generate_many`] runs a batch of text and object
+//! generations with at most `concurrency` requests in flight. A failing item
+//! does not affect the others; its error is kept in its [`BatchItem`]:
+//!
+//! ```ignore
+//! let requests = documents
+//!     .iter()
+//!     .map(|doc| BatchRequest::text("gpt-4o-mini", TextParams::new(vec![Message::user(doc)])));
+//!
+//! let batch = executor.generate_many(requests, 8).await;
+//! println!("{} failed, {} tokens", batch.failed(), batch.usage.total_tokens);
+//! for item in batch.items {
+//!     match item.result {
+//!         Ok(output) => println!("{}: {:?}", item.index, output.text()),
+//!         Err(err) => eprintln!("{}: {}", item.index, err),
+//!     }
+//! }
+//! ```
+//!
+//! [`RuntimeExecutor::stream_many`] yields items as they complete instead,
+//! e.g. to report progress.
+
+use crate::error::AiError;
+use crate::runtime::RuntimeExecutor;
+use crate::types::{ObjectParams, ObjectResult, TextParams, TextResult, Usage};
+use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
+
+/// Generation in a batch
+#[derive(Debug, Clone)]
+pub enum BatchRequest {
+    Text { model: String, params: TextParams },
+    Object { model: String, params: ObjectParams },
+}
+
+impl BatchRequest {
+    /// Create a text generation request
+    pub fn text(model: impl Into<String>, params: TextParams) -> Self {
+        Self::Text {
+            model: model.into(),
+            params,
+        }
+    }
+
+    /// Create an object generation request
+    pub fn object(model: impl Into<String>, params: ObjectParams) -> Self {
+        Self::Object {
+            model: model.into(),
+            params,
+        }
+    }
+
+    /// Requested model
+    pub fn model(&self) -> &str {
+        match self {
+            Self::Text { model, .. } | Self::Object { model, .. } => model,
+        }
+    }
+}
+
+/// Output of a successful batch generation
+#[derive(Debug, Clone)]
+pub enum BatchOutput {
+    Text(TextResult),
+    Object(ObjectResult),
+}
+
+impl BatchOutput {
+    /// Token usage of the generation
+    pub fn usage(&self) -> &Usage {
+        match self {
+            Self::Text(result) => &result.usage,
+            Self::Object(result) => &result.usage,
+        }
+    }
+
+    /// Generated text, for text requests
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::Text(result) => Some(&result.content),
+            Self::Object(_) => None,
+        }
+    }
+
+    /// Generated object, for object requests
+    pub fn object(&self) -> Option<&serde_json::Value> {
+        match self {
+            Self::Text(_) => None,
+            Self::Object(result) => Some(&result.object),
+        }
+    }
+}
+
+/// Outcome of one request of a batch
+#[derive(Debug)]
+pub struct BatchItem {
+    /// Position of the request in the batch
+    pub index: usize,
+    /// Requested model
+    pub model: String,
+    pub result: Result<BatchOutput, AiError>,
+}
+
+/// Outcome of a batch, with usage totals
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    /// Outcomes in request order
+    pub items: Vec<BatchItem>,
+    /// Usage of all successful generations
+    pub usage: Usage,
+    /// Usage of the successful generations by requested model
+    pub usage_by_model: HashMap<String, Usage>,
+}
+
+impl BatchResult {
+    /// Collect items, in any order
+    pub fn from_items(items: impl IntoIterator<Item = BatchItem>) -> Self {
+        let mut batch = Self {
+            items: items.into_iter().collect(),
+            ..Default::default()
+        };
+        batch.items.sort_by_key(|item| item.index);
+        for item in &batch.items {
+            if let Ok(output) = &item.result {
+                add_usage(&mut batch.usage, output.usage());
+                add_usage(
+                    batch.usage_by_model.entry(item.model.clone()).or_default(),
+                    output.usage(),
+                );
+            }
+        }
+        batch
+    }
+
+    /// Number of successful generations
+    pub fn succeeded(&self) -> usize {
+        self.items.iter().filter(|item| item.result.is_ok()).count()
+    }
+
+    /// Number of failed generations
+    pub fn failed(&self) -> usize {
+        self.items.len() - self.succeeded()
+    }
+
+    /// Total cost, given the cost of a model's usage
+    ///
+    /// ```ignore
+    /// let cost = batch.cost(|model, usage| pricing[model].cost(usage));
+    /// ```
+    pub fn cost(&self, price: impl Fn(&str, &Usage) -> f64) -> f64 {
+        self.usage_by_model
+            .iter()
+            .map(|(model, usage)| price(model, usage))
+            .sum()
+    }
+}
+
+fn add_usage(total: &mut Usage, usage: &Usage) {
+    total.prompt_tokens += usage.prompt_tokens;
+    total.completion_tokens += usage.completion_tokens;
+    total.total_tokens += usage.total_tokens;
+}
+
+impl RuntimeExecutor {
+    /// Run a batch of generations, at most `concurrency` at a time
+    ///
+    /// Items are returned in request order; failures are isolated per item.
+    pub async fn generate_many(
+        &self,
+        requests: impl IntoIterator<Item = BatchRequest>,
+        concurrency: usize,
+    ) -> BatchResult {
+        BatchResult::from_items(
+            self.stream_many(requests, concurrency)
+                .collect::<Vec<_>>()
+                .await,
+        )
+    }
+
+    /// Run a batch of generations, yielding items as they complete
+    ///
+    /// Completion order generally differs from request order; use
+    /// [`BatchItem::index`] to match items to requests.
+    pub fn stream_many(
+        &self,
+        requests: impl IntoIterator<Item = BatchRequest>,
+        concurrency: usize,
+    ) -> impl Stream<Item = BatchItem> + Send + '_ {
+        let requests: Vec<_> = requests.into_iter().enumerate().collect();
+        futures::stream::iter(requests)
+            .map(move |(index, request)| self.run_batch_request(index, request))
+            .buffer_unordered(concurrency.max(1))
+    }
+
+    async fn run_batch_request(&self, index: usize, request: BatchRequest) -> BatchItem {
+        let model = request.model().to_string();
+        let result = match request {
+            BatchRequest::Text { model, params } => self
+                .generate_text(model, params)
+                .await
+                .map(BatchOutput::Text),
+            BatchRequest::Object { model, params } => self
+                .generate_object(model, params)
+                .await
+                .map(BatchOutput::Object),
+        };
+        BatchItem {
+            index,
+            model,
+            result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{ChatCompletionStream, Provider};
+    use crate::types::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Echoes the prompt after a delay given by its length, failing on
+    /// "fail"; tracks the peak number of requests in flight
+    #[derive(Debug, Default)]
+    struct EchoProvider {
+        in_flight: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Provider for EchoProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "echo".to_string(),
+                name: "Echo".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let prompt = match req.messages[0].content.first() {
+                Some(ContentPart::Text { text }) => text.clone(),
+                _ => String::new(),
+            };
+            let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10 * prompt.len() as u64)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            if prompt == "fail" {
+                return Err(AiError::provider("failed"));
+            }
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(prompt),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    prompt_tokens: 2,
+                    completion_tokens: 1,
+                    total_tokens: 3,
+                    ..Default::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_generate_many() {
+        let provider = Arc::new(EchoProvider::default());
+        let executor = RuntimeExecutor::builder(provider.clone() as Arc<dyn Provider>).finish();
+        let requests = ["long prompt", "fail", "a", "bb"].map(|prompt| {
+            let model = if prompt == "a" { "small" } else { "large" };
+            BatchRequest::text(model, TextParams::new(vec![Message::user(prompt)]))
+        });
+
+        let batch = executor.generate_many(requests, 2).await;
+        assert_eq!(provider.peak.load(Ordering::SeqCst), 2);
+
+        let texts: Vec<_> = batch
+            .items
+            .iter()
+            .map(|item| item.result.as_ref().ok().and_then(BatchOutput::text))
+            .collect();
+        assert_eq!(texts, [Some("long prompt"), None, Some("a"), Some("bb")]);
+        assert_eq!((batch.succeeded(), batch.failed()), (3, 1));
+        assert_eq!(batch.usage.total_tokens, 9);
+        assert_eq!(batch.usage_by_model["small"].total_tokens, 3);
+        assert_eq!(batch.cost(|_, usage| usage.total_tokens as f64), 9.0);
+    }
+}
@@ -0,0 +1,432 @@
+//! Text classification into a fixed set of labels.
+//!
+//! [`RuntimeExecutor::classify_with_schema`] asks the model for exactly one
+//! label, with the label constrained by a JSON schema, and deserializes it
+//! into a label type, typically a unit enum. With the `schema` feature,
+//! [`RuntimeExecutor::classify`] derives the schema from the type:
+//!
+//! ```ignore
+//! #[derive(Deserialize, JsonSchema)]
+//! #[serde(rename_all = "snake_case")]
+//! enum Intent {
+//!     /// Questions about invoices and payments
+//!     Billing,
+//!     /// Bug reports and outages
+//!     Technical,
+//!     Other,
+//! }
+//!
+//! let result = executor
+//!     .classify::<Intent>("gpt-4o-mini", "I was charged twice", &ClassifyOptions::new())
+//!     .await?;
+//! println!("{:?} ({:?})", result.label, result.confidence);
+//! ```
+//!
+//! Doc comments of the variants are passed to the model as label
+//! descriptions. When the provider returns log probabilities, the
+//! confidence is the probability the model gave to the tokens of the label.
+//!
+//! Classification is an object request: it goes through
+//! [`RuntimeExecutor::generate_object`], so plugins, parameter defaults and
+//! the JSON output strategy apply as for any other object.
+
+use crate::error::AiError;
+use crate::runtime::RuntimeExecutor;
+use crate::types::{Message, ObjectParams, TokenLogprob, Usage};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const CLASSIFY_PROMPT: &str = "Classify the user message into exactly one of the labels below. \
+Reply with the label under \"label\".";
+
+/// Settings of [`RuntimeExecutor::classify_with_schema`]
+#[derive(Debug, Clone)]
+pub struct ClassifyOptions {
+    instructions: Option<String>,
+    logprobs: bool,
+    temperature: Option<f32>,
+}
+
+impl ClassifyOptions {
+    /// Request log probabilities, with temperature 0
+    pub fn new() -> Self {
+        Self {
+            instructions: None,
+            logprobs: true,
+            temperature: Some(0.0),
+        }
+    }
+
+    /// Additional guidance on how to choose a label
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Whether to request log probabilities for the confidence; disable for
+    /// providers that reject the parameter
+    pub fn with_logprobs(mut self, logprobs: bool) -> Self {
+        self.logprobs = logprobs;
+        self
+    }
+
+    /// Sampling temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+}
+
+impl Default for ClassifyOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shape of the object the model answers with
+#[derive(Deserialize)]
+struct LabelObject<L> {
+    label: L,
+}
+
+/// Result of a classification
+#[derive(Debug, Clone)]
+pub struct Classification<L> {
+    pub label: L,
+    /// Probability of the label, between 0 and 1, when the provider
+    /// returned log probabilities
+    pub confidence: Option<f32>,
+    pub usage: Usage,
+}
+
+impl RuntimeExecutor {
+    /// Classify `text` into one of the labels of `label_schema`
+    ///
+    /// The schema describes the label value, e.g. `{"enum": ["spam",
+    /// "ham"]}`; `oneOf` lists of `const` values with descriptions, as
+    /// generated for documented enums, are accepted too.
+    ///
+    /// A missing label or one outside the schema fails with
+    /// [`AiError::Serialization`].
+    pub async fn classify_with_schema<L: DeserializeOwned>(
+        &self,
+        model: &str,
+        text: &str,
+        label_schema: Value,
+        options: &ClassifyOptions,
+    ) -> Result<Classification<L>, AiError> {
+        let (label_schema, descriptions) = match labels_of(&label_schema) {
+            Some(labels) => {
+                let descriptions: Vec<String> = labels
+                    .iter()
+                    .map(|(label, description)| {
+                        let label = label
+                            .as_str()
+                            .map_or_else(|| label.to_string(), str::to_string);
+                        match description {
+                            Some(description) => format!("- {}: {}", label, description),
+                            None => format!("- {}", label),
+                        }
+                    })
+                    .collect();
+                let values: Vec<Value> = labels.into_iter().map(|(label, _)| label).collect();
+                let schema = if values.iter().all(Value::is_string) {
+                    json!({"type": "string", "enum": values})
+                } else {
+                    json!({ "enum": values })
+                };
+                (schema, Some(descriptions.join("\n")))
+            }
+            None => (label_schema, None),
+        };
+
+        let mut system = CLASSIFY_PROMPT.to_string();
+        if let Some(descriptions) = descriptions {
+            system.push_str("\n\nLabels:\n");
+            system.push_str(&descriptions);
+        }
+        if let Some(instructions) = &options.instructions {
+            system.push_str("\n\n");
+            system.push_str(instructions);
+        }
+        let schema = json!({
+            "type": "object",
+            "properties": {"label": label_schema},
+            "required": ["label"],
+            "additionalProperties": false,
+        });
+
+        let mut params =
+            ObjectParams::new(vec![Message::system(system), Message::user(text)], schema);
+        params.temperature = options.temperature;
+        if options.logprobs {
+            params = params.with_logprobs();
+        }
+
+        let result = self.generate_object(model, params).await?;
+        let confidence = result
+            .object
+            .get("label")
+            .zip(result.logprobs.as_deref())
+            .and_then(|(label, logprobs)| label_confidence(label, logprobs));
+        let LabelObject { label } = serde_json::from_value(result.object)?;
+
+        Ok(Classification {
+            label,
+            confidence,
+            usage: result.usage,
+        })
+    }
+
+    /// Classify `text` into a variant of the label type `L`, usually a unit
+    /// enum, using its JSON schema
+    #[cfg(feature = "schema")]
+    pub async fn classify<L: DeserializeOwned + schemars::JsonSchema>(
+        &self,
+        model: &str,
+        text: &str,
+        options: &ClassifyOptions,
+    ) -> Result<Classification<L>, AiError> {
+        let mut schema = serde_json::to_value(schemars::schema_for!(L))?;
+        if let Some(schema) = schema.as_object_mut() {
+            schema.remove("$schema");
+            schema.remove("title");
+            schema.remove("description");
+        }
+        self.classify_with_schema(model, text, schema, options)
+            .await
+    }
+}
+
+/// Label values and their descriptions listed by `schema`
+fn labels_of(schema: &Value) -> Option<Vec<(Value, Option<String>)>> {
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return Some(values.iter().map(|v| (v.clone(), None)).collect());
+    }
+    let variants = schema
+        .get("oneOf")
+        .or_else(|| schema.get("anyOf"))
+        .and_then(Value::as_array)?;
+    let mut labels = Vec::new();
+    for variant in variants {
+        let description = variant
+            .get("description")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        match (
+            variant.get("const"),
+            variant.get("enum").and_then(Value::as_array),
+        ) {
+            (Some(value), _) => labels.push((value.clone(), description)),
+            (None, Some(values)) => {
+                labels.extend(values.iter().map(|v| (v.clone(), description.clone())))
+            }
+            // Not a plain list of values
+            (None, None) => return None,
+        }
+    }
+    Some(labels)
+}
+
+/// Probability of the tokens spelling out `label` in the content made up
+/// by `logprobs`
+///
+/// `None` if the label can't be located.
+fn label_confidence(label: &Value, logprobs: &[TokenLogprob]) -> Option<f32> {
+    let content: String = logprobs.iter().map(|t| t.token.as_str()).collect();
+    let key = content.find("\"label\"")?;
+    let literal = label.to_string();
+    let start = key + content[key..].find(&literal)?;
+    // Skip the quotes of string labels
+    let (start, end) = match label {
+        Value::String(_) => (start + 1, start + literal.len() - 1),
+        _ => (start, start + literal.len()),
+    };
+
+    let mut offset = 0;
+    let mut logprob = 0.0;
+    for token in logprobs {
+        let token_end = offset + token.token.len();
+        if token_end > start && offset < end {
+            logprob += token.logprob;
+        }
+        offset = token_end;
+    }
+    Some(logprob.exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::Plugin;
+    use crate::provider::{ChatCompletionStream, Provider};
+    use crate::types::*;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    /// Answers "billing", or the label in the `label` extra parameter, with
+    /// log probabilities when asked for them; reports `max_tokens` as usage
+    #[derive(Debug)]
+    struct LabelProvider;
+
+    #[async_trait]
+    impl Provider for LabelProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "label".to_string(),
+                name: "Label".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let prompt: String = req
+                .messages
+                .iter()
+                .flat_map(|m| &m.content)
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            assert!(prompt.contains("- billing: Invoices and payments\n- tech"));
+            let label = req
+                .extra
+                .get("label")
+                .and_then(|label| label.as_str())
+                .unwrap_or("billing");
+            let (head, tail) = label.split_at(label.len() / 2);
+            let tokens = [
+                ("{\"", 0.0),
+                ("label", 0.0),
+                ("\":\"", 0.0),
+                (head, -0.1),
+                (tail, -0.2),
+                ("\"}", 0.0),
+            ];
+            let logprobs = req.logprobs.map(|_| {
+                tokens
+                    .iter()
+                    .map(|(token, logprob)| TokenLogprob {
+                        token: token.to_string(),
+                        logprob: *logprob,
+                        bytes: None,
+                        top_logprobs: Vec::new(),
+                    })
+                    .collect()
+            });
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(format!(r#"{{"label":"{}"}}"#, label)),
+                    finish_reason: FinishReason::Stop,
+                    logprobs,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    total_tokens: req.max_tokens.unwrap_or(0),
+                    ..Usage::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Team {
+        Billing,
+        Tech,
+    }
+
+    #[tokio::test]
+    async fn test_classify() {
+        let executor = RuntimeExecutor::builder(LabelProvider).finish();
+        let schema = json!({"oneOf": [
+            {"const": "billing", "description": "Invoices and payments"},
+            {"const": "tech"},
+        ]});
+
+        let result = executor
+            .classify_with_schema::<Team>(
+                "m",
+                "Charged twice",
+                schema.clone(),
+                &ClassifyOptions::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.label, Team::Billing);
+        assert!((result.confidence.unwrap() - (-0.3f32).exp()).abs() < 1e-6);
+
+        let options = ClassifyOptions::new().with_logprobs(false);
+        let result = executor
+            .classify_with_schema::<Team>("m", "Charged twice", schema, &options)
+            .await
+            .unwrap();
+        assert_eq!(result.confidence, None);
+    }
+
+    /// Makes the model answer with the label it is configured with
+    #[derive(Debug)]
+    struct ForceLabelPlugin(&'static str);
+
+    #[async_trait]
+    impl Plugin for ForceLabelPlugin {
+        fn name(&self) -> &str {
+            "force_label"
+        }
+
+        async fn transform_object_params(
+            &self,
+            params: ObjectParams,
+            _ctx: &RequestContext,
+        ) -> Result<ObjectParams, AiError> {
+            Ok(params.with_extra("label", json!(self.0)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_classify_runs_object_hooks() {
+        let schema = json!({"oneOf": [
+            {"const": "billing", "description": "Invoices and payments"},
+            {"const": "tech"},
+        ]});
+
+        let executor = RuntimeExecutor::builder(LabelProvider)
+            .plugin(Arc::new(ForceLabelPlugin("tech")))
+            .default_max_tokens(16)
+            .finish();
+        let result = executor
+            .classify_with_schema::<Team>("m", "Down", schema.clone(), &ClassifyOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(result.label, Team::Tech);
+        assert_eq!(result.usage.total_tokens, 16);
+
+        // Labels outside the schema are rejected as unparseable
+        let executor = RuntimeExecutor::builder(LabelProvider)
+            .plugin(Arc::new(ForceLabelPlugin("legal")))
+            .finish();
+        let err = executor
+            .classify_with_schema::<Team>("m", "Sue them", schema, &ClassifyOptions::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AiError::Serialization(_)), "{:?}", err);
+    }
+}
@@ -0,0 +1,141 @@
+//! Default generation parameters.
+//!
+//! Set on the executor builder, defaults fill in the parameters a call site
+//! leaves unset, so calls don't repeat `with_temperature(0.2)` everywhere.
+//! Per-model defaults take precedence over global ones, and explicitly set
+//! parameters always win:
+//!
+//! ```ignore
+//! let executor = RuntimeExecutor::builder(provider)
+//!     .default_temperature(0.2)
+//!     .default_max_tokens(1024)
+//!     .model_defaults("o3-mini", ParamDefaults::new().with_max_tokens(8192))
+//!     .finish();
+//! ```
+
+use crate::types::{ObjectParams, ReasoningEffort, TextParams};
+use std::collections::HashMap;
+
+/// Parameters applied when a request does not set them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParamDefaults {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f32>,
+    pub seed: Option<i64>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl ParamDefaults {
+    /// Create empty defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the default temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the default max tokens
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set the default top-p
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set the default sampling seed
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set the default reasoning effort
+    pub fn with_reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Fill the unset fields from `fallback`
+    fn or(&self, fallback: &Self) -> Self {
+        Self {
+            temperature: self.temperature.or(fallback.temperature),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            top_p: self.top_p.or(fallback.top_p),
+            seed: self.seed.or(fallback.seed),
+            reasoning_effort: self.reasoning_effort.or(fallback.reasoning_effort),
+        }
+    }
+}
+
+/// Global and per-model defaults of an executor
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Defaults {
+    pub(crate) global: ParamDefaults,
+    pub(crate) models: HashMap<String, ParamDefaults>,
+}
+
+impl Defaults {
+    fn for_model(&self, model: &str) -> ParamDefaults {
+        match self.models.get(model) {
+            Some(defaults) => defaults.or(&self.global),
+            None => self.global.clone(),
+        }
+    }
+
+    /// Fill unset text parameters
+    pub(crate) fn apply_text(&self, model: &str, mut params: TextParams) -> TextParams {
+        let defaults = self.for_model(model);
+        params.temperature = params.temperature.or(defaults.temperature);
+        params.max_tokens = params.max_tokens.or(defaults.max_tokens);
+        params.top_p = params.top_p.or(defaults.top_p);
+        params.seed = params.seed.or(defaults.seed);
+        params.reasoning_effort = params.reasoning_effort.or(defaults.reasoning_effort);
+        params
+    }
+
+    /// Fill unset object parameters
+    pub(crate) fn apply_object(&self, model: &str, mut params: ObjectParams) -> ObjectParams {
+        let defaults = self.for_model(model);
+        params.temperature = params.temperature.or(defaults.temperature);
+        params.max_tokens = params.max_tokens.or(defaults.max_tokens);
+        params.top_p = params.top_p.or(defaults.top_p);
+        params.seed = params.seed.or(defaults.seed);
+        params.reasoning_effort = params.reasoning_effort.or(defaults.reasoning_effort);
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Message;
+
+    #[test]
+    fn test_precedence() {
+        let defaults = Defaults {
+            global: ParamDefaults::new()
+                .with_temperature(0.2)
+                .with_max_tokens(1024),
+            models: HashMap::from([(
+                "big".to_string(),
+                ParamDefaults::new().with_max_tokens(8192),
+            )]),
+        };
+
+        let params = defaults.apply_text("big", TextParams::new(vec![Message::user("Hi")]));
+        assert_eq!(params.temperature, Some(0.2));
+        assert_eq!(params.max_tokens, Some(8192));
+
+        let params = TextParams::new(vec![Message::user("Hi")]).with_temperature(1.0);
+        let params = defaults.apply_text("small", params);
+        assert_eq!(params.temperature, Some(1.0));
+        assert_eq!(params.max_tokens, Some(1024));
+    }
+}
@@ -1,20 +1,40 @@
 //! RuntimeExecutor implementation.
 //!
 //! This module implements the RuntimeExecutor, which provides high-level
-//! generate_text() and generate_object() APIs by orchestrating provider
-//! chat completion calls with strategy selection.
+//! generate_text(), stream_text() and generate_object() APIs by orchestrating
+//! provider chat completion calls with strategy selection.
 
+use crate::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use crate::budget::Deadline;
 use crate::error::AiError;
-use crate::layer::Layer;
+use crate::events::RouteEvent;
+use crate::few_shot::ExampleStyle;
+use crate::layer::{erase_provider, BoxedLayer, Layer};
+use crate::normalize::MessageNormalizer;
 use crate::plugin::{Plugin, PluginEngine};
-use crate::provider::Provider;
+use crate::provider::{ChatCompletionStream, Provider};
+use crate::runtime::defaults::{Defaults, ParamDefaults};
+use crate::runtime::StreamedText;
 use crate::strategy::{detect_json_strategy, JsonOutputStrategy};
 use crate::types::*;
+use futures::StreamExt;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 /// Type-erased provider that can be shared across threads
 type BoxedProvider = Arc<dyn Provider>;
 
+/// How the executor handles responses stopped by a content filter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentFilterPolicy {
+    /// Return the result with [`FinishReason::ContentFilter`]; the filtered
+    /// categories are in its `filtered_categories` metadata
+    #[default]
+    Return,
+    /// Fail with [`AiError::ContentBlocked`]
+    Error,
+}
+
 /// Builder for composing AI providers with layers and plugins.
 ///
 /// This builder allows for flexible composition following OpenDAL's pattern:
@@ -34,6 +54,10 @@ pub struct RuntimeExecutorBuilder<P> {
     provider: P,
     plugins: Vec<Arc<dyn Plugin>>,
     json_strategy: Option<Box<dyn JsonOutputStrategy>>,
+    normalizer: Option<MessageNormalizer>,
+    example_style: Option<ExampleStyle>,
+    defaults: Defaults,
+    content_filter: ContentFilterPolicy,
 }
 
 impl<P: Provider> RuntimeExecutorBuilder<P> {
@@ -43,6 +67,10 @@ impl<P: Provider> RuntimeExecutorBuilder<P> {
             provider,
             plugins: Vec::new(),
             json_strategy: None,
+            normalizer: None,
+            example_style: None,
+            defaults: Defaults::default(),
+            content_filter: ContentFilterPolicy::default(),
         }
     }
 
@@ -58,6 +86,48 @@ impl<P: Provider> RuntimeExecutorBuilder<P> {
             provider: layer.layer(self.provider),
             plugins: self.plugins,
             json_strategy: self.json_strategy,
+            normalizer: self.normalizer,
+            example_style: self.example_style,
+            defaults: self.defaults,
+            content_filter: self.content_filter,
+        }
+    }
+
+    /// Add a type-erased layer
+    ///
+    /// Unlike [`Self::layer`], the builder's provider type becomes
+    /// `Arc<dyn Provider>` no matter which layer is added, so layers can be
+    /// chosen at runtime. Static and boxed layers can be mixed.
+    pub fn boxed_layer(self, layer: BoxedLayer) -> RuntimeExecutorBuilder<Arc<dyn Provider>> {
+        RuntimeExecutorBuilder {
+            provider: layer.layer(erase_provider(self.provider)),
+            plugins: self.plugins,
+            json_strategy: self.json_strategy,
+            normalizer: self.normalizer,
+            example_style: self.example_style,
+            defaults: self.defaults,
+            content_filter: self.content_filter,
+        }
+    }
+
+    /// Add type-erased layers in order
+    pub fn boxed_layers(
+        self,
+        layers: impl IntoIterator<Item = BoxedLayer>,
+    ) -> RuntimeExecutorBuilder<Arc<dyn Provider>> {
+        let provider = layers
+            .into_iter()
+            .fold(erase_provider(self.provider), |provider, layer| {
+                layer.layer(provider)
+            });
+        RuntimeExecutorBuilder {
+            provider,
+            plugins: self.plugins,
+            json_strategy: self.json_strategy,
+            normalizer: self.normalizer,
+            example_style: self.example_style,
+            defaults: self.defaults,
+            content_filter: self.content_filter,
         }
     }
 
@@ -75,20 +145,84 @@ impl<P: Provider> RuntimeExecutorBuilder<P> {
         self
     }
 
+    /// Set the message normalizer applied before requests are sent
+    ///
+    /// If not set, it is chosen based on the provider ID, see
+    /// [`MessageNormalizer::for_provider`].
+    pub fn message_normalizer(mut self, normalizer: MessageNormalizer) -> Self {
+        self.normalizer = Some(normalizer);
+        self
+    }
+
+    /// Set how few-shot examples are placed in the conversation
+    ///
+    /// If not set, it is chosen based on the provider ID, see
+    /// [`ExampleStyle::for_provider`].
+    pub fn example_style(mut self, style: ExampleStyle) -> Self {
+        self.example_style = Some(style);
+        self
+    }
+
+    /// Set the default temperature for requests that don't set one
+    pub fn default_temperature(mut self, temperature: f32) -> Self {
+        self.defaults.global.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the default max tokens for requests that don't set them
+    pub fn default_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.defaults.global.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set all global default parameters
+    pub fn default_params(mut self, defaults: ParamDefaults) -> Self {
+        self.defaults.global = defaults;
+        self
+    }
+
+    /// Set default parameters for one model
+    ///
+    /// They take precedence over the global defaults; fields left unset fall
+    /// back to them. The model is matched after plugins resolved it.
+    pub fn model_defaults(mut self, model: impl Into<String>, defaults: ParamDefaults) -> Self {
+        self.defaults.models.insert(model.into(), defaults);
+        self
+    }
+
+    /// Set how responses stopped by a content filter are handled
+    ///
+    /// Object generation always fails with [`AiError::ContentBlocked`], as
+    /// filtered output cannot be parsed reliably.
+    pub fn content_filter(mut self, policy: ContentFilterPolicy) -> Self {
+        self.content_filter = policy;
+        self
+    }
+
     /// Finish building and create a RuntimeExecutor
     pub fn finish(self) -> RuntimeExecutor {
-        let provider = Arc::new(self.provider);
+        let provider = erase_provider(self.provider);
         let provider_id = provider.info().id.clone();
 
         // Auto-detect strategy if not provided
         let json_strategy = self
             .json_strategy
             .unwrap_or_else(|| detect_json_strategy(&provider_id));
+        let normalizer = self
+            .normalizer
+            .unwrap_or_else(|| MessageNormalizer::for_provider(&provider_id));
+        let example_style = self
+            .example_style
+            .unwrap_or_else(|| ExampleStyle::for_provider(&provider_id));
 
         RuntimeExecutor {
             provider,
             plugin_engine: PluginEngine::new(self.plugins),
             json_strategy,
+            normalizer,
+            example_style,
+            defaults: self.defaults,
+            content_filter: self.content_filter,
         }
     }
 }
@@ -102,6 +236,10 @@ pub struct RuntimeExecutor {
     provider: BoxedProvider,
     plugin_engine: PluginEngine,
     json_strategy: Box<dyn JsonOutputStrategy>,
+    normalizer: MessageNormalizer,
+    example_style: ExampleStyle,
+    defaults: Defaults,
+    content_filter: ContentFilterPolicy,
 }
 
 impl RuntimeExecutor {
@@ -115,6 +253,42 @@ impl RuntimeExecutor {
         self.provider.info()
     }
 
+    /// List the models available from the provider, e.g. for a model picker
+    ///
+    /// Fails with `Unsupported` for providers without a model listing API.
+    pub async fn models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        self.provider.list_models().await
+    }
+
+    /// Check that the provider is reachable and the credentials are valid,
+    /// e.g. at startup
+    pub async fn health_check(&self) -> Result<(), AiError> {
+        self.provider.health_check().await
+    }
+
+    /// Synthesize speech through the provider's layers
+    pub async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        self.provider.speech(req).await
+    }
+
+    /// Transcribe audio through the provider's layers
+    pub async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        self.provider.transcription(req).await
+    }
+
+    /// The provider with all layers applied, for requests the typed APIs
+    /// can't express yet
+    ///
+    /// Calls made through it skip the plugins:
+    ///
+    /// ```ignore
+    /// let body = json!({"model": "o3", "messages": messages, "verbosity": "low"});
+    /// let response = executor.raw().chat_completion_raw(body).await?;
+    /// ```
+    pub fn raw(&self) -> &dyn Provider {
+        &*self.provider
+    }
+
     /// Get reference to the plugin engine
     pub fn plugin_engine(&self) -> &PluginEngine {
         &self.plugin_engine
@@ -130,82 +304,392 @@ impl RuntimeExecutor {
         params: TextParams,
     ) -> Result<TextResult, AiError> {
         let model = model.into();
-        let provider_info = self.provider.info();
+        let ctx = RequestContext::new(self.provider.info().id.clone(), model.clone());
+        self.generate_text_with_context(model, params, ctx).await
+    }
 
-        // Create request context
-        let ctx = RequestContext::new(provider_info.id.clone(), model.clone());
+    /// Generate text with a caller-provided request context
+    ///
+    /// Use this to run requests inside a session: derive each request's
+    /// context with [`RequestContext::child`] so plugins share metadata and
+    /// extensions across turns. The context's provider and model are set to
+    /// this executor's provider and the requested model.
+    pub async fn generate_text_with_context(
+        &self,
+        model: impl Into<String>,
+        params: TextParams,
+        ctx: RequestContext,
+    ) -> Result<TextResult, AiError> {
+        let mut results = self.generate_candidates(model.into(), params, ctx).await?;
+        Ok(results.swap_remove(0))
+    }
+
+    /// Generate several completion candidates for the same prompt
+    ///
+    /// Requests `params.n` choices (see [`TextParams::with_n`]) and returns
+    /// one result per choice, in order. Each result passes through the
+    /// plugins' `transform_result` and `on_request_end`; `usage` covers the
+    /// whole request and is repeated on every candidate.
+    pub async fn generate_texts(
+        &self,
+        model: impl Into<String>,
+        params: TextParams,
+    ) -> Result<Vec<TextResult>, AiError> {
+        let model = model.into();
+        let ctx = RequestContext::new(self.provider.info().id.clone(), model.clone());
+        self.generate_candidates(model, params, ctx).await
+    }
+
+    /// Generate several completion candidates with a caller-provided
+    /// request context
+    pub async fn generate_texts_with_context(
+        &self,
+        model: impl Into<String>,
+        params: TextParams,
+        ctx: RequestContext,
+    ) -> Result<Vec<TextResult>, AiError> {
+        self.generate_candidates(model.into(), params, ctx).await
+    }
+
+    /// Shared implementation of the `generate_text*` APIs; never returns an
+    /// empty vector
+    async fn generate_candidates(
+        &self,
+        model: String,
+        params: TextParams,
+        mut ctx: RequestContext,
+    ) -> Result<Vec<TextResult>, AiError> {
+        ctx.provider_id = self.provider.info().id.clone();
+        ctx.model = model.clone();
+
+        // Stop multi-round runs once the deadline or token budget is used up
+        ctx.check_budget()?;
 
         // Resolve model through plugins
         let resolved_model = self.plugin_engine.resolve_model(&model, &ctx).await?;
 
-        // Transform params through plugins
-        let transformed_params = self.plugin_engine.transform_params(params, &ctx).await?;
+        // Fill unset params from the defaults, then transform through plugins
+        let params = self.defaults.apply_text(&resolved_model, params);
+        let mut transformed_params = self.plugin_engine.transform_params(params, &ctx).await?;
+        let messages = self.example_style.render(
+            std::mem::take(&mut transformed_params.messages),
+            std::mem::take(&mut transformed_params.examples),
+        );
+        transformed_params.messages = self.normalizer.normalize(messages)?;
 
         // Fire on_request_start hooks
         self.plugin_engine.on_request_start(&ctx).await?;
 
         // Convert to chat completion request
-        let chat_req = ChatCompletionRequest {
-            model: resolved_model.clone(),
-            messages: transformed_params.messages,
-            temperature: transformed_params.temperature,
-            max_tokens: transformed_params.max_tokens,
-            top_p: transformed_params.top_p,
-            frequency_penalty: transformed_params.frequency_penalty,
-            presence_penalty: transformed_params.presence_penalty,
-            stop: transformed_params.stop,
-            tools: transformed_params.tools,
-            response_format: Some(ResponseFormat::Text),
-            stream: Some(false),
-            extra: transformed_params.extra,
-        };
+        let chat_req = text_request(resolved_model, transformed_params, &ctx, false);
 
         // Make the actual request
-        let result = self.provider.chat_completion(chat_req).await;
+        let result = with_deadline(ctx.deadline, self.provider.chat_completion(chat_req)).await;
+        self.plugin_engine.on_layer_events(&ctx).await?;
+        let result = match result {
+            Ok(response) => self.candidate_results(response, &ctx).await,
+            Err(err) => Err(err),
+        };
 
         match result {
-            Ok(response) => {
-                // Convert ChatCompletionResponse to TextResult
-                let first_choice = response
-                    .choices
-                    .first()
-                    .ok_or_else(|| AiError::provider("No choices in response"))?;
-
-                let content = first_choice
-                    .message
-                    .content
-                    .iter()
-                    .filter_map(|part| match part {
-                        ContentPart::Text { text } => Some(text.as_str()),
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>()
-                    .join("");
-
-                let mut result = TextResult {
-                    content,
-                    finish_reason: first_choice.finish_reason.clone(),
-                    usage: response.usage,
-                    model: response.model,
-                    tool_calls: None,
+            Ok(results) => {
+                // Fire on_request_end hooks, once per candidate
+                for result in &results {
+                    self.plugin_engine.on_request_end(&ctx, result).await?;
+                }
+                Ok(results)
+            }
+            Err(err) => {
+                // Fire on_error hooks
+                let _ = self.plugin_engine.on_error(&err, &ctx).await;
+                Err(err)
+            }
+        }
+    }
+
+    /// Convert each choice of a response to a `TextResult` and run it
+    /// through the plugins' `transform_result`
+    async fn candidate_results(
+        &self,
+        response: ChatCompletionResponse,
+        ctx: &RequestContext,
+    ) -> Result<Vec<TextResult>, AiError> {
+        if let Some(budget) = &ctx.token_budget {
+            budget.consume(response.usage.total_tokens as u64);
+        }
+        if response.choices.is_empty() {
+            return Err(AiError::provider("No choices in response"));
+        }
+        if self.content_filter == ContentFilterPolicy::Error {
+            if let Some(err) = content_filter_error(&response.choices[0]) {
+                return Err(err);
+            }
+        }
+
+        let mut results = Vec::with_capacity(response.choices.len());
+        for choice in &response.choices {
+            let mut result = text_result(choice, &response);
+            if let Some(route) = ctx.extensions().get::<RouteEvent>() {
+                result.metadata.insert(
+                    "route".to_string(),
+                    serde_json::json!({
+                        "from": route.from,
+                        "to": route.to,
+                        "reason": route.reason,
+                    }),
+                );
+            }
+            results.push(self.plugin_engine.transform_result(result, ctx).await?);
+        }
+        Ok(results)
+    }
+
+    /// Stream text using streaming chat completion
+    ///
+    /// Plugins see the same `resolve_model` / `transform_params` /
+    /// `on_request_start` hooks as [`Self::generate_text`], followed by
+    /// `on_stream_start`, `on_chunk` for every chunk and `on_stream_end` with
+    /// the aggregated result once the stream is exhausted.
+    ///
+    /// The returned [`StreamedText`] is a stream of [`TextChunk`]s that also
+    /// offers [`StreamedText::text_stream`] for plain deltas,
+    /// [`StreamedText::events`] for typed [`StreamEvent`]s and
+    /// [`StreamedText::final_result`] for the aggregated [`TextResult`].
+    pub async fn stream_text(
+        &self,
+        model: impl Into<String>,
+        params: TextParams,
+    ) -> Result<StreamedText, AiError> {
+        let model = model.into();
+        let ctx = RequestContext::new(self.provider.info().id.clone(), model.clone());
+        self.stream_text_with_context(model, params, ctx).await
+    }
+
+    /// Stream text with a caller-provided request context
+    ///
+    /// The context's deadline bounds the whole stream: a chunk that doesn't
+    /// arrive in time ends it with `DeadlineExceeded`.
+    pub async fn stream_text_with_context(
+        &self,
+        model: impl Into<String>,
+        params: TextParams,
+        mut ctx: RequestContext,
+    ) -> Result<StreamedText, AiError> {
+        let model = model.into();
+        ctx.provider_id = self.provider.info().id.clone();
+        ctx.model = model.clone();
+        ctx.check_budget()?;
+
+        let resolved_model = self.plugin_engine.resolve_model(&model, &ctx).await?;
+        let params = self.defaults.apply_text(&resolved_model, params);
+        let mut transformed_params = self.plugin_engine.transform_params(params, &ctx).await?;
+        let messages = self.example_style.render(
+            std::mem::take(&mut transformed_params.messages),
+            std::mem::take(&mut transformed_params.examples),
+        );
+        transformed_params.messages = self.normalizer.normalize(messages)?;
+
+        self.plugin_engine.on_request_start(&ctx).await?;
+        self.plugin_engine.on_stream_start(&ctx).await?;
+
+        let chat_req = text_request(resolved_model.clone(), transformed_params, &ctx, true);
+
+        let stream_result =
+            with_deadline(ctx.deadline, self.provider.stream_chat_completion(chat_req)).await;
+        self.plugin_engine.on_layer_events(&ctx).await?;
+        let mut inner = match stream_result {
+            Ok(stream) => stream,
+            Err(err) => {
+                let _ = self.plugin_engine.on_error(&err, &ctx).await;
+                return Err(err);
+            }
+        };
+
+        let engine = self.plugin_engine.clone();
+        let content_filter = self.content_filter;
+        let result_model = resolved_model.clone();
+        let stream = async_stream::try_stream! {
+            let mut content = String::new();
+            let mut reasoning: Option<String> = None;
+            let mut tool_calls = Vec::new();
+            let mut finish_reason = None;
+            let mut usage = None;
+            let mut model = resolved_model;
+            let mut metadata = HashMap::new();
+
+            loop {
+                // The deadline covers the whole stream, not just its start
+                let next = match ctx.deadline {
+                    Some(deadline) => crate::rt::timeout(deadline.remaining(), inner.next())
+                        .await
+                        .unwrap_or_else(|_| {
+                            Some(Err(AiError::deadline_exceeded(
+                                "provider did not finish streaming before the deadline",
+                            )))
+                        }),
+                    None => inner.next().await,
+                };
+                let Some(chunk) = next else {
+                    break;
+                };
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(err) => {
+                        let _ = engine.on_error(&err, &ctx).await;
+                        Err(err)?
+                    }
                 };
 
-                // Transform result through plugins
-                result = self.plugin_engine.transform_result(result, &ctx).await?;
+                model = chunk.model;
+                for (key, value) in [
+                    ("system_fingerprint", chunk.system_fingerprint),
+                    ("service_tier", chunk.service_tier),
+                ] {
+                    if let Some(value) = value {
+                        metadata.insert(key.to_string(), value.into());
+                    }
+                }
+                let choice = chunk.choices.into_iter().find(|choice| choice.index == 0);
+                let text_chunk = TextChunk {
+                    delta: choice
+                        .as_ref()
+                        .and_then(|choice| choice.delta.content.clone())
+                        .unwrap_or_default(),
+                    reasoning: choice
+                        .as_ref()
+                        .and_then(|choice| choice.delta.reasoning.clone()),
+                    tool_calls: choice
+                        .as_ref()
+                        .and_then(|choice| choice.delta.tool_calls.clone())
+                        .filter(|calls| !calls.is_empty()),
+                    finish_reason: choice.and_then(|choice| choice.finish_reason),
+                    usage: chunk.usage,
+                };
 
-                // Fire on_request_end hooks
-                self.plugin_engine.on_request_end(&ctx, &result).await?;
+                if text_chunk.delta.is_empty()
+                    && text_chunk.reasoning.is_none()
+                    && text_chunk.tool_calls.is_none()
+                    && text_chunk.finish_reason.is_none()
+                    && text_chunk.usage.is_none()
+                {
+                    continue;
+                }
 
-                Ok(result)
+                if content_filter == ContentFilterPolicy::Error
+                    && text_chunk.finish_reason == Some(FinishReason::ContentFilter)
+                {
+                    // Streamed chunks carry no filter categories
+                    let err = AiError::content_blocked(Vec::new());
+                    let _ = engine.on_error(&err, &ctx).await;
+                    Err(err)?;
+                }
+
+                engine.on_chunk(&text_chunk, &ctx).await?;
+
+                content.push_str(&text_chunk.delta);
+                if let Some(delta) = &text_chunk.reasoning {
+                    reasoning.get_or_insert_with(String::new).push_str(delta);
+                }
+                if let Some(calls) = &text_chunk.tool_calls {
+                    tool_calls.extend(calls.iter().cloned());
+                }
+                if let Some(reason) = &text_chunk.finish_reason {
+                    finish_reason = Some(reason.clone());
+                }
+                if let Some(u) = &text_chunk.usage {
+                    usage = Some(u.clone());
+                }
+
+                yield text_chunk;
+            }
+
+            if let (Some(budget), Some(usage)) = (&ctx.token_budget, &usage) {
+                budget.consume(usage.total_tokens as u64);
+            }
+
+            let result = TextResult {
+                content,
+                finish_reason: finish_reason.unwrap_or(FinishReason::Stop),
+                usage: usage.unwrap_or_default(),
+                model,
+                tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                reasoning,
+                citations: None,
+                metadata,
+            };
+            engine.on_stream_end(&ctx, &result).await?;
+        };
+
+        let stream = self
+            .plugin_engine
+            .apply_stream_transforms(Box::new(Box::pin(stream)));
+        Ok(StreamedText::new(stream, result_model))
+    }
+
+    /// Send a prebuilt chat completion request
+    ///
+    /// A lower-level alternative to [`Self::generate_text`] for requests that
+    /// need full control over `response_format`, `tools` or `extra`. The
+    /// request goes through the provider's layers as-is; plugins see
+    /// `resolve_model`, `on_request_start` and `on_error`, but not the
+    /// `TextParams`/`TextResult` transform hooks.
+    pub async fn chat_completion(
+        &self,
+        mut req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let ctx = self.prepare_request(&mut req).await?;
+
+        let result = self.provider.chat_completion(req).await;
+        self.plugin_engine.on_layer_events(&ctx).await?;
+        match result {
+            Ok(response) => Ok(response),
+            Err(err) => {
+                let _ = self.plugin_engine.on_error(&err, &ctx).await;
+                Err(err)
             }
+        }
+    }
+
+    /// Stream a prebuilt chat completion request
+    ///
+    /// The streaming counterpart of [`Self::chat_completion`]; additionally
+    /// fires `on_stream_start` before the request is sent.
+    pub async fn stream(
+        &self,
+        mut req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        req.stream = Some(true);
+        let ctx = self.prepare_request(&mut req).await?;
+        self.plugin_engine.on_stream_start(&ctx).await?;
+
+        let result = self.provider.stream_chat_completion(req).await;
+        self.plugin_engine.on_layer_events(&ctx).await?;
+        match result {
+            Ok(stream) => Ok(stream),
             Err(err) => {
-                // Fire on_error hooks
                 let _ = self.plugin_engine.on_error(&err, &ctx).await;
                 Err(err)
             }
         }
     }
 
+    /// Create a context for a prebuilt request, resolve its model and fire
+    /// `on_request_start`
+    async fn prepare_request(
+        &self,
+        req: &mut ChatCompletionRequest,
+    ) -> Result<RequestContext, AiError> {
+        let ctx = RequestContext::new(self.provider.info().id.clone(), req.model.clone())
+            .with_metadata(req.metadata.clone());
+        req.events = ctx.events().clone();
+        req.model = self.plugin_engine.resolve_model(&req.model, &ctx).await?;
+        req.messages = self
+            .normalizer
+            .normalize(std::mem::take(&mut req.messages))?;
+        self.plugin_engine.on_request_start(&ctx).await?;
+        Ok(ctx)
+    }
+
     /// Generate object using chat completion with JSON output
     ///
     /// This is a high-level API that handles provider-specific JSON output strategies.
@@ -217,27 +701,78 @@ impl RuntimeExecutor {
         params: ObjectParams,
     ) -> Result<ObjectResult, AiError> {
         let model = model.into();
+        let ctx = RequestContext::new(self.provider.info().id.clone(), model.clone());
+        self.generate_object_with_context(model, params, ctx).await
+    }
 
-        // Convert to chat completion request
-        let mut chat_req = ChatCompletionRequest {
-            model: model.clone(),
-            messages: params.messages,
-            temperature: params.temperature,
-            max_tokens: params.max_tokens,
-            top_p: None,
-            frequency_penalty: None,
-            presence_penalty: None,
-            stop: None,
-            tools: None,
-            response_format: None, // Will be set by strategy
-            stream: Some(false),
-            extra: std::collections::HashMap::new(),
+    /// Generate object with a caller-provided request context
+    ///
+    /// Plugins see the same hooks as [`Self::generate_text_with_context`],
+    /// with `transform_object_params`, `transform_object_result` and
+    /// `on_object_end` in place of their text counterparts. The context's
+    /// deadline and token budget are enforced.
+    pub async fn generate_object_with_context(
+        &self,
+        model: impl Into<String>,
+        params: ObjectParams,
+        mut ctx: RequestContext,
+    ) -> Result<ObjectResult, AiError> {
+        let model = model.into();
+        ctx.provider_id = self.provider.info().id.clone();
+        ctx.model = model.clone();
+        ctx.check_budget()?;
+
+        // Resolve model and transform params through plugins
+        let resolved_model = self.plugin_engine.resolve_model(&model, &ctx).await?;
+        let params = self.defaults.apply_object(&resolved_model, params);
+        let mut params = self
+            .plugin_engine
+            .transform_object_params(params, &ctx)
+            .await?;
+        let messages = self.example_style.render(
+            std::mem::take(&mut params.messages),
+            std::mem::take(&mut params.examples),
+        );
+        params.messages = self.normalizer.normalize(messages)?;
+
+        self.plugin_engine.on_request_start(&ctx).await?;
+
+        // Convert to chat completion request and apply the JSON output strategy
+        let schema = params.schema.clone();
+        let mut chat_req = object_request(resolved_model, params, &ctx);
+        self.json_strategy.apply(&mut chat_req, &schema)?;
+
+        let result = with_deadline(ctx.deadline, self.request_object(chat_req)).await;
+        self.plugin_engine.on_layer_events(&ctx).await?;
+        let result = match result {
+            Ok(result) => {
+                if let Some(budget) = &ctx.token_budget {
+                    budget.consume(result.usage.total_tokens as u64);
+                }
+                self.plugin_engine
+                    .transform_object_result(result, &ctx)
+                    .await
+            }
+            Err(err) => Err(err),
         };
 
-        // Apply JSON output strategy
-        self.json_strategy.apply(&mut chat_req, &params.schema)?;
+        match result {
+            Ok(result) => {
+                self.plugin_engine.on_object_end(&ctx, &result).await?;
+                Ok(result)
+            }
+            Err(err) => {
+                let _ = self.plugin_engine.on_error(&err, &ctx).await;
+                Err(err)
+            }
+        }
+    }
 
-        // Make the actual request
+    /// Send an object request and parse the JSON content of the response
+    async fn request_object(
+        &self,
+        chat_req: ChatCompletionRequest,
+    ) -> Result<ObjectResult, AiError> {
         let response = self.provider.chat_completion(chat_req).await?;
 
         // Extract JSON object from response
@@ -245,6 +780,9 @@ impl RuntimeExecutor {
             .choices
             .first()
             .ok_or_else(|| AiError::provider("No choices in response"))?;
+        if let Some(err) = content_filter_error(first_choice) {
+            return Err(err);
+        }
 
         let content = first_choice
             .message
@@ -257,13 +795,887 @@ impl RuntimeExecutor {
             .collect::<Vec<_>>()
             .join("");
 
-        // Parse JSON content
-        let object: serde_json::Value = serde_json::from_str(&content)?;
+        // Parse JSON content, tolerating fences and truncation
+        let object = crate::json_repair::parse(&content)?;
 
+        let logprobs = first_choice.logprobs.clone();
         Ok(ObjectResult {
             object,
             usage: response.usage,
             model: response.model,
+            logprobs,
         })
     }
 }
+
+/// Convert a response choice to a text result
+///
+/// `system_fingerprint`, `service_tier`, `stop_sequence`,
+/// `filtered_categories` and `logprobs` are copied to the result metadata
+/// when the provider reports them.
+fn text_result(choice: &Choice, response: &ChatCompletionResponse) -> TextResult {
+    let collect = |select: fn(&ContentPart) -> Option<&str>| {
+        choice
+            .message
+            .content
+            .iter()
+            .filter_map(select)
+            .collect::<Vec<_>>()
+    };
+
+    let content = collect(|part| match part {
+        ContentPart::Text { text } => Some(text),
+        _ => None,
+    })
+    .join("");
+    let reasoning = collect(|part| match part {
+        ContentPart::Reasoning { text } => Some(text),
+        _ => None,
+    });
+    let reasoning = (!reasoning.is_empty()).then(|| reasoning.join(""));
+
+    let tool_calls: Vec<_> = choice
+        .message
+        .content
+        .iter()
+        .filter(|part| matches!(part, ContentPart::ToolCall { .. }))
+        .cloned()
+        .collect();
+    let tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+
+    let citations: Vec<_> = choice
+        .message
+        .content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Citation(citation) => Some(citation.clone()),
+            _ => None,
+        })
+        .collect();
+    let citations = (!citations.is_empty()).then_some(citations);
+
+    let mut metadata: HashMap<_, _> = [
+        ("system_fingerprint", &response.system_fingerprint),
+        ("service_tier", &response.service_tier),
+        ("stop_sequence", &choice.stop_sequence),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key.to_string(), value.clone()?.into())))
+    .collect();
+    if !choice.filtered_categories.is_empty() {
+        metadata.insert(
+            "filtered_categories".to_string(),
+            choice.filtered_categories.clone().into(),
+        );
+    }
+    if let Some(logprobs) = &choice.logprobs {
+        if let Ok(logprobs) = serde_json::to_value(logprobs) {
+            metadata.insert("logprobs".to_string(), logprobs);
+        }
+    }
+
+    TextResult {
+        content,
+        finish_reason: choice.finish_reason.clone(),
+        usage: response.usage.clone(),
+        model: response.model.clone(),
+        tool_calls,
+        reasoning,
+        citations,
+        metadata,
+    }
+}
+
+/// Build a chat completion request from text params.
+///
+/// Per-request headers: context metadata first, explicit params win.
+fn text_request(
+    model: String,
+    params: TextParams,
+    ctx: &RequestContext,
+    stream: bool,
+) -> ChatCompletionRequest {
+    let mut headers = ctx.headers();
+    headers.extend(params.headers);
+    let max_tokens = budgeted_max_tokens(ctx, params.max_tokens);
+
+    ChatCompletionRequest {
+        model,
+        messages: params.messages,
+        temperature: params.temperature,
+        max_tokens,
+        top_p: params.top_p,
+        frequency_penalty: params.frequency_penalty,
+        presence_penalty: params.presence_penalty,
+        stop: params.stop,
+        tools: params.tools,
+        tool_choice: params.tool_choice,
+        parallel_tool_calls: params.parallel_tool_calls,
+        response_format: Some(ResponseFormat::Text),
+        stream: Some(stream),
+        reasoning_effort: params.reasoning_effort,
+        seed: params.seed,
+        logprobs: params.logprobs,
+        top_logprobs: params.top_logprobs,
+        logit_bias: params.logit_bias,
+        n: params.n,
+        user: params.user,
+        headers,
+        deadline: ctx.deadline,
+        options: ctx.options(),
+        metadata: ctx.metadata(),
+        events: ctx.events().clone(),
+        extra: params.extra,
+    }
+}
+
+/// `ContentBlocked` error for a choice stopped by a content filter
+fn content_filter_error(choice: &Choice) -> Option<AiError> {
+    (choice.finish_reason == FinishReason::ContentFilter)
+        .then(|| AiError::content_blocked(choice.filtered_categories.clone()))
+}
+
+/// Never ask for more completion tokens than the budget has left
+fn budgeted_max_tokens(ctx: &RequestContext, max_tokens: Option<u32>) -> Option<u32> {
+    match &ctx.token_budget {
+        Some(budget) => {
+            let remaining = u32::try_from(budget.remaining()).unwrap_or(u32::MAX);
+            Some(max_tokens.map_or(remaining, |max| max.min(remaining)))
+        }
+        None => max_tokens,
+    }
+}
+
+/// Convert object params to a chat completion request; the response format
+/// is left to the JSON output strategy
+fn object_request(
+    model: String,
+    params: ObjectParams,
+    ctx: &RequestContext,
+) -> ChatCompletionRequest {
+    let mut headers = ctx.headers();
+    headers.extend(params.headers);
+    let max_tokens = budgeted_max_tokens(ctx, params.max_tokens);
+
+    ChatCompletionRequest {
+        model,
+        messages: params.messages,
+        temperature: params.temperature,
+        max_tokens,
+        top_p: params.top_p,
+        frequency_penalty: params.frequency_penalty,
+        presence_penalty: params.presence_penalty,
+        stop: params.stop,
+        tools: params.tools,
+        tool_choice: params.tool_choice,
+        parallel_tool_calls: params.parallel_tool_calls,
+        response_format: None,
+        stream: Some(false),
+        reasoning_effort: params.reasoning_effort,
+        seed: params.seed,
+        logprobs: params.logprobs,
+        top_logprobs: None,
+        logit_bias: params.logit_bias,
+        n: None,
+        user: params.user,
+        headers,
+        deadline: ctx.deadline,
+        options: ctx.options(),
+        metadata: ctx.metadata(),
+        events: ctx.events().clone(),
+        extra: params.extra,
+    }
+}
+
+/// Run a provider call, failing with `DeadlineExceeded` if it outlives the
+/// deadline
+async fn with_deadline<T>(
+    deadline: Option<Deadline>,
+    future: impl std::future::Future<Output = Result<T, AiError>>,
+) -> Result<T, AiError> {
+    match deadline {
+        Some(deadline) => crate::rt::timeout(deadline.remaining(), future)
+            .await
+            .map_err(|_| {
+                AiError::deadline_exceeded("provider did not respond before the deadline")
+            })?,
+        None => future.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::time::Duration;
+
+    /// Returns one choice per requested candidate, echoing its index
+    #[derive(Debug)]
+    struct CandidatesProvider;
+
+    #[async_trait]
+    impl Provider for CandidatesProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "test".to_string(),
+                name: "Test".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let choices = (0..req.n.unwrap_or(1) as u32)
+                .map(|index| Choice {
+                    index,
+                    message: Message::assistant(format!("candidate {}", index)),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                })
+                .collect();
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices,
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_texts_returns_all_candidates() {
+        let executor = RuntimeExecutor::builder(CandidatesProvider).finish();
+        let params = TextParams::new(vec![Message::user("Hi")]).with_n(3);
+
+        let results = executor
+            .generate_texts("test-model", params.clone())
+            .await
+            .unwrap();
+        let contents: Vec<_> = results.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, ["candidate 0", "candidate 1", "candidate 2"]);
+
+        let first = executor.generate_text("test-model", params).await.unwrap();
+        assert_eq!(first.content, "candidate 0");
+    }
+
+    /// Layer replacing the model of every request
+    struct ModelLayer(&'static str);
+
+    #[derive(Debug)]
+    struct ModelProvider(Arc<dyn Provider>, &'static str);
+
+    impl Layer<Arc<dyn Provider>> for ModelLayer {
+        type LayeredProvider = ModelProvider;
+
+        fn layer(&self, inner: Arc<dyn Provider>) -> ModelProvider {
+            ModelProvider(inner, self.0)
+        }
+    }
+
+    #[async_trait]
+    impl Provider for ModelProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            self.0.info()
+        }
+
+        async fn chat_completion(
+            &self,
+            mut req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            req.model = format!("{}/{}", req.model, self.1);
+            self.0.chat_completion(req).await
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            self.0.stream_chat_completion(req).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_boxed_layers() {
+        // A layer stack decided at runtime, e.g. from configuration
+        for with_b in [false, true] {
+            let mut layers = vec![BoxedLayer::new(ModelLayer("a"))];
+            if with_b {
+                layers.push(BoxedLayer::new(ModelLayer("b")));
+            }
+            let executor = RuntimeExecutor::builder(CandidatesProvider)
+                .boxed_layers(layers)
+                .boxed_layer(BoxedLayer::new(ModelLayer("c")))
+                .finish();
+
+            let result = executor
+                .generate_text("m", TextParams::new(vec![Message::user("Hi")]))
+                .await
+                .unwrap();
+            // The outermost layer sees the request first
+            let expected = if with_b { "m/c/b/a" } else { "m/c/a" };
+            assert_eq!(result.model, expected);
+        }
+    }
+
+    /// Answers with the sampling parameters it received, as JSON
+    #[derive(Debug)]
+    struct EchoParamsProvider;
+
+    #[async_trait]
+    impl Provider for EchoParamsProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            CandidatesProvider.info()
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let echo = serde_json::json!({
+                "temperature": req.temperature,
+                "top_p": req.top_p,
+                "frequency_penalty": req.frequency_penalty,
+                "presence_penalty": req.presence_penalty,
+                "stop": req.stop,
+                "parallel_tool_calls": req.parallel_tool_calls,
+                "logit_bias": req.logit_bias,
+                "top_logprobs": req.top_logprobs,
+                "seed": req.seed,
+                "n": req.n,
+                "user": req.user,
+                "extra": req.extra,
+            });
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(echo.to_string()),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: req.logprobs.map(|_| {
+                        vec![TokenLogprob {
+                            token: "{".to_string(),
+                            logprob: -0.5,
+                            bytes: None,
+                            top_logprobs: Vec::new(),
+                        }]
+                    }),
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_object_params_reach_provider() {
+        let executor = RuntimeExecutor::builder(EchoParamsProvider)
+            .default_temperature(0.2)
+            .finish();
+        let params = ObjectParams::new(vec![Message::user("Hi")], serde_json::json!({}))
+            .with_top_p(0.9)
+            .with_stop(vec!["END".to_string()])
+            .with_parallel_tool_calls(false)
+            .with_logit_bias(HashMap::from([("50256".to_string(), -100)]))
+            .with_seed(7)
+            .with_user("user-123")
+            .with_extra("safe_mode", serde_json::json!(true));
+
+        let result = executor.generate_object("m", params).await.unwrap();
+        assert_eq!(
+            result.object,
+            serde_json::json!({
+                "temperature": 0.2f32,
+                "top_p": 0.9f32,
+                "frequency_penalty": null,
+                "presence_penalty": null,
+                "stop": ["END"],
+                "parallel_tool_calls": false,
+                "logit_bias": {"50256": -100},
+                "top_logprobs": null,
+                "seed": 7,
+                "n": null,
+                "user": "user-123",
+                "extra": {"safe_mode": true},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_text_params_reach_provider() {
+        let executor = RuntimeExecutor::builder(EchoParamsProvider).finish();
+        let mut params = TextParams::new(vec![Message::user("Hi")])
+            .with_seed(42)
+            .with_n(3)
+            .with_logprobs(2)
+            .with_logit_bias(HashMap::from([("50256".to_string(), -100)]))
+            .with_user("user-123");
+        params.frequency_penalty = Some(0.5);
+        params.presence_penalty = Some(-0.5);
+
+        let results = executor.generate_texts("m", params).await.unwrap();
+        let echo: serde_json::Value = serde_json::from_str(&results[0].content).unwrap();
+        assert_eq!(echo["seed"], 42);
+        assert_eq!(echo["n"], 3);
+        assert_eq!(echo["top_logprobs"], 2);
+        assert_eq!(echo["logit_bias"], serde_json::json!({"50256": -100}));
+        assert_eq!(echo["user"], "user-123");
+        assert_eq!(echo["frequency_penalty"], 0.5);
+        assert_eq!(echo["presence_penalty"], -0.5);
+
+        let results = executor
+            .generate_texts("m", TextParams::new(vec![Message::user("Hi")]))
+            .await
+            .unwrap();
+        let echo: serde_json::Value = serde_json::from_str(&results[0].content).unwrap();
+        for key in ["seed", "n", "top_logprobs", "logit_bias", "user"] {
+            assert!(echo[key].is_null(), "{key}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_text_logprobs() {
+        let executor = RuntimeExecutor::builder(EchoParamsProvider).finish();
+        let params = TextParams::new(vec![Message::user("Hi")]).with_logprobs(2);
+
+        let result = executor.generate_text("m", params).await.unwrap();
+        let echo: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(echo["top_logprobs"], 2);
+        assert_eq!(result.metadata["logprobs"][0]["logprob"], -0.5);
+
+        let params = TextParams::new(vec![Message::user("Hi")]);
+        let result = executor.generate_text("m", params).await.unwrap();
+        assert!(!result.metadata.contains_key("logprobs"));
+    }
+
+    /// Stops every response with the content filter
+    #[derive(Debug)]
+    struct FilteredProvider;
+
+    #[async_trait]
+    impl Provider for FilteredProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            CandidatesProvider.info()
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant("Sure, here is how to"),
+                    finish_reason: FinishReason::ContentFilter,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: vec!["violence".to_string()],
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_policy() {
+        let params = TextParams::new(vec![Message::user("Hi")]);
+
+        let executor = RuntimeExecutor::builder(FilteredProvider).finish();
+        let result = executor.generate_text("m", params.clone()).await.unwrap();
+        assert_eq!(result.finish_reason, FinishReason::ContentFilter);
+        assert_eq!(
+            result.metadata["filtered_categories"],
+            serde_json::json!(["violence"])
+        );
+
+        let executor = RuntimeExecutor::builder(FilteredProvider)
+            .content_filter(ContentFilterPolicy::Error)
+            .finish();
+        let err = executor.generate_text("m", params).await.unwrap_err();
+        assert!(
+            matches!(err, AiError::ContentBlocked { categories } if categories == ["violence"])
+        );
+    }
+
+    /// Streams "Hel", "lo" and a final chunk; model "slow" stalls before
+    /// the final chunk
+    #[derive(Debug)]
+    struct StreamingProvider;
+
+    #[async_trait]
+    impl Provider for StreamingProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            CandidatesProvider.info()
+        }
+
+        async fn chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            Err(AiError::unsupported("chat"))
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            let chunk = |content: Option<&str>, finish_reason| ChatCompletionChunk {
+                id: "resp".to_string(),
+                model: req.model.clone(),
+                choices: vec![ChoiceDelta {
+                    index: 0,
+                    delta: MessageDelta {
+                        role: None,
+                        content: content.map(str::to_string),
+                        reasoning: None,
+                        tool_calls: None,
+                    },
+                    finish_reason,
+                }],
+                usage: None,
+                system_fingerprint: None,
+                service_tier: None,
+            };
+            let head = vec![Ok(chunk(Some("Hel"), None)), Ok(chunk(Some("lo"), None))];
+            let last = chunk(None, Some(FinishReason::Stop));
+            let slow = req.model == "slow";
+            let stream = futures::stream::iter(head).chain(futures::stream::once(async move {
+                if slow {
+                    crate::rt::sleep(Duration::from_secs(60)).await;
+                }
+                Ok(last)
+            }));
+            Ok(Box::new(Box::pin(stream)))
+        }
+    }
+
+    /// Records the hooks it sees and tags params and results; rejects text
+    /// results of the model "reject"
+    #[derive(Debug, Default)]
+    struct RecordingPlugin {
+        calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl RecordingPlugin {
+        fn record(&self, call: impl Into<String>) {
+            self.calls.lock().unwrap().push(call.into());
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl Plugin for RecordingPlugin {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn transform_result(
+            &self,
+            result: TextResult,
+            ctx: &RequestContext,
+        ) -> Result<TextResult, AiError> {
+            if ctx.model == "reject" {
+                return Err(AiError::plugin("recording", "rejected"));
+            }
+            Ok(result)
+        }
+
+        async fn on_request_end(
+            &self,
+            _ctx: &RequestContext,
+            result: &TextResult,
+        ) -> Result<(), AiError> {
+            self.record(format!("on_request_end {}", result.content));
+            Ok(())
+        }
+
+        async fn transform_object_params(
+            &self,
+            params: ObjectParams,
+            _ctx: &RequestContext,
+        ) -> Result<ObjectParams, AiError> {
+            self.record("transform_object_params");
+            Ok(params.with_temperature(0.5))
+        }
+
+        async fn transform_object_result(
+            &self,
+            mut result: ObjectResult,
+            _ctx: &RequestContext,
+        ) -> Result<ObjectResult, AiError> {
+            self.record("transform_object_result");
+            result.object["tagged"] = true.into();
+            Ok(result)
+        }
+
+        async fn on_object_end(
+            &self,
+            _ctx: &RequestContext,
+            result: &ObjectResult,
+        ) -> Result<(), AiError> {
+            self.record(format!("on_object_end tagged={}", result.object["tagged"]));
+            Ok(())
+        }
+
+        async fn on_error(&self, error: &AiError, _ctx: &RequestContext) -> Result<(), AiError> {
+            self.record(format!("on_error {}", error));
+            Ok(())
+        }
+
+        async fn on_stream_start(&self, _ctx: &RequestContext) -> Result<(), AiError> {
+            self.record("on_stream_start");
+            Ok(())
+        }
+
+        async fn on_chunk(&self, chunk: &TextChunk, _ctx: &RequestContext) -> Result<(), AiError> {
+            self.record(format!("on_chunk {:?}", chunk.delta));
+            Ok(())
+        }
+
+        async fn on_stream_end(
+            &self,
+            _ctx: &RequestContext,
+            result: &TextResult,
+        ) -> Result<(), AiError> {
+            self.record(format!("on_stream_end {:?}", result.content));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_candidate_hooks() {
+        let plugin = Arc::new(RecordingPlugin::default());
+        let executor = RuntimeExecutor::builder(CandidatesProvider)
+            .plugin(plugin.clone())
+            .finish();
+        let params = TextParams::new(vec![Message::user("Hi")]).with_n(2);
+
+        executor.generate_texts("m", params.clone()).await.unwrap();
+        assert_eq!(
+            plugin.calls(),
+            ["on_request_end candidate 0", "on_request_end candidate 1"]
+        );
+
+        // A failing transform_result reaches on_error, not on_request_end
+        let plugin = Arc::new(RecordingPlugin::default());
+        let executor = RuntimeExecutor::builder(CandidatesProvider)
+            .plugin(plugin.clone())
+            .finish();
+        let err = executor.generate_texts("reject", params).await.unwrap_err();
+        assert!(matches!(err, AiError::Plugin { .. }));
+        assert_eq!(
+            plugin.calls(),
+            ["on_error Plugin error (recording): rejected"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_object_hooks() {
+        let plugin = Arc::new(RecordingPlugin::default());
+        let executor = RuntimeExecutor::builder(EchoParamsProvider)
+            .plugin(plugin.clone())
+            .finish();
+        let params = ObjectParams::new(vec![Message::user("Hi")], serde_json::json!({}));
+
+        let result = executor.generate_object("m", params).await.unwrap();
+        assert_eq!(result.object["temperature"], 0.5);
+        assert_eq!(result.object["tagged"], true);
+        assert_eq!(
+            plugin.calls(),
+            [
+                "transform_object_params",
+                "transform_object_result",
+                "on_object_end tagged=true",
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_hooks_and_deadline() {
+        let plugin = Arc::new(RecordingPlugin::default());
+        let executor = RuntimeExecutor::builder(StreamingProvider)
+            .plugin(plugin.clone())
+            .finish();
+        let params = TextParams::new(vec![Message::user("Hi")]);
+
+        let stream = executor.stream_text("m", params.clone()).await.unwrap();
+        assert_eq!(stream.final_result().await.unwrap().content, "Hello");
+        assert_eq!(
+            plugin.calls(),
+            [
+                "on_stream_start",
+                "on_chunk \"Hel\"",
+                "on_chunk \"lo\"",
+                "on_chunk \"\"",
+                "on_stream_end \"Hello\"",
+            ]
+        );
+
+        // A stream that stalls past the deadline fails after it started
+        let plugin = Arc::new(RecordingPlugin::default());
+        let executor = RuntimeExecutor::builder(StreamingProvider)
+            .plugin(plugin.clone())
+            .finish();
+        let ctx = RequestContext::new("test", "slow").with_timeout(Duration::from_secs(5));
+        let stream = executor
+            .stream_text_with_context("slow", params, ctx)
+            .await
+            .unwrap();
+        let err = stream.final_result().await.unwrap_err();
+        assert!(matches!(err, AiError::DeadlineExceeded(_)), "{:?}", err);
+        assert!(plugin
+            .calls()
+            .last()
+            .is_some_and(|call| call.starts_with("on_error Deadline exceeded")));
+    }
+
+    /// Records the requests it receives; answers like [`CandidatesProvider`]
+    /// and streams like [`StreamingProvider`]
+    #[derive(Debug, Default)]
+    struct CapturingProvider {
+        requests: std::sync::Mutex<Vec<ChatCompletionRequest>>,
+    }
+
+    #[async_trait]
+    impl Provider for CapturingProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            CandidatesProvider.info()
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            self.requests.lock().unwrap().push(req.clone());
+            CandidatesProvider.chat_completion(req).await
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            self.requests.lock().unwrap().push(req.clone());
+            StreamingProvider.stream_chat_completion(req).await
+        }
+    }
+
+    /// Resolves the model alias "fast" and records request starts
+    #[derive(Debug, Default)]
+    struct AliasPlugin {
+        calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Plugin for AliasPlugin {
+        fn name(&self) -> &str {
+            "alias"
+        }
+
+        async fn resolve_model(
+            &self,
+            model_id: &str,
+            _ctx: &RequestContext,
+        ) -> Result<Option<String>, AiError> {
+            Ok((model_id == "fast").then(|| "gpt-fast".to_string()))
+        }
+
+        async fn on_request_start(&self, ctx: &RequestContext) -> Result<(), AiError> {
+            let tenant = ctx.metadata().get("tenant").cloned().unwrap_or_default();
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("on_request_start {} tenant={}", ctx.model, tenant));
+            Ok(())
+        }
+
+        async fn on_stream_start(&self, _ctx: &RequestContext) -> Result<(), AiError> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push("on_stream_start".to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prebuilt_requests_are_prepared() {
+        let provider = Arc::new(CapturingProvider::default());
+        let plugin = Arc::new(AliasPlugin::default());
+        let executor = RuntimeExecutor::builder(provider.clone() as Arc<dyn Provider>)
+            .plugin(plugin.clone())
+            .message_normalizer(MessageNormalizer::new().with_merge_consecutive(true))
+            .finish();
+        let mut req =
+            ChatCompletionRequest::new("fast", vec![Message::user("Hi"), Message::user("there")])
+                .with_metadata("tenant", "acme")
+                .with_extra("safe_mode", serde_json::json!(true));
+        req.response_format = Some(ResponseFormat::JsonObject);
+
+        let response = executor.chat_completion(req.clone()).await.unwrap();
+        assert_eq!(response.model, "gpt-fast");
+        let stream = executor.stream(req).await.unwrap();
+        assert_eq!(stream.count().await, 3);
+
+        // Both paths resolve the model and normalize messages, but keep
+        // the caller's response format and extra parameters
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for req in requests.iter() {
+            assert_eq!(req.model, "gpt-fast");
+            assert_eq!(req.messages.len(), 1);
+            assert!(matches!(
+                req.response_format,
+                Some(ResponseFormat::JsonObject)
+            ));
+            assert_eq!(req.extra["safe_mode"], true);
+        }
+        assert_eq!(requests[1].stream, Some(true));
+        assert_eq!(
+            *plugin.calls.lock().unwrap(),
+            [
+                "on_request_start fast tenant=acme",
+                "on_request_start fast tenant=acme",
+                "on_stream_start",
+            ]
+        );
+    }
+}
@@ -0,0 +1,495 @@
+//! Structured extraction from long documents.
+//!
+//! [`RuntimeExecutor::extract`] pulls every item matching a JSON schema out
+//! of a document of any length: the document is split into overlapping
+//! token-sized chunks, each chunk is extracted from concurrently with the
+//! schema enforced, and the items of all chunks are merged into one list:
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct Party {
+//!     name: String,
+//!     role: Option<String>,
+//! }
+//!
+//! let options = ExtractOptions::new(json!({
+//!     "type": "object",
+//!     "properties": {"name": {"type": "string"}, "role": {"type": ["string", "null"]}},
+//!     "required": ["name", "role"],
+//! }))
+//! .with_key_fields(["name"]);
+//! let parties = executor
+//!     .extract::<Party>("gpt-4o-mini", &contract, "Extract the contract parties", &options)
+//!     .await?;
+//! ```
+//!
+//! Items with the same key (the key fields, compared case-insensitively, or
+//! the whole item without key fields) are considered duplicates, which
+//! removes the repeats caused by chunk overlap. Duplicates that disagree
+//! are resolved by the [`ConflictPolicy`] and reported in
+//! [`Extraction::conflicts`].
+
+use crate::error::AiError;
+use crate::ingestion::{TextSplitter, TokenTextSplitter};
+use crate::runtime::{BatchOutput, BatchRequest, RuntimeExecutor};
+use crate::tokenizer::{HeuristicTokenCounter, TokenCounter};
+use crate::types::{Message, ObjectParams, Usage};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const EXTRACT_PROMPT: &str = "The user message is one part of a longer document. Extract every \
+item described by the instructions below that this part states, under \"items\". Use only \
+information from the text; use null for fields it doesn't give. Reply with an empty list if \
+there are none.";
+
+/// How duplicates that disagree are resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Combine the items field by field: missing and null fields are filled
+    /// in from later items, arrays are united, and conflicting values keep
+    /// the first one
+    #[default]
+    Merge,
+    /// Keep the item found first in the document
+    KeepFirst,
+    /// Keep the item found last in the document
+    KeepLast,
+}
+
+/// Settings of [`RuntimeExecutor::extract`]
+#[derive(Clone)]
+pub struct ExtractOptions {
+    item_schema: Value,
+    key_fields: Vec<String>,
+    on_conflict: ConflictPolicy,
+    chunk_tokens: usize,
+    overlap_tokens: usize,
+    concurrency: usize,
+    counter: Arc<dyn TokenCounter>,
+}
+
+impl ExtractOptions {
+    /// Extract items matching the JSON `item_schema`, in 3000-token chunks
+    /// overlapping by 200 tokens with 4 concurrent calls
+    pub fn new(item_schema: Value) -> Self {
+        Self {
+            item_schema,
+            key_fields: Vec::new(),
+            on_conflict: ConflictPolicy::default(),
+            chunk_tokens: 3000,
+            overlap_tokens: 200,
+            concurrency: 4,
+            counter: Arc::new(HeuristicTokenCounter::new()),
+        }
+    }
+
+    /// Fields identifying an item; without any, only identical items are
+    /// duplicates
+    pub fn with_key_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.key_fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// How to resolve duplicates that disagree
+    pub fn with_on_conflict(mut self, policy: ConflictPolicy) -> Self {
+        self.on_conflict = policy;
+        self
+    }
+
+    /// Tokens per chunk
+    pub fn with_chunk_tokens(mut self, chunk_tokens: usize) -> Self {
+        self.chunk_tokens = chunk_tokens.max(1);
+        self
+    }
+
+    /// Tokens shared by neighbouring chunks, so items spanning a chunk
+    /// boundary are seen whole at least once
+    pub fn with_overlap_tokens(mut self, overlap_tokens: usize) -> Self {
+        self.overlap_tokens = overlap_tokens;
+        self
+    }
+
+    /// Maximum number of calls in flight
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Token counter used to split the document
+    pub fn with_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Identity of an item for deduplication
+    fn key(&self, item: &Value) -> String {
+        let fields: Vec<Value> = self
+            .key_fields
+            .iter()
+            .map(|field| normalize(item.get(field).unwrap_or(&Value::Null)))
+            .collect();
+        if fields.is_empty() || fields.iter().all(Value::is_null) {
+            normalize(item).to_string()
+        } else {
+            Value::Array(fields).to_string()
+        }
+    }
+}
+
+impl std::fmt::Debug for ExtractOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExtractOptions")
+            .field("key_fields", &self.key_fields)
+            .field("on_conflict", &self.on_conflict)
+            .field("chunk_tokens", &self.chunk_tokens)
+            .field("overlap_tokens", &self.overlap_tokens)
+            .field("concurrency", &self.concurrency)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Disagreement between duplicates of an item
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractConflict {
+    /// Key of the item
+    pub key: String,
+    /// JSON pointer of the conflicting field; empty for whole items
+    pub field: String,
+    pub kept: Value,
+    pub discarded: Value,
+}
+
+/// Result of an extraction
+#[derive(Debug, Clone)]
+pub struct Extraction<T> {
+    /// Merged items, in order of first appearance in the document
+    pub items: Vec<T>,
+    /// Number of chunks the document was split into
+    pub chunks: usize,
+    pub conflicts: Vec<ExtractConflict>,
+    /// Merged items that didn't deserialize into `T` and were dropped
+    pub invalid: usize,
+    /// Usage of all calls
+    pub usage: Usage,
+}
+
+impl RuntimeExecutor {
+    /// Extract all items matching `options`' schema from a document of any
+    /// length, following `instructions`
+    ///
+    /// Fails with the first error of any call.
+    pub async fn extract<T: DeserializeOwned>(
+        &self,
+        model: &str,
+        document: &str,
+        instructions: &str,
+        options: &ExtractOptions,
+    ) -> Result<Extraction<T>, AiError> {
+        let splitter = TokenTextSplitter::new(
+            options.counter.clone(),
+            options.chunk_tokens,
+            options.overlap_tokens,
+        );
+        let chunks = splitter.split(document);
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "items": {"type": "array", "items": options.item_schema},
+            },
+            "required": ["items"],
+            "additionalProperties": false,
+        });
+        let system = format!("{}\n\nInstructions: {}", EXTRACT_PROMPT, instructions);
+        let requests = chunks.iter().map(|chunk| {
+            let messages = vec![
+                Message::system(system.clone()),
+                Message::user(chunk.clone()),
+            ];
+            BatchRequest::object(model, ObjectParams::new(messages, schema.clone()))
+        });
+
+        let mut results: Vec<_> = self
+            .stream_many(requests, options.concurrency)
+            .collect()
+            .await;
+        results.sort_by_key(|item| item.index);
+
+        let mut usage = Usage::default();
+        let mut merger = Merger::new(options);
+        for item in results {
+            let output = item.result?;
+            let output_usage = output.usage();
+            usage.prompt_tokens += output_usage.prompt_tokens;
+            usage.completion_tokens += output_usage.completion_tokens;
+            usage.total_tokens += output_usage.total_tokens;
+            let object = match output {
+                BatchOutput::Object(result) => result.object,
+                BatchOutput::Text(_) => unreachable!("extraction requests objects"),
+            };
+            let items = match object {
+                Value::Object(mut map) => map.remove("items"),
+                array @ Value::Array(_) => Some(array),
+                _ => None,
+            };
+            let Some(Value::Array(items)) = items else {
+                return Err(AiError::provider(format!(
+                    "Extraction of chunk {} returned no items list",
+                    item.index
+                )));
+            };
+            for item in items {
+                merger.add(item);
+            }
+        }
+
+        let mut invalid = 0;
+        let items = merger
+            .items
+            .into_iter()
+            .filter_map(|(_, item)| match serde_json::from_value(item) {
+                Ok(item) => Some(item),
+                Err(e) => {
+                    tracing::warn!("Dropping extracted item: {}", e);
+                    invalid += 1;
+                    None
+                }
+            })
+            .collect();
+        Ok(Extraction {
+            items,
+            chunks: chunks.len(),
+            conflicts: merger.conflicts,
+            invalid,
+            usage,
+        })
+    }
+}
+
+/// Deduplicates items in document order
+struct Merger<'a> {
+    options: &'a ExtractOptions,
+    items: Vec<(String, Value)>,
+    positions: HashMap<String, usize>,
+    conflicts: Vec<ExtractConflict>,
+}
+
+impl<'a> Merger<'a> {
+    fn new(options: &'a ExtractOptions) -> Self {
+        Self {
+            options,
+            items: Vec::new(),
+            positions: HashMap::new(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, item: Value) {
+        let key = self.options.key(&item);
+        let Some(&position) = self.positions.get(&key) else {
+            self.positions.insert(key.clone(), self.items.len());
+            self.items.push((key, item));
+            return;
+        };
+        let existing = &mut self.items[position].1;
+        if *existing == item {
+            return;
+        }
+        match self.options.on_conflict {
+            ConflictPolicy::Merge => merge(existing, item, &key, "", &mut self.conflicts),
+            ConflictPolicy::KeepFirst => self.conflicts.push(ExtractConflict {
+                key,
+                field: String::new(),
+                kept: existing.clone(),
+                discarded: item,
+            }),
+            ConflictPolicy::KeepLast => {
+                let discarded = std::mem::replace(existing, item);
+                self.conflicts.push(ExtractConflict {
+                    key,
+                    field: String::new(),
+                    kept: existing.clone(),
+                    discarded,
+                });
+            }
+        }
+    }
+}
+
+/// Merge `new` into `existing`, recording values that disagree
+fn merge(
+    existing: &mut Value,
+    new: Value,
+    key: &str,
+    field: &str,
+    conflicts: &mut Vec<ExtractConflict>,
+) {
+    match (existing, new) {
+        (_, Value::Null) => {}
+        (existing @ Value::Null, new) => *existing = new,
+        (Value::Object(existing), Value::Object(new)) => {
+            for (name, value) in new {
+                let path = format!("{}/{}", field, name.replace('~', "~0").replace('/', "~1"));
+                match existing.get_mut(&name) {
+                    Some(current) => merge(current, value, key, &path, conflicts),
+                    None => {
+                        existing.insert(name, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(existing), Value::Array(new)) => {
+            for value in new {
+                if !existing.contains(&value) {
+                    existing.push(value);
+                }
+            }
+        }
+        (existing, new) => {
+            if normalize(existing) != normalize(&new) {
+                conflicts.push(ExtractConflict {
+                    key: key.to_string(),
+                    field: field.to_string(),
+                    kept: existing.clone(),
+                    discarded: new,
+                });
+            }
+        }
+    }
+}
+
+/// Trim and lowercase strings so trivially different spellings match
+fn normalize(value: &Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(s.trim().to_lowercase()),
+        Value::Array(items) => Value::Array(items.iter().map(normalize).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(name, value)| (name.clone(), normalize(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{ChatCompletionStream, Provider};
+    use crate::types::*;
+    use async_trait::async_trait;
+    use serde::Deserialize;
+
+    /// Extracts one person per `Name (role)` or `Name` line
+    #[derive(Debug)]
+    struct LineProvider;
+
+    #[async_trait]
+    impl Provider for LineProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "line".to_string(),
+                name: "Line".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let text = match req.messages.last().unwrap().content.first() {
+                Some(ContentPart::Text { text }) => text.clone(),
+                _ => String::new(),
+            };
+            let items: Vec<Value> = text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| match line.trim().split_once(" (") {
+                    Some((name, role)) => {
+                        json!({"name": name, "role": role.trim_end_matches(')')})
+                    }
+                    None => json!({"name": line.trim(), "role": null}),
+                })
+                .collect();
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(json!({ "items": items }).to_string()),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    total_tokens: 1,
+                    ..Default::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Person {
+        name: String,
+        role: Option<String>,
+    }
+
+    #[tokio::test]
+    async fn test_extract_merges_chunks() {
+        let executor = RuntimeExecutor::builder(LineProvider).finish();
+        let document = "Ada (mathematician)\nGrace\nAlan (cryptanalyst)\n\
+            Katherine (physicist)\nGrace (admiral)\nada\nAda (engineer)\n";
+        let options = ExtractOptions::new(json!({"type": "object"}))
+            .with_key_fields(["name"])
+            .with_chunk_tokens(8)
+            .with_overlap_tokens(4);
+
+        let extraction = executor
+            .extract::<Person>("m", document, "Extract people", &options)
+            .await
+            .unwrap();
+        assert!(extraction.chunks > 1);
+        assert_eq!(extraction.usage.total_tokens as usize, extraction.chunks);
+        let people: Vec<_> = extraction
+            .items
+            .iter()
+            .map(|p| (p.name.as_str(), p.role.as_deref()))
+            .collect();
+        assert_eq!(
+            people,
+            [
+                ("Ada", Some("mathematician")),
+                ("Grace", Some("admiral")),
+                ("Alan", Some("cryptanalyst")),
+                ("Katherine", Some("physicist")),
+            ]
+        );
+        assert_eq!(
+            extraction.conflicts,
+            [ExtractConflict {
+                key: r#"["ada"]"#.to_string(),
+                field: "/role".to_string(),
+                kept: json!("mathematician"),
+                discarded: json!("engineer"),
+            }]
+        );
+    }
+}
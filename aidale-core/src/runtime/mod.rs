@@ -10,6 +10,20 @@
 //! - Executing plugins in the request lifecycle
 //! - Managing layers (logging, retry, caching, etc.)
 
+pub mod batch;
+pub mod classify;
+pub mod defaults;
 pub mod executor;
+pub mod extract;
+pub mod revision;
+pub mod streamed;
+pub mod summarize;
 
-pub use executor::RuntimeExecutor;
+pub use batch::{BatchItem, BatchOutput, BatchRequest, BatchResult};
+pub use classify::{Classification, ClassifyOptions};
+pub use defaults::ParamDefaults;
+pub use executor::{ContentFilterPolicy, RuntimeExecutor};
+pub use extract::{ConflictPolicy, ExtractConflict, ExtractOptions, Extraction};
+pub use revision::{RevisedText, RevisionPolicy, RevisionStep, RevisionStepKind};
+pub use streamed::StreamedText;
+pub use summarize::{SummarizeOptions, SummarizeProgress, Summary};
@@ -0,0 +1,312 @@
+//! Critique-and-revise generation.
+//!
+//! [`RuntimeExecutor::generate_with_revision`] drafts an answer, asks the
+//! model to critique it and then to revise it according to the critique,
+//! for up to [`RevisionPolicy::with_max_rounds`] rounds. A critique that
+//! finds nothing to fix ends the loop early. Every call is kept in the
+//! returned trace:
+//!
+//! ```ignore
+//! let policy = RevisionPolicy::new()
+//!     .with_max_rounds(2)
+//!     .with_critique_model("gpt-4o");
+//! let revised = executor
+//!     .generate_with_revision("gpt-4o-mini", params, &policy)
+//!     .await?;
+//! for step in &revised.trace {
+//!     println!("{:?} #{}: {}", step.kind, step.round, step.result.content);
+//! }
+//! ```
+//!
+//! The critique and revision see the whole conversation, so system prompts
+//! and earlier turns apply to them as well.
+
+use crate::error::AiError;
+use crate::runtime::RuntimeExecutor;
+use crate::types::{Message, TextParams, TextResult, Usage};
+
+const CRITIQUE_PROMPT: &str = "Review your previous answer to the conversation above. Check it \
+for factual errors, unsupported claims, missing parts of the request and unclear wording. List \
+each problem with a short suggestion for fixing it. If there is nothing to fix, reply with \
+NO_ISSUES only.";
+
+const REVISE_PROMPT: &str = "Rewrite your previous answer, fixing every problem in the review. \
+Reply with the improved answer only, without mentioning the review.";
+
+/// Settings of [`RuntimeExecutor::generate_with_revision`]
+#[derive(Debug, Clone)]
+pub struct RevisionPolicy {
+    max_rounds: usize,
+    critique_model: Option<String>,
+    critique_prompt: String,
+    revise_prompt: String,
+    approval: Option<String>,
+}
+
+impl RevisionPolicy {
+    /// Create a policy with one critique-and-revise round
+    pub fn new() -> Self {
+        Self {
+            max_rounds: 1,
+            critique_model: None,
+            critique_prompt: CRITIQUE_PROMPT.to_string(),
+            revise_prompt: REVISE_PROMPT.to_string(),
+            approval: Some("NO_ISSUES".to_string()),
+        }
+    }
+
+    /// Maximum number of critique-and-revise rounds
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Critique with a different model, e.g. a stronger one
+    pub fn with_critique_model(mut self, model: impl Into<String>) -> Self {
+        self.critique_model = Some(model.into());
+        self
+    }
+
+    /// Prompt asking for a critique of the latest answer
+    pub fn with_critique_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.critique_prompt = prompt.into();
+        self
+    }
+
+    /// Prompt asking for a revision according to the critique
+    pub fn with_revise_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.revise_prompt = prompt.into();
+        self
+    }
+
+    /// Text a critique contains when there is nothing to fix (default:
+    /// `NO_ISSUES`); `None` always runs every round
+    pub fn with_approval(mut self, approval: Option<String>) -> Self {
+        self.approval = approval;
+        self
+    }
+
+    fn approves(&self, critique: &str) -> bool {
+        self.approval
+            .as_deref()
+            .is_some_and(|approval| critique.contains(approval))
+    }
+}
+
+impl Default for RevisionPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Kind of call in a revision trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevisionStepKind {
+    Draft,
+    Critique,
+    Revision,
+}
+
+/// One call of a revision workflow
+#[derive(Debug, Clone)]
+pub struct RevisionStep {
+    pub kind: RevisionStepKind,
+    /// 0 for the draft, 1 and up for critique-and-revise rounds
+    pub round: usize,
+    pub result: TextResult,
+}
+
+/// Result of [`RuntimeExecutor::generate_with_revision`]
+#[derive(Debug, Clone)]
+pub struct RevisedText {
+    /// The last draft or revision
+    pub result: TextResult,
+    /// Every call, in order
+    pub trace: Vec<RevisionStep>,
+    /// Whether the last critique found nothing to fix
+    pub approved: bool,
+    /// Usage of all calls
+    pub usage: Usage,
+}
+
+impl RevisedText {
+    /// Number of revisions made
+    pub fn revisions(&self) -> usize {
+        self.trace
+            .iter()
+            .filter(|step| step.kind == RevisionStepKind::Revision)
+            .count()
+    }
+
+    fn push(&mut self, kind: RevisionStepKind, round: usize, result: TextResult) {
+        self.usage.prompt_tokens += result.usage.prompt_tokens;
+        self.usage.completion_tokens += result.usage.completion_tokens;
+        self.usage.total_tokens += result.usage.total_tokens;
+        self.trace.push(RevisionStep {
+            kind,
+            round,
+            result,
+        });
+    }
+}
+
+impl RuntimeExecutor {
+    /// Generate text, then critique and revise it
+    ///
+    /// Every call goes through the plugins like [`Self::generate_text`].
+    /// Tools are only offered for the draft. Fails with the first error of
+    /// any call.
+    pub async fn generate_with_revision(
+        &self,
+        model: impl Into<String>,
+        params: TextParams,
+        policy: &RevisionPolicy,
+    ) -> Result<RevisedText, AiError> {
+        let model = model.into();
+        let critique_model = policy.critique_model.as_deref().unwrap_or(&model);
+
+        let draft = self.generate_text(model.as_str(), params.clone()).await?;
+        let mut revised = RevisedText {
+            result: draft.clone(),
+            trace: Vec::new(),
+            approved: false,
+            usage: Usage::default(),
+        };
+        revised.push(RevisionStepKind::Draft, 0, draft);
+
+        let mut followup = params;
+        followup.tools = None;
+        followup.tool_choice = None;
+        followup.parallel_tool_calls = None;
+        followup.n = None;
+
+        for round in 1..=policy.max_rounds {
+            let mut messages = followup.messages.clone();
+            messages.push(Message::assistant(revised.result.content.clone()));
+            messages.push(Message::user(policy.critique_prompt.clone()));
+
+            let mut critique_params = followup.clone();
+            critique_params.messages = messages.clone();
+            let critique = self.generate_text(critique_model, critique_params).await?;
+            revised.approved = policy.approves(&critique.content);
+            let critique_text = critique.content.clone();
+            revised.push(RevisionStepKind::Critique, round, critique);
+            if revised.approved {
+                break;
+            }
+
+            messages.push(Message::assistant(critique_text));
+            messages.push(Message::user(policy.revise_prompt.clone()));
+            let mut revise_params = followup.clone();
+            revise_params.messages = messages;
+            let revision = self.generate_text(model.as_str(), revise_params).await?;
+            revised.result = revision.clone();
+            revised.push(RevisionStepKind::Revision, round, revision);
+        }
+
+        Ok(revised)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{ChatCompletionStream, Provider};
+    use crate::types::*;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    /// Drafts "2 + 2 = 5", objects once, then approves
+    #[derive(Debug)]
+    struct Reviewer;
+
+    #[async_trait]
+    impl Provider for Reviewer {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "reviewer".to_string(),
+                name: "Reviewer".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let last = |role: Role| {
+                req.messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == role)
+                    .and_then(|m| match m.content.first() {
+                        Some(ContentPart::Text { text }) => Some(text.clone()),
+                        _ => None,
+                    })
+            };
+            let prompt = last(Role::User).unwrap_or_default();
+            let answer = last(Role::Assistant);
+            let reply = if prompt.starts_with("Review") {
+                match answer.as_deref() {
+                    Some("2 + 2 = 5") => "The sum is wrong.",
+                    _ => "NO_ISSUES",
+                }
+            } else if prompt.starts_with("Rewrite") {
+                "2 + 2 = 4"
+            } else {
+                "2 + 2 = 5"
+            };
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(reply),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    total_tokens: 1,
+                    ..Default::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_critique_and_revise() {
+        let executor = RuntimeExecutor::builder(Reviewer).finish();
+        let params = TextParams::new(vec![Message::user("What is 2 + 2?")]);
+        let policy = RevisionPolicy::new().with_max_rounds(3);
+
+        let revised = executor
+            .generate_with_revision("m", params, &policy)
+            .await
+            .unwrap();
+        assert_eq!(revised.result.content, "2 + 2 = 4");
+        assert!(revised.approved);
+        assert_eq!(revised.revisions(), 1);
+        let kinds: Vec<_> = revised.trace.iter().map(|s| (s.kind, s.round)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (RevisionStepKind::Draft, 0),
+                (RevisionStepKind::Critique, 1),
+                (RevisionStepKind::Revision, 1),
+                (RevisionStepKind::Critique, 2),
+            ]
+        );
+        assert_eq!(revised.usage.total_tokens, 4);
+    }
+}
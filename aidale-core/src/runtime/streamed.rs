@@ -0,0 +1,218 @@
+//! Streaming text result.
+//!
+//! [`StreamedText`] is returned by
+//! [`RuntimeExecutor::stream_text`](crate::RuntimeExecutor::stream_text). It
+//! is itself a stream of [`TextChunk`]s and aggregates them as they pass, so
+//! callers can forward deltas and still get the final [`TextResult`]:
+//!
+//! ```ignore
+//! let mut streamed = executor.stream_text("gpt-4o-mini", params).await?;
+//!
+//! let mut deltas = streamed.text_stream();
+//! while let Some(delta) = deltas.next().await {
+//!     print!("{}", delta?);
+//! }
+//! drop(deltas);
+//!
+//! let result = streamed.final_result().await?;
+//! println!("\n{} tokens", result.usage.total_tokens);
+//! ```
+//!
+//! [`StreamedText::events`] yields the same stream as typed [`StreamEvent`]s
+//! instead, with errors as a final [`StreamEvent::Error`].
+
+use crate::error::AiError;
+use crate::provider::TextStream;
+use crate::types::{ContentPart, FinishReason, StreamEvent, TextChunk, TextResult, Usage};
+use futures::stream::{Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Text stream that also collects the final result
+pub struct StreamedText {
+    inner: Box<TextStream>,
+    model: String,
+    content: String,
+    reasoning: Option<String>,
+    tool_calls: Vec<ContentPart>,
+    finish_reason: Option<FinishReason>,
+    usage: Option<Usage>,
+}
+
+impl std::fmt::Debug for StreamedText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamedText")
+            .field("model", &self.model)
+            .field("content_len", &self.content.len())
+            .field("finish_reason", &self.finish_reason)
+            .finish()
+    }
+}
+
+impl StreamedText {
+    /// Wrap a text stream for the given model
+    pub fn new(inner: Box<TextStream>, model: impl Into<String>) -> Self {
+        Self {
+            inner,
+            model: model.into(),
+            content: String::new(),
+            reasoning: None,
+            tool_calls: Vec::new(),
+            finish_reason: None,
+            usage: None,
+        }
+    }
+
+    /// Stream of the non-empty text deltas
+    ///
+    /// Chunks consumed through this stream still count toward
+    /// [`Self::final_result`].
+    pub fn text_stream(&mut self) -> impl Stream<Item = Result<String, AiError>> + Send + '_ {
+        self.filter_map(|chunk| {
+            futures::future::ready(match chunk {
+                Ok(chunk) if chunk.delta.is_empty() => None,
+                Ok(chunk) => Some(Ok(chunk.delta)),
+                Err(err) => Some(Err(err)),
+            })
+        })
+    }
+
+    /// Stream of typed events
+    ///
+    /// Events consumed through this stream still count toward
+    /// [`Self::final_result`].
+    pub fn events(&mut self) -> impl Stream<Item = StreamEvent> + Send + '_ {
+        self.flat_map(|chunk| {
+            futures::stream::iter(match chunk {
+                Ok(chunk) => chunk.into_events(),
+                Err(err) => vec![StreamEvent::Error(err)],
+            })
+        })
+    }
+
+    /// Text received so far
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Drive the stream to completion and return the aggregated result
+    pub async fn final_result(mut self) -> Result<TextResult, AiError> {
+        while let Some(chunk) = self.next().await {
+            chunk?;
+        }
+
+        Ok(TextResult {
+            content: self.content,
+            finish_reason: self.finish_reason.unwrap_or(FinishReason::Stop),
+            usage: self.usage.unwrap_or_default(),
+            model: self.model,
+            tool_calls: (!self.tool_calls.is_empty()).then_some(self.tool_calls),
+            reasoning: self.reasoning,
+            citations: None,
+            metadata: std::collections::HashMap::new(),
+        })
+    }
+
+    /// Unwrap the underlying chunk stream, dropping the aggregation
+    pub fn into_inner(self) -> Box<TextStream> {
+        self.inner
+    }
+
+    fn record(&mut self, chunk: &TextChunk) {
+        self.content.push_str(&chunk.delta);
+        if let Some(delta) = &chunk.reasoning {
+            self.reasoning
+                .get_or_insert_with(String::new)
+                .push_str(delta);
+        }
+        if let Some(calls) = &chunk.tool_calls {
+            self.tool_calls.extend(calls.iter().cloned());
+        }
+        if let Some(reason) = &chunk.finish_reason {
+            self.finish_reason = Some(reason.clone());
+        }
+        if let Some(usage) = &chunk.usage {
+            self.usage = Some(usage.clone());
+        }
+    }
+}
+
+impl Stream for StreamedText {
+    type Item = Result<TextChunk, AiError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            self.record(chunk);
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(delta: &str, finish_reason: Option<FinishReason>) -> Result<TextChunk, AiError> {
+        Ok(TextChunk {
+            delta: delta.to_string(),
+            reasoning: None,
+            tool_calls: None,
+            finish_reason,
+            usage: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_partial_consumption_then_final_result() {
+        let inner = futures::stream::iter(vec![
+            chunk("Hello", None),
+            chunk("", None),
+            chunk(", world", Some(FinishReason::Length)),
+        ]);
+        let mut streamed = StreamedText::new(Box::new(inner), "test-model");
+
+        let first = streamed.text_stream().next().await.unwrap().unwrap();
+        assert_eq!(first, "Hello");
+
+        let result = streamed.final_result().await.unwrap();
+        assert_eq!(result.content, "Hello, world");
+        assert_eq!(result.finish_reason, FinishReason::Length);
+        assert_eq!(result.model, "test-model");
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let call = TextChunk {
+            tool_calls: Some(vec![ContentPart::ToolCall {
+                id: "call_1".to_string(),
+                name: "lookup".to_string(),
+                arguments: serde_json::json!({"q": "rust"}),
+            }]),
+            ..chunk("", Some(FinishReason::ToolCalls)).unwrap()
+        };
+        let inner = futures::stream::iter(vec![
+            chunk("Let me check", None),
+            Ok(call),
+            Err(AiError::stream("connection reset")),
+        ]);
+        let mut streamed = StreamedText::new(Box::new(inner), "test-model");
+
+        let events: Vec<_> = streamed.events().collect().await;
+        assert!(matches!(&events[0], StreamEvent::TextDelta(text) if text == "Let me check"));
+        assert!(matches!(&events[1], StreamEvent::ToolCallStart { name, .. } if name == "lookup"));
+        assert!(
+            matches!(&events[2], StreamEvent::ToolCallDelta { arguments, .. } if arguments == r#"{"q":"rust"}"#)
+        );
+        assert!(matches!(&events[3], StreamEvent::ToolCallEnd { id, .. } if id == "call_1"));
+        assert!(matches!(
+            &events[4],
+            StreamEvent::FinishReason(FinishReason::ToolCalls)
+        ));
+        assert!(matches!(&events[5], StreamEvent::Error(_)));
+        assert_eq!(events.len(), 6);
+
+        let result = streamed.final_result().await.unwrap();
+        assert_eq!(result.tool_calls.map(|calls| calls.len()), Some(1));
+    }
+}
@@ -0,0 +1,382 @@
+//! Map-reduce summarization of long texts.
+//!
+//! [`RuntimeExecutor::summarize_long_text`] summarizes texts that do not fit
+//! a model's context: the text is split into token-sized chunks, the chunks
+//! are summarized concurrently (map), and the chunk summaries are combined
+//! group by group (reduce) until a single summary under the target length
+//! remains:
+//!
+//! ```ignore
+//! let options = SummarizeOptions::new()
+//!     .with_chunk_tokens(4000)
+//!     .with_target_tokens(300)
+//!     .with_progress(|progress| {
+//!         println!("round {}: {}/{}", progress.round, progress.completed, progress.total)
+//!     });
+//! let summary = executor.summarize_long_text("gpt-4o-mini", &book, &options).await?;
+//! ```
+
+use crate::error::AiError;
+use crate::ingestion::{TextSplitter, TokenTextSplitter};
+use crate::runtime::{BatchOutput, BatchRequest, BatchResult, RuntimeExecutor};
+use crate::tokenizer::{HeuristicTokenCounter, TokenCounter};
+use crate::types::{Message, TextParams, Usage};
+use futures::StreamExt;
+use std::sync::Arc;
+
+const MAP_PROMPT: &str = "Summarize the following text. Keep the key facts, names, numbers and \
+conclusions. Reply with the summary only.";
+
+const REDUCE_PROMPT: &str = "The following are summaries of consecutive parts of one text. \
+Combine them into a single coherent summary, keeping the key facts, names, numbers and \
+conclusions. Reply with the summary only.";
+
+/// Progress callback
+type ProgressFn = dyn Fn(&SummarizeProgress) + Send + Sync;
+
+/// Progress of a summarization, reported after every completed call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummarizeProgress {
+    /// 0 for the map round over the chunks, 1 and up for reduce rounds
+    pub round: usize,
+    /// Calls of this round completed so far
+    pub completed: usize,
+    /// Calls in this round
+    pub total: usize,
+}
+
+/// Settings of [`RuntimeExecutor::summarize_long_text`]
+#[derive(Clone)]
+pub struct SummarizeOptions {
+    chunk_tokens: usize,
+    target_tokens: usize,
+    concurrency: usize,
+    max_rounds: usize,
+    counter: Arc<dyn TokenCounter>,
+    map_prompt: String,
+    reduce_prompt: String,
+    params: TextParams,
+    progress: Option<Arc<ProgressFn>>,
+}
+
+impl SummarizeOptions {
+    /// Create options with 3000-token chunks, a 500-token target, 4
+    /// concurrent calls and at most 5 reduce rounds
+    pub fn new() -> Self {
+        Self {
+            chunk_tokens: 3000,
+            target_tokens: 500,
+            concurrency: 4,
+            max_rounds: 5,
+            counter: Arc::new(HeuristicTokenCounter::new()),
+            map_prompt: MAP_PROMPT.to_string(),
+            reduce_prompt: REDUCE_PROMPT.to_string(),
+            params: TextParams::new(Vec::new()),
+            progress: None,
+        }
+    }
+
+    /// Tokens per chunk, and per group of summaries combined in one call
+    pub fn with_chunk_tokens(mut self, chunk_tokens: usize) -> Self {
+        self.chunk_tokens = chunk_tokens.max(1);
+        self
+    }
+
+    /// Length the final summary should stay under; also the `max_tokens` of
+    /// every call
+    pub fn with_target_tokens(mut self, target_tokens: usize) -> Self {
+        self.target_tokens = target_tokens.max(1);
+        self
+    }
+
+    /// Maximum number of calls in flight
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Maximum number of reduce rounds; the summaries left after the last
+    /// one are joined
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Token counter used to split the text and measure summaries
+    pub fn with_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// System prompt for summarizing a chunk
+    pub fn with_map_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.map_prompt = prompt.into();
+        self
+    }
+
+    /// System prompt for combining summaries
+    pub fn with_reduce_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.reduce_prompt = prompt.into();
+        self
+    }
+
+    /// Generation parameters for every call; their messages are replaced
+    pub fn with_params(mut self, params: TextParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Call a function after every completed call
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&SummarizeProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    fn request(&self, model: &str, prompt: &str, text: String) -> BatchRequest {
+        let mut params = self.params.clone();
+        params.messages = vec![Message::system(prompt), Message::user(text)];
+        params.max_tokens = Some(u32::try_from(self.target_tokens).unwrap_or(u32::MAX));
+        BatchRequest::text(model, params)
+    }
+
+    /// Pack consecutive summaries into groups of up to `chunk_tokens`
+    fn group(&self, summaries: Vec<String>) -> Vec<String> {
+        let mut groups: Vec<(String, usize)> = Vec::new();
+        for summary in summaries {
+            let tokens = self.counter.count_tokens(&summary);
+            match groups.last_mut() {
+                Some((group, total)) if *total + tokens <= self.chunk_tokens => {
+                    group.push_str("\n\n");
+                    group.push_str(&summary);
+                    *total += tokens;
+                }
+                _ => groups.push((summary, tokens)),
+            }
+        }
+        groups.into_iter().map(|(group, _)| group).collect()
+    }
+}
+
+impl Default for SummarizeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for SummarizeOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SummarizeOptions")
+            .field("chunk_tokens", &self.chunk_tokens)
+            .field("target_tokens", &self.target_tokens)
+            .field("concurrency", &self.concurrency)
+            .field("max_rounds", &self.max_rounds)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Result of a summarization
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub text: String,
+    /// Number of chunks the text was split into
+    pub chunks: usize,
+    /// Number of reduce rounds
+    pub rounds: usize,
+    /// Usage of all calls
+    pub usage: Usage,
+}
+
+impl RuntimeExecutor {
+    /// Summarize a text of any length with map-reduce
+    ///
+    /// A text that fits in one chunk is summarized in a single
+    /// call. Fails with the first error of any call.
+    pub async fn summarize_long_text(
+        &self,
+        model: &str,
+        text: &str,
+        options: &SummarizeOptions,
+    ) -> Result<Summary, AiError> {
+        let splitter = TokenTextSplitter::new(options.counter.clone(), options.chunk_tokens, 0);
+        let chunks = splitter.split(text);
+        let mut usage = Usage::default();
+
+        let requests = chunks
+            .iter()
+            .map(|chunk| options.request(model, &options.map_prompt, chunk.clone()));
+        let mut summaries = self
+            .summarize_round(requests, 0, options, &mut usage)
+            .await?;
+
+        let mut rounds = 0;
+        while rounds < options.max_rounds && !is_done(&summaries, options) {
+            rounds += 1;
+            let requests = options
+                .group(summaries)
+                .into_iter()
+                .map(|group| options.request(model, &options.reduce_prompt, group));
+            summaries = self
+                .summarize_round(requests, rounds, options, &mut usage)
+                .await?;
+        }
+
+        Ok(Summary {
+            text: summaries.join("\n\n"),
+            chunks: chunks.len(),
+            rounds,
+            usage,
+        })
+    }
+
+    /// Run one round of calls, returning the summaries in order
+    async fn summarize_round(
+        &self,
+        requests: impl Iterator<Item = BatchRequest>,
+        round: usize,
+        options: &SummarizeOptions,
+        usage: &mut Usage,
+    ) -> Result<Vec<String>, AiError> {
+        let requests: Vec<_> = requests.collect();
+        let total = requests.len();
+        let mut items = Vec::with_capacity(total);
+        let mut stream = self.stream_many(requests, options.concurrency);
+        while let Some(item) = stream.next().await {
+            items.push(item);
+            if let Some(progress) = &options.progress {
+                progress(&SummarizeProgress {
+                    round,
+                    completed: items.len(),
+                    total,
+                });
+            }
+        }
+
+        let batch = BatchResult::from_items(items);
+        usage.prompt_tokens += batch.usage.prompt_tokens;
+        usage.completion_tokens += batch.usage.completion_tokens;
+        usage.total_tokens += batch.usage.total_tokens;
+        batch
+            .items
+            .into_iter()
+            .map(|item| match item.result? {
+                BatchOutput::Text(result) => Ok(result.content.trim().to_string()),
+                BatchOutput::Object(result) => Ok(result.object.to_string()),
+            })
+            .collect()
+    }
+}
+
+/// Whether a single summary under the target length is left
+fn is_done(summaries: &[String], options: &SummarizeOptions) -> bool {
+    match summaries {
+        [] => true,
+        [summary] => options.counter.count_tokens(summary) <= options.target_tokens,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{ChatCompletionStream, Provider};
+    use crate::types::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// "Summarizes" by keeping the first three words of every paragraph
+    #[derive(Debug)]
+    struct TruncatingProvider;
+
+    #[async_trait]
+    impl Provider for TruncatingProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "truncating".to_string(),
+                name: "Truncating".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let text = match req.messages[1].content.first() {
+                Some(ContentPart::Text { text }) => text.clone(),
+                _ => String::new(),
+            };
+            let summary: Vec<_> = text
+                .split("\n\n")
+                .map(|paragraph| {
+                    let words: Vec<_> = paragraph.split_whitespace().take(3).collect();
+                    words.join(" ")
+                })
+                .collect();
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(summary.join(" ")),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    total_tokens: 1,
+                    ..Default::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_map_reduce() {
+        let executor = RuntimeExecutor::builder(TruncatingProvider).finish();
+        let text = (0..40)
+            .map(|i| {
+                format!(
+                    "Paragraph {} talks about many interesting things at length.",
+                    i
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let seen = progress.clone();
+        let options = SummarizeOptions::new()
+            .with_chunk_tokens(40)
+            .with_target_tokens(10)
+            .with_progress(move |p| seen.lock().unwrap().push(p.clone()));
+
+        let summary = executor
+            .summarize_long_text("m", &text, &options)
+            .await
+            .unwrap();
+        assert!(summary.chunks > 1);
+        assert!(summary.rounds >= 2);
+        assert_eq!(summary.text, "Paragraph 0 talks");
+
+        let progress = progress.lock().unwrap();
+        assert_eq!(progress.len() as u32, summary.usage.total_tokens);
+        let last = progress.last().unwrap();
+        assert_eq!(
+            (last.round, last.completed, last.total),
+            (summary.rounds, 1, 1)
+        );
+    }
+}
@@ -149,6 +149,7 @@ impl JsonOutputStrategy for JsonModeStrategy {
                 role: Role::System,
                 content: vec![ContentPart::Text { text: instruction }],
                 name: None,
+                cache_control: None,
             };
             req.messages.insert(0, system_msg);
         } else {
@@ -163,6 +164,7 @@ impl JsonOutputStrategy for JsonModeStrategy {
                     role: Role::User,
                     content: vec![ContentPart::Text { text: instruction }],
                     name: None,
+                    cache_control: None,
                 };
                 req.messages.push(user_msg);
             }
@@ -179,10 +181,11 @@ impl JsonOutputStrategy for JsonModeStrategy {
 pub fn detect_json_strategy(provider_id: &str) -> Box<dyn JsonOutputStrategy> {
     match provider_id {
         // Providers that support JSON Schema
-        "openai" | "anthropic" | "azure" => Box::new(JsonSchemaStrategy::new()),
+        "openai" | "anthropic" | "azure" | "cohere" | "xai" | "groq" | "fireworks"
+        | "openrouter" => Box::new(JsonSchemaStrategy::new()),
 
         // Providers that only support basic JSON mode
-        "deepseek" => Box::new(JsonModeStrategy::new()),
+        "deepseek" | "together" => Box::new(JsonModeStrategy::new()),
 
         // Default to JSON Mode for unknown providers (safer fallback)
         _ => Box::new(JsonModeStrategy::new()),
@@ -245,13 +248,32 @@ mod tests {
 
     #[test]
     fn test_detect_json_strategy() {
-        // OpenAI should get JsonSchemaStrategy
-        let openai_strategy = detect_json_strategy("openai");
-        assert_eq!(openai_strategy.name(), "JsonSchemaStrategy");
+        // Providers with structured outputs get JsonSchemaStrategy
+        for provider_id in [
+            "openai",
+            "anthropic",
+            "azure",
+            "cohere",
+            "xai",
+            "groq",
+            "fireworks",
+            "openrouter",
+        ] {
+            assert_eq!(
+                detect_json_strategy(provider_id).name(),
+                "JsonSchemaStrategy",
+                "{provider_id}"
+            );
+        }
 
-        // DeepSeek should get JsonModeStrategy
-        let deepseek_strategy = detect_json_strategy("deepseek");
-        assert_eq!(deepseek_strategy.name(), "JsonModeStrategy");
+        // JSON mode only
+        for provider_id in ["deepseek", "together"] {
+            assert_eq!(
+                detect_json_strategy(provider_id).name(),
+                "JsonModeStrategy",
+                "{provider_id}"
+            );
+        }
 
         // Unknown providers should get JsonModeStrategy (safer fallback)
         let unknown_strategy = detect_json_strategy("unknown");
@@ -0,0 +1,87 @@
+//! Token counting utilities.
+//!
+//! Exact token counts depend on each model's tokenizer. The [`TokenCounter`]
+//! trait lets callers plug in an exact implementation, while
+//! [`HeuristicTokenCounter`] provides a dependency-free estimate.
+
+use crate::types::{ContentPart, Message};
+use std::fmt::Debug;
+
+/// Tokens added per message for role and formatting markers
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Tokens added per conversation to prime the reply
+const REPLY_OVERHEAD: usize = 3;
+
+/// Trait for counting tokens in text and messages.
+pub trait TokenCounter: Send + Sync + Debug + 'static {
+    /// Count tokens in a piece of text
+    fn count_tokens(&self, text: &str) -> usize;
+
+    /// Count tokens in a single message, including formatting overhead
+    fn count_message_tokens(&self, message: &Message) -> usize {
+        let content: usize = message
+            .content
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text { text } => self.count_tokens(text),
+                ContentPart::ToolCall {
+                    name, arguments, ..
+                } => self.count_tokens(name) + self.count_tokens(&arguments.to_string()),
+                ContentPart::ToolResult { result, .. } => self.count_tokens(&result.to_string()),
+                _ => 0,
+            })
+            .sum();
+        let name = message
+            .name
+            .as_deref()
+            .map_or(0, |name| self.count_tokens(name));
+
+        MESSAGE_OVERHEAD + content + name
+    }
+
+    /// Count tokens in a conversation
+    fn count_messages(&self, messages: &[Message]) -> usize {
+        messages
+            .iter()
+            .map(|msg| self.count_message_tokens(msg))
+            .sum::<usize>()
+            + REPLY_OVERHEAD
+    }
+}
+
+/// Estimates tokens without a vocabulary.
+///
+/// Counts roughly four characters per token for Latin text and one token per
+/// CJK character, which errs on the side of overestimating.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenCounter;
+
+impl HeuristicTokenCounter {
+    /// Create a new heuristic counter
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn is_wide(c: char) -> bool {
+        matches!(c as u32,
+            0x3040..=0x30FF     // Hiragana, Katakana
+            | 0x3400..=0x4DBF   // CJK Extension A
+            | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+            | 0xAC00..=0xD7AF   // Hangul
+            | 0xF900..=0xFAFF) // CJK Compatibility Ideographs
+    }
+}
+
+impl TokenCounter for HeuristicTokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        let (wide, narrow): (usize, usize) = text.chars().fold((0, 0), |(wide, narrow), c| {
+            if Self::is_wide(c) {
+                (wide + 1, narrow)
+            } else {
+                (wide, narrow + 1)
+            }
+        });
+        wide + narrow.div_ceil(4)
+    }
+}
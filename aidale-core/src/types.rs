@@ -2,7 +2,13 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+use crate::budget::{Deadline, TokenBudget};
+use crate::error::AiError;
+use crate::events::EventBus;
+use crate::extensions::Extensions;
+use crate::few_shot::Example;
 
 /// Message role
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -24,6 +30,21 @@ pub enum ContentPart {
     Image {
         url: String,
     },
+    /// Inline image, base64 encoded
+    ImageData {
+        mime_type: String,
+        data: String,
+    },
+    /// Document attachment such as a PDF, base64 encoded
+    File {
+        name: String,
+        mime: String,
+        data: String,
+    },
+    /// Reference to a file uploaded to the provider, e.g. an OpenAI file ID
+    FileRef {
+        id: String,
+    },
     ToolCall {
         id: String,
         name: String,
@@ -33,6 +54,151 @@ pub enum ContentPart {
         id: String,
         result: serde_json::Value,
     },
+    Citation(Citation),
+    /// Thinking output of reasoning models (e.g. DeepSeek R1's
+    /// `reasoning_content`), kept apart from the answer text
+    Reasoning {
+        text: String,
+    },
+}
+
+impl ContentPart {
+    /// Create an inline image part from raw image bytes
+    pub fn image_data(mime_type: impl Into<String>, bytes: &[u8]) -> Self {
+        use base64::Engine;
+        Self::ImageData {
+            mime_type: mime_type.into(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    /// Create a document attachment part from raw file bytes
+    pub fn file(name: impl Into<String>, mime: impl Into<String>, bytes: &[u8]) -> Self {
+        use base64::Engine;
+        Self::File {
+            name: name.into(),
+            mime: mime.into(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    /// URL of an image part, as a `data:` URL for inline images
+    pub fn image_url(&self) -> Option<String> {
+        match self {
+            Self::Image { url } => Some(url.clone()),
+            Self::ImageData { mime_type, data } => {
+                Some(format!("data:{};base64,{}", mime_type, data))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reasoning effort for reasoning models (o1/o3 family and similar)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+/// Citation linking a span of generated text to its sources
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    /// Character offset where the cited span starts
+    pub start: usize,
+    /// Character offset where the cited span ends (exclusive)
+    pub end: usize,
+    /// The cited span of generated text
+    pub text: String,
+    /// IDs of the documents supporting this span
+    pub sources: Vec<String>,
+    /// How strongly the sources support the span, from 0 to 1, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+impl Citation {
+    /// Citations for numbered markers such as `[1]` in generated text
+    ///
+    /// A marker cites the text since the previous sentence end or marker;
+    /// adjacent markers like `[1][2]` cite the same span. `source` resolves a
+    /// marker number to a source ID and confidence; markers it doesn't
+    /// resolve are ignored.
+    pub fn from_markers(
+        text: &str,
+        mut source: impl FnMut(usize) -> Option<(String, Option<f32>)>,
+    ) -> Vec<Citation> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut citations: Vec<Citation> = Vec::new();
+        let mut span_start = 0;
+        // Whether only whitespace follows the last cited marker
+        let mut after_marker = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let digits = chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            let close = i + 1 + digits;
+            if chars[i] != '[' || digits == 0 || chars.get(close) != Some(&']') {
+                // Sentence ends, but not decimal points as in `1.5`
+                let sentence_end = matches!(chars[i], '.' | '!' | '?')
+                    && chars.get(i + 1).map_or(true, |c| c.is_whitespace());
+                if sentence_end || chars[i] == '\n' {
+                    span_start = i + 1;
+                }
+                if !chars[i].is_whitespace() {
+                    after_marker = false;
+                }
+                i += 1;
+                continue;
+            }
+
+            let number: String = chars[i + 1..close].iter().collect();
+            let resolved = number.parse().ok().and_then(&mut source);
+            after_marker = match (resolved, citations.last_mut()) {
+                (Some((id, confidence)), Some(citation)) if after_marker => {
+                    if !citation.sources.contains(&id) {
+                        citation.sources.push(id);
+                    }
+                    citation.confidence = match (citation.confidence, confidence) {
+                        (Some(a), Some(b)) => Some(a.max(b)),
+                        (a, b) => a.or(b),
+                    };
+                    true
+                }
+                (Some((id, confidence)), _) => {
+                    let start = span_start
+                        + chars[span_start..i]
+                            .iter()
+                            .take_while(|c| c.is_whitespace())
+                            .count();
+                    let end = i - chars[start..i]
+                        .iter()
+                        .rev()
+                        .take_while(|c| c.is_whitespace())
+                        .count();
+                    if end > start {
+                        citations.push(Citation {
+                            start,
+                            end,
+                            text: chars[start..end].iter().collect(),
+                            sources: vec![id],
+                            confidence,
+                        });
+                    }
+                    end > start
+                }
+                (None, _) => false,
+            };
+            i = close + 1;
+            span_start = i;
+        }
+        citations
+    }
 }
 
 /// Message in a conversation
@@ -42,6 +208,35 @@ pub struct Message {
     pub content: Vec<ContentPart>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Prompt caching breakpoint: cache everything up to and including this
+    /// message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// Prompt caching annotation, serialized as Anthropic's `cache_control`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CacheControl {
+    Ephemeral {
+        /// Cache lifetime such as `5m` or `1h` (provider default if unset)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ttl: Option<String>,
+    },
+}
+
+impl CacheControl {
+    /// Ephemeral cache entry with the provider's default lifetime
+    pub fn ephemeral() -> Self {
+        Self::Ephemeral { ttl: None }
+    }
+
+    /// Ephemeral cache entry with an explicit lifetime (e.g. `1h`)
+    pub fn ephemeral_with_ttl(ttl: impl Into<String>) -> Self {
+        Self::Ephemeral {
+            ttl: Some(ttl.into()),
+        }
+    }
 }
 
 impl Message {
@@ -51,15 +246,27 @@ impl Message {
             role: Role::User,
             content: vec![ContentPart::Text { text: text.into() }],
             name: None,
+            cache_control: None,
         }
     }
 
+    /// Create a new user message with an image read from a file
+    ///
+    /// The image is downscaled to the default [`ImageLimits`] when the
+    /// `image` feature is enabled.
+    ///
+    /// [`ImageLimits`]: crate::image::ImageLimits
+    pub fn user_with_image_file(path: impl AsRef<std::path::Path>) -> Result<Self, AiError> {
+        Ok(Self::builder(Role::User).image_file(path)?.build())
+    }
+
     /// Create a new assistant message with text
     pub fn assistant(text: impl Into<String>) -> Self {
         Self {
             role: Role::Assistant,
             content: vec![ContentPart::Text { text: text.into() }],
             name: None,
+            cache_control: None,
         }
     }
 
@@ -69,14 +276,42 @@ impl Message {
             role: Role::System,
             content: vec![ContentPart::Text { text: text.into() }],
             name: None,
+            cache_control: None,
         }
     }
 
+    /// Create a new tool message carrying a tool result
+    pub fn tool_result(id: impl Into<String>, result: serde_json::Value) -> Self {
+        Self {
+            role: Role::Tool,
+            content: vec![ContentPart::ToolResult {
+                id: id.into(),
+                result,
+            }],
+            name: None,
+            cache_control: None,
+        }
+    }
+
+    /// Create a builder for a multi-part message with the given role
+    pub fn builder(role: Role) -> crate::message::MessageBuilder {
+        crate::message::MessageBuilder::new(role)
+    }
+
     /// Set the message name
     pub fn with_name(mut self, name: impl Into<String>) -> Self {
         self.name = Some(name.into());
         self
     }
+
+    /// Mark this message as a prompt caching breakpoint.
+    ///
+    /// Put it on the last message of a long, stable prefix (system prompt,
+    /// reference documents) so later requests reuse the cached prefix.
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
 }
 
 /// Tool definition
@@ -85,6 +320,55 @@ pub struct Tool {
     pub name: String,
     pub description: String,
     pub parameters: serde_json::Value,
+    /// Ask the provider to validate arguments against `parameters`
+    ///
+    /// OpenAI requires strict schemas to list every property as required
+    /// and to set `additionalProperties: false`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict: bool,
+}
+
+impl Tool {
+    /// Create a non-strict tool definition
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: serde_json::Value,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters,
+            strict: false,
+        }
+    }
+
+    /// Enable or disable strict argument validation
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+/// Whether and which tools the model may call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides (the provider default when tools are given)
+    Auto,
+    /// Never call tools, answer with a message
+    None,
+    /// Call at least one tool
+    Required,
+    /// Call the tool with this name, e.g. to get structured output
+    Named(String),
+}
+
+impl ToolChoice {
+    /// Force a call to the named tool
+    pub fn named(name: impl Into<String>) -> Self {
+        Self::Named(name.into())
+    }
 }
 
 /// Text generation parameters
@@ -93,6 +377,10 @@ pub struct TextParams {
     /// Messages in the conversation
     pub messages: Vec<Message>,
 
+    /// Few-shot examples, inserted into the conversation by the executor
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<Example>,
+
     /// Maximum number of tokens to generate
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
@@ -121,6 +409,46 @@ pub struct TextParams {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
 
+    /// Whether and which tools the model may call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// Whether the model may call several tools in one turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+
+    /// Reasoning effort for reasoning models
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+
+    /// Seed for best-effort deterministic sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+
+    /// Number of completion candidates to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u8>,
+
+    /// Whether to return log probabilities of the output tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+
+    /// Number of most likely alternatives to return per token (requires `logprobs`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+
+    /// Token ID to bias (-100 to 100) map
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<HashMap<String, i32>>,
+
+    /// End-user identifier for abuse monitoring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    /// Extra HTTP headers to send with this request
+    #[serde(skip)]
+    pub headers: HashMap<String, String>,
+
     /// Additional provider-specific parameters
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
@@ -131,6 +459,7 @@ impl TextParams {
     pub fn new(messages: Vec<Message>) -> Self {
         Self {
             messages,
+            examples: Vec::new(),
             max_tokens: None,
             temperature: None,
             top_p: None,
@@ -138,10 +467,32 @@ impl TextParams {
             presence_penalty: None,
             stop: None,
             tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+            reasoning_effort: None,
+            seed: None,
+            n: None,
+            logprobs: None,
+            top_logprobs: None,
+            logit_bias: None,
+            user: None,
+            headers: HashMap::new(),
             extra: HashMap::new(),
         }
     }
 
+    /// Add a few-shot example of an input and the expected output
+    pub fn with_example(mut self, input: impl Into<String>, output: impl Into<String>) -> Self {
+        self.examples.push(Example::new(input, output));
+        self
+    }
+
+    /// Add few-shot examples
+    pub fn with_examples(mut self, examples: impl IntoIterator<Item = Example>) -> Self {
+        self.examples.extend(examples);
+        self
+    }
+
     /// Set max tokens
     pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
         self.max_tokens = Some(max_tokens);
@@ -159,6 +510,72 @@ impl TextParams {
         self.tools = Some(tools);
         self
     }
+
+    /// Set whether and which tools the model may call
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Allow or forbid several tool calls in one turn, e.g. for stateful tools
+    pub fn with_parallel_tool_calls(mut self, parallel: bool) -> Self {
+        self.parallel_tool_calls = Some(parallel);
+        self
+    }
+
+    /// Set the reasoning effort
+    pub fn with_reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Set the sampling seed
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set the number of completion candidates
+    ///
+    /// Use [`RuntimeExecutor::generate_texts`](crate::RuntimeExecutor::generate_texts)
+    /// to receive all of them.
+    pub fn with_n(mut self, n: u8) -> Self {
+        self.n = Some(n);
+        self
+    }
+
+    /// Request log probabilities, with `top` alternatives per token
+    ///
+    /// They are returned in the result's `metadata["logprobs"]`.
+    pub fn with_logprobs(mut self, top: u8) -> Self {
+        self.logprobs = Some(true);
+        self.top_logprobs = (top > 0).then_some(top);
+        self
+    }
+
+    /// Set logit bias
+    pub fn with_logit_bias(mut self, logit_bias: HashMap<String, i32>) -> Self {
+        self.logit_bias = Some(logit_bias);
+        self
+    }
+
+    /// Set the end-user identifier
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Add an HTTP header to send with this request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set an additional provider-specific parameter
+    pub fn with_extra(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.extra.insert(key.into(), value);
+        self
+    }
 }
 
 /// Text request with provider info
@@ -179,11 +596,70 @@ impl TextRequest {
 }
 
 /// Usage statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    /// Breakdown of the prompt tokens, for providers that report one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    /// Breakdown of the completion tokens, for providers that report one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+impl Usage {
+    /// Prompt tokens served from the provider's prompt cache
+    pub fn cached_tokens(&self) -> u32 {
+        self.prompt_tokens_details
+            .as_ref()
+            .and_then(|details| details.cached_tokens)
+            .unwrap_or(0)
+    }
+
+    /// Completion tokens spent on hidden reasoning
+    pub fn reasoning_tokens(&self) -> u32 {
+        self.completion_tokens_details
+            .as_ref()
+            .and_then(|details| details.reasoning_tokens)
+            .unwrap_or(0)
+    }
+
+    /// Audio tokens in the prompt and completion combined
+    pub fn audio_tokens(&self) -> u32 {
+        let prompt = self
+            .prompt_tokens_details
+            .as_ref()
+            .and_then(|details| details.audio_tokens);
+        let completion = self
+            .completion_tokens_details
+            .as_ref()
+            .and_then(|details| details.audio_tokens);
+        prompt.unwrap_or(0) + completion.unwrap_or(0)
+    }
+}
+
+/// Breakdown of prompt tokens
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptTokensDetails {
+    /// Tokens read from the prompt cache (usually billed at a discount)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u32>,
+    /// Audio input tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_tokens: Option<u32>,
+}
+
+/// Breakdown of completion tokens
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionTokensDetails {
+    /// Tokens spent on reasoning that is not part of the visible answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u32>,
+    /// Audio output tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_tokens: Option<u32>,
 }
 
 /// Finish reason
@@ -206,18 +682,108 @@ pub struct TextResult {
     pub model: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ContentPart>>,
+    /// Thinking output of reasoning models, when the provider returns it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// Sources of spans of `content`, from providers with native citations
+    /// or from retrieval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<Vec<Citation>>,
+    /// Annotations added by plugins and the runtime
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 /// Streaming text chunk
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextChunk {
     pub delta: String,
+    /// Incremental thinking output of reasoning models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// Completed tool calls ([`ContentPart::ToolCall`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ContentPart>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub finish_reason: Option<FinishReason>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<Usage>,
 }
 
+impl TextChunk {
+    /// Split the chunk into typed events, in stream order
+    pub fn into_events(self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        if let Some(reasoning) = self.reasoning.filter(|r| !r.is_empty()) {
+            events.push(StreamEvent::ReasoningDelta(reasoning));
+        }
+        if !self.delta.is_empty() {
+            events.push(StreamEvent::TextDelta(self.delta));
+        }
+        for part in self.tool_calls.into_iter().flatten() {
+            if let ContentPart::ToolCall {
+                id,
+                name,
+                arguments,
+            } = part
+            {
+                events.push(StreamEvent::ToolCallStart {
+                    id: id.clone(),
+                    name: name.clone(),
+                });
+                events.push(StreamEvent::ToolCallDelta {
+                    id: id.clone(),
+                    arguments: arguments.to_string(),
+                });
+                events.push(StreamEvent::ToolCallEnd {
+                    id,
+                    name,
+                    arguments,
+                });
+            }
+        }
+        if let Some(reason) = self.finish_reason {
+            events.push(StreamEvent::FinishReason(reason));
+        }
+        if let Some(usage) = self.usage {
+            events.push(StreamEvent::Usage(usage));
+        }
+        events
+    }
+}
+
+/// Typed streaming event
+///
+/// Yielded by [`StreamedText::events`](crate::StreamedText::events).
+/// Providers report tool calls once their arguments are complete, so a
+/// `ToolCallStart` is followed by a single `ToolCallDelta` carrying the whole
+/// arguments and then `ToolCallEnd`.
+#[derive(Debug)]
+pub enum StreamEvent {
+    /// Incremental answer text
+    TextDelta(String),
+    /// Incremental thinking output of reasoning models
+    ReasoningDelta(String),
+    ToolCallStart {
+        id: String,
+        name: String,
+    },
+    /// Fragment of a tool call's JSON arguments
+    ToolCallDelta {
+        id: String,
+        arguments: String,
+    },
+    ToolCallEnd {
+        id: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+    Usage(Usage),
+    FinishReason(FinishReason),
+    /// The stream failed; no events follow
+    Error(AiError),
+}
+
 /// Response metadata from streaming
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextResponse {
@@ -228,12 +794,202 @@ pub struct TextResponse {
 /// Object generation parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectParams {
+    /// Messages in the conversation
     pub messages: Vec<Message>,
+
+    /// Few-shot examples, inserted into the conversation by the executor
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<Example>,
+
+    /// JSON Schema the object must match
     pub schema: serde_json::Value,
+
+    /// Maximum number of tokens to generate
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+
+    /// Temperature (0.0 - 2.0)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
+
+    /// Top-p sampling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// Frequency penalty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+
+    /// Presence penalty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+
+    /// Stop sequences
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    /// Tools available for the model to use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+
+    /// Whether and which tools the model may call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// Whether the model may call several tools in one turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+
+    /// Reasoning effort for reasoning models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+
+    /// Seed for best-effort deterministic sampling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+
+    /// Whether to return log probabilities of the output tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+
+    /// Token ID to bias (-100 to 100) map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<HashMap<String, i32>>,
+
+    /// End-user identifier for abuse monitoring
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    /// Extra HTTP headers to send with this request
+    #[serde(skip)]
+    pub headers: HashMap<String, String>,
+
+    /// Additional provider-specific parameters
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl ObjectParams {
+    /// Create new object parameters with messages and a JSON Schema
+    pub fn new(messages: Vec<Message>, schema: serde_json::Value) -> Self {
+        Self {
+            messages,
+            examples: Vec::new(),
+            schema,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+            reasoning_effort: None,
+            seed: None,
+            logprobs: None,
+            logit_bias: None,
+            user: None,
+            headers: HashMap::new(),
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Add a few-shot example of an input and the expected output
+    pub fn with_example(mut self, input: impl Into<String>, output: impl Into<String>) -> Self {
+        self.examples.push(Example::new(input, output));
+        self
+    }
+
+    /// Add few-shot examples
+    pub fn with_examples(mut self, examples: impl IntoIterator<Item = Example>) -> Self {
+        self.examples.extend(examples);
+        self
+    }
+
+    /// Set max tokens
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set top-p
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set stop sequences
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Set tools
+    pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Set whether and which tools the model may call
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Allow or forbid several tool calls in one turn, e.g. for stateful tools
+    pub fn with_parallel_tool_calls(mut self, parallel: bool) -> Self {
+        self.parallel_tool_calls = Some(parallel);
+        self
+    }
+
+    /// Set the reasoning effort
+    pub fn with_reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Set the sampling seed
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Request log probabilities, returned in the result's `logprobs`
+    pub fn with_logprobs(mut self) -> Self {
+        self.logprobs = Some(true);
+        self
+    }
+
+    /// Set logit bias
+    pub fn with_logit_bias(mut self, logit_bias: HashMap<String, i32>) -> Self {
+        self.logit_bias = Some(logit_bias);
+        self
+    }
+
+    /// Set the end-user identifier
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Add an HTTP header to send with this request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set an additional provider-specific parameter
+    pub fn with_extra(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.extra.insert(key.into(), value);
+        self
+    }
 }
 
 /// Object request
@@ -249,6 +1005,10 @@ pub struct ObjectResult {
     pub object: serde_json::Value,
     pub usage: Usage,
     pub model: String,
+    /// Log probabilities of the output tokens, when requested with
+    /// [`ObjectParams::with_logprobs`] and returned by the provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
 }
 
 /// Object response
@@ -265,31 +1025,255 @@ pub struct ProviderInfo {
     pub name: String,
 }
 
+/// Model offered by a provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Organization owning the model, when reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owned_by: Option<String>,
+    /// Creation time as a Unix timestamp, when reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+}
+
+impl ModelInfo {
+    /// Create model info with an ID
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            owned_by: None,
+            created: None,
+        }
+    }
+}
+
+/// Scheduling priority of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// User-facing requests, served first
+    #[default]
+    Interactive,
+    /// Background jobs, served when no interactive request is waiting
+    Batch,
+}
+
+/// Per-request overrides of layer behavior
+///
+/// Attach them with [`RequestContext::with_options`]; the executor copies
+/// them to [`ChatCompletionRequest::options`], where the built-in layers
+/// read them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Bypass response caches
+    pub no_cache: bool,
+    /// Override the retry layer's maximum number of retries
+    pub max_retries: Option<u32>,
+    /// Timeout for this request, tightening the context's deadline
+    pub timeout: Option<std::time::Duration>,
+    /// Scheduling priority, see the scheduler layer
+    pub priority: Priority,
+}
+
+impl RequestOptions {
+    /// Create options that change nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bypass response caches
+    pub fn with_no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Override the maximum number of retries, e.g. 0 to fail fast
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Set a timeout for this request
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the scheduling priority
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
 /// Request context for plugins
+///
+/// Metadata and [`Extensions`] are shared handles: every hook of a request
+/// sees the same maps, and writes made by one plugin are visible to the
+/// plugins that run after it. Contexts derived with [`Self::child`] share
+/// them as well, which makes them session-scoped.
 #[derive(Debug, Clone)]
 pub struct RequestContext {
     pub request_id: String,
+    /// Session this request belongs to, shared by all child contexts
+    pub session_id: Option<String>,
+    /// Request ID of the context this one was derived from
+    pub parent_id: Option<String>,
     pub provider_id: String,
     pub model: String,
-    pub metadata: Arc<HashMap<String, String>>,
+    /// Deadline for the request, checked before and enforced during
+    /// provider calls
+    pub deadline: Option<Deadline>,
+    /// Token budget, shared with child contexts
+    pub token_budget: Option<TokenBudget>,
+    metadata: Arc<RwLock<HashMap<String, String>>>,
+    extensions: Extensions,
+    events: EventBus,
 }
 
 impl RequestContext {
+    /// Metadata key prefix for per-request HTTP headers.
+    ///
+    /// Metadata entries such as `header.X-Tenant-Id` are forwarded to the
+    /// provider as HTTP headers (`X-Tenant-Id`).
+    pub const HEADER_PREFIX: &'static str = "header.";
+
     /// Create a new request context
     pub fn new(provider_id: impl Into<String>, model: impl Into<String>) -> Self {
         Self {
             request_id: uuid::Uuid::new_v4().to_string(),
+            session_id: None,
+            parent_id: None,
             provider_id: provider_id.into(),
             model: model.into(),
-            metadata: Arc::new(HashMap::new()),
+            deadline: None,
+            token_budget: None,
+            metadata: Arc::new(RwLock::new(HashMap::new())),
+            extensions: Extensions::new(),
+            events: EventBus::new(),
         }
     }
 
     /// Create context with metadata
-    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
-        self.metadata = Arc::new(metadata);
+    pub fn with_metadata(self, metadata: HashMap<String, String>) -> Self {
+        self.metadata.write().unwrap().extend(metadata);
+        self
+    }
+
+    /// Set the session ID
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Set the request deadline
+    pub fn with_deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the request deadline `timeout` from now
+    pub fn with_timeout(self, timeout: std::time::Duration) -> Self {
+        self.with_deadline(Deadline::after(timeout))
+    }
+
+    /// Attach per-request layer overrides
+    ///
+    /// A timeout tightens the deadline: the earlier of the two applies.
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        if let Some(timeout) = options.timeout {
+            let deadline = Deadline::after(timeout);
+            self.deadline = Some(match self.deadline {
+                Some(current) if current.instant() <= deadline.instant() => current,
+                _ => deadline,
+            });
+        }
+        self.extensions.insert(options);
+        self
+    }
+
+    /// Per-request layer overrides, default if none were attached
+    pub fn options(&self) -> RequestOptions {
+        self.extensions.get().unwrap_or_default()
+    }
+
+    /// Set the token budget
+    pub fn with_token_budget(mut self, budget: TokenBudget) -> Self {
+        self.token_budget = Some(budget);
         self
     }
+
+    /// Fail if the deadline has passed or the token budget is used up
+    pub fn check_budget(&self) -> Result<(), crate::error::AiError> {
+        if let Some(deadline) = &self.deadline {
+            deadline.check()?;
+        }
+        if let Some(budget) = &self.token_budget {
+            budget.check()?;
+        }
+        Ok(())
+    }
+
+    /// Derive a context for a follow-up request in the same session.
+    ///
+    /// The child gets a fresh request ID, records this context as its parent
+    /// and shares its metadata, extensions, deadline and token budget.
+    pub fn child(&self) -> Self {
+        Self {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            session_id: self.session_id.clone(),
+            parent_id: Some(self.request_id.clone()),
+            provider_id: self.provider_id.clone(),
+            model: self.model.clone(),
+            deadline: self.deadline,
+            token_budget: self.token_budget.clone(),
+            metadata: Arc::clone(&self.metadata),
+            extensions: self.extensions.clone(),
+            events: EventBus::new(),
+        }
+    }
+
+    /// Snapshot of the current metadata
+    pub fn metadata(&self) -> HashMap<String, String> {
+        self.metadata.read().unwrap().clone()
+    }
+
+    /// Get a metadata value
+    pub fn get_metadata(&self, key: &str) -> Option<String> {
+        self.metadata.read().unwrap().get(key).cloned()
+    }
+
+    /// Set a metadata value, visible to every hook sharing this context
+    pub fn set_metadata(&self, key: impl Into<String>, value: impl Into<String>) {
+        self.metadata
+            .write()
+            .unwrap()
+            .insert(key.into(), value.into());
+    }
+
+    /// Typed extensions shared across plugin hooks
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Layer events of this request, not shared with child contexts
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Get the HTTP headers carried in metadata under [`Self::HEADER_PREFIX`]
+    pub fn headers(&self) -> HashMap<String, String> {
+        self.metadata
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix(Self::HEADER_PREFIX)
+                    .map(|name| (name.to_string(), value.clone()))
+            })
+            .collect()
+    }
 }
 
 // ============================================================================
@@ -331,10 +1315,53 @@ pub struct ChatCompletionRequest {
     pub stop: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+    /// Whether and which tools the model may call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    /// Whether the model may call several tools in one turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response_format: Option<ResponseFormat>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
+    /// Reasoning effort for reasoning models
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Seed for best-effort deterministic sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Whether to return log probabilities of the output tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    /// Number of most likely alternatives to return per token (requires `logprobs`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    /// Token ID to bias (-100 to 100) map
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<HashMap<String, i32>>,
+    /// Number of choices to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u8>,
+    /// End-user identifier for abuse monitoring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Extra HTTP headers to send with this request (not part of the body)
+    #[serde(skip)]
+    pub headers: HashMap<String, String>,
+    /// Deadline propagated from the request context (not part of the body)
+    #[serde(skip)]
+    pub deadline: Option<Deadline>,
+    /// Layer overrides propagated from the request context (not part of the body)
+    #[serde(skip)]
+    pub options: RequestOptions,
+    /// Metadata propagated from the request context, e.g. the tenant (not
+    /// part of the body)
+    #[serde(skip)]
+    pub metadata: HashMap<String, String>,
+    /// Queue layers report retries and cache lookups on (not part of the body)
+    #[serde(skip)]
+    pub events: EventBus,
     /// Additional provider-specific parameters
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
@@ -353,8 +1380,22 @@ impl ChatCompletionRequest {
             presence_penalty: None,
             stop: None,
             tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
             response_format: None,
             stream: None,
+            reasoning_effort: None,
+            seed: None,
+            logprobs: None,
+            top_logprobs: None,
+            logit_bias: None,
+            n: None,
+            user: None,
+            headers: HashMap::new(),
+            deadline: None,
+            options: RequestOptions::default(),
+            metadata: HashMap::new(),
+            events: EventBus::new(),
             extra: HashMap::new(),
         }
     }
@@ -371,6 +1412,18 @@ impl ChatCompletionRequest {
         self
     }
 
+    /// Set per-request layer overrides
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set a metadata value
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
     /// Set response format
     pub fn with_response_format(mut self, response_format: ResponseFormat) -> Self {
         self.response_format = Some(response_format);
@@ -383,11 +1436,72 @@ impl ChatCompletionRequest {
         self
     }
 
+    /// Set whether and which tools the model may call
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Allow or forbid several tool calls in one turn, e.g. for stateful tools
+    pub fn with_parallel_tool_calls(mut self, parallel: bool) -> Self {
+        self.parallel_tool_calls = Some(parallel);
+        self
+    }
+
     /// Enable streaming
     pub fn with_stream(mut self, stream: bool) -> Self {
         self.stream = Some(stream);
         self
     }
+
+    /// Set the reasoning effort
+    pub fn with_reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Set the sampling seed
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Request log probabilities, with `top` alternatives per token
+    pub fn with_logprobs(mut self, top: u8) -> Self {
+        self.logprobs = Some(true);
+        self.top_logprobs = (top > 0).then_some(top);
+        self
+    }
+
+    /// Set logit bias
+    pub fn with_logit_bias(mut self, logit_bias: HashMap<String, i32>) -> Self {
+        self.logit_bias = Some(logit_bias);
+        self
+    }
+
+    /// Set the number of choices to generate
+    pub fn with_n(mut self, n: u8) -> Self {
+        self.n = Some(n);
+        self
+    }
+
+    /// Set the end-user identifier
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Add an HTTP header to send with this request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set an additional provider-specific parameter
+    pub fn with_extra(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.extra.insert(key.into(), value);
+        self
+    }
 }
 
 /// Single choice in chat completion response
@@ -396,6 +1510,37 @@ pub struct Choice {
     pub index: u32,
     pub message: Message,
     pub finish_reason: FinishReason,
+    /// Log probabilities of the content tokens, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
+    /// Stop sequence that ended generation, for providers that report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequence: Option<String>,
+    /// Categories that triggered the content filter, for providers that
+    /// report them (Azure OpenAI, Vertex AI)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filtered_categories: Vec<String>,
+}
+
+/// Log probability of a generated token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
+    /// Most likely alternatives at this position
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+/// Alternative token at a position, with its log probability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
 }
 
 /// Chat completion response
@@ -407,6 +1552,13 @@ pub struct ChatCompletionResponse {
     pub usage: Usage,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created: Option<u64>,
+    /// Backend configuration fingerprint; a change means the model was
+    /// updated even though its name stayed the same
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
+    /// Service tier that processed the request (e.g. `default`, `flex`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
 }
 
 /// Chat completion streaming chunk
@@ -417,6 +1569,12 @@ pub struct ChatCompletionChunk {
     pub choices: Vec<ChoiceDelta>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<Usage>,
+    /// See [`ChatCompletionResponse::system_fingerprint`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
+    /// See [`ChatCompletionResponse::service_tier`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
 }
 
 /// Delta choice in streaming response
@@ -435,6 +1593,9 @@ pub struct MessageDelta {
     pub role: Option<Role>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+    /// Incremental thinking output of reasoning models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ContentPart>>,
 }
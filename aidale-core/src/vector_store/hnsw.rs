@@ -0,0 +1,579 @@
+//! In-memory vector store with an HNSW index.
+//!
+//! Hierarchical navigable small world graphs (Malkov & Yashunin) link each
+//! vector to its nearest neighbors on several layers; upper layers are
+//! sparse and let a search jump close to the query before the dense bottom
+//! layer refines it. Searches touch a few hundred vectors instead of all of
+//! them, at the cost of occasionally missing a true neighbor.
+//!
+//! Deleted and replaced records stay in the graph as tombstones so the
+//! links through them keep working. Once tombstones outnumber live records
+//! the index is rebuilt.
+//!
+//! Filtered searches widen the beam until enough matching records are
+//! found, falling back to visiting the whole graph for very selective
+//! filters.
+//!
+//! ```ignore
+//! let store = HnswVectorStore::load_or_new("index.json", HnswConfig::default())?;
+//! store.upsert(records).await?;
+//! store.save("index.json")?;
+//! ```
+
+use super::{ScoredRecord, VectorQuery, VectorRecord, VectorStore};
+use crate::error::AiError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::RwLock;
+
+/// Highest layer a node can be placed on
+const MAX_LEVEL: usize = 16;
+
+/// Index parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HnswConfig {
+    /// Links per node on the upper layers; the bottom layer allows twice as
+    /// many
+    pub m: usize,
+    /// Beam width while inserting; higher builds a better graph more slowly
+    pub ef_construction: usize,
+    /// Beam width while searching; higher improves recall at some latency
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+impl HnswConfig {
+    /// Create the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the links per node
+    pub fn with_m(mut self, m: usize) -> Self {
+        self.m = m.max(2);
+        self
+    }
+
+    /// Set the beam width while inserting
+    pub fn with_ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction.max(1);
+        self
+    }
+
+    /// Set the beam width while searching
+    pub fn with_ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search.max(1);
+        self
+    }
+}
+
+/// Similarity of a node to the vector being searched for
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored {
+    score: f32,
+    node: usize,
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Node {
+    record: VectorRecord,
+    /// Neighbors on each layer the node is part of, bottom layer first
+    links: Vec<Vec<usize>>,
+    deleted: bool,
+    /// Normalized vector, so a dot product gives the cosine similarity
+    #[serde(skip)]
+    unit: Vec<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Index {
+    config: HnswConfig,
+    dimensions: Option<usize>,
+    nodes: Vec<Node>,
+    entry: Option<usize>,
+    /// Random number generator state for node levels
+    seed: u64,
+    /// Live node of each record ID
+    #[serde(skip)]
+    ids: HashMap<String, usize>,
+}
+
+fn normalize(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vec![0.0; vector.len()];
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+impl Index {
+    fn new(config: HnswConfig) -> Self {
+        Self {
+            config,
+            dimensions: None,
+            nodes: Vec::new(),
+            entry: None,
+            seed: 0x2545_F491_4F6C_DD1D,
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Recompute the state that isn't persisted
+    fn restore(&mut self) {
+        self.ids.clear();
+        for (i, node) in self.nodes.iter_mut().enumerate() {
+            node.unit = normalize(&node.record.vector);
+            if !node.deleted {
+                self.ids.insert(node.record.id.clone(), i);
+            }
+        }
+    }
+
+    fn check_dimensions(&self, vector: &[f32]) -> Result<(), AiError> {
+        match self.dimensions {
+            Some(dimensions) if dimensions != vector.len() => {
+                Err(AiError::invalid_request(format!(
+                    "Vector has {} dimensions, the index {}",
+                    vector.len(),
+                    dimensions
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Draw a level with exponentially decaying probability (SplitMix64)
+    fn random_level(&mut self) -> usize {
+        self.seed = self.seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        let uniform = ((z >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level = -uniform.ln() / (self.config.m as f64).ln();
+        (level as usize).min(MAX_LEVEL)
+    }
+
+    fn similarity(&self, vector: &[f32], node: usize) -> f32 {
+        dot(vector, &self.nodes[node].unit)
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.config.m * 2
+        } else {
+            self.config.m
+        }
+    }
+
+    /// Best `ef` nodes reachable on `layer` from `entries`, best first
+    fn search_layer(
+        &self,
+        vector: &[f32],
+        entries: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Scored> {
+        let mut visited: HashSet<usize> = entries.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut results = BinaryHeap::new();
+        for &node in entries {
+            let scored = Scored {
+                score: self.similarity(vector, node),
+                node,
+            };
+            candidates.push(scored);
+            results.push(Reverse(scored));
+        }
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = results.peek().map_or(f32::MIN, |Reverse(s)| s.score);
+            if candidate.score < worst && results.len() >= ef {
+                break;
+            }
+            for &neighbor in &self.nodes[candidate.node].links[layer] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let score = self.similarity(vector, neighbor);
+                let worst = results.peek().map_or(f32::MIN, |Reverse(s)| s.score);
+                if results.len() < ef || score > worst {
+                    let scored = Scored {
+                        score,
+                        node: neighbor,
+                    };
+                    candidates.push(scored);
+                    results.push(Reverse(scored));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(scored)| scored)
+            .collect()
+    }
+
+    /// Pick up to `m` neighbors from candidates sorted best first
+    ///
+    /// A candidate closer to an already picked neighbor than to the base
+    /// node is skipped while better spread candidates remain, which keeps
+    /// links to separate clusters and the graph navigable.
+    fn select_neighbors(&self, candidates: &[Scored], m: usize) -> Vec<usize> {
+        let mut selected: Vec<usize> = Vec::with_capacity(m);
+        let mut skipped = Vec::new();
+        for candidate in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let unit = &self.nodes[candidate.node].unit;
+            if selected
+                .iter()
+                .all(|&other| self.similarity(unit, other) < candidate.score)
+            {
+                selected.push(candidate.node);
+            } else {
+                skipped.push(candidate.node);
+            }
+        }
+        for node in skipped {
+            if selected.len() >= m {
+                break;
+            }
+            selected.push(node);
+        }
+        selected
+    }
+
+    /// Descend from the entry point to `layer`, returning the closest node
+    fn descend(&self, vector: &[f32], layer: usize) -> Option<usize> {
+        let mut entry = self.entry?;
+        let top = self.nodes[entry].links.len() - 1;
+        for current in (layer + 1..=top).rev() {
+            entry = self.search_layer(vector, &[entry], 1, current)[0].node;
+        }
+        Some(entry)
+    }
+
+    fn insert(&mut self, record: VectorRecord) -> Result<(), AiError> {
+        self.check_dimensions(&record.vector)?;
+        self.dimensions = Some(record.vector.len());
+        if let Some(old) = self.ids.remove(&record.id) {
+            self.nodes[old].deleted = true;
+        }
+
+        let level = self.random_level();
+        let index = self.nodes.len();
+        let unit = normalize(&record.vector);
+        self.ids.insert(record.id.clone(), index);
+        self.nodes.push(Node {
+            record,
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+            unit: unit.clone(),
+        });
+
+        let Some(entry_point) = self.entry else {
+            self.entry = Some(index);
+            return Ok(());
+        };
+        let top = self.nodes[entry_point].links.len() - 1;
+
+        let mut entries: Vec<usize> = self.descend(&unit, level).into_iter().collect();
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&unit, &entries, self.config.ef_construction, layer);
+            let neighbors = self.select_neighbors(&found, self.config.m);
+            for &neighbor in &neighbors {
+                self.nodes[neighbor].links[layer].push(index);
+                if self.nodes[neighbor].links[layer].len() > self.max_links(layer) {
+                    self.prune(neighbor, layer);
+                }
+            }
+            self.nodes[index].links[layer] = neighbors;
+            entries = found.into_iter().map(|scored| scored.node).collect();
+        }
+
+        if level > top {
+            self.entry = Some(index);
+        }
+        Ok(())
+    }
+
+    /// Cut a node's links on `layer` back to the maximum
+    fn prune(&mut self, node: usize, layer: usize) {
+        let unit = &self.nodes[node].unit;
+        let mut candidates: Vec<Scored> = self.nodes[node].links[layer]
+            .iter()
+            .map(|&neighbor| Scored {
+                score: self.similarity(unit, neighbor),
+                node: neighbor,
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.cmp(a));
+        let links = self.select_neighbors(&candidates, self.max_links(layer));
+        self.nodes[node].links[layer] = links;
+    }
+
+    fn delete(&mut self, id: &str) {
+        if let Some(node) = self.ids.remove(id) {
+            self.nodes[node].deleted = true;
+        }
+    }
+
+    /// Rebuild the graph without tombstones once they outnumber live nodes
+    fn compact(&mut self) -> Result<(), AiError> {
+        let live = self.ids.len();
+        if self.nodes.len() - live <= live.max(64) {
+            return Ok(());
+        }
+        let nodes = std::mem::take(&mut self.nodes);
+        self.entry = None;
+        self.ids.clear();
+        if live == 0 {
+            self.dimensions = None;
+        }
+        for node in nodes.into_iter().filter(|node| !node.deleted) {
+            self.insert(node.record)?;
+        }
+        Ok(())
+    }
+
+    fn search(&self, query: &VectorQuery) -> Result<Vec<ScoredRecord>, AiError> {
+        if self.ids.is_empty() || query.limit == 0 {
+            return Ok(Vec::new());
+        }
+        self.check_dimensions(&query.vector)?;
+        let unit = normalize(&query.vector);
+        let Some(entry) = self.descend(&unit, 0) else {
+            return Ok(Vec::new());
+        };
+
+        let mut ef = self.config.ef_search.max(query.limit);
+        loop {
+            let found = self.search_layer(&unit, &[entry], ef, 0);
+            let below_min = found
+                .last()
+                .zip(query.min_score)
+                .is_some_and(|(worst, min)| worst.score < min);
+            let hits: Vec<ScoredRecord> = found
+                .into_iter()
+                .filter(|scored| query.min_score.map_or(true, |min| scored.score >= min))
+                .filter_map(|scored| {
+                    let node = &self.nodes[scored.node];
+                    (!node.deleted && query.matches(&node.record.payload)).then(|| ScoredRecord {
+                        record: node.record.clone(),
+                        score: scored.score,
+                    })
+                })
+                .take(query.limit)
+                .collect();
+
+            // Widening can't help once the beam reaches past `min_score` or
+            // covers the whole graph
+            if hits.len() >= query.limit || below_min || ef >= self.nodes.len() {
+                return Ok(hits);
+            }
+            ef = (ef * 4).min(self.nodes.len());
+        }
+    }
+}
+
+/// In-memory vector store with an HNSW index and optional persistence.
+///
+/// Scores are cosine similarities, like [`InMemoryVectorStore`].
+///
+/// [`InMemoryVectorStore`]: super::InMemoryVectorStore
+#[derive(Debug)]
+pub struct HnswVectorStore {
+    index: RwLock<Index>,
+}
+
+impl Default for HnswVectorStore {
+    fn default() -> Self {
+        Self::new(HnswConfig::default())
+    }
+}
+
+impl HnswVectorStore {
+    /// Create an empty store
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            index: RwLock::new(Index::new(config)),
+        }
+    }
+
+    /// Number of stored records
+    pub fn len(&self) -> usize {
+        self.index.read().unwrap().ids.len()
+    }
+
+    /// Whether the store is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the records and the graph to a file
+    ///
+    /// The file is replaced atomically, so a crash never leaves a partial
+    /// index behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AiError> {
+        let path = path.as_ref();
+        let bytes = serde_json::to_vec(&*self.index.read().unwrap())?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, bytes)
+            .and_then(|()| std::fs::rename(&temp, path))
+            .map_err(|e| AiError::other(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    /// Read a store written by [`save`](Self::save)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AiError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| AiError::other(format!("Failed to read {}: {}", path.display(), e)))?;
+        let mut index: Index = serde_json::from_slice(&bytes)?;
+        index.restore();
+        Ok(Self {
+            index: RwLock::new(index),
+        })
+    }
+
+    /// Read a saved store, or create an empty one if the file doesn't exist
+    pub fn load_or_new(path: impl AsRef<Path>, config: HnswConfig) -> Result<Self, AiError> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::new(config))
+        }
+    }
+}
+
+#[async_trait]
+impl VectorStore for HnswVectorStore {
+    async fn upsert(&self, records: Vec<VectorRecord>) -> Result<(), AiError> {
+        let mut index = self.index.write().unwrap();
+        for record in records {
+            index.insert(record)?;
+        }
+        index.compact()
+    }
+
+    async fn search(&self, query: &VectorQuery) -> Result<Vec<ScoredRecord>, AiError> {
+        self.index.read().unwrap().search(query)
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<(), AiError> {
+        let mut index = self.index.write().unwrap();
+        for id in ids {
+            index.delete(id);
+        }
+        index.compact()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::InMemoryVectorStore;
+    use super::*;
+    use serde_json::json;
+
+    fn random_vectors(count: usize, dimensions: usize) -> Vec<Vec<f32>> {
+        let mut state = 42u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 2000) as f32 / 1000.0 - 1.0
+        };
+        (0..count)
+            .map(|_| (0..dimensions).map(|_| next()).collect())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_hnsw_store() {
+        let vectors = random_vectors(500, 16);
+        let records: Vec<VectorRecord> = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, vector)| {
+                VectorRecord::new(i.to_string(), vector.clone(), json!({"even": i % 2 == 0}))
+            })
+            .collect();
+        let store = HnswVectorStore::default();
+        let exact = InMemoryVectorStore::new();
+        store.upsert(records.clone()).await.unwrap();
+        exact.upsert(records).await.unwrap();
+
+        // Recall of the 10 nearest neighbors against exhaustive search
+        let mut found = 0;
+        for vector in random_vectors(520, 16).into_iter().skip(500) {
+            let query = VectorQuery::new(vector, 10);
+            let expected = exact.search(&query).await.unwrap();
+            let hits = store.search(&query).await.unwrap();
+            found += hits
+                .iter()
+                .filter(|hit| expected.iter().any(|e| e.record.id == hit.record.id))
+                .count();
+        }
+        assert!(found >= 190, "recall {}/200", found);
+
+        let query = VectorQuery::new(vectors[3].clone(), 5).with_filter("even", json!(true));
+        let hits = store.search(&query).await.unwrap();
+        assert_eq!(hits.len(), 5);
+        assert!(hits.iter().all(|hit| hit.record.payload["even"] == true));
+
+        let query = VectorQuery::new(vectors[7].clone(), 1);
+        assert_eq!(store.search(&query).await.unwrap()[0].record.id, "7");
+        store.delete(&["7".to_string()]).await.unwrap();
+        assert_ne!(store.search(&query).await.unwrap()[0].record.id, "7");
+        assert_eq!(store.len(), 499);
+        assert!(store.search(&VectorQuery::new(vec![1.0], 1)).await.is_err());
+
+        let path = std::env::temp_dir().join(format!("aidale-hnsw-{}.json", std::process::id()));
+        store.save(&path).unwrap();
+        let loaded = HnswVectorStore::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), 499);
+        let query = VectorQuery::new(vectors[8].clone(), 1);
+        assert_eq!(loaded.search(&query).await.unwrap()[0].record.id, "8");
+    }
+}
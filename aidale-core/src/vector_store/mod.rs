@@ -0,0 +1,224 @@
+//! Vector store abstractions.
+//!
+//! A vector store keeps embedding vectors together with a JSON payload and
+//! supports similarity search. The trait is shared by semantic caching and
+//! retrieval so a single backend can serve both.
+//!
+//! [`InMemoryVectorStore`] searches exhaustively and suits tests;
+//! [`HnswVectorStore`] keeps an approximate nearest neighbor index that
+//! stays fast with hundreds of thousands of records and can be saved to
+//! disk.
+
+mod hnsw;
+
+pub use hnsw::{HnswConfig, HnswVectorStore};
+
+use crate::embedding::Embedding;
+use crate::error::AiError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::RwLock;
+
+/// A vector with its ID and payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorRecord {
+    pub id: String,
+    pub vector: Embedding,
+    /// Arbitrary JSON payload; top-level fields can be used for filtering
+    pub payload: serde_json::Value,
+}
+
+impl VectorRecord {
+    /// Create a new record
+    pub fn new(id: impl Into<String>, vector: Embedding, payload: serde_json::Value) -> Self {
+        Self {
+            id: id.into(),
+            vector,
+            payload,
+        }
+    }
+}
+
+/// A search hit with its similarity score
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoredRecord {
+    pub record: VectorRecord,
+    /// Cosine similarity (higher is more similar)
+    pub score: f32,
+}
+
+/// Similarity search query
+#[derive(Debug, Clone)]
+pub struct VectorQuery {
+    pub vector: Embedding,
+    pub limit: usize,
+    /// Payload fields that must equal the given values
+    pub filter: HashMap<String, serde_json::Value>,
+    /// Minimum similarity score for returned hits
+    pub min_score: Option<f32>,
+}
+
+impl VectorQuery {
+    /// Create a new query returning up to `limit` hits
+    pub fn new(vector: Embedding, limit: usize) -> Self {
+        Self {
+            vector,
+            limit,
+            filter: HashMap::new(),
+            min_score: None,
+        }
+    }
+
+    /// Require a payload field to equal a value
+    pub fn with_filter(mut self, field: impl Into<String>, value: serde_json::Value) -> Self {
+        self.filter.insert(field.into(), value);
+        self
+    }
+
+    /// Set minimum similarity score
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Check whether a payload matches this query's filter
+    pub fn matches(&self, payload: &serde_json::Value) -> bool {
+        self.filter
+            .iter()
+            .all(|(field, value)| payload.get(field) == Some(value))
+    }
+}
+
+/// Storage backend for vectors with similarity search.
+#[async_trait]
+pub trait VectorStore: Send + Sync + Debug + 'static {
+    /// Insert or replace records by ID
+    async fn upsert(&self, records: Vec<VectorRecord>) -> Result<(), AiError>;
+
+    /// Search for the most similar records
+    async fn search(&self, query: &VectorQuery) -> Result<Vec<ScoredRecord>, AiError>;
+
+    /// Delete records by ID
+    async fn delete(&self, ids: &[String]) -> Result<(), AiError>;
+}
+
+/// Cosine similarity between two vectors.
+///
+/// Returns 0.0 when the vectors differ in length or either is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+
+    let mut dot = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// Simple in-memory vector store using brute-force search.
+///
+/// Suitable for tests and small datasets; see [`HnswVectorStore`] for
+/// larger ones.
+#[derive(Debug, Default)]
+pub struct InMemoryVectorStore {
+    records: RwLock<HashMap<String, VectorRecord>>,
+}
+
+impl InMemoryVectorStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored records
+    pub fn len(&self) -> usize {
+        self.records.read().unwrap().len()
+    }
+
+    /// Whether the store is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn upsert(&self, records: Vec<VectorRecord>) -> Result<(), AiError> {
+        let mut store = self.records.write().unwrap();
+        for record in records {
+            store.insert(record.id.clone(), record);
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &VectorQuery) -> Result<Vec<ScoredRecord>, AiError> {
+        let store = self.records.read().unwrap();
+        let mut hits: Vec<ScoredRecord> = store
+            .values()
+            .filter(|record| query.matches(&record.payload))
+            .map(|record| ScoredRecord {
+                score: cosine_similarity(&query.vector, &record.vector),
+                record: record.clone(),
+            })
+            .filter(|hit| query.min_score.map_or(true, |min| hit.score >= min))
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(query.limit);
+        Ok(hits)
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<(), AiError> {
+        let mut store = self.records.write().unwrap();
+        for id in ids {
+            store.remove(id);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_search_with_filter() {
+        let store = InMemoryVectorStore::new();
+        store
+            .upsert(vec![
+                VectorRecord::new("a", vec![1.0, 0.0], serde_json::json!({"model": "x"})),
+                VectorRecord::new("b", vec![0.9, 0.1], serde_json::json!({"model": "y"})),
+                VectorRecord::new("c", vec![0.0, 1.0], serde_json::json!({"model": "y"})),
+            ])
+            .await
+            .unwrap();
+
+        let query = VectorQuery::new(vec![1.0, 0.0], 5).with_filter("model", "y".into());
+        let hits = store.search(&query).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].record.id, "b");
+
+        let hits = store.search(&query.with_min_score(0.5)).await.unwrap();
+        assert_eq!(hits.len(), 1);
+    }
+}
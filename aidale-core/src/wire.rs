@@ -0,0 +1,143 @@
+//! Access to the wire-level JSON exchanged with provider APIs.
+//!
+//! Providers accept an optional [`WireObserver`] on their builders. It is
+//! called with every serialized request body before it is sent, every
+//! response body, each server-sent event of a stream and the body of error
+//! responses. This is meant for debugging provider incompatibilities (what
+//! exactly was sent when a request came back 400?) and for byte-accurate
+//! contract tests; it is not needed in normal operation.
+//!
+//! ```ignore
+//! let wire = WireLog::new();
+//! let provider = CohereProvider::new(api_key).with_wire_observer(Arc::new(wire.clone()));
+//! // ... run a request ...
+//! println!("{}", wire.requests()[0].body);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// What a [`WireBody`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireDirection {
+    /// Request body sent to the provider
+    Request,
+    /// Body of a successful non-streaming response
+    Response,
+    /// Data of one server-sent event of a streaming response
+    StreamEvent,
+    /// Body of an unsuccessful response
+    Error,
+}
+
+/// A body exchanged with a provider API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireBody {
+    /// Provider ID
+    pub provider: String,
+    pub direction: WireDirection,
+    /// HTTP status, for responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// The body as sent or received
+    pub body: String,
+}
+
+impl WireBody {
+    /// Create a body without a status
+    pub fn new(
+        provider: impl Into<String>,
+        direction: WireDirection,
+        body: impl Into<String>,
+    ) -> Self {
+        Self {
+            provider: provider.into(),
+            direction,
+            status: None,
+            body: body.into(),
+        }
+    }
+
+    /// Set the HTTP status
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Parse the body as JSON
+    pub fn json(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::from_str(&self.body)
+    }
+}
+
+/// Receives the wire-level bodies of a provider
+///
+/// Called inline on the request path, so implementations should be cheap.
+pub trait WireObserver: Send + Sync {
+    fn observe(&self, body: &WireBody);
+}
+
+impl<F> WireObserver for F
+where
+    F: Fn(&WireBody) + Send + Sync,
+{
+    fn observe(&self, body: &WireBody) {
+        self(body)
+    }
+}
+
+/// Observer keeping every body in memory
+#[derive(Debug, Clone, Default)]
+pub struct WireLog {
+    bodies: Arc<Mutex<Vec<WireBody>>>,
+}
+
+impl WireLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All bodies, oldest first
+    pub fn bodies(&self) -> Vec<WireBody> {
+        self.bodies.lock().unwrap().clone()
+    }
+
+    /// Request bodies, oldest first
+    pub fn requests(&self) -> Vec<WireBody> {
+        self.filter(WireDirection::Request)
+    }
+
+    /// Response, stream event and error bodies, oldest first
+    pub fn responses(&self) -> Vec<WireBody> {
+        self.bodies
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|body| body.direction != WireDirection::Request)
+            .cloned()
+            .collect()
+    }
+
+    /// Forget all bodies
+    pub fn clear(&self) {
+        self.bodies.lock().unwrap().clear();
+    }
+
+    fn filter(&self, direction: WireDirection) -> Vec<WireBody> {
+        self.bodies
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|body| body.direction == direction)
+            .cloned()
+            .collect()
+    }
+}
+
+impl WireObserver for WireLog {
+    fn observe(&self, body: &WireBody) {
+        self.bodies.lock().unwrap().push(body.clone());
+    }
+}
@@ -0,0 +1,154 @@
+//! Evaluation datasets.
+
+use aidale_core::error::AiError;
+use aidale_core::types::Message;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A single prompt with its expected answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub id: String,
+    pub messages: Vec<Message>,
+    /// Reference answer for graders: a string for text matching, an object
+    /// for JSON field matching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<serde_json::Value>,
+    /// Free-form annotations, e.g. a category to slice reports by
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+impl EvalCase {
+    /// Create a case with a single user prompt
+    pub fn new(id: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self::with_messages(id, vec![Message::user(prompt)])
+    }
+
+    /// Create a case from a full conversation
+    pub fn with_messages(id: impl Into<String>, messages: Vec<Message>) -> Self {
+        Self {
+            id: id.into(),
+            messages,
+            expected: None,
+            metadata: serde_json::Map::new(),
+        }
+    }
+
+    /// Prepend a system prompt
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.messages.insert(0, Message::system(system));
+        self
+    }
+
+    /// Set the reference answer
+    pub fn with_expected(mut self, expected: impl Into<serde_json::Value>) -> Self {
+        self.expected = Some(expected.into());
+        self
+    }
+
+    /// Attach an annotation
+    pub fn with_metadata(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.metadata.insert(key.into(), value);
+        self
+    }
+
+    /// The reference answer as text, if it is a string
+    pub fn expected_text(&self) -> Option<&str> {
+        self.expected
+            .as_ref()
+            .and_then(|expected| expected.as_str())
+    }
+}
+
+/// Line of a JSONL dataset file: either a `prompt` or `messages`
+#[derive(Deserialize)]
+struct CaseLine {
+    id: Option<String>,
+    prompt: Option<String>,
+    system: Option<String>,
+    messages: Option<Vec<Message>>,
+    expected: Option<serde_json::Value>,
+    #[serde(default)]
+    metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Named collection of cases
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Dataset {
+    pub name: String,
+    pub cases: Vec<EvalCase>,
+}
+
+impl Dataset {
+    /// Create an empty dataset
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            cases: Vec::new(),
+        }
+    }
+
+    /// Add a case
+    pub fn with_case(mut self, case: EvalCase) -> Self {
+        self.cases.push(case);
+        self
+    }
+
+    /// Parse JSONL, one case per line
+    ///
+    /// Each line has an optional `id` (defaults to the line number), either
+    /// `prompt` (with an optional `system`) or `messages`, and optional
+    /// `expected` and `metadata`. Blank lines are skipped.
+    pub fn from_jsonl(name: impl Into<String>, jsonl: &str) -> Result<Self, AiError> {
+        let mut dataset = Self::new(name);
+        for (number, line) in jsonl.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line: CaseLine = serde_json::from_str(line)?;
+            let id = line.id.unwrap_or_else(|| (number + 1).to_string());
+
+            let mut case = match (line.prompt, line.messages) {
+                (Some(prompt), None) => EvalCase::new(id, prompt),
+                (None, Some(messages)) => EvalCase::with_messages(id, messages),
+                _ => {
+                    return Err(AiError::configuration(format!(
+                        "Dataset line {} needs exactly one of `prompt` or `messages`",
+                        number + 1
+                    )))
+                }
+            };
+            if let Some(system) = line.system {
+                case = case.with_system(system);
+            }
+            case.expected = line.expected;
+            case.metadata = line.metadata;
+            dataset.cases.push(case);
+        }
+        Ok(dataset)
+    }
+
+    /// Load a JSONL file, named after the file stem
+    pub fn load_jsonl(path: impl AsRef<Path>) -> Result<Self, AiError> {
+        let path = path.as_ref();
+        let jsonl = std::fs::read_to_string(path).map_err(|e| {
+            AiError::configuration(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::from_jsonl(name, &jsonl)
+    }
+
+    /// Number of cases
+    pub fn len(&self) -> usize {
+        self.cases.len()
+    }
+
+    /// Whether the dataset has no cases
+    pub fn is_empty(&self) -> bool {
+        self.cases.is_empty()
+    }
+}
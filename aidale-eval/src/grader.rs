@@ -0,0 +1,338 @@
+//! Graders scoring a model output against a case.
+
+use crate::dataset::EvalCase;
+use aidale_core::error::AiError;
+use aidale_core::json_repair;
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_core::types::*;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Score given by a grader
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grade {
+    /// Score between 0 and 1
+    pub score: f64,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Grade {
+    /// Full score
+    pub fn pass() -> Self {
+        Self {
+            score: 1.0,
+            passed: true,
+            reason: None,
+        }
+    }
+
+    /// Zero score with an explanation
+    pub fn fail(reason: impl Into<String>) -> Self {
+        Self {
+            score: 0.0,
+            passed: false,
+            reason: Some(reason.into()),
+        }
+    }
+
+    /// Add an explanation
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// Scores the output of one case
+#[async_trait]
+pub trait Grader: Send + Sync {
+    /// Name used as the column in reports
+    fn name(&self) -> &str;
+
+    /// Grade `output`, produced for `case`
+    async fn grade(&self, case: &EvalCase, output: &TextResult) -> Result<Grade, AiError>;
+}
+
+/// Compares the output text with the expected string
+#[derive(Debug, Clone)]
+pub struct ExactMatch {
+    trim: bool,
+    case_insensitive: bool,
+}
+
+impl ExactMatch {
+    /// Create a grader that ignores surrounding whitespace
+    pub fn new() -> Self {
+        Self {
+            trim: true,
+            case_insensitive: false,
+        }
+    }
+
+    /// Whether to ignore surrounding whitespace (default: true)
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Whether to ignore letter case (default: false)
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    fn normalize(&self, text: &str) -> String {
+        let text = if self.trim { text.trim() } else { text };
+        if self.case_insensitive {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+impl Default for ExactMatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Grader for ExactMatch {
+    fn name(&self) -> &str {
+        "exact_match"
+    }
+
+    async fn grade(&self, case: &EvalCase, output: &TextResult) -> Result<Grade, AiError> {
+        let expected = case.expected_text().ok_or_else(|| {
+            AiError::configuration(format!("Case {} has no expected text", case.id))
+        })?;
+
+        if self.normalize(&output.content) == self.normalize(expected) {
+            Ok(Grade::pass())
+        } else {
+            Ok(Grade::fail(format!(
+                "expected {:?}, got {:?}",
+                expected, output.content
+            )))
+        }
+    }
+}
+
+/// Parses the output as JSON and compares fields with the expected object
+///
+/// Fields are dot-separated paths (`address.city`, `items.0.name`). Without
+/// explicit fields, every top-level field of the expected object is checked.
+/// The score is the fraction of matching fields.
+#[derive(Debug, Clone, Default)]
+pub struct JsonFieldMatch {
+    fields: Vec<String>,
+}
+
+impl JsonFieldMatch {
+    /// Check every top-level field of the expected object
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check only these fields
+    pub fn with_fields(mut self, fields: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// Convert a dot-separated path to a JSON pointer
+fn pointer(path: &str) -> String {
+    path.split('.').fold(String::new(), |mut pointer, segment| {
+        pointer.push('/');
+        pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+        pointer
+    })
+}
+
+#[async_trait]
+impl Grader for JsonFieldMatch {
+    fn name(&self) -> &str {
+        "json_fields"
+    }
+
+    async fn grade(&self, case: &EvalCase, output: &TextResult) -> Result<Grade, AiError> {
+        let expected = match &case.expected {
+            Some(serde_json::Value::Object(expected)) => expected,
+            _ => {
+                return Err(AiError::configuration(format!(
+                    "Case {} has no expected JSON object",
+                    case.id
+                )))
+            }
+        };
+        let Some(actual) = json_repair::parse(&output.content).ok() else {
+            return Ok(Grade::fail("output is not valid JSON"));
+        };
+
+        let fields = if self.fields.is_empty() {
+            expected.keys().cloned().collect()
+        } else {
+            self.fields.clone()
+        };
+        if fields.is_empty() {
+            return Ok(Grade::pass());
+        }
+
+        let expected = serde_json::Value::Object(expected.clone());
+        let mismatched: Vec<_> = fields
+            .iter()
+            .filter(|field| {
+                let pointer = pointer(field);
+                actual.pointer(&pointer) != expected.pointer(&pointer)
+            })
+            .map(String::as_str)
+            .collect();
+
+        let score = (fields.len() - mismatched.len()) as f64 / fields.len() as f64;
+        let grade = Grade {
+            score,
+            passed: mismatched.is_empty(),
+            reason: None,
+        };
+        Ok(if mismatched.is_empty() {
+            grade
+        } else {
+            grade.with_reason(format!("mismatched fields: {}", mismatched.join(", ")))
+        })
+    }
+}
+
+/// Default instructions for [`LlmJudge`]
+pub const DEFAULT_JUDGE_PROMPT: &str = "You are grading the response of an AI assistant. \
+Score how well the response satisfies the rubric, from 0 (not at all) to 1 (fully). \
+Reply with a JSON object {\"score\": number, \"reason\": string}.";
+
+/// Uses another model, through its own executor, as the grader
+pub struct LlmJudge {
+    executor: Arc<RuntimeExecutor>,
+    model: String,
+    rubric: String,
+    prompt: String,
+    threshold: f64,
+}
+
+impl LlmJudge {
+    /// Create a judge asking whether the response is correct and helpful
+    pub fn new(executor: Arc<RuntimeExecutor>, model: impl Into<String>) -> Self {
+        Self {
+            executor,
+            model: model.into(),
+            rubric: "The response correctly and helpfully answers the conversation, \
+                     agreeing with the reference answer when one is given."
+                .to_string(),
+            prompt: DEFAULT_JUDGE_PROMPT.to_string(),
+            threshold: 0.5,
+        }
+    }
+
+    /// Set the grading rubric
+    pub fn with_rubric(mut self, rubric: impl Into<String>) -> Self {
+        self.rubric = rubric.into();
+        self
+    }
+
+    /// Replace the judge's system prompt
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Minimum score to pass (default: 0.5)
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Render the case and output for the judge
+    fn render(&self, case: &EvalCase, output: &TextResult) -> String {
+        let mut text = format!("Rubric:\n{}\n\nConversation:\n", self.rubric);
+        for msg in &case.messages {
+            let content: Vec<_> = msg
+                .content
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            let role = match msg.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+                Role::Tool => "tool",
+            };
+            text.push_str(&format!("{}: {}\n", role, content.join("")));
+        }
+        if let Some(expected) = &case.expected {
+            let expected = match expected.as_str() {
+                Some(expected) => expected.to_string(),
+                None => expected.to_string(),
+            };
+            text.push_str(&format!("\nReference answer:\n{}\n", expected));
+        }
+        text.push_str(&format!("\nResponse to grade:\n{}", output.content));
+        text
+    }
+}
+
+impl std::fmt::Debug for LlmJudge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlmJudge")
+            .field("model", &self.model)
+            .field("rubric", &self.rubric)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+struct Verdict {
+    score: f64,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[async_trait]
+impl Grader for LlmJudge {
+    fn name(&self) -> &str {
+        "llm_judge"
+    }
+
+    async fn grade(&self, case: &EvalCase, output: &TextResult) -> Result<Grade, AiError> {
+        let params = ObjectParams::new(
+            vec![
+                Message::system(self.prompt.clone()),
+                Message::user(self.render(case, output)),
+            ],
+            json!({
+                "type": "object",
+                "properties": {
+                    "score": {"type": "number"},
+                    "reason": {"type": "string"}
+                },
+                "required": ["score", "reason"],
+                "additionalProperties": false
+            }),
+        )
+        .with_temperature(0.0);
+        let result = self.executor.generate_object(&self.model, params).await?;
+        let verdict: Verdict = serde_json::from_value(result.object)?;
+
+        let score = verdict.score.clamp(0.0, 1.0);
+        Ok(Grade {
+            score,
+            passed: score >= self.threshold,
+            reason: verdict.reason,
+        })
+    }
+}
@@ -0,0 +1,38 @@
+//! # Aidale Eval
+//!
+//! Evaluation of prompts and models on top of
+//! [`RuntimeExecutor`](aidale_core::RuntimeExecutor).
+//!
+//! A [`Dataset`] holds prompts with reference answers, [`Grader`]s score each
+//! output ([`ExactMatch`], [`JsonFieldMatch`], or [`LlmJudge`] using a
+//! second executor), and an [`Evaluator`] runs the dataset concurrently
+//! through one or more [`EvalTarget`]s, producing per-model score, cost and
+//! latency reports.
+//!
+//! ```ignore
+//! let dataset = Dataset::load_jsonl("evals/capitals.jsonl")?;
+//! let evaluator = Evaluator::new(dataset)
+//!     .with_grader(ExactMatch::new().with_case_insensitive(true))
+//!     .with_grader(LlmJudge::new(judge_executor, "gpt-4o"))
+//!     .with_pricing("gpt-4o-mini", ModelPricing::new(0.15, 0.6))
+//!     .with_concurrency(8);
+//!
+//! let report = evaluator
+//!     .run_all(&[
+//!         EvalTarget::new(openai.clone(), "gpt-4o-mini"),
+//!         EvalTarget::new(deepseek.clone(), "deepseek-chat"),
+//!     ])
+//!     .await;
+//! println!("{}", report.to_markdown());
+//! ```
+
+pub mod dataset;
+pub mod grader;
+pub mod report;
+pub mod runner;
+
+// Re-exports
+pub use dataset::{Dataset, EvalCase};
+pub use grader::{ExactMatch, Grade, Grader, JsonFieldMatch, LlmJudge};
+pub use report::{CaseResult, EvalReport, ModelPricing, ModelReport, ReportSummary};
+pub use runner::{EvalTarget, Evaluator};
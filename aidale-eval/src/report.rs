@@ -0,0 +1,194 @@
+//! Per-model score, cost and latency reports.
+
+use crate::grader::Grade;
+use aidale_core::types::Usage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Token prices of a model, in currency units per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPricing {
+    /// Create pricing from per-million-token prices
+    pub fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+        }
+    }
+
+    /// Cost of a request
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Outcome of one case for one model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseResult {
+    pub case_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Generation error; the case scores zero
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Grades by grader name
+    pub grades: BTreeMap<String, Grade>,
+    pub usage: Usage,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+}
+
+impl CaseResult {
+    /// Mean score over all graders (zero on error or without graders)
+    pub fn score(&self) -> f64 {
+        if self.error.is_some() || self.grades.is_empty() {
+            return 0.0;
+        }
+        self.grades.values().map(|grade| grade.score).sum::<f64>() / self.grades.len() as f64
+    }
+
+    /// Whether generation succeeded and every grader passed
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.grades.values().all(|grade| grade.passed)
+    }
+}
+
+/// Aggregates of a [`ModelReport`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportSummary {
+    pub cases: usize,
+    pub errors: usize,
+    pub mean_score: f64,
+    pub pass_rate: f64,
+    /// Mean score by grader name
+    pub grader_scores: BTreeMap<String, f64>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Total cost, when pricing is known for the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_cost: Option<f64>,
+    pub mean_latency_ms: u64,
+    pub p50_latency_ms: u64,
+    pub p95_latency_ms: u64,
+}
+
+impl ReportSummary {
+    fn new(cases: &[CaseResult]) -> Self {
+        let count = cases.len().max(1) as f64;
+
+        let mut grader_totals: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+        for (name, grade) in cases.iter().flat_map(|case| &case.grades) {
+            let total = grader_totals.entry(name.clone()).or_default();
+            total.0 += grade.score;
+            total.1 += 1;
+        }
+
+        let costs: Vec<_> = cases.iter().filter_map(|case| case.cost).collect();
+        let mut latencies: Vec<_> = cases.iter().map(|case| case.latency_ms).collect();
+        latencies.sort_unstable();
+        let percentile = |p: f64| -> u64 {
+            if latencies.is_empty() {
+                return 0;
+            }
+            let rank = (p * latencies.len() as f64).ceil() as usize;
+            latencies[rank.clamp(1, latencies.len()) - 1]
+        };
+
+        Self {
+            cases: cases.len(),
+            errors: cases.iter().filter(|case| case.error.is_some()).count(),
+            mean_score: cases.iter().map(CaseResult::score).sum::<f64>() / count,
+            pass_rate: cases.iter().filter(|case| case.passed()).count() as f64 / count,
+            grader_scores: grader_totals
+                .into_iter()
+                .map(|(name, (total, n))| (name, total / n as f64))
+                .collect(),
+            prompt_tokens: cases
+                .iter()
+                .map(|case| case.usage.prompt_tokens as u64)
+                .sum(),
+            completion_tokens: cases
+                .iter()
+                .map(|case| case.usage.completion_tokens as u64)
+                .sum(),
+            total_cost: (!costs.is_empty()).then(|| costs.iter().sum()),
+            mean_latency_ms: (latencies.iter().sum::<u64>() as f64 / count).round() as u64,
+            p50_latency_ms: percentile(0.5),
+            p95_latency_ms: percentile(0.95),
+        }
+    }
+}
+
+/// Results of one model on a dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelReport {
+    /// Target label, the model name unless set otherwise
+    pub label: String,
+    pub model: String,
+    pub summary: ReportSummary,
+    pub cases: Vec<CaseResult>,
+}
+
+impl ModelReport {
+    /// Build a report, computing its summary
+    pub fn new(label: impl Into<String>, model: impl Into<String>, cases: Vec<CaseResult>) -> Self {
+        Self {
+            label: label.into(),
+            model: model.into(),
+            summary: ReportSummary::new(&cases),
+            cases,
+        }
+    }
+}
+
+/// Results of several models on the same dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub dataset: String,
+    pub models: Vec<ModelReport>,
+}
+
+impl EvalReport {
+    /// Model with the highest mean score
+    pub fn best(&self) -> Option<&ModelReport> {
+        self.models
+            .iter()
+            .max_by(|a, b| a.summary.mean_score.total_cmp(&b.summary.mean_score))
+    }
+
+    /// Markdown comparison table, one row per model
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("## {}\n\n", self.dataset);
+        out.push_str("| model | score | pass rate | errors | p50 ms | p95 ms | tokens | cost |\n");
+        out.push_str("|---|---|---|---|---|---|---|---|\n");
+        for report in &self.models {
+            let summary = &report.summary;
+            let cost = summary
+                .total_cost
+                .map(|cost| format!("{:.4}", cost))
+                .unwrap_or_else(|| "-".to_string());
+            let _ = writeln!(
+                out,
+                "| {} | {:.3} | {:.1}% | {} | {} | {} | {} | {} |",
+                report.label,
+                summary.mean_score,
+                summary.pass_rate * 100.0,
+                summary.errors,
+                summary.p50_latency_ms,
+                summary.p95_latency_ms,
+                summary.prompt_tokens + summary.completion_tokens,
+                cost
+            );
+        }
+        out
+    }
+}
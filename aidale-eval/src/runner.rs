@@ -0,0 +1,269 @@
+//! Running datasets through executors.
+
+use crate::dataset::{Dataset, EvalCase};
+use crate::grader::{Grade, Grader};
+use crate::report::{CaseResult, EvalReport, ModelPricing, ModelReport};
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_core::types::*;
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Model to evaluate, with the executor serving it
+#[derive(Clone)]
+pub struct EvalTarget {
+    pub label: String,
+    pub executor: Arc<RuntimeExecutor>,
+    pub model: String,
+}
+
+impl EvalTarget {
+    /// Create a target labelled with the model name
+    pub fn new(executor: Arc<RuntimeExecutor>, model: impl Into<String>) -> Self {
+        let model = model.into();
+        Self {
+            label: model.clone(),
+            executor,
+            model,
+        }
+    }
+
+    /// Label the target in reports, e.g. to compare two prompts on one model
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+}
+
+impl std::fmt::Debug for EvalTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvalTarget")
+            .field("label", &self.label)
+            .field("model", &self.model)
+            .finish()
+    }
+}
+
+/// Runs a dataset through targets and grades the outputs
+///
+/// Cases of one target run concurrently; targets run one after another so
+/// their latencies are comparable. Generation and grader errors are
+/// recorded in the report rather than aborting the run.
+pub struct Evaluator {
+    dataset: Dataset,
+    graders: Vec<Arc<dyn Grader>>,
+    params: TextParams,
+    concurrency: usize,
+    pricing: HashMap<String, ModelPricing>,
+}
+
+impl Evaluator {
+    /// Create an evaluator running 4 cases at a time
+    pub fn new(dataset: Dataset) -> Self {
+        Self {
+            dataset,
+            graders: Vec::new(),
+            params: TextParams::new(Vec::new()),
+            concurrency: 4,
+            pricing: HashMap::new(),
+        }
+    }
+
+    /// Add a grader
+    pub fn with_grader(mut self, grader: impl Grader + 'static) -> Self {
+        self.graders.push(Arc::new(grader));
+        self
+    }
+
+    /// Generation parameters for every case; their messages are replaced by
+    /// each case's messages
+    pub fn with_params(mut self, params: TextParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Maximum number of cases in flight per target
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the token prices of a model, enabling cost reporting for it
+    pub fn with_pricing(mut self, model: impl Into<String>, pricing: ModelPricing) -> Self {
+        self.pricing.insert(model.into(), pricing);
+        self
+    }
+
+    /// The dataset being evaluated
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    /// Evaluate one target
+    pub async fn run(&self, target: &EvalTarget) -> ModelReport {
+        let cases = futures::stream::iter(&self.dataset.cases)
+            .map(|case| self.run_case(target, case))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        ModelReport::new(&target.label, &target.model, cases)
+    }
+
+    /// Evaluate several targets on the same dataset
+    pub async fn run_all(&self, targets: &[EvalTarget]) -> EvalReport {
+        let mut models = Vec::with_capacity(targets.len());
+        for target in targets {
+            models.push(self.run(target).await);
+        }
+        EvalReport {
+            dataset: self.dataset.name.clone(),
+            models,
+        }
+    }
+
+    async fn run_case(&self, target: &EvalTarget, case: &EvalCase) -> CaseResult {
+        let mut params = self.params.clone();
+        params.messages = case.messages.clone();
+
+        let started = Instant::now();
+        let result = target.executor.generate_text(&target.model, params).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let output = match result {
+            Ok(output) => output,
+            Err(err) => {
+                tracing::debug!(case = %case.id, model = %target.model, "Eval case failed: {}", err);
+                return CaseResult {
+                    case_id: case.id.clone(),
+                    output: None,
+                    error: Some(err.to_string()),
+                    grades: BTreeMap::new(),
+                    usage: Usage::default(),
+                    latency_ms,
+                    cost: None,
+                };
+            }
+        };
+
+        let mut grades = BTreeMap::new();
+        for grader in &self.graders {
+            let grade = grader
+                .grade(case, &output)
+                .await
+                .unwrap_or_else(|err| Grade::fail(format!("grader error: {}", err)));
+            grades.insert(grader.name().to_string(), grade);
+        }
+
+        CaseResult {
+            case_id: case.id.clone(),
+            cost: self
+                .pricing
+                .get(&target.model)
+                .map(|pricing| pricing.cost(&output.usage)),
+            output: Some(output.content),
+            error: None,
+            grades,
+            usage: output.usage,
+            latency_ms,
+        }
+    }
+}
+
+impl std::fmt::Debug for Evaluator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let graders: Vec<_> = self.graders.iter().map(|grader| grader.name()).collect();
+        f.debug_struct("Evaluator")
+            .field("dataset", &self.dataset.name)
+            .field("cases", &self.dataset.len())
+            .field("graders", &graders)
+            .field("concurrency", &self.concurrency)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grader::{ExactMatch, JsonFieldMatch, LlmJudge};
+    use aidale_test::MockProvider;
+    use serde_json::json;
+
+    fn target(mock: MockProvider, model: &str) -> EvalTarget {
+        EvalTarget::new(Arc::new(RuntimeExecutor::builder(mock).finish()), model)
+    }
+
+    #[tokio::test]
+    async fn test_graders_and_report() {
+        let dataset = Dataset::from_jsonl(
+            "capitals",
+            r#"{"id": "fr", "prompt": "Capital of France?", "expected": "Paris"}
+{"id": "de", "prompt": "Capital of Germany?", "expected": "Berlin"}
+{"id": "it", "prompt": "Capital of Italy?", "expected": "Rome"}"#,
+        )
+        .unwrap();
+
+        let judge = MockProvider::new()
+            .with_text(r#"{"score": 1.0, "reason": "correct"}"#)
+            .with_text(r#"{"score": 0.2, "reason": "wrong city"}"#);
+        let judge = Arc::new(RuntimeExecutor::builder(judge).finish());
+
+        let evaluator = Evaluator::new(dataset)
+            .with_grader(ExactMatch::new().with_case_insensitive(true))
+            .with_grader(LlmJudge::new(judge, "judge"))
+            .with_concurrency(1)
+            .with_pricing("small", ModelPricing::new(1.0, 2.0));
+
+        let mock = MockProvider::new()
+            .with_response(ChatCompletionResponse {
+                id: String::new(),
+                model: String::new(),
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(" paris\n"),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    prompt_tokens: 10,
+                    completion_tokens: 5,
+                    ..Default::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+            .with_text("Munich")
+            .with_error(aidale_core::error::AiError::timeout("slow"));
+
+        let report = evaluator.run_all(&[target(mock, "small")]).await;
+        let summary = &report.models[0].summary;
+        assert_eq!(summary.cases, 3);
+        assert_eq!(summary.errors, 1);
+        assert!((summary.pass_rate - 1.0 / 3.0).abs() < 1e-9);
+        assert!((summary.grader_scores["exact_match"] - 0.5).abs() < 1e-9);
+        assert!((summary.grader_scores["llm_judge"] - 0.6).abs() < 1e-9);
+        assert!((summary.total_cost.unwrap() - 20.0 / 1_000_000.0).abs() < 1e-12);
+        assert!(report
+            .to_markdown()
+            .contains("| small | 0.367 | 33.3% | 1 |"));
+
+        let case =
+            EvalCase::new("json", "Extract").with_expected(json!({"name": "Ada", "age": 36}));
+        let output = TextResult {
+            content: "```json\n{\"name\": \"Ada\", \"age\": 37}\n```".to_string(),
+            finish_reason: FinishReason::Stop,
+            usage: Usage::default(),
+            model: "m".to_string(),
+            tool_calls: None,
+            reasoning: None,
+            citations: None,
+            metadata: HashMap::new(),
+        };
+        let grade = JsonFieldMatch::new().grade(&case, &output).await.unwrap();
+        assert_eq!(grade.score, 0.5);
+        assert!(!grade.passed);
+    }
+}
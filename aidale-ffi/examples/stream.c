@@ -0,0 +1,54 @@
+/*
+ * Stream a completion to stdout.
+ *
+ *   cargo build --release -p aidale-ffi
+ *   cc examples/stream.c -Iinclude -L../target/release -laidale -o stream
+ *   OPENAI_API_KEY=sk-... LD_LIBRARY_PATH=../target/release ./stream
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "aidale.h"
+
+static int print_chunk(const char *chunk, void *user_data) {
+    (void)user_data;
+    /* A real host would parse the JSON; print it as-is */
+    printf("%s\n", chunk);
+    return 0;
+}
+
+int main(void) {
+    const char *api_key = getenv("OPENAI_API_KEY");
+    const char *base_url = getenv("OPENAI_BASE_URL");
+    char config[1024];
+    char *error = NULL;
+    AidaleExecutor *executor = NULL;
+
+    if (api_key == NULL) {
+        fprintf(stderr, "OPENAI_API_KEY is not set\n");
+        return 1;
+    }
+    snprintf(config, sizeof config,
+             "{\"provider\":\"openai\",\"api_key\":\"%s\",\"base_url\":%s%s%s,"
+             "\"layers\":[{\"type\":\"retry\",\"max_retries\":3}]}",
+             api_key, base_url ? "\"" : "", base_url ? base_url : "null", base_url ? "\"" : "");
+
+    if (aidale_executor_new(config, &executor, &error) != AIDALE_OK) {
+        fprintf(stderr, "%s\n", error);
+        aidale_string_free(error);
+        return 1;
+    }
+
+    const char *request =
+        "{\"model\":\"gpt-4o-mini\",\"messages\":[{\"role\":\"user\",\"content\":\"Hello\"}]}";
+    AidaleStatus status = aidale_stream(executor, request, print_chunk, NULL, &error);
+    if (status != AIDALE_OK) {
+        fprintf(stderr, "%s\n", error);
+        aidale_string_free(error);
+    }
+
+    aidale_executor_free(executor);
+    return status == AIDALE_OK ? 0 : 1;
+}
@@ -0,0 +1,77 @@
+/*
+ * Aidale C ABI
+ *
+ * Requests, responses and stream chunks are OpenAI chat completion JSON.
+ * Calls block the calling thread; executor handles may be shared between
+ * threads. On failure `*error` receives an OpenAI-style error body, to be
+ * released with aidale_string_free. `error` may be NULL.
+ */
+
+#ifndef AIDALE_H
+#define AIDALE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum AidaleStatus {
+    AIDALE_OK = 0,
+    /* Null pointer, invalid UTF-8 or malformed JSON */
+    AIDALE_INVALID_ARGUMENT = 1,
+    /* The provider or a layer failed the request */
+    AIDALE_REQUEST_FAILED = 2,
+    /* The stream callback asked to stop */
+    AIDALE_CANCELLED = 3,
+    /* A bug inside Aidale; the handle remains usable */
+    AIDALE_PANIC = 4,
+} AidaleStatus;
+
+/* Opaque executor handle */
+typedef struct AidaleExecutor AidaleExecutor;
+
+/*
+ * Receives one chat.completion.chunk JSON object, valid for the duration of
+ * the call; returning non-zero stops the stream
+ */
+typedef int (*AidaleChunkCallback)(const char *chunk, void *user_data);
+
+/*
+ * Create an executor from a JSON configuration:
+ *
+ *   {"provider": "openai", "api_key": "sk-...", "base_url": null,
+ *    "layers": [{"type": "retry", "max_retries": 3}]}
+ *
+ * `provider` is one of openai, openai_responses, cohere or deepseek.
+ */
+AidaleStatus aidale_executor_new(const char *config, AidaleExecutor **out, char **error);
+
+/* Destroy an executor; NULL is ignored */
+void aidale_executor_free(AidaleExecutor *executor);
+
+/* Send a chat completion request; `*response` receives the chat.completion */
+AidaleStatus aidale_chat(const AidaleExecutor *executor,
+                         const char *request,
+                         char **response,
+                         char **error);
+
+/*
+ * Stream a chat completion, calling `on_chunk` for every chunk on the calling
+ * thread. Returns AIDALE_CANCELLED if the callback stopped the stream.
+ */
+AidaleStatus aidale_stream(const AidaleExecutor *executor,
+                           const char *request,
+                           AidaleChunkCallback on_chunk,
+                           void *user_data,
+                           char **error);
+
+/* Release a string returned by Aidale; NULL is ignored */
+void aidale_string_free(char *text);
+
+/* Library version, e.g. "0.1.0"; statically allocated */
+const char *aidale_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AIDALE_H */
@@ -0,0 +1,474 @@
+//! # Aidale C ABI
+//!
+//! Embeds Aidale in non-Rust applications (game engines, C++ services,
+//! other language runtimes) through a small C interface, declared in
+//! `include/aidale.h`:
+//!
+//! - an executor is an opaque handle created from a JSON configuration
+//!   naming the provider and layer stack
+//! - requests, responses and stream chunks are OpenAI chat completion JSON
+//! - streaming delivers chunks to a callback, which can stop the stream
+//!
+//! Calls block the calling thread while the executor's own Tokio runtime
+//! does the I/O, so a host with a frame loop calls them from a worker
+//! thread. Handles may be shared between threads. Every function returns an
+//! [`AidaleStatus`]; on failure `*error` receives an OpenAI-style error body
+//! to be released with [`aidale_string_free`]. Panics never cross the
+//! boundary and are reported as [`AidaleStatus::Panic`].
+
+use aidale_core::error::AiError;
+use aidale_core::provider::Provider;
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_layer::{build_layer_stack, LayerDescriptor};
+use aidale_provider::{CohereProvider, OpenAiProvider, OpenAiResponsesProvider};
+use aidale_serve::convert::{
+    error_json, parse_request, response_json, ChunkEncoder, OpenAiRequest,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::Value;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Result of an FFI call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AidaleStatus {
+    Ok = 0,
+    /// Null pointer, invalid UTF-8 or malformed JSON
+    InvalidArgument = 1,
+    /// The provider or a layer failed the request
+    RequestFailed = 2,
+    /// The stream callback asked to stop
+    Cancelled = 3,
+    /// A bug inside Aidale; the handle remains usable
+    Panic = 4,
+}
+
+/// Executor configuration
+#[derive(Debug, Deserialize)]
+struct Config {
+    /// `openai`, `openai_responses`, `cohere` or `deepseek`
+    provider: String,
+    api_key: String,
+    #[serde(default)]
+    base_url: Option<String>,
+    #[serde(default)]
+    layers: Vec<LayerDescriptor>,
+}
+
+impl Config {
+    fn provider(&self) -> Result<Arc<dyn Provider>, AiError> {
+        let api_key = self.api_key.clone();
+        let provider: Arc<dyn Provider> = match self.provider.as_str() {
+            "openai" => {
+                let mut builder = OpenAiProvider::builder().api_key(api_key);
+                if let Some(base_url) = &self.base_url {
+                    builder = builder.api_base(base_url.clone());
+                }
+                Arc::new(builder.build()?)
+            }
+            "openai_responses" => {
+                let mut provider = OpenAiResponsesProvider::new(api_key);
+                if let Some(base_url) = &self.base_url {
+                    provider = provider.with_api_base(base_url.clone());
+                }
+                Arc::new(provider)
+            }
+            "cohere" => {
+                let mut provider = CohereProvider::new(api_key);
+                if let Some(base_url) = &self.base_url {
+                    provider = provider.with_api_base(base_url.clone());
+                }
+                Arc::new(provider)
+            }
+            "deepseek" => Arc::new(aidale_provider::deepseek(api_key)?),
+            other => {
+                return Err(AiError::configuration(format!(
+                    "Unknown provider: {}",
+                    other
+                )))
+            }
+        };
+        build_layer_stack(provider, &self.layers)
+    }
+}
+
+/// Opaque executor handle
+pub struct AidaleExecutor {
+    runtime: tokio::runtime::Runtime,
+    executor: Arc<RuntimeExecutor>,
+}
+
+/// Receives one `chat.completion.chunk` JSON object, valid for the duration
+/// of the call; returning non-zero stops the stream
+pub type AidaleChunkCallback =
+    Option<unsafe extern "C" fn(chunk: *const c_char, user_data: *mut c_void) -> c_int>;
+
+/// Failure of a call, before it is handed to C
+struct Failure {
+    status: AidaleStatus,
+    error: AiError,
+}
+
+impl Failure {
+    fn invalid(message: impl Into<String>) -> Self {
+        Self {
+            status: AidaleStatus::InvalidArgument,
+            error: AiError::invalid_request(message.into()),
+        }
+    }
+
+    fn request(error: AiError) -> Self {
+        let status = match error {
+            AiError::InvalidRequest { .. } => AidaleStatus::InvalidArgument,
+            _ => AidaleStatus::RequestFailed,
+        };
+        Self { status, error }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Copy a string for C; interior NULs cannot occur in serialized JSON
+fn into_c_string(text: String) -> *mut c_char {
+    CString::new(text)
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// Read a JSON argument
+///
+/// # Safety
+///
+/// `ptr` must be null or a valid NUL-terminated string.
+unsafe fn read_json(ptr: *const c_char, name: &str) -> Result<Value, Failure> {
+    if ptr.is_null() {
+        return Err(Failure::invalid(format!("'{}' is null", name)));
+    }
+    let text = CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| Failure::invalid(format!("'{}' is not valid UTF-8", name)))?;
+    serde_json::from_str(text)
+        .map_err(|e| Failure::invalid(format!("'{}' is not valid JSON: {}", name, e)))
+}
+
+/// Run `body`, reporting failures and panics through `error`
+///
+/// # Safety
+///
+/// `error` must be null or valid for writes.
+unsafe fn ffi_call<F>(error: *mut *mut c_char, body: F) -> AidaleStatus
+where
+    F: FnOnce() -> Result<AidaleStatus, Failure>,
+{
+    if !error.is_null() {
+        *error = std::ptr::null_mut();
+    }
+    let failure = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(status)) => return status,
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure {
+            status: AidaleStatus::Panic,
+            error: AiError::other("Aidale panicked"),
+        },
+    };
+    if !error.is_null() {
+        let (_, body) = error_json(&failure.error);
+        *error = into_c_string(body.to_string());
+    }
+    failure.status
+}
+
+/// Create an executor from a JSON configuration:
+///
+/// ```json
+/// {"provider": "openai", "api_key": "sk-...", "base_url": null,
+///  "layers": [{"type": "retry", "max_retries": 3}]}
+/// ```
+///
+/// # Safety
+///
+/// `config` must be a valid NUL-terminated string, `out` valid for writes,
+/// and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn aidale_executor_new(
+    config: *const c_char,
+    out: *mut *mut AidaleExecutor,
+    error: *mut *mut c_char,
+) -> AidaleStatus {
+    ffi_call(error, || {
+        if out.is_null() {
+            return Err(Failure::invalid("'out' is null"));
+        }
+        let config: Config = serde_json::from_value(read_json(config, "config")?)
+            .map_err(|e| Failure::invalid(format!("Invalid config: {}", e)))?;
+        let provider = config.provider().map_err(|error| Failure {
+            status: AidaleStatus::InvalidArgument,
+            error,
+        })?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("aidale")
+            .build()
+            .map_err(|e| Failure {
+                status: AidaleStatus::RequestFailed,
+                error: AiError::other(format!("Failed to start the runtime: {}", e)),
+            })?;
+        let handle = AidaleExecutor {
+            runtime,
+            executor: Arc::new(RuntimeExecutor::builder(provider).finish()),
+        };
+        *out = Box::into_raw(Box::new(handle));
+        Ok(AidaleStatus::Ok)
+    })
+}
+
+/// Destroy an executor, waiting for its runtime to stop
+///
+/// # Safety
+///
+/// `executor` must be null or a handle from [`aidale_executor_new`] that is
+/// not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn aidale_executor_free(executor: *mut AidaleExecutor) {
+    if !executor.is_null() {
+        drop(Box::from_raw(executor));
+    }
+}
+
+/// Send a chat completion request; on success `*response` receives the
+/// `chat.completion` JSON
+///
+/// # Safety
+///
+/// `executor` must be a live handle, `request` a valid NUL-terminated
+/// string, `response` valid for writes, and `error` null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn aidale_chat(
+    executor: *const AidaleExecutor,
+    request: *const c_char,
+    response: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> AidaleStatus {
+    ffi_call(error, || {
+        let Some(handle) = executor.as_ref() else {
+            return Err(Failure::invalid("'executor' is null"));
+        };
+        if response.is_null() {
+            return Err(Failure::invalid("'response' is null"));
+        }
+        let OpenAiRequest { request, .. } =
+            parse_request(read_json(request, "request")?).map_err(Failure::request)?;
+
+        let result = handle
+            .runtime
+            .block_on(handle.executor.chat_completion(request))
+            .map_err(Failure::request)?;
+        *response = into_c_string(response_json(&result, unix_time()).to_string());
+        Ok(AidaleStatus::Ok)
+    })
+}
+
+/// Stream a chat completion, calling `on_chunk` for every
+/// `chat.completion.chunk` on the calling thread
+///
+/// Returns [`AidaleStatus::Cancelled`] if the callback stopped the stream.
+///
+/// # Safety
+///
+/// `executor` must be a live handle, `request` a valid NUL-terminated
+/// string, `on_chunk` safe to call with `user_data`, and `error` null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn aidale_stream(
+    executor: *const AidaleExecutor,
+    request: *const c_char,
+    on_chunk: AidaleChunkCallback,
+    user_data: *mut c_void,
+    error: *mut *mut c_char,
+) -> AidaleStatus {
+    ffi_call(error, || {
+        let Some(handle) = executor.as_ref() else {
+            return Err(Failure::invalid("'executor' is null"));
+        };
+        let Some(on_chunk) = on_chunk else {
+            return Err(Failure::invalid("'on_chunk' is null"));
+        };
+        let OpenAiRequest {
+            request,
+            include_usage,
+            ..
+        } = parse_request(read_json(request, "request")?).map_err(Failure::request)?;
+
+        handle.runtime.block_on(async {
+            let mut chunks = handle
+                .executor
+                .stream(request)
+                .await
+                .map_err(Failure::request)?;
+            let mut encoder = ChunkEncoder::new(unix_time(), include_usage);
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk.map_err(Failure::request)?;
+                for body in encoder.encode(&chunk) {
+                    let Ok(body) = CString::new(body.to_string()) else {
+                        continue;
+                    };
+                    if on_chunk(body.as_ptr(), user_data) != 0 {
+                        return Ok(AidaleStatus::Cancelled);
+                    }
+                }
+            }
+            Ok(AidaleStatus::Ok)
+        })
+    })
+}
+
+/// Release a string returned by Aidale
+///
+/// # Safety
+///
+/// `text` must be null or a string from this library, not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn aidale_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+/// Library version, e.g. `0.1.0`; statically allocated
+#[no_mangle]
+pub extern "C" fn aidale_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::ptr;
+
+    /// OpenAI-compatible server answering `requests` connections
+    fn serve(requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let (content_type, body) = if String::from_utf8_lossy(&body)
+                    .contains("\"stream\":true")
+                {
+                    let chunk = |delta: &str, finish: &str| {
+                        format!(
+                            "data: {{\"id\":\"c\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{{\"index\":0,\"delta\":{},\"finish_reason\":{}}}]}}\n\n",
+                            delta, finish
+                        )
+                    };
+                    let body = chunk("{\"content\":\"Hel\"}", "null")
+                        + &chunk("{\"content\":\"lo\"}", "null")
+                        + &chunk("{}", "\"stop\"")
+                        + "data: [DONE]\n\n";
+                    ("text/event-stream", body)
+                } else {
+                    let body = r#"{"id":"c","object":"chat.completion","created":1,"model":"m","choices":[{"index":0,"message":{"role":"assistant","content":"Hello"},"finish_reason":"stop"}]}"#;
+                    ("application/json", body.to_string())
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    content_type,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}/v1", addr)
+    }
+
+    unsafe extern "C" fn collect(chunk: *const c_char, user_data: *mut c_void) -> c_int {
+        let chunks = &mut *(user_data as *mut Vec<Value>);
+        chunks.push(serde_json::from_str(CStr::from_ptr(chunk).to_str().unwrap()).unwrap());
+        0
+    }
+
+    #[test]
+    fn test_executor() {
+        let base_url = serve(2);
+        let config = CString::new(
+            serde_json::json!({"provider": "openai", "api_key": "k", "base_url": base_url})
+                .to_string(),
+        )
+        .unwrap();
+        let request =
+            CString::new(r#"{"model":"m","messages":[{"role":"user","content":"hi"}]}"#).unwrap();
+
+        unsafe {
+            let mut executor = ptr::null_mut();
+            let mut error = ptr::null_mut();
+            let bad = CString::new(r#"{"provider":"nope","api_key":"k"}"#).unwrap();
+            let status = aidale_executor_new(bad.as_ptr(), &mut executor, &mut error);
+            assert_eq!(status, AidaleStatus::InvalidArgument);
+            assert!(CStr::from_ptr(error)
+                .to_str()
+                .unwrap()
+                .contains("Unknown provider"));
+            aidale_string_free(error);
+
+            let status = aidale_executor_new(config.as_ptr(), &mut executor, &mut error);
+            assert_eq!(status, AidaleStatus::Ok);
+
+            let mut response = ptr::null_mut();
+            let status = aidale_chat(executor, request.as_ptr(), &mut response, &mut error);
+            assert_eq!(status, AidaleStatus::Ok);
+            let body: Value =
+                serde_json::from_str(CStr::from_ptr(response).to_str().unwrap()).unwrap();
+            assert_eq!(body["choices"][0]["message"]["content"], "Hello");
+            aidale_string_free(response);
+
+            let mut chunks: Vec<Value> = Vec::new();
+            let user_data = &mut chunks as *mut Vec<Value> as *mut c_void;
+            let status = aidale_stream(
+                executor,
+                request.as_ptr(),
+                Some(collect),
+                user_data,
+                &mut error,
+            );
+            assert_eq!(status, AidaleStatus::Ok);
+            let text: String = chunks
+                .iter()
+                .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+                .collect();
+            assert_eq!(text, "Hello");
+
+            let status = aidale_chat(executor, ptr::null(), &mut response, &mut error);
+            assert_eq!(status, AidaleStatus::InvalidArgument);
+            aidale_string_free(error);
+            aidale_executor_free(executor);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! axum responses (requires the `axum` feature).
+//!
+//! ```ignore
+//! async fn chat(State(executor): State<Arc<RuntimeExecutor>>, Json(body): Json<ChatBody>) -> Response {
+//!     match executor.stream_text("gpt-4o-mini", body.into_params()).await {
+//!         Ok(streamed) => aidale_http::axum::vercel_ai_response(streamed),
+//!         Err(err) => (StatusCode::BAD_GATEWAY, err.to_string()).into_response(),
+//!     }
+//! }
+//! ```
+
+use crate::{sse, vercel, SSE_HEADERS, VERCEL_AI_HEADERS};
+use aidale_core::error::AiError;
+use aidale_core::types::TextChunk;
+use axum_core::body::Body;
+use axum_core::response::Response;
+use bytes::Bytes;
+use futures::Stream;
+use http::header::{HeaderName, HeaderValue};
+use std::convert::Infallible;
+
+fn streaming_response<B>(body: B, headers: &[(&'static str, &'static str)]) -> Response
+where
+    B: Stream<Item = Result<Bytes, Infallible>> + Send + 'static,
+{
+    let mut response = Response::new(Body::from_stream(body));
+    for (name, value) in headers {
+        response.headers_mut().insert(
+            HeaderName::from_static(name),
+            HeaderValue::from_static(value),
+        );
+    }
+    response
+}
+
+/// Serve a text chunk stream as server-sent events
+pub fn sse_response<S>(chunks: S) -> Response
+where
+    S: Stream<Item = Result<TextChunk, AiError>> + Send + 'static,
+{
+    streaming_response(sse::text_sse(chunks), SSE_HEADERS)
+}
+
+/// Serve a text chunk stream using the Vercel AI SDK UI message stream
+/// protocol
+pub fn vercel_ai_response<S>(chunks: S) -> Response
+where
+    S: Stream<Item = Result<TextChunk, AiError>> + Send + 'static,
+{
+    streaming_response(vercel::ui_message_stream(chunks), VERCEL_AI_HEADERS)
+}
@@ -0,0 +1,50 @@
+//! # Aidale HTTP
+//!
+//! Helpers for serving Aidale text streams from web frameworks.
+//!
+//! - [`sse`]: plain server-sent events, one JSON [`TextChunk`] per event
+//! - [`vercel`]: the Vercel AI SDK UI message stream protocol, consumable by
+//!   `useChat` from `@ai-sdk/react` and friends
+//! - `axum` feature: ready-made `Response`s for axum handlers
+//!
+//! The encoders produce `Stream<Item = Result<Bytes, Infallible>>`, which
+//! every framework built on `http-body` accepts. With actix-web:
+//!
+//! ```ignore
+//! let streamed = executor.stream_text("gpt-4o-mini", params).await?;
+//! let mut response = HttpResponse::Ok();
+//! for (name, value) in aidale_http::VERCEL_AI_HEADERS {
+//!     response.insert_header((*name, *value));
+//! }
+//! Ok(response.streaming(aidale_http::vercel::ui_message_stream(streamed)))
+//! ```
+//!
+//! [`TextChunk`]: aidale_core::types::TextChunk
+
+pub mod sse;
+pub mod vercel;
+
+#[cfg(feature = "axum")]
+pub mod axum;
+
+/// Response headers for a server-sent events stream
+pub const SSE_HEADERS: &[(&str, &str)] = &[
+    ("content-type", "text/event-stream"),
+    ("cache-control", "no-cache"),
+    ("connection", "keep-alive"),
+    // Keep nginx from buffering the stream
+    ("x-accel-buffering", "no"),
+];
+
+/// Response headers for a Vercel AI SDK UI message stream
+pub const VERCEL_AI_HEADERS: &[(&str, &str)] = &[
+    ("content-type", "text/event-stream"),
+    ("cache-control", "no-cache"),
+    ("connection", "keep-alive"),
+    ("x-accel-buffering", "no"),
+    ("x-vercel-ai-ui-message-stream", "v1"),
+];
+
+// Re-exports
+pub use sse::text_sse;
+pub use vercel::ui_message_stream;
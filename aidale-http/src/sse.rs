@@ -0,0 +1,88 @@
+//! Server-sent events encoding.
+
+use aidale_core::error::AiError;
+use aidale_core::types::TextChunk;
+use bytes::Bytes;
+use futures::stream::{self, Stream, StreamExt};
+use std::convert::Infallible;
+
+/// Terminal event sent after the last chunk, as in OpenAI's streaming API
+pub const DONE: &str = "[DONE]";
+
+/// Encode one SSE event.
+///
+/// Multi-line data is split over several `data:` lines so clients
+/// reassemble it unchanged.
+pub fn encode_event(event: Option<&str>, data: &str) -> Bytes {
+    let mut out = String::new();
+    if let Some(event) = event {
+        out.push_str("event: ");
+        out.push_str(event);
+        out.push('\n');
+    }
+    for line in data.split('\n') {
+        out.push_str("data: ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push('\n');
+    Bytes::from(out)
+}
+
+/// JSON payload describing a stream error
+pub fn error_payload(error: &AiError) -> serde_json::Value {
+    serde_json::json!({
+        "error": {
+            "message": error.to_string(),
+            "code": error.code(),
+        }
+    })
+}
+
+/// Convert a text chunk stream into SSE bytes.
+///
+/// Each chunk becomes a `data:` event holding the serialized [`TextChunk`];
+/// errors are sent as an `error` event, and the stream ends with
+/// `data: [DONE]`.
+pub fn text_sse<S>(chunks: S) -> impl Stream<Item = Result<Bytes, Infallible>> + Send
+where
+    S: Stream<Item = Result<TextChunk, AiError>> + Send + 'static,
+{
+    chunks
+        .map(|chunk| {
+            Ok(match chunk {
+                Ok(chunk) => encode_event(None, &serde_json::json!(chunk).to_string()),
+                Err(err) => encode_event(Some("error"), &error_payload(&err).to_string()),
+            })
+        })
+        .chain(stream::once(async { Ok(encode_event(None, DONE)) }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_text_sse() {
+        assert_eq!(
+            encode_event(Some("note"), "a\nb"),
+            "event: note\ndata: a\ndata: b\n\n"
+        );
+
+        let chunks = stream::iter(vec![
+            Ok(TextChunk {
+                delta: "Hi".to_string(),
+                reasoning: None,
+                tool_calls: None,
+                finish_reason: None,
+                usage: None,
+            }),
+            Err(AiError::stream("connection reset")),
+        ]);
+        let body: Vec<Bytes> = text_sse(chunks).map(Result::unwrap).collect().await;
+
+        assert_eq!(body[0], "data: {\"delta\":\"Hi\"}\n\n");
+        assert!(body[1].starts_with(b"event: error\n"));
+        assert_eq!(body[2], "data: [DONE]\n\n");
+    }
+}
@@ -0,0 +1,190 @@
+//! Vercel AI SDK UI message stream protocol.
+//!
+//! Encodes a [`TextChunk`] stream as the SSE-based protocol AI SDK 5 clients
+//! (`useChat`, `readUIMessageStream`) consume: a `start` part, `text-*` and
+//! `reasoning-*` parts wrapped in a step, a `finish` part and a final
+//! `[DONE]`. Serve it with [`VERCEL_AI_HEADERS`](crate::VERCEL_AI_HEADERS).
+
+use crate::sse::{encode_event, DONE};
+use aidale_core::error::AiError;
+use aidale_core::types::TextChunk;
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, Stream, StreamExt};
+use serde_json::json;
+use std::convert::Infallible;
+
+/// Stateful encoder turning text chunks into UI message stream parts
+#[derive(Debug)]
+pub struct UiMessageEncoder {
+    message_id: String,
+    started: bool,
+    text_open: bool,
+    reasoning_open: bool,
+}
+
+const TEXT_ID: &str = "text-0";
+const REASONING_ID: &str = "reasoning-0";
+
+impl UiMessageEncoder {
+    /// Create an encoder with a random message ID
+    pub fn new() -> Self {
+        Self::with_message_id(format!("msg_{}", uuid::Uuid::new_v4().simple()))
+    }
+
+    /// Create an encoder with a caller-chosen message ID
+    pub fn with_message_id(message_id: impl Into<String>) -> Self {
+        Self {
+            message_id: message_id.into(),
+            started: false,
+            text_open: false,
+            reasoning_open: false,
+        }
+    }
+
+    fn start(&mut self, parts: &mut Vec<serde_json::Value>) {
+        if !self.started {
+            self.started = true;
+            parts.push(json!({"type": "start", "messageId": self.message_id}));
+            parts.push(json!({"type": "start-step"}));
+        }
+    }
+
+    /// Parts for one chunk
+    pub fn chunk(&mut self, chunk: &TextChunk) -> Vec<serde_json::Value> {
+        let mut parts = Vec::new();
+        self.start(&mut parts);
+
+        if let Some(reasoning) = chunk.reasoning.as_deref().filter(|r| !r.is_empty()) {
+            if !self.reasoning_open {
+                self.reasoning_open = true;
+                parts.push(json!({"type": "reasoning-start", "id": REASONING_ID}));
+            }
+            parts.push(json!({"type": "reasoning-delta", "id": REASONING_ID, "delta": reasoning}));
+        }
+
+        if !chunk.delta.is_empty() {
+            // Reasoning precedes the answer
+            if self.reasoning_open {
+                self.reasoning_open = false;
+                parts.push(json!({"type": "reasoning-end", "id": REASONING_ID}));
+            }
+            if !self.text_open {
+                self.text_open = true;
+                parts.push(json!({"type": "text-start", "id": TEXT_ID}));
+            }
+            parts.push(json!({"type": "text-delta", "id": TEXT_ID, "delta": chunk.delta}));
+        }
+
+        parts
+    }
+
+    /// Parts for a stream error
+    pub fn error(&mut self, error: &AiError) -> Vec<serde_json::Value> {
+        let mut parts = Vec::new();
+        self.start(&mut parts);
+        parts.push(json!({"type": "error", "errorText": error.to_string()}));
+        parts
+    }
+
+    /// Parts closing the message
+    pub fn finish(&mut self) -> Vec<serde_json::Value> {
+        let mut parts = Vec::new();
+        self.start(&mut parts);
+        if self.reasoning_open {
+            self.reasoning_open = false;
+            parts.push(json!({"type": "reasoning-end", "id": REASONING_ID}));
+        }
+        if self.text_open {
+            self.text_open = false;
+            parts.push(json!({"type": "text-end", "id": TEXT_ID}));
+        }
+        parts.push(json!({"type": "finish-step"}));
+        parts.push(json!({"type": "finish"}));
+        parts
+    }
+}
+
+impl Default for UiMessageEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn encode_parts(parts: Vec<serde_json::Value>) -> Bytes {
+    let mut out = BytesMut::new();
+    for part in parts {
+        out.extend_from_slice(&encode_event(None, &part.to_string()));
+    }
+    out.freeze()
+}
+
+/// Convert a text chunk stream into a UI message stream body.
+///
+/// The stream stops after the first error.
+pub fn ui_message_stream<S>(chunks: S) -> impl Stream<Item = Result<Bytes, Infallible>> + Send
+where
+    S: Stream<Item = Result<TextChunk, AiError>> + Send + 'static,
+{
+    let state = (Box::pin(chunks), UiMessageEncoder::new(), false);
+
+    stream::unfold(state, |(mut chunks, mut encoder, done)| async move {
+        if done {
+            return None;
+        }
+        let (bytes, done) = match chunks.next().await {
+            Some(Ok(chunk)) => (encode_parts(encoder.chunk(&chunk)), false),
+            Some(Err(err)) => (encode_parts(encoder.error(&err)), true),
+            None => {
+                let mut bytes = BytesMut::from(&encode_parts(encoder.finish())[..]);
+                bytes.extend_from_slice(&encode_event(None, DONE));
+                (bytes.freeze(), true)
+            }
+        };
+        Some((Ok(bytes), (chunks, encoder, done)))
+    })
+    .filter(|bytes| futures::future::ready(!matches!(bytes, Ok(b) if b.is_empty())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(delta: &str, reasoning: Option<&str>) -> TextChunk {
+        TextChunk {
+            delta: delta.to_string(),
+            reasoning: reasoning.map(str::to_string),
+            tool_calls: None,
+            finish_reason: None,
+            usage: None,
+        }
+    }
+
+    #[test]
+    fn test_part_sequence() {
+        let mut encoder = UiMessageEncoder::with_message_id("msg_1");
+        let mut parts = encoder.chunk(&chunk("", Some("Thinking")));
+        parts.extend(encoder.chunk(&chunk("Hello", None)));
+        parts.extend(encoder.chunk(&chunk("!", None)));
+        parts.extend(encoder.finish());
+
+        let types: Vec<_> = parts.iter().map(|p| p["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            [
+                "start",
+                "start-step",
+                "reasoning-start",
+                "reasoning-delta",
+                "reasoning-end",
+                "text-start",
+                "text-delta",
+                "text-delta",
+                "text-end",
+                "finish-step",
+                "finish",
+            ]
+        );
+        assert_eq!(parts[0]["messageId"], "msg_1");
+        assert_eq!(parts[6]["delta"], "Hello");
+    }
+}
@@ -0,0 +1,738 @@
+//! Response caching layer for identical requests.
+//!
+//! Responses are stored under a hash of the request (model, messages and
+//! sampling parameters) and returned for later identical requests without
+//! calling the provider. Storage is a pluggable [`CacheBackend`]: the
+//! default [`InMemoryCacheBackend`] serves one process, and with the
+//! `cache-redis` feature `RedisCacheBackend` lets several gateway instances
+//! share one cache:
+//!
+//! ```ignore
+//! let backend = RedisCacheBackend::connect("redis://cache:6379/").await?;
+//! let cache = CacheLayer::new()
+//!     .with_backend(backend)
+//!     .with_ttl(Duration::from_secs(600));
+//! let executor = RuntimeExecutor::builder(provider).layer(cache).finish();
+//! ```
+//!
+//! Concurrent misses for the same request make a single provider call
+//! (single-flight): within a process the other callers wait for its
+//! result, and across instances a short-lived lock in the backend makes
+//! them wait for the entry to appear instead of stampeding the provider.
+//!
+//! Entries are kept apart per tenant: by default the key also covers the
+//! `tenant` metadata and the per-request headers, so one tenant is never
+//! served another's response. See [`CacheScope`] to change what separates
+//! entries.
+//!
+//! Requests offering tools, requests with [`RequestOptions::no_cache`] and
+//! streamed requests are passed through uncached.
+
+use crate::single_flight::SingleFlight;
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::events::{CacheEvent, LayerEvent};
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::rt::{self, Instant};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Storage for cached responses
+#[async_trait]
+pub trait CacheBackend: Send + Sync + Debug {
+    /// Cached response for `key`, or `None` if absent or expired
+    async fn get(&self, key: &str) -> Result<Option<ChatCompletionResponse>, AiError>;
+
+    /// Store a response, expiring after `ttl` if given
+    async fn set(
+        &self,
+        key: &str,
+        response: &ChatCompletionResponse,
+        ttl: Option<Duration>,
+    ) -> Result<(), AiError>;
+
+    /// Claim the computation of `key` among the processes sharing the
+    /// backend, for at most `ttl`; `false` if another process holds it
+    ///
+    /// Backends local to one process don't need to coordinate and always
+    /// succeed.
+    async fn try_lock(&self, _key: &str, _ttl: Duration) -> Result<bool, AiError> {
+        Ok(true)
+    }
+
+    /// Release a claim taken with [`Self::try_lock`]
+    async fn unlock(&self, _key: &str) -> Result<(), AiError> {
+        Ok(())
+    }
+}
+
+/// Backend keeping responses in process memory
+#[derive(Debug, Default)]
+pub struct InMemoryCacheBackend {
+    entries: DashMap<String, (ChatCompletionResponse, Option<Instant>)>,
+}
+
+impl InMemoryCacheBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CacheBackend for InMemoryCacheBackend {
+    async fn get(&self, key: &str) -> Result<Option<ChatCompletionResponse>, AiError> {
+        let now = rt::now();
+        // Expired entries are removed when they are next looked up
+        let expired = |(_, expires): &(ChatCompletionResponse, Option<Instant>)| {
+            expires.is_some_and(|expires| expires <= now)
+        };
+        if self
+            .entries
+            .remove_if(key, |_, entry| expired(entry))
+            .is_some()
+        {
+            return Ok(None);
+        }
+        Ok(self.entries.get(key).map(|entry| entry.0.clone()))
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        response: &ChatCompletionResponse,
+        ttl: Option<Duration>,
+    ) -> Result<(), AiError> {
+        let expires = ttl.map(|ttl| rt::now() + ttl);
+        self.entries
+            .insert(key.to_string(), (response.clone(), expires));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cache-redis")]
+pub use redis_backend::RedisCacheBackend;
+
+#[cfg(feature = "cache-redis")]
+mod redis_backend {
+    use super::CacheBackend;
+    use aidale_core::error::AiError;
+    use aidale_core::types::ChatCompletionResponse;
+    use async_trait::async_trait;
+    use redis::aio::ConnectionManager;
+    use redis::Script;
+    use std::fmt;
+    use std::time::Duration;
+
+    /// Deletes the lock only if this backend still holds it
+    const UNLOCK: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+    /// Backend storing responses as JSON in Redis, shared by every
+    /// instance using the same server
+    #[derive(Clone)]
+    pub struct RedisCacheBackend {
+        conn: ConnectionManager,
+        prefix: String,
+        /// Value of the locks this backend takes
+        owner: String,
+    }
+
+    fn redis_error(e: redis::RedisError) -> AiError {
+        AiError::other(format!("Redis error: {}", e))
+    }
+
+    impl RedisCacheBackend {
+        /// Connect to `url`, e.g. `redis://127.0.0.1/`
+        pub async fn connect(url: &str) -> Result<Self, AiError> {
+            let client = redis::Client::open(url).map_err(redis_error)?;
+            let conn = ConnectionManager::new(client).await.map_err(redis_error)?;
+            Ok(Self::from_connection(conn))
+        }
+
+        /// Use an existing connection
+        pub fn from_connection(conn: ConnectionManager) -> Self {
+            Self {
+                conn,
+                prefix: "aidale:cache:".to_string(),
+                owner: uuid::Uuid::new_v4().to_string(),
+            }
+        }
+
+        /// Prefix of the cache keys (default: `aidale:cache:`)
+        pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+            self.prefix = prefix.into();
+            self
+        }
+
+        fn lock_key(&self, key: &str) -> String {
+            format!("{}{}:lock", self.prefix, key)
+        }
+    }
+
+    impl fmt::Debug for RedisCacheBackend {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("RedisCacheBackend")
+                .field("prefix", &self.prefix)
+                .finish()
+        }
+    }
+
+    #[async_trait]
+    impl CacheBackend for RedisCacheBackend {
+        async fn get(&self, key: &str) -> Result<Option<ChatCompletionResponse>, AiError> {
+            let mut conn = self.conn.clone();
+            let json: Option<String> = redis::cmd("GET")
+                .arg(format!("{}{}", self.prefix, key))
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error)?;
+            json.map(|json| serde_json::from_str(&json))
+                .transpose()
+                .map_err(AiError::from)
+        }
+
+        async fn set(
+            &self,
+            key: &str,
+            response: &ChatCompletionResponse,
+            ttl: Option<Duration>,
+        ) -> Result<(), AiError> {
+            let mut conn = self.conn.clone();
+            let mut cmd = redis::cmd("SET");
+            cmd.arg(format!("{}{}", self.prefix, key))
+                .arg(serde_json::to_string(response)?);
+            if let Some(ttl) = ttl {
+                cmd.arg("PX").arg(ttl.as_millis().max(1) as u64);
+            }
+            cmd.query_async::<()>(&mut conn).await.map_err(redis_error)
+        }
+
+        async fn try_lock(&self, key: &str, ttl: Duration) -> Result<bool, AiError> {
+            let mut conn = self.conn.clone();
+            let reply: Option<String> = redis::cmd("SET")
+                .arg(self.lock_key(key))
+                .arg(&self.owner)
+                .arg("NX")
+                .arg("PX")
+                .arg(ttl.as_millis().max(1) as u64)
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error)?;
+            Ok(reply.is_some())
+        }
+
+        async fn unlock(&self, key: &str) -> Result<(), AiError> {
+            let mut conn = self.conn.clone();
+            Script::new(UNLOCK)
+                .key(self.lock_key(key))
+                .arg(&self.owner)
+                .invoke_async::<i64>(&mut conn)
+                .await
+                .map_err(redis_error)?;
+            Ok(())
+        }
+    }
+}
+
+/// What keeps cache entries of otherwise identical requests apart
+///
+/// The request body is always part of the key. By default so are the
+/// `tenant` metadata (the key read by `QuotaLayer` and `SchedulerLayer`)
+/// and every per-request header, which carry tenant IDs and credentials of
+/// gateway callers.
+#[derive(Debug, Clone)]
+pub struct CacheScope {
+    metadata_keys: Vec<String>,
+    /// Header names (lowercase) to include, or `None` for all headers
+    headers: Option<Vec<String>>,
+}
+
+impl CacheScope {
+    /// Scope by the `tenant` metadata and all per-request headers
+    pub fn new() -> Self {
+        Self {
+            metadata_keys: vec!["tenant".to_string()],
+            headers: None,
+        }
+    }
+
+    /// Also scope by the metadata under `key`
+    pub fn with_metadata_key(mut self, key: impl Into<String>) -> Self {
+        self.metadata_keys.push(key.into());
+        self
+    }
+
+    /// Scope by these headers only, e.g. to ignore request IDs that differ
+    /// on every call
+    pub fn with_headers<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.headers = Some(
+            names
+                .into_iter()
+                .map(|name| name.into().to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+
+    /// Cache key of `req`: `sha256` hex digest of its JSON form and its
+    /// scope
+    ///
+    /// Requests without scope values are keyed by their body alone.
+    pub fn key(&self, req: &ChatCompletionRequest) -> Result<String, AiError> {
+        let mut scope = BTreeMap::new();
+        for key in &self.metadata_keys {
+            if let Some(value) = req.metadata.get(key) {
+                scope.insert(format!("metadata.{}", key), value.clone());
+            }
+        }
+        for (name, value) in &req.headers {
+            let name = name.to_ascii_lowercase();
+            if self
+                .headers
+                .as_ref()
+                .map_or(true, |names| names.contains(&name))
+            {
+                scope.insert(format!("header.{}", name), value.clone());
+            }
+        }
+
+        // Going through `Value` sorts object keys, so maps hash the same in
+        // every process
+        let mut value = serde_json::to_value(req)?;
+        if !scope.is_empty() {
+            value = serde_json::json!({"request": value, "scope": scope});
+        }
+        let digest = Sha256::digest(serde_json::to_vec(&value)?);
+        Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+impl Default for CacheScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cache key of a request in the default [`CacheScope`]
+///
+/// Covers everything sent to the provider, the `tenant` metadata and the
+/// per-request headers, but not options or other metadata.
+pub fn request_key(req: &ChatCompletionRequest) -> Result<String, AiError> {
+    CacheScope::new().key(req)
+}
+
+/// Response cache layer configuration
+#[derive(Clone)]
+pub struct CacheLayer {
+    backend: Arc<dyn CacheBackend>,
+    scope: CacheScope,
+    ttl: Option<Duration>,
+    lock_timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl CacheLayer {
+    /// Create a cache layer keeping responses in memory for an hour
+    ///
+    /// Providers layered with clones of this layer share its backend.
+    pub fn new() -> Self {
+        Self {
+            backend: Arc::new(InMemoryCacheBackend::new()),
+            scope: CacheScope::new(),
+            ttl: Some(Duration::from_secs(3600)),
+            lock_timeout: Duration::from_secs(30),
+            poll_interval: Duration::from_millis(100),
+        }
+    }
+
+    /// Store responses in `backend`
+    pub fn with_backend(mut self, backend: impl CacheBackend + 'static) -> Self {
+        self.backend = Arc::new(backend);
+        self
+    }
+
+    /// Set what keeps entries of identical requests apart (default: the
+    /// `tenant` metadata and all per-request headers)
+    pub fn with_scope(mut self, scope: CacheScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// How long responses are kept; `None` keeps them until the backend
+    /// evicts them (default: 1 hour)
+    pub fn with_ttl(mut self, ttl: impl Into<Option<Duration>>) -> Self {
+        self.ttl = ttl.into();
+        self
+    }
+
+    /// How long to wait for another instance computing the same response
+    /// before calling the provider anyway (default: 30s)
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// How often to look for the other instance's response while waiting
+    /// (default: 100ms)
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Cached response, treating backend errors as misses
+    async fn lookup(&self, key: &str) -> Option<ChatCompletionResponse> {
+        match self.backend.get(key).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!("Failed to read cache entry: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl Default for CacheLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for CacheLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheLayer")
+            .field("backend", &self.backend)
+            .field("scope", &self.scope)
+            .field("ttl", &self.ttl)
+            .field("lock_timeout", &self.lock_timeout)
+            .field("poll_interval", &self.poll_interval)
+            .finish()
+    }
+}
+
+impl<P: Provider> Layer<P> for CacheLayer {
+    type LayeredProvider = CacheProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        CacheProvider {
+            inner,
+            config: self.clone(),
+            flights: SingleFlight::default(),
+        }
+    }
+}
+
+/// Provider wrapped with response caching
+pub struct CacheProvider<P> {
+    inner: P,
+    config: CacheLayer,
+    /// Misses being computed in this process, by key
+    flights: SingleFlight<(ChatCompletionResponse, bool)>,
+}
+
+impl<P: Debug> Debug for CacheProvider<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheProvider")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+fn cache_event(hit: bool) -> LayerEvent {
+    LayerEvent::Cache(CacheEvent {
+        layer: "cache".to_string(),
+        hit,
+    })
+}
+
+impl<P: Provider> CacheProvider<P> {
+    /// Compute a missing response and store it, or wait for another
+    /// instance to; returns whether the response came from the cache
+    async fn fill(
+        &self,
+        key: &str,
+        req: ChatCompletionRequest,
+    ) -> Result<(ChatCompletionResponse, bool), AiError> {
+        let config = &self.config;
+        let locked = match config.backend.try_lock(key, config.lock_timeout).await {
+            Ok(locked) => locked,
+            Err(e) => {
+                tracing::warn!("Failed to lock cache entry: {}", e);
+                true
+            }
+        };
+        if !locked {
+            tracing::debug!("Waiting for another instance to fill the cache entry");
+            let deadline = rt::now() + config.lock_timeout;
+            while rt::now() < deadline {
+                rt::sleep(config.poll_interval).await;
+                if let Some(response) = config.lookup(key).await {
+                    return Ok((response, true));
+                }
+            }
+        }
+
+        let result = self.inner.chat_completion(req).await;
+        if let Ok(response) = &result {
+            if let Err(e) = config.backend.set(key, response, config.ttl).await {
+                tracing::warn!("Failed to store cache entry: {}", e);
+            }
+        }
+        if locked {
+            if let Err(e) = config.backend.unlock(key).await {
+                tracing::warn!("Failed to unlock cache entry: {}", e);
+            }
+        }
+        result.map(|response| (response, false))
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for CacheProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        // Tool-calling requests depend on external state, don't cache them
+        if req.tools.is_some() || req.options.no_cache {
+            return self.inner.chat_completion(req).await;
+        }
+
+        let key = self.config.scope.key(&req)?;
+        if let Some(response) = self.config.lookup(&key).await {
+            tracing::debug!("Cache hit");
+            req.events.emit(cache_event(true));
+            return Ok(response);
+        }
+
+        let events = req.events.clone();
+        let (result, _) = self.flights.run(&key, self.fill(&key, req)).await;
+        let (response, hit) = result?;
+        tracing::debug!("Cache {}", if hit { "hit" } else { "miss" });
+        events.emit(cache_event(hit));
+        Ok(response)
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        self.inner.stream_chat_completion(req).await
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for CacheProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Answers after a second with the number of calls so far
+    #[derive(Debug, Default)]
+    struct CountingProvider {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Provider for CountingProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "counting".to_string(),
+                name: "Counting".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(ChatCompletionResponse {
+                id: call.to_string(),
+                model: req.model,
+                choices: Vec::new(),
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_and_single_flight() {
+        let counting = Arc::new(CountingProvider::default());
+        let provider = CacheLayer::new()
+            .with_ttl(Duration::from_secs(60))
+            .layer(counting.clone() as Arc<dyn Provider>);
+        let req = ChatCompletionRequest::new("m", vec![Message::user("Hi")]);
+
+        // Concurrent misses share one call
+        let (a, b) = tokio::join!(
+            provider.chat_completion(req.clone()),
+            provider.chat_completion(req.clone())
+        );
+        assert_eq!(a.unwrap().id, "1");
+        assert_eq!(b.unwrap().id, "1");
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 1);
+
+        // Later requests hit the cache until the entry expires
+        let hit = ChatCompletionRequest::new("m", vec![Message::user("Hi")]);
+        let response = provider.chat_completion(hit.clone()).await.unwrap();
+        assert_eq!(response.id, "1");
+        assert_eq!(
+            hit.events.take(),
+            [LayerEvent::Cache(CacheEvent {
+                layer: "cache".to_string(),
+                hit: true,
+            })]
+        );
+        let other = ChatCompletionRequest::new("m", vec![Message::user("Hello")]);
+        assert_eq!(provider.chat_completion(other).await.unwrap().id, "2");
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(provider.chat_completion(req).await.unwrap().id, "3");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_entries_are_scoped_per_tenant() {
+        let counting = Arc::new(CountingProvider::default());
+        let provider = CacheLayer::new().layer(counting.clone() as Arc<dyn Provider>);
+        let req = |tenant: &str| {
+            ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+                .with_metadata("tenant", tenant)
+        };
+
+        assert_eq!(provider.chat_completion(req("acme")).await.unwrap().id, "1");
+        assert_eq!(
+            provider.chat_completion(req("globex")).await.unwrap().id,
+            "2"
+        );
+        assert_eq!(provider.chat_completion(req("acme")).await.unwrap().id, "1");
+
+        // Tenants identified by a header are kept apart too
+        let req = |tenant: &str| {
+            ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+                .with_header("X-Tenant-Id", tenant)
+        };
+        assert_eq!(provider.chat_completion(req("acme")).await.unwrap().id, "3");
+        assert_eq!(
+            provider.chat_completion(req("globex")).await.unwrap().id,
+            "4"
+        );
+
+        // Other metadata doesn't split entries
+        let traced = ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+            .with_metadata("trace_id", "abc");
+        assert_eq!(provider.chat_completion(traced).await.unwrap().id, "5");
+        let traced = ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+            .with_metadata("trace_id", "def");
+        assert_eq!(provider.chat_completion(traced).await.unwrap().id, "5");
+
+        // An explicit scope can ignore headers that vary per call
+        let provider = CacheLayer::new()
+            .with_scope(CacheScope::new().with_headers(["x-tenant-id"]))
+            .layer(counting.clone() as Arc<dyn Provider>);
+        let req = |request_id: &str| {
+            ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+                .with_header("X-Tenant-Id", "acme")
+                .with_header("X-Request-Id", request_id)
+        };
+        assert_eq!(provider.chat_completion(req("1")).await.unwrap().id, "6");
+        assert_eq!(provider.chat_completion(req("2")).await.unwrap().id, "6");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_cache_option_bypasses_entries() {
+        let counting = Arc::new(CountingProvider::default());
+        let provider = CacheLayer::new().layer(counting.clone() as Arc<dyn Provider>);
+        let req = ChatCompletionRequest::new("m", vec![Message::user("Hi")]);
+
+        assert_eq!(provider.chat_completion(req.clone()).await.unwrap().id, "1");
+
+        // Neither served from nor written to the cache
+        let uncached = ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+            .with_options(RequestOptions::new().with_no_cache());
+        let response = provider.chat_completion(uncached.clone()).await.unwrap();
+        assert_eq!(response.id, "2");
+        assert!(uncached.events.take().is_empty());
+        assert_eq!(provider.chat_completion(req).await.unwrap().id, "1");
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,355 @@
+//! Stream coalescing layer.
+//!
+//! Providers often stream a token or two per chunk. When every chunk is
+//! forwarded to a websocket or SSE connection, the per-message overhead
+//! dominates at high concurrency. This layer merges consecutive text and
+//! reasoning deltas into larger chunks, flushing when the merged text reaches
+//! a size threshold or has waited for a maximum delay.
+//!
+//! The stream is pull-based: the provider stream is only read while the
+//! consumer is reading, and at most one merged chunk plus one pending
+//! non-text chunk are held at any time.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::rt::{self, Instant};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use futures::stream::{Stream, StreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Coalescing layer configuration
+#[derive(Debug, Clone, Copy)]
+pub struct CoalescingLayer {
+    max_delay: Duration,
+    max_chars: usize,
+}
+
+impl CoalescingLayer {
+    /// Create a layer flushing every 50ms or 256 characters
+    pub fn new() -> Self {
+        Self {
+            max_delay: Duration::from_millis(50),
+            max_chars: 256,
+        }
+    }
+
+    /// Longest time a delta is held back before it is forwarded
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Merged text length (in bytes) at which a chunk is forwarded
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars.max(1);
+        self
+    }
+
+    /// Coalesce a chunk stream with this configuration
+    pub fn coalesce(&self, inner: Box<ChatCompletionStream>) -> CoalescingStream {
+        CoalescingStream {
+            inner,
+            config: *self,
+            pending: None,
+            queued: None,
+            deadline: None,
+            timer: None,
+            done: false,
+        }
+    }
+}
+
+impl Default for CoalescingLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Provider> Layer<P> for CoalescingLayer {
+    type LayeredProvider = CoalescingProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        CoalescingProvider {
+            inner,
+            config: *self,
+        }
+    }
+}
+
+/// Provider wrapped with stream coalescing
+#[derive(Debug)]
+pub struct CoalescingProvider<P> {
+    inner: P,
+    config: CoalescingLayer,
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for CoalescingProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let inner = self.inner.stream_chat_completion(req).await?;
+        Ok(Box::new(self.config.coalesce(inner)))
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for CoalescingProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+/// Chunk stream merging small text deltas, see [`CoalescingLayer`]
+pub struct CoalescingStream {
+    inner: Box<ChatCompletionStream>,
+    config: CoalescingLayer,
+    /// Text chunk being merged into
+    pending: Option<ChatCompletionChunk>,
+    /// Item to forward after `pending`
+    queued: Option<Result<ChatCompletionChunk, AiError>>,
+    /// Deadline for forwarding `pending`, and its timer
+    deadline: Option<Instant>,
+    timer: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    done: bool,
+}
+
+impl std::fmt::Debug for CoalescingStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoalescingStream")
+            .field("config", &self.config)
+            .field("pending", &self.pending.is_some())
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// Which deltas a plain text chunk carries, or None if it carries anything
+/// else and must be forwarded as-is
+fn text_kind(chunk: &ChatCompletionChunk) -> Option<(bool, bool)> {
+    let [choice] = chunk.choices.as_slice() else {
+        return None;
+    };
+    let delta = &choice.delta;
+    let plain = choice.finish_reason.is_none()
+        && chunk.usage.is_none()
+        && delta.role.is_none()
+        && delta.tool_calls.is_none();
+    plain.then_some((delta.content.is_some(), delta.reasoning.is_some()))
+}
+
+fn text_len(chunk: &ChatCompletionChunk) -> usize {
+    chunk
+        .choices
+        .iter()
+        .map(|choice| {
+            choice.delta.content.as_ref().map_or(0, String::len)
+                + choice.delta.reasoning.as_ref().map_or(0, String::len)
+        })
+        .sum()
+}
+
+fn append(target: &mut Option<String>, delta: Option<String>) {
+    if let (Some(target), Some(delta)) = (target.as_mut(), delta) {
+        target.push_str(&delta);
+    }
+}
+
+impl CoalescingStream {
+    /// Whether a text chunk can be merged into the pending one
+    fn can_merge(&self, chunk: &ChatCompletionChunk) -> bool {
+        self.pending.as_ref().is_some_and(|pending| {
+            text_kind(pending) == text_kind(chunk)
+                && pending.choices[0].index == chunk.choices[0].index
+        })
+    }
+
+    /// Merge a text chunk into the pending one
+    fn merge(&mut self, chunk: ChatCompletionChunk) {
+        let pending = self.pending.as_mut().map(|pending| &mut pending.choices[0]);
+        let delta = chunk.choices.into_iter().next().map(|choice| choice.delta);
+        if let (Some(pending), Some(delta)) = (pending, delta) {
+            append(&mut pending.delta.content, delta.content);
+            append(&mut pending.delta.reasoning, delta.reasoning);
+        }
+    }
+
+    /// Start merging into a new chunk, returning the previous one
+    fn start(&mut self, chunk: ChatCompletionChunk) -> Option<ChatCompletionChunk> {
+        self.deadline = Some(rt::now() + self.config.max_delay);
+        self.timer = Some(Box::pin(rt::sleep(self.config.max_delay)));
+        self.pending.replace(chunk)
+    }
+
+    fn is_due(&self) -> bool {
+        self.pending.as_ref().is_some_and(|pending| {
+            text_len(pending) >= self.config.max_chars
+                || self.deadline.is_some_and(|deadline| rt::now() >= deadline)
+        })
+    }
+}
+
+impl Stream for CoalescingStream {
+    type Item = Result<ChatCompletionChunk, AiError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(item) = this.queued.take() {
+            return Poll::Ready(Some(item));
+        }
+
+        loop {
+            if this.done {
+                return Poll::Ready(this.pending.take().map(Ok));
+            }
+
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(chunk))) if text_kind(&chunk).is_some() => {
+                    let flushed = if this.can_merge(&chunk) {
+                        this.merge(chunk);
+                        None
+                    } else {
+                        this.start(chunk)
+                    };
+                    if let Some(flushed) = flushed {
+                        return Poll::Ready(Some(Ok(flushed)));
+                    }
+                    if this.is_due() {
+                        return Poll::Ready(this.pending.take().map(Ok));
+                    }
+                }
+                Poll::Ready(Some(item)) => {
+                    // Finish reasons, usage, tool calls and errors keep their
+                    // position after the text before them
+                    return Poll::Ready(Some(match this.pending.take() {
+                        Some(pending) => {
+                            this.queued = Some(item);
+                            Ok(pending)
+                        }
+                        None => item,
+                    }));
+                }
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => {
+                    if this.pending.is_some() {
+                        if let Some(timer) = this.timer.as_mut() {
+                            if timer.as_mut().poll(cx).is_ready() {
+                                return Poll::Ready(this.pending.take().map(Ok));
+                            }
+                        }
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(content: Option<&str>, finish_reason: Option<FinishReason>) -> ChatCompletionChunk {
+        ChatCompletionChunk {
+            id: "c".to_string(),
+            model: "m".to_string(),
+            choices: vec![ChoiceDelta {
+                index: 0,
+                delta: MessageDelta {
+                    role: None,
+                    content: content.map(str::to_string),
+                    reasoning: None,
+                    tool_calls: None,
+                },
+                finish_reason,
+            }],
+            usage: None,
+            system_fingerprint: None,
+            service_tier: None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_size_and_delay_flushes() {
+        let inner = async_stream::stream! {
+            for _ in 0..5 {
+                yield Ok(chunk(Some("a"), None));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            yield Ok(chunk(Some("b"), None));
+            yield Ok(chunk(None, Some(FinishReason::Stop)));
+        };
+        let layer = CoalescingLayer::new()
+            .with_max_chars(4)
+            .with_max_delay(Duration::from_millis(50));
+        let chunks: Vec<_> = layer
+            .coalesce(Box::new(Box::pin(inner)))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let contents: Vec<_> = chunks
+            .iter()
+            .map(|chunk| chunk.choices[0].delta.content.as_deref())
+            .collect();
+        // Size flush, delay flush while the provider stalls, flush before the
+        // finish chunk
+        assert_eq!(contents, [Some("aaaa"), Some("a"), Some("b"), None]);
+        assert_eq!(chunks[3].choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+}
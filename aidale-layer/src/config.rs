@@ -0,0 +1,440 @@
+//! Layer stacks built from configuration.
+//!
+//! `builder.layer(...)` chains are fixed at compile time. For deployments
+//! where operators should be able to turn retries or logging on and off
+//! from a config file, the stack can instead be described as a list of
+//! [`LayerDescriptor`]s and applied at runtime to a type-erased provider:
+//!
+//! ```ignore
+//! let layers: Vec<LayerDescriptor> = serde_json::from_str(r#"[
+//!     {"type": "retry", "max_retries": 3, "initial_delay_ms": 500},
+//!     {"type": "logging", "enabled": false},
+//!     {"type": "truncation", "strategy": "keep_system"}
+//! ]"#)?;
+//!
+//! let provider = build_layer_stack(Arc::new(openai), &layers)?;
+//! let executor = RuntimeExecutor::builder(provider).finish();
+//! ```
+//!
+//! Descriptors are applied in order, so the first one wraps the provider
+//! directly, as with the first `builder.layer(...)` call.
+
+use crate::{
+    CacheLayer, CoalescingLayer, DedupLayer, FileSink, HedgeLayer, JitterStrategy, LoggingLayer,
+    ModelRouterLayer, PayloadLoggingLayer, QuotaLayer, QuotaLimits, RedactionLayer, RetryLayer,
+    SchedulerLayer, TracingSink, TruncationLayer, TruncationStrategy,
+};
+use aidale_core::error::AiError;
+use aidale_core::layer::Layer;
+use aidale_core::provider::Provider;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Built-in layer and its settings; unset settings keep the layer's defaults
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayerConfig {
+    Logging {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        prefix: Option<String>,
+    },
+    Retry {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_retries: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initial_delay_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_delay_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_elapsed_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        jitter: Option<JitterStrategy>,
+    },
+    Truncation {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strategy: Option<TruncationStrategy>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        context_window: Option<u32>,
+    },
+    Redaction {
+        /// Enable the built-in email, phone number and credit card rules
+        #[serde(default = "enabled")]
+        defaults: bool,
+        /// Extra rules as name to regex
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        patterns: BTreeMap<String, String>,
+    },
+    PayloadLogging {
+        /// JSON lines file to append to; events go to `tracing` when unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sample_rate: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_size: Option<usize>,
+    },
+    Coalescing {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_delay_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_chars: Option<usize>,
+    },
+    Scheduler {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_concurrency: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_queued: Option<usize>,
+        /// Metadata key identifying the tenant
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tenant_key: Option<String>,
+    },
+    Hedge {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        percentile: Option<f64>,
+        /// Fixed delay, replacing the percentile
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        delay_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initial_delay_ms: Option<u64>,
+    },
+    Router {
+        cheap_model: String,
+        strong_model: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alias: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_tokens: Option<usize>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        keywords: Vec<String>,
+        #[serde(default)]
+        escalate_tools: bool,
+    },
+    Quota {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        requests_per_minute: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tokens_per_minute: Option<u64>,
+        /// Metadata key identifying the tenant
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tenant_key: Option<String>,
+    },
+    /// In-memory response cache
+    Cache {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ttl_secs: Option<u64>,
+    },
+    Dedup,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Entry of a configured layer stack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerDescriptor {
+    /// Skip the layer without removing its settings
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub layer: LayerConfig,
+}
+
+impl LayerDescriptor {
+    /// Create an enabled descriptor
+    pub fn new(layer: LayerConfig) -> Self {
+        Self {
+            enabled: true,
+            layer,
+        }
+    }
+
+    /// Enable or disable the layer
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+impl From<LayerConfig> for LayerDescriptor {
+    fn from(layer: LayerConfig) -> Self {
+        Self::new(layer)
+    }
+}
+
+fn wrap<L>(layer: L, provider: Arc<dyn Provider>) -> Arc<dyn Provider>
+where
+    L: Layer<Arc<dyn Provider>>,
+{
+    Arc::new(layer.layer(provider))
+}
+
+impl LayerConfig {
+    /// Wrap a provider with the configured layer
+    ///
+    /// Fails on invalid settings, such as a malformed redaction pattern or
+    /// an unwritable payload log file.
+    pub fn apply(&self, provider: Arc<dyn Provider>) -> Result<Arc<dyn Provider>, AiError> {
+        let provider = match self {
+            Self::Logging { prefix } => match prefix {
+                Some(prefix) => wrap(LoggingLayer::with_prefix(prefix.clone()), provider),
+                None => wrap(LoggingLayer::new(), provider),
+            },
+            Self::Retry {
+                max_retries,
+                initial_delay_ms,
+                max_delay_ms,
+                max_elapsed_ms,
+                jitter,
+            } => {
+                let mut layer = RetryLayer::new();
+                if let Some(max_retries) = max_retries {
+                    layer = layer.with_max_retries(*max_retries);
+                }
+                if let Some(ms) = initial_delay_ms {
+                    layer = layer.with_initial_delay(Duration::from_millis(*ms));
+                }
+                if let Some(ms) = max_delay_ms {
+                    layer = layer.with_max_delay(Duration::from_millis(*ms));
+                }
+                if let Some(ms) = max_elapsed_ms {
+                    layer = layer.with_max_elapsed(Duration::from_millis(*ms));
+                }
+                if let Some(jitter) = jitter {
+                    layer = layer.with_jitter(*jitter);
+                }
+                wrap(layer, provider)
+            }
+            Self::Truncation {
+                strategy,
+                context_window,
+            } => {
+                let mut layer = TruncationLayer::new();
+                if let Some(strategy) = strategy {
+                    layer = layer.with_strategy(*strategy);
+                }
+                if let Some(context_window) = context_window {
+                    layer = layer.with_context_window(*context_window);
+                }
+                wrap(layer, provider)
+            }
+            Self::Redaction { defaults, patterns } => {
+                let mut layer = if *defaults {
+                    RedactionLayer::with_defaults()
+                } else {
+                    RedactionLayer::new()
+                };
+                for (name, pattern) in patterns {
+                    layer = layer.with_pattern(name.clone(), pattern)?;
+                }
+                wrap(layer, provider)
+            }
+            Self::PayloadLogging {
+                path,
+                sample_rate,
+                max_size,
+            } => {
+                let mut layer = match path {
+                    Some(path) => PayloadLoggingLayer::new(Arc::new(FileSink::new(path)?)),
+                    None => PayloadLoggingLayer::new(Arc::new(TracingSink)),
+                };
+                if let Some(sample_rate) = sample_rate {
+                    layer = layer.with_sample_rate(*sample_rate);
+                }
+                if let Some(max_size) = max_size {
+                    layer = layer.with_max_size(*max_size);
+                }
+                wrap(layer, provider)
+            }
+            Self::Coalescing {
+                max_delay_ms,
+                max_chars,
+            } => {
+                let mut layer = CoalescingLayer::new();
+                if let Some(ms) = max_delay_ms {
+                    layer = layer.with_max_delay(Duration::from_millis(*ms));
+                }
+                if let Some(max_chars) = max_chars {
+                    layer = layer.with_max_chars(*max_chars);
+                }
+                wrap(layer, provider)
+            }
+            Self::Scheduler {
+                max_concurrency,
+                max_queued,
+                tenant_key,
+            } => {
+                let mut layer = SchedulerLayer::new();
+                if let Some(max_concurrency) = max_concurrency {
+                    layer = layer.with_max_concurrency(*max_concurrency);
+                }
+                if let Some(max_queued) = max_queued {
+                    layer = layer.with_max_queued(*max_queued);
+                }
+                if let Some(tenant_key) = tenant_key {
+                    layer = layer.with_tenant_key(tenant_key.clone());
+                }
+                wrap(layer, provider)
+            }
+            Self::Hedge {
+                percentile,
+                delay_ms,
+                initial_delay_ms,
+            } => {
+                let mut layer = HedgeLayer::new();
+                if let Some(percentile) = percentile {
+                    layer = layer.with_percentile(*percentile);
+                }
+                if let Some(ms) = delay_ms {
+                    layer = layer.with_delay(Duration::from_millis(*ms));
+                }
+                if let Some(ms) = initial_delay_ms {
+                    layer = layer.with_initial_delay(Duration::from_millis(*ms));
+                }
+                wrap(layer, provider)
+            }
+            Self::Router {
+                cheap_model,
+                strong_model,
+                alias,
+                max_tokens,
+                keywords,
+                escalate_tools,
+            } => {
+                let mut layer = ModelRouterLayer::new(cheap_model.clone(), strong_model.clone())
+                    .with_keywords(keywords.iter().cloned())
+                    .with_escalate_tools(*escalate_tools);
+                if let Some(alias) = alias {
+                    layer = layer.with_alias(alias.clone());
+                }
+                if let Some(max_tokens) = max_tokens {
+                    layer = layer.with_max_tokens(*max_tokens);
+                }
+                wrap(layer, provider)
+            }
+            Self::Quota {
+                requests_per_minute,
+                tokens_per_minute,
+                tenant_key,
+            } => {
+                let mut limits = QuotaLimits::new();
+                if let Some(rpm) = requests_per_minute {
+                    limits = limits.with_requests_per_minute(*rpm);
+                }
+                if let Some(tpm) = tokens_per_minute {
+                    limits = limits.with_tokens_per_minute(*tpm);
+                }
+                let mut layer = QuotaLayer::new().with_limits(limits);
+                if let Some(tenant_key) = tenant_key {
+                    layer = layer.with_tenant_key(tenant_key.clone());
+                }
+                wrap(layer, provider)
+            }
+            Self::Cache { ttl_secs } => {
+                let mut layer = CacheLayer::new();
+                if let Some(secs) = ttl_secs {
+                    layer = layer.with_ttl(Duration::from_secs(*secs));
+                }
+                wrap(layer, provider)
+            }
+            Self::Dedup => wrap(DedupLayer::new(), provider),
+        };
+        Ok(provider)
+    }
+}
+
+/// Wrap a provider with every enabled layer, in order
+pub fn build_layer_stack(
+    provider: Arc<dyn Provider>,
+    layers: &[LayerDescriptor],
+) -> Result<Arc<dyn Provider>, AiError> {
+    layers
+        .iter()
+        .filter(|descriptor| descriptor.enabled)
+        .try_fold(provider, |provider, descriptor| {
+            descriptor.layer.apply(provider)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::types::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails with a rate limit until the given number of calls was made
+    #[derive(Debug, Default)]
+    struct Flaky {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Provider for Flaky {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "flaky".to_string(),
+                name: "Flaky".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(AiError::rate_limit("slow down"))
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<aidale_core::provider::ChatCompletionStream>, AiError> {
+            Err(AiError::rate_limit("slow down"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stack_from_config() {
+        let layers: Vec<LayerDescriptor> = serde_json::from_str(
+            r#"[
+                {"type": "retry", "max_retries": 2, "initial_delay_ms": 10},
+                {"type": "retry", "max_retries": 5, "enabled": false},
+                {"type": "logging"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            layers[0].layer,
+            LayerConfig::Retry {
+                max_retries: Some(2),
+                initial_delay_ms: Some(10),
+                max_delay_ms: None,
+                max_elapsed_ms: None,
+                jitter: None,
+            }
+        );
+
+        let flaky = Arc::new(Flaky::default());
+        let provider = build_layer_stack(flaky.clone(), &layers).unwrap();
+        assert_eq!(provider.info().id, "flaky");
+
+        let req = ChatCompletionRequest::new("m", vec![Message::user("hi")]);
+        assert!(provider.chat_completion(req.clone()).await.is_err());
+        // One call plus two retries; the disabled retry layer is skipped
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+
+        // Per-request override
+        let req = req.with_options(RequestOptions::new().with_max_retries(0));
+        assert!(provider.chat_completion(req).await.is_err());
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 4);
+
+        let bad = LayerConfig::Redaction {
+            defaults: false,
+            patterns: BTreeMap::from([("id".to_string(), "(".to_string())]),
+        };
+        assert!(bad.apply(flaky).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Request deduplication layer.
+//!
+//! Concurrent identical requests (same [`request_key`]) are coalesced into
+//! one provider call whose response, or error, is handed to every caller.
+//! Like the cache, requests of different tenants or with different
+//! per-request headers are never merged; see [`CacheScope`].
+//! This catches UI retries and double submits that would otherwise pay for
+//! the same expensive call several times. Unlike [`CacheLayer`], nothing is
+//! kept once the call finishes.
+//!
+//! If the caller whose request is running is cancelled, one of the waiting
+//! callers takes over with its own call. Streamed requests are passed
+//! through.
+//!
+//! [`CacheLayer`]: crate::CacheLayer
+//! [`request_key`]: crate::request_key
+
+use crate::cache::CacheScope;
+use crate::single_flight::SingleFlight;
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+/// Deduplication layer configuration
+#[derive(Debug, Clone, Default)]
+pub struct DedupLayer {
+    scope: CacheScope,
+}
+
+impl DedupLayer {
+    /// Create a new deduplication layer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what keeps identical requests apart (default: the `tenant`
+    /// metadata and all per-request headers)
+    pub fn with_scope(mut self, scope: CacheScope) -> Self {
+        self.scope = scope;
+        self
+    }
+}
+
+impl<P: Provider> Layer<P> for DedupLayer {
+    type LayeredProvider = DedupProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        DedupProvider {
+            inner,
+            scope: self.scope.clone(),
+            flights: SingleFlight::default(),
+        }
+    }
+}
+
+/// Provider that shares one call between concurrent identical requests
+pub struct DedupProvider<P> {
+    inner: P,
+    scope: CacheScope,
+    flights: SingleFlight<ChatCompletionResponse>,
+}
+
+impl<P: Debug> Debug for DedupProvider<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DedupProvider")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for DedupProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let key = self.scope.key(&req)?;
+        let (result, shared) = self
+            .flights
+            .run(&key, self.inner.chat_completion(req))
+            .await;
+        if shared {
+            tracing::debug!("Shared the response of an identical request");
+        }
+        result
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        self.inner.stream_chat_completion(req).await
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for DedupProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    /// Fails "bad" requests after a second and answers others
+    #[derive(Debug, Default)]
+    struct SlowProvider {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Provider for SlowProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "slow".to_string(),
+                name: "Slow".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if req.model == "bad" {
+                return Err(AiError::rate_limit("slow down"));
+            }
+            Ok(ChatCompletionResponse {
+                id: call.to_string(),
+                model: req.model,
+                choices: Vec::new(),
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_duplicates_share_a_call() {
+        let slow = Arc::new(SlowProvider::default());
+        let provider = DedupLayer::new().layer(slow.clone() as Arc<dyn Provider>);
+        let req = |model: &str| ChatCompletionRequest::new(model, vec![Message::user("Hi")]);
+
+        let (a, b, c) = tokio::join!(
+            provider.chat_completion(req("m")),
+            provider.chat_completion(req("m")),
+            provider.chat_completion(req("other")),
+        );
+        assert_eq!(a.unwrap().id, b.unwrap().id);
+        assert_eq!(c.unwrap().model, "other");
+        assert_eq!(slow.calls.load(Ordering::SeqCst), 2);
+
+        // Errors reach every caller with their kind intact
+        let (a, b) = tokio::join!(
+            provider.chat_completion(req("bad")),
+            provider.chat_completion(req("bad")),
+        );
+        assert!(matches!(a, Err(AiError::RateLimit { .. })));
+        assert!(matches!(b, Err(AiError::RateLimit { .. })));
+        assert_eq!(slow.calls.load(Ordering::SeqCst), 3);
+
+        // Nothing is kept after the call
+        provider.chat_completion(req("m")).await.unwrap();
+        assert_eq!(slow.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tenants_are_not_merged() {
+        let slow = Arc::new(SlowProvider::default());
+        let provider = DedupLayer::new().layer(slow.clone() as Arc<dyn Provider>);
+        let req = |tenant: &str| {
+            ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+                .with_metadata("tenant", tenant)
+        };
+
+        let (a, b, c) = tokio::join!(
+            provider.chat_completion(req("acme")),
+            provider.chat_completion(req("acme")),
+            provider.chat_completion(req("globex")),
+        );
+        assert_eq!(a.unwrap().id, b.unwrap().id);
+        assert_ne!(c.unwrap().id, "1");
+        assert_eq!(slow.calls.load(Ordering::SeqCst), 2);
+
+        let req = |key: &str| {
+            ChatCompletionRequest::new("m", vec![Message::user("Hi")])
+                .with_header("Authorization", key)
+        };
+        let (a, b) = tokio::join!(
+            provider.chat_completion(req("Bearer a")),
+            provider.chat_completion(req("Bearer b")),
+        );
+        assert_ne!(a.unwrap().id, b.unwrap().id);
+        assert_eq!(slow.calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,386 @@
+//! Hedged requests layer for cutting tail latency.
+//!
+//! When a request takes longer than usual, a duplicate is sent, either to
+//! the same provider or to a backup added with [`HedgeLayer::with_backend`],
+//! and the first successful response wins; the other request is cancelled.
+//! "Longer than usual" is a percentile of recently observed latencies
+//! (P95 by default), so only the slowest few percent of requests cost a
+//! second call. A fixed delay can be set instead.
+//!
+//! Streamed requests are hedged on the time until the stream opens.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::rt;
+use aidale_core::types::*;
+use async_trait::async_trait;
+use futures::future::{select, Either};
+use std::collections::VecDeque;
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Latencies kept for the percentile
+const WINDOW: usize = 128;
+
+/// Hedge layer configuration
+#[derive(Clone)]
+pub struct HedgeLayer {
+    backend: Option<Arc<dyn Provider>>,
+    percentile: f64,
+    delay: Option<Duration>,
+    initial_delay: Duration,
+    min_samples: usize,
+}
+
+impl HedgeLayer {
+    /// Create a new hedge layer with default settings
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            percentile: 0.95,
+            delay: None,
+            initial_delay: Duration::from_secs(2),
+            min_samples: 20,
+        }
+    }
+
+    /// Send hedged requests to `provider` instead of the wrapped provider
+    pub fn with_backend(mut self, provider: impl Provider) -> Self {
+        self.backend = Some(Arc::new(provider));
+        self
+    }
+
+    /// Latency percentile after which a request is hedged, in `(0, 1]`
+    /// (default: 0.95)
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        self.percentile = percentile.clamp(f64::EPSILON, 1.0);
+        self
+    }
+
+    /// Hedge after a fixed delay instead of a percentile
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Delay used until enough latencies were observed (default: 2s)
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Latencies to observe before the percentile is used (default: 20)
+    pub fn with_min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples.clamp(1, WINDOW);
+        self
+    }
+}
+
+impl Default for HedgeLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for HedgeLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HedgeLayer")
+            .field(
+                "backend",
+                &self.backend.as_ref().map(|p| p.info().id.clone()),
+            )
+            .field("percentile", &self.percentile)
+            .field("delay", &self.delay)
+            .field("initial_delay", &self.initial_delay)
+            .field("min_samples", &self.min_samples)
+            .finish()
+    }
+}
+
+impl<P: Provider> Layer<P> for HedgeLayer {
+    type LayeredProvider = HedgeProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        let inner = Arc::new(inner);
+        let backend = self
+            .backend
+            .clone()
+            .unwrap_or_else(|| inner.clone() as Arc<dyn Provider>);
+        HedgeProvider {
+            inner,
+            backend,
+            config: self.clone(),
+            latencies: Mutex::new(VecDeque::with_capacity(WINDOW)),
+        }
+    }
+}
+
+/// Provider that duplicates slow requests
+pub struct HedgeProvider<P> {
+    inner: Arc<P>,
+    backend: Arc<dyn Provider>,
+    config: HedgeLayer,
+    /// Recent latencies of successful calls, oldest first
+    latencies: Mutex<VecDeque<Duration>>,
+}
+
+impl<P: Debug> Debug for HedgeProvider<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HedgeProvider")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<P: Provider> HedgeProvider<P> {
+    /// How long to wait before hedging
+    fn hedge_delay(&self) -> Duration {
+        if let Some(delay) = self.config.delay {
+            return delay;
+        }
+        let latencies = self.latencies.lock().unwrap();
+        if latencies.len() < self.config.min_samples {
+            return self.config.initial_delay;
+        }
+        let mut sorted: Vec<Duration> = latencies.iter().copied().collect();
+        sorted.sort();
+        let rank = (self.config.percentile * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    fn record(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() == WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// Run `primary`, starting `hedge` if it is slow, and return the first
+    /// success; the error of the primary call if both fail
+    async fn race<T, F>(&self, primary: F, hedge: impl FnOnce() -> F) -> Result<T, AiError>
+    where
+        F: Future<Output = Result<T, AiError>>,
+    {
+        let delay = self.hedge_delay();
+        let mut primary = pin!(timed(primary));
+        if let Ok((result, latency)) = rt::timeout(delay, primary.as_mut()).await {
+            if result.is_ok() {
+                self.record(latency);
+            }
+            return result;
+        }
+
+        tracing::debug!(
+            "Hedging request to {} after {:?}",
+            self.backend.info().id,
+            delay
+        );
+        let hedge = pin!(timed(hedge()));
+        // Dropping the loser cancels it
+        let (primary_error, remaining) = match select(primary, hedge).await {
+            Either::Left(((Ok(value), latency), _)) | Either::Right(((Ok(value), latency), _)) => {
+                self.record(latency);
+                return Ok(value);
+            }
+            Either::Left(((Err(error), _), hedge)) => (Some(error), Either::Left(hedge)),
+            Either::Right(((Err(_), _), primary)) => (None, Either::Right(primary)),
+        };
+        match remaining.await {
+            (Ok(value), latency) => {
+                self.record(latency);
+                Ok(value)
+            }
+            (Err(error), _) => Err(primary_error.unwrap_or(error)),
+        }
+    }
+}
+
+/// Measure how long `future` takes
+async fn timed<F: Future>(future: F) -> (F::Output, Duration) {
+    let start = rt::now();
+    let output = future.await;
+    (output, rt::now() - start)
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for HedgeProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let hedge_req = req.clone();
+        self.race(self.inner.chat_completion(req), || {
+            self.backend.chat_completion(hedge_req)
+        })
+        .await
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let hedge_req = req.clone();
+        self.race(self.inner.stream_chat_completion(req), || {
+            self.backend.stream_chat_completion(hedge_req)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for HedgeProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Answers after `latency`, counting started and finished calls
+    #[derive(Debug)]
+    struct DelayedProvider {
+        id: &'static str,
+        latency: Duration,
+        started: AtomicU32,
+        finished: AtomicU32,
+    }
+
+    impl DelayedProvider {
+        fn new(id: &'static str, latency: Duration) -> Self {
+            Self {
+                id,
+                latency,
+                started: AtomicU32::new(0),
+                finished: AtomicU32::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Provider for DelayedProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: self.id.to_string(),
+                name: self.id.to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.latency).await;
+            self.finished.fetch_add(1, Ordering::SeqCst);
+            Ok(ChatCompletionResponse {
+                id: self.id.to_string(),
+                model: "test".to_string(),
+                choices: Vec::new(),
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_request_is_hedged() {
+        let backup = Arc::new(DelayedProvider::new("backup", Duration::from_secs(1)));
+        let primary = Arc::new(DelayedProvider::new("primary", Duration::from_secs(1)));
+        let provider = HedgeLayer::new()
+            .with_backend(backup.clone() as Arc<dyn Provider>)
+            .with_initial_delay(Duration::from_secs(5))
+            .with_min_samples(2)
+            .layer(primary.clone() as Arc<dyn Provider>);
+        let req = ChatCompletionRequest::new("test", Vec::new());
+
+        // Fast requests are not hedged and set the percentile to 1s
+        for _ in 0..2 {
+            let response = provider.chat_completion(req.clone()).await.unwrap();
+            assert_eq!(response.id, "primary");
+        }
+        assert_eq!(provider.hedge_delay(), Duration::from_secs(1));
+        assert_eq!(backup.started.load(Ordering::SeqCst), 0);
+
+        // A request stuck for 10s is hedged after 1s and answered after 2s
+        let slow = DelayedProvider::new("slow", Duration::from_secs(10));
+        let provider = HedgeLayer::new()
+            .with_backend(backup.clone() as Arc<dyn Provider>)
+            .with_delay(Duration::from_secs(1))
+            .layer(slow);
+        let start = tokio::time::Instant::now();
+        let response = provider.chat_completion(req).await.unwrap();
+        assert_eq!(response.id, "backup");
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+        // The slow request was cancelled
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        assert_eq!(provider.inner().started.load(Ordering::SeqCst), 1);
+        assert_eq!(provider.inner().finished.load(Ordering::SeqCst), 0);
+    }
+}
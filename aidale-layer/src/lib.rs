@@ -3,8 +3,21 @@
 //! Built-in layers for AI Core.
 //!
 //! Currently implemented layers:
+//! - `CacheLayer`: Returns stored responses for identical requests
+//! - `CoalescingLayer`: Merges small streamed deltas into larger chunks
+//! - `DedupLayer`: Shares one call between concurrent identical requests
+//! - `HedgeLayer`: Duplicates slow requests and keeps the first response
 //! - `LoggingLayer`: Logs all provider operations with timing information
+//! - `LoadBalancingLayer`: Spreads traffic over several API keys or endpoints
+//! - `ModelRouterLayer`: Sends simple prompts to a cheap model and escalates hard ones
+//! - `QuotaLayer`: Enforces per-tenant request, token and cost limits
+//! - `PayloadLoggingLayer`: Emits full request/response payloads as structured events
+//! - `RecordingLayer`: Records interactions to a cassette for `ReplayProvider`
 //! - `RetryLayer`: Automatic retry with exponential backoff for retryable errors
+//! - `RedactionLayer`: Replaces PII in outgoing messages with placeholders
+//! - `TruncationLayer`: Trims conversation history to fit the context window
+//! - `SemanticCacheLayer`: Returns cached responses for semantically similar prompts
+//! - `SchedulerLayer`: Limits concurrency and queues requests by priority and tenant
 //!
 //! ## Usage
 //!
@@ -17,10 +30,51 @@
 //!     .layer(RetryLayer::new().with_max_retries(3))
 //!     .finish();
 //! ```
+//!
+//! Stacks can also be described in configuration and built at runtime, see
+//! [`config`].
 
+pub mod cache;
+pub mod coalescing;
+pub mod config;
+pub mod dedup;
+pub mod hedge;
+pub mod load_balancing;
 pub mod logging;
+pub mod payload_logging;
+pub mod quota;
+pub mod recording;
+pub mod redaction;
 pub mod retry;
+pub mod router;
+pub mod scheduler;
+pub mod semantic_cache;
+mod single_flight;
+pub mod truncation;
 
 // Re-exports
+#[cfg(feature = "cache-redis")]
+pub use cache::RedisCacheBackend;
+pub use cache::{
+    request_key, CacheBackend, CacheLayer, CacheProvider, CacheScope, InMemoryCacheBackend,
+};
+pub use coalescing::{CoalescingLayer, CoalescingStream};
+pub use config::{build_layer_stack, LayerConfig, LayerDescriptor};
+pub use dedup::{DedupLayer, DedupProvider};
+pub use hedge::{HedgeLayer, HedgeProvider};
+pub use load_balancing::{BalanceStrategy, LoadBalancingLayer};
 pub use logging::LoggingLayer;
-pub use retry::RetryLayer;
+pub use payload_logging::{
+    CallbackSink, FileSink, PayloadEvent, PayloadEventKind, PayloadLoggingLayer, PayloadSink,
+    TracingSink,
+};
+#[cfg(feature = "redis")]
+pub use quota::RedisQuotaStore;
+pub use quota::{InMemoryQuotaStore, QuotaLayer, QuotaLimits, QuotaProvider, QuotaStore, Rate};
+pub use recording::{Cassette, RecordingLayer, ReplayProvider};
+pub use redaction::{RedactionLayer, RedactionRule};
+pub use retry::{JitterStrategy, RetryAttempt, RetryLayer};
+pub use router::{ModelRouterLayer, ModelRouterProvider};
+pub use scheduler::{SchedulerLayer, SchedulerProvider};
+pub use semantic_cache::SemanticCacheLayer;
+pub use truncation::{TruncationLayer, TruncationStrategy};
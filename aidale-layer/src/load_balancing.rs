@@ -0,0 +1,460 @@
+//! Load balancing layer for spreading traffic over several backends.
+//!
+//! The wrapped provider is the first backend; additional providers (for
+//! example the same API with different keys or regions) are added with
+//! [`LoadBalancingLayer::with_backend`]. Backends that keep failing are
+//! taken out of rotation for a cooldown period.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::rt::{self, Instant};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use rand::Rng;
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Backend selection strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BalanceStrategy {
+    /// Cycle through healthy backends in order
+    #[default]
+    RoundRobin,
+    /// Pick the backend with the fewest in-flight requests
+    LeastPending,
+    /// Pick randomly, proportionally to backend weights
+    Weighted,
+}
+
+/// Load balancing layer configuration
+#[derive(Clone)]
+pub struct LoadBalancingLayer {
+    backends: Vec<(Arc<dyn Provider>, u32)>,
+    inner_weight: u32,
+    strategy: BalanceStrategy,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl LoadBalancingLayer {
+    /// Create a new load balancing layer with default settings
+    pub fn new() -> Self {
+        Self {
+            backends: Vec::new(),
+            inner_weight: 1,
+            strategy: BalanceStrategy::RoundRobin,
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+
+    /// Add a backend with weight 1
+    pub fn with_backend(self, provider: impl Provider) -> Self {
+        self.with_weighted_backend(provider, 1)
+    }
+
+    /// Add a backend with a weight (used by [`BalanceStrategy::Weighted`])
+    pub fn with_weighted_backend(mut self, provider: impl Provider, weight: u32) -> Self {
+        self.backends.push((Arc::new(provider), weight));
+        self
+    }
+
+    /// Set the weight of the wrapped provider (default: 1)
+    pub fn with_inner_weight(mut self, weight: u32) -> Self {
+        self.inner_weight = weight;
+        self
+    }
+
+    /// Set the selection strategy
+    pub fn with_strategy(mut self, strategy: BalanceStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Consecutive failures before a backend is taken out of rotation
+    /// (default: 3)
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// How long an unhealthy backend stays out of rotation (default: 30s)
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+impl Default for LoadBalancingLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for LoadBalancingLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadBalancingLayer")
+            .field("backends", &self.backends.len())
+            .field("inner_weight", &self.inner_weight)
+            .field("strategy", &self.strategy)
+            .field("failure_threshold", &self.failure_threshold)
+            .field("cooldown", &self.cooldown)
+            .finish()
+    }
+}
+
+impl<P: Provider> Layer<P> for LoadBalancingLayer {
+    type LayeredProvider = LoadBalancingProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        let inner = Arc::new(inner);
+        let mut backends = vec![Arc::new(Backend::new(
+            inner.clone() as Arc<dyn Provider>,
+            self.inner_weight,
+        ))];
+        backends.extend(
+            self.backends
+                .iter()
+                .map(|(provider, weight)| Arc::new(Backend::new(provider.clone(), *weight))),
+        );
+
+        LoadBalancingProvider {
+            inner,
+            backends,
+            config: self.clone(),
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Per-backend state
+struct Backend {
+    provider: Arc<dyn Provider>,
+    weight: u32,
+    pending: AtomicUsize,
+    failures: AtomicU32,
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl Backend {
+    fn new(provider: Arc<dyn Provider>, weight: u32) -> Self {
+        Self {
+            provider,
+            weight,
+            pending: AtomicUsize::new(0),
+            failures: AtomicU32::new(0),
+            unhealthy_until: Mutex::new(None),
+        }
+    }
+
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until
+            .lock()
+            .unwrap()
+            .map_or(true, |until| now >= until)
+    }
+
+    fn record_success(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        *self.unhealthy_until.lock().unwrap() = None;
+    }
+
+    fn record_failure(&self, error: &AiError, config: &LoadBalancingLayer) {
+        // Errors caused by the request itself say nothing about the backend
+        let counts = error.is_retryable() || matches!(error, AiError::Authentication { .. });
+        if !counts {
+            return;
+        }
+
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        let cooldown = if failures >= config.failure_threshold {
+            Some(config.cooldown)
+        } else {
+            None
+        };
+        // A rate-limited key is useless until its advertised reset
+        let cooldown = match (cooldown, error.retry_after()) {
+            (Some(cooldown), Some(retry_after)) => Some(cooldown.max(retry_after)),
+            (cooldown, retry_after) => cooldown.or(retry_after),
+        };
+
+        if let Some(cooldown) = cooldown {
+            tracing::warn!(
+                "Backend {} marked unhealthy for {:?} after {} failure(s)",
+                self.provider.info().id,
+                cooldown,
+                failures
+            );
+            *self.unhealthy_until.lock().unwrap() = Some(rt::now() + cooldown);
+        }
+    }
+}
+
+/// Decrements a backend's pending counter when dropped
+struct PendingGuard(Arc<Backend>);
+
+impl PendingGuard {
+    fn new(backend: Arc<Backend>) -> Self {
+        backend.pending.fetch_add(1, Ordering::Relaxed);
+        Self(backend)
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Provider that spreads requests across several backends
+pub struct LoadBalancingProvider<P> {
+    inner: Arc<P>,
+    backends: Vec<Arc<Backend>>,
+    config: LoadBalancingLayer,
+    next: AtomicUsize,
+}
+
+impl<P> Debug for LoadBalancingProvider<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadBalancingProvider")
+            .field("backends", &self.backends.len())
+            .field("strategy", &self.config.strategy)
+            .finish()
+    }
+}
+
+impl<P: Provider> LoadBalancingProvider<P> {
+    /// Pick a backend according to the configured strategy.
+    ///
+    /// Unhealthy backends are skipped; if every backend is unhealthy, all of
+    /// them are considered so requests still go out.
+    fn select(&self) -> Arc<Backend> {
+        let now = rt::now();
+        let healthy: Vec<&Arc<Backend>> = self
+            .backends
+            .iter()
+            .filter(|backend| backend.is_healthy(now))
+            .collect();
+        let candidates = if healthy.is_empty() {
+            self.backends.iter().collect()
+        } else {
+            healthy
+        };
+
+        let chosen = match self.config.strategy {
+            BalanceStrategy::RoundRobin => {
+                let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                candidates[index]
+            }
+            BalanceStrategy::LeastPending => candidates
+                .iter()
+                .min_by_key(|backend| backend.pending.load(Ordering::Relaxed))
+                .copied()
+                .unwrap_or(candidates[0]),
+            BalanceStrategy::Weighted => {
+                let total: u32 = candidates.iter().map(|backend| backend.weight).sum();
+                if total == 0 {
+                    candidates[0]
+                } else {
+                    let mut point = rand::rng().random_range(0..total);
+                    candidates
+                        .iter()
+                        .find(|backend| {
+                            if point < backend.weight {
+                                true
+                            } else {
+                                point -= backend.weight;
+                                false
+                            }
+                        })
+                        .copied()
+                        .unwrap_or(candidates[0])
+                }
+            }
+        };
+        chosen.clone()
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for LoadBalancingProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let backend = self.select();
+        let _guard = PendingGuard::new(backend.clone());
+
+        let result = backend.provider.chat_completion(req).await;
+        match &result {
+            Ok(_) => backend.record_success(),
+            Err(e) => backend.record_failure(e, &self.config),
+        }
+        result
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let backend = self.select();
+        let guard = PendingGuard::new(backend.clone());
+
+        match backend.provider.stream_chat_completion(req).await {
+            Ok(stream) => {
+                backend.record_success();
+                // Keep the request pending until the stream is dropped
+                let stream = stream.map(move |chunk| {
+                    let _ = &guard;
+                    chunk
+                });
+                Ok(Box::new(stream))
+            }
+            Err(e) => {
+                backend.record_failure(&e, &self.config);
+                Err(e)
+            }
+        }
+    }
+
+    async fn layered_chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        let backend = self.select();
+        let _guard = PendingGuard::new(backend.clone());
+
+        let result = backend.provider.chat_completion_raw(body).await;
+        match &result {
+            Ok(_) => backend.record_success(),
+            Err(e) => backend.record_failure(e, &self.config),
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for LoadBalancingProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct StubProvider {
+        id: &'static str,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Provider for StubProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: self.id.to_string(),
+                name: self.id.to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            if self.fail {
+                return Err(AiError::rate_limit("slow down"));
+            }
+            Ok(ChatCompletionResponse {
+                id: self.id.to_string(),
+                model: "test".to_string(),
+                choices: Vec::new(),
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failing_backend_leaves_rotation() {
+        let provider = LoadBalancingLayer::new()
+            .with_backend(StubProvider {
+                id: "b",
+                fail: false,
+            })
+            .with_failure_threshold(1)
+            .layer(StubProvider {
+                id: "a",
+                fail: true,
+            });
+
+        let req = ChatCompletionRequest::new("test", Vec::new());
+        assert!(provider.chat_completion(req.clone()).await.is_err());
+        for _ in 0..4 {
+            let response = provider.chat_completion(req.clone()).await.unwrap();
+            assert_eq!(response.id, "b");
+        }
+    }
+}
@@ -1,8 +1,9 @@
 //! Logging layer for provider operations.
 
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
 use aidale_core::error::AiError;
 use aidale_core::layer::{Layer, LayeredProvider};
-use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
 use aidale_core::types::*;
 use async_trait::async_trait;
 use std::fmt::Debug;
@@ -73,7 +74,7 @@ impl<P: Provider> LayeredProvider for LoggingProvider<P> {
             req.messages.len()
         );
 
-        let start = std::time::Instant::now();
+        let start = aidale_core::rt::now();
         let result = self.inner.chat_completion(req).await;
         let elapsed = start.elapsed();
 
@@ -111,7 +112,7 @@ impl<P: Provider> LayeredProvider for LoggingProvider<P> {
             req.messages.len()
         );
 
-        let start = std::time::Instant::now();
+        let start = aidale_core::rt::now();
         let result = self.inner.stream_chat_completion(req).await;
         let elapsed = start.elapsed();
 
@@ -135,6 +136,143 @@ impl<P: Provider> LayeredProvider for LoggingProvider<P> {
 
         result
     }
+
+    async fn layered_chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        tracing::debug!(
+            "{} chat_completion_raw request: model={}",
+            self.prefix,
+            body["model"]
+        );
+
+        let start = aidale_core::rt::now();
+        let result = self.inner.chat_completion_raw(body).await;
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(_) => {
+                tracing::debug!(
+                    "{} chat_completion_raw success, elapsed={:?}",
+                    self.prefix,
+                    elapsed
+                );
+            }
+            Err(e) => {
+                tracing::error!(
+                    "{} chat_completion_raw error: {:?}, elapsed={:?}",
+                    self.prefix,
+                    e,
+                    elapsed
+                );
+            }
+        }
+
+        result
+    }
+
+    async fn layered_api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        tracing::debug!(
+            "{} api_request: {} {}",
+            self.prefix,
+            req.method.as_str(),
+            req.path
+        );
+
+        let start = aidale_core::rt::now();
+        let result = self.inner.api_request(req).await;
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(_) => {
+                tracing::debug!("{} api_request success, elapsed={:?}", self.prefix, elapsed);
+            }
+            Err(e) => {
+                tracing::error!(
+                    "{} api_request error: {:?}, elapsed={:?}",
+                    self.prefix,
+                    e,
+                    elapsed
+                );
+            }
+        }
+
+        result
+    }
+
+    async fn layered_speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        tracing::debug!(
+            "{} speech: model={}, voice={}, chars={}",
+            self.prefix,
+            req.model,
+            req.voice,
+            req.input.chars().count()
+        );
+
+        let start = aidale_core::rt::now();
+        let result = self.inner.speech(req).await;
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(speech) => {
+                tracing::debug!(
+                    "{} speech success, bytes={}, elapsed={:?}",
+                    self.prefix,
+                    speech.audio.len(),
+                    elapsed
+                );
+            }
+            Err(e) => {
+                tracing::error!(
+                    "{} speech error: {:?}, elapsed={:?}",
+                    self.prefix,
+                    e,
+                    elapsed
+                );
+            }
+        }
+
+        result
+    }
+
+    async fn layered_transcription(
+        &self,
+        req: TranscriptionRequest,
+    ) -> Result<Transcription, AiError> {
+        tracing::debug!(
+            "{} transcription: model={}, format={}, bytes={}",
+            self.prefix,
+            req.model,
+            req.format,
+            req.audio.len()
+        );
+
+        let start = aidale_core::rt::now();
+        let result = self.inner.transcription(req).await;
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(transcription) => {
+                tracing::debug!(
+                    "{} transcription success, chars={}, elapsed={:?}",
+                    self.prefix,
+                    transcription.text.chars().count(),
+                    elapsed
+                );
+            }
+            Err(e) => {
+                tracing::error!(
+                    "{} transcription error: {:?}, elapsed={:?}",
+                    self.prefix,
+                    e,
+                    elapsed
+                );
+            }
+        }
+
+        result
+    }
 }
 
 #[async_trait]
@@ -156,4 +294,31 @@ impl<P: Provider> Provider for LoggingProvider<P> {
     ) -> Result<Box<ChatCompletionStream>, AiError> {
         LayeredProvider::layered_stream_chat_completion(self, req).await
     }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
 }
@@ -0,0 +1,413 @@
+//! Payload logging layer.
+//!
+//! Emits full request and response payloads as structured JSON events for
+//! auditing. Events go to a pluggable [`PayloadSink`] and support field-level
+//! redaction, sampling, and size limits.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::rt::{self, Instant};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Kind of payload event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadEventKind {
+    Request,
+    Response,
+    Error,
+}
+
+/// A structured payload log event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadEvent {
+    /// Correlates the request event with its response or error
+    pub exchange_id: String,
+    pub kind: PayloadEventKind,
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub provider: String,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+    pub payload: serde_json::Value,
+}
+
+/// Destination for payload events
+pub trait PayloadSink: Send + Sync + Debug + 'static {
+    /// Emit an event
+    fn emit(&self, event: &PayloadEvent);
+}
+
+/// Sink that emits events through `tracing` at INFO level
+#[derive(Debug, Clone, Default)]
+pub struct TracingSink;
+
+impl PayloadSink for TracingSink {
+    fn emit(&self, event: &PayloadEvent) {
+        match serde_json::to_string(event) {
+            Ok(json) => tracing::info!(target: "aidale::payload", "{}", json),
+            Err(e) => tracing::warn!("Failed to serialize payload event: {}", e),
+        }
+    }
+}
+
+/// Sink that appends events to a file as JSON lines
+#[derive(Debug)]
+pub struct FileSink {
+    file: Mutex<std::fs::File>,
+}
+
+impl FileSink {
+    /// Open (or create) a file for appending
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, AiError> {
+        let path = path.as_ref();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                AiError::configuration(format!("Failed to open {}: {}", path.display(), e))
+            })?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl PayloadSink for FileSink {
+    fn emit(&self, event: &PayloadEvent) {
+        let result = serde_json::to_string(event)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let mut file = self.file.lock().unwrap();
+                writeln!(file, "{}", json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to write payload event: {}", e);
+        }
+    }
+}
+
+/// Type alias for payload callbacks
+type PayloadCallback = Arc<dyn Fn(&PayloadEvent) + Send + Sync>;
+
+/// Sink that forwards events to a callback
+#[derive(Clone)]
+pub struct CallbackSink {
+    callback: PayloadCallback,
+}
+
+impl CallbackSink {
+    /// Create a sink from a callback
+    pub fn new(callback: impl Fn(&PayloadEvent) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Arc::new(callback),
+        }
+    }
+}
+
+impl Debug for CallbackSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackSink").finish()
+    }
+}
+
+impl PayloadSink for CallbackSink {
+    fn emit(&self, event: &PayloadEvent) {
+        (self.callback)(event)
+    }
+}
+
+/// Payload logging layer configuration
+#[derive(Debug, Clone)]
+pub struct PayloadLoggingLayer {
+    sink: Arc<dyn PayloadSink>,
+    redact_fields: HashSet<String>,
+    sample_rate: f64,
+    max_size: Option<usize>,
+}
+
+impl PayloadLoggingLayer {
+    /// Create a layer that logs every exchange to the given sink
+    pub fn new(sink: Arc<dyn PayloadSink>) -> Self {
+        Self {
+            sink,
+            redact_fields: HashSet::new(),
+            sample_rate: 1.0,
+            max_size: None,
+        }
+    }
+
+    /// Redact every JSON field with this name, at any depth
+    pub fn redact_field(mut self, field: impl Into<String>) -> Self {
+        self.redact_fields.insert(field.into());
+        self
+    }
+
+    /// Log only a fraction (0.0 - 1.0) of exchanges
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = sample_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Truncate serialized payloads larger than `max_size` bytes
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    fn sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || rand::random::<f64>() < self.sample_rate
+    }
+
+    /// Replace values of redacted fields
+    fn redact(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.redact_fields.contains(key) {
+                        *value = serde_json::Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.redact(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Redact and size-limit a payload
+    fn prepare<T: Serialize>(&self, payload: &T) -> serde_json::Value {
+        let mut value = serde_json::to_value(payload).unwrap_or_default();
+        self.redact(&mut value);
+
+        if let Some(max_size) = self.max_size {
+            let json = value.to_string();
+            if json.len() > max_size {
+                let mut cut = max_size;
+                while !json.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                value = serde_json::Value::String(format!(
+                    "{}...[truncated {} bytes]",
+                    &json[..cut],
+                    json.len() - cut
+                ));
+            }
+        }
+
+        value
+    }
+}
+
+impl<P: Provider> Layer<P> for PayloadLoggingLayer {
+    type LayeredProvider = PayloadLoggingProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        PayloadLoggingProvider {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Provider wrapped with payload logging
+#[derive(Debug)]
+pub struct PayloadLoggingProvider<P> {
+    inner: P,
+    config: PayloadLoggingLayer,
+}
+
+/// Per-exchange event emitter
+#[derive(Debug, Clone)]
+struct Exchange {
+    config: PayloadLoggingLayer,
+    id: String,
+    provider: String,
+    model: String,
+    start: Instant,
+}
+
+impl Exchange {
+    fn emit<T: Serialize>(&self, kind: PayloadEventKind, payload: &T) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let elapsed_ms =
+            (kind != PayloadEventKind::Request).then(|| self.start.elapsed().as_millis() as u64);
+
+        self.config.sink.emit(&PayloadEvent {
+            exchange_id: self.id.clone(),
+            kind,
+            timestamp_ms,
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            elapsed_ms,
+            payload: self.config.prepare(payload),
+        });
+    }
+}
+
+impl<P: Provider> PayloadLoggingProvider<P> {
+    /// Start an exchange if this request is sampled
+    fn start(&self, req: &ChatCompletionRequest) -> Option<Exchange> {
+        if !self.config.sampled() {
+            return None;
+        }
+        let exchange = Exchange {
+            config: self.config.clone(),
+            id: uuid::Uuid::new_v4().to_string(),
+            provider: self.inner.info().id.clone(),
+            model: req.model.clone(),
+            start: rt::now(),
+        };
+        exchange.emit(PayloadEventKind::Request, req);
+        Some(exchange)
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for PayloadLoggingProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let exchange = self.start(&req);
+        let result = self.inner.chat_completion(req).await;
+
+        if let Some(exchange) = exchange {
+            match &result {
+                Ok(response) => exchange.emit(PayloadEventKind::Response, response),
+                Err(e) => exchange.emit(PayloadEventKind::Error, &e.to_string()),
+            }
+        }
+
+        result
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let exchange = self.start(&req);
+        let result = self.inner.stream_chat_completion(req).await;
+
+        let Some(exchange) = exchange else {
+            return result;
+        };
+        let mut stream = match result {
+            Ok(stream) => stream,
+            Err(e) => {
+                exchange.emit(PayloadEventKind::Error, &e.to_string());
+                return Err(e);
+            }
+        };
+
+        // Forward chunks unchanged and emit the collected chunks at the end
+        let logged = async_stream::stream! {
+            let mut chunks = Vec::new();
+            while let Some(item) = stream.next().await {
+                match &item {
+                    Ok(chunk) => chunks.push(chunk.clone()),
+                    Err(e) => exchange.emit(PayloadEventKind::Error, &e.to_string()),
+                }
+                yield item;
+            }
+            exchange.emit(PayloadEventKind::Response, &chunks);
+        };
+
+        Ok(Box::new(Box::pin(logged)))
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for PayloadLoggingProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_and_truncate() {
+        let layer = PayloadLoggingLayer::new(Arc::new(TracingSink)).redact_field("text");
+        let req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("secret")]);
+
+        let payload = layer.prepare(&req);
+        assert_eq!(payload["messages"][0]["content"][0]["text"], REDACTED);
+        assert_eq!(payload["model"], "gpt-4o");
+
+        let payload = layer.with_max_size(10).prepare(&req);
+        assert!(payload.as_str().unwrap().contains("[truncated"));
+    }
+}
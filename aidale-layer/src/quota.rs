@@ -0,0 +1,616 @@
+//! Quota layer for per-tenant request, token and cost limits.
+//!
+//! Each tenant gets token buckets that refill continuously: a limit of 60
+//! requests per minute allows a burst of 60 and then one more request every
+//! second. Requests beyond a limit fail with a rate limit error carrying the
+//! time until the bucket has room again, which [`RetryLayer`] honors.
+//!
+//! Token usage and cost are only known once a response arrives, so they are
+//! charged afterwards: a request is admitted while the tenant has budget
+//! left, and a large response may overdraw it, delaying later requests
+//! until the debt has refilled.
+//!
+//! The tenant is read from the request metadata, which the executor copies
+//! from the [`RequestContext`]:
+//!
+//! ```ignore
+//! let quota = QuotaLayer::new()
+//!     .with_limits(QuotaLimits::new().with_requests_per_minute(60))
+//!     .with_tenant_limits(
+//!         "acme",
+//!         QuotaLimits::new()
+//!             .with_requests_per_minute(600)
+//!             .with_tokens_per_minute(200_000)
+//!             .with_cost(50.0, Duration::from_secs(86_400)),
+//!     )
+//!     .with_pricing(|model, usage| pricing.cost(model, usage));
+//! let executor = RuntimeExecutor::builder(provider).layer(quota).finish();
+//! ```
+//!
+//! Buckets live in a [`QuotaStore`]. The default [`InMemoryQuotaStore`]
+//! enforces limits within one process; with the `redis` feature,
+//! `RedisQuotaStore` shares them between instances.
+//!
+//! [`RetryLayer`]: crate::RetryLayer
+//! [`RequestContext`]: aidale_core::types::RequestContext
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::rt::{self, Instant};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Refill rate of a token bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    /// Bucket size, refilled in full over `window`
+    pub capacity: f64,
+    pub window: Duration,
+}
+
+impl Rate {
+    /// `capacity` units per `window`
+    pub fn new(capacity: f64, window: Duration) -> Self {
+        Self { capacity, window }
+    }
+
+    /// Time to refill `amount` units
+    pub fn refill_time(&self, amount: f64) -> Duration {
+        Duration::from_secs_f64((amount * self.window.as_secs_f64() / self.capacity).max(0.0))
+    }
+
+    /// Units refilled in `elapsed`
+    pub fn refilled(&self, elapsed: Duration) -> f64 {
+        self.capacity * elapsed.as_secs_f64() / self.window.as_secs_f64()
+    }
+}
+
+/// Limits of one tenant
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuotaLimits {
+    requests: Option<Rate>,
+    tokens: Option<Rate>,
+    cost: Option<Rate>,
+}
+
+impl QuotaLimits {
+    /// No limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum requests per minute
+    pub fn with_requests_per_minute(mut self, rpm: u32) -> Self {
+        self.requests = Some(Rate::new(rpm as f64, Duration::from_secs(60)));
+        self
+    }
+
+    /// Maximum total tokens per minute
+    pub fn with_tokens_per_minute(mut self, tpm: u64) -> Self {
+        self.tokens = Some(Rate::new(tpm as f64, Duration::from_secs(60)));
+        self
+    }
+
+    /// Maximum cost per `window`, in the unit of [`QuotaLayer::with_pricing`]
+    pub fn with_cost(mut self, max_cost: f64, window: Duration) -> Self {
+        self.cost = Some(Rate::new(max_cost, window));
+        self
+    }
+}
+
+/// Storage for token buckets, keyed by tenant and limit
+#[async_trait]
+pub trait QuotaStore: Send + Sync + Debug {
+    /// Refill the bucket `key` at `rate`, then take `amount` from it
+    ///
+    /// Returns `None` if the amount was taken, or how long until the bucket
+    /// holds enough if it doesn't. With `overdraw` the amount is always
+    /// taken, possibly leaving the bucket negative. A new bucket starts
+    /// full.
+    async fn take(
+        &self,
+        key: &str,
+        rate: Rate,
+        amount: f64,
+        overdraw: bool,
+    ) -> Result<Option<Duration>, AiError>;
+}
+
+/// Store keeping buckets in process memory
+#[derive(Debug, Default)]
+pub struct InMemoryQuotaStore {
+    /// Level and time of the last update
+    buckets: DashMap<String, (f64, Instant)>,
+}
+
+impl InMemoryQuotaStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl QuotaStore for InMemoryQuotaStore {
+    async fn take(
+        &self,
+        key: &str,
+        rate: Rate,
+        amount: f64,
+        overdraw: bool,
+    ) -> Result<Option<Duration>, AiError> {
+        let now = rt::now();
+        let mut bucket = self
+            .buckets
+            .entry(key.to_string())
+            .or_insert((rate.capacity, now));
+        let (level, updated) = &mut *bucket;
+        *level = (*level + rate.refilled(now - *updated)).min(rate.capacity);
+        *updated = now;
+        if overdraw || *level >= amount {
+            *level -= amount;
+            Ok(None)
+        } else {
+            Ok(Some(rate.refill_time(amount - *level)))
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use redis_store::RedisQuotaStore;
+
+#[cfg(feature = "redis")]
+mod redis_store {
+    use super::{QuotaStore, Rate};
+    use aidale_core::error::AiError;
+    use async_trait::async_trait;
+    use redis::aio::ConnectionManager;
+    use redis::Script;
+    use std::fmt;
+    use std::time::Duration;
+
+    /// Refills and takes atomically on the server clock; returns the wait
+    /// in milliseconds, 0 if the amount was taken
+    const TAKE: &str = r#"
+local capacity = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local amount = tonumber(ARGV[3])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local state = redis.call('HMGET', KEYS[1], 'level', 'updated')
+local level = tonumber(state[1]) or capacity
+local updated = tonumber(state[2]) or now
+level = math.min(capacity, level + (now - updated) * capacity / window)
+local wait = 0
+if ARGV[4] == '1' or level >= amount then
+    level = level - amount
+else
+    wait = math.max(1, math.ceil((amount - level) * window / capacity))
+end
+redis.call('HSET', KEYS[1], 'level', tostring(level), 'updated', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil((capacity - level) * window / capacity) + 1)
+return wait
+"#;
+
+    /// Store keeping buckets in Redis, shared by every instance using the
+    /// same server
+    #[derive(Clone)]
+    pub struct RedisQuotaStore {
+        conn: ConnectionManager,
+        prefix: String,
+        script: Script,
+    }
+
+    fn redis_error(e: redis::RedisError) -> AiError {
+        AiError::other(format!("Redis error: {}", e))
+    }
+
+    impl RedisQuotaStore {
+        /// Connect to `url`, e.g. `redis://127.0.0.1/`
+        pub async fn connect(url: &str) -> Result<Self, AiError> {
+            let client = redis::Client::open(url).map_err(redis_error)?;
+            let conn = ConnectionManager::new(client).await.map_err(redis_error)?;
+            Ok(Self::from_connection(conn))
+        }
+
+        /// Use an existing connection
+        pub fn from_connection(conn: ConnectionManager) -> Self {
+            Self {
+                conn,
+                prefix: "aidale:quota:".to_string(),
+                script: Script::new(TAKE),
+            }
+        }
+
+        /// Prefix of the bucket keys (default: `aidale:quota:`)
+        pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+            self.prefix = prefix.into();
+            self
+        }
+    }
+
+    impl fmt::Debug for RedisQuotaStore {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("RedisQuotaStore")
+                .field("prefix", &self.prefix)
+                .finish()
+        }
+    }
+
+    #[async_trait]
+    impl QuotaStore for RedisQuotaStore {
+        async fn take(
+            &self,
+            key: &str,
+            rate: Rate,
+            amount: f64,
+            overdraw: bool,
+        ) -> Result<Option<Duration>, AiError> {
+            let mut conn = self.conn.clone();
+            let wait_ms: u64 = self
+                .script
+                .key(format!("{}{}", self.prefix, key))
+                .arg(rate.capacity)
+                .arg(rate.window.as_millis().max(1) as u64)
+                .arg(amount)
+                .arg(if overdraw { "1" } else { "0" })
+                .invoke_async(&mut conn)
+                .await
+                .map_err(redis_error)?;
+            Ok((wait_ms > 0).then(|| Duration::from_millis(wait_ms)))
+        }
+    }
+}
+
+type Pricing = Arc<dyn Fn(&str, &Usage) -> f64 + Send + Sync>;
+
+/// Quota layer configuration
+#[derive(Clone)]
+pub struct QuotaLayer {
+    limits: QuotaLimits,
+    tenants: HashMap<String, QuotaLimits>,
+    tenant_key: String,
+    store: Arc<dyn QuotaStore>,
+    pricing: Option<Pricing>,
+}
+
+impl QuotaLayer {
+    /// Create a quota layer without limits, keeping buckets in memory
+    ///
+    /// Providers layered with clones of this layer share its store.
+    pub fn new() -> Self {
+        Self {
+            limits: QuotaLimits::new(),
+            tenants: HashMap::new(),
+            tenant_key: "tenant".to_string(),
+            store: Arc::new(InMemoryQuotaStore::new()),
+            pricing: None,
+        }
+    }
+
+    /// Limits of tenants without their own
+    pub fn with_limits(mut self, limits: QuotaLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits of `tenant`, replacing the defaults
+    pub fn with_tenant_limits(mut self, tenant: impl Into<String>, limits: QuotaLimits) -> Self {
+        self.tenants.insert(tenant.into(), limits);
+        self
+    }
+
+    /// Metadata key identifying the tenant (default: `tenant`)
+    ///
+    /// Requests without it share a single anonymous tenant.
+    pub fn with_tenant_key(mut self, key: impl Into<String>) -> Self {
+        self.tenant_key = key.into();
+        self
+    }
+
+    /// Keep buckets in `store`, e.g. one shared between instances
+    pub fn with_store(mut self, store: impl QuotaStore + 'static) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// Cost of a response from its model and usage; cost limits are not
+    /// enforced without it
+    pub fn with_pricing(
+        mut self,
+        pricing: impl Fn(&str, &Usage) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        self.pricing = Some(Arc::new(pricing));
+        self
+    }
+
+    fn tenant(&self, req: &ChatCompletionRequest) -> String {
+        req.metadata
+            .get(&self.tenant_key)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn limits(&self, tenant: &str) -> &QuotaLimits {
+        self.tenants.get(tenant).unwrap_or(&self.limits)
+    }
+
+    /// Admit a request or fail with the time until the tenant has budget
+    async fn admit(&self, tenant: &str) -> Result<(), AiError> {
+        let limits = self.limits(tenant);
+        let cost = limits.cost.filter(|_| self.pricing.is_some());
+        // Tokens and cost only need a non-negative balance up front
+        let checks = [
+            ("tokens", limits.tokens, 0.0),
+            ("cost", cost, 0.0),
+            ("requests", limits.requests, 1.0),
+        ];
+        for (kind, rate, amount) in checks {
+            let Some(rate) = rate else { continue };
+            let key = format!("{}:{}", tenant, kind);
+            if let Some(wait) = self.store.take(&key, rate, amount, false).await? {
+                return Err(AiError::rate_limit_after(
+                    format!("Tenant '{}' exceeded its {} quota", tenant, kind),
+                    Some(wait),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Charge the tokens and cost of a response
+    async fn charge(&self, tenant: &str, model: &str, usage: &Usage) {
+        let limits = self.limits(tenant);
+        let cost = self.pricing.as_ref().map(|pricing| pricing(model, usage));
+        let charges = [
+            ("tokens", limits.tokens, Some(usage.total_tokens as f64)),
+            ("cost", limits.cost, cost),
+        ];
+        for (kind, rate, amount) in charges {
+            let (Some(rate), Some(amount)) = (rate, amount) else {
+                continue;
+            };
+            let key = format!("{}:{}", tenant, kind);
+            // The response is already paid for; failing it now would only
+            // lose it
+            if let Err(e) = self.store.take(&key, rate, amount, true).await {
+                tracing::warn!("Failed to charge {} quota of '{}': {}", kind, tenant, e);
+            }
+        }
+    }
+}
+
+impl Default for QuotaLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for QuotaLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaLayer")
+            .field("limits", &self.limits)
+            .field("tenants", &self.tenants)
+            .field("tenant_key", &self.tenant_key)
+            .field("store", &self.store)
+            .field("pricing", &self.pricing.is_some())
+            .finish()
+    }
+}
+
+impl<P: Provider> Layer<P> for QuotaLayer {
+    type LayeredProvider = QuotaProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        QuotaProvider {
+            inner,
+            config: Arc::new(self.clone()),
+        }
+    }
+}
+
+/// Provider that enforces per-tenant quotas
+pub struct QuotaProvider<P> {
+    inner: P,
+    config: Arc<QuotaLayer>,
+}
+
+impl<P: Debug> Debug for QuotaProvider<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaProvider")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for QuotaProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let tenant = self.config.tenant(&req);
+        self.config.admit(&tenant).await?;
+        let response = self.inner.chat_completion(req).await?;
+        self.config
+            .charge(&tenant, &response.model, &response.usage)
+            .await;
+        Ok(response)
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let tenant = self.config.tenant(&req);
+        self.config.admit(&tenant).await?;
+        let stream = self.inner.stream_chat_completion(req).await?;
+        // Usage arrives with the last chunk, if the provider reports it
+        let config = self.config.clone();
+        let stream = stream.then(move |chunk| {
+            let config = config.clone();
+            let tenant = tenant.clone();
+            async move {
+                if let Ok(ChatCompletionChunk {
+                    model,
+                    usage: Some(usage),
+                    ..
+                }) = &chunk
+                {
+                    config.charge(&tenant, model, usage).await;
+                }
+                chunk
+            }
+        });
+        Ok(Box::new(Box::pin(stream)))
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for QuotaProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every request with 100 tokens
+    #[derive(Debug)]
+    struct FixedUsage;
+
+    #[async_trait]
+    impl Provider for FixedUsage {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "fixed".to_string(),
+                name: "Fixed".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            Ok(ChatCompletionResponse {
+                id: "1".to_string(),
+                model: req.model,
+                choices: Vec::new(),
+                usage: Usage {
+                    total_tokens: 100,
+                    ..Default::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    fn retry_after(result: Result<ChatCompletionResponse, AiError>) -> Option<Duration> {
+        match result {
+            Err(AiError::RateLimit { retry_after, .. }) => retry_after,
+            other => panic!("expected a rate limit error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tenant_quotas() {
+        let provider = QuotaLayer::new()
+            .with_limits(QuotaLimits::new().with_requests_per_minute(2))
+            .with_tenant_limits(
+                "acme",
+                QuotaLimits::new()
+                    .with_tokens_per_minute(150)
+                    .with_cost(1.0, Duration::from_secs(3600)),
+            )
+            .with_pricing(|_, usage| usage.total_tokens as f64 / 1000.0)
+            .layer(FixedUsage);
+        let request = |tenant: &str| {
+            ChatCompletionRequest::new("m", Vec::new()).with_metadata("tenant", tenant)
+        };
+
+        // Two requests per minute, one more every 30s
+        for _ in 0..2 {
+            provider.chat_completion(request("free")).await.unwrap();
+        }
+        let wait = retry_after(provider.chat_completion(request("free")).await);
+        assert_eq!(wait, Some(Duration::from_secs(30)));
+        tokio::time::advance(Duration::from_secs(30)).await;
+        provider.chat_completion(request("free")).await.unwrap();
+
+        // 150 tokens per minute: the second response overdraws the budget
+        // by 50 tokens, which takes 20s to refill
+        for _ in 0..2 {
+            provider.chat_completion(request("acme")).await.unwrap();
+        }
+        let wait = retry_after(provider.chat_completion(request("acme")).await);
+        assert_eq!(wait, Some(Duration::from_secs(20)));
+        tokio::time::advance(Duration::from_secs(20)).await;
+        provider.chat_completion(request("acme")).await.unwrap();
+    }
+}
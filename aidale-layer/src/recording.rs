@@ -0,0 +1,499 @@
+//! Record-and-replay of provider interactions.
+//!
+//! [`RecordingLayer`] captures every successful request/response pair,
+//! including the chunks of streamed responses, into a cassette file.
+//! [`ReplayProvider`] serves a cassette back without network access, which
+//! makes integration tests hermetic and lets demos run without API keys.
+//!
+//! A cassette is a pretty-printed JSON file. Interactions are matched by a
+//! hash of the request body (model, messages and parameters; headers and
+//! deadlines are ignored). Identical requests are replayed in the order they
+//! were recorded.
+//!
+//! ```ignore
+//! // Record once against the real API...
+//! let executor = RuntimeExecutor::builder(openai)
+//!     .layer(RecordingLayer::new("tests/cassettes/chat.json"))
+//!     .finish();
+//!
+//! // ...then replay offline
+//! let executor = RuntimeExecutor::builder(ReplayProvider::from_file("tests/cassettes/chat.json")?)
+//!     .finish();
+//! ```
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Current cassette format version
+const CASSETTE_VERSION: u32 = 1;
+
+/// Outcome of a recorded request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedOutcome {
+    /// Non-streaming chat completion
+    Response { response: ChatCompletionResponse },
+    /// Streaming chat completion, chunk by chunk
+    Stream { chunks: Vec<ChatCompletionChunk> },
+}
+
+/// A recorded request with its outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// Hash of the request, see [`Cassette::request_key`]
+    pub key: String,
+    pub request: ChatCompletionRequest,
+    #[serde(flatten)]
+    pub outcome: RecordedOutcome,
+}
+
+/// A recorded session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cassette {
+    pub version: u32,
+    /// ID of the recorded provider, reported again on replay so that
+    /// provider-specific strategies behave the same
+    pub provider_id: String,
+    pub provider_name: String,
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Create an empty cassette for a provider
+    pub fn new(provider_id: impl Into<String>, provider_name: impl Into<String>) -> Self {
+        Self {
+            version: CASSETTE_VERSION,
+            provider_id: provider_id.into(),
+            provider_name: provider_name.into(),
+            interactions: Vec::new(),
+        }
+    }
+
+    /// Load a cassette from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AiError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| {
+            AiError::configuration(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let cassette: Self = serde_json::from_str(&json)?;
+        if cassette.version != CASSETTE_VERSION {
+            return Err(AiError::configuration(format!(
+                "Unsupported cassette version {} in {}",
+                cassette.version,
+                path.display()
+            )));
+        }
+        Ok(cassette)
+    }
+
+    /// Write the cassette to a file, replacing it
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AiError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                AiError::configuration(format!("Failed to create {}: {}", dir.display(), e))
+            })?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n").map_err(|e| {
+            AiError::configuration(format!("Failed to write {}: {}", path.display(), e))
+        })
+    }
+
+    /// Stable key identifying a request
+    ///
+    /// FNV-1a hash of the request body serialized with sorted keys, so the
+    /// key does not depend on `HashMap` iteration order or the Rust version.
+    pub fn request_key(req: &ChatCompletionRequest) -> String {
+        let value = serde_json::to_value(req).unwrap_or_default();
+        let json = canonicalize(value).to_string();
+
+        let hash = json.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        format!("{:016x}", hash)
+    }
+}
+
+/// Rebuild objects with their keys in sorted order
+fn canonicalize(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonicalize(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(canonicalize).collect())
+        }
+        other => other,
+    }
+}
+
+/// Layer that records interactions to a cassette file
+///
+/// The cassette is rewritten after every recorded interaction. Failed
+/// requests and streams that end in an error are not recorded.
+#[derive(Debug, Clone)]
+pub struct RecordingLayer {
+    path: PathBuf,
+    cassette: Arc<Mutex<Option<Cassette>>>,
+}
+
+impl RecordingLayer {
+    /// Record into a new cassette at `path`, replacing any existing file
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cassette: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn record(
+        &self,
+        info: &ProviderInfo,
+        request: ChatCompletionRequest,
+        outcome: RecordedOutcome,
+    ) {
+        let mut cassette = self.cassette.lock().unwrap();
+        let cassette = cassette.get_or_insert_with(|| Cassette::new(&info.id, &info.name));
+        cassette.interactions.push(Interaction {
+            key: Cassette::request_key(&request),
+            request,
+            outcome,
+        });
+
+        if let Err(e) = cassette.save(&self.path) {
+            tracing::warn!("Failed to write cassette: {}", e);
+        }
+    }
+}
+
+impl<P: Provider> Layer<P> for RecordingLayer {
+    type LayeredProvider = RecordingProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        RecordingProvider {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Provider wrapped with recording
+#[derive(Debug)]
+pub struct RecordingProvider<P> {
+    inner: P,
+    config: RecordingLayer,
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for RecordingProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let response = self.inner.chat_completion(req.clone()).await?;
+        self.config.record(
+            &self.inner.info(),
+            req,
+            RecordedOutcome::Response {
+                response: response.clone(),
+            },
+        );
+        Ok(response)
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let mut stream = self.inner.stream_chat_completion(req.clone()).await?;
+        let config = self.config.clone();
+        let info = self.inner.info();
+
+        // Forward chunks unchanged and record them once the stream completes
+        let recorded = async_stream::stream! {
+            let mut chunks = Vec::new();
+            let mut failed = false;
+            while let Some(item) = stream.next().await {
+                match &item {
+                    Ok(chunk) => chunks.push(chunk.clone()),
+                    Err(_) => failed = true,
+                }
+                yield item;
+            }
+            if !failed {
+                config.record(&info, req, RecordedOutcome::Stream { chunks });
+            }
+        };
+
+        Ok(Box::new(Box::pin(recorded)))
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for RecordingProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+/// Provider that serves recorded interactions
+///
+/// Requests without a recording fail with `AiError::InvalidRequest`. When a
+/// request was recorded several times, the recordings are served in order
+/// and the last one is repeated once they run out.
+#[derive(Debug)]
+pub struct ReplayProvider {
+    info: Arc<ProviderInfo>,
+    outcomes: HashMap<String, Vec<RecordedOutcome>>,
+    cursors: Mutex<HashMap<String, usize>>,
+}
+
+impl ReplayProvider {
+    /// Replay a cassette
+    pub fn new(cassette: Cassette) -> Self {
+        let mut outcomes: HashMap<String, Vec<RecordedOutcome>> = HashMap::new();
+        for interaction in cassette.interactions {
+            outcomes
+                .entry(interaction.key)
+                .or_default()
+                .push(interaction.outcome);
+        }
+
+        Self {
+            info: Arc::new(ProviderInfo {
+                id: cassette.provider_id,
+                name: cassette.provider_name,
+            }),
+            outcomes,
+            cursors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replay a cassette file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AiError> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Next recorded outcome for a request
+    fn next_outcome(&self, req: &ChatCompletionRequest) -> Result<RecordedOutcome, AiError> {
+        let key = Cassette::request_key(req);
+        let outcomes = self.outcomes.get(&key).ok_or_else(|| {
+            AiError::invalid_request(format!(
+                "No recorded interaction for request {} (model {})",
+                key, req.model
+            ))
+        })?;
+
+        let mut cursors = self.cursors.lock().unwrap();
+        let cursor = cursors.entry(key).or_default();
+        let outcome = outcomes[(*cursor).min(outcomes.len() - 1)].clone();
+        *cursor += 1;
+        Ok(outcome)
+    }
+}
+
+#[async_trait]
+impl Provider for ReplayProvider {
+    fn info(&self) -> Arc<ProviderInfo> {
+        self.info.clone()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        match self.next_outcome(&req)? {
+            RecordedOutcome::Response { response } => Ok(response),
+            RecordedOutcome::Stream { .. } => {
+                Err(AiError::invalid_request("Request was recorded as a stream"))
+            }
+        }
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        match self.next_outcome(&req)? {
+            RecordedOutcome::Stream { chunks } => {
+                Ok(Box::new(futures::stream::iter(chunks.into_iter().map(Ok))))
+            }
+            RecordedOutcome::Response { .. } => Err(AiError::invalid_request(
+                "Request was recorded without streaming",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Numbers its responses so replays can be told apart
+    #[derive(Debug, Default)]
+    struct CountingProvider {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Provider for CountingProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "deepseek".to_string(),
+                name: "DeepSeek".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(ChatCompletionResponse {
+                id: format!("resp-{}", call),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(format!("answer {}", call)),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            let chunk = ChatCompletionChunk {
+                id: "chunk".to_string(),
+                model: req.model,
+                choices: vec![ChoiceDelta {
+                    index: 0,
+                    delta: MessageDelta {
+                        role: None,
+                        content: Some("streamed".to_string()),
+                        reasoning: None,
+                        tool_calls: None,
+                    },
+                    finish_reason: Some(FinishReason::Stop),
+                }],
+                usage: None,
+                system_fingerprint: None,
+                service_tier: None,
+            };
+            Ok(Box::new(futures::stream::iter(vec![Ok(chunk)])))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let path = std::env::temp_dir().join(format!("cassette-{}.json", uuid::Uuid::new_v4()));
+        let recorder = RecordingLayer::new(&path).layer(CountingProvider::default());
+
+        let req = ChatCompletionRequest::new("deepseek-chat", vec![Message::user("Hi")]);
+        recorder.chat_completion(req.clone()).await.unwrap();
+        recorder.chat_completion(req.clone()).await.unwrap();
+        let stream_req = req.clone().with_stream(true);
+        let chunks: Vec<_> = recorder
+            .stream_chat_completion(stream_req.clone())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 1);
+
+        let replay = ReplayProvider::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.info().id, "deepseek");
+
+        let ids: Vec<_> = [
+            replay.chat_completion(req.clone()).await.unwrap().id,
+            replay.chat_completion(req.clone()).await.unwrap().id,
+            replay.chat_completion(req).await.unwrap().id,
+        ]
+        .into();
+        assert_eq!(ids, ["resp-0", "resp-1", "resp-1"]);
+
+        let mut stream = replay.stream_chat_completion(stream_req).await.unwrap();
+        let chunk = stream.next().await.unwrap().unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("streamed"));
+
+        let other = ChatCompletionRequest::new("deepseek-chat", vec![Message::user("Bye")]);
+        assert!(matches!(
+            replay.chat_completion(other).await,
+            Err(AiError::InvalidRequest { .. })
+        ));
+    }
+}
@@ -0,0 +1,364 @@
+//! PII redaction layer.
+//!
+//! Scans outgoing messages for sensitive values (emails, phone numbers,
+//! credit cards, custom patterns) and replaces them with placeholders before
+//! the request leaves the process. Text, tool call arguments and tool
+//! results are scanned; in JSON only string values are rewritten. Placeholders
+//! can optionally be restored in the response, including in the arguments of
+//! tool calls, so callers and tools still see the original values.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+const PHONE_PATTERN: &str = r"\+?\d{1,3}?[-. (]*\d{3}[-. )]*\d{3,4}[-. ]*\d{4}\b";
+const CREDIT_CARD_PATTERN: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+
+/// A named redaction pattern
+#[derive(Debug, Clone)]
+pub struct RedactionRule {
+    name: String,
+    regex: Regex,
+}
+
+impl RedactionRule {
+    /// Create a rule from a regular expression
+    pub fn new(name: impl Into<String>, pattern: &str) -> Result<Self, AiError> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| AiError::configuration(format!("Invalid redaction pattern: {}", e)))?;
+        Ok(Self {
+            name: name.into().to_uppercase(),
+            regex,
+        })
+    }
+
+    fn builtin(name: &str, pattern: &str) -> Self {
+        Self::new(name, pattern).expect("built-in redaction pattern is valid")
+    }
+}
+
+/// Redaction layer configuration
+#[derive(Debug, Clone, Default)]
+pub struct RedactionLayer {
+    rules: Vec<RedactionRule>,
+    restore: bool,
+}
+
+impl RedactionLayer {
+    /// Create a redaction layer without any rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a redaction layer with email, phone and credit card rules
+    pub fn with_defaults() -> Self {
+        Self::new()
+            .with_credit_cards()
+            .with_emails()
+            .with_phone_numbers()
+    }
+
+    /// Redact email addresses
+    pub fn with_emails(self) -> Self {
+        self.with_rule(RedactionRule::builtin("email", EMAIL_PATTERN))
+    }
+
+    /// Redact phone numbers
+    pub fn with_phone_numbers(self) -> Self {
+        self.with_rule(RedactionRule::builtin("phone", PHONE_PATTERN))
+    }
+
+    /// Redact credit card numbers
+    pub fn with_credit_cards(self) -> Self {
+        self.with_rule(RedactionRule::builtin("credit_card", CREDIT_CARD_PATTERN))
+    }
+
+    /// Redact values matching a custom regular expression
+    pub fn with_pattern(self, name: impl Into<String>, pattern: &str) -> Result<Self, AiError> {
+        Ok(self.with_rule(RedactionRule::new(name, pattern)?))
+    }
+
+    /// Add a redaction rule
+    ///
+    /// Rules are applied in insertion order.
+    pub fn with_rule(mut self, rule: RedactionRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Restore original values in non-streaming responses
+    pub fn with_restore(mut self, restore: bool) -> Self {
+        self.restore = restore;
+        self
+    }
+}
+
+impl<P: Provider> Layer<P> for RedactionLayer {
+    type LayeredProvider = RedactionProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        RedactionProvider {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Per-request mapping between placeholders and original values
+#[derive(Debug, Default)]
+struct Redactor {
+    placeholders: HashMap<String, String>,
+    originals: HashMap<String, String>,
+    counters: HashMap<String, usize>,
+}
+
+impl Redactor {
+    fn redact(&mut self, rules: &[RedactionRule], text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in rules {
+            text = rule
+                .regex
+                .replace_all(&text, |caps: &regex::Captures| {
+                    let value = caps[0].to_string();
+                    if let Some(placeholder) = self.originals.get(&value) {
+                        return placeholder.clone();
+                    }
+                    let counter = self.counters.entry(rule.name.clone()).or_insert(0);
+                    *counter += 1;
+                    let placeholder = format!("[REDACTED_{}_{}]", rule.name, counter);
+                    self.originals.insert(value.clone(), placeholder.clone());
+                    self.placeholders.insert(placeholder.clone(), value);
+                    placeholder
+                })
+                .into_owned();
+        }
+        text
+    }
+
+    fn restore(&self, text: &str) -> String {
+        self.placeholders
+            .iter()
+            .fold(text.to_string(), |text, (placeholder, original)| {
+                text.replace(placeholder, original)
+            })
+    }
+
+    /// Redact the string values of a JSON value, leaving keys intact
+    fn redact_value(&mut self, rules: &[RedactionRule], value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(text) => *text = self.redact(rules, text),
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.redact_value(rules, item);
+                }
+            }
+            serde_json::Value::Object(fields) => {
+                for field in fields.values_mut() {
+                    self.redact_value(rules, field);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn restore_value(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(text) => *text = self.restore(text),
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|v| self.restore_value(v)),
+            serde_json::Value::Object(fields) => {
+                fields.values_mut().for_each(|v| self.restore_value(v))
+            }
+            _ => {}
+        }
+    }
+
+    fn redact_messages(&mut self, rules: &[RedactionRule], messages: &mut [Message]) {
+        for msg in messages {
+            for part in &mut msg.content {
+                match part {
+                    ContentPart::Text { text } => *text = self.redact(rules, text),
+                    ContentPart::ToolCall { arguments, .. } => self.redact_value(rules, arguments),
+                    ContentPart::ToolResult { result, .. } => self.redact_value(rules, result),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Provider wrapped with PII redaction
+#[derive(Debug)]
+pub struct RedactionProvider<P> {
+    inner: P,
+    config: RedactionLayer,
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for RedactionProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        mut req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let mut redactor = Redactor::default();
+        redactor.redact_messages(&self.config.rules, &mut req.messages);
+
+        let mut response = self.inner.chat_completion(req).await?;
+
+        if self.config.restore && !redactor.placeholders.is_empty() {
+            for choice in &mut response.choices {
+                for part in &mut choice.message.content {
+                    match part {
+                        ContentPart::Text { text } => *text = redactor.restore(text),
+                        ContentPart::ToolCall { arguments, .. } => {
+                            redactor.restore_value(arguments)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        mut req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        // Placeholders may be split across chunks, so streams are not restored
+        let mut redactor = Redactor::default();
+        redactor.redact_messages(&self.config.rules, &mut req.messages);
+        self.inner.stream_chat_completion(req).await
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for RedactionProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_and_restore() {
+        let layer = RedactionLayer::with_defaults();
+        let mut redactor = Redactor::default();
+
+        let text = "Mail jane@example.com or john@example.com, card 4111 1111 1111 1111, again jane@example.com";
+        let redacted = redactor.redact(&layer.rules, text);
+
+        assert!(!redacted.contains("jane@example.com"));
+        assert!(!redacted.contains("4111"));
+        assert_eq!(redacted.matches("[REDACTED_EMAIL_1]").count(), 2);
+        assert!(redacted.contains("[REDACTED_EMAIL_2]"));
+        assert!(redacted.contains("[REDACTED_CREDIT_CARD_1]"));
+        assert_eq!(redactor.restore(&redacted), text);
+    }
+
+    #[test]
+    fn test_custom_pattern() {
+        let layer = RedactionLayer::new()
+            .with_pattern("employee_id", r"EMP-\d{6}")
+            .unwrap();
+        let mut redactor = Redactor::default();
+
+        let redacted = redactor.redact(&layer.rules, "Ticket for EMP-123456");
+        assert_eq!(redacted, "Ticket for [REDACTED_EMPLOYEE_ID_1]");
+    }
+
+    #[test]
+    fn test_redact_tool_calls_and_results() {
+        let layer = RedactionLayer::with_defaults();
+        let mut redactor = Redactor::default();
+        let mut messages = vec![
+            Message::builder(Role::Assistant)
+                .tool_call(
+                    "call_1",
+                    "send_email",
+                    serde_json::json!({"to": ["jane@example.com"], "subject": "Hi"}),
+                )
+                .build(),
+            Message::tool_result(
+                "call_1",
+                serde_json::json!({"status": "sent", "contact": {"email": "jane@example.com"}}),
+            ),
+        ];
+        redactor.redact_messages(&layer.rules, &mut messages);
+
+        let serialized = serde_json::to_string(&messages).unwrap();
+        assert!(!serialized.contains("jane@example.com"));
+        let ContentPart::ToolCall { arguments, .. } = &messages[0].content[0] else {
+            panic!("tool call expected");
+        };
+        assert_eq!(arguments["to"][0], "[REDACTED_EMAIL_1]");
+        let ContentPart::ToolResult { result, .. } = &messages[1].content[0] else {
+            panic!("tool result expected");
+        };
+        assert_eq!(result["contact"]["email"], "[REDACTED_EMAIL_1]");
+
+        let ContentPart::ToolCall { arguments, .. } = &mut messages[0].content[0] else {
+            unreachable!()
+        };
+        redactor.restore_value(arguments);
+        assert_eq!(arguments["to"][0], "jane@example.com");
+    }
+}
@@ -1,21 +1,66 @@
 //! Retry layer with exponential backoff.
+//!
+//! Backoff can be randomized with a [`JitterStrategy`] to avoid thundering
+//! herds, rate-limit errors honor the provider's advertised `Retry-After`,
+//! and the total time spent retrying can be capped with a budget.
 
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::budget::Deadline;
 use aidale_core::error::AiError;
+use aidale_core::events::{EventBus, LayerEvent, RetryEvent};
+use aidale_core::http::HttpMethod;
 use aidale_core::layer::{Layer, LayeredProvider};
-use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::rt;
 use aidale_core::types::*;
 use async_trait::async_trait;
-use std::fmt::Debug;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
 use std::sync::Arc;
 use std::time::Duration;
 
+/// Randomization applied to the exponential backoff delay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JitterStrategy {
+    /// Plain exponential backoff
+    #[default]
+    None,
+    /// Uniform delay in `[0, backoff]`
+    Full,
+    /// Half the backoff plus a uniform delay in `[0, backoff / 2]`
+    Equal,
+    /// Uniform delay in `[initial_delay, previous_delay * 3]`
+    Decorrelated,
+}
+
+/// Information about a retry that is about to happen
+#[derive(Debug)]
+pub struct RetryAttempt<'a> {
+    /// Retry number, starting at 1
+    pub attempt: u32,
+    /// Configured maximum number of retries
+    pub max_retries: u32,
+    /// Delay before the retry is issued
+    pub delay: Duration,
+    /// Error that triggered the retry
+    pub error: &'a AiError,
+}
+
+type RetryCallback = Arc<dyn Fn(&RetryAttempt<'_>) + Send + Sync>;
+
 /// Retry layer configuration
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct RetryLayer {
     max_retries: u32,
     initial_delay: Duration,
     max_delay: Duration,
     backoff_multiplier: f64,
+    jitter: JitterStrategy,
+    max_elapsed: Option<Duration>,
+    respect_retry_after: bool,
+    on_retry: Option<RetryCallback>,
 }
 
 impl RetryLayer {
@@ -26,6 +71,10 @@ impl RetryLayer {
             initial_delay: Duration::from_millis(100),
             max_delay: Duration::from_secs(10),
             backoff_multiplier: 2.0,
+            jitter: JitterStrategy::None,
+            max_elapsed: None,
+            respect_retry_after: true,
+            on_retry: None,
         }
     }
 
@@ -53,13 +102,65 @@ impl RetryLayer {
         self
     }
 
+    /// Set the jitter strategy
+    pub fn with_jitter(mut self, jitter: JitterStrategy) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Cap the total time spent retrying.
+    ///
+    /// A retry whose delay would exceed the budget is not attempted and the
+    /// last error is returned instead.
+    pub fn with_max_elapsed(mut self, max_elapsed: Duration) -> Self {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    /// Whether to wait for the provider's advertised `Retry-After` on rate
+    /// limit errors (default: true)
+    pub fn with_respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    /// Register a callback invoked before every retry
+    pub fn on_retry(
+        mut self,
+        callback: impl Fn(&RetryAttempt<'_>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_retry = Some(Arc::new(callback));
+        self
+    }
+
     /// Calculate delay for a given attempt
-    fn calculate_delay(&self, attempt: u32) -> Duration {
+    fn calculate_delay(&self, attempt: u32, previous: Duration) -> Duration {
         let delay_ms =
             self.initial_delay.as_millis() as f64 * self.backoff_multiplier.powi(attempt as i32);
-        let delay = Duration::from_millis(delay_ms as u64);
+        let backoff = Duration::from_millis(delay_ms as u64).min(self.max_delay);
+
+        let mut rng = rand::rng();
+        let delay = match self.jitter {
+            JitterStrategy::None => backoff,
+            JitterStrategy::Full => backoff.mul_f64(rng.random::<f64>()),
+            JitterStrategy::Equal => backoff / 2 + (backoff / 2).mul_f64(rng.random::<f64>()),
+            JitterStrategy::Decorrelated => {
+                let low = self.initial_delay.as_secs_f64();
+                let high = (previous.max(self.initial_delay) * 3).as_secs_f64();
+                Duration::from_secs_f64(rng.random_range(low..=high))
+            }
+        };
         delay.min(self.max_delay)
     }
+
+    /// Delay before the next retry, honoring the provider's `Retry-After`
+    fn retry_delay(&self, error: &AiError, attempt: u32, previous: Duration) -> Duration {
+        let backoff = self.calculate_delay(attempt, previous);
+        match error.retry_after() {
+            Some(retry_after) if self.respect_retry_after => retry_after.max(backoff),
+            _ => backoff,
+        }
+    }
 }
 
 impl Default for RetryLayer {
@@ -68,6 +169,21 @@ impl Default for RetryLayer {
     }
 }
 
+impl Debug for RetryLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryLayer")
+            .field("max_retries", &self.max_retries)
+            .field("initial_delay", &self.initial_delay)
+            .field("max_delay", &self.max_delay)
+            .field("backoff_multiplier", &self.backoff_multiplier)
+            .field("jitter", &self.jitter)
+            .field("max_elapsed", &self.max_elapsed)
+            .field("respect_retry_after", &self.respect_retry_after)
+            .field("on_retry", &self.on_retry.is_some())
+            .finish()
+    }
+}
+
 impl<P: Provider> Layer<P> for RetryLayer {
     type LayeredProvider = RetryProvider<P>;
 
@@ -88,30 +204,92 @@ pub struct RetryProvider<P> {
 
 impl<P: Provider> RetryProvider<P> {
     /// Execute with retry logic
-    async fn execute_with_retry<T, F, Fut>(&self, mut operation: F) -> Result<T, AiError>
+    ///
+    /// Retries stop early when the next attempt would start after the
+    /// request deadline. The request's [`RequestOptions::max_retries`]
+    /// overrides the configured maximum.
+    async fn execute_with_retry<T, F, Fut>(
+        &self,
+        req: &ChatCompletionRequest,
+        operation: F,
+    ) -> Result<T, AiError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, AiError>>,
+    {
+        self.retry_loop(
+            req.deadline,
+            req.options.max_retries,
+            &req.events,
+            operation,
+        )
+        .await
+    }
+
+    /// Retry `operation`; raw requests have no deadline, options or events
+    /// of their own
+    async fn retry_loop<T, F, Fut>(
+        &self,
+        deadline: Option<Deadline>,
+        max_retries: Option<u32>,
+        events: &EventBus,
+        mut operation: F,
+    ) -> Result<T, AiError>
     where
         F: FnMut() -> Fut,
         Fut: std::future::Future<Output = Result<T, AiError>>,
     {
+        let max_retries = max_retries.unwrap_or(self.config.max_retries);
+        let started = rt::now();
         let mut attempt = 0;
+        let mut previous = self.config.initial_delay;
 
         loop {
             match operation().await {
                 Ok(result) => return Ok(result),
                 Err(e) => {
-                    if !e.is_retryable() || attempt >= self.config.max_retries {
+                    if !e.is_retryable() || attempt >= max_retries {
                         return Err(e);
                     }
 
-                    let delay = self.config.calculate_delay(attempt);
+                    let delay = self.config.retry_delay(&e, attempt, previous);
+                    if let Some(budget) = self.config.max_elapsed {
+                        if rt::now() - started + delay > budget {
+                            tracing::debug!("Retry budget of {:?} exhausted", budget);
+                            return Err(e);
+                        }
+                    }
+
+                    if let Some(deadline) = deadline {
+                        if delay >= deadline.remaining() {
+                            tracing::debug!("Not retrying: request deadline would pass");
+                            return Err(e);
+                        }
+                    }
+
                     tracing::debug!(
                         "Retry attempt {}/{}, waiting {:?}",
                         attempt + 1,
-                        self.config.max_retries,
+                        max_retries,
                         delay
                     );
+                    if let Some(callback) = &self.config.on_retry {
+                        callback(&RetryAttempt {
+                            attempt: attempt + 1,
+                            max_retries,
+                            delay,
+                            error: &e,
+                        });
+                    }
+                    events.emit(LayerEvent::Retry(RetryEvent {
+                        attempt: attempt + 1,
+                        max_retries,
+                        delay,
+                        error: e.to_string(),
+                    }));
 
-                    tokio::time::sleep(delay).await;
+                    rt::sleep(delay).await;
+                    previous = delay;
                     attempt += 1;
                 }
             }
@@ -133,7 +311,7 @@ impl<P: Provider> LayeredProvider for RetryProvider<P> {
     ) -> Result<ChatCompletionResponse, AiError> {
         // Clone req for retry attempts
         let req_clone = req.clone();
-        self.execute_with_retry(|| {
+        self.execute_with_retry(&req, || {
             let req = req_clone.clone();
             async move { self.inner.chat_completion(req).await }
         })
@@ -146,12 +324,50 @@ impl<P: Provider> LayeredProvider for RetryProvider<P> {
     ) -> Result<Box<ChatCompletionStream>, AiError> {
         // For streaming, we don't retry mid-stream - only retry the initial connection
         let req_clone = req.clone();
-        self.execute_with_retry(|| {
+        self.execute_with_retry(&req, || {
             let req = req_clone.clone();
             async move { self.inner.stream_chat_completion(req).await }
         })
         .await
     }
+
+    async fn layered_chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        self.retry_loop(None, None, &EventBus::new(), || {
+            self.inner.chat_completion_raw(body.clone())
+        })
+        .await
+    }
+
+    async fn layered_api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        // A repeated POST may create a second thread or run
+        if req.method == HttpMethod::Post {
+            return self.inner.api_request(req).await;
+        }
+        self.retry_loop(None, None, &EventBus::new(), || {
+            self.inner.api_request(req.clone())
+        })
+        .await
+    }
+
+    async fn layered_speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        self.retry_loop(None, None, &EventBus::new(), || {
+            self.inner.speech(req.clone())
+        })
+        .await
+    }
+
+    async fn layered_transcription(
+        &self,
+        req: TranscriptionRequest,
+    ) -> Result<Transcription, AiError> {
+        self.retry_loop(None, None, &EventBus::new(), || {
+            self.inner.transcription(req.clone())
+        })
+        .await
+    }
 }
 
 #[async_trait]
@@ -173,4 +389,228 @@ impl<P: Provider> Provider for RetryProvider<P> {
     ) -> Result<Box<ChatCompletionStream>, AiError> {
         LayeredProvider::layered_stream_chat_completion(self, req).await
     }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_and_retry_after() {
+        let layer = RetryLayer::new()
+            .with_initial_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_secs(5));
+
+        for attempt in 0..5 {
+            let full = layer
+                .clone()
+                .with_jitter(JitterStrategy::Full)
+                .calculate_delay(attempt, Duration::ZERO);
+            assert!(full <= layer.calculate_delay(attempt, Duration::ZERO));
+
+            let equal = layer
+                .clone()
+                .with_jitter(JitterStrategy::Equal)
+                .calculate_delay(attempt, Duration::ZERO);
+            assert!(equal >= layer.calculate_delay(attempt, Duration::ZERO) / 2);
+        }
+
+        let decorrelated = layer
+            .clone()
+            .with_jitter(JitterStrategy::Decorrelated)
+            .calculate_delay(0, Duration::from_millis(400));
+        assert!(decorrelated >= Duration::from_millis(100));
+        assert!(decorrelated <= Duration::from_millis(1200));
+
+        let error = AiError::rate_limit_after("slow down", Some(Duration::from_secs(30)));
+        assert_eq!(
+            layer.retry_delay(&error, 0, Duration::ZERO),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            layer
+                .with_respect_retry_after(false)
+                .retry_delay(&error, 0, Duration::ZERO),
+            Duration::from_millis(100)
+        );
+    }
+
+    /// Rate limited on the first two calls
+    #[derive(Debug, Default)]
+    struct Flaky {
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl Provider for Flaky {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "flaky".to_string(),
+                name: "Flaky".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            if self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 {
+                return Err(AiError::rate_limit("slow down"));
+            }
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant("ok"),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+
+        async fn chat_completion_raw(
+            &self,
+            body: serde_json::Value,
+        ) -> Result<serde_json::Value, AiError> {
+            if self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 {
+                return Err(AiError::rate_limit("slow down"));
+            }
+            Ok(serde_json::json!({"echo": body}))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_raw_requests_are_retried() {
+        let executor = aidale_core::RuntimeExecutor::builder(Flaky::default())
+            .layer(RetryLayer::new().with_max_retries(3))
+            .finish();
+
+        let body = serde_json::json!({"model": "m", "verbosity": "low"});
+        let response = executor
+            .raw()
+            .chat_completion_raw(body.clone())
+            .await
+            .unwrap();
+        assert_eq!(response["echo"], body);
+    }
+
+    /// Records the retry attempts it is told about
+    #[derive(Debug, Default)]
+    struct RetryRecorder {
+        attempts: std::sync::Mutex<Vec<u32>>,
+    }
+
+    #[async_trait]
+    impl aidale_core::plugin::Plugin for RetryRecorder {
+        fn name(&self) -> &str {
+            "retry_recorder"
+        }
+
+        async fn on_retry(&self, event: &RetryEvent, _ctx: &RequestContext) -> Result<(), AiError> {
+            self.attempts.lock().unwrap().push(event.attempt);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_events_reach_plugins() {
+        let recorder = Arc::new(RetryRecorder::default());
+        let executor = aidale_core::RuntimeExecutor::builder(Flaky::default())
+            .layer(RetryLayer::new().with_max_retries(3))
+            .plugin(recorder.clone())
+            .finish();
+
+        let result = executor
+            .generate_text("m", TextParams::new(vec![Message::user("hi")]))
+            .await
+            .unwrap();
+        assert_eq!(result.content, "ok");
+        assert_eq!(*recorder.attempts.lock().unwrap(), [1, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_options_override_max_retries() {
+        let flaky = Arc::new(Flaky::default());
+        let recorder = Arc::new(RetryRecorder::default());
+        let executor = aidale_core::RuntimeExecutor::builder(flaky.clone() as Arc<dyn Provider>)
+            .layer(RetryLayer::new().with_max_retries(3))
+            .plugin(recorder.clone())
+            .finish();
+        let params = TextParams::new(vec![Message::user("hi")]);
+        let ctx = |max_retries: u32| {
+            RequestContext::new("flaky", "m")
+                .with_options(RequestOptions::new().with_max_retries(max_retries))
+        };
+
+        // Fail fast despite the layer's three retries
+        let err = executor
+            .generate_text_with_context("m", params.clone(), ctx(0))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AiError::RateLimit { .. }));
+        assert_eq!(flaky.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(recorder.attempts.lock().unwrap().is_empty());
+
+        // One retry is enough for the second failure
+        let result = executor
+            .generate_text_with_context("m", params.clone(), ctx(1))
+            .await
+            .unwrap();
+        assert_eq!(result.content, "ok");
+        assert_eq!(flaky.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(*recorder.attempts.lock().unwrap(), [1]);
+
+        // Options can also raise the limit of a layer that doesn't retry
+        let flaky = Arc::new(Flaky::default());
+        let executor = aidale_core::RuntimeExecutor::builder(flaky.clone() as Arc<dyn Provider>)
+            .layer(RetryLayer::new().with_max_retries(0))
+            .finish();
+        let result = executor
+            .generate_text_with_context("m", params, ctx(2))
+            .await
+            .unwrap();
+        assert_eq!(result.content, "ok");
+        assert_eq!(flaky.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
 }
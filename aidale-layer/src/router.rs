@@ -0,0 +1,370 @@
+//! Model routing layer for sending each prompt to the cheapest model that
+//! can handle it.
+//!
+//! Requests for the router's alias model (`auto` by default) go to the
+//! cheap model unless a rule escalates them to the strong one:
+//!
+//! - the prompt is longer than a token threshold
+//! - the latest user message contains an escalation keyword
+//! - a classifier callback says so
+//!
+//! Requests naming any other model pass through unchanged. The decision is
+//! reported as a [`RouteEvent`], which the executor hands to the plugins'
+//! `on_route` hook and records as `metadata["route"]` on text results.
+//!
+//! ```ignore
+//! let router = ModelRouterLayer::new("gpt-4o-mini", "gpt-4o")
+//!     .with_max_tokens(2_000)
+//!     .with_keywords(["prove", "step by step", "refactor"]);
+//! let executor = RuntimeExecutor::builder(provider).layer(router).finish();
+//!
+//! let result = executor.generate_text("auto", params).await?;
+//! println!("{}", result.metadata["route"]["to"]);
+//! ```
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::events::{LayerEvent, RouteEvent};
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::tokenizer::{HeuristicTokenCounter, TokenCounter};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+type Classifier = Arc<dyn Fn(&ChatCompletionRequest) -> bool + Send + Sync>;
+
+/// Model routing layer configuration
+#[derive(Clone)]
+pub struct ModelRouterLayer {
+    alias: String,
+    cheap_model: String,
+    strong_model: String,
+    max_tokens: Option<usize>,
+    keywords: Vec<String>,
+    escalate_tools: bool,
+    classifier: Option<Classifier>,
+    counter: Arc<dyn TokenCounter>,
+}
+
+impl ModelRouterLayer {
+    /// Route between a cheap and a strong model
+    ///
+    /// Without further rules every request goes to the cheap model.
+    pub fn new(cheap_model: impl Into<String>, strong_model: impl Into<String>) -> Self {
+        Self {
+            alias: "auto".to_string(),
+            cheap_model: cheap_model.into(),
+            strong_model: strong_model.into(),
+            max_tokens: None,
+            keywords: Vec::new(),
+            escalate_tools: false,
+            classifier: None,
+            counter: Arc::new(HeuristicTokenCounter::new()),
+        }
+    }
+
+    /// Model name that selects routing (default: `auto`)
+    pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
+        self.alias = alias.into();
+        self
+    }
+
+    /// Escalate prompts longer than `max_tokens`
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Escalate when the latest user message contains `keyword`, ignoring
+    /// case
+    pub fn with_keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.keywords.push(keyword.into().to_lowercase());
+        self
+    }
+
+    /// Escalate on any of `keywords`
+    pub fn with_keywords<I, S>(self, keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        keywords.into_iter().fold(self, Self::with_keyword)
+    }
+
+    /// Escalate requests that offer tools
+    pub fn with_escalate_tools(mut self, escalate: bool) -> Self {
+        self.escalate_tools = escalate;
+        self
+    }
+
+    /// Escalate when `classifier` returns true, e.g. for a small model or
+    /// embedding-based complexity score
+    pub fn with_classifier(
+        mut self,
+        classifier: impl Fn(&ChatCompletionRequest) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.classifier = Some(Arc::new(classifier));
+        self
+    }
+
+    /// Use a custom token counter
+    pub fn with_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Model for a request and the rule that chose it, or `None` if the
+    /// request doesn't ask for routing
+    pub fn route(&self, req: &ChatCompletionRequest) -> Option<(String, String)> {
+        if req.model != self.alias {
+            return None;
+        }
+        let strong = |reason: String| Some((self.strong_model.clone(), reason));
+
+        if let Some(max_tokens) = self.max_tokens {
+            if self.counter.count_messages(&req.messages) > max_tokens {
+                return strong("tokens".to_string());
+            }
+        }
+        if self.escalate_tools && req.tools.as_ref().is_some_and(|tools| !tools.is_empty()) {
+            return strong("tools".to_string());
+        }
+        if !self.keywords.is_empty() {
+            let prompt = latest_user_text(&req.messages).to_lowercase();
+            if let Some(keyword) = self.keywords.iter().find(|kw| prompt.contains(kw.as_str())) {
+                return strong(format!("keyword:{}", keyword));
+            }
+        }
+        if let Some(classifier) = &self.classifier {
+            if classifier(req) {
+                return strong("classifier".to_string());
+            }
+        }
+        Some((self.cheap_model.clone(), "default".to_string()))
+    }
+}
+
+/// Text of the last user message
+fn latest_user_text(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .rev()
+        .find(|msg| msg.role == Role::User)
+        .map(|msg| {
+            msg.content
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+impl Debug for ModelRouterLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModelRouterLayer")
+            .field("alias", &self.alias)
+            .field("cheap_model", &self.cheap_model)
+            .field("strong_model", &self.strong_model)
+            .field("max_tokens", &self.max_tokens)
+            .field("keywords", &self.keywords)
+            .field("escalate_tools", &self.escalate_tools)
+            .field("classifier", &self.classifier.is_some())
+            .finish()
+    }
+}
+
+impl<P: Provider> Layer<P> for ModelRouterLayer {
+    type LayeredProvider = ModelRouterProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        ModelRouterProvider {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Provider that rewrites the model of routed requests
+pub struct ModelRouterProvider<P> {
+    inner: P,
+    config: ModelRouterLayer,
+}
+
+impl<P: Debug> Debug for ModelRouterProvider<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModelRouterProvider")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<P> ModelRouterProvider<P> {
+    fn apply(&self, mut req: ChatCompletionRequest) -> ChatCompletionRequest {
+        if let Some((model, reason)) = self.config.route(&req) {
+            tracing::debug!("Routing {} to {} ({})", req.model, model, reason);
+            req.events.emit(LayerEvent::Route(RouteEvent {
+                from: std::mem::replace(&mut req.model, model.clone()),
+                to: model,
+                reason,
+            }));
+        }
+        req
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for ModelRouterProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        self.inner.chat_completion(self.apply(req)).await
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        self.inner.stream_chat_completion(self.apply(req)).await
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for ModelRouterProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::runtime::RuntimeExecutor;
+
+    /// Answers with the model it was asked for
+    #[derive(Debug)]
+    struct EchoModel;
+
+    #[async_trait]
+    impl Provider for EchoModel {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "echo".to_string(),
+                name: "Echo".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            Ok(ChatCompletionResponse {
+                id: "1".to_string(),
+                model: req.model.clone(),
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(req.model),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routing() {
+        let router = ModelRouterLayer::new("mini", "large")
+            .with_max_tokens(50)
+            .with_keywords(["Prove"])
+            .with_classifier(|req| req.temperature == Some(0.0));
+        let routed = |text: &str| {
+            let req = ChatCompletionRequest::new("auto", vec![Message::user(text)]);
+            router.route(&req).unwrap()
+        };
+
+        assert_eq!(routed("Hi!"), ("mini".to_string(), "default".to_string()));
+        assert_eq!(routed("please prove it").1, "keyword:prove");
+        assert_eq!(routed(&"word ".repeat(100)).1, "tokens");
+        let req =
+            ChatCompletionRequest::new("auto", vec![Message::user("Hi")]).with_temperature(0.0);
+        assert_eq!(router.route(&req).unwrap().1, "classifier");
+        let req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("prove it")]);
+        assert!(router.route(&req).is_none());
+
+        let executor = RuntimeExecutor::builder(EchoModel).layer(router).finish();
+        let params = TextParams::new(vec![Message::user("Prove Fermat's last theorem")]);
+        let result = executor.generate_text("auto", params).await.unwrap();
+        assert_eq!(result.content, "large");
+        assert_eq!(
+            result.metadata["route"],
+            serde_json::json!({"from": "auto", "to": "large", "reason": "keyword:prove"})
+        );
+    }
+}
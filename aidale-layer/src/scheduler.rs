@@ -0,0 +1,403 @@
+//! Scheduling layer for sharing a provider between workloads.
+//!
+//! Limits how many requests run against the wrapped provider at once and
+//! decides who goes next when the limit is reached:
+//!
+//! - interactive requests are always served before batch requests (see
+//!   [`RequestOptions::priority`])
+//! - within a priority, tenants take turns, so one tenant with a thousand
+//!   queued requests doesn't block another with one
+//!
+//! The tenant is read from the request metadata, which the executor copies
+//! from the [`RequestContext`]:
+//!
+//! ```ignore
+//! let executor = RuntimeExecutor::builder(provider)
+//!     .layer(SchedulerLayer::new().with_max_concurrency(4))
+//!     .finish();
+//!
+//! let ctx = RequestContext::new("openai", "gpt-4o-mini")
+//!     .with_metadata(HashMap::from([("tenant".into(), "acme".into())]))
+//!     .with_options(RequestOptions::new().with_priority(Priority::Batch));
+//! executor.generate_text_with_context("gpt-4o-mini", params, ctx).await?;
+//! ```
+//!
+//! Each wrapped provider gets its own limit. A streamed request holds its
+//! slot until the stream is dropped.
+//!
+//! [`RequestContext`]: aidale_core::types::RequestContext
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use futures::StreamExt;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex};
+
+/// Scheduling layer configuration
+#[derive(Debug, Clone)]
+pub struct SchedulerLayer {
+    max_concurrency: usize,
+    max_queued: Option<usize>,
+    tenant_key: String,
+}
+
+impl SchedulerLayer {
+    /// Create a new scheduling layer with default settings
+    pub fn new() -> Self {
+        Self {
+            max_concurrency: 8,
+            max_queued: None,
+            tenant_key: "tenant".to_string(),
+        }
+    }
+
+    /// Maximum number of requests running at once (default: 8)
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// Maximum number of waiting requests; further requests fail with a
+    /// rate limit error instead of queueing (default: unbounded)
+    pub fn with_max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = Some(max_queued);
+        self
+    }
+
+    /// Metadata key identifying the tenant (default: `tenant`)
+    ///
+    /// Requests without it share a single anonymous tenant.
+    pub fn with_tenant_key(mut self, key: impl Into<String>) -> Self {
+        self.tenant_key = key.into();
+        self
+    }
+}
+
+impl Default for SchedulerLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Provider> Layer<P> for SchedulerLayer {
+    type LayeredProvider = SchedulerProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        SchedulerProvider {
+            inner,
+            scheduler: Arc::new(Scheduler {
+                config: self.clone(),
+                state: Mutex::new(State::default()),
+            }),
+        }
+    }
+}
+
+/// Waiting requests of one priority, grouped by tenant
+#[derive(Default)]
+struct TenantQueues {
+    /// Tenants with waiting requests, in turn order
+    turns: VecDeque<String>,
+    waiting: HashMap<String, VecDeque<oneshot::Sender<Permit>>>,
+}
+
+impl TenantQueues {
+    fn push(&mut self, tenant: String, waiter: oneshot::Sender<Permit>) {
+        let queue = self.waiting.entry(tenant.clone()).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(tenant);
+        }
+        queue.push_back(waiter);
+    }
+
+    /// Next waiter of the tenant whose turn it is
+    fn pop(&mut self) -> Option<oneshot::Sender<Permit>> {
+        let tenant = self.turns.pop_front()?;
+        let queue = self.waiting.get_mut(&tenant)?;
+        let waiter = queue.pop_front();
+        if queue.is_empty() {
+            self.waiting.remove(&tenant);
+        } else {
+            self.turns.push_back(tenant);
+        }
+        waiter
+    }
+}
+
+#[derive(Default)]
+struct State {
+    running: usize,
+    queued: usize,
+    interactive: TenantQueues,
+    batch: TenantQueues,
+}
+
+impl State {
+    fn pop(&mut self) -> Option<oneshot::Sender<Permit>> {
+        let waiter = self.interactive.pop().or_else(|| self.batch.pop())?;
+        self.queued -= 1;
+        Some(waiter)
+    }
+}
+
+struct Scheduler {
+    config: SchedulerLayer,
+    state: Mutex<State>,
+}
+
+impl Scheduler {
+    /// Wait for a slot
+    async fn acquire(self: &Arc<Self>, req: &ChatCompletionRequest) -> Result<Permit, AiError> {
+        let slot = {
+            let mut state = self.state.lock().unwrap();
+            // Slots are handed over directly on release, so a free slot
+            // means nobody is waiting
+            if state.running < self.config.max_concurrency {
+                state.running += 1;
+                return Ok(Permit(Some(self.clone())));
+            }
+            if self
+                .config
+                .max_queued
+                .is_some_and(|max_queued| state.queued >= max_queued)
+            {
+                return Err(AiError::rate_limit("Scheduler queue is full"));
+            }
+
+            let tenant = req
+                .metadata
+                .get(&self.config.tenant_key)
+                .cloned()
+                .unwrap_or_default();
+            let (waiter, slot) = oneshot::channel();
+            match req.options.priority {
+                Priority::Interactive => state.interactive.push(tenant, waiter),
+                Priority::Batch => state.batch.push(tenant, waiter),
+            }
+            state.queued += 1;
+            slot
+        };
+        slot.await
+            .map_err(|_| AiError::other("Scheduler dropped a waiting request"))
+    }
+
+    /// Hand a finished request's slot to the next waiter, or free it
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(waiter) = state.pop() {
+            match waiter.send(Permit(Some(self.clone()))) {
+                Ok(()) => return,
+                // The waiter was cancelled; the slot stays taken for the
+                // next one
+                Err(mut permit) => permit.0 = None,
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+/// A running request's slot, released when dropped
+struct Permit(Option<Arc<Scheduler>>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.0.take() {
+            scheduler.release();
+        }
+    }
+}
+
+/// Provider that queues requests beyond the concurrency limit
+pub struct SchedulerProvider<P> {
+    inner: P,
+    scheduler: Arc<Scheduler>,
+}
+
+impl<P: Debug> Debug for SchedulerProvider<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SchedulerProvider")
+            .field("inner", &self.inner)
+            .field("config", &self.scheduler.config)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for SchedulerProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let _permit = self.scheduler.acquire(&req).await?;
+        self.inner.chat_completion(req).await
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let permit = self.scheduler.acquire(&req).await?;
+        let stream = self.inner.stream_chat_completion(req).await?;
+        // Keep the slot until the stream is dropped
+        let stream = stream.map(move |chunk| {
+            let _ = &permit;
+            chunk
+        });
+        Ok(Box::new(stream))
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for SchedulerProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Records the order in which requests reach it, identified by model
+    #[derive(Debug, Default)]
+    struct SlowProvider {
+        served: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Provider for SlowProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "slow".to_string(),
+                name: "Slow".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            self.served.lock().unwrap().push(req.model.clone());
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(ChatCompletionResponse {
+                id: req.model.clone(),
+                model: req.model,
+                choices: Vec::new(),
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_priority_and_fairness() {
+        let slow = Arc::new(SlowProvider::default());
+        let provider = Arc::new(
+            SchedulerLayer::new()
+                .with_max_concurrency(1)
+                .layer(slow.clone() as Arc<dyn Provider>),
+        );
+        let request = |model: &str, tenant: &str, priority: Priority| {
+            ChatCompletionRequest::new(model, Vec::new())
+                .with_metadata("tenant", tenant)
+                .with_options(RequestOptions::new().with_priority(priority))
+        };
+
+        // One running request, then a backlog: tenant a floods the batch
+        // queue, b queues one batch job, c an interactive chat
+        let mut requests = vec![request("first", "a", Priority::Batch)];
+        requests.extend((1..=3).map(|i| request(&format!("a{}", i), "a", Priority::Batch)));
+        requests.push(request("b1", "b", Priority::Batch));
+        requests.push(request("c1", "c", Priority::Interactive));
+
+        let mut handles = Vec::new();
+        for req in requests {
+            let provider = provider.clone();
+            handles.push(tokio::spawn(
+                async move { provider.chat_completion(req).await },
+            ));
+            tokio::task::yield_now().await;
+        }
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        assert_eq!(
+            *slow.served.lock().unwrap(),
+            ["first", "c1", "a1", "b1", "a2", "a3"]
+        );
+
+        let full = SchedulerLayer::new()
+            .with_max_concurrency(1)
+            .with_max_queued(0)
+            .layer(slow.clone() as Arc<dyn Provider>);
+        let busy = full.chat_completion(request("x", "a", Priority::Interactive));
+        let rejected = full.chat_completion(request("y", "a", Priority::Interactive));
+        let (busy, rejected) = tokio::join!(busy, rejected);
+        assert!(busy.is_ok());
+        assert!(matches!(rejected, Err(AiError::RateLimit { .. })));
+    }
+}
@@ -0,0 +1,355 @@
+//! Semantic caching layer using embeddings.
+//!
+//! Unlike exact-match caching, this layer embeds the prompt and looks up
+//! previously answered prompts by vector similarity. When a cached prompt is
+//! similar enough, its response is returned without calling the provider.
+//!
+//! The cache is best-effort: if the embedder or the vector store fails, the
+//! request goes to the provider as if the cache weren't there. Requests with
+//! images, files or tool calls in the conversation are never cached, since
+//! only their text would be compared.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::embedding::Embedder;
+use aidale_core::error::AiError;
+use aidale_core::events::{CacheEvent, LayerEvent};
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use aidale_core::vector_store::{VectorQuery, VectorRecord, VectorStore};
+use async_trait::async_trait;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Semantic cache layer configuration
+#[derive(Debug, Clone)]
+pub struct SemanticCacheLayer {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn VectorStore>,
+    threshold: f32,
+}
+
+impl SemanticCacheLayer {
+    /// Create a new semantic cache layer
+    ///
+    /// The default similarity threshold is 0.95.
+    pub fn new(embedder: Arc<dyn Embedder>, store: Arc<dyn VectorStore>) -> Self {
+        Self {
+            embedder,
+            store,
+            threshold: 0.95,
+        }
+    }
+
+    /// Set the minimum cosine similarity for a cache hit
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl<P: Provider> Layer<P> for SemanticCacheLayer {
+    type LayeredProvider = SemanticCacheProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        SemanticCacheProvider {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Provider wrapped with semantic caching
+#[derive(Debug)]
+pub struct SemanticCacheProvider<P> {
+    inner: P,
+    config: SemanticCacheLayer,
+}
+
+impl<P: Provider> SemanticCacheProvider<P> {
+    /// Whether every message consists of text only
+    fn is_text_only(req: &ChatCompletionRequest) -> bool {
+        req.messages.iter().all(|msg| {
+            msg.content
+                .iter()
+                .all(|part| matches!(part, ContentPart::Text { .. }))
+        })
+    }
+
+    /// Render the conversation as the text to embed
+    fn prompt_text(req: &ChatCompletionRequest) -> String {
+        req.messages
+            .iter()
+            .map(|msg| {
+                let text = msg
+                    .content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("{:?}: {}", msg.role, text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Build the query restricted to entries with the same model and format
+    fn scoped_query(
+        &self,
+        req: &ChatCompletionRequest,
+        vector: Vec<f32>,
+    ) -> Result<VectorQuery, AiError> {
+        Ok(VectorQuery::new(vector, 1)
+            .with_filter("model", serde_json::Value::String(req.model.clone()))
+            .with_filter("format", serde_json::to_value(&req.response_format)?)
+            .with_min_score(self.config.threshold))
+    }
+
+    /// Embed `prompt` and look up the most similar cached response
+    async fn lookup(
+        &self,
+        req: &ChatCompletionRequest,
+        prompt: &str,
+    ) -> Result<(Vec<f32>, Option<(f32, ChatCompletionResponse)>), AiError> {
+        let vector = self.config.embedder.embed_one(prompt).await?;
+        let query = self.scoped_query(req, vector.clone())?;
+        let hit = self.config.store.search(&query).await?.into_iter().next();
+        let cached = match hit {
+            Some(hit) => match hit.record.payload.get("response") {
+                Some(cached) => Some((hit.score, serde_json::from_value(cached.clone())?)),
+                None => None,
+            },
+            None => None,
+        };
+        Ok((vector, cached))
+    }
+}
+
+fn cache_event(hit: bool) -> LayerEvent {
+    LayerEvent::Cache(CacheEvent {
+        layer: "semantic_cache".to_string(),
+        hit,
+    })
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for SemanticCacheProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        // Tool-calling requests depend on external state, don't cache them
+        if req.tools.is_some() || req.options.no_cache || !Self::is_text_only(&req) {
+            return self.inner.chat_completion(req).await;
+        }
+
+        let prompt = Self::prompt_text(&req);
+        let vector = match self.lookup(&req, &prompt).await {
+            Ok((_, Some((score, cached)))) => {
+                tracing::debug!("Semantic cache hit: score={:.4}", score);
+                req.events.emit(cache_event(true));
+                return Ok(cached);
+            }
+            Ok((vector, None)) => vector,
+            Err(e) => {
+                tracing::warn!("Semantic cache lookup failed, bypassing the cache: {}", e);
+                return self.inner.chat_completion(req).await;
+            }
+        };
+
+        tracing::debug!("Semantic cache miss");
+        req.events.emit(cache_event(false));
+        let format = serde_json::to_value(&req.response_format)?;
+        let model = req.model.clone();
+        let response = self.inner.chat_completion(req).await?;
+
+        let record = VectorRecord::new(
+            uuid::Uuid::new_v4().to_string(),
+            vector,
+            serde_json::json!({
+                "model": model,
+                "format": format,
+                "prompt": prompt,
+                "response": response,
+            }),
+        );
+        if let Err(e) = self.config.store.upsert(vec![record]).await {
+            tracing::warn!("Failed to store semantic cache entry: {}", e);
+        }
+
+        Ok(response)
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        // Streaming responses are passed through uncached
+        self.inner.stream_chat_completion(req).await
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for SemanticCacheProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::embedding::Embedding;
+    use aidale_core::vector_store::InMemoryVectorStore;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Embeds text by counting a few keywords, failing on "outage"
+    #[derive(Debug)]
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl Embedder for KeywordEmbedder {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AiError> {
+            if texts.iter().any(|text| text.contains("outage")) {
+                return Err(AiError::provider("embedder down"));
+            }
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    ["rust", "memory", "coffee"]
+                        .iter()
+                        .map(|word| text.matches(word).count() as f32)
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    /// Answers with the number of calls so far
+    #[derive(Debug, Default)]
+    struct CountingProvider {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Provider for CountingProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "counting".to_string(),
+                name: "Counting".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(ChatCompletionResponse {
+                id: call.to_string(),
+                model: req.model,
+                choices: Vec::new(),
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_semantic_cache() {
+        let store = Arc::new(InMemoryVectorStore::new());
+        let provider = SemanticCacheLayer::new(Arc::new(KeywordEmbedder), store)
+            .with_threshold(0.9)
+            .layer(CountingProvider::default());
+        let ask = |model: &str, message: Message| {
+            let req = ChatCompletionRequest::new(model, vec![message]);
+            let provider = &provider;
+            async move { provider.chat_completion(req).await.unwrap().id }
+        };
+
+        assert_eq!(
+            ask("m", Message::user("How does Rust manage memory?")).await,
+            "1"
+        );
+        // A similar prompt hits, a different one or another model misses
+        assert_eq!(
+            ask("m", Message::user("how does rust handle memory")).await,
+            "1"
+        );
+        assert_eq!(ask("m", Message::user("How is coffee brewed?")).await, "2");
+        assert_eq!(
+            ask("other", Message::user("How does Rust manage memory?")).await,
+            "3"
+        );
+
+        // The same text with an image is not answered from the cache
+        let with_image = Message::builder(Role::User)
+            .text("How does Rust manage memory?")
+            .image("https://example.com/diagram.png")
+            .build();
+        assert_eq!(ask("m", with_image).await, "4");
+
+        // Embedder failures fall through to the provider
+        assert_eq!(ask("m", Message::user("Rust outage")).await, "5");
+    }
+}
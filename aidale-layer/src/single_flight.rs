@@ -0,0 +1,161 @@
+//! Coalescing of concurrent calls with the same key.
+
+use aidale_core::error::AiError;
+use futures::channel::oneshot;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+type Waiter<T> = oneshot::Sender<Result<T, AiError>>;
+
+/// Runs one call per key at a time and hands its result to every caller
+/// that asked for the same key in the meantime
+pub(crate) struct SingleFlight<T> {
+    /// Waiting callers of each running call
+    calls: Mutex<HashMap<String, Vec<Waiter<T>>>>,
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    /// Run `call`, or wait for the result of the running call for `key`
+    ///
+    /// Returns whether the result was shared from another caller's call. If
+    /// that caller is cancelled, one of the waiters runs its own call.
+    pub(crate) async fn run<F>(&self, key: &str, call: F) -> (Result<T, AiError>, bool)
+    where
+        F: Future<Output = Result<T, AiError>>,
+    {
+        loop {
+            let waiting = {
+                let mut calls = self.calls.lock().unwrap();
+                match calls.get_mut(key) {
+                    Some(waiters) => {
+                        let (waiter, result) = oneshot::channel();
+                        waiters.push(waiter);
+                        Some(result)
+                    }
+                    None => {
+                        calls.insert(key.to_string(), Vec::new());
+                        None
+                    }
+                }
+            };
+            match waiting {
+                Some(result) => match result.await {
+                    Ok(result) => return (result, true),
+                    Err(oneshot::Canceled) => continue,
+                },
+                None => break,
+            }
+        }
+
+        let flight = Flight {
+            calls: self,
+            key,
+            done: false,
+        };
+        let result = call.await;
+        flight.finish(&result);
+        (result, false)
+    }
+}
+
+/// A running call, removed from the map when finished or cancelled
+struct Flight<'a, T> {
+    calls: &'a SingleFlight<T>,
+    key: &'a str,
+    done: bool,
+}
+
+impl<T: Clone> Flight<'_, T> {
+    fn finish(mut self, result: &Result<T, AiError>) {
+        self.done = true;
+        let waiters = self.calls.calls.lock().unwrap().remove(self.key);
+        for waiter in waiters.into_iter().flatten() {
+            let shared = match result {
+                Ok(value) => Ok(value.clone()),
+                Err(e) => Err(duplicate(e)),
+            };
+            let _ = waiter.send(shared);
+        }
+    }
+}
+
+impl<T> Drop for Flight<'_, T> {
+    fn drop(&mut self) {
+        if !self.done {
+            // Dropping the waiters wakes them up to retry
+            self.calls.calls.lock().unwrap().remove(self.key);
+        }
+    }
+}
+
+/// Copy of an error for a waiting caller, keeping its kind so retries and
+/// fallbacks treat it the same
+fn duplicate(e: &AiError) -> AiError {
+    match e {
+        AiError::Provider { message, details } => AiError::Provider {
+            message: message.clone(),
+            details: details.clone(),
+        },
+        AiError::Authentication { message, details } => AiError::Authentication {
+            message: message.clone(),
+            details: details.clone(),
+        },
+        AiError::RateLimit {
+            message,
+            retry_after,
+            details,
+        } => AiError::RateLimit {
+            message: message.clone(),
+            retry_after: *retry_after,
+            details: details.clone(),
+        },
+        AiError::InvalidRequest { message, details } => AiError::InvalidRequest {
+            message: message.clone(),
+            details: details.clone(),
+        },
+        AiError::ModelNotFound { message, details } => AiError::ModelNotFound {
+            message: message.clone(),
+            details: details.clone(),
+        },
+        AiError::Timeout { message, details } => AiError::Timeout {
+            message: message.clone(),
+            details: details.clone(),
+        },
+        AiError::DeadlineExceeded(message) => AiError::DeadlineExceeded(message.clone()),
+        AiError::BudgetExhausted { used, limit } => AiError::BudgetExhausted {
+            used: *used,
+            limit: *limit,
+        },
+        AiError::ContentBlocked { categories } => AiError::ContentBlocked {
+            categories: categories.clone(),
+        },
+        AiError::Plugin { plugin, message } => AiError::Plugin {
+            plugin: plugin.clone(),
+            message: message.clone(),
+        },
+        AiError::Layer { layer, message } => AiError::Layer {
+            layer: layer.clone(),
+            message: message.clone(),
+        },
+        AiError::Configuration(message) => AiError::Configuration(message.clone()),
+        AiError::Stream(message) => AiError::Stream(message.clone()),
+        AiError::Unsupported(message) => AiError::Unsupported(message.clone()),
+        // The wrapped errors can't be cloned; a timeout keeps network
+        // errors retryable
+        AiError::Network(e) => AiError::Timeout {
+            message: format!("Network error: {}", e),
+            details: None,
+        },
+        AiError::Serialization(e) => AiError::other(format!("Serialization error: {}", e)),
+        AiError::Other(message) => AiError::Other(message.clone()),
+    }
+}
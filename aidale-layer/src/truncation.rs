@@ -0,0 +1,267 @@
+//! Prompt truncation layer.
+//!
+//! Trims conversation history so requests fit in the model's context window
+//! instead of failing with context-length errors.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::capabilities::model_capabilities;
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::tokenizer::{HeuristicTokenCounter, TokenCounter};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Default tokens reserved for the completion when `max_tokens` is unset
+const DEFAULT_COMPLETION_RESERVE: u32 = 1024;
+
+/// Which messages to remove when a prompt is too long
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationStrategy {
+    /// Remove the oldest messages first, including system messages
+    DropOldest,
+    /// Remove the oldest non-system messages first
+    KeepSystem,
+    /// Keep system messages and the first exchange, remove from the middle
+    MiddleOut,
+}
+
+/// Truncation layer configuration
+#[derive(Debug, Clone)]
+pub struct TruncationLayer {
+    counter: Arc<dyn TokenCounter>,
+    strategy: TruncationStrategy,
+    context_window: Option<u32>,
+}
+
+impl TruncationLayer {
+    /// Create a truncation layer with the heuristic token counter
+    ///
+    /// The context window is looked up from the model's capabilities.
+    pub fn new() -> Self {
+        Self {
+            counter: Arc::new(HeuristicTokenCounter::new()),
+            strategy: TruncationStrategy::KeepSystem,
+            context_window: None,
+        }
+    }
+
+    /// Use a custom token counter
+    pub fn with_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Set the truncation strategy
+    pub fn with_strategy(mut self, strategy: TruncationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Override the context window for all models
+    pub fn with_context_window(mut self, context_window: u32) -> Self {
+        self.context_window = Some(context_window);
+        self
+    }
+
+    /// Prompt token budget for a request, if the context window is known
+    fn prompt_budget(&self, req: &ChatCompletionRequest) -> Option<usize> {
+        let context_window = self
+            .context_window
+            .or_else(|| model_capabilities(&req.model).map(|c| c.context_window))?;
+        let reserve = req.max_tokens.unwrap_or(DEFAULT_COMPLETION_RESERVE);
+        Some(context_window.saturating_sub(reserve) as usize)
+    }
+
+    /// Remove messages until the conversation fits in `budget` tokens.
+    ///
+    /// The last message is always kept.
+    pub fn truncate(&self, messages: &mut Vec<Message>, budget: usize) {
+        while messages.len() > 1 && self.counter.count_messages(messages) > budget {
+            let Some(index) = self.removal_index(messages) else {
+                break;
+            };
+            messages.remove(index);
+        }
+    }
+
+    /// Pick the next message to remove, or None if nothing can be removed
+    fn removal_index(&self, messages: &[Message]) -> Option<usize> {
+        let last = messages.len() - 1;
+        let removable = |i: &usize| *i < last && messages[*i].role != Role::System;
+
+        match self.strategy {
+            TruncationStrategy::DropOldest => Some(0),
+            TruncationStrategy::KeepSystem => (0..last).find(|i| removable(i)),
+            TruncationStrategy::MiddleOut => {
+                // Keep the first non-system message as the conversation anchor
+                let anchor = (0..last).find(|i| removable(i));
+                (0..last)
+                    .find(|i| removable(i) && Some(*i) != anchor)
+                    .or(anchor)
+            }
+        }
+    }
+
+    fn apply(&self, req: &mut ChatCompletionRequest) {
+        if let Some(budget) = self.prompt_budget(req) {
+            let before = req.messages.len();
+            self.truncate(&mut req.messages, budget);
+            if req.messages.len() < before {
+                tracing::debug!(
+                    "Truncated {} messages to fit {} prompt tokens",
+                    before - req.messages.len(),
+                    budget
+                );
+            }
+        }
+    }
+}
+
+impl Default for TruncationLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Provider> Layer<P> for TruncationLayer {
+    type LayeredProvider = TruncationProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        TruncationProvider {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Provider wrapped with prompt truncation
+#[derive(Debug)]
+pub struct TruncationProvider<P> {
+    inner: P,
+    config: TruncationLayer,
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for TruncationProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        mut req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        self.config.apply(&mut req);
+        self.inner.chat_completion(req).await
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        mut req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        self.config.apply(&mut req);
+        self.inner.stream_chat_completion(req).await
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for TruncationProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::system("system prompt"),
+            Message::user("first question ".repeat(20)),
+            Message::assistant("first answer ".repeat(20)),
+            Message::user("second question ".repeat(20)),
+            Message::assistant("second answer ".repeat(20)),
+            Message::user("latest question"),
+        ]
+    }
+
+    fn texts(messages: &[Message]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|m| match &m.content[0] {
+                ContentPart::Text { text } => text.split(' ').next().unwrap().to_string(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_truncation_strategies() {
+        let budget = 200;
+
+        let mut messages = conversation();
+        TruncationLayer::new().truncate(&mut messages, budget);
+        assert_eq!(texts(&messages), ["system", "second", "second", "latest"]);
+
+        let mut messages = conversation();
+        TruncationLayer::new()
+            .with_strategy(TruncationStrategy::MiddleOut)
+            .truncate(&mut messages, budget);
+        assert_eq!(texts(&messages), ["system", "first", "second", "latest"]);
+
+        let mut messages = conversation();
+        TruncationLayer::new()
+            .with_strategy(TruncationStrategy::DropOldest)
+            .truncate(&mut messages, 10);
+        assert_eq!(texts(&messages), ["latest"]);
+    }
+}
@@ -0,0 +1,31 @@
+/** A model provider */
+export class Provider {
+  /** OpenAI, or any OpenAI-compatible server at `baseUrl` */
+  static openai(apiKey: string, baseUrl?: string | undefined | null): Provider
+  /** OpenAI through the Responses API */
+  static openaiResponses(apiKey: string, baseUrl?: string | undefined | null): Provider
+  /** Cohere */
+  static cohere(apiKey: string, baseUrl?: string | undefined | null): Provider
+  /** DeepSeek */
+  static deepseek(apiKey: string): Provider
+  /** Provider ID, e.g. `openai` */
+  get id(): string
+}
+
+/** Runs requests against a provider wrapped in a layer stack */
+export class Executor {
+  constructor(provider: Provider, layers?: Array<Record<string, any>> | undefined | null)
+  /** Send a chat completion request, resolving to the `chat.completion` object */
+  chat(request: Record<string, any>): Promise<Record<string, any>>
+  /** Stream a chat completion as `chat.completion.chunk` objects */
+  stream(request: Record<string, any>): ChatStream
+  /** List the provider's models */
+  models(): Promise<Array<Record<string, any>>>
+}
+
+/** Chunks of a streamed chat completion; the request is sent when iteration starts */
+export class ChatStream implements AsyncIterable<Record<string, any>> {
+  /** Next chunk, or `null` once the stream is finished */
+  nextChunk(): Promise<Record<string, any> | null>
+  [Symbol.asyncIterator](): AsyncIterator<Record<string, any>>
+}
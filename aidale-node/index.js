@@ -0,0 +1,13 @@
+'use strict'
+
+const native = require('./aidale.node')
+
+// napi-rs cannot export async generators; iterate over `nextChunk` instead
+native.ChatStream.prototype[Symbol.asyncIterator] = async function* () {
+  let chunk
+  while ((chunk = await this.nextChunk()) !== null) {
+    yield chunk
+  }
+}
+
+module.exports = native
@@ -0,0 +1,354 @@
+//! # Aidale Node.js bindings
+//!
+//! Exposes providers, layer stacks and the runtime executor to JavaScript
+//! through napi-rs, so JS AI apps can run on the same layered gateway as
+//! Rust services. Requests and responses use the OpenAI chat completion
+//! format as plain objects, methods return promises, and streams are async
+//! iterables:
+//!
+//! ```js
+//! const { Provider, Executor } = require('aidale')
+//!
+//! const provider = Provider.openai(process.env.OPENAI_API_KEY)
+//! const executor = new Executor(provider, [{ type: 'retry', max_retries: 3 }])
+//!
+//! const response = await executor.chat({
+//!   model: 'gpt-4o-mini',
+//!   messages: [{ role: 'user', content: 'Hello' }],
+//! })
+//!
+//! for await (const chunk of executor.stream({ model: 'gpt-4o-mini', messages })) {
+//!   process.stdout.write(chunk.choices[0]?.delta.content ?? '')
+//! }
+//! ```
+//!
+//! Layers are given as descriptors (see `aidale_layer::build_layer_stack`).
+//! napi-rs cannot export async generators, so `index.js` makes
+//! [`ChatStream`] iterable on top of its `nextChunk` method.
+
+use aidale_core::error::AiError;
+use aidale_core::provider::{ChatCompletionStream, Provider as AiProvider};
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_core::types::ChatCompletionRequest;
+use aidale_layer::{build_layer_stack, LayerDescriptor};
+use aidale_provider::{CohereProvider, OpenAiProvider, OpenAiResponsesProvider};
+use aidale_serve::convert::{parse_request, response_json, ChunkEncoder, OpenAiRequest};
+use futures::StreamExt;
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Reject with the error's message
+fn js_err(err: AiError) -> Error {
+    Error::new(Status::GenericFailure, err.to_string())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A model provider
+#[napi(js_name = "Provider")]
+pub struct JsProvider {
+    inner: Arc<dyn AiProvider>,
+}
+
+#[napi]
+impl JsProvider {
+    /// OpenAI, or any OpenAI-compatible server at `baseUrl`
+    #[napi(factory)]
+    pub fn openai(api_key: String, base_url: Option<String>) -> Result<Self> {
+        let mut builder = OpenAiProvider::builder().api_key(api_key);
+        if let Some(base_url) = base_url {
+            builder = builder.api_base(base_url);
+        }
+        Ok(Self {
+            inner: Arc::new(builder.build().map_err(js_err)?),
+        })
+    }
+
+    /// OpenAI through the Responses API
+    #[napi(factory)]
+    pub fn openai_responses(api_key: String, base_url: Option<String>) -> Self {
+        let mut provider = OpenAiResponsesProvider::new(api_key);
+        if let Some(base_url) = base_url {
+            provider = provider.with_api_base(base_url);
+        }
+        Self {
+            inner: Arc::new(provider),
+        }
+    }
+
+    /// Cohere
+    #[napi(factory)]
+    pub fn cohere(api_key: String, base_url: Option<String>) -> Self {
+        let mut provider = CohereProvider::new(api_key);
+        if let Some(base_url) = base_url {
+            provider = provider.with_api_base(base_url);
+        }
+        Self {
+            inner: Arc::new(provider),
+        }
+    }
+
+    /// DeepSeek
+    #[napi(factory)]
+    pub fn deepseek(api_key: String) -> Result<Self> {
+        Ok(Self {
+            inner: Arc::new(aidale_provider::deepseek(api_key).map_err(js_err)?),
+        })
+    }
+
+    /// Provider ID, e.g. `openai`
+    #[napi(getter)]
+    pub fn id(&self) -> String {
+        self.inner.info().id.clone()
+    }
+}
+
+/// Runs requests against a provider wrapped in a layer stack
+#[napi(js_name = "Executor")]
+pub struct JsExecutor {
+    inner: Arc<RuntimeExecutor>,
+}
+
+#[napi]
+impl JsExecutor {
+    #[napi(constructor)]
+    pub fn new(provider: &JsProvider, layers: Option<Value>) -> Result<Self> {
+        let mut inner = provider.inner.clone();
+        if let Some(layers) = layers {
+            let layers: Vec<LayerDescriptor> = serde_json::from_value(layers)
+                .map_err(|e| Error::new(Status::InvalidArg, format!("Invalid layers: {}", e)))?;
+            inner = build_layer_stack(inner, &layers).map_err(js_err)?;
+        }
+        Ok(Self {
+            inner: Arc::new(RuntimeExecutor::builder(inner).finish()),
+        })
+    }
+
+    /// Send a chat completion request, resolving to the `chat.completion`
+    /// object
+    #[napi(ts_return_type = "Promise<Record<string, any>>")]
+    pub async fn chat(&self, request: Value) -> Result<Value> {
+        let OpenAiRequest { request, .. } = parse_request(request).map_err(js_err)?;
+        let response = self.inner.chat_completion(request).await.map_err(js_err)?;
+        Ok(response_json(&response, unix_time()))
+    }
+
+    /// Stream a chat completion as `chat.completion.chunk` objects
+    #[napi]
+    pub fn stream(&self, request: Value) -> Result<ChatStream> {
+        let OpenAiRequest {
+            request,
+            include_usage,
+            ..
+        } = parse_request(request).map_err(js_err)?;
+        let state = StreamState {
+            executor: self.inner.clone(),
+            request: Some(request),
+            chunks: None,
+            encoder: ChunkEncoder::new(unix_time(), include_usage),
+            ready: VecDeque::new(),
+        };
+        Ok(ChatStream {
+            state: Arc::new(tokio::sync::Mutex::new(state)),
+        })
+    }
+
+    /// List the provider's models
+    #[napi(ts_return_type = "Promise<Array<Record<string, any>>>")]
+    pub async fn models(&self) -> Result<Value> {
+        let models = self.inner.models().await.map_err(js_err)?;
+        serde_json::to_value(models).map_err(|e| js_err(AiError::Serialization(e)))
+    }
+}
+
+struct StreamState {
+    executor: Arc<RuntimeExecutor>,
+    /// Request to send on the first `nextChunk`
+    request: Option<ChatCompletionRequest>,
+    chunks: Option<Box<ChatCompletionStream>>,
+    encoder: ChunkEncoder,
+    /// Encoded chunks not yet returned
+    ready: VecDeque<Value>,
+}
+
+/// Chunks of a streamed chat completion
+///
+/// The request is sent when iteration starts.
+#[napi]
+pub struct ChatStream {
+    state: Arc<tokio::sync::Mutex<StreamState>>,
+}
+
+#[napi]
+impl ChatStream {
+    /// Next chunk, or `null` once the stream is finished
+    #[napi(ts_return_type = "Promise<Record<string, any> | null>")]
+    pub async fn next_chunk(&self) -> Result<Option<Value>> {
+        let mut state = self.state.lock().await;
+        loop {
+            if let Some(chunk) = state.ready.pop_front() {
+                return Ok(Some(chunk));
+            }
+            if state.chunks.is_none() {
+                let Some(request) = state.request.take() else {
+                    return Ok(None);
+                };
+                let chunks = state.executor.stream(request).await.map_err(js_err)?;
+                state.chunks = Some(chunks);
+            }
+
+            let next = match state.chunks.as_mut() {
+                Some(chunks) => chunks.next().await,
+                None => None,
+            };
+            match next {
+                Some(Ok(chunk)) => {
+                    let encoded = state.encoder.encode(&chunk);
+                    state.ready.extend(encoded);
+                }
+                Some(Err(err)) => {
+                    state.chunks = None;
+                    return Err(js_err(err));
+                }
+                None => {
+                    state.chunks = None;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::ChatCompletionStream;
+    use aidale_core::types::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    /// Answers "Hello" and streams it in two chunks
+    #[derive(Debug)]
+    struct HelloProvider;
+
+    #[async_trait]
+    impl AiProvider for HelloProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "hello".to_string(),
+                name: "Hello".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> std::result::Result<ChatCompletionResponse, AiError> {
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant("Hello"),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> std::result::Result<Box<ChatCompletionStream>, AiError> {
+            let chunk = |content: &str, finish_reason| ChatCompletionChunk {
+                id: "resp".to_string(),
+                model: req.model.clone(),
+                choices: vec![ChoiceDelta {
+                    index: 0,
+                    delta: MessageDelta {
+                        role: None,
+                        content: Some(content.to_string()),
+                        reasoning: None,
+                        tool_calls: None,
+                    },
+                    finish_reason,
+                }],
+                usage: None,
+                system_fingerprint: None,
+                service_tier: None,
+            };
+            let chunks = vec![
+                Ok(chunk("Hel", None)),
+                Ok(chunk("lo", Some(FinishReason::Stop))),
+            ];
+            Ok(Box::new(futures::stream::iter(chunks)))
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let provider = JsProvider::openai(
+            "key".to_string(),
+            Some("http://localhost:8000/v1".to_string()),
+        )
+        .unwrap();
+        assert_eq!(provider.id(), "openai");
+        assert_eq!(
+            JsProvider::deepseek("key".to_string()).unwrap().id(),
+            "deepseek"
+        );
+        assert_eq!(JsProvider::cohere("key".to_string(), None).id(), "cohere");
+
+        let layers = json!([{"type": "retry", "max_retries": 2}, {"type": "logging"}]);
+        assert!(JsExecutor::new(&provider, Some(layers)).is_ok());
+
+        let err = JsExecutor::new(&provider, Some(json!([{"type": "teleport"}])))
+            .err()
+            .unwrap();
+        assert_eq!(err.status, Status::InvalidArg);
+        assert!(err.reason.starts_with("Invalid layers"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_and_stream_json() {
+        let provider = JsProvider {
+            inner: Arc::new(HelloProvider),
+        };
+        let executor = JsExecutor::new(&provider, None).unwrap();
+        let request = json!({"model": "m", "messages": [{"role": "user", "content": "Hi"}]});
+
+        let response = executor.chat(request.clone()).await.unwrap();
+        assert_eq!(response["object"], "chat.completion");
+        assert_eq!(response["model"], "m");
+        assert_eq!(response["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(response["choices"][0]["finish_reason"], "stop");
+
+        let stream = executor.stream(request).unwrap();
+        let mut content = String::new();
+        while let Some(chunk) = stream.next_chunk().await.unwrap() {
+            assert_eq!(chunk["object"], "chat.completion.chunk");
+            if let Some(delta) = chunk["choices"][0]["delta"]["content"].as_str() {
+                content.push_str(delta);
+            }
+        }
+        assert_eq!(content, "Hello");
+
+        let err = executor.chat(json!({"messages": []})).await.err().unwrap();
+        assert_eq!(err.status, Status::GenericFailure);
+    }
+}
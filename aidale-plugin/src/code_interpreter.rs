@@ -0,0 +1,494 @@
+//! Code execution tool.
+//!
+//! [`CodeInterpreterTool`] runs model-written code in a jailed subprocess and
+//! returns the captured output as the tool result:
+//!
+//! ```json
+//! {"stdout": "4\n", "stderr": "", "exit_code": 0, "timed_out": false, "truncated": false}
+//! ```
+//!
+//! Each run gets a fresh temporary working directory (removed afterwards), an
+//! empty environment apart from `PATH`, no stdin and a wall-clock timeout.
+//! On Unix the process also runs in its own process group, so the whole tree
+//! is killed on timeout, under CPU time, address space and file size limits.
+//!
+//! The jail limits resources but does not isolate the file system or the
+//! network. For untrusted code, route it through a container or namespace
+//! tool with [`CodeInterpreterTool::with_wrapper`], e.g. to cut off the
+//! network:
+//!
+//! ```ignore
+//! let tool = CodeInterpreterTool::new(Interpreter::python())
+//!     .with_wrapper(["unshare", "--user", "--map-root-user", "--net"])
+//!     .with_limits(SandboxLimits::default().with_timeout(Duration::from_secs(30)));
+//! registry.register(tool.name(), Arc::new(tool));
+//! ```
+
+use crate::tool_use::ToolExecutor;
+use aidale_core::error::AiError;
+use aidale_core::types::Tool;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+
+/// Program that runs code of one language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interpreter {
+    /// Language name the model selects, e.g. `python`
+    pub language: String,
+    pub program: String,
+    /// Arguments before the path of the code file
+    pub args: Vec<String>,
+    /// Extension of the code file, e.g. `py`
+    pub extension: String,
+}
+
+impl Interpreter {
+    /// Create an interpreter invoked as `program <file>`
+    pub fn new(
+        language: impl Into<String>,
+        program: impl Into<String>,
+        extension: impl Into<String>,
+    ) -> Self {
+        Self {
+            language: language.into(),
+            program: program.into(),
+            args: Vec::new(),
+            extension: extension.into(),
+        }
+    }
+
+    /// Python 3 as `python3`
+    pub fn python() -> Self {
+        Self::new("python", "python3", "py")
+    }
+
+    /// JavaScript as `node`
+    pub fn javascript() -> Self {
+        Self::new("javascript", "node", "js")
+    }
+
+    /// POSIX shell as `sh`
+    pub fn shell() -> Self {
+        Self::new("shell", "sh", "sh")
+    }
+
+    /// Add an argument before the path of the code file
+    pub fn with_arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+}
+
+/// Resource limits of one run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SandboxLimits {
+    /// Wall-clock time before the process is killed
+    pub timeout: Duration,
+    /// CPU time, in seconds (Unix only)
+    pub cpu_seconds: u64,
+    /// Address space, in bytes (Unix only)
+    pub memory_bytes: u64,
+    /// Size of any file the process writes, in bytes (Unix only)
+    pub file_size_bytes: u64,
+    /// Bytes of stdout and of stderr returned; the rest is dropped
+    pub max_output_bytes: usize,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            cpu_seconds: 10,
+            memory_bytes: 1 << 30,
+            file_size_bytes: 16 << 20,
+            max_output_bytes: 64 << 10,
+        }
+    }
+}
+
+impl SandboxLimits {
+    /// Set the wall-clock timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the CPU time limit in seconds
+    pub fn with_cpu_seconds(mut self, cpu_seconds: u64) -> Self {
+        self.cpu_seconds = cpu_seconds;
+        self
+    }
+
+    /// Set the address space limit; JavaScript engines reserve a lot of it
+    pub fn with_memory_bytes(mut self, memory_bytes: u64) -> Self {
+        self.memory_bytes = memory_bytes;
+        self
+    }
+
+    /// Set the file size limit
+    pub fn with_file_size_bytes(mut self, file_size_bytes: u64) -> Self {
+        self.file_size_bytes = file_size_bytes;
+        self
+    }
+
+    /// Set how much of stdout and of stderr is returned
+    pub fn with_max_output_bytes(mut self, max_output_bytes: usize) -> Self {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
+}
+
+/// Tool running model-written code in a jailed subprocess
+#[derive(Debug, Clone)]
+pub struct CodeInterpreterTool {
+    name: String,
+    interpreters: Vec<Interpreter>,
+    wrapper: Vec<String>,
+    limits: SandboxLimits,
+}
+
+/// Removes the working directory of a run, even if the run is cancelled
+struct WorkDir(PathBuf);
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// How long output is still read after the process exits
+///
+/// Descendants that left the process group, e.g. through `setsid`, survive
+/// the kill and may hold the pipes open indefinitely.
+const PIPE_GRACE: Duration = Duration::from_millis(200);
+
+/// Output read from a pipe so far
+#[derive(Debug, Default)]
+struct Captured {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+impl Captured {
+    fn take(captured: &Mutex<Captured>) -> (String, bool) {
+        let captured = captured.lock().unwrap_or_else(|e| e.into_inner());
+        (
+            String::from_utf8_lossy(&captured.bytes).into_owned(),
+            captured.truncated,
+        )
+    }
+}
+
+/// Read a pipe to the end, keeping the first `limit` bytes
+async fn read_capped(
+    mut pipe: impl AsyncRead + Unpin,
+    limit: usize,
+    captured: Arc<Mutex<Captured>>,
+) -> std::io::Result<()> {
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = pipe.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        let mut captured = captured.lock().unwrap_or_else(|e| e.into_inner());
+        let room = limit.saturating_sub(captured.bytes.len());
+        captured.bytes.extend_from_slice(&buf[..n.min(room)]);
+        captured.truncated |= n > room;
+    }
+}
+
+/// Command running `program` in `dir` with a clean environment and, on
+/// Unix, in its own process group under the resource limits
+pub(crate) fn jailed_command<I, S>(
+    program: &str,
+    args: I,
+    dir: &Path,
+    limits: SandboxLimits,
+) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(dir)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(path) = std::env::var_os("PATH") {
+        command.env("PATH", path);
+    }
+
+    #[cfg(unix)]
+    {
+        command.process_group(0);
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                let set = |resource, value: u64| {
+                    let limit = libc::rlimit {
+                        rlim_cur: value as libc::rlim_t,
+                        rlim_max: value as libc::rlim_t,
+                    };
+                    if libc::setrlimit(resource, &limit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                };
+                set(libc::RLIMIT_CPU, limits.cpu_seconds.max(1))?;
+                set(libc::RLIMIT_AS, limits.memory_bytes)?;
+                set(libc::RLIMIT_FSIZE, limits.file_size_bytes)?;
+                set(libc::RLIMIT_CORE, 0)
+            });
+        }
+    }
+    command
+}
+
+/// Run a [`jailed_command`], killing it on timeout, and capture its output
+///
+/// Output still pending [`PIPE_GRACE`] after the process exits is dropped.
+pub(crate) async fn run_jailed(
+    mut command: Command,
+    limits: SandboxLimits,
+) -> std::io::Result<Value> {
+    let mut child = command.spawn()?;
+    let limit = limits.max_output_bytes;
+    let stdout = Arc::new(Mutex::new(Captured::default()));
+    let stderr = Arc::new(Mutex::new(Captured::default()));
+    let mut readers = [
+        tokio::spawn(read_capped(
+            child.stdout.take().expect("piped"),
+            limit,
+            stdout.clone(),
+        )),
+        tokio::spawn(read_capped(
+            child.stderr.take().expect("piped"),
+            limit,
+            stderr.clone(),
+        )),
+    ];
+
+    let (status, timed_out) = match tokio::time::timeout(limits.timeout, child.wait()).await {
+        Ok(status) => (status?, false),
+        Err(_) => {
+            #[cfg(unix)]
+            if let Some(pid) = child.id() {
+                // SAFETY: signals the process group created for the child
+                unsafe {
+                    libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+                }
+            }
+            let _ = child.start_kill();
+            (child.wait().await?, true)
+        }
+    };
+
+    let drained = tokio::time::timeout(PIPE_GRACE, async {
+        for reader in &mut readers {
+            reader.await??;
+        }
+        Ok::<_, std::io::Error>(())
+    })
+    .await;
+    match drained {
+        Ok(result) => result?,
+        Err(_) => {
+            tracing::debug!("Output pipes still open after exit; keeping what was read");
+            for reader in &readers {
+                reader.abort();
+            }
+        }
+    }
+
+    let (stdout, stdout_truncated) = Captured::take(&stdout);
+    let (stderr, stderr_truncated) = Captured::take(&stderr);
+    Ok(json!({
+        "stdout": stdout,
+        "stderr": stderr,
+        "exit_code": status.code(),
+        "timed_out": timed_out,
+        "truncated": stdout_truncated || stderr_truncated,
+    }))
+}
+
+impl CodeInterpreterTool {
+    /// Create a tool named `code_interpreter` running code with `interpreter`
+    pub fn new(interpreter: Interpreter) -> Self {
+        Self {
+            name: "code_interpreter".to_string(),
+            interpreters: vec![interpreter],
+            wrapper: Vec::new(),
+            limits: SandboxLimits::default(),
+        }
+    }
+
+    /// Set the tool name
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Offer another language; the model picks one per call
+    pub fn with_interpreter(mut self, interpreter: Interpreter) -> Self {
+        self.interpreters.push(interpreter);
+        self
+    }
+
+    /// Run the interpreter through a wrapper command, e.g. `bwrap` or `firejail`
+    pub fn with_wrapper<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.wrapper = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the resource limits
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Tool name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn error(message: impl Into<String>) -> AiError {
+        AiError::plugin("CodeInterpreterTool", message)
+    }
+
+    /// Run `code` and capture its output
+    pub async fn run(&self, language: Option<&str>, code: &str) -> Result<Value, AiError> {
+        let interpreter = match language {
+            Some(language) => self
+                .interpreters
+                .iter()
+                .find(|interpreter| interpreter.language.eq_ignore_ascii_case(language))
+                .ok_or_else(|| Self::error(format!("Unsupported language: {}", language)))?,
+            None => &self.interpreters[0],
+        };
+
+        let dir = WorkDir(
+            std::env::temp_dir().join(format!("aidale-sandbox-{}", uuid::Uuid::new_v4().simple())),
+        );
+        let file = format!("main.{}", interpreter.extension);
+        std::fs::create_dir(&dir.0)
+            .and_then(|_| std::fs::write(dir.0.join(&file), code))
+            .map_err(|e| Self::error(format!("Failed to prepare sandbox: {}", e)))?;
+
+        let mut argv = self
+            .wrapper
+            .iter()
+            .chain([&interpreter.program])
+            .chain(&interpreter.args)
+            .chain([&file]);
+        let mut command = jailed_command(argv.next().expect("program"), argv, &dir.0, self.limits);
+        command.env("HOME", &dir.0).env("TMPDIR", &dir.0);
+        run_jailed(command, self.limits)
+            .await
+            .map_err(|e| Self::error(format!("Failed to run {}: {}", interpreter.program, e)))
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for CodeInterpreterTool {
+    async fn execute(&self, _name: &str, arguments: &Value) -> Result<Value, AiError> {
+        let code = arguments["code"]
+            .as_str()
+            .ok_or_else(|| Self::error("Missing `code` argument"))?;
+        self.run(arguments["language"].as_str(), code).await
+    }
+
+    fn definition(&self) -> Option<Tool> {
+        let languages: Vec<_> = self
+            .interpreters
+            .iter()
+            .map(|interpreter| interpreter.language.as_str())
+            .collect();
+        let mut parameters = json!({
+            "type": "object",
+            "properties": {
+                "code": {"type": "string", "description": "Source code to run"},
+            },
+            "required": ["code"],
+        });
+        if languages.len() > 1 {
+            parameters["properties"]["language"] = json!({"type": "string", "enum": languages});
+            parameters["required"] = json!(["code", "language"]);
+        }
+        Some(Tool::new(
+            self.name.clone(),
+            format!(
+                "Run {} code in a sandbox and return its stdout, stderr and exit code. \
+                 Print results to stdout; runs time out after {} seconds.",
+                languages.join(" or "),
+                self.limits.timeout.as_secs()
+            ),
+            parameters,
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_code_interpreter() {
+        let tool = CodeInterpreterTool::new(Interpreter::shell()).with_limits(
+            SandboxLimits::default()
+                .with_timeout(Duration::from_millis(500))
+                .with_max_output_bytes(16),
+        );
+        let definition = ToolExecutor::definition(&tool).unwrap();
+        assert_eq!(definition.name, "code_interpreter");
+
+        let args = json!({"code": "echo hello; echo oops >&2; pwd; exit 3"});
+        let result = tool.execute("code_interpreter", &args).await.unwrap();
+        assert!(result["stdout"].as_str().unwrap().starts_with("hello\n/"));
+        assert_eq!(result["stderr"], "oops\n");
+        assert_eq!(result["exit_code"], 3);
+        assert_eq!(result["truncated"], true);
+
+        let args = json!({"code": "sleep 5 & sleep 5"});
+        let result = tool.execute("code_interpreter", &args).await.unwrap();
+        assert_eq!(result["timed_out"], true);
+
+        let args = json!({"code": "echo hi", "language": "cobol"});
+        assert!(tool.execute("code_interpreter", &args).await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_detached_child_does_not_block() {
+        let tool = CodeInterpreterTool::new(Interpreter::shell())
+            .with_limits(SandboxLimits::default().with_timeout(Duration::from_millis(500)));
+        let started = std::time::Instant::now();
+
+        // The detached sleep keeps stdout open after the shell exits
+        let args = json!({"code": "setsid sleep 5 & echo started"});
+        let result = tool.execute("code_interpreter", &args).await.unwrap();
+        assert_eq!(result["stdout"], "started\n");
+        assert_eq!(result["timed_out"], false);
+
+        // ... and survives the kill of the process group on timeout
+        let args = json!({"code": "setsid sleep 5 & sleep 5"});
+        let result = tool.execute("code_interpreter", &args).await.unwrap();
+        assert_eq!(result["timed_out"], true);
+
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+}
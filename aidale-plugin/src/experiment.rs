@@ -0,0 +1,319 @@
+//! Prompt and model A/B experiments.
+//!
+//! An [`ExperimentPlugin`] assigns every request to one of its weighted
+//! [`Variant`]s and applies that variant's model, system prompt, template
+//! messages and temperature. Requests carrying a user ID in their metadata
+//! (`user_id` by default) are assigned by hashing it, so a user always sees
+//! the same variant; other requests are assigned by their request ID.
+//!
+//! The assignment is written to the request metadata as
+//! `experiment.<name>` before any other hook runs, so a caller can pin a
+//! variant by setting that key up front. Text results are tagged under
+//! `metadata["experiments"]` as `{"<name>": "<variant>"}` for analytics.
+//!
+//! ```ignore
+//! let plugin = ExperimentPlugin::new("onboarding-prompt")
+//!     .with_variant(Variant::new("v1").with_system_prompt("You are concise."))
+//!     .with_variant(
+//!         Variant::new("v2")
+//!             .with_weight(3)
+//!             .with_model("gpt-4o")
+//!             .with_system_prompt("You are friendly and concise."),
+//!     );
+//! ```
+
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::types::*;
+use async_trait::async_trait;
+
+/// One arm of an experiment
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    /// Relative share of traffic
+    pub weight: u32,
+    /// Model to use instead of the requested one
+    pub model: Option<String>,
+    /// System prompt replacing the leading system message
+    pub system_prompt: Option<String>,
+    /// Messages inserted after the system prompt, e.g. few-shot examples
+    pub template: Vec<Message>,
+    pub temperature: Option<f32>,
+}
+
+impl Variant {
+    /// Create a variant with weight 1 that changes nothing
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            weight: 1,
+            model: None,
+            system_prompt: None,
+            template: Vec::new(),
+            temperature: None,
+        }
+    }
+
+    /// Set the relative share of traffic
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Use a different model
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Replace the system prompt
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Insert template messages before the conversation
+    pub fn with_template(mut self, messages: Vec<Message>) -> Self {
+        self.template = messages;
+        self
+    }
+
+    /// Override the sampling temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Apply the prompt changes to a conversation
+    fn apply_messages(&self, messages: &mut Vec<Message>) {
+        let mut position = messages
+            .iter()
+            .take_while(|msg| msg.role == Role::System)
+            .count();
+        if let Some(prompt) = &self.system_prompt {
+            if position > 0 {
+                messages[0] = Message::system(prompt.clone());
+            } else {
+                messages.insert(0, Message::system(prompt.clone()));
+                position = 1;
+            }
+        }
+        messages.splice(position..position, self.template.iter().cloned());
+    }
+}
+
+/// Plugin assigning requests to experiment variants
+#[derive(Debug, Clone)]
+pub struct ExperimentPlugin {
+    name: String,
+    variants: Vec<Variant>,
+    unit_key: String,
+}
+
+impl ExperimentPlugin {
+    /// Create an experiment without variants
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            variants: Vec::new(),
+            unit_key: "user_id".to_string(),
+        }
+    }
+
+    /// Add a variant
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variants.push(variant);
+        self
+    }
+
+    /// Metadata key identifying the assignment unit (default `user_id`)
+    pub fn with_unit_key(mut self, key: impl Into<String>) -> Self {
+        self.unit_key = key.into();
+        self
+    }
+
+    /// Experiment name
+    pub fn experiment_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Metadata key holding the assigned variant
+    pub fn metadata_key(&self) -> String {
+        format!("experiment.{}", self.name)
+    }
+
+    /// Variant for an assignment unit, by weight
+    pub fn variant_for(&self, unit: &str) -> Option<&Variant> {
+        let total: u64 = self.variants.iter().map(|v| v.weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        // FNV-1a, stable across processes and releases
+        let key = format!("{}:{}", self.name, unit);
+        let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+
+        let mut bucket = hash % total;
+        self.variants.iter().find(|variant| {
+            let weight = variant.weight as u64;
+            if bucket < weight {
+                true
+            } else {
+                bucket -= weight;
+                false
+            }
+        })
+    }
+
+    /// Variant of the request, assigning one on first use
+    pub fn assign(&self, ctx: &RequestContext) -> Option<&Variant> {
+        let key = self.metadata_key();
+        if let Some(pinned) = ctx.get_metadata(&key) {
+            if let Some(variant) = self.variants.iter().find(|v| v.name == pinned) {
+                return Some(variant);
+            }
+        }
+
+        let unit = ctx
+            .get_metadata(&self.unit_key)
+            .unwrap_or_else(|| ctx.request_id.clone());
+        let variant = self.variant_for(&unit)?;
+        ctx.set_metadata(key, variant.name.clone());
+        Some(variant)
+    }
+}
+
+#[async_trait]
+impl Plugin for ExperimentPlugin {
+    fn name(&self) -> &str {
+        "experiment"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        PluginPhase::Pre
+    }
+
+    async fn resolve_model(
+        &self,
+        _model_id: &str,
+        ctx: &RequestContext,
+    ) -> Result<Option<String>, AiError> {
+        Ok(self.assign(ctx).and_then(|variant| variant.model.clone()))
+    }
+
+    async fn transform_params(
+        &self,
+        mut params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        if let Some(variant) = self.assign(ctx) {
+            variant.apply_messages(&mut params.messages);
+            if variant.temperature.is_some() {
+                params.temperature = variant.temperature;
+            }
+        }
+        Ok(params)
+    }
+
+    async fn transform_object_params(
+        &self,
+        mut params: ObjectParams,
+        ctx: &RequestContext,
+    ) -> Result<ObjectParams, AiError> {
+        if let Some(variant) = self.assign(ctx) {
+            variant.apply_messages(&mut params.messages);
+            if variant.temperature.is_some() {
+                params.temperature = variant.temperature;
+            }
+        }
+        Ok(params)
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        let Some(variant) = self.assign(ctx) else {
+            return Ok(result);
+        };
+
+        let experiments = result
+            .metadata
+            .entry("experiments".to_string())
+            .or_insert_with(|| serde_json::json!({}));
+        if let Some(experiments) = experiments.as_object_mut() {
+            experiments.insert(self.name.clone(), variant.name.clone().into());
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_sticky_assignment_and_tagging() {
+        let plugin = ExperimentPlugin::new("greeting")
+            .with_variant(Variant::new("control"))
+            .with_variant(
+                Variant::new("friendly")
+                    .with_weight(3)
+                    .with_model("big-model")
+                    .with_system_prompt("Be friendly.")
+                    .with_template(vec![Message::user("Hi"), Message::assistant("Hello!")]),
+            );
+
+        // Weighted and deterministic per user
+        let friendly = (0..1000)
+            .filter(|i| plugin.variant_for(&format!("user-{}", i)).unwrap().name == "friendly")
+            .count();
+        assert!(
+            (650..850).contains(&friendly),
+            "friendly share {}",
+            friendly
+        );
+        let user = |id: &str| {
+            RequestContext::new("test", "small-model")
+                .with_metadata(HashMap::from([("user_id".to_string(), id.to_string())]))
+        };
+        let first = plugin.assign(&user("ada")).unwrap().name.clone();
+        assert_eq!(plugin.assign(&user("ada")).unwrap().name, first);
+
+        // A pinned variant wins over hashing
+        let ctx = user("ada");
+        ctx.set_metadata(plugin.metadata_key(), "friendly");
+        let model = plugin.resolve_model("small-model", &ctx).await.unwrap();
+        assert_eq!(model.as_deref(), Some("big-model"));
+
+        let params = TextParams::new(vec![Message::system("Be terse."), Message::user("Hey")]);
+        let params = plugin.transform_params(params, &ctx).await.unwrap();
+        let texts: Vec<_> = params
+            .messages
+            .iter()
+            .map(|msg| match &msg.content[0] {
+                ContentPart::Text { text } => text.as_str(),
+                _ => "",
+            })
+            .collect();
+        assert_eq!(texts, ["Be friendly.", "Hi", "Hello!", "Hey"]);
+
+        let result = TextResult {
+            content: "Hello there!".to_string(),
+            finish_reason: FinishReason::Stop,
+            usage: Usage::default(),
+            model: "big-model".to_string(),
+            tool_calls: None,
+            reasoning: None,
+            citations: None,
+            metadata: HashMap::new(),
+        };
+        let result = plugin.transform_result(result, &ctx).await.unwrap();
+        assert_eq!(result.metadata["experiments"]["greeting"], "friendly");
+    }
+}
@@ -0,0 +1,264 @@
+//! Guardrails plugin for output constraints.
+//!
+//! Validates generated text against a set of rules (regex allow/deny lists,
+//! maximum length, JSON well-formedness, custom async validators) and either
+//! rejects, repairs via a re-prompt, or annotates the result.
+
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::provider::Provider;
+use aidale_core::types::*;
+use async_trait::async_trait;
+use regex::Regex;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Custom output validator
+#[async_trait]
+pub trait Validator: Send + Sync + Debug + 'static {
+    /// Validator name used in violation messages
+    fn name(&self) -> &str;
+
+    /// Validate content, returning a violation description if it fails
+    async fn validate(&self, content: &str) -> Result<Option<String>, AiError>;
+}
+
+/// A single guardrail rule
+#[derive(Debug, Clone)]
+enum Rule {
+    Allow(Regex),
+    Deny(Regex),
+    MaxLength(usize),
+    Json,
+    Custom(Arc<dyn Validator>),
+}
+
+impl Rule {
+    async fn check(&self, content: &str) -> Result<Option<String>, AiError> {
+        Ok(match self {
+            Rule::Allow(regex) if !regex.is_match(content) => {
+                Some(format!("output must match pattern `{}`", regex.as_str()))
+            }
+            Rule::Deny(regex) if regex.is_match(content) => Some(format!(
+                "output must not match pattern `{}`",
+                regex.as_str()
+            )),
+            Rule::MaxLength(max) if content.chars().count() > *max => {
+                Some(format!("output must be at most {} characters", max))
+            }
+            Rule::Json => serde_json::from_str::<serde_json::Value>(content)
+                .err()
+                .map(|e| format!("output must be valid JSON ({})", e)),
+            Rule::Custom(validator) => validator
+                .validate(content)
+                .await?
+                .map(|violation| format!("{}: {}", validator.name(), violation)),
+            _ => None,
+        })
+    }
+}
+
+/// What to do when output violates the guardrails
+#[derive(Debug, Clone)]
+pub enum GuardrailAction {
+    /// Fail the request with a plugin error
+    Reject,
+    /// Ask the model to rewrite the output, up to `max_attempts` times
+    Repair {
+        provider: Arc<dyn Provider>,
+        max_attempts: u32,
+    },
+    /// Keep the output and record violations under `metadata["guardrails"]`
+    Annotate,
+}
+
+/// Guardrails plugin
+#[derive(Debug, Clone)]
+pub struct GuardrailsPlugin {
+    rules: Vec<Rule>,
+    action: GuardrailAction,
+}
+
+impl GuardrailsPlugin {
+    /// Create a plugin with no rules that rejects violations
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            action: GuardrailAction::Reject,
+        }
+    }
+
+    /// Require output to match a regular expression
+    pub fn allow_pattern(mut self, pattern: &str) -> Result<Self, AiError> {
+        self.rules.push(Rule::Allow(Self::compile(pattern)?));
+        Ok(self)
+    }
+
+    /// Reject output matching a regular expression
+    pub fn deny_pattern(mut self, pattern: &str) -> Result<Self, AiError> {
+        self.rules.push(Rule::Deny(Self::compile(pattern)?));
+        Ok(self)
+    }
+
+    /// Limit output length in characters
+    pub fn max_length(mut self, max: usize) -> Self {
+        self.rules.push(Rule::MaxLength(max));
+        self
+    }
+
+    /// Require output to be well-formed JSON
+    pub fn require_json(mut self) -> Self {
+        self.rules.push(Rule::Json);
+        self
+    }
+
+    /// Add a custom validator
+    pub fn validator(mut self, validator: Arc<dyn Validator>) -> Self {
+        self.rules.push(Rule::Custom(validator));
+        self
+    }
+
+    /// Set the action taken on violations
+    pub fn with_action(mut self, action: GuardrailAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    fn compile(pattern: &str) -> Result<Regex, AiError> {
+        Regex::new(pattern)
+            .map_err(|e| AiError::configuration(format!("Invalid guardrail pattern: {}", e)))
+    }
+
+    /// Collect all rule violations for the content
+    pub async fn violations(&self, content: &str) -> Result<Vec<String>, AiError> {
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            if let Some(violation) = rule.check(content).await? {
+                violations.push(violation);
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Ask the model to rewrite the content so it satisfies the rules
+    async fn repair(
+        provider: &Arc<dyn Provider>,
+        model: &str,
+        content: &str,
+        violations: &[String],
+    ) -> Result<String, AiError> {
+        let instruction = format!(
+            "Rewrite the following response so that it satisfies these constraints:\n- {}\n\n\
+            Return only the rewritten response.\n\nResponse:\n{}",
+            violations.join("\n- "),
+            content
+        );
+        let req = ChatCompletionRequest::new(model, vec![Message::user(instruction)]);
+        let response = provider.chat_completion(req).await?;
+
+        let choice = response
+            .choices
+            .first()
+            .ok_or_else(|| AiError::provider("No choices in response"))?;
+        Ok(choice
+            .message
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn rejection(violations: &[String]) -> AiError {
+        AiError::plugin(
+            "guardrails",
+            format!("Output violated guardrails: {}", violations.join("; ")),
+        )
+    }
+}
+
+impl Default for GuardrailsPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Plugin for GuardrailsPlugin {
+    fn name(&self) -> &str {
+        "guardrails"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        PluginPhase::Post
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        let mut violations = self.violations(&result.content).await?;
+        if violations.is_empty() {
+            return Ok(result);
+        }
+
+        match &self.action {
+            GuardrailAction::Reject => Err(Self::rejection(&violations)),
+            GuardrailAction::Repair {
+                provider,
+                max_attempts,
+            } => {
+                for attempt in 1..=*max_attempts {
+                    tracing::debug!(
+                        "Guardrails repair attempt {}/{}: {:?}",
+                        attempt,
+                        max_attempts,
+                        violations
+                    );
+                    result.content =
+                        Self::repair(provider, &ctx.model, &result.content, &violations).await?;
+                    violations = self.violations(&result.content).await?;
+                    if violations.is_empty() {
+                        return Ok(result);
+                    }
+                }
+                Err(Self::rejection(&violations))
+            }
+            GuardrailAction::Annotate => {
+                result
+                    .metadata
+                    .insert("guardrails".to_string(), serde_json::json!(violations));
+                Ok(result)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_violations() {
+        let plugin = GuardrailsPlugin::new()
+            .deny_pattern(r"(?i)password")
+            .unwrap()
+            .max_length(20)
+            .require_json();
+
+        assert!(plugin
+            .violations(r#"{"ok": true}"#)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let violations = plugin
+            .violations("here is the password you asked for")
+            .await
+            .unwrap();
+        assert_eq!(violations.len(), 3);
+    }
+}
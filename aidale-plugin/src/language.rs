@@ -0,0 +1,463 @@
+//! Output language enforcement.
+//!
+//! Models often answer in the language of the context rather than the
+//! user's, e.g. in English when a Chinese question comes with English
+//! documents. [`LanguagePlugin`] adds an instruction naming the expected
+//! language, checks the language of the answer with a fast trigram-based
+//! detector ([whatlang]), and, with a provider set, re-prompts once when
+//! the model answered in another language:
+//!
+//! ```ignore
+//! let executor = RuntimeExecutor::builder(provider.clone())
+//!     // Expected language from `metadata["locale"]`, e.g. `zh-CN`
+//!     .plugin(Arc::new(
+//!         LanguagePlugin::from_metadata("locale").with_reprompt(Arc::new(provider)),
+//!     ))
+//!     .finish();
+//! ```
+//!
+//! Code blocks and inline code are ignored by the check, and so are answers
+//! too short to tell. Mismatches are recorded on the result as
+//! `metadata["language"]`, e.g. `{"expected": "zh", "detected": "en",
+//! "reprompted": true, "resolved": true}`, where `detected` is the language
+//! of the first answer and `resolved` tells whether the retry was in the
+//! expected language.
+//!
+//! [whatlang]: https://docs.rs/whatlang
+
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::provider::Provider;
+use aidale_core::types::*;
+use async_trait::async_trait;
+use std::sync::Arc;
+use whatlang::Lang;
+
+/// Two-letter codes and English names of common languages
+const LANGUAGES: &[(&str, Lang, &str)] = &[
+    ("zh", Lang::Cmn, "Chinese"),
+    ("en", Lang::Eng, "English"),
+    ("ja", Lang::Jpn, "Japanese"),
+    ("ko", Lang::Kor, "Korean"),
+    ("es", Lang::Spa, "Spanish"),
+    ("fr", Lang::Fra, "French"),
+    ("de", Lang::Deu, "German"),
+    ("it", Lang::Ita, "Italian"),
+    ("pt", Lang::Por, "Portuguese"),
+    ("ru", Lang::Rus, "Russian"),
+    ("ar", Lang::Ara, "Arabic"),
+    ("hi", Lang::Hin, "Hindi"),
+    ("vi", Lang::Vie, "Vietnamese"),
+    ("th", Lang::Tha, "Thai"),
+    ("tr", Lang::Tur, "Turkish"),
+    ("nl", Lang::Nld, "Dutch"),
+    ("pl", Lang::Pol, "Polish"),
+    ("uk", Lang::Ukr, "Ukrainian"),
+    ("id", Lang::Ind, "Indonesian"),
+    ("he", Lang::Heb, "Hebrew"),
+    ("sv", Lang::Swe, "Swedish"),
+    ("fa", Lang::Pes, "Persian"),
+    ("el", Lang::Ell, "Greek"),
+    ("cs", Lang::Ces, "Czech"),
+    ("ro", Lang::Ron, "Romanian"),
+    ("hu", Lang::Hun, "Hungarian"),
+    ("da", Lang::Dan, "Danish"),
+    ("fi", Lang::Fin, "Finnish"),
+    ("nb", Lang::Nob, "Norwegian"),
+    ("bn", Lang::Ben, "Bengali"),
+];
+
+/// A language that answers can be checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    lang: Lang,
+    code: &'static str,
+    name: &'static str,
+}
+
+impl Language {
+    /// Parse a language code or locale: `zh`, `zh-CN`, `zh_TW` or an ISO
+    /// 639-3 code such as `cmn`
+    pub fn parse(code: &str) -> Option<Self> {
+        let primary = code.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        if let Some((code, lang, name)) = LANGUAGES.iter().find(|(code, ..)| *code == primary) {
+            return Some(Self {
+                lang: *lang,
+                code,
+                name,
+            });
+        }
+        Lang::from_code(primary).map(Self::from_lang)
+    }
+
+    /// Detect the language of `text`, if the detector is confident
+    pub fn detect(text: &str) -> Option<Self> {
+        whatlang::detect(text)
+            .filter(|info| info.is_reliable())
+            .map(|info| Self::from_lang(info.lang()))
+    }
+
+    fn from_lang(lang: Lang) -> Self {
+        match LANGUAGES.iter().find(|(_, known, _)| *known == lang) {
+            Some((code, lang, name)) => Self {
+                lang: *lang,
+                code,
+                name,
+            },
+            None => Self {
+                lang,
+                code: lang.code(),
+                name: lang.eng_name(),
+            },
+        }
+    }
+
+    /// Two-letter code, or the ISO 639-3 code for less common languages
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// English name of the language
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Where the expected language comes from
+#[derive(Debug, Clone)]
+enum Target {
+    Fixed(Language),
+    Metadata(String),
+}
+
+/// Language and prompt of a request, kept for the check of its result
+#[derive(Debug, Clone)]
+struct LanguageCheck {
+    language: Language,
+    messages: Vec<Message>,
+}
+
+/// Output language enforcement plugin
+#[derive(Debug, Clone)]
+pub struct LanguagePlugin {
+    target: Target,
+    provider: Option<Arc<dyn Provider>>,
+    min_chars: usize,
+}
+
+impl LanguagePlugin {
+    /// Expect answers in `language`, e.g. `zh` or `en-US`
+    pub fn new(language: &str) -> Result<Self, AiError> {
+        let language = Language::parse(language)
+            .ok_or_else(|| AiError::configuration(format!("Unknown language '{}'", language)))?;
+        Ok(Self::with_target(Target::Fixed(language)))
+    }
+
+    /// Expect answers in the language of the locale in `metadata[key]`;
+    /// requests without it, or with an unknown language, are not checked
+    pub fn from_metadata(key: impl Into<String>) -> Self {
+        Self::with_target(Target::Metadata(key.into()))
+    }
+
+    fn with_target(target: Target) -> Self {
+        Self {
+            target,
+            provider: None,
+            min_chars: 20,
+        }
+    }
+
+    /// Re-prompt through `provider` once when the answer is in another
+    /// language
+    pub fn with_reprompt(mut self, provider: Arc<dyn Provider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Skip the check for answers shorter than this many characters,
+    /// excluding code (default 20)
+    pub fn with_min_chars(mut self, min_chars: usize) -> Self {
+        self.min_chars = min_chars;
+        self
+    }
+
+    fn language(&self, ctx: &RequestContext) -> Option<Language> {
+        match &self.target {
+            Target::Fixed(language) => Some(*language),
+            Target::Metadata(key) => ctx
+                .get_metadata(key)
+                .and_then(|code| Language::parse(&code)),
+        }
+    }
+
+    /// Language `content` is written in, if it can be told and differs
+    /// from `expected`
+    fn mismatch(&self, content: &str, expected: Language) -> Option<Language> {
+        let prose = strip_code(content);
+        if prose.trim().chars().count() < self.min_chars {
+            return None;
+        }
+        Language::detect(&prose).filter(|detected| detected.lang != expected.lang)
+    }
+
+    /// Ask the model to answer `messages` again in `language`
+    async fn reprompt(
+        provider: &Arc<dyn Provider>,
+        model: &str,
+        mut messages: Vec<Message>,
+        answer: &str,
+        language: Language,
+        detected: Language,
+    ) -> Result<(String, Usage), AiError> {
+        messages.push(Message::assistant(answer));
+        messages.push(Message::user(format!(
+            "Your answer was written in {}. Answer again, entirely in {}.",
+            detected.name(),
+            language.name()
+        )));
+        let response = provider
+            .chat_completion(ChatCompletionRequest::new(model, messages))
+            .await?;
+        let choice = response
+            .choices
+            .first()
+            .ok_or_else(|| AiError::provider("No choices in response"))?;
+        let content = choice
+            .message
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        Ok((content, response.usage))
+    }
+}
+
+/// `content` without fenced code blocks and inline code
+fn strip_code(content: &str) -> String {
+    let mut prose = String::with_capacity(content.len());
+    for (i, block) in content.split("```").enumerate() {
+        // Odd segments are inside fences
+        if i % 2 == 1 {
+            continue;
+        }
+        for (j, span) in block.split('`').enumerate() {
+            if j % 2 == 0 {
+                prose.push_str(span);
+            }
+        }
+    }
+    prose
+}
+
+#[async_trait]
+impl Plugin for LanguagePlugin {
+    fn name(&self) -> &str {
+        "language"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        // See the prompt after other plugins added their context
+        PluginPhase::Post
+    }
+
+    async fn transform_params(
+        &self,
+        mut params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        let Some(language) = self.language(ctx) else {
+            return Ok(params);
+        };
+        let leading_system = params
+            .messages
+            .iter()
+            .take_while(|msg| msg.role == Role::System)
+            .count();
+        params.messages.insert(
+            leading_system,
+            Message::system(format!(
+                "Always answer in {}, even when the question or the provided context is \
+                in another language. Keep code, identifiers and quotations unchanged.",
+                language.name()
+            )),
+        );
+        ctx.extensions().insert(LanguageCheck {
+            language,
+            messages: params.messages.clone(),
+        });
+        Ok(params)
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        let Some(check) = ctx.extensions().get::<LanguageCheck>() else {
+            return Ok(result);
+        };
+        let Some(detected) = self.mismatch(&result.content, check.language) else {
+            return Ok(result);
+        };
+
+        let mut resolved = false;
+        if let Some(provider) = &self.provider {
+            tracing::debug!(
+                "Answer in {} instead of {}, re-prompting",
+                detected.name(),
+                check.language.name()
+            );
+            let (content, usage) = Self::reprompt(
+                provider,
+                &ctx.model,
+                check.messages,
+                &result.content,
+                check.language,
+                detected,
+            )
+            .await?;
+            result.content = content;
+            result.usage.prompt_tokens += usage.prompt_tokens;
+            result.usage.completion_tokens += usage.completion_tokens;
+            result.usage.total_tokens += usage.total_tokens;
+            resolved = self.mismatch(&result.content, check.language).is_none();
+        }
+
+        result.metadata.insert(
+            "language".to_string(),
+            serde_json::json!({
+                "expected": check.language.code(),
+                "detected": detected.code(),
+                "reprompted": self.provider.is_some(),
+                "resolved": resolved,
+            }),
+        );
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::ChatCompletionStream;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Answers in Chinese and records the last prompt
+    #[derive(Debug, Default)]
+    struct ChineseProvider {
+        last: Mutex<Vec<Message>>,
+    }
+
+    #[async_trait]
+    impl Provider for ChineseProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "chinese".to_string(),
+                name: "Chinese".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            *self.last.lock().unwrap() = req.messages;
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(
+                        "Rust 是一门注重安全与性能的系统编程语言，使用 `cargo` 构建项目。",
+                    ),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    fn result(content: &str) -> TextResult {
+        TextResult {
+            content: content.to_string(),
+            finish_reason: FinishReason::Stop,
+            usage: Usage::default(),
+            model: "gpt-4o".to_string(),
+            tool_calls: None,
+            reasoning: None,
+            citations: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reprompts_wrong_language() {
+        let provider = Arc::new(ChineseProvider::default());
+        let plugin = LanguagePlugin::from_metadata("locale").with_reprompt(provider.clone());
+        let ctx = RequestContext::new("openai", "gpt-4o")
+            .with_metadata(HashMap::from([("locale".to_string(), "zh_CN".to_string())]));
+
+        let params = TextParams::new(vec![
+            Message::system("You are helpful."),
+            Message::user("什么是 Rust?"),
+        ]);
+        let params = plugin.transform_params(params, &ctx).await.unwrap();
+        assert_eq!(params.messages[1].role, Role::System);
+
+        // Chinese prose around English code passes as is
+        let answer =
+            "Rust 是一门系统编程语言。\n```rust\nfn main() { println!(\"hello world\"); }\n```";
+        let checked = plugin.transform_result(result(answer), &ctx).await.unwrap();
+        assert_eq!(checked.content, answer);
+        assert!(!checked.metadata.contains_key("language"));
+
+        let english = "Rust is a systems programming language focused on safety and speed.";
+        let checked = plugin
+            .transform_result(result(english), &ctx)
+            .await
+            .unwrap();
+        assert!(checked.content.starts_with("Rust 是"));
+        assert_eq!(
+            checked.metadata["language"],
+            serde_json::json!({
+                "expected": "zh",
+                "detected": "en",
+                "reprompted": true,
+                "resolved": true
+            })
+        );
+        let last = provider.last.lock().unwrap().clone();
+        assert_eq!(last.len(), 5);
+        assert_eq!(last[3].role, Role::Assistant);
+
+        // Without a locale nothing is checked
+        let ctx = RequestContext::new("openai", "gpt-4o");
+        let params = TextParams::new(vec![Message::user("Hi")]);
+        assert_eq!(
+            plugin
+                .transform_params(params, &ctx)
+                .await
+                .unwrap()
+                .messages
+                .len(),
+            1
+        );
+    }
+}
@@ -2,7 +2,50 @@
 //!
 //! Built-in plugins for AI Core.
 
+pub mod code_interpreter;
+pub mod experiment;
+pub mod guardrails;
+#[cfg(feature = "language")]
+pub mod language;
+pub mod locale;
+pub mod mcp;
+pub mod mcp_server;
+pub mod moderation;
+#[cfg(feature = "templates")]
+pub mod prompt_library;
+pub mod provenance;
+pub mod retrieval;
+pub mod summarizing_memory;
+#[cfg(feature = "templates")]
+pub mod template;
+pub mod tool_result;
 pub mod tool_use;
+pub mod trace_export;
+pub mod workspace;
 
 // Re-exports
-pub use tool_use::{FunctionTool, ToolExecutor, ToolRegistry, ToolUsePlugin};
+pub use code_interpreter::{CodeInterpreterTool, Interpreter, SandboxLimits};
+pub use experiment::{ExperimentPlugin, Variant};
+pub use guardrails::{GuardrailAction, GuardrailsPlugin, Validator};
+#[cfg(feature = "language")]
+pub use language::{Language, LanguagePlugin};
+pub use locale::{LocalePlugin, ResolvedLocale};
+pub use mcp::{McpClient, McpToolProvider};
+pub use mcp_server::McpServer;
+pub use moderation::{ModerationAction, ModerationPlugin};
+#[cfg(feature = "templates")]
+pub use prompt_library::{PromptInfo, PromptLibrary, RenderedPrompt};
+pub use provenance::{Provenance, ProvenancePlugin};
+pub use retrieval::{RetrievalPlugin, RetrievedChunk, RetrievedContext};
+pub use summarizing_memory::SummarizingMemoryPlugin;
+#[cfg(feature = "templates")]
+pub use template::{PromptTemplates, TemplatePlugin, TemplateVars};
+pub use tool_result::ToolResultLimiter;
+pub use tool_use::{
+    ApprovalChannel, ApprovalRequest, FunctionTool, PendingToolCall, ToolApproval, ToolApprover,
+    ToolErrorPolicy, ToolExecutor, ToolOptions, ToolOutcome, ToolRegistry, ToolUsePlugin,
+};
+pub use trace_export::{
+    LangSmithExporter, LangfuseExporter, Trace, TraceExportPlugin, TraceExporter,
+};
+pub use workspace::{FsTool, ShellTool, WorkspacePolicy};
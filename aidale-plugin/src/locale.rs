@@ -0,0 +1,226 @@
+//! Locale-aware prompt selection.
+//!
+//! [`LocalePlugin`] wraps a plugin that serves templates, such as a
+//! [`PromptLibrary`](crate::PromptLibrary) or a
+//! [`TemplatePlugin`](crate::TemplatePlugin), and picks the variant of a
+//! template for the locale in the request metadata. For the locale `zh-CN`,
+//! `load_template("support/answer")` tries, in order:
+//!
+//! 1. `support/answer.zh-CN` (in a library: `support/answer.zh-CN.md`)
+//! 2. `support/answer.zh`
+//! 3. `support/answer.<fallback>` for each configured fallback locale
+//! 4. `support/answer`
+//!
+//! ```ignore
+//! let library = Arc::new(PromptLibrary::load("prompts")?);
+//! let executor = RuntimeExecutor::builder(provider)
+//!     .plugin(Arc::new(LocalePlugin::new(library.clone()).with_fallback("en")))
+//!     .plugin(library)
+//!     .finish();
+//!
+//! let ctx = RequestContext::new("openai", "gpt-4o")
+//!     .with_metadata(HashMap::from([("locale".to_string(), "zh_CN".to_string())]));
+//! let messages = executor.plugin_engine().load_template("support/answer", &ctx).await?;
+//! ```
+//!
+//! The locale that was used is recorded on the result as
+//! `metadata["locale"]`.
+
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Default metadata key holding the request's locale
+pub const LOCALE_METADATA_KEY: &str = "locale";
+
+/// Locale whose template variant was used, stored in the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedLocale(pub String);
+
+/// Resolves templates per locale with a fallback chain
+#[derive(Debug)]
+pub struct LocalePlugin {
+    source: Arc<dyn Plugin>,
+    metadata_key: String,
+    fallbacks: Vec<String>,
+}
+
+impl LocalePlugin {
+    /// Resolve templates served by `source`
+    pub fn new(source: Arc<dyn Plugin>) -> Self {
+        Self {
+            source,
+            metadata_key: LOCALE_METADATA_KEY.to_string(),
+            fallbacks: Vec::new(),
+        }
+    }
+
+    /// Read the locale from another metadata key (default `locale`)
+    pub fn with_metadata_key(mut self, key: impl Into<String>) -> Self {
+        self.metadata_key = key.into();
+        self
+    }
+
+    /// Add a locale to try when the request's locale has no variant,
+    /// before the unsuffixed template
+    pub fn with_fallback(mut self, locale: impl Into<String>) -> Self {
+        self.fallbacks.push(normalize(&locale.into()));
+        self
+    }
+
+    /// Locales to try for `locale`, most specific first
+    fn chain(&self, locale: Option<&str>) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        if let Some(locale) = locale.map(normalize).filter(|l| !l.is_empty()) {
+            let mut tags: Vec<&str> = locale.split('-').collect();
+            while !tags.is_empty() {
+                chain.push(tags.join("-"));
+                tags.pop();
+            }
+        }
+        for fallback in &self.fallbacks {
+            if !chain.contains(fallback) {
+                chain.push(fallback.clone());
+            }
+        }
+        chain
+    }
+}
+
+/// `zh_cn` → `zh-CN`: underscores become hyphens, the language is
+/// lowercased and two-letter regions uppercased
+fn normalize(locale: &str) -> String {
+    locale
+        .trim()
+        .split(['-', '_'])
+        .filter(|tag| !tag.is_empty())
+        .enumerate()
+        .map(|(i, tag)| match (i, tag.len()) {
+            (0, _) => tag.to_ascii_lowercase(),
+            (_, 2) => tag.to_ascii_uppercase(),
+            _ => tag.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[async_trait]
+impl Plugin for LocalePlugin {
+    fn name(&self) -> &str {
+        "locale"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        // Must answer load_template before the source plugin does
+        PluginPhase::Pre
+    }
+
+    async fn load_template(
+        &self,
+        template_name: &str,
+        ctx: &RequestContext,
+    ) -> Result<Option<Vec<Message>>, AiError> {
+        let locale = ctx.get_metadata(&self.metadata_key);
+        for candidate in self.chain(locale.as_deref()) {
+            let name = format!("{}.{}", template_name, candidate);
+            if let Some(messages) = self.source.load_template(&name, ctx).await? {
+                tracing::debug!("Resolved template '{}' as '{}'", template_name, name);
+                ctx.extensions().insert(ResolvedLocale(candidate));
+                return Ok(Some(messages));
+            }
+        }
+        self.source.load_template(template_name, ctx).await
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        if let Some(ResolvedLocale(locale)) = ctx.extensions().get::<ResolvedLocale>() {
+            result
+                .metadata
+                .insert("locale".to_string(), serde_json::Value::String(locale));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Serves fixed templates by name
+    #[derive(Debug)]
+    struct Templates(Vec<&'static str>);
+
+    #[async_trait]
+    impl Plugin for Templates {
+        fn name(&self) -> &str {
+            "templates"
+        }
+
+        async fn load_template(
+            &self,
+            template_name: &str,
+            _ctx: &RequestContext,
+        ) -> Result<Option<Vec<Message>>, AiError> {
+            Ok(self
+                .0
+                .contains(&template_name)
+                .then(|| vec![Message::user(template_name)]))
+        }
+    }
+
+    fn ctx(locale: &str) -> RequestContext {
+        RequestContext::new("openai", "gpt-4o").with_metadata(HashMap::from([(
+            LOCALE_METADATA_KEY.to_string(),
+            locale.to_string(),
+        )]))
+    }
+
+    async fn resolve(plugin: &LocalePlugin, locale: &str) -> Option<String> {
+        let messages = plugin
+            .load_template("answer", &ctx(locale))
+            .await
+            .unwrap()?;
+        match &messages[0].content[0] {
+            ContentPart::Text { text } => Some(text.clone()),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_locale_fallback() {
+        let source = Arc::new(Templates(vec![
+            "answer",
+            "answer.zh",
+            "answer.zh-TW",
+            "answer.en",
+        ]));
+        let plugin = LocalePlugin::new(source).with_fallback("en");
+
+        assert_eq!(resolve(&plugin, "zh_tw").await.unwrap(), "answer.zh-TW");
+        assert_eq!(resolve(&plugin, "zh-CN").await.unwrap(), "answer.zh");
+        assert_eq!(resolve(&plugin, "fr-FR").await.unwrap(), "answer.en");
+        assert_eq!(
+            LocalePlugin::new(Arc::new(Templates(vec!["answer"])))
+                .load_template("answer", &ctx("de"))
+                .await
+                .unwrap()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let ctx = ctx("zh-CN");
+        plugin.load_template("answer", &ctx).await.unwrap();
+        assert_eq!(
+            ctx.extensions().get::<ResolvedLocale>(),
+            Some(ResolvedLocale("zh".to_string()))
+        );
+    }
+}
@@ -0,0 +1,583 @@
+//! Model Context Protocol (MCP) client.
+//!
+//! [`McpToolProvider`] connects to an MCP server, lists its tools and
+//! registers them in a [`ToolRegistry`]; executing a registered tool sends
+//! a `tools/call` request to the server. Servers are reached over stdio (a
+//! child process speaking newline-delimited JSON-RPC) or over HTTP with
+//! server-sent events:
+//!
+//! ```ignore
+//! let mut command = tokio::process::Command::new("npx");
+//! command.args(["-y", "@modelcontextprotocol/server-filesystem", "/tmp"]);
+//! let filesystem = McpToolProvider::stdio(command).await?;
+//!
+//! let search = McpToolProvider::sse("http://localhost:3001/sse")
+//!     .await?
+//!     .with_prefix("search_");
+//!
+//! let mut registry = ToolRegistry::new();
+//! filesystem.register(&mut registry).await?;
+//! search.register(&mut registry).await?;
+//! let agent = Agent::builder(executor, "gpt-4o").tools(registry).build();
+//! ```
+//!
+//! The child process of a stdio server is killed when the last handle to
+//! its client is dropped.
+
+use crate::tool_use::{ToolExecutor, ToolRegistry};
+use aidale_core::error::AiError;
+use aidale_core::types::Tool;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+
+/// MCP protocol revision sent in the `initialize` request
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const PLUGIN_NAME: &str = "mcp";
+
+fn mcp_error(message: impl Into<String>) -> AiError {
+    AiError::plugin(PLUGIN_NAME, message)
+}
+
+/// Outgoing half of a transport
+#[async_trait]
+trait Outbox: Send + Sync {
+    async fn send(&self, message: &Value) -> Result<(), AiError>;
+}
+
+/// Writes messages as lines to a child process
+struct StdioOutbox {
+    stdin: tokio::sync::Mutex<ChildStdin>,
+}
+
+#[async_trait]
+impl Outbox for StdioOutbox {
+    async fn send(&self, message: &Value) -> Result<(), AiError> {
+        let mut line = message.to_string();
+        line.push('\n');
+        let mut stdin = self.stdin.lock().await;
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| mcp_error(format!("Failed to write to server: {}", e)))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| mcp_error(format!("Failed to write to server: {}", e)))
+    }
+}
+
+/// Posts messages to the endpoint announced on the event stream
+struct SseOutbox {
+    client: reqwest::Client,
+    endpoint: reqwest::Url,
+}
+
+#[async_trait]
+impl Outbox for SseOutbox {
+    async fn send(&self, message: &Value) -> Result<(), AiError> {
+        let response = self
+            .client
+            .post(self.endpoint.clone())
+            .json(message)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(mcp_error(format!(
+                "Server rejected message with status {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, AiError>>>>>;
+
+/// Client of one MCP server
+pub struct McpClient {
+    outbox: Arc<dyn Outbox>,
+    pending: Pending,
+    next_id: AtomicU64,
+    timeout: Duration,
+    server_info: Value,
+    // Kept to kill the server process on drop
+    _child: Option<Child>,
+}
+
+impl McpClient {
+    /// Spawn a server process and talk to it over stdin/stdout
+    ///
+    /// The server's stderr is inherited.
+    pub async fn stdio(mut command: Command) -> Result<Self, AiError> {
+        let mut child = command
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| mcp_error(format!("Failed to start server: {}", e)))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let lines = tokio_stream::wrappers::LinesStream::new(BufReader::new(stdout).lines());
+        let incoming = lines.filter_map(|line| async move {
+            let line = line.ok()?;
+            match serde_json::from_str(&line) {
+                Ok(message) => Some(message),
+                Err(_) => {
+                    tracing::debug!("Ignoring non-JSON output of MCP server: {}", line);
+                    None
+                }
+            }
+        });
+        let outbox = StdioOutbox {
+            stdin: tokio::sync::Mutex::new(stdin),
+        };
+        let mut client = Self::connect(Arc::new(outbox), incoming);
+        client._child = Some(child);
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Spawn `program` with `args`; see [`Self::stdio`]
+    pub async fn spawn(
+        program: impl AsRef<OsStr>,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> Result<Self, AiError> {
+        let mut command = Command::new(program);
+        command.args(args);
+        Self::stdio(command).await
+    }
+
+    /// Connect to a server over HTTP with server-sent events
+    pub async fn sse(url: &str) -> Result<Self, AiError> {
+        Self::sse_with_client(url, reqwest::Client::new()).await
+    }
+
+    /// Connect over HTTP with a preconfigured client, e.g. one sending
+    /// authentication headers
+    pub async fn sse_with_client(url: &str, client: reqwest::Client) -> Result<Self, AiError> {
+        use eventsource_stream::Eventsource;
+
+        let base = reqwest::Url::parse(url)
+            .map_err(|e| AiError::invalid_request(format!("Invalid MCP server URL: {}", e)))?;
+        let response = client
+            .get(base.clone())
+            .header("accept", "text/event-stream")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(mcp_error(format!(
+                "Server returned status {}",
+                response.status()
+            )));
+        }
+        let mut events = response.bytes_stream().eventsource();
+
+        // The first event announces where to post messages
+        let endpoint = loop {
+            match events.next().await {
+                Some(Ok(event)) if event.event == "endpoint" => {
+                    break base.join(event.data.trim()).map_err(|e| {
+                        mcp_error(format!("Invalid message endpoint {}: {}", event.data, e))
+                    })?;
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(mcp_error(format!("Event stream error: {}", e))),
+                None => return Err(mcp_error("Event stream closed before the endpoint event")),
+            }
+        };
+
+        let incoming = events.filter_map(|event| async move {
+            match event {
+                Ok(event) if event.event == "message" || event.event.is_empty() => {
+                    serde_json::from_str(&event.data).ok()
+                }
+                _ => None,
+            }
+        });
+        let outbox = SseOutbox { client, endpoint };
+        let mut client = Self::connect(Arc::new(outbox), incoming);
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Start dispatching incoming messages
+    fn connect(
+        outbox: Arc<dyn Outbox>,
+        incoming: impl Stream<Item = Value> + Send + 'static,
+    ) -> Self {
+        let pending: Pending = Arc::default();
+        tokio::spawn(dispatch(incoming, outbox.clone(), pending.clone()));
+        Self {
+            outbox,
+            pending,
+            next_id: AtomicU64::new(1),
+            timeout: Duration::from_secs(60),
+            server_info: Value::Null,
+            _child: None,
+        }
+    }
+
+    /// Time to wait for a response (default: 60 seconds)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// `serverInfo` reported by the server during initialization
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    async fn initialize(&mut self) -> Result<(), AiError> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "aidale", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await?;
+        self.server_info = result["serverInfo"].clone();
+        self.outbox
+            .send(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await
+    }
+
+    /// Send a JSON-RPC request and wait for its result
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, AiError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        if let Err(err) = self.outbox.send(&message).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(err);
+        }
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(mcp_error("Connection to server closed")),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(AiError::timeout(format!(
+                    "MCP server did not answer {} within {:?}",
+                    method, self.timeout
+                )))
+            }
+        }
+    }
+
+    /// List the server's tools, following pagination
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, AiError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            for tool in result["tools"].as_array().into_iter().flatten() {
+                let name = tool["name"]
+                    .as_str()
+                    .ok_or_else(|| mcp_error("Tool without a name"))?;
+                let parameters = match &tool["inputSchema"] {
+                    Value::Null => json!({"type": "object", "properties": {}}),
+                    schema => schema.clone(),
+                };
+                tools.push(Tool::new(
+                    name,
+                    tool["description"].as_str().unwrap_or_default(),
+                    parameters,
+                ));
+            }
+            cursor = result["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Call a tool
+    ///
+    /// Returns the structured content when the server provides it, the text
+    /// when the result is text only, and the content array otherwise. A
+    /// result flagged `isError` is returned as an error.
+    pub async fn call_tool(&self, name: &str, arguments: &Value) -> Result<Value, AiError> {
+        let arguments = match arguments {
+            Value::Null => json!({}),
+            arguments => arguments.clone(),
+        };
+        let mut result = self
+            .request("tools/call", json!({"name": name, "arguments": arguments}))
+            .await?;
+
+        let content = result["content"].take();
+        let parts = content.as_array().map(Vec::as_slice).unwrap_or_default();
+        let text: Option<Vec<&str>> = parts
+            .iter()
+            .map(|part| match part["type"].as_str() {
+                Some("text") => part["text"].as_str(),
+                _ => None,
+            })
+            .collect();
+        let text = text.map(|text| text.join("\n"));
+
+        if result["isError"].as_bool() == Some(true) {
+            return Err(mcp_error(format!(
+                "Tool {} failed: {}",
+                name,
+                text.unwrap_or_else(|| content.to_string())
+            )));
+        }
+        Ok(match (result["structuredContent"].take(), text) {
+            (Value::Null, Some(text)) => Value::String(text),
+            (Value::Null, None) => content,
+            (structured, _) => structured,
+        })
+    }
+}
+
+impl fmt::Debug for McpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("McpClient")
+            .field("server_info", &self.server_info)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Route responses to their requests and answer server requests
+async fn dispatch(
+    incoming: impl Stream<Item = Value> + Send + 'static,
+    outbox: Arc<dyn Outbox>,
+    pending: Pending,
+) {
+    futures::pin_mut!(incoming);
+    while let Some(message) = incoming.next().await {
+        match (&message["id"], message["method"].as_str()) {
+            // Response to one of our requests
+            (Value::Number(id), None) => {
+                let Some(tx) = id
+                    .as_u64()
+                    .and_then(|id| pending.lock().unwrap().remove(&id))
+                else {
+                    continue;
+                };
+                let result = match &message["error"] {
+                    Value::Null => Ok(message["result"].clone()),
+                    error => Err(mcp_error(format!(
+                        "{} (code {})",
+                        error["message"].as_str().unwrap_or("Unknown error"),
+                        error["code"]
+                    ))),
+                };
+                let _ = tx.send(result);
+            }
+            // Request from the server; only pings are supported
+            (id, Some(method)) if !id.is_null() => {
+                let response = match method {
+                    "ping" => json!({"jsonrpc": "2.0", "id": id, "result": {}}),
+                    _ => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {"code": -32601, "message": format!("Method {} not supported", method)},
+                    }),
+                };
+                if let Err(err) = outbox.send(&response).await {
+                    tracing::warn!("Failed to answer MCP server request: {}", err);
+                }
+            }
+            (_, Some(method)) => tracing::debug!("MCP notification: {}", method),
+            _ => {}
+        }
+    }
+    // Fail the requests still waiting
+    pending.lock().unwrap().clear();
+}
+
+/// A server tool registered in a [`ToolRegistry`]
+struct McpTool {
+    client: Arc<McpClient>,
+    remote_name: String,
+    definition: Tool,
+}
+
+#[async_trait]
+impl ToolExecutor for McpTool {
+    async fn execute(&self, _name: &str, arguments: &Value) -> Result<Value, AiError> {
+        self.client.call_tool(&self.remote_name, arguments).await
+    }
+
+    fn definition(&self) -> Option<Tool> {
+        Some(self.definition.clone())
+    }
+}
+
+/// Tools of an MCP server, ready to register in a [`ToolRegistry`]
+#[derive(Debug, Clone)]
+pub struct McpToolProvider {
+    client: Arc<McpClient>,
+    prefix: String,
+}
+
+impl McpToolProvider {
+    /// Use a connected client
+    pub fn new(client: Arc<McpClient>) -> Self {
+        Self {
+            client,
+            prefix: String::new(),
+        }
+    }
+
+    /// Spawn a server process; see [`McpClient::stdio`]
+    pub async fn stdio(command: Command) -> Result<Self, AiError> {
+        Ok(Self::new(Arc::new(McpClient::stdio(command).await?)))
+    }
+
+    /// Connect to a server over HTTP with server-sent events
+    pub async fn sse(url: &str) -> Result<Self, AiError> {
+        Ok(Self::new(Arc::new(McpClient::sse(url).await?)))
+    }
+
+    /// Prefix the tool names, to keep tools of several servers apart
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The underlying client
+    pub fn client(&self) -> &Arc<McpClient> {
+        &self.client
+    }
+
+    /// Definitions of the server's tools, with prefixed names
+    pub async fn tools(&self) -> Result<Vec<Tool>, AiError> {
+        let mut tools = self.client.list_tools().await?;
+        for tool in &mut tools {
+            tool.name = format!("{}{}", self.prefix, tool.name);
+        }
+        Ok(tools)
+    }
+
+    /// Register every tool of the server, returning their number
+    pub async fn register(&self, registry: &mut ToolRegistry) -> Result<usize, AiError> {
+        let tools = self.client.list_tools().await?;
+        let count = tools.len();
+        for definition in tools {
+            let remote_name = definition.name.clone();
+            let name = format!("{}{}", self.prefix, remote_name);
+            let definition = Tool {
+                name: name.clone(),
+                ..definition
+            };
+            let tool = McpTool {
+                client: self.client.clone(),
+                remote_name,
+                definition,
+            };
+            registry.register(name, Arc::new(tool));
+        }
+        tracing::debug!("Registered {} MCP tool(s)", count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    /// Forwards client messages to an in-process fake server
+    struct ChannelOutbox(mpsc::UnboundedSender<Value>);
+
+    #[async_trait]
+    impl Outbox for ChannelOutbox {
+        async fn send(&self, message: &Value) -> Result<(), AiError> {
+            self.0
+                .send(message.clone())
+                .map_err(|_| mcp_error("closed"))
+        }
+    }
+
+    /// Serves an `add` tool and pings the client once initialized
+    async fn fake_server(
+        mut requests: mpsc::UnboundedReceiver<Value>,
+        responses: mpsc::UnboundedSender<Value>,
+    ) {
+        while let Some(msg) = requests.recv().await {
+            let id = msg["id"].clone();
+            let result = match msg["method"].as_str() {
+                Some("initialize") => json!({"serverInfo": {"name": "fake"}}),
+                Some("notifications/initialized") => {
+                    responses
+                        .send(json!({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"}))
+                        .unwrap();
+                    continue;
+                }
+                Some("tools/list") if msg["params"]["cursor"].is_null() => json!({
+                    "tools": [{"name": "add", "inputSchema": {"type": "object"}}],
+                    "nextCursor": "2",
+                }),
+                Some("tools/list") => json!({"tools": [{"name": "fail"}]}),
+                Some("tools/call") if msg["params"]["name"] == "add" => {
+                    let args = &msg["params"]["arguments"];
+                    let sum = args["a"].as_i64().unwrap() + args["b"].as_i64().unwrap();
+                    json!({"content": [{"type": "text", "text": sum.to_string()}]})
+                }
+                Some("tools/call") => {
+                    json!({"isError": true, "content": [{"type": "text", "text": "boom"}]})
+                }
+                // The client's answer to our ping
+                None => {
+                    assert_eq!(msg, json!({"jsonrpc": "2.0", "id": "srv-1", "result": {}}));
+                    continue;
+                }
+                Some(_) => unreachable!(),
+            };
+            responses
+                .send(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mcp_tools() {
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        let (response_tx, response_rx) = mpsc::unbounded_channel();
+        tokio::spawn(fake_server(request_rx, response_tx));
+        let incoming = tokio_stream::wrappers::UnboundedReceiverStream::new(response_rx);
+        let mut client = McpClient::connect(Arc::new(ChannelOutbox(request_tx)), incoming);
+        client.initialize().await.unwrap();
+        assert_eq!(client.server_info()["name"], "fake");
+
+        let provider = McpToolProvider::new(Arc::new(client)).with_prefix("calc_");
+        let mut registry = ToolRegistry::new();
+        assert_eq!(provider.register(&mut registry).await.unwrap(), 2);
+        let mut names: Vec<_> = registry.definitions().into_iter().map(|t| t.name).collect();
+        names.sort();
+        assert_eq!(names, ["calc_add", "calc_fail"]);
+
+        let sum = registry
+            .execute("calc_add", &json!({"a": 2, "b": 3}))
+            .await
+            .unwrap();
+        assert_eq!(sum, json!("5"));
+        let err = registry.execute("calc_fail", &json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("boom"));
+    }
+}
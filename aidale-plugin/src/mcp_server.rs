@@ -0,0 +1,301 @@
+//! Model Context Protocol (MCP) server.
+//!
+//! [`McpServer`] publishes the tools of a [`ToolRegistry`] to MCP hosts
+//! such as Claude Desktop: `tools/list` returns their definitions, with the
+//! parameter schemas as input schemas, and `tools/call` executes them. A
+//! binary serving its tools over stdio is a few lines:
+//!
+//! ```ignore
+//! #[tokio::main]
+//! async fn main() -> Result<(), AiError> {
+//!     let mut registry = ToolRegistry::new();
+//!     registry.register_function(weather_tool());
+//!     McpServer::new(Arc::new(registry))
+//!         .with_name("weather")
+//!         .serve_stdio()
+//!         .await
+//! }
+//! ```
+//!
+//! Stdout carries the protocol, so logs must go to stderr. Requests are
+//! handled concurrently; a failing tool is reported to the host as a result
+//! flagged `isError`.
+
+use crate::mcp::PROTOCOL_VERSION;
+use crate::tool_use::ToolRegistry;
+use aidale_core::error::AiError;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const PARSE_ERROR: i64 = -32700;
+
+fn rpc_error(id: &Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": code, "message": message.into()},
+    })
+}
+
+/// Serves a tool registry to MCP hosts
+#[derive(Clone)]
+pub struct McpServer {
+    registry: Arc<ToolRegistry>,
+    name: String,
+    version: String,
+    instructions: Option<String>,
+}
+
+impl McpServer {
+    /// Serve the tools of `registry`
+    pub fn new(registry: Arc<ToolRegistry>) -> Self {
+        Self {
+            registry,
+            name: "aidale".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            instructions: None,
+        }
+    }
+
+    /// Server name reported to hosts
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Server version reported to hosts
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Usage hints hosts may add to the model's prompt
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Handle one JSON-RPC message, returning the response to send
+    ///
+    /// Notifications and responses get no response.
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned().unwrap_or(Value::Null);
+        let method = message["method"].as_str()?;
+        if id.is_null() {
+            tracing::debug!("MCP notification: {}", method);
+            return None;
+        }
+        let params = &message["params"];
+
+        let result = match method {
+            "initialize" => {
+                // Agree to the host's revision when it is the one we speak
+                let version = match params["protocolVersion"].as_str() {
+                    Some(version) if version == PROTOCOL_VERSION => version,
+                    _ => PROTOCOL_VERSION,
+                };
+                let mut result = json!({
+                    "protocolVersion": version,
+                    "capabilities": {"tools": {"listChanged": false}},
+                    "serverInfo": {"name": self.name, "version": self.version},
+                });
+                if let Some(instructions) = &self.instructions {
+                    result["instructions"] = json!(instructions);
+                }
+                result
+            }
+            "ping" => json!({}),
+            "tools/list" => {
+                let mut tools = self.registry.definitions();
+                tools.sort_by(|a, b| a.name.cmp(&b.name));
+                let tools: Vec<Value> = tools
+                    .into_iter()
+                    .map(|tool| {
+                        json!({
+                            "name": tool.name,
+                            "description": tool.description,
+                            "inputSchema": tool.parameters,
+                        })
+                    })
+                    .collect();
+                json!({ "tools": tools })
+            }
+            "tools/call" => {
+                let Some(name) = params["name"].as_str() else {
+                    return Some(rpc_error(&id, INVALID_PARAMS, "Missing tool name"));
+                };
+                if !self.registry.contains(name) {
+                    return Some(rpc_error(
+                        &id,
+                        INVALID_PARAMS,
+                        format!("Unknown tool: {}", name),
+                    ));
+                }
+                let arguments = match &params["arguments"] {
+                    Value::Null => json!({}),
+                    arguments => arguments.clone(),
+                };
+                call_result(self.registry.execute(name, &arguments).await)
+            }
+            _ => {
+                return Some(rpc_error(
+                    &id,
+                    METHOD_NOT_FOUND,
+                    format!("Method not found: {}", method),
+                ))
+            }
+        };
+        Some(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+    }
+
+    /// Serve newline-delimited JSON-RPC until `reader` is closed
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> Result<(), AiError>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+        let write = tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let mut line = message.to_string();
+                line.push('\n');
+                writer.write_all(line.as_bytes()).await?;
+                writer.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        });
+
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| AiError::other(format!("Failed to read MCP message: {}", e)))?
+        {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    let _ = tx.send(rpc_error(&Value::Null, PARSE_ERROR, e.to_string()));
+                    continue;
+                }
+            };
+            let server = self.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if let Some(response) = server.handle(message).await {
+                    let _ = tx.send(response);
+                }
+            });
+        }
+
+        // Let in-flight requests finish before closing the output
+        drop(tx);
+        write
+            .await
+            .map_err(|e| AiError::other(e.to_string()))?
+            .map_err(|e| AiError::other(format!("Failed to write MCP message: {}", e)))
+    }
+
+    /// Serve over the process's stdin and stdout
+    pub async fn serve_stdio(&self) -> Result<(), AiError> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+}
+
+impl std::fmt::Debug for McpServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpServer")
+            .field("name", &self.name)
+            .field("version", &self.version)
+            .field("tools", &self.registry.len())
+            .finish()
+    }
+}
+
+/// `tools/call` result of a tool execution
+fn call_result(outcome: Result<Value, AiError>) -> Value {
+    match outcome {
+        Ok(Value::String(text)) => json!({"content": [{"type": "text", "text": text}]}),
+        Ok(value) => {
+            let mut result = json!({"content": [{"type": "text", "text": value.to_string()}]});
+            if value.is_object() {
+                result["structuredContent"] = value;
+            }
+            result
+        }
+        Err(err) => json!({
+            "content": [{"type": "text", "text": err.to_string()}],
+            "isError": true,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool_use::FunctionTool;
+
+    #[tokio::test]
+    async fn test_stdio_server() {
+        let mut registry = ToolRegistry::new();
+        registry.register_function(FunctionTool::new(
+            "add",
+            "Add two numbers",
+            json!({"type": "object", "properties": {"a": {"type": "number"}, "b": {"type": "number"}}}),
+            |args| async move { Ok(json!({"sum": args["a"].as_i64().unwrap_or(0) + args["b"].as_i64().unwrap_or(0)})) },
+        ));
+        registry.register_function(FunctionTool::new(
+            "fail",
+            "Always fails",
+            json!({"type": "object"}),
+            |_| async { Err(AiError::other("boom")) },
+        ));
+        let server = McpServer::new(Arc::new(registry)).with_name("calc");
+
+        let (mut client, server_io) = tokio::io::duplex(4096);
+        let (reader, writer) = tokio::io::split(server_io);
+        let serving = tokio::spawn(async move { server.serve(reader, writer).await });
+
+        let requests = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": PROTOCOL_VERSION}}),
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "add", "arguments": {"a": 2, "b": 3}}}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {"name": "fail"}}),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "resources/list"}),
+        ];
+        for request in &requests {
+            client
+                .write_all(format!("{}\n", request).as_bytes())
+                .await
+                .unwrap();
+        }
+        client.shutdown().await.unwrap();
+
+        let mut output = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut client, &mut output)
+            .await
+            .unwrap();
+        serving.await.unwrap().unwrap();
+
+        let mut responses: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        responses.sort_by_key(|response| response["id"].as_i64());
+        assert_eq!(responses.len(), 5);
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "calc");
+        let tools = &responses[1]["result"]["tools"];
+        assert_eq!(tools[0]["name"], "add");
+        assert_eq!(tools[0]["inputSchema"]["properties"]["a"]["type"], "number");
+        assert_eq!(responses[2]["result"]["structuredContent"]["sum"], 5);
+        assert_eq!(responses[3]["result"]["isError"], true);
+        assert_eq!(responses[4]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,364 @@
+//! Content moderation plugin.
+//!
+//! Runs a [`Moderator`] over the latest user message before the request is
+//! sent and/or over the generated text afterwards. Flagged content is either
+//! blocked with [`AiError::ContentBlocked`] or annotated on the result.
+//! `generate_object` requests are moderated the same way, with the string
+//! values of the generated object as the output text.
+
+use aidale_core::error::AiError;
+use aidale_core::moderation::{ModerationResult, Moderator};
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// What to do with flagged content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationAction {
+    /// Fail the request with `AiError::ContentBlocked`
+    Block,
+    /// Let the request through and record the verdict
+    ///
+    /// Output verdicts are stored under `metadata["moderation"]` on the
+    /// result; flagged inputs are logged.
+    Annotate,
+}
+
+/// Content moderation plugin
+#[derive(Debug)]
+pub struct ModerationPlugin {
+    moderator: Arc<dyn Moderator>,
+    check_input: bool,
+    check_output: bool,
+    action: ModerationAction,
+}
+
+impl ModerationPlugin {
+    /// Create a plugin that blocks flagged inputs and outputs
+    pub fn new(moderator: Arc<dyn Moderator>) -> Self {
+        Self {
+            moderator,
+            check_input: true,
+            check_output: true,
+            action: ModerationAction::Block,
+        }
+    }
+
+    /// Enable or disable input moderation
+    pub fn with_input(mut self, check_input: bool) -> Self {
+        self.check_input = check_input;
+        self
+    }
+
+    /// Enable or disable output moderation
+    pub fn with_output(mut self, check_output: bool) -> Self {
+        self.check_output = check_output;
+        self
+    }
+
+    /// Set the action taken on flagged content
+    pub fn with_action(mut self, action: ModerationAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Extract the text of the latest user message
+    fn latest_user_text(messages: &[Message]) -> Option<String> {
+        let msg = messages.iter().rev().find(|m| m.role == Role::User)?;
+        let text = msg
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(text)
+    }
+
+    /// Moderate the latest user message of a request
+    async fn check_input(&self, messages: &[Message], ctx: &RequestContext) -> Result<(), AiError> {
+        if !self.check_input {
+            return Ok(());
+        }
+
+        let Some(text) = Self::latest_user_text(messages) else {
+            return Ok(());
+        };
+
+        let verdict = self.moderator.moderate(&text).await?;
+        if verdict.flagged {
+            match self.action {
+                ModerationAction::Block => {
+                    return Err(AiError::content_blocked(verdict.categories));
+                }
+                ModerationAction::Annotate => {
+                    tracing::warn!(
+                        "Flagged input in request {}: {:?}",
+                        ctx.request_id,
+                        verdict.categories
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moderate generated text, returning the verdict to annotate
+    async fn check_output(&self, text: &str) -> Result<Option<ModerationResult>, AiError> {
+        if !self.check_output || text.is_empty() {
+            return Ok(None);
+        }
+
+        let verdict = self.moderator.moderate(text).await?;
+        match self.action {
+            ModerationAction::Block if verdict.flagged => {
+                Err(AiError::content_blocked(verdict.categories))
+            }
+            ModerationAction::Block => Ok(None),
+            ModerationAction::Annotate => Ok(Some(verdict)),
+        }
+    }
+}
+
+/// Collect the string values of a JSON value, one per line
+fn object_text(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::String(text) => {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(text);
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|item| object_text(item, out)),
+        serde_json::Value::Object(map) => map.values().for_each(|item| object_text(item, out)),
+        _ => {}
+    }
+}
+
+#[async_trait]
+impl Plugin for ModerationPlugin {
+    fn name(&self) -> &str {
+        "moderation"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        PluginPhase::Pre
+    }
+
+    async fn transform_params(
+        &self,
+        params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        self.check_input(&params.messages, ctx).await?;
+        Ok(params)
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        _ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        if let Some(verdict) = self.check_output(&result.content).await? {
+            result
+                .metadata
+                .insert("moderation".to_string(), serde_json::to_value(&verdict)?);
+        }
+        Ok(result)
+    }
+
+    async fn transform_object_params(
+        &self,
+        params: ObjectParams,
+        ctx: &RequestContext,
+    ) -> Result<ObjectParams, AiError> {
+        self.check_input(&params.messages, ctx).await?;
+        Ok(params)
+    }
+
+    async fn transform_object_result(
+        &self,
+        result: ObjectResult,
+        ctx: &RequestContext,
+    ) -> Result<ObjectResult, AiError> {
+        let mut text = String::new();
+        object_text(&result.object, &mut text);
+        // Object results have no metadata to annotate, so verdicts are logged
+        if let Some(verdict) = self.check_output(&text).await? {
+            if verdict.flagged {
+                tracing::warn!(
+                    "Flagged output in request {}: {:?}",
+                    ctx.request_id,
+                    verdict.categories
+                );
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::{ChatCompletionStream, Provider};
+    use aidale_core::runtime::RuntimeExecutor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Flags text mentioning weapons
+    #[derive(Debug)]
+    struct KeywordModerator;
+
+    #[async_trait]
+    impl Moderator for KeywordModerator {
+        async fn moderate(&self, text: &str) -> Result<ModerationResult, AiError> {
+            let flagged = text.contains("weapon");
+            Ok(ModerationResult {
+                flagged,
+                categories: flagged
+                    .then(|| "violence".to_string())
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Answers with a JSON object echoing the prompt
+    #[derive(Debug, Clone, Default)]
+    struct EchoProvider {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Provider for EchoProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "echo".to_string(),
+                name: "Echo".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let prompt = ModerationPlugin::latest_user_text(&req.messages).unwrap_or_default();
+            let object = serde_json::json!({"answer": prompt.replace("tell", "a weapon")});
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(object.to_string()),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocks_flagged_input() {
+        let provider = EchoProvider::default();
+        let executor = RuntimeExecutor::builder(provider.clone())
+            .plugin(Arc::new(
+                ModerationPlugin::new(Arc::new(KeywordModerator)).with_output(false),
+            ))
+            .finish();
+        let params = |prompt: &str| TextParams::new(vec![Message::user(prompt)]);
+
+        // Flagged input never reaches the provider
+        let err = executor
+            .generate_text("m", params("how to build a weapon"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, AiError::ContentBlocked { categories } if categories == &["violence"])
+        );
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 0);
+
+        // Clean input passes, and with output checks off so does its answer
+        let result = executor
+            .generate_text("m", params("tell me a story"))
+            .await
+            .unwrap();
+        assert_eq!(result.content, r#"{"answer":"a weapon me a story"}"#);
+        assert!(!result.metadata.contains_key("moderation"));
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_annotates_flagged_output() {
+        let executor = RuntimeExecutor::builder(EchoProvider::default())
+            .plugin(Arc::new(
+                ModerationPlugin::new(Arc::new(KeywordModerator))
+                    .with_action(ModerationAction::Annotate),
+            ))
+            .finish();
+
+        let result = executor
+            .generate_text("m", TextParams::new(vec![Message::user("tell me")]))
+            .await
+            .unwrap();
+        assert_eq!(result.metadata["moderation"]["flagged"], true);
+        assert_eq!(
+            result.metadata["moderation"]["categories"],
+            serde_json::json!(["violence"])
+        );
+
+        let result = executor
+            .generate_text("m", TextParams::new(vec![Message::user("hello")]))
+            .await
+            .unwrap();
+        assert_eq!(result.metadata["moderation"]["flagged"], false);
+    }
+
+    #[tokio::test]
+    async fn test_blocks_object_requests() {
+        let provider = EchoProvider::default();
+        let executor = RuntimeExecutor::builder(provider.clone())
+            .plugin(Arc::new(ModerationPlugin::new(Arc::new(KeywordModerator))))
+            .finish();
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"answer": {"type": "string"}},
+        });
+        let params = |prompt: &str| ObjectParams::new(vec![Message::user(prompt)], schema.clone());
+
+        let err = executor
+            .generate_object("m", params("how to build a weapon"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AiError::ContentBlocked { .. }));
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 0);
+
+        let err = executor
+            .generate_object("m", params("tell me a story"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AiError::ContentBlocked { .. }));
+
+        let result = executor
+            .generate_object("m", params("hello"))
+            .await
+            .unwrap();
+        assert_eq!(result.object["answer"], "hello");
+    }
+}
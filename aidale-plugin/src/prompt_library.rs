@@ -0,0 +1,514 @@
+//! Prompt library loaded from a directory.
+//!
+//! Every `.jinja`, `.j2`, `.prompt`, `.md` or `.txt` file under the
+//! directory is a template named by its path without the extension, e.g.
+//! `support/answer` for `prompts/support/answer.jinja`. Templates can
+//! `{% include %}` each other by these names. A file may start with a
+//! front-matter block of flat `key: value` lines:
+//!
+//! ```text
+//! ---
+//! model: gpt-4o-mini
+//! temperature: 0.2
+//! max_tokens: 400
+//! system: support/persona
+//! ---
+//! Answer the customer's question: {{ question }}
+//! ```
+//!
+//! Used as a plugin, [`PromptLibrary`] serves the prompts through
+//! [`Plugin::load_template`]; requests made with the same context then use
+//! the prompt's model, fill unset parameters from the front-matter, and
+//! carry the prompt's name and version in `metadata["prompt"]`:
+//!
+//! ```ignore
+//! let library = Arc::new(PromptLibrary::load("prompts")?);
+//! library.watch(Duration::from_secs(2));
+//! let executor = RuntimeExecutor::builder(provider)
+//!     .plugin(library.clone())
+//!     .finish();
+//!
+//! let ctx = RequestContext::new("openai", "gpt-4o");
+//! ctx.extensions().insert(TemplateVars::new(json!({"question": question}))?);
+//! let messages = executor
+//!     .plugin_engine()
+//!     .load_template("support/answer", &ctx)
+//!     .await?
+//!     .unwrap();
+//! let result = executor
+//!     .generate_text_with_context("gpt-4o", TextParams::new(messages), ctx)
+//!     .await?;
+//! ```
+//!
+//! The version of a prompt is a hash of its file, so results can be traced
+//! back to the exact prompt text even after it was edited.
+
+use crate::template::{template_vars, PromptTemplates};
+use aidale_core::error::AiError;
+use aidale_core::plugin::Plugin;
+use aidale_core::runtime::ParamDefaults;
+use aidale_core::types::*;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// File extensions of prompt files
+const EXTENSIONS: &[&str] = &["jinja", "j2", "prompt", "md", "txt"];
+
+/// Hex digits of the content hash kept as the version
+const VERSION_LEN: usize = 12;
+
+/// A prompt of the library, as recorded on requests that use it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptInfo {
+    pub name: String,
+    /// Content hash of the prompt file
+    pub version: String,
+    pub path: PathBuf,
+    pub description: Option<String>,
+    /// Model the prompt is written for; replaces the requested model
+    pub model: Option<String>,
+    /// Role of the rendered message (default user)
+    pub role: Role,
+    /// Template rendered into a system message before this prompt
+    pub system: Option<String>,
+    /// Parameters filled in when a request leaves them unset
+    pub defaults: ParamDefaults,
+}
+
+/// A rendered prompt
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub messages: Vec<Message>,
+    pub info: PromptInfo,
+}
+
+/// Templates and front-matter of one version of the directory
+#[derive(Debug)]
+struct Snapshot {
+    templates: PromptTemplates,
+    prompts: HashMap<String, PromptInfo>,
+    digest: String,
+}
+
+/// Named prompts loaded from a directory, see the [module docs](self)
+#[derive(Debug)]
+pub struct PromptLibrary {
+    dir: PathBuf,
+    snapshot: RwLock<Arc<Snapshot>>,
+}
+
+impl PromptLibrary {
+    /// Load all prompts under `dir`
+    pub fn load(dir: impl Into<PathBuf>) -> Result<Self, AiError> {
+        let dir = dir.into();
+        let snapshot = Snapshot::build(read_files(&dir)?)?;
+        Ok(Self {
+            dir,
+            snapshot: RwLock::new(Arc::new(snapshot)),
+        })
+    }
+
+    /// Directory the prompts are loaded from
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read the directory again and swap in the new prompts if any file
+    /// changed; returns whether they did
+    ///
+    /// If a file fails to parse, the error is returned and the previous
+    /// prompts stay in use.
+    pub fn reload(&self) -> Result<bool, AiError> {
+        let files = read_files(&self.dir)?;
+        if digest(&files) == self.current().digest {
+            return Ok(false);
+        }
+        let snapshot = Snapshot::build(files)?;
+        *self.snapshot.write().unwrap() = Arc::new(snapshot);
+        Ok(true)
+    }
+
+    /// Reload every `interval` in the background, until the library is
+    /// dropped
+    ///
+    /// Failed reloads are logged and retried on the next tick.
+    pub fn watch(self: &Arc<Self>, interval: Duration) {
+        let library = Arc::downgrade(self);
+        aidale_core::rt::spawn(async move {
+            loop {
+                aidale_core::rt::sleep(interval).await;
+                let Some(library) = library.upgrade() else {
+                    break;
+                };
+                match library.reload() {
+                    Ok(true) => tracing::info!("Reloaded prompts from {}", library.dir.display()),
+                    Ok(false) => {}
+                    Err(err) => tracing::warn!(
+                        "Keeping previous prompts, reload of {} failed: {}",
+                        library.dir.display(),
+                        err
+                    ),
+                }
+            }
+        });
+    }
+
+    /// Names of all prompts, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.current().prompts.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Front-matter and version of a prompt
+    pub fn get(&self, name: &str) -> Option<PromptInfo> {
+        self.current().prompts.get(name).cloned()
+    }
+
+    /// Render a prompt, preceded by its system template if it has one
+    pub fn render(&self, name: &str, vars: impl Serialize) -> Result<RenderedPrompt, AiError> {
+        let snapshot = self.current();
+        let info = snapshot
+            .prompts
+            .get(name)
+            .cloned()
+            .ok_or_else(|| AiError::invalid_request(format!("Unknown prompt '{}'", name)))?;
+        let vars = serde_json::to_value(vars)?;
+
+        let mut messages = Vec::with_capacity(2);
+        if let Some(system) = &info.system {
+            messages.push(Message::system(snapshot.templates.render(system, &vars)?));
+        }
+        let text = snapshot.templates.render(name, &vars)?;
+        messages.push(Message::builder(info.role.clone()).text(text).build());
+        Ok(RenderedPrompt { messages, info })
+    }
+
+    fn current(&self) -> Arc<Snapshot> {
+        self.snapshot.read().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Plugin for PromptLibrary {
+    fn name(&self) -> &str {
+        "prompt_library"
+    }
+
+    async fn load_template(
+        &self,
+        template_name: &str,
+        ctx: &RequestContext,
+    ) -> Result<Option<Vec<Message>>, AiError> {
+        if !self.current().prompts.contains_key(template_name) {
+            return Ok(None);
+        }
+        let rendered = self.render(template_name, template_vars(ctx))?;
+        ctx.extensions().insert(rendered.info);
+        Ok(Some(rendered.messages))
+    }
+
+    async fn resolve_model(
+        &self,
+        _model_id: &str,
+        ctx: &RequestContext,
+    ) -> Result<Option<String>, AiError> {
+        Ok(ctx
+            .extensions()
+            .get::<PromptInfo>()
+            .and_then(|info| info.model))
+    }
+
+    async fn transform_params(
+        &self,
+        mut params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        if let Some(info) = ctx.extensions().get::<PromptInfo>() {
+            let defaults = info.defaults;
+            params.temperature = params.temperature.or(defaults.temperature);
+            params.max_tokens = params.max_tokens.or(defaults.max_tokens);
+            params.top_p = params.top_p.or(defaults.top_p);
+            params.seed = params.seed.or(defaults.seed);
+            params.reasoning_effort = params.reasoning_effort.or(defaults.reasoning_effort);
+        }
+        Ok(params)
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        if let Some(info) = ctx.extensions().get::<PromptInfo>() {
+            result.metadata.insert(
+                "prompt".to_string(),
+                serde_json::json!({"name": info.name, "version": info.version}),
+            );
+        }
+        Ok(result)
+    }
+}
+
+impl Snapshot {
+    fn build(files: BTreeMap<String, (PathBuf, String)>) -> Result<Self, AiError> {
+        let digest = digest(&files);
+        let mut templates = PromptTemplates::new();
+        let mut prompts = HashMap::with_capacity(files.len());
+        for (name, (path, source)) in files {
+            let hash: String = Sha256::digest(source.as_bytes())
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let (front_matter, body) = split_front_matter(&source);
+            let mut info = PromptInfo {
+                name: name.clone(),
+                version: hash[..VERSION_LEN].to_string(),
+                path,
+                description: None,
+                model: None,
+                role: Role::User,
+                system: None,
+                defaults: ParamDefaults::new(),
+            };
+            if let Some(front_matter) = front_matter {
+                parse_front_matter(front_matter, &mut info).map_err(|e| {
+                    AiError::configuration(format!(
+                        "Prompt '{}' ({}): {}",
+                        name,
+                        info.path.display(),
+                        e
+                    ))
+                })?;
+            }
+            templates.add(name.clone(), body.to_string())?;
+            prompts.insert(name, info);
+        }
+
+        for info in prompts.values() {
+            if let Some(system) = &info.system {
+                if !prompts.contains_key(system) {
+                    return Err(AiError::configuration(format!(
+                        "Prompt '{}' uses unknown system prompt '{}'",
+                        info.name, system
+                    )));
+                }
+            }
+        }
+        Ok(Self {
+            templates,
+            prompts,
+            digest,
+        })
+    }
+}
+
+/// Prompt files under `dir` by name, with their path and content
+fn read_files(dir: &Path) -> Result<BTreeMap<String, (PathBuf, String)>, AiError> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = std::fs::read_dir(&current).map_err(|e| {
+            AiError::configuration(format!("Cannot read {}: {}", current.display(), e))
+        })?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| AiError::configuration(e.to_string()))?
+                .path();
+            let hidden = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(true, |name| name.starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let is_prompt = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext));
+            if !is_prompt {
+                continue;
+            }
+            let name = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .with_extension("")
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let source = std::fs::read_to_string(&path).map_err(|e| {
+                AiError::configuration(format!("Cannot read {}: {}", path.display(), e))
+            })?;
+            files.insert(name, (path, source));
+        }
+    }
+    Ok(files)
+}
+
+/// Hash over the names and contents of all files
+fn digest(files: &BTreeMap<String, (PathBuf, String)>) -> String {
+    let mut hasher = Sha256::new();
+    for (name, (_, source)) in files {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(source.as_bytes());
+        hasher.update([0]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Front-matter between `---` lines at the start of `source`, and the rest
+fn split_front_matter(source: &str) -> (Option<&str>, &str) {
+    let Some(rest) = source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    else {
+        return (None, source);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, source)
+}
+
+fn parse_front_matter(front_matter: &str, info: &mut PromptInfo) -> Result<(), String> {
+    for line in front_matter.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, raw) = line
+            .split_once(':')
+            .ok_or_else(|| format!("expected `key: value`, got `{}`", line))?;
+        let raw = raw.trim();
+        // Numbers and booleans parse as JSON, anything else is a string
+        let value = serde_json::from_str::<Value>(raw)
+            .ok()
+            .filter(|value| !value.is_string())
+            .unwrap_or_else(|| Value::String(raw.trim_matches(['"', '\'']).to_string()));
+        let invalid = || format!("invalid value for `{}`: {}", key.trim(), raw);
+
+        match key.trim() {
+            "description" => info.description = Some(scalar(&value)),
+            "model" => info.model = Some(scalar(&value)),
+            "system" => info.system = Some(scalar(&value)),
+            "role" => info.role = serde_json::from_value(value).map_err(|_| invalid())?,
+            "temperature" => {
+                info.defaults.temperature = Some(value.as_f64().ok_or_else(invalid)? as f32)
+            }
+            "top_p" => info.defaults.top_p = Some(value.as_f64().ok_or_else(invalid)? as f32),
+            "max_tokens" => {
+                let max_tokens = value.as_u64().ok_or_else(invalid)?;
+                info.defaults.max_tokens = Some(u32::try_from(max_tokens).map_err(|_| invalid())?)
+            }
+            "seed" => info.defaults.seed = Some(value.as_i64().ok_or_else(invalid)?),
+            "reasoning_effort" => {
+                info.defaults.reasoning_effort =
+                    Some(serde_json::from_value(value).map_err(|_| invalid())?)
+            }
+            other => return Err(format!("unknown front-matter key `{}`", other)),
+        }
+    }
+    Ok(())
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::TemplateVars;
+    use aidale_core::plugin::PluginEngine;
+
+    #[tokio::test]
+    async fn test_prompt_library() {
+        let dir = std::env::temp_dir().join(format!("aidale-prompts-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("support")).unwrap();
+        std::fs::write(
+            dir.join("support/persona.md"),
+            "You are {{ company }}'s support agent.",
+        )
+        .unwrap();
+        let answer = dir.join("support/answer.jinja");
+        std::fs::write(
+            &answer,
+            "---\nmodel: gpt-4o-mini\ntemperature: 0.2\nsystem: support/persona\n---\nQ: {{ question }}",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.json"), "{}").unwrap();
+
+        let library = Arc::new(PromptLibrary::load(&dir).unwrap());
+        assert_eq!(library.names(), ["support/answer", "support/persona"]);
+        let version = library.get("support/answer").unwrap().version;
+
+        let engine = PluginEngine::new(vec![library.clone()]);
+        let ctx = RequestContext::new("openai", "gpt-4o");
+        ctx.extensions().insert(
+            TemplateVars::new(serde_json::json!({"company": "Acme", "question": "Refund?"}))
+                .unwrap(),
+        );
+        let messages = engine
+            .load_template("support/answer", &ctx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(
+            engine.resolve_model("gpt-4o", &ctx).await.unwrap(),
+            "gpt-4o-mini"
+        );
+        let params = engine
+            .transform_params(TextParams::new(messages), &ctx)
+            .await
+            .unwrap();
+        assert_eq!(params.temperature, Some(0.2));
+        let result = TextResult {
+            content: "Sure.".to_string(),
+            finish_reason: FinishReason::Stop,
+            usage: Usage::default(),
+            model: "gpt-4o-mini".to_string(),
+            tool_calls: None,
+            reasoning: None,
+            citations: None,
+            metadata: HashMap::new(),
+        };
+        let result = engine.transform_result(result, &ctx).await.unwrap();
+        assert_eq!(result.metadata["prompt"]["version"], version.as_str());
+
+        // Edits are picked up with a new version; broken edits are not
+        assert!(!library.reload().unwrap());
+        std::fs::write(&answer, "Question: {{ question }}").unwrap();
+        assert!(library.reload().unwrap());
+        assert_ne!(library.get("support/answer").unwrap().version, version);
+        std::fs::write(&answer, "{% if %}").unwrap();
+        assert!(library.reload().is_err());
+        assert!(library.get("support/answer").unwrap().model.is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
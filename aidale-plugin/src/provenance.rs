@@ -0,0 +1,249 @@
+//! Provenance metadata for generated text.
+//!
+//! A [`ProvenancePlugin`] stamps every text result with where it came from,
+//! for audit trails:
+//!
+//! ```json
+//! "provenance": {
+//!   "model": "gpt-4o-mini",
+//!   "provider": "openai",
+//!   "request_id": "5b0c…",
+//!   "created_at": 1735689600,
+//!   "prompt_hash": "sha256:9f86d0…",
+//!   "generator": "aidale/0.1.0"
+//! }
+//! ```
+//!
+//! The prompt hash covers the messages as sent, after the other plugins'
+//! transformations, so the prompt itself need not be stored to prove which
+//! one produced a result. Results can also be handed to a recorder, e.g. an
+//! audit log writer.
+//!
+//! Optionally the request ID is embedded in the text as an invisible
+//! watermark of zero-width characters, which survives copy and paste and
+//! can be read back with [`extract_watermark`]. It does not survive
+//! paraphrasing or normalization that strips format characters.
+
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Context metadata key holding the prompt hash between hooks
+const PROMPT_HASH_KEY: &str = "provenance.prompt_hash";
+
+/// Encodes a 0 bit
+const ZERO: char = '\u{200B}';
+/// Encodes a 1 bit
+const ONE: char = '\u{200C}';
+/// Delimits the watermark
+const MARK: char = '\u{2060}';
+
+/// Where a result came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub model: String,
+    pub provider: String,
+    pub request_id: String,
+    /// Unix time in seconds
+    pub created_at: u64,
+    /// `sha256:` followed by the hex digest of the JSON-encoded messages
+    pub prompt_hash: Option<String>,
+    /// Library and version that produced the result
+    pub generator: String,
+}
+
+type Recorder = Arc<dyn Fn(&Provenance) + Send + Sync>;
+
+/// Plugin attaching provenance metadata to text results
+#[derive(Clone, Default)]
+pub struct ProvenancePlugin {
+    watermark: bool,
+    recorder: Option<Recorder>,
+}
+
+impl ProvenancePlugin {
+    /// Create a plugin that only adds metadata
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Embed the request ID in the text as an invisible watermark
+    pub fn with_watermark(mut self, watermark: bool) -> Self {
+        self.watermark = watermark;
+        self
+    }
+
+    /// Call a function with the provenance of every result
+    pub fn with_recorder(mut self, recorder: impl Fn(&Provenance) + Send + Sync + 'static) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
+}
+
+impl fmt::Debug for ProvenancePlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProvenancePlugin")
+            .field("watermark", &self.watermark)
+            .field("recorder", &self.recorder.is_some())
+            .finish()
+    }
+}
+
+/// `sha256:<hex>` of the JSON-encoded messages
+pub fn prompt_hash(messages: &[Message]) -> String {
+    let json = serde_json::to_vec(messages).unwrap_or_default();
+    let digest = Sha256::digest(&json);
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// Append `payload` to `text` as zero-width characters
+pub fn embed_watermark(text: &str, payload: &str) -> String {
+    let mut marked = String::with_capacity(text.len() + payload.len() * 8 * 3 + 6);
+    marked.push_str(text);
+    marked.push(MARK);
+    for byte in payload.bytes() {
+        for bit in (0..8).rev() {
+            marked.push(if byte >> bit & 1 == 1 { ONE } else { ZERO });
+        }
+    }
+    marked.push(MARK);
+    marked
+}
+
+/// Payload of the first watermark in `text`
+pub fn extract_watermark(text: &str) -> Option<String> {
+    let start = text.find(MARK)? + MARK.len_utf8();
+    let end = start + text[start..].find(MARK)?;
+    let bits: Vec<u8> = text[start..end]
+        .chars()
+        .map(|c| match c {
+            ZERO => Some(0),
+            ONE => Some(1),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    if bits.len() % 8 != 0 {
+        return None;
+    }
+    let bytes = bits
+        .chunks(8)
+        .map(|bits| bits.iter().fold(0u8, |byte, bit| byte << 1 | bit))
+        .collect();
+    String::from_utf8(bytes).ok()
+}
+
+/// Remove watermarks from `text`
+pub fn strip_watermark(text: &str) -> String {
+    text.chars()
+        .filter(|c| !matches!(*c, ZERO | ONE | MARK))
+        .collect()
+}
+
+#[async_trait]
+impl Plugin for ProvenancePlugin {
+    fn name(&self) -> &str {
+        "provenance"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        // Hash the prompt and stamp the text after every other change
+        PluginPhase::Post
+    }
+
+    async fn transform_params(
+        &self,
+        params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        ctx.set_metadata(PROMPT_HASH_KEY, prompt_hash(&params.messages));
+        Ok(params)
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        let provenance = Provenance {
+            model: result.model.clone(),
+            provider: ctx.provider_id.clone(),
+            request_id: ctx.request_id.clone(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            prompt_hash: ctx.get_metadata(PROMPT_HASH_KEY),
+            generator: concat!("aidale/", env!("CARGO_PKG_VERSION")).to_string(),
+        };
+
+        if self.watermark {
+            result.content = embed_watermark(&result.content, &provenance.request_id);
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder(&provenance);
+        }
+        result.metadata.insert(
+            "provenance".to_string(),
+            serde_json::to_value(&provenance)
+                .map_err(|e| AiError::plugin(self.name(), e.to_string()))?,
+        );
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_provenance_and_watermark() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let sink = recorded.clone();
+        let plugin = ProvenancePlugin::new()
+            .with_watermark(true)
+            .with_recorder(move |p| sink.lock().unwrap().push(p.clone()));
+        let ctx = RequestContext::new("openai", "gpt-4o-mini");
+
+        let messages = vec![Message::user("Hello")];
+        plugin
+            .transform_params(TextParams::new(messages.clone()), &ctx)
+            .await
+            .unwrap();
+        let result = TextResult {
+            content: "Hi there!".to_string(),
+            finish_reason: FinishReason::Stop,
+            usage: Usage::default(),
+            model: "gpt-4o-mini-2024-07-18".to_string(),
+            tool_calls: None,
+            reasoning: None,
+            citations: None,
+            metadata: HashMap::new(),
+        };
+        let result = plugin.transform_result(result, &ctx).await.unwrap();
+
+        let provenance = &result.metadata["provenance"];
+        assert_eq!(provenance["model"], "gpt-4o-mini-2024-07-18");
+        assert_eq!(provenance["provider"], "openai");
+        assert_eq!(provenance["request_id"], ctx.request_id.as_str());
+        assert_eq!(provenance["prompt_hash"], prompt_hash(&messages).as_str());
+        assert!(prompt_hash(&messages).starts_with("sha256:"));
+        assert_eq!(recorded.lock().unwrap().len(), 1);
+
+        assert_ne!(result.content, "Hi there!");
+        assert_eq!(strip_watermark(&result.content), "Hi there!");
+        assert_eq!(
+            extract_watermark(&result.content).as_deref(),
+            Some(ctx.request_id.as_str())
+        );
+        assert_eq!(extract_watermark("Hi there!"), None);
+    }
+}
@@ -0,0 +1,531 @@
+//! Retrieval-augmented generation plugin.
+//!
+//! Embeds the latest user message, searches a [`VectorStore`] for related
+//! chunks and injects them as a system message right before that user
+//! message. The chunks that were used are recorded on the result under
+//! `metadata["sources"]`, and can optionally be listed after the answer.
+//!
+//! Unless the provider returned citations itself, `[n]` markers in the
+//! answer become [`Citation`]s of the sentence they end, with the record ID
+//! as source and the retrieval score as confidence.
+//!
+//! With a [`Reranker`], more candidates are fetched from the store and only
+//! the most relevant `top_k` after reranking are injected.
+//!
+//! `generate_object` requests get the same context; their results carry no
+//! sources or citations.
+//!
+//! ```ignore
+//! let plugin = RetrievalPlugin::new(embedder, store)
+//!     .with_top_k(5)
+//!     .with_min_score(0.3)
+//!     .with_reranker(Arc::new(cohere.reranker("rerank-v3.5")), 25)
+//!     .with_sources_footer(true);
+//! ```
+
+use aidale_core::embedding::Embedder;
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::rerank::{RerankRequest, Reranker};
+use aidale_core::types::*;
+use aidale_core::vector_store::{ScoredRecord, VectorQuery, VectorStore};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Default instructions placed above the retrieved chunks
+pub const DEFAULT_CONTEXT_PROMPT: &str = "Answer using the context below when it is relevant. \
+Cite the sources you rely on by their number, e.g. [1].";
+
+/// A chunk retrieved for a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetrievedChunk {
+    /// 1-based number the chunk is cited by
+    pub index: usize,
+    /// ID of the record in the vector store
+    pub id: String,
+    pub text: String,
+    /// Document the chunk came from, if the payload names one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub score: f32,
+}
+
+/// Chunks retrieved for the current request, stored in the request
+/// extensions so later plugins can read them
+#[derive(Debug, Clone, Default)]
+pub struct RetrievedContext(pub Vec<RetrievedChunk>);
+
+/// Retrieval-augmented generation plugin
+#[derive(Debug)]
+pub struct RetrievalPlugin {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn VectorStore>,
+    reranker: Option<(Arc<dyn Reranker>, usize)>,
+    top_k: usize,
+    min_score: Option<f32>,
+    filter: HashMap<String, serde_json::Value>,
+    text_field: String,
+    source_field: String,
+    prompt: String,
+    #[cfg(feature = "templates")]
+    template: Option<(Arc<crate::template::PromptTemplates>, String)>,
+    sources_footer: bool,
+}
+
+impl RetrievalPlugin {
+    /// Create a plugin retrieving the 4 most similar chunks
+    pub fn new(embedder: Arc<dyn Embedder>, store: Arc<dyn VectorStore>) -> Self {
+        Self {
+            embedder,
+            store,
+            reranker: None,
+            top_k: 4,
+            min_score: None,
+            filter: HashMap::new(),
+            text_field: "text".to_string(),
+            source_field: "source".to_string(),
+            prompt: DEFAULT_CONTEXT_PROMPT.to_string(),
+            #[cfg(feature = "templates")]
+            template: None,
+            sources_footer: false,
+        }
+    }
+
+    /// Set the maximum number of chunks to inject
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Ignore chunks scoring below `min_score`
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Fetch `candidates` chunks and keep the `top_k` that `reranker`
+    /// scores highest
+    ///
+    /// Chunk scores are then the reranker's relevance scores.
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>, candidates: usize) -> Self {
+        self.reranker = Some((reranker, candidates));
+        self
+    }
+
+    /// Only retrieve records whose payload `field` equals `value`
+    pub fn with_filter(mut self, field: impl Into<String>, value: serde_json::Value) -> Self {
+        self.filter.insert(field.into(), value);
+        self
+    }
+
+    /// Payload field holding the chunk text (default `text`)
+    pub fn with_text_field(mut self, field: impl Into<String>) -> Self {
+        self.text_field = field.into();
+        self
+    }
+
+    /// Payload field holding the source name (default `source`)
+    pub fn with_source_field(mut self, field: impl Into<String>) -> Self {
+        self.source_field = field.into();
+        self
+    }
+
+    /// Replace the instructions placed above the retrieved chunks
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Render the context message from a template instead
+    ///
+    /// The template sees the instructions as `prompt` and the chunks as
+    /// `documents`.
+    #[cfg(feature = "templates")]
+    pub fn with_template(
+        mut self,
+        templates: Arc<crate::template::PromptTemplates>,
+        name: impl Into<String>,
+    ) -> Self {
+        self.template = Some((templates, name.into()));
+        self
+    }
+
+    /// Append a numbered list of sources to the generated text
+    pub fn with_sources_footer(mut self, enabled: bool) -> Self {
+        self.sources_footer = enabled;
+        self
+    }
+
+    /// Index and text of the latest user message
+    fn latest_user_text(messages: &[Message]) -> Option<(usize, String)> {
+        let (index, msg) = messages
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, m)| m.role == Role::User)?;
+        let text = msg
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some((index, text))
+    }
+
+    /// Convert a hit, skipping records without chunk text (numbered later)
+    fn to_chunk(&self, hit: ScoredRecord) -> Option<RetrievedChunk> {
+        let payload = &hit.record.payload;
+        let text = payload.get(&self.text_field)?.as_str()?.to_string();
+        let source = payload
+            .get(&self.source_field)
+            .and_then(|value| value.as_str())
+            .map(str::to_string);
+        Some(RetrievedChunk {
+            index: 0,
+            id: hit.record.id,
+            text,
+            source,
+            score: hit.score,
+        })
+    }
+
+    /// Keep the `top_k` chunks most relevant to `query`, best first
+    async fn rerank(
+        &self,
+        reranker: &dyn Reranker,
+        query: &str,
+        chunks: Vec<RetrievedChunk>,
+    ) -> Result<Vec<RetrievedChunk>, AiError> {
+        if chunks.is_empty() {
+            return Ok(chunks);
+        }
+        let documents = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+        let results = reranker
+            .rerank(RerankRequest::new(query, documents).with_top_n(self.top_k))
+            .await?;
+        Ok(results
+            .into_iter()
+            .filter_map(|result| {
+                let mut chunk = chunks.get(result.index)?.clone();
+                chunk.score = result.score;
+                Some(chunk)
+            })
+            .take(self.top_k)
+            .collect())
+    }
+
+    /// Citations for the `[n]` markers in `content`
+    fn citations(content: &str, chunks: &[RetrievedChunk]) -> Vec<Citation> {
+        Citation::from_markers(content, |n| {
+            let chunk = chunks.iter().find(|chunk| chunk.index == n)?;
+            Some((chunk.id.clone(), Some(chunk.score)))
+        })
+    }
+
+    /// Insert the chunks retrieved for the latest user message before it
+    async fn augment(
+        &self,
+        messages: &mut Vec<Message>,
+        ctx: &RequestContext,
+    ) -> Result<(), AiError> {
+        let Some((position, text)) = Self::latest_user_text(messages) else {
+            return Ok(());
+        };
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        let vector = self.embedder.embed_one(&text).await?;
+        let limit = match &self.reranker {
+            Some((_, candidates)) => (*candidates).max(self.top_k),
+            None => self.top_k,
+        };
+        let mut query = VectorQuery::new(vector, limit);
+        query.filter = self.filter.clone();
+        query.min_score = self.min_score;
+
+        let hits = self.store.search(&query).await?;
+        let mut chunks: Vec<RetrievedChunk> = hits
+            .into_iter()
+            .filter_map(|hit| self.to_chunk(hit))
+            .collect();
+        if let Some((reranker, _)) = &self.reranker {
+            chunks = self.rerank(&**reranker, &text, chunks).await?;
+        }
+        let chunks: Vec<RetrievedChunk> = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut chunk)| {
+                chunk.index = i + 1;
+                chunk
+            })
+            .collect();
+
+        tracing::debug!(
+            "Retrieved {} chunks for request {}",
+            chunks.len(),
+            ctx.request_id
+        );
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        messages.insert(position, self.context_message(&chunks)?);
+        ctx.extensions().insert(RetrievedContext(chunks));
+        Ok(())
+    }
+
+    /// Render the context system message
+    fn context_message(&self, chunks: &[RetrievedChunk]) -> Result<Message, AiError> {
+        #[cfg(feature = "templates")]
+        if let Some((templates, name)) = &self.template {
+            let vars = serde_json::json!({
+                "prompt": self.prompt,
+                crate::template::DOCUMENTS_VAR: chunks,
+            });
+            return Ok(Message::system(templates.render(name, vars)?));
+        }
+
+        let mut text = format!("{}\n\nContext:", self.prompt);
+        for chunk in chunks {
+            text.push_str(&format!("\n\n[{}]", chunk.index));
+            if let Some(source) = &chunk.source {
+                text.push_str(&format!(" (source: {})", source));
+            }
+            text.push('\n');
+            text.push_str(&chunk.text);
+        }
+        Ok(Message::system(text))
+    }
+}
+
+#[async_trait]
+impl Plugin for RetrievalPlugin {
+    fn name(&self) -> &str {
+        "retrieval"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        PluginPhase::Pre
+    }
+
+    async fn transform_params(
+        &self,
+        mut params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        self.augment(&mut params.messages, ctx).await?;
+        Ok(params)
+    }
+
+    async fn transform_object_params(
+        &self,
+        mut params: ObjectParams,
+        ctx: &RequestContext,
+    ) -> Result<ObjectParams, AiError> {
+        self.augment(&mut params.messages, ctx).await?;
+        Ok(params)
+    }
+
+    async fn transform_result(
+        &self,
+        mut result: TextResult,
+        ctx: &RequestContext,
+    ) -> Result<TextResult, AiError> {
+        let Some(RetrievedContext(chunks)) = ctx.extensions().get::<RetrievedContext>() else {
+            return Ok(result);
+        };
+
+        if result.citations.is_none() {
+            let citations = Self::citations(&result.content, &chunks);
+            result.citations = (!citations.is_empty()).then_some(citations);
+        }
+
+        if self.sources_footer {
+            result.content.push_str("\n\nSources:");
+            for chunk in &chunks {
+                let source = chunk.source.as_deref().unwrap_or(&chunk.id);
+                result
+                    .content
+                    .push_str(&format!("\n[{}] {}", chunk.index, source));
+            }
+        }
+        result
+            .metadata
+            .insert("sources".to_string(), serde_json::to_value(&chunks)?);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::embedding::Embedding;
+    use aidale_core::rerank::RerankResult;
+    use aidale_core::vector_store::{InMemoryVectorStore, VectorRecord};
+    use serde_json::json;
+
+    /// Embeds text by counting a few keywords
+    #[derive(Debug)]
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl Embedder for KeywordEmbedder {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AiError> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    ["rust", "python", "coffee"]
+                        .iter()
+                        .map(|word| text.matches(word).count() as f32)
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injects_context_and_sources() {
+        let store = Arc::new(InMemoryVectorStore::new());
+        store
+            .upsert(vec![
+                VectorRecord::new(
+                    "a",
+                    vec![1.0, 0.0, 0.0],
+                    json!({"text": "Rust has no garbage collector.", "source": "rust-book.md"}),
+                ),
+                VectorRecord::new(
+                    "b",
+                    vec![0.0, 0.0, 1.0],
+                    json!({"text": "Espresso is brewed under pressure.", "source": "coffee.md"}),
+                ),
+            ])
+            .await
+            .unwrap();
+
+        let plugin = RetrievalPlugin::new(Arc::new(KeywordEmbedder), store)
+            .with_min_score(0.5)
+            .with_sources_footer(true);
+        let ctx = RequestContext::new("test", "test-model");
+
+        let params = TextParams::new(vec![
+            Message::system("You are helpful."),
+            Message::user("How does Rust manage memory?"),
+        ]);
+        let params = plugin.transform_params(params, &ctx).await.unwrap();
+
+        assert_eq!(params.messages.len(), 3);
+        assert_eq!(params.messages[1].role, Role::System);
+        let ContentPart::Text { text } = &params.messages[1].content[0] else {
+            panic!("expected text context");
+        };
+        assert!(text.contains("[1] (source: rust-book.md)\nRust has no garbage collector."));
+        assert!(!text.contains("Espresso"));
+
+        let result = TextResult {
+            content: "It uses ownership [1].".to_string(),
+            finish_reason: FinishReason::Stop,
+            usage: Usage::default(),
+            model: "test-model".to_string(),
+            tool_calls: None,
+            reasoning: None,
+            citations: None,
+            metadata: HashMap::new(),
+        };
+        let result = plugin.transform_result(result, &ctx).await.unwrap();
+
+        assert_eq!(
+            result.content,
+            "It uses ownership [1].\n\nSources:\n[1] rust-book.md"
+        );
+        assert_eq!(result.metadata["sources"][0]["id"], "a");
+        let citations = result.citations.unwrap();
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].text, "It uses ownership");
+        assert_eq!((citations[0].start, citations[0].end), (0, 17));
+        assert_eq!(citations[0].sources, ["a"]);
+        assert!(citations[0].confidence.unwrap() > 0.5);
+    }
+
+    #[test]
+    fn test_citation_spans() {
+        let chunk = |index: usize, id: &str, score: f32| RetrievedChunk {
+            index,
+            id: id.to_string(),
+            text: String::new(),
+            source: None,
+            score,
+        };
+        let chunks = [chunk(1, "a", 0.9), chunk(2, "b", 0.7)];
+        let citations = RetrievalPlugin::citations(
+            "Rust is fast [1]. It has no GC, only ownership [1][2]. Unsure [9].",
+            &chunks,
+        );
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].text, "Rust is fast");
+        assert_eq!(citations[1].text, "It has no GC, only ownership");
+        assert_eq!(citations[1].start, 18);
+        assert_eq!(citations[1].sources, ["a", "b"]);
+        assert_eq!(citations[1].confidence, Some(0.9));
+    }
+
+    /// Prefers shorter documents
+    #[derive(Debug)]
+    struct ShortestReranker;
+
+    #[async_trait]
+    impl Reranker for ShortestReranker {
+        async fn rerank(&self, req: RerankRequest) -> Result<Vec<RerankResult>, AiError> {
+            let mut results: Vec<_> = req
+                .documents
+                .iter()
+                .enumerate()
+                .map(|(index, doc)| RerankResult {
+                    index,
+                    score: 1.0 / doc.len() as f32,
+                })
+                .collect();
+            results.sort_by(|a, b| b.score.total_cmp(&a.score));
+            results.truncate(req.top_n.unwrap_or(results.len()));
+            Ok(results)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reranks_candidates() {
+        let store = Arc::new(InMemoryVectorStore::new());
+        store
+            .upsert(vec![
+                VectorRecord::new(
+                    "long",
+                    vec![1.0, 0.0, 0.0],
+                    json!({"text": "Rust manages memory through ownership and borrowing."}),
+                ),
+                VectorRecord::new(
+                    "short",
+                    vec![0.9, 0.1, 0.0],
+                    json!({"text": "Rust uses ownership."}),
+                ),
+            ])
+            .await
+            .unwrap();
+
+        let plugin = RetrievalPlugin::new(Arc::new(KeywordEmbedder), store)
+            .with_top_k(1)
+            .with_reranker(Arc::new(ShortestReranker), 10);
+        let ctx = RequestContext::new("test", "test-model");
+        let params = TextParams::new(vec![Message::user("Rust memory?")]);
+        plugin.transform_params(params, &ctx).await.unwrap();
+
+        let RetrievedContext(chunks) = ctx.extensions().get::<RetrievedContext>().unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].id, "short");
+        assert_eq!(chunks[0].index, 1);
+    }
+}
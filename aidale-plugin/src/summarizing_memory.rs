@@ -0,0 +1,359 @@
+//! History compaction plugin.
+//!
+//! Long conversations eventually outgrow the context window. The
+//! [`SummarizingMemoryPlugin`] counts the tokens of every request and, past a
+//! threshold, replaces the older turns with a summary written by a
+//! (typically small) model. System messages are pinned and always kept
+//! verbatim, as are the most recent messages:
+//!
+//! ```ignore
+//! let plugin = SummarizingMemoryPlugin::new(provider, "gpt-4o-mini")
+//!     .with_max_tokens(8000)
+//!     .with_keep_recent(10);
+//! ```
+//!
+//! The last summary is cached, so a growing conversation only has its new
+//! turns folded into the existing summary instead of being summarized from
+//! scratch on every request. `generate_object` requests are compacted the
+//! same way.
+
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::provider::Provider;
+use aidale_core::tokenizer::{HeuristicTokenCounter, TokenCounter};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// Default instructions for the summarization model
+pub const DEFAULT_SUMMARY_PROMPT: &str = "Summarize the conversation below for the assistant \
+that continues it. Keep the user's goals, decisions, facts, names, numbers and tool results that \
+may matter later. Reply with the summary only.";
+
+/// Prefix of the system message carrying the summary
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+/// Summary of the first `len` compacted messages
+#[derive(Debug)]
+struct CachedSummary {
+    len: usize,
+    hash: u64,
+    summary: String,
+}
+
+/// Plugin replacing old turns with a summary once a conversation is too long
+#[derive(Debug)]
+pub struct SummarizingMemoryPlugin {
+    provider: Arc<dyn Provider>,
+    model: String,
+    counter: Arc<dyn TokenCounter>,
+    max_tokens: usize,
+    keep_recent: usize,
+    prompt: String,
+    cache: Mutex<Option<CachedSummary>>,
+}
+
+impl SummarizingMemoryPlugin {
+    /// Summarize with `model` on `provider` once a request exceeds 4000
+    /// tokens, keeping the 6 most recent messages
+    pub fn new(provider: Arc<dyn Provider>, model: impl Into<String>) -> Self {
+        Self {
+            provider,
+            model: model.into(),
+            counter: Arc::new(HeuristicTokenCounter::new()),
+            max_tokens: 4000,
+            keep_recent: 6,
+            prompt: DEFAULT_SUMMARY_PROMPT.to_string(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Compact requests whose messages exceed this many tokens
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Number of recent non-system messages never summarized
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// Set the token counter
+    pub fn with_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Set the summarization instructions
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Summarize `old`, folding only the messages after the cached summary
+    /// into it when possible
+    async fn summarize(&self, old: &[Message]) -> Result<String, AiError> {
+        let cached = {
+            let cache = self.cache.lock().unwrap();
+            cache
+                .as_ref()
+                .filter(|c| c.len <= old.len() && c.hash == hash_messages(&old[..c.len]))
+                .map(|c| (c.len, c.summary.clone()))
+        };
+        let (transcript, from) = match cached {
+            Some((len, summary)) if len == old.len() => return Ok(summary),
+            Some((len, summary)) => (format!("{}{}\n\n", SUMMARY_PREFIX, summary), len),
+            None => (String::new(), 0),
+        };
+        let transcript = transcript + &render(&old[from..]);
+
+        let req = ChatCompletionRequest::new(
+            self.model.clone(),
+            vec![
+                Message::system(self.prompt.clone()),
+                Message::user(transcript),
+            ],
+        );
+        let response = self.provider.chat_completion(req).await?;
+        let summary = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| text_of(&choice.message))
+            .filter(|summary| !summary.trim().is_empty())
+            .ok_or_else(|| AiError::plugin(self.name(), "Summarization returned no text"))?;
+
+        *self.cache.lock().unwrap() = Some(CachedSummary {
+            len: old.len(),
+            hash: hash_messages(old),
+            summary: summary.clone(),
+        });
+        Ok(summary)
+    }
+
+    /// Replace all but the recent messages with a summary when the
+    /// conversation is over budget
+    async fn compact(
+        &self,
+        mut messages: Vec<Message>,
+        ctx: &RequestContext,
+    ) -> Result<Vec<Message>, AiError> {
+        let tokens = self.counter.count_messages(&messages);
+        if tokens <= self.max_tokens {
+            return Ok(messages);
+        }
+
+        // Start of the recent messages: the last `keep_recent` non-system
+        // messages, plus the tool call of any tool result among them
+        let mut split = messages.len();
+        let mut kept = 0;
+        while split > 0 && kept < self.keep_recent {
+            split -= 1;
+            if messages[split].role != Role::System {
+                kept += 1;
+            }
+        }
+        while split > 0 && messages.get(split).is_some_and(|m| m.role == Role::Tool) {
+            split -= 1;
+        }
+
+        let recent = messages.split_off(split);
+        let (pinned, old): (Vec<_>, Vec<_>) = messages
+            .into_iter()
+            .partition(|msg| msg.role == Role::System);
+        if old.is_empty() {
+            return Ok(pinned.into_iter().chain(recent).collect());
+        }
+
+        let summary = self.summarize(&old).await?;
+        tracing::debug!(
+            "Compacted {} messages of request {} ({} tokens) into a summary",
+            old.len(),
+            ctx.request_id,
+            tokens
+        );
+        let summary = Message::system(format!("{}{}", SUMMARY_PREFIX, summary));
+        Ok(pinned
+            .into_iter()
+            .chain(std::iter::once(summary))
+            .chain(recent)
+            .collect())
+    }
+}
+
+fn hash_messages(messages: &[Message]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(messages)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+fn text_of(message: &Message) -> String {
+    message
+        .content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render messages as a plain transcript for the summarization model
+fn render(messages: &[Message]) -> String {
+    let mut lines = Vec::new();
+    for msg in messages {
+        let role = match msg.role {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::Tool => "Tool",
+        };
+        for part in &msg.content {
+            match part {
+                ContentPart::Text { text } => lines.push(format!("{}: {}", role, text)),
+                ContentPart::ToolCall {
+                    name, arguments, ..
+                } => lines.push(format!("{} called {}({})", role, name, arguments)),
+                ContentPart::ToolResult { result, .. } => {
+                    lines.push(format!("Tool result: {}", result))
+                }
+                _ => {}
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[async_trait]
+impl Plugin for SummarizingMemoryPlugin {
+    fn name(&self) -> &str {
+        "summarizing_memory"
+    }
+
+    fn enforce(&self) -> PluginPhase {
+        PluginPhase::Pre
+    }
+
+    async fn transform_params(
+        &self,
+        mut params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        params.messages = self
+            .compact(std::mem::take(&mut params.messages), ctx)
+            .await?;
+        Ok(params)
+    }
+
+    async fn transform_object_params(
+        &self,
+        mut params: ObjectParams,
+        ctx: &RequestContext,
+    ) -> Result<ObjectParams, AiError> {
+        params.messages = self
+            .compact(std::mem::take(&mut params.messages), ctx)
+            .await?;
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::ChatCompletionStream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Summarizes by counting the transcript lines, remembering the inputs
+    #[derive(Debug, Default)]
+    struct CountingProvider {
+        calls: AtomicUsize,
+        inputs: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Provider for CountingProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "counting".to_string(),
+                name: "Counting".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let input = text_of(&req.messages[1]);
+            let summary = format!("{} lines", input.lines().count());
+            self.inputs.lock().unwrap().push(input);
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(summary),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage::default(),
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    fn conversation(turns: usize) -> Vec<Message> {
+        let mut messages = vec![Message::system("Be helpful.")];
+        for i in 0..turns {
+            messages.push(Message::user(format!("Question number {}", i)));
+            messages.push(Message::assistant(format!("Answer number {}", i)));
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_compaction() {
+        let provider = Arc::new(CountingProvider::default());
+        let plugin = SummarizingMemoryPlugin::new(provider.clone(), "small")
+            .with_max_tokens(40)
+            .with_keep_recent(2);
+        let ctx = RequestContext::new("test", "m");
+
+        let params = TextParams::new(conversation(1));
+        let params = plugin.transform_params(params, &ctx).await.unwrap();
+        assert_eq!(params.messages.len(), 3);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 0);
+
+        let params = TextParams::new(conversation(6));
+        let params = plugin.transform_params(params, &ctx).await.unwrap();
+        assert_eq!(params.messages.len(), 4);
+        assert_eq!(text_of(&params.messages[0]), "Be helpful.");
+        assert!(text_of(&params.messages[1]).ends_with("10 lines"));
+        assert_eq!(text_of(&params.messages[3]), "Answer number 5");
+
+        // Only the new turns are folded into the cached summary
+        let params = TextParams::new(conversation(7));
+        plugin.transform_params(params, &ctx).await.unwrap();
+        let inputs = provider.inputs.lock().unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert!(inputs[1].starts_with(SUMMARY_PREFIX));
+        assert_eq!(inputs[1].lines().count(), 5);
+    }
+}
@@ -0,0 +1,364 @@
+//! Jinja prompt templates.
+//!
+//! [`PromptTemplates`] compiles templates with [minijinja], so prompts can
+//! use conditionals, loops, filters, macros and `{% include %}`d partials
+//! instead of string concatenation. Templates are parsed when they are
+//! added, and [`PromptTemplates::add_typed`] also checks the variables a
+//! template reads against the fields of a struct, so a typo fails at
+//! startup rather than rendering an empty string in production:
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Answer {
+//!     question: String,
+//!     strict: bool,
+//! }
+//!
+//! let mut templates = PromptTemplates::new();
+//! templates.add("doc", "[{{ doc.index }}] {{ doc.text }}")?;
+//! templates.add_typed::<Answer>(
+//!     "answer",
+//!     "{% for doc in documents %}{% include 'doc' %}\n{% endfor %}\
+//!      {% if strict %}Only use the documents above.\n{% endif %}{{ question }}",
+//! )?;
+//! ```
+//!
+//! `documents` is available to every template rendered by
+//! [`TemplatePlugin`]: it holds the chunks the
+//! [`RetrievalPlugin`](crate::RetrievalPlugin) retrieved for the request.
+//! Rendering fails on variables that are not set.
+//!
+//! [minijinja]: https://docs.rs/minijinja
+
+use crate::retrieval::{RetrievedChunk, RetrievedContext};
+use aidale_core::error::AiError;
+use aidale_core::plugin::Plugin;
+use aidale_core::types::{Message, RequestContext, Role};
+use async_trait::async_trait;
+use minijinja::{Environment, UndefinedBehavior};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Variable holding the retrieved chunks in [`TemplatePlugin`] templates
+pub const DOCUMENTS_VAR: &str = "documents";
+
+/// A set of named templates that can include each other
+#[derive(Debug)]
+pub struct PromptTemplates {
+    env: Environment<'static>,
+}
+
+impl PromptTemplates {
+    /// Create an empty set
+    ///
+    /// Block tags don't leave blank lines behind, and undefined variables
+    /// are errors (testing them with `{% if %}` is allowed).
+    pub fn new() -> Self {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        Self { env }
+    }
+
+    /// Add a template, or a partial to include from other templates
+    ///
+    /// Fails with a configuration error on syntax errors.
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Result<(), AiError> {
+        let name = name.into();
+        self.env
+            .add_template_owned(name.clone(), source.into())
+            .map_err(|e| AiError::configuration(format!("Template '{}': {}", name, e)))
+    }
+
+    /// Add a template rendered from `T`, checking that every variable it
+    /// reads is a field of `T`
+    ///
+    /// `T` must be a plain struct deriving `Deserialize`; flattened fields
+    /// are not supported. Partials included by the template are not
+    /// checked, and neither is [`DOCUMENTS_VAR`], which is always allowed.
+    pub fn add_typed<T: DeserializeOwned>(
+        &mut self,
+        name: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Result<(), AiError> {
+        let name = name.into();
+        let type_name = std::any::type_name::<T>();
+        let fields = field_names::<T>().ok_or_else(|| {
+            AiError::configuration(format!(
+                "Template '{}': {} is not a plain struct",
+                name, type_name
+            ))
+        })?;
+        self.add(name.clone(), source)?;
+
+        let template = self
+            .env
+            .get_template(&name)
+            .expect("template was just added");
+        let globals: Vec<&str> = self.env.globals().map(|(name, _)| name).collect();
+        let mut unknown: Vec<String> = template
+            .undeclared_variables(false)
+            .into_iter()
+            .filter(|var| {
+                var != DOCUMENTS_VAR
+                    && !fields.contains(&var.as_str())
+                    && !globals.contains(&var.as_str())
+            })
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        unknown.sort();
+        self.env.remove_template(&name);
+        Err(AiError::configuration(format!(
+            "Template '{}' uses variables that are not fields of {}: {}",
+            name,
+            type_name,
+            unknown.join(", ")
+        )))
+    }
+
+    /// Whether a template with this name exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.env.get_template(name).is_ok()
+    }
+
+    /// Render a template
+    pub fn render(&self, name: &str, vars: impl Serialize) -> Result<String, AiError> {
+        let template = self
+            .env
+            .get_template(name)
+            .map_err(|_| AiError::invalid_request(format!("Unknown template '{}'", name)))?;
+        template
+            .render(vars)
+            .map_err(|e| AiError::invalid_request(format!("Template '{}': {}", name, e)))
+    }
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Variables for [`TemplatePlugin`], stored in the request extensions
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars(pub Map<String, Value>);
+
+impl TemplateVars {
+    /// Serialize `vars`, which must serialize to a JSON object
+    pub fn new(vars: impl Serialize) -> Result<Self, AiError> {
+        match serde_json::to_value(vars)? {
+            Value::Object(map) => Ok(Self(map)),
+            other => Err(AiError::invalid_request(format!(
+                "Template variables must be an object, got {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Serves message templates through [`Plugin::load_template`]
+///
+/// A prompt is a list of messages, each rendered from a template of the
+/// set. Variables come from the request metadata, overridden by the
+/// [`TemplateVars`] in the request extensions; [`DOCUMENTS_VAR`] holds the
+/// retrieved chunks, or an empty list.
+///
+/// ```ignore
+/// let plugin = TemplatePlugin::new(Arc::new(templates))
+///     .with_prompt("answer", [(Role::System, "persona"), (Role::User, "answer")]);
+///
+/// ctx.extensions().insert(TemplateVars::new(&Answer { question, strict: true })?);
+/// let messages = executor.plugin_engine().load_template("answer", &ctx).await?;
+/// ```
+#[derive(Debug)]
+pub struct TemplatePlugin {
+    templates: Arc<PromptTemplates>,
+    prompts: HashMap<String, Vec<(Role, String)>>,
+}
+
+impl TemplatePlugin {
+    /// Create a plugin serving prompts from `templates`
+    pub fn new(templates: Arc<PromptTemplates>) -> Self {
+        Self {
+            templates,
+            prompts: HashMap::new(),
+        }
+    }
+
+    /// Define a prompt as messages of the given roles, each rendered from
+    /// the named template
+    pub fn with_prompt(
+        mut self,
+        name: impl Into<String>,
+        messages: impl IntoIterator<Item = (Role, impl Into<String>)>,
+    ) -> Self {
+        let messages = messages
+            .into_iter()
+            .map(|(role, template)| (role, template.into()))
+            .collect();
+        self.prompts.insert(name.into(), messages);
+        self
+    }
+}
+
+#[async_trait]
+impl Plugin for TemplatePlugin {
+    fn name(&self) -> &str {
+        "template"
+    }
+
+    async fn load_template(
+        &self,
+        template_name: &str,
+        ctx: &RequestContext,
+    ) -> Result<Option<Vec<Message>>, AiError> {
+        let Some(prompt) = self.prompts.get(template_name) else {
+            return Ok(None);
+        };
+        let vars = template_vars(ctx);
+        let messages = prompt
+            .iter()
+            .map(|(role, template)| {
+                let text = self.templates.render(template, &vars)?;
+                Ok(Message::builder(role.clone()).text(text).build())
+            })
+            .collect::<Result<_, AiError>>()?;
+        Ok(Some(messages))
+    }
+}
+
+/// Variables of a template rendered for a request: the metadata, the
+/// [`TemplateVars`] and [`DOCUMENTS_VAR`]
+pub(crate) fn template_vars(ctx: &RequestContext) -> Map<String, Value> {
+    let mut vars: Map<String, Value> = ctx
+        .metadata()
+        .into_iter()
+        .map(|(key, value)| (key, Value::String(value)))
+        .collect();
+    if let Some(TemplateVars(extra)) = ctx.extensions().get::<TemplateVars>() {
+        vars.extend(extra);
+    }
+    let documents: Vec<RetrievedChunk> = ctx
+        .extensions()
+        .get::<RetrievedContext>()
+        .map(|RetrievedContext(chunks)| chunks)
+        .unwrap_or_default();
+    vars.insert(
+        DOCUMENTS_VAR.to_string(),
+        serde_json::to_value(documents).unwrap_or_default(),
+    );
+    vars
+}
+
+/// Field names of the struct `T` deserializes from, or `None` if `T`
+/// doesn't deserialize from a struct
+fn field_names<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut probe = FieldProbe(None);
+    let _ = T::deserialize(&mut probe);
+    probe.0
+}
+
+/// Deserializer that records the fields it is asked for and fails
+struct FieldProbe(Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for &mut FieldProbe {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0 = Some(fields);
+        Err(de::Error::custom("probed"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Serialize, Deserialize)]
+    struct Answer {
+        question: String,
+        strict: bool,
+    }
+
+    #[tokio::test]
+    async fn test_template_plugin() {
+        let mut templates = PromptTemplates::new();
+        templates
+            .add("doc", "[{{ doc.index }}] {{ doc.text }}")
+            .unwrap();
+        templates
+            .add_typed::<Answer>(
+                "answer",
+                "{% for doc in documents %}\n{% include 'doc' %}\n\n{% endfor %}\
+                 {% if strict %}\nOnly use the documents above.\n{% endif %}\
+                 {{ question }}",
+            )
+            .unwrap();
+        assert!(templates.add("broken", "{% if %}").is_err());
+        let err = templates
+            .add_typed::<Answer>("typo", "{{ questoin }}")
+            .unwrap_err();
+        assert!(err.to_string().contains("questoin"));
+        assert!(!templates.contains("typo"));
+
+        let plugin = TemplatePlugin::new(Arc::new(templates))
+            .with_prompt("answer", [(Role::User, "answer")]);
+        let ctx = RequestContext::new("openai".to_string(), "gpt-4o".to_string());
+        ctx.extensions()
+            .insert(RetrievedContext(vec![RetrievedChunk {
+                index: 1,
+                id: "a".to_string(),
+                text: "Rust is a language.".to_string(),
+                source: None,
+                score: 0.9,
+            }]));
+        ctx.extensions().insert(
+            TemplateVars::new(Answer {
+                question: "What is Rust?".to_string(),
+                strict: true,
+            })
+            .unwrap(),
+        );
+
+        let messages = plugin.load_template("answer", &ctx).await.unwrap().unwrap();
+        let aidale_core::types::ContentPart::Text { text } = &messages[0].content[0] else {
+            panic!("expected text");
+        };
+        assert_eq!(
+            text,
+            "[1] Rust is a language.\nOnly use the documents above.\nWhat is Rust?"
+        );
+        assert!(plugin.load_template("other", &ctx).await.unwrap().is_none());
+
+        // Missing variables are errors
+        ctx.extensions().insert(TemplateVars::default());
+        assert!(plugin.load_template("answer", &ctx).await.is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Tool result size limiting.
+//!
+//! A single tool call can return far more text than the context window holds
+//! (a log file, a directory listing, a web page). [`ToolResultLimiter`] caps
+//! each result at a token budget before it is sent back to the model, either
+//! by cutting out the middle or, when a summarizer is configured, by asking
+//! a (typically small) model to condense it:
+//!
+//! ```ignore
+//! let limiter = ToolResultLimiter::new(2000)
+//!     .with_summarizer(provider, "gpt-4o-mini");
+//! let content = limiter.limit("fetch_page", result).await;
+//! ```
+//!
+//! Results within the budget are passed through unchanged. If summarization
+//! fails, or the summary is itself too long, the text is truncated instead.
+
+use aidale_core::error::AiError;
+use aidale_core::provider::Provider;
+use aidale_core::tokenizer::{HeuristicTokenCounter, TokenCounter};
+use aidale_core::types::*;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Default instructions for the summarization model
+pub const DEFAULT_TOOL_SUMMARY_PROMPT: &str = "The user message is the output of a tool call \
+that is too long to pass on verbatim. Summarize it for the assistant that made the call. Keep \
+identifiers, numbers, error messages and anything the assistant is likely to need next. Reply \
+with the summary only.";
+
+/// Model used to condense oversized results
+#[derive(Debug, Clone)]
+struct Summarizer {
+    provider: Arc<dyn Provider>,
+    model: String,
+    prompt: String,
+}
+
+/// Caps the size of tool results before they are appended to a conversation
+#[derive(Debug, Clone)]
+pub struct ToolResultLimiter {
+    max_tokens: usize,
+    head_ratio: f32,
+    counter: Arc<dyn TokenCounter>,
+    summarizer: Option<Summarizer>,
+}
+
+impl ToolResultLimiter {
+    /// Limit results to `max_tokens`, truncating the middle of longer ones
+    pub fn new(max_tokens: usize) -> Self {
+        Self {
+            max_tokens,
+            head_ratio: 0.75,
+            counter: Arc::new(HeuristicTokenCounter::new()),
+            summarizer: None,
+        }
+    }
+
+    /// Set the token counter
+    pub fn with_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Share of the budget kept from the start of a truncated result; the
+    /// rest is kept from its end
+    pub fn with_head_ratio(mut self, head_ratio: f32) -> Self {
+        self.head_ratio = head_ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Summarize oversized results with `model` on `provider` instead of
+    /// truncating them
+    pub fn with_summarizer(
+        mut self,
+        provider: Arc<dyn Provider>,
+        model: impl Into<String>,
+    ) -> Self {
+        self.summarizer = Some(Summarizer {
+            provider,
+            model: model.into(),
+            prompt: DEFAULT_TOOL_SUMMARY_PROMPT.to_string(),
+        });
+        self
+    }
+
+    /// Set the summarization instructions; only used with a summarizer
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        if let Some(summarizer) = &mut self.summarizer {
+            summarizer.prompt = prompt.into();
+        }
+        self
+    }
+
+    /// Token budget per result
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    /// Bring the result of `tool` within the token budget.
+    ///
+    /// Oversized results are replaced by a string, since their JSON structure
+    /// can't survive truncation.
+    pub async fn limit(&self, tool: &str, result: Value) -> Value {
+        let text = match result {
+            Value::String(text) => text,
+            other => {
+                let text = other.to_string();
+                if self.counter.count_tokens(&text) <= self.max_tokens {
+                    return other;
+                }
+                text
+            }
+        };
+        let tokens = self.counter.count_tokens(&text);
+        if tokens <= self.max_tokens {
+            return Value::String(text);
+        }
+
+        if let Some(summarizer) = &self.summarizer {
+            match self.summarize(summarizer, tool, &text).await {
+                Ok(summary) if self.counter.count_tokens(&summary) <= self.max_tokens => {
+                    tracing::debug!("Summarized {} token result of tool {}", tokens, tool);
+                    return Value::String(summary);
+                }
+                Ok(_) => tracing::warn!("Summary of tool {} result exceeds the budget", tool),
+                Err(e) => tracing::warn!("Failed to summarize tool {} result: {}", tool, e),
+            }
+        }
+        Value::String(self.truncate(&text, tokens))
+    }
+
+    async fn summarize(
+        &self,
+        summarizer: &Summarizer,
+        tool: &str,
+        text: &str,
+    ) -> Result<String, AiError> {
+        let req = ChatCompletionRequest::new(
+            summarizer.model.clone(),
+            vec![
+                Message::system(format!("{}\n\nTool: {}", summarizer.prompt, tool)),
+                Message::user(text),
+            ],
+        );
+        let response = summarizer.provider.chat_completion(req).await?;
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| {
+                choice
+                    .message
+                    .content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<String>()
+            })
+            .filter(|summary| !summary.trim().is_empty())
+            .ok_or_else(|| AiError::plugin("tool_result", "Summarization returned no text"))
+    }
+
+    /// Keep the start and end of `text`, replacing the middle with a marker
+    fn truncate(&self, text: &str, tokens: usize) -> String {
+        // Size the marker for the worst case, where nearly everything is cut
+        let marker_tokens = self.counter.count_tokens(&marker(tokens));
+        let budget = self.max_tokens.saturating_sub(marker_tokens);
+        let head_budget = (budget as f32 * self.head_ratio) as usize;
+        let tail_budget = budget - head_budget;
+
+        let bounds: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let head = longest(&bounds, |end| {
+            self.counter.count_tokens(&text[..end]) <= head_budget
+        });
+        let mut rev = bounds.clone();
+        rev.reverse();
+        let tail = longest(&rev, |start| {
+            self.counter.count_tokens(&text[start..]) <= tail_budget
+        })
+        .max(head);
+
+        let kept =
+            self.counter.count_tokens(&text[..head]) + self.counter.count_tokens(&text[tail..]);
+        format!(
+            "{}{}{}",
+            &text[..head],
+            marker(tokens.saturating_sub(kept)),
+            &text[tail..]
+        )
+    }
+}
+
+fn marker(omitted: usize) -> String {
+    format!("\n[... {} tokens omitted ...]\n", omitted)
+}
+
+/// Last element of `bounds` accepted by `fits`, which must accept a prefix of
+/// `bounds`; `bounds[0]` is assumed to fit
+fn longest(bounds: &[usize], fits: impl Fn(usize) -> bool) -> usize {
+    let accepted = bounds.partition_point(|&b| fits(b));
+    bounds[accepted.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tool_result_limit() {
+        let limiter = ToolResultLimiter::new(50);
+
+        let small = serde_json::json!({"files": ["a.rs", "b.rs"]});
+        assert_eq!(limiter.limit("ls", small.clone()).await, small);
+
+        let lines: Vec<String> = (0..200).map(|i| format!("line {:03}", i)).collect();
+        let limited = limiter.limit("cat", Value::String(lines.join("\n"))).await;
+        let text = limited.as_str().unwrap();
+        assert!(HeuristicTokenCounter::new().count_tokens(text) <= 50);
+        assert!(text.starts_with("line 000\nline 001"));
+        assert!(text.ends_with("line 199"));
+        assert!(text.contains("tokens omitted"));
+    }
+}
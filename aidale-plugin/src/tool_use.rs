@@ -7,8 +7,12 @@ use aidale_core::error::AiError;
 use aidale_core::plugin::{Plugin, PluginPhase};
 use aidale_core::types::*;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
 
 /// Tool executor trait
 #[async_trait]
@@ -19,6 +23,11 @@ pub trait ToolExecutor: Send + Sync {
         name: &str,
         arguments: &serde_json::Value,
     ) -> Result<serde_json::Value, AiError>;
+
+    /// Tool definition advertised to the model, if the executor has one
+    fn definition(&self) -> Option<Tool> {
+        None
+    }
 }
 
 /// Simple function-based tool executor
@@ -36,6 +45,7 @@ pub struct FunctionTool {
     name: String,
     description: String,
     parameters: serde_json::Value,
+    strict: bool,
     executor: ToolExecutorFn,
 }
 
@@ -55,16 +65,24 @@ impl FunctionTool {
             name: name.into(),
             description: description.into(),
             parameters,
+            strict: false,
             executor: Arc::new(move |args| Box::pin(executor(args))),
         }
     }
 
+    /// Ask the provider to validate arguments against the parameter schema
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
     /// Get tool definition
     pub fn definition(&self) -> Tool {
         Tool {
             name: self.name.clone(),
             description: self.description.clone(),
             parameters: self.parameters.clone(),
+            strict: self.strict,
         }
     }
 }
@@ -85,11 +103,70 @@ impl ToolExecutor for FunctionTool {
 
         (self.executor)(arguments.clone()).await
     }
+
+    fn definition(&self) -> Option<Tool> {
+        Some(FunctionTool::definition(self))
+    }
+}
+
+/// What the tool loop does when a tool call fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolErrorPolicy {
+    /// Send the error to the model as the tool result, so it can recover
+    #[default]
+    Report,
+    /// Stop the agent loop after this step
+    Abort,
+}
+
+/// How a registered tool is executed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolOptions {
+    /// Time an attempt may take before it fails
+    pub timeout: Option<Duration>,
+    /// Attempts after the first when the tool fails or times out
+    pub retries: u32,
+    /// Wait between attempts
+    pub retry_delay: Duration,
+    pub on_error: ToolErrorPolicy,
+}
+
+impl ToolOptions {
+    /// Set the time an attempt may take
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry failed calls up to `retries` times, waiting `delay` in between
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Set what the tool loop does when the call still fails
+    pub fn with_on_error(mut self, on_error: ToolErrorPolicy) -> Self {
+        self.on_error = on_error;
+        self
+    }
+}
+
+/// Outcome of [`ToolRegistry::execute_approved`]
+#[derive(Debug)]
+pub struct ToolOutcome {
+    /// Arguments the tool ran with, which the approver may have changed
+    pub arguments: serde_json::Value,
+    pub result: Result<serde_json::Value, AiError>,
+    /// Number of attempts made; 0 if the call was denied
+    pub attempts: u32,
 }
 
 /// Tool registry that can execute multiple tools
 pub struct ToolRegistry {
     tools: HashMap<String, Arc<dyn ToolExecutor>>,
+    options: HashMap<String, ToolOptions>,
+    default_options: ToolOptions,
 }
 
 impl ToolRegistry {
@@ -97,47 +174,154 @@ impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            options: HashMap::new(),
+            default_options: ToolOptions::default(),
         }
     }
 
+    /// Set the options of tools registered without their own
+    pub fn with_default_options(mut self, options: ToolOptions) -> Self {
+        self.default_options = options;
+        self
+    }
+
     /// Register a tool
     pub fn register(&mut self, name: impl Into<String>, tool: Arc<dyn ToolExecutor>) {
         self.tools.insert(name.into(), tool);
     }
 
+    /// Register a tool executed with its own options
+    pub fn register_with_options(
+        &mut self,
+        name: impl Into<String>,
+        tool: Arc<dyn ToolExecutor>,
+        options: ToolOptions,
+    ) {
+        let name = name.into();
+        self.options.insert(name.clone(), options);
+        self.register(name, tool);
+    }
+
+    /// Options the tool registered under `name` is executed with
+    pub fn options(&self, name: &str) -> ToolOptions {
+        self.options
+            .get(name)
+            .copied()
+            .unwrap_or(self.default_options)
+    }
+
+    /// Register a function tool under its own name
+    pub fn register_function(&mut self, tool: FunctionTool) {
+        let name = tool.name.clone();
+        self.register(name, Arc::new(tool));
+    }
+
+    /// Number of registered tools
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    /// Whether no tools are registered
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Whether a tool is registered under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
     /// Get all tool definitions
     pub fn definitions(&self) -> Vec<Tool> {
         self.tools
             .iter()
             .map(|(name, tool)| {
-                // If the tool is a FunctionTool, get its definition
-                // Otherwise, create a basic definition
-                if let Some(func_tool) = (tool as &dyn std::any::Any).downcast_ref::<FunctionTool>()
-                {
-                    func_tool.definition()
-                } else {
-                    Tool {
-                        name: name.clone(),
-                        description: format!("Tool: {}", name),
-                        parameters: serde_json::json!({}),
-                    }
-                }
+                tool.definition().unwrap_or_else(|| {
+                    Tool::new(
+                        name.clone(),
+                        format!("Tool: {}", name),
+                        serde_json::json!({}),
+                    )
+                })
             })
             .collect()
     }
 
-    /// Execute a tool
+    /// Execute a tool, applying its timeout and retries
     pub async fn execute(
         &self,
         name: &str,
         arguments: &serde_json::Value,
     ) -> Result<serde_json::Value, AiError> {
-        let tool = self
-            .tools
-            .get(name)
-            .ok_or_else(|| AiError::plugin("ToolUsePlugin", format!("Tool {} not found", name)))?;
+        self.execute_counted(name, arguments).await.0
+    }
 
-        tool.execute(name, arguments).await
+    /// Execute a tool, returning the result and the number of attempts
+    async fn execute_counted(
+        &self,
+        name: &str,
+        arguments: &serde_json::Value,
+    ) -> (Result<serde_json::Value, AiError>, u32) {
+        let Some(tool) = self.tools.get(name) else {
+            let error = AiError::plugin("ToolUsePlugin", format!("Tool {} not found", name));
+            return (Err(error), 0);
+        };
+
+        let options = self.options(name);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let result = match options.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, tool.execute(name, arguments))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(AiError::timeout(format!(
+                            "Tool {} timed out after {:?}",
+                            name, timeout
+                        )))
+                    }),
+                None => tool.execute(name, arguments).await,
+            };
+            if result.is_ok() || attempts > options.retries {
+                return (result, attempts);
+            }
+            tracing::debug!("Tool {} failed, retrying (attempt {})", name, attempts);
+            tokio::time::sleep(options.retry_delay).await;
+        }
+    }
+
+    /// Execute a tool call once `approver`, if any, allows it
+    ///
+    /// A denied call fails with the denial reason.
+    pub async fn execute_approved(
+        &self,
+        call: PendingToolCall,
+        approver: Option<&dyn ToolApprover>,
+    ) -> ToolOutcome {
+        let arguments = match approver {
+            None => call.arguments,
+            Some(approver) => match approver.approve(&call).await {
+                ToolApproval::Approve => call.arguments,
+                ToolApproval::Modify { arguments } => arguments,
+                ToolApproval::Deny { reason } => {
+                    let error = AiError::plugin(
+                        "ToolUsePlugin",
+                        format!("Tool call {} was denied: {}", call.name, reason),
+                    );
+                    return ToolOutcome {
+                        arguments: call.arguments,
+                        result: Err(error),
+                        attempts: 0,
+                    };
+                }
+            },
+        };
+        let (result, attempts) = self.execute_counted(&call.name, &arguments).await;
+        ToolOutcome {
+            arguments,
+            result,
+            attempts,
+        }
     }
 }
 
@@ -147,6 +331,124 @@ impl Default for ToolRegistry {
     }
 }
 
+/// Tool call proposed by the model, waiting to be approved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// Decision on a [`PendingToolCall`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolApproval {
+    /// Execute the call as proposed
+    Approve,
+    /// Don't execute the call; the reason is reported to the model
+    Deny { reason: String },
+    /// Execute the call with these arguments instead
+    Modify { arguments: serde_json::Value },
+}
+
+/// Decides whether proposed tool calls may run
+///
+/// Implemented for async closures taking a [`PendingToolCall`], and by
+/// [`ApprovalChannel`] for applications that answer from elsewhere, e.g. a
+/// UI.
+#[async_trait]
+pub trait ToolApprover: Send + Sync {
+    /// Approve, deny or modify `call`; the tool loop waits for the answer
+    async fn approve(&self, call: &PendingToolCall) -> ToolApproval;
+}
+
+#[async_trait]
+impl<F, Fut> ToolApprover for F
+where
+    F: Fn(PendingToolCall) -> Fut + Send + Sync,
+    Fut: Future<Output = ToolApproval> + Send,
+{
+    async fn approve(&self, call: &PendingToolCall) -> ToolApproval {
+        self(call.clone()).await
+    }
+}
+
+/// Tool call waiting for an answer from the application
+#[derive(Debug)]
+pub struct ApprovalRequest {
+    pub call: PendingToolCall,
+    reply: oneshot::Sender<ToolApproval>,
+}
+
+impl ApprovalRequest {
+    /// Answer the request
+    pub fn respond(self, approval: ToolApproval) {
+        // The run may have been cancelled meanwhile
+        let _ = self.reply.send(approval);
+    }
+
+    /// Execute the call as proposed
+    pub fn approve(self) {
+        self.respond(ToolApproval::Approve);
+    }
+
+    /// Refuse the call
+    pub fn deny(self, reason: impl Into<String>) {
+        self.respond(ToolApproval::Deny {
+            reason: reason.into(),
+        });
+    }
+
+    /// Execute the call with other arguments
+    pub fn modify(self, arguments: serde_json::Value) {
+        self.respond(ToolApproval::Modify { arguments });
+    }
+}
+
+/// Approver forwarding each call to a channel read by the application
+///
+/// Calls are denied if the receiving end is dropped or a request is dropped
+/// unanswered.
+///
+/// ```ignore
+/// let (approver, mut requests) = ApprovalChannel::new(16);
+/// let agent = Agent::builder(executor, "gpt-4o").on_tool_call_pending(approver).build();
+/// tokio::spawn(async move {
+///     while let Some(request) = requests.recv().await {
+///         if ask_user(&request.call).await { request.approve() } else { request.deny("Rejected by user") }
+///     }
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct ApprovalChannel {
+    sender: mpsc::Sender<ApprovalRequest>,
+}
+
+impl ApprovalChannel {
+    /// Create an approver and the receiver of its requests
+    pub fn new(buffer: usize) -> (Self, mpsc::Receiver<ApprovalRequest>) {
+        let (sender, receiver) = mpsc::channel(buffer.max(1));
+        (Self { sender }, receiver)
+    }
+}
+
+#[async_trait]
+impl ToolApprover for ApprovalChannel {
+    async fn approve(&self, call: &PendingToolCall) -> ToolApproval {
+        let (reply, answer) = oneshot::channel();
+        let request = ApprovalRequest {
+            call: call.clone(),
+            reply,
+        };
+        let unanswered = ToolApproval::Deny {
+            reason: "No approval was given".to_string(),
+        };
+        if self.sender.send(request).await.is_err() {
+            return unanswered;
+        }
+        answer.await.unwrap_or(unanswered)
+    }
+}
+
 /// Tool use plugin configuration
 #[derive(Debug, Clone)]
 pub struct ToolUsePluginConfig {
@@ -250,8 +552,12 @@ impl Plugin for ToolUsePlugin {
     async fn transform_result(
         &self,
         result: TextResult,
-        _ctx: &RequestContext,
+        ctx: &RequestContext,
     ) -> Result<TextResult, AiError> {
+        // Don't start another tool round once the deadline or budget is spent
+        if result.finish_reason == FinishReason::ToolCalls {
+            ctx.check_budget()?;
+        }
         self.process_tool_calls(result).await
     }
 }
@@ -293,5 +599,54 @@ mod tests {
         let definitions = registry.definitions();
         assert_eq!(definitions.len(), 1);
         assert_eq!(definitions[0].name, "add");
+        assert_eq!(definitions[0].description, "Add two numbers");
+    }
+
+    #[tokio::test]
+    async fn test_tool_options() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let flaky = FunctionTool::new(
+            "flaky",
+            "Fails twice",
+            serde_json::json!({"type": "object"}),
+            move |_args: serde_json::Value| {
+                let calls = counter.clone();
+                async move {
+                    match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 => Err(AiError::provider("busy")),
+                        // Outlives the timeout
+                        1 => {
+                            tokio::time::sleep(Duration::from_secs(60)).await;
+                            Ok(serde_json::json!("late"))
+                        }
+                        _ => Ok(serde_json::json!("ok")),
+                    }
+                }
+            },
+        );
+        let mut registry = ToolRegistry::new();
+        registry.register_with_options(
+            "flaky",
+            Arc::new(flaky),
+            ToolOptions::default()
+                .with_timeout(Duration::from_millis(50))
+                .with_retries(2, Duration::ZERO),
+        );
+
+        let call = PendingToolCall {
+            id: "1".to_string(),
+            name: "flaky".to_string(),
+            arguments: serde_json::json!({}),
+        };
+        let outcome = registry.execute_approved(call.clone(), None).await;
+        assert_eq!(outcome.result.unwrap(), "ok");
+        assert_eq!(outcome.attempts, 3);
+
+        let registry = ToolRegistry::new()
+            .with_default_options(ToolOptions::default().with_on_error(ToolErrorPolicy::Abort));
+        assert_eq!(registry.options("any").on_error, ToolErrorPolicy::Abort);
     }
 }
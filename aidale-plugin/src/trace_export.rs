@@ -0,0 +1,692 @@
+//! Trace export plugin.
+//!
+//! Records a [`Trace`] per request (prompt, completion, tool calls, token
+//! usage, latency and errors) and ships them in batches to an observability
+//! backend from a background task. Exporters for the Langfuse and LangSmith
+//! ingestion APIs are included:
+//!
+//! ```ignore
+//! let exporter = LangfuseExporter::new(public_key, secret_key)
+//!     .with_host("https://langfuse.internal.example.com");
+//! let plugin = Arc::new(
+//!     TraceExportPlugin::new(Arc::new(exporter))
+//!         .with_sample_rate(0.25)
+//!         .with_batch_size(50),
+//! );
+//!
+//! let executor = RuntimeExecutor::builder(provider).plugin(plugin.clone()).finish();
+//! // ...
+//! plugin.flush().await; // before shutdown
+//! ```
+//!
+//! Requests sent with `RuntimeExecutor::chat_completion` bypass the params
+//! hooks and are not traced. Export failures are logged and the batch is
+//! dropped; tracing never fails a request.
+
+use aidale_core::error::AiError;
+use aidale_core::plugin::{Plugin, PluginPhase};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot};
+
+/// A finished request
+#[derive(Debug, Clone, Serialize)]
+pub struct Trace {
+    /// Request ID
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Request ID of the parent request, for child contexts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    pub provider: String,
+    pub model: String,
+    /// Messages sent to the provider
+    pub input: Vec<Message>,
+    /// Generated text, or the generated object for object requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ContentPart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// Milliseconds since the Unix epoch
+    pub start_time_ms: u64,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Request context metadata
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl Trace {
+    /// Milliseconds since the Unix epoch at which the request finished
+    pub fn end_time_ms(&self) -> u64 {
+        self.start_time_ms + self.latency_ms
+    }
+}
+
+/// Destination for traces
+#[async_trait]
+pub trait TraceExporter: Send + Sync + Debug + 'static {
+    /// Export a batch of traces
+    async fn export(&self, traces: Vec<Trace>) -> Result<(), AiError>;
+}
+
+/// Format milliseconds since the Unix epoch as RFC 3339 in UTC
+fn rfc3339(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        ms % 1000
+    )
+}
+
+fn check_status(backend: &str, response: reqwest::Response) -> Result<(), AiError> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(AiError::provider(format!(
+            "{} ingestion failed with status {}",
+            backend, status
+        )))
+    }
+}
+
+/// Exporter for the Langfuse ingestion API
+///
+/// Each trace becomes a Langfuse trace with a single generation carrying
+/// the model, usage and timings.
+#[derive(Clone)]
+pub struct LangfuseExporter {
+    client: reqwest::Client,
+    host: String,
+    public_key: String,
+    secret_key: String,
+}
+
+impl LangfuseExporter {
+    /// Export to Langfuse Cloud with a project's API keys
+    pub fn new(public_key: impl Into<String>, secret_key: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            host: "https://cloud.langfuse.com".to_string(),
+            public_key: public_key.into(),
+            secret_key: secret_key.into(),
+        }
+    }
+
+    /// Export to a self-hosted instance
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn body(traces: &[Trace]) -> serde_json::Value {
+        let mut batch = Vec::with_capacity(traces.len() * 2);
+        for trace in traces {
+            let start = rfc3339(trace.start_time_ms);
+            let end = rfc3339(trace.end_time_ms());
+            batch.push(json!({
+                "id": uuid::Uuid::new_v4().to_string(),
+                "type": "trace-create",
+                "timestamp": start,
+                "body": {
+                    "id": trace.id,
+                    "name": "aidale",
+                    "sessionId": trace.session_id,
+                    "input": trace.input,
+                    "output": trace.output,
+                    "metadata": trace.metadata,
+                    "timestamp": start,
+                },
+            }));
+            batch.push(json!({
+                "id": uuid::Uuid::new_v4().to_string(),
+                "type": "generation-create",
+                "timestamp": start,
+                "body": {
+                    "id": format!("{}-generation", trace.id),
+                    "traceId": trace.id,
+                    "name": trace.provider,
+                    "model": trace.model,
+                    "input": trace.input,
+                    "output": {
+                        "content": trace.output,
+                        "tool_calls": trace.tool_calls,
+                    },
+                    "startTime": start,
+                    "endTime": end,
+                    "usage": trace.usage.as_ref().map(|usage| json!({
+                        "input": usage.prompt_tokens,
+                        "output": usage.completion_tokens,
+                        "total": usage.total_tokens,
+                        "unit": "TOKENS",
+                    })),
+                    "level": if trace.error.is_some() { "ERROR" } else { "DEFAULT" },
+                    "statusMessage": trace.error,
+                },
+            }));
+        }
+        json!({ "batch": batch })
+    }
+}
+
+impl Debug for LangfuseExporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LangfuseExporter")
+            .field("host", &self.host)
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TraceExporter for LangfuseExporter {
+    async fn export(&self, traces: Vec<Trace>) -> Result<(), AiError> {
+        let response = self
+            .client
+            .post(format!("{}/api/public/ingestion", self.host))
+            .basic_auth(&self.public_key, Some(&self.secret_key))
+            .json(&Self::body(&traces))
+            .send()
+            .await?;
+        check_status("Langfuse", response)
+    }
+}
+
+/// Exporter for the LangSmith run ingestion API
+///
+/// Each trace becomes a root `llm` run in the configured project.
+#[derive(Clone)]
+pub struct LangSmithExporter {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    project: String,
+}
+
+impl LangSmithExporter {
+    /// Export to LangSmith's default project with an API key
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: "https://api.smith.langchain.com".to_string(),
+            api_key: api_key.into(),
+            project: "default".to_string(),
+        }
+    }
+
+    /// Set the API endpoint, e.g. for the EU region or self-hosted instances
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set the project runs are recorded in
+    pub fn with_project(mut self, project: impl Into<String>) -> Self {
+        self.project = project.into();
+        self
+    }
+
+    fn body(&self, traces: &[Trace]) -> serde_json::Value {
+        let runs: Vec<_> = traces
+            .iter()
+            .map(|trace| {
+                let start = rfc3339(trace.start_time_ms);
+                // Root runs are ordered by their start time and ID
+                let dotted_order = format!(
+                    "{}000Z{}",
+                    start.replace(['-', ':', '.'], "").trim_end_matches('Z'),
+                    trace.id
+                );
+                let mut metadata = trace.metadata.clone();
+                metadata.insert("ls_provider".to_string(), trace.provider.clone());
+                metadata.insert("ls_model_name".to_string(), trace.model.clone());
+                json!({
+                    "id": trace.id,
+                    "trace_id": trace.id,
+                    "dotted_order": dotted_order,
+                    "name": "aidale",
+                    "run_type": "llm",
+                    "session_name": self.project,
+                    "start_time": start,
+                    "end_time": rfc3339(trace.end_time_ms()),
+                    "inputs": { "messages": trace.input },
+                    "outputs": {
+                        "output": trace.output,
+                        "tool_calls": trace.tool_calls,
+                        "usage_metadata": trace.usage.as_ref().map(|usage| json!({
+                            "input_tokens": usage.prompt_tokens,
+                            "output_tokens": usage.completion_tokens,
+                            "total_tokens": usage.total_tokens,
+                        })),
+                    },
+                    "error": trace.error,
+                    "extra": { "metadata": metadata },
+                })
+            })
+            .collect();
+        json!({ "post": runs })
+    }
+}
+
+impl Debug for LangSmithExporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LangSmithExporter")
+            .field("endpoint", &self.endpoint)
+            .field("project", &self.project)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TraceExporter for LangSmithExporter {
+    async fn export(&self, traces: Vec<Trace>) -> Result<(), AiError> {
+        let response = self
+            .client
+            .post(format!("{}/runs/batch", self.endpoint))
+            .header("x-api-key", &self.api_key)
+            .json(&self.body(&traces))
+            .send()
+            .await?;
+        check_status("LangSmith", response)
+    }
+}
+
+/// Request recorded by the params hooks, waiting for its outcome
+#[derive(Debug)]
+struct PendingTrace {
+    started: Instant,
+    start_time_ms: u64,
+    input: Vec<Message>,
+}
+
+/// Outcome of a traced request
+struct Outcome {
+    model: Option<String>,
+    output: Option<serde_json::Value>,
+    tool_calls: Vec<ContentPart>,
+    usage: Option<Usage>,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Export(Box<Trace>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Plugin recording requests as [`Trace`]s and exporting them in batches
+#[derive(Debug)]
+pub struct TraceExportPlugin {
+    exporter: Arc<dyn TraceExporter>,
+    sample_rate: f64,
+    batch_size: usize,
+    flush_interval: Duration,
+    pending: Mutex<HashMap<String, PendingTrace>>,
+    // Started on the first trace, as construction may happen outside a runtime
+    sender: OnceLock<mpsc::UnboundedSender<Command>>,
+}
+
+impl TraceExportPlugin {
+    /// Trace every request, exporting batches of up to 20 traces at least
+    /// every 5 seconds
+    pub fn new(exporter: Arc<dyn TraceExporter>) -> Self {
+        Self {
+            exporter,
+            sample_rate: 1.0,
+            batch_size: 20,
+            flush_interval: Duration::from_secs(5),
+            pending: Mutex::new(HashMap::new()),
+            sender: OnceLock::new(),
+        }
+    }
+
+    /// Trace only this fraction of requests
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = sample_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Export as soon as this many traces are queued
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Export queued traces at least this often
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Export all queued traces and wait for the export to finish
+    pub async fn flush(&self) {
+        if let Some(sender) = self.sender.get() {
+            let (ack, done) = oneshot::channel();
+            if sender.send(Command::Flush(ack)).is_ok() {
+                let _ = done.await;
+            }
+        }
+    }
+
+    fn sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || rand::random::<f64>() < self.sample_rate
+    }
+
+    fn start(&self, ctx: &RequestContext, input: &[Message]) {
+        if !self.sampled() {
+            return;
+        }
+        let start_time_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.pending.lock().unwrap().insert(
+            ctx.request_id.clone(),
+            PendingTrace {
+                started: Instant::now(),
+                start_time_ms,
+                input: input.to_vec(),
+            },
+        );
+    }
+
+    fn finish(&self, ctx: &RequestContext, outcome: Outcome) {
+        let Some(pending) = self.pending.lock().unwrap().remove(&ctx.request_id) else {
+            return;
+        };
+        let trace = Trace {
+            id: ctx.request_id.clone(),
+            session_id: ctx.session_id.clone(),
+            parent_id: ctx.parent_id.clone(),
+            provider: ctx.provider_id.clone(),
+            model: outcome.model.unwrap_or_else(|| ctx.model.clone()),
+            input: pending.input,
+            output: outcome.output,
+            tool_calls: outcome.tool_calls,
+            usage: outcome.usage,
+            start_time_ms: pending.start_time_ms,
+            latency_ms: pending.started.elapsed().as_millis() as u64,
+            error: outcome.error,
+            metadata: ctx.metadata(),
+        };
+        let sender = self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            tokio::spawn(run_exporter(
+                self.exporter.clone(),
+                receiver,
+                self.batch_size,
+                self.flush_interval,
+            ));
+            sender
+        });
+        let _ = sender.send(Command::Export(Box::new(trace)));
+    }
+
+    fn finish_text(&self, ctx: &RequestContext, result: &TextResult) {
+        self.finish(
+            ctx,
+            Outcome {
+                model: Some(result.model.clone()),
+                output: Some(result.content.clone().into()),
+                tool_calls: result.tool_calls.clone().unwrap_or_default(),
+                usage: Some(result.usage.clone()),
+                error: None,
+            },
+        );
+    }
+}
+
+async fn run_exporter(
+    exporter: Arc<dyn TraceExporter>,
+    mut receiver: mpsc::UnboundedReceiver<Command>,
+    batch_size: usize,
+    flush_interval: Duration,
+) {
+    let mut batch = Vec::new();
+    let mut ticker = tokio::time::interval(flush_interval);
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(Command::Export(trace)) => {
+                    batch.push(*trace);
+                    if batch.len() >= batch_size {
+                        export(exporter.as_ref(), &mut batch).await;
+                    }
+                }
+                Some(Command::Flush(ack)) => {
+                    export(exporter.as_ref(), &mut batch).await;
+                    let _ = ack.send(());
+                }
+                None => {
+                    export(exporter.as_ref(), &mut batch).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => export(exporter.as_ref(), &mut batch).await,
+        }
+    }
+}
+
+async fn export(exporter: &dyn TraceExporter, batch: &mut Vec<Trace>) {
+    if batch.is_empty() {
+        return;
+    }
+    let traces = std::mem::take(batch);
+    let count = traces.len();
+    if let Err(e) = exporter.export(traces).await {
+        tracing::warn!("Failed to export {} traces: {}", count, e);
+    }
+}
+
+#[async_trait]
+impl Plugin for TraceExportPlugin {
+    fn name(&self) -> &str {
+        "trace_export"
+    }
+
+    /// Run last, so traces show the messages other plugins produced
+    fn enforce(&self) -> PluginPhase {
+        PluginPhase::Post
+    }
+
+    async fn transform_params(
+        &self,
+        params: TextParams,
+        ctx: &RequestContext,
+    ) -> Result<TextParams, AiError> {
+        self.start(ctx, &params.messages);
+        Ok(params)
+    }
+
+    async fn transform_object_params(
+        &self,
+        params: ObjectParams,
+        ctx: &RequestContext,
+    ) -> Result<ObjectParams, AiError> {
+        self.start(ctx, &params.messages);
+        Ok(params)
+    }
+
+    async fn on_request_end(
+        &self,
+        ctx: &RequestContext,
+        result: &TextResult,
+    ) -> Result<(), AiError> {
+        self.finish_text(ctx, result);
+        Ok(())
+    }
+
+    async fn on_stream_end(
+        &self,
+        ctx: &RequestContext,
+        result: &TextResult,
+    ) -> Result<(), AiError> {
+        self.finish_text(ctx, result);
+        Ok(())
+    }
+
+    async fn on_object_end(
+        &self,
+        ctx: &RequestContext,
+        result: &ObjectResult,
+    ) -> Result<(), AiError> {
+        self.finish(
+            ctx,
+            Outcome {
+                model: Some(result.model.clone()),
+                output: Some(result.object.clone()),
+                tool_calls: Vec::new(),
+                usage: Some(result.usage.clone()),
+                error: None,
+            },
+        );
+        Ok(())
+    }
+
+    async fn on_error(&self, error: &AiError, ctx: &RequestContext) -> Result<(), AiError> {
+        self.finish(
+            ctx,
+            Outcome {
+                model: None,
+                output: None,
+                tool_calls: Vec::new(),
+                usage: None,
+                error: Some(error.to_string()),
+            },
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::{ChatCompletionStream, Provider};
+    use aidale_core::runtime::RuntimeExecutor;
+
+    /// Answers "pong", failing on "fail"
+    #[derive(Debug)]
+    struct PongProvider;
+
+    #[async_trait]
+    impl Provider for PongProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "pong".to_string(),
+                name: "Pong".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            if matches!(&req.messages[0].content[0], ContentPart::Text { text } if text == "fail") {
+                return Err(AiError::provider("boom"));
+            }
+            Ok(ChatCompletionResponse {
+                id: "resp".to_string(),
+                model: req.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant("pong"),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    prompt_tokens: 3,
+                    completion_tokens: 1,
+                    total_tokens: 4,
+                    ..Default::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            Err(AiError::unsupported("stream"))
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Collector {
+        batches: Mutex<Vec<Vec<Trace>>>,
+    }
+
+    #[async_trait]
+    impl TraceExporter for Collector {
+        async fn export(&self, traces: Vec<Trace>) -> Result<(), AiError> {
+            self.batches.lock().unwrap().push(traces);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_traces_are_batched() {
+        let collector = Arc::new(Collector::default());
+        let plugin = Arc::new(TraceExportPlugin::new(collector.clone()).with_batch_size(2));
+        let executor = RuntimeExecutor::builder(PongProvider)
+            .plugin(plugin.clone())
+            .finish();
+
+        for prompt in ["ping", "fail", "ping"] {
+            let params = TextParams::new(vec![Message::user(prompt)]);
+            let _ = executor.generate_text("m", params).await;
+        }
+        plugin.flush().await;
+
+        let batches = collector.batches.lock().unwrap();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+        let ok = &batches[0][0];
+        assert_eq!(ok.output, Some("pong".into()));
+        assert_eq!(ok.usage.as_ref().unwrap().total_tokens, 4);
+        assert_eq!(batches[0][1].error.as_deref(), Some("Provider error: boom"));
+
+        let body = LangfuseExporter::body(&batches[0]);
+        assert_eq!(body["batch"].as_array().unwrap().len(), 4);
+        assert_eq!(body["batch"][1]["body"]["usage"]["total"], 4);
+        let body = LangSmithExporter::new("key").body(&batches[1]);
+        assert_eq!(body["post"][0]["run_type"], "llm");
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(rfc3339(1_709_251_199_123), "2024-02-29T23:59:59.123Z");
+    }
+}
@@ -0,0 +1,605 @@
+//! Shell and file system tools for coding agents.
+//!
+//! [`ShellTool`] runs commands and [`FsTool`] reads, lists and edits files,
+//! both within the bounds of a shared [`WorkspacePolicy`]:
+//!
+//! - Paths must resolve, after following `..` and symlinks, inside one of the
+//!   allowed prefixes. Relative paths are resolved against the workspace root.
+//! - Commands must be on the allow-list. They are run directly rather than
+//!   through a shell, so pipes, redirects and `;` are plain arguments.
+//!   Arguments that may name paths (absolute, containing `/` or `..`, or
+//!   existing relative to the working directory), as well as `--flag=path`
+//!   and `-Xpath` values, must resolve inside the allowed prefixes too.
+//! - In read-only mode [`FsTool`] refuses to write. The policy can't tell
+//!   whether a command writes, so only allow commands that are safe.
+//!
+//! ```ignore
+//! let policy = Arc::new(
+//!     WorkspacePolicy::new("/work/repo")?
+//!         .allow_commands(["cargo", "git", "ls", "rg"]),
+//! );
+//! registry.register("shell", Arc::new(ShellTool::new(policy.clone())));
+//! registry.register("fs", Arc::new(FsTool::new(policy)));
+//! ```
+
+use crate::code_interpreter::{jailed_command, run_jailed, SandboxLimits};
+use crate::tool_use::ToolExecutor;
+use aidale_core::error::AiError;
+use aidale_core::types::Tool;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What [`ShellTool`] and [`FsTool`] may touch
+#[derive(Debug, Clone)]
+pub struct WorkspacePolicy {
+    root: PathBuf,
+    allowed_paths: Vec<PathBuf>,
+    allowed_commands: HashSet<String>,
+    read_only: bool,
+}
+
+impl WorkspacePolicy {
+    /// Create a policy allowing access to `root` only, and no commands
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, AiError> {
+        let root = Self::canonicalize(root.as_ref())?;
+        Ok(Self {
+            allowed_paths: vec![root.clone()],
+            root,
+            allowed_commands: HashSet::new(),
+            read_only: false,
+        })
+    }
+
+    /// Also allow access below `prefix`
+    pub fn allow_path(mut self, prefix: impl AsRef<Path>) -> Result<Self, AiError> {
+        self.allowed_paths
+            .push(Self::canonicalize(prefix.as_ref())?);
+        Ok(self)
+    }
+
+    /// Allow running `command`, matched against the program's file name
+    pub fn allow_command(mut self, command: impl Into<String>) -> Self {
+        self.allowed_commands.insert(command.into());
+        self
+    }
+
+    /// Allow running each of `commands`
+    pub fn allow_commands<I, S>(self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        commands
+            .into_iter()
+            .fold(self, |policy, command| policy.allow_command(command))
+    }
+
+    /// Forbid writing files
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Directory relative paths are resolved against
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether writing files is forbidden
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn error(message: impl Into<String>) -> AiError {
+        AiError::plugin("WorkspacePolicy", message)
+    }
+
+    fn canonicalize(path: &Path) -> Result<PathBuf, AiError> {
+        path.canonicalize()
+            .map_err(|e| Self::error(format!("Can't resolve {}: {}", path.display(), e)))
+    }
+
+    /// Resolve `path`, checking that it lies within an allowed prefix
+    ///
+    /// The path itself need not exist, but its parent must. Dangling
+    /// symlinks are rejected, since writing through one would create its
+    /// target wherever it points.
+    pub fn check_path(&self, path: &str) -> Result<PathBuf, AiError> {
+        self.resolve(&self.root.join(path))
+    }
+
+    fn resolve(&self, joined: &Path) -> Result<PathBuf, AiError> {
+        let resolved = match joined.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) => {
+                if joined
+                    .symlink_metadata()
+                    .is_ok_and(|meta| meta.file_type().is_symlink())
+                {
+                    return Err(Self::error(format!(
+                        "Dangling symlink: {}",
+                        joined.display()
+                    )));
+                }
+                let name = match joined.components().next_back() {
+                    Some(Component::Normal(name)) => name,
+                    _ => return Err(Self::error(format!("Invalid path: {}", joined.display()))),
+                };
+                let parent = joined.parent().unwrap_or(&self.root);
+                Self::canonicalize(parent)?.join(name)
+            }
+        };
+        if !self
+            .allowed_paths
+            .iter()
+            .any(|prefix| resolved.starts_with(prefix))
+        {
+            return Err(Self::error(format!(
+                "Path is outside the workspace: {}",
+                joined.display()
+            )));
+        }
+        Ok(resolved)
+    }
+
+    /// Check the command argument `arg`, run in `dir`, if it may name a path
+    ///
+    /// For flags such as `--manifest-path=../x` or `-C..`, the value is
+    /// checked as well. Paths that don't exist are resolved lexically from
+    /// their deepest existing ancestor, so `origin/main` passes while
+    /// `-f/etc/passwd` doesn't.
+    pub fn check_argument(&self, arg: &str, dir: &Path) -> Result<(), AiError> {
+        let mut candidates = vec![arg];
+        if let Some(flag) = arg.strip_prefix('-') {
+            if let Some((_, value)) = flag.split_once('=') {
+                candidates.push(value);
+            } else if let Some(flag) = flag.strip_prefix(|c: char| c != '-') {
+                // `-Xvalue`: the value is attached to a short flag
+                candidates.push(flag);
+            }
+        }
+        for candidate in candidates.into_iter().filter(|c| !c.is_empty()) {
+            let path = Path::new(candidate);
+            let joined = dir.join(path);
+            let names_path = path.is_absolute()
+                || candidate.contains('/')
+                || candidate.contains("..")
+                || joined.symlink_metadata().is_ok();
+            if names_path {
+                self.resolve_lexically(&joined)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve `joined` like [`Self::check_path`] without requiring its
+    /// parent to exist
+    fn resolve_lexically(&self, joined: &Path) -> Result<PathBuf, AiError> {
+        let components: Vec<Component> = joined.components().collect();
+        // Longest prefix that exists and resolves
+        let mut split = components.len();
+        while split > 1
+            && components[..split]
+                .iter()
+                .collect::<PathBuf>()
+                .canonicalize()
+                .is_err()
+        {
+            split -= 1;
+        }
+        if split == components.len() {
+            return self.resolve(joined);
+        }
+        // Only the first missing component can be a (dangling) symlink
+        let first_missing: PathBuf = components[..=split].iter().collect();
+        if first_missing.symlink_metadata().is_ok() {
+            return self.resolve(&first_missing);
+        }
+
+        let mut resolved = Self::canonicalize(&components[..split].iter().collect::<PathBuf>())?;
+        for component in &components[split..] {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::Normal(name) => resolved.push(name),
+                _ => {}
+            }
+        }
+        if !self
+            .allowed_paths
+            .iter()
+            .any(|prefix| resolved.starts_with(prefix))
+        {
+            return Err(Self::error(format!(
+                "Path is outside the workspace: {}",
+                joined.display()
+            )));
+        }
+        Ok(resolved)
+    }
+
+    /// Check that `program` is an allowed command
+    pub fn check_command(&self, program: &str) -> Result<(), AiError> {
+        let name = Path::new(program)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(program);
+        // A path could name a different binary than the allowed one
+        if name != program || !self.allowed_commands.contains(name) {
+            return Err(Self::error(format!("Command not allowed: {}", program)));
+        }
+        Ok(())
+    }
+
+    /// Check that files may be written
+    pub fn check_write(&self) -> Result<(), AiError> {
+        if self.read_only {
+            return Err(Self::error("The workspace is read-only"));
+        }
+        Ok(())
+    }
+}
+
+/// Split a command line into words, honoring quotes and backslashes
+fn split_words(line: &str) -> Result<Vec<String>, AiError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                word.extend(chars.next());
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(AiError::plugin("ShellTool", "Unterminated quote"));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Tool running allow-listed commands in the workspace
+#[derive(Debug, Clone)]
+pub struct ShellTool {
+    policy: Arc<WorkspacePolicy>,
+    limits: SandboxLimits,
+}
+
+impl ShellTool {
+    /// Create a tool bound by `policy`
+    pub fn new(policy: Arc<WorkspacePolicy>) -> Self {
+        Self {
+            policy,
+            // Builds and test runs need more than a code snippet
+            limits: SandboxLimits::default()
+                .with_timeout(Duration::from_secs(120))
+                .with_cpu_seconds(120)
+                .with_memory_bytes(4 << 30),
+        }
+    }
+
+    /// Set the resource limits; by default commands get 120 seconds and 4 GiB
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Run `command_line` in `cwd`, or the workspace root
+    pub async fn run(&self, command_line: &str, cwd: Option<&str>) -> Result<Value, AiError> {
+        let words = split_words(command_line)?;
+        let (program, args) = words
+            .split_first()
+            .ok_or_else(|| AiError::plugin("ShellTool", "Empty command"))?;
+        self.policy.check_command(program)?;
+        let dir = match cwd {
+            Some(cwd) => self.policy.check_path(cwd)?,
+            None => self.policy.root.clone(),
+        };
+        for arg in args {
+            self.policy.check_argument(arg, &dir)?;
+        }
+
+        let mut command = jailed_command(program, args, &dir, self.limits);
+        if let Some(home) = std::env::var_os("HOME") {
+            command.env("HOME", home);
+        }
+        run_jailed(command, self.limits)
+            .await
+            .map_err(|e| AiError::plugin("ShellTool", format!("Failed to run {}: {}", program, e)))
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for ShellTool {
+    async fn execute(&self, _name: &str, arguments: &Value) -> Result<Value, AiError> {
+        let command = arguments["command"]
+            .as_str()
+            .ok_or_else(|| AiError::plugin("ShellTool", "Missing `command` argument"))?;
+        self.run(command, arguments["cwd"].as_str()).await
+    }
+
+    fn definition(&self) -> Option<Tool> {
+        let mut commands: Vec<_> = self.policy.allowed_commands.iter().collect();
+        commands.sort();
+        Some(Tool::new(
+            "shell",
+            format!(
+                "Run a command in the workspace and return its stdout, stderr and exit code. \
+                 The command is not run by a shell, so pipes and redirects don't work. \
+                 Allowed commands: {}.",
+                commands
+                    .iter()
+                    .map(|command| command.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string", "description": "Command line, e.g. `cargo test`"},
+                    "cwd": {"type": "string", "description": "Working directory relative to the workspace root"},
+                },
+                "required": ["command"],
+            }),
+        ))
+    }
+}
+
+/// Tool reading, listing and editing files in the workspace
+#[derive(Debug, Clone)]
+pub struct FsTool {
+    policy: Arc<WorkspacePolicy>,
+    max_read_bytes: usize,
+}
+
+impl FsTool {
+    /// Create a tool bound by `policy`
+    pub fn new(policy: Arc<WorkspacePolicy>) -> Self {
+        Self {
+            policy,
+            max_read_bytes: 256 << 10,
+        }
+    }
+
+    /// Set how much of a file is returned (default 256 KiB)
+    pub fn with_max_read_bytes(mut self, max_read_bytes: usize) -> Self {
+        self.max_read_bytes = max_read_bytes;
+        self
+    }
+
+    fn error(message: impl Into<String>) -> AiError {
+        AiError::plugin("FsTool", message)
+    }
+
+    fn io_error(path: &str) -> impl FnOnce(std::io::Error) -> AiError + '_ {
+        move |e| Self::error(format!("{}: {}", path, e))
+    }
+
+    async fn read(&self, path: &str) -> Result<Value, AiError> {
+        let resolved = self.policy.check_path(path)?;
+        let bytes = tokio::fs::read(&resolved)
+            .await
+            .map_err(Self::io_error(path))?;
+        let truncated = bytes.len() > self.max_read_bytes;
+        let content = String::from_utf8_lossy(&bytes[..bytes.len().min(self.max_read_bytes)]);
+        Ok(json!({"content": content, "truncated": truncated}))
+    }
+
+    async fn list(&self, path: &str) -> Result<Value, AiError> {
+        let resolved = self.policy.check_path(path)?;
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&resolved)
+            .await
+            .map_err(Self::io_error(path))?;
+        while let Some(entry) = dir.next_entry().await.map_err(Self::io_error(path))? {
+            let is_dir = entry.file_type().await.is_ok_and(|kind| kind.is_dir());
+            let mut name = entry.file_name().to_string_lossy().into_owned();
+            if is_dir {
+                name.push('/');
+            }
+            entries.push(name);
+        }
+        entries.sort();
+        Ok(json!({"entries": entries}))
+    }
+
+    async fn write(&self, path: &str, content: &str) -> Result<Value, AiError> {
+        self.policy.check_write()?;
+        let resolved = self.policy.check_path(path)?;
+        tokio::fs::write(&resolved, content)
+            .await
+            .map_err(Self::io_error(path))?;
+        Ok(json!({"written": content.len()}))
+    }
+
+    async fn edit(&self, path: &str, old: &str, new: &str) -> Result<Value, AiError> {
+        self.policy.check_write()?;
+        let resolved = self.policy.check_path(path)?;
+        let content = tokio::fs::read_to_string(&resolved)
+            .await
+            .map_err(Self::io_error(path))?;
+        match content.matches(old).count() {
+            1 => {}
+            0 => {
+                return Err(Self::error(format!(
+                    "Text to replace not found in {}",
+                    path
+                )))
+            }
+            n => {
+                return Err(Self::error(format!(
+                    "Text to replace occurs {} times in {}; include more context",
+                    n, path
+                )))
+            }
+        }
+        tokio::fs::write(&resolved, content.replacen(old, new, 1))
+            .await
+            .map_err(Self::io_error(path))?;
+        Ok(json!({"replaced": 1}))
+    }
+
+    async fn delete(&self, path: &str) -> Result<Value, AiError> {
+        self.policy.check_write()?;
+        let resolved = self.policy.check_path(path)?;
+        if self.policy.allowed_paths.contains(&resolved) {
+            return Err(Self::error("Can't delete a workspace root"));
+        }
+        tokio::fs::remove_file(&resolved)
+            .await
+            .map_err(Self::io_error(path))?;
+        Ok(json!({"deleted": path}))
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for FsTool {
+    async fn execute(&self, _name: &str, arguments: &Value) -> Result<Value, AiError> {
+        let arg = |name: &str| {
+            arguments[name]
+                .as_str()
+                .ok_or_else(|| Self::error(format!("Missing `{}` argument", name)))
+        };
+        let path = arg("path")?;
+        match arg("operation")? {
+            "read" => self.read(path).await,
+            "list" => self.list(path).await,
+            "write" => self.write(path, arg("content")?).await,
+            "edit" => self.edit(path, arg("old_text")?, arg("new_text")?).await,
+            "delete" => self.delete(path).await,
+            other => Err(Self::error(format!("Unknown operation: {}", other))),
+        }
+    }
+
+    fn definition(&self) -> Option<Tool> {
+        let operations: &[&str] = if self.policy.read_only {
+            &["read", "list"]
+        } else {
+            &["read", "list", "write", "edit", "delete"]
+        };
+        Some(Tool::new(
+            "fs",
+            "Read, list, write, edit or delete files in the workspace. Paths are relative to \
+             the workspace root. `edit` replaces `old_text`, which must occur exactly once, \
+             with `new_text`.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "operation": {"type": "string", "enum": operations},
+                    "path": {"type": "string"},
+                    "content": {"type": "string", "description": "File content for `write`"},
+                    "old_text": {"type": "string", "description": "Text to replace for `edit`"},
+                    "new_text": {"type": "string", "description": "Replacement for `edit`"},
+                },
+                "required": ["operation", "path"],
+            }),
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_workspace_tools() {
+        let root = std::env::temp_dir().join(format!("aidale-workspace-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let policy = Arc::new(WorkspacePolicy::new(&root).unwrap().allow_command("cat"));
+        let fs = FsTool::new(policy.clone());
+        let shell = ShellTool::new(policy.clone());
+
+        let call = |args: Value| {
+            let fs = fs.clone();
+            async move { fs.execute("fs", &args).await }
+        };
+        call(json!({"operation": "write", "path": "src/lib.rs", "content": "fn a() {}"}))
+            .await
+            .unwrap();
+        call(json!({"operation": "edit", "path": "src/lib.rs", "old_text": "a()", "new_text": "b()"}))
+            .await
+            .unwrap();
+        let listed = call(json!({"operation": "list", "path": "."}))
+            .await
+            .unwrap();
+        assert_eq!(listed["entries"], json!(["src/"]));
+        assert!(call(json!({"operation": "read", "path": "../etc/passwd"}))
+            .await
+            .is_err());
+        assert!(call(json!({"operation": "read", "path": "/etc/passwd"}))
+            .await
+            .is_err());
+
+        let output = shell.run("cat 'lib.rs'", Some("src")).await.unwrap();
+        assert_eq!(output["stdout"], "fn b() {}");
+        assert!(shell.run("rm -rf src", None).await.is_err());
+        assert!(shell.run("/tmp/cat lib.rs", None).await.is_err());
+        assert!(shell.run("cat ../x", None).await.is_err());
+        assert!(shell.run("cat /etc/passwd", None).await.is_err());
+        assert!(shell
+            .run("cat --file=../../etc/passwd", None)
+            .await
+            .is_err());
+        assert!(shell.run("cat src/lib.rs", None).await.is_ok());
+        // Values attached to short flags
+        assert!(shell.run("cat -C..", None).await.is_err());
+        assert!(shell.run("cat -C/", None).await.is_err());
+        assert!(shell.run("cat -f/etc/passwd", None).await.is_err());
+        assert!(shell.run("cat -n src/lib.rs", None).await.is_ok());
+        // Paths that don't exist are checked lexically
+        assert!(shell.run("cat origin/main", None).await.is_ok());
+        assert!(shell.run("cat no/such/../../../x", None).await.is_err());
+
+        // A dangling symlink must not let writes escape the workspace
+        let outside = root.with_extension("outside");
+        std::os::unix::fs::symlink(outside.join("foo"), root.join("link")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        assert!(
+            call(json!({"operation": "write", "path": "link", "content": "x"}))
+                .await
+                .is_err()
+        );
+        assert!(!outside.join("foo").exists());
+        assert!(shell.run("cat link", None).await.is_err());
+        std::fs::remove_file(root.join("link")).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+        assert_eq!(
+            split_words(r#"git commit -m "fix \"it\"" 'a b'"#).unwrap(),
+            ["git", "commit", "-m", r#"fix "it""#, "a b"]
+        );
+
+        let read_only = FsTool::new(Arc::new(
+            WorkspacePolicy::new(&root).unwrap().with_read_only(true),
+        ));
+        let args = json!({"operation": "delete", "path": "src/lib.rs"});
+        assert!(read_only.execute("fs", &args).await.is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
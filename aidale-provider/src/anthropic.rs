@@ -0,0 +1,725 @@
+//! Anthropic provider implementation.
+//!
+//! Talks to the Anthropic Messages API directly. System messages are sent
+//! as the top-level `system` blocks, tool results as `tool_result` blocks of
+//! a user turn. Prompt caching breakpoints set with
+//! [`Message::with_cache_control`] become `cache_control` on the last
+//! content block of the message:
+//!
+//! ```ignore
+//! let messages = vec![
+//!     Message::system(long_instructions).with_cache_control(CacheControl::ephemeral()),
+//!     Message::user("Summarize the contract"),
+//! ];
+//! ```
+//!
+//! Cache reads are reported in [`Usage::cached_tokens`].
+
+use crate::http::{read_json, send_json, sse_events, Wire};
+use aidale_core::error::AiError;
+use aidale_core::http::{HttpClient, HttpRequest};
+use aidale_core::json_repair;
+use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use aidale_core::wire::WireObserver;
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Beta flag of `output_format` JSON Schema outputs and strict tools
+const STRUCTURED_OUTPUTS_BETA: &str = "structured-outputs-2025-11-13";
+/// `max_tokens` is required by the API
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Anthropic provider
+#[derive(Clone)]
+pub struct AnthropicProvider {
+    http: Arc<dyn HttpClient>,
+    api_key: String,
+    api_base: String,
+    info: Arc<ProviderInfo>,
+    wire: Option<Wire>,
+}
+
+impl std::fmt::Debug for AnthropicProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnthropicProvider")
+            .field("api_base", &self.api_base)
+            .field("info", &self.info)
+            .finish()
+    }
+}
+
+impl AnthropicProvider {
+    /// Create a new Anthropic provider with default configuration
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            http: Arc::new(reqwest::Client::new()),
+            api_key: api_key.into(),
+            api_base: ANTHROPIC_API_BASE.to_string(),
+            info: Arc::new(ProviderInfo {
+                id: "anthropic".to_string(),
+                name: "Anthropic".to_string(),
+            }),
+            wire: None,
+        }
+    }
+
+    /// Set API base URL
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
+    /// Use a preconfigured HTTP client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http = Arc::new(client);
+        self
+    }
+
+    /// Send requests through a custom HTTP backend, e.g. a runtime's `fetch`
+    pub fn with_http_backend(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Observe the JSON bodies sent to and received from the API
+    pub fn with_wire_observer(mut self, observer: Arc<dyn WireObserver>) -> Self {
+        self.wire = Some(Wire::new(self.info.id.clone(), observer));
+        self
+    }
+
+    /// Convert a content part to an Anthropic content block
+    ///
+    /// Citations and reasoning of earlier turns are not sent back.
+    fn convert_part(part: &ContentPart) -> Result<Option<serde_json::Value>, AiError> {
+        let block = match part {
+            ContentPart::Text { text } => serde_json::json!({"type": "text", "text": text}),
+            ContentPart::Image { url } => serde_json::json!({
+                "type": "image",
+                "source": {"type": "url", "url": url},
+            }),
+            ContentPart::ImageData { mime_type, data } => serde_json::json!({
+                "type": "image",
+                "source": {"type": "base64", "media_type": mime_type, "data": data},
+            }),
+            ContentPart::File { name, mime, data } => serde_json::json!({
+                "type": "document",
+                "title": name,
+                "source": {"type": "base64", "media_type": mime, "data": data},
+            }),
+            ContentPart::FileRef { .. } => {
+                return Err(AiError::unsupported(
+                    "Anthropic does not support file references",
+                ))
+            }
+            ContentPart::ToolCall {
+                id,
+                name,
+                arguments,
+            } => serde_json::json!({
+                "type": "tool_use",
+                "id": id,
+                "name": name,
+                "input": arguments,
+            }),
+            ContentPart::ToolResult { id, result } => serde_json::json!({
+                "type": "tool_result",
+                "tool_use_id": id,
+                "content": match result {
+                    serde_json::Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                },
+            }),
+            ContentPart::Citation(_) | ContentPart::Reasoning { .. } => return Ok(None),
+        };
+        Ok(Some(block))
+    }
+
+    /// Convert message content to blocks, with the message's cache
+    /// breakpoint on the last block
+    fn convert_content(msg: &Message) -> Result<Vec<serde_json::Value>, AiError> {
+        let mut blocks = Vec::new();
+        for part in &msg.content {
+            blocks.extend(Self::convert_part(part)?);
+        }
+        if let (Some(cache_control), Some(last)) = (&msg.cache_control, blocks.last_mut()) {
+            last["cache_control"] = serde_json::json!(cache_control);
+        }
+        Ok(blocks)
+    }
+
+    /// Build the request body for the messages endpoint
+    fn build_body(req: &ChatCompletionRequest, stream: bool) -> Result<serde_json::Value, AiError> {
+        let mut system = Vec::new();
+        let mut messages = Vec::new();
+        for msg in &req.messages {
+            let content = Self::convert_content(msg)?;
+            let role = match msg.role {
+                Role::System => {
+                    system.extend(content);
+                    continue;
+                }
+                Role::User | Role::Tool => "user",
+                Role::Assistant => "assistant",
+            };
+            messages.push(serde_json::json!({"role": role, "content": content}));
+        }
+
+        let mut body = serde_json::json!({
+            "model": req.model,
+            "messages": messages,
+            "max_tokens": req.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            "stream": stream,
+        });
+
+        if !system.is_empty() {
+            body["system"] = serde_json::Value::Array(system);
+        }
+        if let Some(temperature) = req.temperature {
+            body["temperature"] = temperature.into();
+        }
+        if let Some(top_p) = req.top_p {
+            body["top_p"] = top_p.into();
+        }
+        if let Some(stop) = &req.stop {
+            body["stop_sequences"] = serde_json::json!(stop);
+        }
+        if let Some(user) = &req.user {
+            body["metadata"] = serde_json::json!({"user_id": user});
+        }
+        if let Some(tools) = &req.tools {
+            body["tools"] = tools
+                .iter()
+                .map(|tool| {
+                    let mut value = serde_json::json!({
+                        "name": tool.name,
+                        "description": tool.description,
+                        "input_schema": tool.parameters,
+                    });
+                    if tool.strict {
+                        value["strict"] = true.into();
+                    }
+                    value
+                })
+                .collect();
+        }
+        if let Some(tool_choice) = Self::convert_tool_choice(req) {
+            body["tool_choice"] = tool_choice;
+        }
+        if let Some(ResponseFormat::JsonSchema { schema, .. }) = &req.response_format {
+            body["output_format"] = serde_json::json!({"type": "json_schema", "schema": schema});
+        }
+
+        // Provider-specific parameters such as `thinking`
+        for (key, value) in &req.extra {
+            body[key] = value.clone();
+        }
+
+        Ok(body)
+    }
+
+    /// Map `tool_choice` and `parallel_tool_calls` to Anthropic's
+    /// `tool_choice`, which carries both
+    fn convert_tool_choice(req: &ChatCompletionRequest) -> Option<serde_json::Value> {
+        let mut tool_choice = match &req.tool_choice {
+            Some(ToolChoice::Auto) => serde_json::json!({"type": "auto"}),
+            Some(ToolChoice::None) => serde_json::json!({"type": "none"}),
+            Some(ToolChoice::Required) => serde_json::json!({"type": "any"}),
+            Some(ToolChoice::Named(name)) => serde_json::json!({"type": "tool", "name": name}),
+            None if req.parallel_tool_calls == Some(false) => serde_json::json!({"type": "auto"}),
+            None => return None,
+        };
+        if req.parallel_tool_calls == Some(false) && tool_choice["type"] != "none" {
+            tool_choice["disable_parallel_tool_use"] = true.into();
+        }
+        Some(tool_choice)
+    }
+
+    /// Build an authenticated request to the messages endpoint
+    fn messages_request(
+        &self,
+        headers: &HashMap<String, String>,
+        body: &serde_json::Value,
+    ) -> HttpRequest {
+        let mut request = HttpRequest::post(format!("{}/messages", self.api_base))
+            .with_header("x-api-key", &self.api_key)
+            .with_header("anthropic-version", ANTHROPIC_VERSION);
+        let strict_tools = body["tools"]
+            .as_array()
+            .is_some_and(|tools| tools.iter().any(|tool| tool["strict"] == true));
+        if body.get("output_format").is_some() || strict_tools {
+            request = request.with_header("anthropic-beta", STRUCTURED_OUTPUTS_BETA);
+        }
+        for (name, value) in headers {
+            request = request.with_header(name, value);
+        }
+        request
+    }
+
+    /// Convert an Anthropic stop reason
+    fn convert_stop_reason(reason: &str) -> FinishReason {
+        match reason {
+            "end_turn" | "stop_sequence" | "pause_turn" => FinishReason::Stop,
+            "max_tokens" => FinishReason::Length,
+            "tool_use" => FinishReason::ToolCalls,
+            "refusal" => FinishReason::ContentFilter,
+            other => FinishReason::Other(other.to_string()),
+        }
+    }
+
+    /// Convert an Anthropic response to our ChatCompletionResponse
+    fn convert_response(response: AnthropicResponse) -> ChatCompletionResponse {
+        let content = response
+            .content
+            .into_iter()
+            .filter_map(|block| match block {
+                AnthropicContent::Text { text } => Some(ContentPart::Text { text }),
+                AnthropicContent::Thinking { thinking } => {
+                    Some(ContentPart::Reasoning { text: thinking })
+                }
+                AnthropicContent::ToolUse { id, name, input } => Some(ContentPart::ToolCall {
+                    id,
+                    name,
+                    arguments: input,
+                }),
+                AnthropicContent::Other => None,
+            })
+            .collect();
+
+        ChatCompletionResponse {
+            id: response.id,
+            model: response.model,
+            choices: vec![Choice {
+                index: 0,
+                message: Message {
+                    role: Role::Assistant,
+                    content,
+                    name: None,
+                    cache_control: None,
+                },
+                finish_reason: response
+                    .stop_reason
+                    .as_deref()
+                    .map_or(FinishReason::Stop, Self::convert_stop_reason),
+                logprobs: None,
+                stop_sequence: response.stop_sequence,
+                filtered_categories: Vec::new(),
+            }],
+            usage: response.usage.into_usage(),
+            created: None,
+            system_fingerprint: None,
+            service_tier: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    fn info(&self) -> Arc<ProviderInfo> {
+        self.info.clone()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let body = Self::build_body(&req, false)?;
+        let wire = self.wire.as_ref();
+        let response = send_json(
+            "Anthropic",
+            &*self.http,
+            self.messages_request(&req.headers, &body),
+            &body,
+            wire,
+        )
+        .await?;
+        let response: AnthropicResponse = read_json(response, wire).await?;
+
+        Ok(Self::convert_response(response))
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let body = Self::build_body(&req, true)?;
+        let response = send_json(
+            "Anthropic",
+            &*self.http,
+            self.messages_request(&req.headers, &body),
+            &body,
+            self.wire.as_ref(),
+        )
+        .await?;
+        let wire = self.wire.clone();
+
+        let stream = async_stream::try_stream! {
+            let mut events = Box::pin(sse_events(response, wire));
+            let mut id = String::new();
+            let mut model = req.model.clone();
+            // Prompt usage arrives first, completion usage last
+            let mut usage = AnthropicUsage::default();
+            // Tool call being streamed: (id, name, accumulated arguments)
+            let mut tool_call: Option<(String, String, String)> = None;
+
+            while let Some(event) = events.next().await {
+                let event = event?;
+                let data: serde_json::Value = serde_json::from_str(&event.data)?;
+
+                let mut message_delta = MessageDelta {
+                    role: None,
+                    content: None,
+                    reasoning: None,
+                    tool_calls: None,
+                };
+                let mut finish_reason = None;
+                let mut chunk_usage = None;
+
+                match data["type"].as_str().unwrap_or_default() {
+                    "message_start" => {
+                        let message = &data["message"];
+                        id = message["id"].as_str().unwrap_or_default().to_string();
+                        if let Some(name) = message["model"].as_str() {
+                            model = name.to_string();
+                        }
+                        if let Ok(start) = serde_json::from_value(message["usage"].clone()) {
+                            usage = start;
+                        }
+                        message_delta.role = Some(Role::Assistant);
+                    }
+                    "content_block_start" => {
+                        let block = &data["content_block"];
+                        if block["type"] == "tool_use" {
+                            tool_call = Some((
+                                block["id"].as_str().unwrap_or_default().to_string(),
+                                block["name"].as_str().unwrap_or_default().to_string(),
+                                String::new(),
+                            ));
+                        }
+                        continue;
+                    }
+                    "content_block_delta" => {
+                        let delta = &data["delta"];
+                        match delta["type"].as_str().unwrap_or_default() {
+                            "text_delta" => {
+                                message_delta.content = delta["text"].as_str().map(str::to_string);
+                            }
+                            "thinking_delta" => {
+                                message_delta.reasoning =
+                                    delta["thinking"].as_str().map(str::to_string);
+                            }
+                            "input_json_delta" => {
+                                if let (Some((_, _, arguments)), Some(fragment)) =
+                                    (tool_call.as_mut(), delta["partial_json"].as_str())
+                                {
+                                    arguments.push_str(fragment);
+                                }
+                                continue;
+                            }
+                            _ => continue,
+                        }
+                    }
+                    "content_block_stop" => {
+                        let Some((call_id, name, arguments)) = tool_call.take() else {
+                            continue;
+                        };
+                        let arguments = if arguments.is_empty() {
+                            serde_json::json!({})
+                        } else {
+                            json_repair::parse(&arguments)?
+                        };
+                        message_delta.tool_calls = Some(vec![ContentPart::ToolCall {
+                            id: call_id,
+                            name,
+                            arguments,
+                        }]);
+                    }
+                    "message_delta" => {
+                        finish_reason = data
+                            .pointer("/delta/stop_reason")
+                            .and_then(|r| r.as_str())
+                            .map(Self::convert_stop_reason);
+                        if let Some(output_tokens) = data
+                            .pointer("/usage/output_tokens")
+                            .and_then(|t| t.as_u64())
+                        {
+                            usage.output_tokens = output_tokens as u32;
+                        }
+                        chunk_usage = Some(usage.clone().into_usage());
+                    }
+                    "error" => {
+                        let message = data
+                            .pointer("/error/message")
+                            .and_then(|m| m.as_str())
+                            .unwrap_or("unknown error");
+                        Err(AiError::stream(format!("Anthropic stream error: {}", message)))?;
+                    }
+                    _ => continue,
+                }
+
+                yield ChatCompletionChunk {
+                    id: id.clone(),
+                    model: model.clone(),
+                    choices: vec![ChoiceDelta {
+                        index: 0,
+                        delta: message_delta,
+                        finish_reason,
+                    }],
+                    usage: chunk_usage,
+                    system_fingerprint: None,
+                    service_tier: None,
+                };
+            }
+        };
+
+        Ok(Box::new(Box::pin(stream)))
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        let wire = self.wire.as_ref();
+        let request = self.messages_request(&HashMap::new(), &body);
+        let response = send_json("Anthropic", &*self.http, request, &body, wire).await?;
+        read_json(response, wire).await
+    }
+}
+
+// ============================================================================
+// Anthropic wire types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    id: String,
+    model: String,
+    #[serde(default)]
+    content: Vec<AnthropicContent>,
+    stop_reason: Option<String>,
+    stop_sequence: Option<String>,
+    #[serde(default)]
+    usage: AnthropicUsage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicContent {
+    Text {
+        text: String,
+    },
+    Thinking {
+        thinking: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: u32,
+    #[serde(default)]
+    cache_read_input_tokens: u32,
+}
+
+impl AnthropicUsage {
+    /// `input_tokens` excludes cache reads and writes; the prompt total
+    /// includes them, as OpenAI reports it
+    fn into_usage(self) -> Usage {
+        let prompt_tokens =
+            self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens;
+        Usage {
+            prompt_tokens,
+            completion_tokens: self.output_tokens,
+            total_tokens: prompt_tokens + self.output_tokens,
+            prompt_tokens_details: (self.cache_read_input_tokens > 0).then_some(
+                PromptTokensDetails {
+                    cached_tokens: Some(self.cache_read_input_tokens),
+                    audio_tokens: None,
+                },
+            ),
+            completion_tokens_details: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_control_blocks() {
+        let req = ChatCompletionRequest::new(
+            "claude-sonnet-4-5",
+            vec![
+                Message::system("Long instructions").with_cache_control(CacheControl::ephemeral()),
+                Message::builder(Role::User)
+                    .text("Contract:")
+                    .text("...")
+                    .build()
+                    .with_cache_control(CacheControl::ephemeral_with_ttl("1h")),
+                Message::user("Summarize it"),
+            ],
+        );
+        let body = AnthropicProvider::build_body(&req, false).unwrap();
+
+        assert_eq!(
+            body["system"],
+            serde_json::json!([{
+                "type": "text",
+                "text": "Long instructions",
+                "cache_control": {"type": "ephemeral"},
+            }])
+        );
+        let content = &body["messages"][0]["content"];
+        assert!(content[0].get("cache_control").is_none());
+        assert_eq!(
+            content[1]["cache_control"],
+            serde_json::json!({"type": "ephemeral", "ttl": "1h"})
+        );
+        assert!(body["messages"][1]["content"][0]
+            .get("cache_control")
+            .is_none());
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn test_tool_choice() {
+        let tool_choice = |choice: Option<ToolChoice>, parallel: Option<bool>| {
+            let mut req =
+                ChatCompletionRequest::new("claude-sonnet-4-5", vec![Message::user("Hi")]);
+            req.tools = Some(vec![Tool::new(
+                "get_weather",
+                "Current weather",
+                serde_json::json!({"type": "object"}),
+            )]);
+            req.tool_choice = choice;
+            req.parallel_tool_calls = parallel;
+            AnthropicProvider::build_body(&req, false).unwrap()["tool_choice"].clone()
+        };
+
+        assert_eq!(
+            tool_choice(Some(ToolChoice::Auto), None),
+            serde_json::json!({"type": "auto"})
+        );
+        assert_eq!(
+            tool_choice(Some(ToolChoice::None), None),
+            serde_json::json!({"type": "none"})
+        );
+        assert_eq!(
+            tool_choice(Some(ToolChoice::Required), None),
+            serde_json::json!({"type": "any"})
+        );
+        assert_eq!(
+            tool_choice(Some(ToolChoice::named("get_weather")), None),
+            serde_json::json!({"type": "tool", "name": "get_weather"})
+        );
+        assert_eq!(tool_choice(None, None), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_strict_and_parallel_tools() {
+        let provider = AnthropicProvider::new("test");
+        let mut req = ChatCompletionRequest::new("claude-sonnet-4-5", vec![Message::user("Hi")]);
+        req.tools = Some(vec![
+            Tool::new(
+                "get_weather",
+                "Current weather",
+                serde_json::json!({"type": "object"}),
+            )
+            .with_strict(true),
+            Tool::new(
+                "get_time",
+                "Current time",
+                serde_json::json!({"type": "object"}),
+            ),
+        ]);
+
+        let body = AnthropicProvider::build_body(&req, false).unwrap();
+        assert_eq!(body["tools"][0]["strict"], true);
+        assert!(body["tools"][1].get("strict").is_none());
+        assert!(body.get("tool_choice").is_none());
+        let request = provider.messages_request(&req.headers, &body);
+        assert!(request.headers.contains(&(
+            "anthropic-beta".to_string(),
+            STRUCTURED_OUTPUTS_BETA.to_string()
+        )));
+
+        req.parallel_tool_calls = Some(false);
+        let body = AnthropicProvider::build_body(&req, false).unwrap();
+        assert_eq!(
+            body["tool_choice"],
+            serde_json::json!({"type": "auto", "disable_parallel_tool_use": true})
+        );
+        req.tool_choice = Some(ToolChoice::Required);
+        let body = AnthropicProvider::build_body(&req, false).unwrap();
+        assert_eq!(
+            body["tool_choice"],
+            serde_json::json!({"type": "any", "disable_parallel_tool_use": true})
+        );
+        req.tool_choice = Some(ToolChoice::None);
+        let body = AnthropicProvider::build_body(&req, false).unwrap();
+        assert_eq!(body["tool_choice"], serde_json::json!({"type": "none"}));
+
+        req.tools = Some(vec![Tool::new(
+            "get_time",
+            "Current time",
+            serde_json::json!({"type": "object"}),
+        )]);
+        let body = AnthropicProvider::build_body(&req, false).unwrap();
+        let request = provider.messages_request(&req.headers, &body);
+        assert!(!request
+            .headers
+            .iter()
+            .any(|(name, _)| name == "anthropic-beta"));
+    }
+
+    #[test]
+    fn test_convert_response_with_cache_usage() {
+        let response: AnthropicResponse = serde_json::from_value(serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-5",
+            "content": [
+                {"type": "text", "text": "Checking"},
+                {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {"city": "Oslo"}},
+            ],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": {
+                "input_tokens": 10,
+                "output_tokens": 5,
+                "cache_creation_input_tokens": 0,
+                "cache_read_input_tokens": 2000,
+            },
+        }))
+        .unwrap();
+
+        let converted = AnthropicProvider::convert_response(response);
+        let choice = &converted.choices[0];
+        assert_eq!(choice.finish_reason, FinishReason::ToolCalls);
+        assert!(matches!(
+            &choice.message.content[1],
+            ContentPart::ToolCall { name, arguments, .. }
+                if name == "weather" && arguments["city"] == "Oslo"
+        ));
+        assert_eq!(converted.usage.prompt_tokens, 2010);
+        assert_eq!(converted.usage.cached_tokens(), 2000);
+    }
+}
@@ -0,0 +1,410 @@
+//! Google Cloud OAuth access tokens.
+//!
+//! Vertex AI authenticates with short-lived OAuth tokens instead of API keys.
+//! A [`TokenSource`] knows how to mint a fresh token; [`TokenCache`] wraps one
+//! and refreshes shortly before expiry, so every request sees a valid token.
+//!
+//! [`application_default`] follows the Application Default Credentials
+//! lookup order: `GOOGLE_APPLICATION_CREDENTIALS`, the gcloud well-known
+//! file, then the GCE/Cloud Run metadata server.
+
+use aidale_core::error::AiError;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// OAuth scope covering Vertex AI
+pub const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+const GOOGLE_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const METADATA_TOKEN_URI: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Refresh tokens this long before they expire
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// OAuth access token with its expiry
+#[derive(Clone)]
+pub struct AccessToken {
+    pub token: String,
+    pub expires_at: Instant,
+}
+
+impl AccessToken {
+    /// Whether the token expires within `margin`
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        Instant::now() + margin >= self.expires_at
+    }
+}
+
+impl Debug for AccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Source of fresh OAuth access tokens
+#[async_trait]
+pub trait TokenSource: Send + Sync + Debug {
+    /// Mint a new access token
+    async fn fetch_token(&self, client: &reqwest::Client) -> Result<AccessToken, AiError>;
+
+    /// GCP project the credentials belong to, if known
+    fn project_id(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Caches the token from a [`TokenSource`] and refreshes it before expiry
+#[derive(Debug)]
+pub struct TokenCache {
+    source: Arc<dyn TokenSource>,
+    cached: tokio::sync::Mutex<Option<AccessToken>>,
+}
+
+impl TokenCache {
+    /// Wrap a token source
+    pub fn new(source: Arc<dyn TokenSource>) -> Self {
+        Self {
+            source,
+            cached: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// The underlying token source
+    pub fn source(&self) -> &Arc<dyn TokenSource> {
+        &self.source
+    }
+
+    /// Return a valid token, refreshing it if needed.
+    ///
+    /// Concurrent callers wait on the same refresh instead of each fetching
+    /// their own token.
+    pub async fn token(&self, client: &reqwest::Client) -> Result<String, AiError> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if !token.expires_within(REFRESH_MARGIN) {
+                return Ok(token.token.clone());
+            }
+        }
+
+        tracing::debug!("Refreshing Google access token");
+        let token = self.source.fetch_token(client).await?;
+        let value = token.token.clone();
+        *cached = Some(token);
+        Ok(value)
+    }
+
+    /// Drop the cached token so the next call fetches a new one
+    pub async fn invalidate(&self) {
+        *self.cached.lock().await = None;
+    }
+}
+
+/// Fixed token, e.g. from `gcloud auth print-access-token`
+#[derive(Clone)]
+pub struct StaticToken(String);
+
+impl StaticToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+}
+
+impl Debug for StaticToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StaticToken(..)")
+    }
+}
+
+#[async_trait]
+impl TokenSource for StaticToken {
+    async fn fetch_token(&self, _client: &reqwest::Client) -> Result<AccessToken, AiError> {
+        // Never considered expired; the caller is responsible for rotation
+        Ok(AccessToken {
+            token: self.0.clone(),
+            expires_at: Instant::now() + Duration::from_secs(365 * 24 * 3600),
+        })
+    }
+}
+
+/// Service account key file credentials (signed JWT grant)
+#[derive(Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default)]
+    private_key_id: Option<String>,
+    #[serde(default)]
+    project_id: Option<String>,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    GOOGLE_TOKEN_URI.to_string()
+}
+
+impl ServiceAccountKey {
+    /// Parse a service account key from its JSON contents
+    pub fn from_json(json: &str) -> Result<Self, AiError> {
+        serde_json::from_str(json)
+            .map_err(|e| AiError::configuration(format!("Invalid service account key: {}", e)))
+    }
+
+    /// Load a service account key file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AiError> {
+        Self::from_json(&read_credentials_file(path.as_ref())?)
+    }
+
+    /// Service account email
+    pub fn client_email(&self) -> &str {
+        &self.client_email
+    }
+
+    /// Build the signed JWT assertion for the token endpoint
+    fn assertion(&self) -> Result<String, AiError> {
+        #[derive(serde::Serialize)]
+        struct Claims<'a> {
+            iss: &'a str,
+            scope: &'a str,
+            aud: &'a str,
+            iat: u64,
+            exp: u64,
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claims = Claims {
+            iss: &self.client_email,
+            scope: CLOUD_PLATFORM_SCOPE,
+            aud: &self.token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+        header.kid = self.private_key_id.clone();
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(self.private_key.as_bytes())
+            .map_err(|e| AiError::configuration(format!("Invalid service account key: {}", e)))?;
+
+        jsonwebtoken::encode(&header, &claims, &key)
+            .map_err(|e| AiError::authentication(format!("Failed to sign JWT: {}", e)))
+    }
+}
+
+impl Debug for ServiceAccountKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServiceAccountKey")
+            .field("client_email", &self.client_email)
+            .field("project_id", &self.project_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TokenSource for ServiceAccountKey {
+    async fn fetch_token(&self, client: &reqwest::Client) -> Result<AccessToken, AiError> {
+        let assertion = self.assertion()?;
+        let request = client.post(&self.token_uri).form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ]);
+        request_token(request).await
+    }
+
+    fn project_id(&self) -> Option<String> {
+        self.project_id.clone()
+    }
+}
+
+/// User credentials written by `gcloud auth application-default login`
+#[derive(Clone, Deserialize)]
+pub struct AuthorizedUser {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    #[serde(default)]
+    quota_project_id: Option<String>,
+}
+
+impl AuthorizedUser {
+    /// Parse authorized user credentials from their JSON contents
+    pub fn from_json(json: &str) -> Result<Self, AiError> {
+        serde_json::from_str(json)
+            .map_err(|e| AiError::configuration(format!("Invalid user credentials: {}", e)))
+    }
+}
+
+impl Debug for AuthorizedUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthorizedUser")
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TokenSource for AuthorizedUser {
+    async fn fetch_token(&self, client: &reqwest::Client) -> Result<AccessToken, AiError> {
+        let request = client.post(GOOGLE_TOKEN_URI).form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("refresh_token", self.refresh_token.as_str()),
+        ]);
+        request_token(request).await
+    }
+
+    fn project_id(&self) -> Option<String> {
+        self.quota_project_id.clone()
+    }
+}
+
+/// Default service account of the GCE / Cloud Run / GKE metadata server
+#[derive(Debug, Clone, Default)]
+pub struct MetadataServer;
+
+#[async_trait]
+impl TokenSource for MetadataServer {
+    async fn fetch_token(&self, client: &reqwest::Client) -> Result<AccessToken, AiError> {
+        let request = client
+            .get(METADATA_TOKEN_URI)
+            .header("Metadata-Flavor", "Google");
+        request_token(request).await
+    }
+}
+
+/// Discover Application Default Credentials.
+///
+/// Checks `GOOGLE_APPLICATION_CREDENTIALS`, then the gcloud well-known file,
+/// and falls back to the metadata server.
+pub fn application_default() -> Result<Arc<dyn TokenSource>, AiError> {
+    if let Ok(path) = std::env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+        return credentials_from_file(Path::new(&path));
+    }
+
+    if let Some(path) = well_known_file().filter(|path| path.exists()) {
+        return credentials_from_file(&path);
+    }
+
+    Ok(Arc::new(MetadataServer))
+}
+
+/// Load a credentials file of either supported type
+pub fn credentials_from_file(path: &Path) -> Result<Arc<dyn TokenSource>, AiError> {
+    let json = read_credentials_file(path)?;
+    let kind: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|e| AiError::configuration(format!("Invalid credentials file: {}", e)))?;
+
+    match kind["type"].as_str() {
+        Some("service_account") => Ok(Arc::new(ServiceAccountKey::from_json(&json)?)),
+        Some("authorized_user") => Ok(Arc::new(AuthorizedUser::from_json(&json)?)),
+        other => Err(AiError::configuration(format!(
+            "Unsupported credentials type in {}: {:?}",
+            path.display(),
+            other
+        ))),
+    }
+}
+
+/// gcloud's application default credentials file
+fn well_known_file() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("gcloud"))
+    } else {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/gcloud"))
+    }
+    .map(|dir| dir.join("application_default_credentials.json"))
+}
+
+fn read_credentials_file(path: &Path) -> Result<String, AiError> {
+    std::fs::read_to_string(path).map_err(|e| {
+        AiError::configuration(format!(
+            "Failed to read credentials file {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Send a token request and parse the OAuth token response
+async fn request_token(request: reqwest::RequestBuilder) -> Result<AccessToken, AiError> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        access_token: String,
+        expires_in: u64,
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| AiError::authentication(format!("Token request failed: {}", e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AiError::authentication(format!(
+            "Token request failed ({}): {}",
+            status.as_u16(),
+            body
+        )));
+    }
+
+    let token: TokenResponse = response.json().await?;
+    Ok(AccessToken {
+        token: token.access_token,
+        expires_at: Instant::now() + Duration::from_secs(token.expires_in),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct CountingSource {
+        fetches: AtomicUsize,
+        lifetime: Duration,
+    }
+
+    #[async_trait]
+    impl TokenSource for CountingSource {
+        async fn fetch_token(&self, _client: &reqwest::Client) -> Result<AccessToken, AiError> {
+            let n = self.fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(AccessToken {
+                token: format!("token-{}", n),
+                expires_at: Instant::now() + self.lifetime,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_cache_refresh() {
+        let client = reqwest::Client::new();
+
+        let cache = TokenCache::new(Arc::new(CountingSource {
+            fetches: AtomicUsize::new(0),
+            lifetime: Duration::from_secs(3600),
+        }));
+        assert_eq!(cache.token(&client).await.unwrap(), "token-0");
+        assert_eq!(cache.token(&client).await.unwrap(), "token-0");
+        cache.invalidate().await;
+        assert_eq!(cache.token(&client).await.unwrap(), "token-1");
+
+        // Tokens inside the refresh margin are replaced on every call
+        let short = TokenCache::new(Arc::new(CountingSource {
+            fetches: AtomicUsize::new(0),
+            lifetime: Duration::from_secs(30),
+        }));
+        assert_eq!(short.token(&client).await.unwrap(), "token-0");
+        assert_eq!(short.token(&client).await.unwrap(), "token-1");
+    }
+}
@@ -0,0 +1,262 @@
+//! Shared HTTP helpers for providers that talk to their APIs directly.
+
+use aidale_core::error::{AiError, ApiErrorDetails};
+use aidale_core::http::{HttpClient, HttpRequest, HttpResponse};
+use aidale_core::wire::{WireBody, WireDirection, WireObserver};
+use eventsource_stream::{Event, Eventsource};
+use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Wire observer of a provider, tagged with the provider ID
+#[derive(Clone)]
+pub(crate) struct Wire {
+    provider: String,
+    observer: Arc<dyn WireObserver>,
+}
+
+impl Wire {
+    pub(crate) fn new(provider: impl Into<String>, observer: Arc<dyn WireObserver>) -> Self {
+        Self {
+            provider: provider.into(),
+            observer,
+        }
+    }
+
+    pub(crate) fn observe(&self, direction: WireDirection, status: Option<u16>, body: &str) {
+        let mut body = WireBody::new(self.provider.clone(), direction, body);
+        body.status = status;
+        self.observer.observe(&body);
+    }
+}
+
+/// Map an unsuccessful HTTP response to the matching `AiError` variant
+pub(crate) async fn error_from_response(
+    provider: &str,
+    response: HttpResponse,
+    wire: Option<&Wire>,
+) -> AiError {
+    let status = response.status;
+    let retry_after = retry_after(&response.headers);
+    let request_id = ["x-request-id", "request-id", "x-amzn-requestid", "cf-ray"]
+        .iter()
+        .find_map(|name| response.header(name))
+        .map(str::to_string);
+    let body = response.text().await.unwrap_or_default();
+    if let Some(wire) = wire {
+        wire.observe(WireDirection::Error, Some(status), &body);
+    }
+
+    // Most APIs return {"message": ...} or {"error": {"message": ...}}
+    let json = serde_json::from_str::<serde_json::Value>(&body).ok();
+    let field = |name: &str| {
+        json.as_ref().and_then(|json| {
+            json.get(name)
+                .or_else(|| json.get("error").and_then(|error| error.get(name)))
+                .and_then(|value| value.as_str())
+                .map(str::to_string)
+        })
+    };
+    let raw_message = field("message").unwrap_or(body);
+    let message = format!("{} API error ({}): {}", provider, status, raw_message);
+
+    let mut details = ApiErrorDetails::new(provider.to_lowercase())
+        .with_status(status)
+        .with_message(raw_message);
+    details.error_type = field("type");
+    details.code = field("code").or_else(|| details.error_type.clone());
+    details.request_id = request_id;
+
+    let error = match status {
+        401 | 403 => AiError::authentication(message),
+        404 => AiError::model_not_found(message),
+        408 | 504 => AiError::timeout(message),
+        429 => AiError::rate_limit_after(message, retry_after),
+        400 | 422 => AiError::invalid_request(message),
+        _ => AiError::provider(message),
+    };
+    error.with_details(details)
+}
+
+/// Read the advertised wait time from rate-limit response headers.
+///
+/// Checks `Retry-After` (seconds), `retry-after-ms`, and the OpenAI-style
+/// `x-ratelimit-reset-requests` / `x-ratelimit-reset-tokens` durations
+/// (e.g. `1s`, `6m0s`, `20ms`), taking the longest of them.
+pub(crate) fn retry_after(headers: &HashMap<String, String>) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).map(String::as_str);
+
+    let candidates = [
+        header("retry-after-ms")
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map(|ms| Duration::from_secs_f64(ms.max(0.0) / 1000.0)),
+        header("retry-after")
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map(|secs| Duration::from_secs_f64(secs.max(0.0))),
+        header("x-ratelimit-reset-requests").and_then(parse_reset_duration),
+        header("x-ratelimit-reset-tokens").and_then(parse_reset_duration),
+    ];
+
+    candidates.into_iter().flatten().max()
+}
+
+/// Parse Go-style durations such as `1m30s`, `250ms` or `0.5s`
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" | "" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+        total += number * scale;
+    }
+
+    Some(Duration::from_secs_f64(total))
+}
+
+/// Send a JSON request and check the status code
+pub(crate) async fn send_json(
+    provider: &str,
+    http: &dyn HttpClient,
+    request: HttpRequest,
+    body: &serde_json::Value,
+    wire: Option<&Wire>,
+) -> Result<HttpResponse, AiError> {
+    let body = serde_json::to_string(body)?;
+    if let Some(wire) = wire {
+        wire.observe(WireDirection::Request, None, &body);
+    }
+
+    let request = request
+        .with_header("content-type", "application/json")
+        .with_body(body);
+    send(provider, http, request, wire).await
+}
+
+/// Send a request and check the status code
+pub(crate) async fn send(
+    provider: &str,
+    http: &dyn HttpClient,
+    request: HttpRequest,
+    wire: Option<&Wire>,
+) -> Result<HttpResponse, AiError> {
+    let response = http.send(request).await?;
+    if !response.is_success() {
+        return Err(error_from_response(provider, response, wire).await);
+    }
+    Ok(response)
+}
+
+/// Read and parse a JSON response body
+pub(crate) async fn read_json<T: serde::de::DeserializeOwned>(
+    response: HttpResponse,
+    wire: Option<&Wire>,
+) -> Result<T, AiError> {
+    let status = response.status;
+    let body = response.text().await?;
+    if let Some(wire) = wire {
+        wire.observe(WireDirection::Response, Some(status), &body);
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Turn a streaming HTTP response into a stream of server-sent events
+pub(crate) fn sse_events(
+    response: HttpResponse,
+    wire: Option<Wire>,
+) -> impl Stream<Item = Result<Event, AiError>> + Send {
+    response.body.eventsource().map(move |event| {
+        let event = event.map_err(|e| AiError::stream(e.to_string()))?;
+        if let Some(wire) = &wire {
+            wire.observe(WireDirection::StreamEvent, None, &event.data);
+        }
+        Ok(event)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after_headers() {
+        let mut headers = HashMap::new();
+        headers.insert("retry-after".to_string(), "2".to_string());
+        headers.insert("x-ratelimit-reset-tokens".to_string(), "6m0s".to_string());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(360)));
+
+        assert_eq!(
+            parse_reset_duration("250ms"),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(parse_reset_duration("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_wire_observer_sees_request_and_error_bodies() {
+        use aidale_core::wire::WireLog;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/chat", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read until the end of the JSON body
+            while !request.ends_with(b"}") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = r#"{"error":{"message":"bad temperature","type":"invalid_request_error"}}"#;
+            let response = format!(
+                "HTTP/1.1 400 Bad Request\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let log = WireLog::new();
+        let wire = Wire::new("test", Arc::new(log.clone()));
+        let body = serde_json::json!({"model": "m", "temperature": 9});
+        let client = reqwest::Client::new();
+        let err = send_json("Test", &client, HttpRequest::post(url), &body, Some(&wire))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.status(), Some(400));
+        let bodies = log.bodies();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[0].direction, WireDirection::Request);
+        assert_eq!(bodies[0].body, r#"{"model":"m","temperature":9}"#);
+        assert_eq!(bodies[1].direction, WireDirection::Error);
+        assert_eq!(bodies[1].status, Some(400));
+        assert_eq!(
+            bodies[1].json().unwrap()["error"]["message"],
+            "bad temperature"
+        );
+    }
+}
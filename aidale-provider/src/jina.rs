@@ -0,0 +1,94 @@
+//! Jina AI reranker.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let reranker = JinaReranker::new("jina_...", "jina-reranker-v2-base-multilingual");
+//! let results = reranker
+//!     .rerank(RerankRequest::new("capital of France", documents).with_top_n(3))
+//!     .await?;
+//! ```
+
+use crate::http::Wire;
+use crate::rerank::send_rerank;
+use aidale_core::error::AiError;
+use aidale_core::http::{HttpClient, HttpRequest};
+use aidale_core::rerank::{RerankRequest, RerankResult, Reranker};
+use aidale_core::wire::WireObserver;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+const JINA_API_BASE: &str = "https://api.jina.ai/v1";
+
+/// Reranker backed by the Jina AI rerank API
+#[derive(Clone)]
+pub struct JinaReranker {
+    http: Arc<dyn HttpClient>,
+    api_key: String,
+    api_base: String,
+    model: String,
+    wire: Option<Wire>,
+}
+
+impl std::fmt::Debug for JinaReranker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JinaReranker")
+            .field("api_base", &self.api_base)
+            .field("model", &self.model)
+            .finish()
+    }
+}
+
+impl JinaReranker {
+    /// Create a reranker using `model`
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            http: Arc::new(reqwest::Client::new()),
+            api_key: api_key.into(),
+            api_base: JINA_API_BASE.to_string(),
+            model: model.into(),
+            wire: None,
+        }
+    }
+
+    /// Set API base URL
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
+    /// Use a preconfigured HTTP client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http = Arc::new(client);
+        self
+    }
+
+    /// Send requests through a custom HTTP backend, e.g. a runtime's `fetch`
+    pub fn with_http_backend(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Observe the JSON bodies sent to and received from the API
+    pub fn with_wire_observer(mut self, observer: Arc<dyn WireObserver>) -> Self {
+        self.wire = Some(Wire::new("jina", observer));
+        self
+    }
+}
+
+#[async_trait]
+impl Reranker for JinaReranker {
+    async fn rerank(&self, req: RerankRequest) -> Result<Vec<RerankResult>, AiError> {
+        let request =
+            HttpRequest::post(format!("{}/rerank", self.api_base)).with_bearer_auth(&self.api_key);
+        send_rerank(
+            "jina",
+            &*self.http,
+            request,
+            &self.model,
+            &req,
+            self.wire.as_ref(),
+        )
+        .await
+    }
+}
@@ -1,14 +1,53 @@
 //! # AI Core Providers
 //!
 //! Provider implementations for various AI services.
+//!
+//! On `wasm32` only the providers that send requests through
+//! [`aidale_core::http::HttpClient`] are available: [`AnthropicProvider`],
+//! [`CohereProvider`], [`OpenAiResponsesProvider`], [`PerplexityProvider`],
+//! [`JinaReranker`] and the Qdrant and pgvector stores.
 
+pub mod anthropic;
+pub mod cohere;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod google_auth;
+mod http;
+pub mod jina;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod openai;
+#[cfg(all(feature = "realtime", not(target_arch = "wasm32")))]
+pub mod openai_realtime;
+pub mod openai_responses;
+pub mod perplexity;
+#[cfg(feature = "pgvector")]
+pub mod pgvector;
+#[cfg(feature = "qdrant")]
+pub mod qdrant;
+mod rerank;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod vertex;
 
 // Re-exports
-pub use openai::{OpenAiBuilder, OpenAiProvider};
+pub use anthropic::AnthropicProvider;
+pub use cohere::{CohereProvider, CohereReranker};
+pub use jina::JinaReranker;
+#[cfg(not(target_arch = "wasm32"))]
+pub use openai::{OpenAiBuilder, OpenAiEmbedder, OpenAiModerator, OpenAiProvider};
+#[cfg(all(feature = "realtime", not(target_arch = "wasm32")))]
+pub use openai_realtime::OpenAiRealtimeProvider;
+pub use openai_responses::OpenAiResponsesProvider;
+pub use perplexity::{PerplexityProvider, PerplexitySearch, SearchContextSize, SearchRecency};
+#[cfg(feature = "pgvector")]
+pub use pgvector::{PgExecutor, PgVectorStore};
+#[cfg(feature = "qdrant")]
+pub use qdrant::{PayloadSchema, QdrantStore};
+#[cfg(not(target_arch = "wasm32"))]
+pub use vertex::VertexAiProvider;
 
+#[cfg(not(target_arch = "wasm32"))]
 use aidale_core::error::AiError;
 
+#[cfg(not(target_arch = "wasm32"))]
 /// Create a DeepSeek provider (OpenAI-compatible)
 ///
 /// DeepSeek uses the OpenAI API protocol but with a different endpoint.
@@ -28,3 +67,126 @@ pub fn deepseek(api_key: impl Into<String>) -> Result<OpenAiProvider, AiError> {
         .api_base("https://api.deepseek.com/v1")
         .build_with_id("deepseek", "DeepSeek")
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Create an xAI Grok provider (OpenAI-compatible)
+///
+/// Configured for `https://api.x.ai/v1` with provider ID `xai`.
+///
+/// # Example
+///
+/// ```ignore
+/// use aidale_provider::grok;
+///
+/// let provider = grok("your-api-key")?;
+/// ```
+pub fn grok(api_key: impl Into<String>) -> Result<OpenAiProvider, AiError> {
+    OpenAiProvider::builder()
+        .api_key(api_key)
+        .api_base("https://api.x.ai/v1")
+        .build_with_id("xai", "xAI")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Create a Groq provider (OpenAI-compatible)
+///
+/// Configured for `https://api.groq.com/openai/v1` with provider ID `groq`.
+///
+/// # Example
+///
+/// ```ignore
+/// use aidale_provider::groq;
+///
+/// let provider = groq("your-api-key")?;
+/// ```
+pub fn groq(api_key: impl Into<String>) -> Result<OpenAiProvider, AiError> {
+    OpenAiProvider::builder()
+        .api_key(api_key)
+        .api_base("https://api.groq.com/openai/v1")
+        .build_with_id("groq", "Groq")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Create a Together AI provider (OpenAI-compatible)
+///
+/// Configured for `https://api.together.xyz/v1` with provider ID `together`.
+///
+/// # Example
+///
+/// ```ignore
+/// use aidale_provider::together;
+///
+/// let provider = together("your-api-key")?;
+/// ```
+pub fn together(api_key: impl Into<String>) -> Result<OpenAiProvider, AiError> {
+    OpenAiProvider::builder()
+        .api_key(api_key)
+        .api_base("https://api.together.xyz/v1")
+        .build_with_id("together", "Together AI")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Create an OpenRouter provider (OpenAI-compatible)
+///
+/// Configured for `https://openrouter.ai/api/v1` with provider ID `openrouter`.
+///
+/// # Example
+///
+/// ```ignore
+/// use aidale_provider::openrouter;
+///
+/// let provider = openrouter("your-api-key")?;
+/// ```
+pub fn openrouter(api_key: impl Into<String>) -> Result<OpenAiProvider, AiError> {
+    OpenAiProvider::builder()
+        .api_key(api_key)
+        .api_base("https://openrouter.ai/api/v1")
+        .build_with_id("openrouter", "OpenRouter")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Create a Fireworks AI provider (OpenAI-compatible)
+///
+/// Configured for `https://api.fireworks.ai/inference/v1` with provider ID `fireworks`.
+///
+/// # Example
+///
+/// ```ignore
+/// use aidale_provider::fireworks;
+///
+/// let provider = fireworks("your-api-key")?;
+/// ```
+pub fn fireworks(api_key: impl Into<String>) -> Result<OpenAiProvider, AiError> {
+    OpenAiProvider::builder()
+        .api_key(api_key)
+        .api_base("https://api.fireworks.ai/inference/v1")
+        .build_with_id("fireworks", "Fireworks AI")
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use aidale_core::provider::Provider;
+    use aidale_core::strategy::detect_json_strategy;
+
+    #[test]
+    fn test_compatible_providers() {
+        let providers = [
+            (deepseek("key").unwrap(), "deepseek", "JsonModeStrategy"),
+            (grok("key").unwrap(), "xai", "JsonSchemaStrategy"),
+            (groq("key").unwrap(), "groq", "JsonSchemaStrategy"),
+            (together("key").unwrap(), "together", "JsonModeStrategy"),
+            (
+                openrouter("key").unwrap(),
+                "openrouter",
+                "JsonSchemaStrategy",
+            ),
+            (fireworks("key").unwrap(), "fireworks", "JsonSchemaStrategy"),
+        ];
+        for (provider, id, strategy) in providers {
+            let info = provider.info();
+            assert_eq!(info.id, id);
+            assert_eq!(detect_json_strategy(&info.id).name(), strategy, "{id}");
+        }
+    }
+}
@@ -4,27 +4,249 @@
 //! chat_completion() and stream_chat_completion(). Higher-level abstractions
 //! like generate_text() and generate_object() are handled by the Runtime layer.
 
-use aidale_core::error::AiError;
-use aidale_core::provider::{ChatCompletionStream, Provider};
+mod audio;
+pub mod files;
+
+use crate::http::{read_json, send, send_json, Wire};
+use crate::openai_responses::convert_model_list;
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::embedding::{Embedder, Embedding};
+use aidale_core::error::{AiError, ApiErrorDetails};
+use aidale_core::http::HttpRequest;
+use aidale_core::json_repair;
+use aidale_core::moderation::{ModerationResult, Moderator};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
 use aidale_core::types::*;
-use async_openai::config::OpenAIConfig;
+use aidale_core::wire::{WireDirection, WireObserver};
+use async_openai::config::{Config, OpenAIConfig};
+use async_openai::error::OpenAIError;
 use async_openai::types::{
-    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
-    ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequest,
-    CreateChatCompletionRequestArgs, CreateChatCompletionStreamResponse,
+    ChatCompletionMessageToolCall, ChatCompletionNamedToolChoice,
+    ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+    ChatCompletionRequestMessageContentPartImage, ChatCompletionRequestMessageContentPartText,
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestToolMessageArgs,
+    ChatCompletionRequestUserMessageArgs, ChatCompletionRequestUserMessageContent,
+    ChatCompletionRequestUserMessageContentPart, ChatCompletionTool,
+    ChatCompletionToolChoiceOption, ChatCompletionToolType, CreateChatCompletionRequest,
+    CreateChatCompletionRequestArgs, CreateChatCompletionStreamResponse, FunctionCall,
+    FunctionName, FunctionObject, ImageUrl as OpenAIImageUrl,
     ResponseFormat as OpenAIResponseFormat,
     ResponseFormatJsonSchema as OpenAIResponseFormatJsonSchema,
 };
 use async_openai::Client;
 use async_trait::async_trait;
 use futures::stream::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// OpenAI client configuration with additional HTTP headers.
+///
+/// Wraps `OpenAIConfig` so custom headers (e.g. `OpenAI-Beta`, tenant IDs)
+/// are sent alongside the authentication headers on every call.
+#[derive(Clone, Debug)]
+struct HeaderConfig {
+    inner: OpenAIConfig,
+    headers: HeaderMap,
+}
+
+impl HeaderConfig {
+    /// Return a copy of this config with extra headers merged in
+    fn with_headers(&self, headers: &HashMap<String, String>) -> Result<Self, AiError> {
+        let mut config = self.clone();
+        for (name, value) in headers {
+            let (name, value) = parse_header(name, value)?;
+            config.headers.insert(name, value);
+        }
+        Ok(config)
+    }
+}
+
+impl Config for HeaderConfig {
+    fn headers(&self) -> HeaderMap {
+        let mut headers = self.inner.headers();
+        headers.extend(self.headers.clone());
+        headers
+    }
+
+    fn url(&self, path: &str) -> String {
+        self.inner.url(path)
+    }
+
+    fn query(&self) -> Vec<(&str, &str)> {
+        self.inner.query()
+    }
+
+    fn api_base(&self) -> &str {
+        self.inner.api_base()
+    }
+
+    fn api_key(&self) -> &secrecy::SecretString {
+        self.inner.api_key()
+    }
+}
+
+/// Parse a header name/value pair
+fn parse_header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), AiError> {
+    let header_name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| AiError::configuration(format!("Invalid header name {}: {}", name, e)))?;
+    let header_value = HeaderValue::from_str(value)
+        .map_err(|e| AiError::configuration(format!("Invalid header value for {}: {}", name, e)))?;
+    Ok((header_name, header_value))
+}
+
+/// Build an async-openai client with its built-in retries disabled.
+///
+/// async-openai retries 429 and 5xx responses internally for up to 15
+/// minutes, hiding them from `RetryLayer`; errors are surfaced immediately
+/// instead and classified by [`map_openai_error`].
+fn new_client(config: HeaderConfig, http_client: reqwest::Client) -> Client<HeaderConfig> {
+    let backoff = backoff::ExponentialBackoff {
+        max_elapsed_time: Some(Duration::ZERO),
+        ..Default::default()
+    };
+    Client::build(http_client, config, backoff)
+}
+
+/// Reasoning text of a message or delta: DeepSeek uses `reasoning_content`,
+/// OpenRouter and some other gateways use `reasoning`
+fn reasoning_text(message: &serde_json::Value) -> Option<String> {
+    message
+        .get("reasoning_content")
+        .or_else(|| message.get("reasoning"))
+        .and_then(|value| value.as_str())
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+/// Matched stop sequence of a choice. OpenAI does not report it, but vLLM
+/// (`stop_reason`) and SGLang (`matched_stop`) do; token IDs are ignored.
+fn stop_sequence(choice: &serde_json::Value) -> Option<String> {
+    ["stop_reason", "matched_stop", "stop_sequence"]
+        .iter()
+        .find_map(|key| choice.get(*key).and_then(|value| value.as_str()))
+        .map(str::to_string)
+}
+
+/// Content filter categories flagged as filtered in a choice's Azure
+/// `content_filter_results`
+fn filtered_categories(choice: &serde_json::Value) -> Vec<String> {
+    choice["content_filter_results"]
+        .as_object()
+        .map(|results| {
+            results
+                .iter()
+                .filter(|(_, result)| result["filtered"].as_bool() == Some(true))
+                .map(|(category, _)| category.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A top-level string field of a raw response, e.g. `system_fingerprint`
+fn raw_string(raw: &serde_json::Value, key: &str) -> Option<String> {
+    raw.get(key)
+        .and_then(|value| value.as_str())
+        .map(str::to_string)
+}
+
+/// Whether a model is an OpenAI reasoning model (o-series, GPT-5).
+///
+/// These reject `max_tokens` in favour of `max_completion_tokens` and only
+/// accept default sampling parameters.
+fn is_reasoning_model(model: &str) -> bool {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    ["o1", "o3", "o4", "gpt-5"]
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+}
+
+/// Forward prompt caching breakpoints as Anthropic-style `cache_control` on
+/// the last content block of each annotated message.
+///
+/// OpenAI caches long prompt prefixes automatically; the annotation matters
+/// for OpenAI-compatible gateways such as OpenRouter that route to Anthropic
+/// models.
+fn apply_cache_control(body: &mut serde_json::Value, messages: &[Message]) {
+    let Some(wire_messages) = body["messages"].as_array_mut() else {
+        return;
+    };
+
+    for (wire, message) in wire_messages.iter_mut().zip(messages) {
+        let Some(cache_control) = &message.cache_control else {
+            continue;
+        };
+        let content = &mut wire["content"];
+        if let Some(text) = content.as_str() {
+            *content = serde_json::json!([{"type": "text", "text": text}]);
+        }
+        if let Some(last) = content.as_array_mut().and_then(|parts| parts.last_mut()) {
+            last["cache_control"] = serde_json::json!(cache_control);
+        }
+    }
+}
+
+/// Map an async-openai error to the matching `AiError` variant.
+///
+/// async-openai does not expose the HTTP status, so it is inferred from the
+/// error code and type in the response body. Server errors are the only
+/// ones returned without a body-level type or code.
+fn map_openai_error(provider: &str, err: OpenAIError) -> AiError {
+    match err {
+        OpenAIError::ApiError(api_error) => {
+            let code = api_error.code.clone().or_else(|| api_error.r#type.clone());
+            let message = format!("{} API error: {}", provider, api_error);
+            let mut details = ApiErrorDetails::new(provider).with_message(&api_error.message);
+            details.code = code;
+            details.error_type = api_error.r#type.clone();
+
+            let code = api_error.code.as_deref().unwrap_or_default();
+            let error_type = api_error.r#type.as_deref().unwrap_or_default();
+            let (error, status) = match (code, error_type) {
+                ("", "") => (AiError::provider(message), 500),
+                ("insufficient_quota", _) | (_, "insufficient_quota") => {
+                    (AiError::provider(message), 429)
+                }
+                ("rate_limit_exceeded", _)
+                | (_, "requests" | "tokens" | "rate_limit_exceeded" | "rate_limit_error") => {
+                    (AiError::rate_limit(message), 429)
+                }
+                ("invalid_api_key" | "invalid_authentication", _)
+                | (_, "authentication_error" | "invalid_api_key") => {
+                    (AiError::authentication(message), 401)
+                }
+                (_, "permission_error" | "permission_denied") => {
+                    (AiError::authentication(message), 403)
+                }
+                ("model_not_found", _) | (_, "not_found_error") => {
+                    (AiError::model_not_found(message), 404)
+                }
+                (_, "server_error" | "api_error" | "overloaded_error") => {
+                    (AiError::provider(message), 500)
+                }
+                _ => (AiError::invalid_request(message), 400),
+            };
+            error.with_details(details.with_status(status))
+        }
+        OpenAIError::Reqwest(e) if e.is_timeout() => {
+            AiError::timeout(format!("{} request timed out: {}", provider, e))
+        }
+        OpenAIError::Reqwest(e) => AiError::Network(e),
+        OpenAIError::InvalidArgument(msg) => AiError::invalid_request(msg),
+        OpenAIError::StreamError(e) => AiError::stream(e.to_string()),
+        e => AiError::provider(format!("{} API error: {}", provider, e)),
+    }
+}
 
 /// OpenAI provider using async-openai
 #[derive(Clone)]
 pub struct OpenAiProvider {
-    client: Client<OpenAIConfig>,
+    client: Client<HeaderConfig>,
+    http_client: reqwest::Client,
+    config: HeaderConfig,
     info: Arc<ProviderInfo>,
+    wire: Option<Wire>,
 }
 
 impl std::fmt::Debug for OpenAiProvider {
@@ -38,15 +260,22 @@ impl std::fmt::Debug for OpenAiProvider {
 impl OpenAiProvider {
     /// Create a new OpenAI provider with default configuration
     pub fn new(api_key: impl Into<String>) -> Self {
-        let config = OpenAIConfig::new().with_api_key(api_key);
-        let client = Client::with_config(config);
+        let config = HeaderConfig {
+            inner: OpenAIConfig::new().with_api_key(api_key),
+            headers: HeaderMap::new(),
+        };
+        let http_client = reqwest::Client::new();
+        let client = new_client(config.clone(), http_client.clone());
 
         Self {
             client,
+            http_client,
+            config,
             info: Arc::new(ProviderInfo {
                 id: "openai".to_string(),
                 name: "OpenAI".to_string(),
             }),
+            wire: None,
         }
     }
 
@@ -55,8 +284,68 @@ impl OpenAiProvider {
         OpenAiBuilder::default()
     }
 
+    /// Create an embedder using this provider's client and the given model
+    pub fn embedder(&self, model: impl Into<String>) -> OpenAiEmbedder {
+        OpenAiEmbedder {
+            client: self.client.clone(),
+            model: model.into(),
+            dimensions: None,
+        }
+    }
+
+    /// Create a moderator backed by the OpenAI moderations API
+    pub fn moderator(&self) -> OpenAiModerator {
+        OpenAiModerator {
+            client: self.client.clone(),
+            model: None,
+        }
+    }
+
+    /// Report a request body to the wire observer
+    fn observe_request(&self, body: &serde_json::Value) {
+        if let Some(wire) = &self.wire {
+            wire.observe(WireDirection::Request, None, &body.to_string());
+        }
+    }
+
+    /// Map an error, reporting API error bodies to the wire observer
+    fn map_error(&self, err: OpenAIError) -> AiError {
+        let api_error = match (&self.wire, &err) {
+            (Some(_), OpenAIError::ApiError(api_error)) => Some(api_error.clone()),
+            _ => None,
+        };
+        let err = map_openai_error(&self.info.id, err);
+        if let (Some(wire), Some(api_error)) = (&self.wire, api_error) {
+            let body = serde_json::json!({ "error": api_error });
+            wire.observe(WireDirection::Error, err.status(), &body.to_string());
+        }
+        err
+    }
+
+    /// Get a client for a request, injecting any per-request headers
+    fn client_for(&self, req: &ChatCompletionRequest) -> Result<Client<HeaderConfig>, AiError> {
+        if req.headers.is_empty() {
+            return Ok(self.client.clone());
+        }
+
+        let config = self.config.with_headers(&req.headers)?;
+        Ok(new_client(config, self.http_client.clone()))
+    }
+
     /// Convert our Message type to OpenAI's ChatCompletionRequestMessage
-    fn convert_message(msg: &Message) -> Result<ChatCompletionRequestMessage, AiError> {
+    ///
+    /// A tool message expands into one OpenAI message per tool result.
+    fn convert_message(msg: &Message) -> Result<Vec<ChatCompletionRequestMessage>, AiError> {
+        if msg
+            .content
+            .iter()
+            .any(|part| matches!(part, ContentPart::File { .. } | ContentPart::FileRef { .. }))
+        {
+            return Err(AiError::unsupported(
+                "OpenAI chat completions do not support file attachments; use the Responses API",
+            ));
+        }
+
         // Extract text content from message
         let content = msg
             .content
@@ -76,39 +365,146 @@ impl OpenAiProvider {
                     .map_err(|e| {
                         AiError::provider(format!("Failed to build system message: {}", e))
                     })?;
-                Ok(ChatCompletionRequestMessage::System(msg))
+                Ok(vec![ChatCompletionRequestMessage::System(msg)])
             }
             Role::User => {
+                // Images need the multi-part form; plain text stays a string
+                let content = if msg.content.iter().any(|part| part.image_url().is_some()) {
+                    let parts = msg
+                        .content
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::Text { text } => {
+                                Some(ChatCompletionRequestUserMessageContentPart::Text(
+                                    ChatCompletionRequestMessageContentPartText {
+                                        text: text.clone(),
+                                    },
+                                ))
+                            }
+                            _ => part.image_url().map(|url| {
+                                ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                                    ChatCompletionRequestMessageContentPartImage {
+                                        image_url: OpenAIImageUrl { url, detail: None },
+                                    },
+                                )
+                            }),
+                        })
+                        .collect();
+                    ChatCompletionRequestUserMessageContent::Array(parts)
+                } else {
+                    ChatCompletionRequestUserMessageContent::Text(content)
+                };
                 let msg = ChatCompletionRequestUserMessageArgs::default()
                     .content(content)
                     .build()
                     .map_err(|e| {
                         AiError::provider(format!("Failed to build user message: {}", e))
                     })?;
-                Ok(ChatCompletionRequestMessage::User(msg))
+                Ok(vec![ChatCompletionRequestMessage::User(msg)])
             }
             Role::Assistant => {
-                let msg = async_openai::types::ChatCompletionRequestAssistantMessageArgs::default()
-                    .content(content)
-                    .build()
-                    .map_err(|e| {
-                        AiError::provider(format!("Failed to build assistant message: {}", e))
-                    })?;
-                Ok(ChatCompletionRequestMessage::Assistant(msg))
+                let tool_calls: Vec<_> = msg
+                    .content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::ToolCall {
+                            id,
+                            name,
+                            arguments,
+                        } => Some(ChatCompletionMessageToolCall {
+                            id: id.clone(),
+                            r#type: ChatCompletionToolType::Function,
+                            function: FunctionCall {
+                                name: name.clone(),
+                                arguments: arguments.to_string(),
+                            },
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+
+                let mut builder = ChatCompletionRequestAssistantMessageArgs::default();
+                // Content is optional when the message carries tool calls
+                if !content.is_empty() || tool_calls.is_empty() {
+                    builder.content(content);
+                }
+                if !tool_calls.is_empty() {
+                    builder.tool_calls(tool_calls);
+                }
+                let msg = builder.build().map_err(|e| {
+                    AiError::provider(format!("Failed to build assistant message: {}", e))
+                })?;
+                Ok(vec![ChatCompletionRequestMessage::Assistant(msg)])
             }
-            Role::Tool => {
-                // For tool messages, we'll use a system message as fallback
-                let msg = ChatCompletionRequestSystemMessageArgs::default()
-                    .content(content)
-                    .build()
-                    .map_err(|e| {
-                        AiError::provider(format!("Failed to build tool message: {}", e))
-                    })?;
-                Ok(ChatCompletionRequestMessage::System(msg))
+            Role::Tool => msg
+                .content
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::ToolResult { id, result } => Some((id, result)),
+                    _ => None,
+                })
+                .map(|(id, result)| {
+                    let content = match result {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    ChatCompletionRequestToolMessageArgs::default()
+                        .tool_call_id(id)
+                        .content(content)
+                        .build()
+                        .map(ChatCompletionRequestMessage::Tool)
+                        .map_err(|e| {
+                            AiError::provider(format!("Failed to build tool message: {}", e))
+                        })
+                })
+                .collect(),
+        }
+    }
+
+    /// Convert tool definitions to OpenAI function tools
+    fn convert_tools(tools: &[Tool]) -> Vec<ChatCompletionTool> {
+        tools
+            .iter()
+            .map(|tool| ChatCompletionTool {
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionObject {
+                    name: tool.name.clone(),
+                    description: Some(tool.description.clone()),
+                    parameters: Some(tool.parameters.clone()),
+                    strict: tool.strict.then_some(true),
+                },
+            })
+            .collect()
+    }
+
+    /// Convert our ToolChoice to OpenAI's tool choice option
+    fn convert_tool_choice(tool_choice: &ToolChoice) -> ChatCompletionToolChoiceOption {
+        match tool_choice {
+            ToolChoice::Auto => ChatCompletionToolChoiceOption::Auto,
+            ToolChoice::None => ChatCompletionToolChoiceOption::None,
+            ToolChoice::Required => ChatCompletionToolChoiceOption::Required,
+            ToolChoice::Named(name) => {
+                ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                    r#type: ChatCompletionToolType::Function,
+                    function: FunctionName { name: name.clone() },
+                })
             }
         }
     }
 
+    /// Convert OpenAI tool calls, keeping unparsable arguments as a string
+    fn convert_tool_calls(tool_calls: Vec<ChatCompletionMessageToolCall>) -> Vec<ContentPart> {
+        tool_calls
+            .into_iter()
+            .map(|call| ContentPart::ToolCall {
+                id: call.id,
+                name: call.function.name,
+                arguments: json_repair::parse(&call.function.arguments)
+                    .unwrap_or(serde_json::Value::String(call.function.arguments)),
+            })
+            .collect()
+    }
+
     /// Convert our ResponseFormat to OpenAI's ResponseFormat
     fn convert_response_format(format: &ResponseFormat) -> Result<OpenAIResponseFormat, AiError> {
         match format {
@@ -130,33 +526,99 @@ impl OpenAiProvider {
         }
     }
 
+    /// Build the request body, with annotations async-openai does not model
+    /// and the provider-specific parameters in `extra`
+    fn build_body(
+        &self,
+        req: &ChatCompletionRequest,
+        stream: bool,
+    ) -> Result<serde_json::Value, AiError> {
+        let mut openai_req = self.build_request(req)?;
+        openai_req.stream = stream.then_some(true);
+
+        let mut body = serde_json::to_value(openai_req)?;
+        if req.messages.iter().any(|m| m.cache_control.is_some()) {
+            apply_cache_control(&mut body, &req.messages);
+        }
+
+        // Parameters async-openai doesn't model, such as `repetition_penalty`
+        // for compatible servers; they replace typed fields of the same name
+        for (key, value) in &req.extra {
+            if matches!(key.as_str(), "model" | "messages" | "stream") {
+                return Err(AiError::invalid_request(format!(
+                    "Extra parameter '{}' would replace a field set by the provider",
+                    key
+                )));
+            }
+            body[key] = value.clone();
+        }
+        Ok(body)
+    }
+
     /// Build CreateChatCompletionRequest from our ChatCompletionRequest
     fn build_request(
         &self,
         req: &ChatCompletionRequest,
     ) -> Result<CreateChatCompletionRequest, AiError> {
-        let messages: Result<Vec<_>, _> = req.messages.iter().map(Self::convert_message).collect();
+        let mut messages = Vec::with_capacity(req.messages.len());
+        for msg in &req.messages {
+            messages.extend(Self::convert_message(msg)?);
+        }
 
         let mut builder = CreateChatCompletionRequestArgs::default();
-        builder.model(&req.model).messages(messages?);
+        builder.model(&req.model).messages(messages);
 
-        if let Some(max_tokens) = req.max_tokens {
-            builder.max_tokens(max_tokens);
+        if is_reasoning_model(&req.model) {
+            // Reasoning models reject max_tokens and non-default sampling
+            if let Some(max_tokens) = req.max_tokens {
+                builder.max_completion_tokens(max_tokens);
+            }
+            if req.temperature.is_some()
+                || req.top_p.is_some()
+                || req.frequency_penalty.is_some()
+                || req.presence_penalty.is_some()
+            {
+                tracing::debug!(
+                    "Dropping sampling parameters unsupported by reasoning model {}",
+                    req.model
+                );
+            }
+        } else {
+            if let Some(max_tokens) = req.max_tokens {
+                builder.max_tokens(max_tokens);
+            }
+            if let Some(temperature) = req.temperature {
+                builder.temperature(temperature);
+            }
+            if let Some(top_p) = req.top_p {
+                builder.top_p(top_p);
+            }
+            if let Some(frequency_penalty) = req.frequency_penalty {
+                builder.frequency_penalty(frequency_penalty);
+            }
+            if let Some(presence_penalty) = req.presence_penalty {
+                builder.presence_penalty(presence_penalty);
+            }
         }
-        if let Some(temperature) = req.temperature {
-            builder.temperature(temperature);
+        if let Some(effort) = req.reasoning_effort {
+            builder.reasoning_effort(match effort {
+                ReasoningEffort::Minimal => async_openai::types::ReasoningEffort::Minimal,
+                ReasoningEffort::Low => async_openai::types::ReasoningEffort::Low,
+                ReasoningEffort::Medium => async_openai::types::ReasoningEffort::Medium,
+                ReasoningEffort::High => async_openai::types::ReasoningEffort::High,
+            });
         }
-        if let Some(top_p) = req.top_p {
-            builder.top_p(top_p);
+        if let Some(stop) = &req.stop {
+            builder.stop(stop.clone());
         }
-        if let Some(frequency_penalty) = req.frequency_penalty {
-            builder.frequency_penalty(frequency_penalty);
+        if let Some(tools) = req.tools.as_deref().filter(|tools| !tools.is_empty()) {
+            builder.tools(Self::convert_tools(tools));
         }
-        if let Some(presence_penalty) = req.presence_penalty {
-            builder.presence_penalty(presence_penalty);
+        if let Some(tool_choice) = &req.tool_choice {
+            builder.tool_choice(Self::convert_tool_choice(tool_choice));
         }
-        if let Some(stop) = &req.stop {
-            builder.stop(stop.clone());
+        if let Some(parallel) = req.parallel_tool_calls {
+            builder.parallel_tool_calls(parallel);
         }
         if let Some(response_format) = &req.response_format {
             builder.response_format(Self::convert_response_format(response_format)?);
@@ -164,67 +626,167 @@ impl OpenAiProvider {
         if let Some(stream) = req.stream {
             builder.stream(stream);
         }
+        if let Some(seed) = req.seed {
+            builder.seed(seed);
+        }
+        if let Some(logprobs) = req.logprobs {
+            builder.logprobs(logprobs);
+        }
+        if let Some(top_logprobs) = req.top_logprobs {
+            builder.top_logprobs(top_logprobs);
+        }
+        if let Some(logit_bias) = &req.logit_bias {
+            builder.logit_bias(
+                logit_bias
+                    .iter()
+                    .map(|(token, bias)| (token.clone(), serde_json::Value::from(*bias)))
+                    .collect::<HashMap<_, _>>(),
+            );
+        }
+        if let Some(n) = req.n {
+            builder.n(n);
+        }
+        if let Some(user) = &req.user {
+            builder.user(user);
+        }
 
         builder
             .build()
             .map_err(|e| AiError::provider(format!("Failed to build request: {}", e)))
     }
 
+    /// Convert OpenAI usage, including the token breakdowns
+    fn convert_usage(usage: async_openai::types::CompletionUsage) -> Usage {
+        Usage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+            prompt_tokens_details: usage
+                .prompt_tokens_details
+                .map(|details| PromptTokensDetails {
+                    cached_tokens: details.cached_tokens,
+                    audio_tokens: details.audio_tokens,
+                }),
+            completion_tokens_details: usage.completion_tokens_details.map(|details| {
+                CompletionTokensDetails {
+                    reasoning_tokens: details.reasoning_tokens,
+                    audio_tokens: details.audio_tokens,
+                }
+            }),
+        }
+    }
+
+    /// Convert an OpenAI token log probability
+    fn convert_logprob(token: async_openai::types::ChatCompletionTokenLogprob) -> TokenLogprob {
+        TokenLogprob {
+            token: token.token,
+            logprob: token.logprob,
+            bytes: token.bytes,
+            top_logprobs: token
+                .top_logprobs
+                .into_iter()
+                .map(|top| TopLogprob {
+                    token: top.token,
+                    logprob: top.logprob,
+                    bytes: top.bytes,
+                })
+                .collect(),
+        }
+    }
+
     /// Convert OpenAI response to our ChatCompletionResponse
-    fn convert_response(
-        &self,
-        response: async_openai::types::CreateChatCompletionResponse,
-    ) -> Result<ChatCompletionResponse, AiError> {
+    ///
+    /// Takes the raw JSON so that fields async-openai does not model, such
+    /// as DeepSeek R1's `reasoning_content`, are not lost.
+    fn convert_response(&self, raw: serde_json::Value) -> Result<ChatCompletionResponse, AiError> {
+        let reasoning: Vec<Option<String>> = raw["choices"]
+            .as_array()
+            .map(|choices| {
+                choices
+                    .iter()
+                    .map(|choice| reasoning_text(&choice["message"]))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let stop_sequences: Vec<Option<String>> = raw["choices"]
+            .as_array()
+            .map(|choices| choices.iter().map(stop_sequence).collect())
+            .unwrap_or_default();
+        let filtered: Vec<Vec<String>> = raw["choices"]
+            .as_array()
+            .map(|choices| choices.iter().map(filtered_categories).collect())
+            .unwrap_or_default();
+        let system_fingerprint = raw_string(&raw, "system_fingerprint");
+        let service_tier = raw_string(&raw, "service_tier");
+        let response: async_openai::types::CreateChatCompletionResponse =
+            serde_json::from_value(raw)?;
+
         let choices = response
             .choices
             .into_iter()
-            .map(|choice| {
-                let message = Message {
-                    role: match choice.message.role {
-                        async_openai::types::Role::System => Role::System,
-                        async_openai::types::Role::User => Role::User,
-                        async_openai::types::Role::Assistant => Role::Assistant,
-                        async_openai::types::Role::Tool => Role::Tool,
-                        _ => Role::Assistant,
-                    },
-                    content: vec![ContentPart::Text {
-                        text: choice.message.content.unwrap_or_default(),
-                    }],
-                    name: None, // OpenAI doesn't return name in responses
-                };
+            .zip(reasoning.into_iter().chain(std::iter::repeat(None)))
+            .zip(stop_sequences.into_iter().chain(std::iter::repeat(None)))
+            .zip(filtered.into_iter().chain(std::iter::repeat(Vec::new())))
+            .map(
+                |(((choice, reasoning), stop_sequence), filtered_categories)| {
+                    let mut content = Vec::new();
+                    if let Some(text) = reasoning {
+                        content.push(ContentPart::Reasoning { text });
+                    }
+                    let tool_calls = choice.message.tool_calls.unwrap_or_default();
+                    if choice.message.content.is_some() || tool_calls.is_empty() {
+                        content.push(ContentPart::Text {
+                            text: choice.message.content.unwrap_or_default(),
+                        });
+                    }
+                    content.extend(Self::convert_tool_calls(tool_calls));
 
-                let finish_reason = choice
-                    .finish_reason
-                    .map_or(FinishReason::Stop, |r| match r {
-                        async_openai::types::FinishReason::Stop => FinishReason::Stop,
-                        async_openai::types::FinishReason::Length => FinishReason::Length,
-                        async_openai::types::FinishReason::ToolCalls => FinishReason::ToolCalls,
-                        async_openai::types::FinishReason::ContentFilter => {
-                            FinishReason::ContentFilter
-                        }
-                        _ => FinishReason::Other("unknown".to_string()),
-                    });
+                    let message = Message {
+                        role: match choice.message.role {
+                            async_openai::types::Role::System => Role::System,
+                            async_openai::types::Role::User => Role::User,
+                            async_openai::types::Role::Assistant => Role::Assistant,
+                            async_openai::types::Role::Tool => Role::Tool,
+                            _ => Role::Assistant,
+                        },
+                        content,
+                        name: None, // OpenAI doesn't return name in responses
+                        cache_control: None,
+                    };
 
-                Choice {
-                    index: choice.index,
-                    message,
-                    finish_reason,
-                }
-            })
+                    let finish_reason =
+                        choice
+                            .finish_reason
+                            .map_or(FinishReason::Stop, |r| match r {
+                                async_openai::types::FinishReason::Stop => FinishReason::Stop,
+                                async_openai::types::FinishReason::Length => FinishReason::Length,
+                                async_openai::types::FinishReason::ToolCalls => {
+                                    FinishReason::ToolCalls
+                                }
+                                async_openai::types::FinishReason::ContentFilter => {
+                                    FinishReason::ContentFilter
+                                }
+                                _ => FinishReason::Other("unknown".to_string()),
+                            });
+
+                    let logprobs = choice
+                        .logprobs
+                        .and_then(|logprobs| logprobs.content)
+                        .map(|tokens| tokens.into_iter().map(Self::convert_logprob).collect());
+
+                    Choice {
+                        index: choice.index,
+                        message,
+                        finish_reason,
+                        logprobs,
+                        stop_sequence,
+                        filtered_categories,
+                    }
+                },
+            )
             .collect();
 
-        let usage = response.usage.map_or(
-            Usage {
-                prompt_tokens: 0,
-                completion_tokens: 0,
-                total_tokens: 0,
-            },
-            |u| Usage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            },
-        );
+        let usage = response.usage.map_or(Usage::default(), Self::convert_usage);
 
         Ok(ChatCompletionResponse {
             id: response.id,
@@ -232,17 +794,31 @@ impl OpenAiProvider {
             choices,
             usage,
             created: Some(response.created as u64),
+            system_fingerprint,
+            service_tier,
         })
     }
 
     /// Convert OpenAI stream chunk to our ChatCompletionChunk
-    fn convert_stream_chunk(
-        response: CreateChatCompletionStreamResponse,
-    ) -> Result<ChatCompletionChunk, AiError> {
+    fn convert_stream_chunk(raw: serde_json::Value) -> Result<ChatCompletionChunk, AiError> {
+        let reasoning: Vec<Option<String>> = raw["choices"]
+            .as_array()
+            .map(|choices| {
+                choices
+                    .iter()
+                    .map(|choice| reasoning_text(&choice["delta"]))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let system_fingerprint = raw_string(&raw, "system_fingerprint");
+        let service_tier = raw_string(&raw, "service_tier");
+        let response: CreateChatCompletionStreamResponse = serde_json::from_value(raw)?;
+
         let choices = response
             .choices
             .into_iter()
-            .map(|choice| {
+            .zip(reasoning.into_iter().chain(std::iter::repeat(None)))
+            .map(|(choice, reasoning)| {
                 let delta = MessageDelta {
                     role: choice.delta.role.as_ref().map(|r| match r {
                         async_openai::types::Role::System => Role::System,
@@ -252,6 +828,7 @@ impl OpenAiProvider {
                         _ => Role::Assistant,
                     }),
                     content: choice.delta.content,
+                    reasoning,
                     tool_calls: None,
                 };
 
@@ -275,7 +852,9 @@ impl OpenAiProvider {
             id: response.id,
             model: response.model,
             choices,
-            usage: None,
+            usage: response.usage.map(Self::convert_usage),
+            system_fingerprint,
+            service_tier,
         })
     }
 }
@@ -290,15 +869,19 @@ impl Provider for OpenAiProvider {
         &self,
         req: ChatCompletionRequest,
     ) -> Result<ChatCompletionResponse, AiError> {
-        let openai_req = self.build_request(&req)?;
+        let body = self.build_body(&req, false)?;
+        self.observe_request(&body);
 
-        let response = self
-            .client
+        let response: serde_json::Value = self
+            .client_for(&req)?
             .chat()
-            .create(openai_req)
+            .create_byot(body)
             .await
-            .map_err(|e| AiError::provider(format!("OpenAI API error: {}", e)))?;
+            .map_err(|e| self.map_error(e))?;
 
+        if let Some(wire) = &self.wire {
+            wire.observe(WireDirection::Response, Some(200), &response.to_string());
+        }
         self.convert_response(response)
     }
 
@@ -306,20 +889,26 @@ impl Provider for OpenAiProvider {
         &self,
         req: ChatCompletionRequest,
     ) -> Result<Box<ChatCompletionStream>, AiError> {
-        let mut openai_req = self.build_request(&req)?;
-        openai_req.stream = Some(true);
+        let body = self.build_body(&req, true)?;
+        self.observe_request(&body);
 
         let stream = self
-            .client
+            .client_for(&req)?
             .chat()
-            .create_stream(openai_req)
+            .create_stream_byot::<_, serde_json::Value>(body)
             .await
-            .map_err(|e| AiError::provider(format!("OpenAI API error: {}", e)))?;
+            .map_err(|e| self.map_error(e))?;
 
         // Convert OpenAI stream to our ChatCompletionStream
-        let chat_stream = stream.map(|result| match result {
-            Ok(response) => Self::convert_stream_chunk(response),
-            Err(e) => Err(AiError::provider(format!("Stream error: {}", e))),
+        let provider = self.clone();
+        let chat_stream = stream.map(move |result| match result {
+            Ok(response) => {
+                if let Some(wire) = &provider.wire {
+                    wire.observe(WireDirection::StreamEvent, None, &response.to_string());
+                }
+                Self::convert_stream_chunk(response)
+            }
+            Err(e) => Err(provider.map_error(e)),
         });
 
         Ok(Box::new(chat_stream)
@@ -327,6 +916,187 @@ impl Provider for OpenAiProvider {
                 dyn Stream<Item = Result<ChatCompletionChunk, AiError>> + Send + Unpin,
             >)
     }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        self.observe_request(&body);
+        let response: serde_json::Value = self
+            .client
+            .chat()
+            .create_byot(body)
+            .await
+            .map_err(|e| self.map_error(e))?;
+
+        if let Some(wire) = &self.wire {
+            wire.observe(WireDirection::Response, Some(200), &response.to_string());
+        }
+        Ok(response)
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        let config = self.config.with_headers(&req.headers)?;
+        let mut request = HttpRequest::new(req.method, config.url(&req.path));
+        for (name, value) in config.headers().iter() {
+            if let Ok(value) = value.to_str() {
+                request = request.with_header(name.as_str(), value);
+            }
+        }
+
+        let wire = self.wire.as_ref();
+        let response = match &req.body {
+            Some(body) => send_json(&self.info.id, &self.http_client, request, body, wire).await?,
+            None => send(&self.info.id, &self.http_client, request, wire).await?,
+        };
+        read_json(response, wire).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        self.create_speech(req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        self.create_transcription(req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        let response: serde_json::Value = self
+            .client
+            .models()
+            .list_byot()
+            .await
+            .map_err(|e| self.map_error(e))?;
+        Ok(convert_model_list(&response))
+    }
+}
+
+/// Embedder backed by the OpenAI embeddings API
+#[derive(Clone)]
+pub struct OpenAiEmbedder {
+    client: Client<HeaderConfig>,
+    model: String,
+    dimensions: Option<u32>,
+}
+
+impl std::fmt::Debug for OpenAiEmbedder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAiEmbedder")
+            .field("model", &self.model)
+            .field("dimensions", &self.dimensions)
+            .finish()
+    }
+}
+
+impl OpenAiEmbedder {
+    /// Request embeddings with a reduced number of dimensions
+    ///
+    /// Only supported by `text-embedding-3` and later models.
+    pub fn with_dimensions(mut self, dimensions: u32) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAiEmbedder {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AiError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut builder = async_openai::types::CreateEmbeddingRequestArgs::default();
+        builder.model(&self.model).input(texts.to_vec());
+        if let Some(dimensions) = self.dimensions {
+            builder.dimensions(dimensions);
+        }
+        let request = builder
+            .build()
+            .map_err(|e| AiError::provider(format!("Failed to build request: {}", e)))?;
+
+        let response = self
+            .client
+            .embeddings()
+            .create(request)
+            .await
+            .map_err(|e| map_openai_error("openai", e))?;
+
+        let mut data = response.data;
+        data.sort_by_key(|embedding| embedding.index);
+        Ok(data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+}
+
+/// Moderator backed by the OpenAI moderations API
+#[derive(Clone)]
+pub struct OpenAiModerator {
+    client: Client<HeaderConfig>,
+    model: Option<String>,
+}
+
+impl std::fmt::Debug for OpenAiModerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAiModerator")
+            .field("model", &self.model)
+            .finish()
+    }
+}
+
+impl OpenAiModerator {
+    /// Use a specific moderation model (e.g. `omni-moderation-latest`)
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+}
+
+#[async_trait]
+impl Moderator for OpenAiModerator {
+    async fn moderate(&self, text: &str) -> Result<ModerationResult, AiError> {
+        let mut builder = async_openai::types::CreateModerationRequestArgs::default();
+        builder.input(text);
+        if let Some(model) = &self.model {
+            builder.model(model);
+        }
+        let request = builder
+            .build()
+            .map_err(|e| AiError::provider(format!("Failed to build request: {}", e)))?;
+
+        let response = self
+            .client
+            .moderations()
+            .create(request)
+            .await
+            .map_err(|e| map_openai_error("openai", e))?;
+
+        let mut result = ModerationResult::default();
+        for item in response.results {
+            result.flagged |= item.flagged;
+
+            // Category structs serialize to {"category/name": value} maps
+            if let serde_json::Value::Object(categories) = serde_json::to_value(&item.categories)? {
+                for (name, value) in categories {
+                    if value == serde_json::Value::Bool(true) && !result.categories.contains(&name)
+                    {
+                        result.categories.push(name);
+                    }
+                }
+            }
+            if let serde_json::Value::Object(scores) = serde_json::to_value(&item.category_scores)?
+            {
+                for (name, value) in scores {
+                    let score = value.as_f64().unwrap_or_default() as f32;
+                    let entry = result.scores.entry(name).or_insert(0.0);
+                    *entry = entry.max(score);
+                }
+            }
+        }
+
+        Ok(result)
+    }
 }
 
 /// Builder for OpenAI provider with custom configuration
@@ -335,6 +1105,13 @@ pub struct OpenAiBuilder {
     api_key: Option<String>,
     api_base: Option<String>,
     org_id: Option<String>,
+    headers: HashMap<String, String>,
+    proxy: Option<String>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    accept_invalid_certs: bool,
+    http_client: Option<reqwest::Client>,
+    wire_observer: Option<Arc<dyn WireObserver>>,
 }
 
 impl OpenAiBuilder {
@@ -356,31 +1133,65 @@ impl OpenAiBuilder {
         self
     }
 
-    /// Build the provider
-    pub fn build(self) -> Result<OpenAiProvider, AiError> {
-        let api_key = self
-            .api_key
-            .ok_or_else(|| AiError::configuration("API key is required"))?;
+    /// Add a header sent with every request (e.g. `OpenAI-Beta`, tenant IDs)
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
 
-        let mut config = OpenAIConfig::new().with_api_key(api_key);
+    /// Route all requests through an HTTP/HTTPS/SOCKS proxy
+    ///
+    /// Ignored when a custom client is set with [`Self::http_client`].
+    pub fn proxy(mut self, proxy_url: impl Into<String>) -> Self {
+        self.proxy = Some(proxy_url.into());
+        self
+    }
 
-        if let Some(api_base) = self.api_base {
-            config = config.with_api_base(api_base);
-        }
+    /// Set the total request timeout
+    ///
+    /// Ignored when a custom client is set with [`Self::http_client`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
 
-        if let Some(org_id) = self.org_id {
-            config = config.with_org_id(org_id);
-        }
+    /// Set the connection timeout
+    ///
+    /// Ignored when a custom client is set with [`Self::http_client`].
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
 
-        let client = Client::with_config(config);
+    /// Accept invalid TLS certificates (e.g. for intercepting corporate proxies)
+    ///
+    /// Ignored when a custom client is set with [`Self::http_client`].
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
 
-        Ok(OpenAiProvider {
-            client,
-            info: Arc::new(ProviderInfo {
-                id: "openai".to_string(),
-                name: "OpenAI".to_string(),
-            }),
-        })
+    /// Use a preconfigured HTTP client
+    ///
+    /// This gives full control over TLS, proxies and connection pooling.
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Observe the JSON bodies sent to and received from the API
+    ///
+    /// Request bodies are observed exactly as sent. Responses are parsed by
+    /// async-openai first, so response bodies are re-serialized JSON and
+    /// error bodies are rebuilt from the parsed `error` object.
+    pub fn wire_observer(mut self, observer: Arc<dyn WireObserver>) -> Self {
+        self.wire_observer = Some(observer);
+        self
+    }
+
+    /// Build the provider
+    pub fn build(self) -> Result<OpenAiProvider, AiError> {
+        self.build_with_id("openai", "OpenAI")
     }
 
     /// Build a provider with a custom provider ID and name
@@ -396,24 +1207,652 @@ impl OpenAiBuilder {
             .api_key
             .ok_or_else(|| AiError::configuration("API key is required"))?;
 
-        let mut config = OpenAIConfig::new().with_api_key(api_key);
+        let mut inner = OpenAIConfig::new().with_api_key(api_key);
 
         if let Some(api_base) = self.api_base {
-            config = config.with_api_base(api_base);
+            inner = inner.with_api_base(api_base);
         }
 
         if let Some(org_id) = self.org_id {
-            config = config.with_org_id(org_id);
+            inner = inner.with_org_id(org_id);
+        }
+
+        let config = HeaderConfig {
+            inner,
+            headers: HeaderMap::new(),
         }
+        .with_headers(&self.headers)?;
+
+        let http_client = match self.http_client {
+            Some(client) => client,
+            None => {
+                let mut builder = reqwest::Client::builder();
+
+                if let Some(proxy) = self.proxy {
+                    let proxy = reqwest::Proxy::all(&proxy).map_err(|e| {
+                        AiError::configuration(format!("Invalid proxy URL {}: {}", proxy, e))
+                    })?;
+                    builder = builder.proxy(proxy);
+                }
+
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+
+                if self.accept_invalid_certs {
+                    builder = builder.danger_accept_invalid_certs(true);
+                }
+
+                builder.build().map_err(|e| {
+                    AiError::configuration(format!("Failed to build HTTP client: {}", e))
+                })?
+            }
+        };
 
-        let client = Client::with_config(config);
+        let client = new_client(config.clone(), http_client.clone());
+        let provider_id = provider_id.into();
 
         Ok(OpenAiProvider {
             client,
+            http_client,
+            config,
+            wire: self
+                .wire_observer
+                .map(|observer| Wire::new(provider_id.clone(), observer)),
             info: Arc::new(ProviderInfo {
-                id: provider_id.into(),
+                id: provider_id,
                 name: provider_name.into(),
             }),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::error::ApiError;
+
+    fn api_error(error_type: Option<&str>, code: Option<&str>) -> OpenAIError {
+        OpenAIError::ApiError(ApiError {
+            message: "boom".to_string(),
+            r#type: error_type.map(str::to_string),
+            param: None,
+            code: code.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let provider = OpenAiProvider::new("test");
+        let req = ChatCompletionRequest::new(
+            "anthropic/claude-sonnet-4",
+            vec![
+                Message::system("Long reference document")
+                    .with_cache_control(CacheControl::ephemeral()),
+                Message::user("Question"),
+            ],
+        );
+
+        let body = provider.build_body(&req, false).unwrap();
+        assert_eq!(
+            body["messages"][0]["content"],
+            serde_json::json!([{
+                "type": "text",
+                "text": "Long reference document",
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+        assert_eq!(body["messages"][1]["content"], "Question");
+    }
+
+    #[test]
+    fn test_extra_parameters() {
+        let provider = OpenAiProvider::new("test");
+        let req = ChatCompletionRequest::new("qwen2.5", vec![Message::user("Hi")])
+            .with_temperature(0.5)
+            .with_extra("repetition_penalty", serde_json::json!(1.1))
+            .with_extra("temperature", serde_json::json!(0.2));
+
+        let body = provider.build_body(&req, false).unwrap();
+        assert_eq!(body["repetition_penalty"], 1.1);
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(body["model"], "qwen2.5");
+
+        let req = req.with_extra("stream", serde_json::json!(true));
+        assert!(matches!(
+            provider.build_body(&req, false),
+            Err(AiError::InvalidRequest { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sampling_parameters() {
+        let provider = OpenAiProvider::new("test");
+        let mut req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("Hi")]);
+        req.frequency_penalty = Some(0.5);
+        req.presence_penalty = Some(-0.5);
+        req.seed = Some(42);
+        req.logprobs = Some(true);
+        req.top_logprobs = Some(3);
+        req.logit_bias = Some(HashMap::from([("50256".to_string(), -100)]));
+        req.n = Some(2);
+        req.user = Some("user-123".to_string());
+
+        let body = provider.build_body(&req, false).unwrap();
+        assert_eq!(body["frequency_penalty"], 0.5);
+        assert_eq!(body["presence_penalty"], -0.5);
+        assert_eq!(body["seed"], 42);
+        assert_eq!(body["logprobs"], true);
+        assert_eq!(body["top_logprobs"], 3);
+        assert_eq!(body["logit_bias"], serde_json::json!({"50256": -100}));
+        assert_eq!(body["n"], 2);
+        assert_eq!(body["user"], "user-123");
+
+        // Reasoning models keep the parameters that don't affect sampling
+        req.model = "o4-mini".to_string();
+        let body = provider.build_body(&req, false).unwrap();
+        assert!(body.get("frequency_penalty").is_none());
+        assert!(body.get("presence_penalty").is_none());
+        assert_eq!(body["seed"], 42);
+        assert_eq!(body["n"], 2);
+        assert_eq!(body["user"], "user-123");
+
+        let body = provider
+            .build_body(&ChatCompletionRequest::new("gpt-4o", vec![]), false)
+            .unwrap();
+        for key in [
+            "seed",
+            "logprobs",
+            "top_logprobs",
+            "logit_bias",
+            "n",
+            "user",
+        ] {
+            assert!(body.get(key).is_none(), "{key}");
+        }
+    }
+
+    #[test]
+    fn test_tool_calls() {
+        let provider = OpenAiProvider::new("test");
+        let mut req = ChatCompletionRequest::new(
+            "gpt-4o-mini",
+            vec![
+                Message::user("Weather in Paris?"),
+                Message::builder(Role::Assistant)
+                    .tool_call(
+                        "call_1",
+                        "get_weather",
+                        serde_json::json!({"city": "Paris"}),
+                    )
+                    .build(),
+                Message::tool_result("call_1", serde_json::json!({"temp": 21})),
+            ],
+        );
+        req.tools = Some(vec![Tool::new(
+            "get_weather",
+            "Current weather",
+            serde_json::json!({"type": "object"}),
+        )
+        .with_strict(true)]);
+        req.tool_choice = Some(ToolChoice::named("get_weather"));
+        req.parallel_tool_calls = Some(false);
+
+        let body = provider.build_body(&req, false).unwrap();
+        assert_eq!(body["tools"][0]["function"]["name"], "get_weather");
+        assert_eq!(
+            body["tool_choice"],
+            serde_json::json!({"type": "function", "function": {"name": "get_weather"}})
+        );
+        assert_eq!(body["tools"][0]["function"]["strict"], true);
+        assert_eq!(body["parallel_tool_calls"], false);
+        assert!(body["messages"][1].get("content").is_none());
+        assert_eq!(
+            body["messages"][1]["tool_calls"][0]["function"]["arguments"],
+            r#"{"city":"Paris"}"#
+        );
+        assert_eq!(body["messages"][2]["role"], "tool");
+        assert_eq!(body["messages"][2]["tool_call_id"], "call_1");
+
+        let response = provider
+            .convert_response(serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "gpt-4o-mini",
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_2",
+                            "type": "function",
+                            "function": {"name": "get_weather", "arguments": "{\"city\":\"Rome\"}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }]
+            }))
+            .unwrap();
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason, FinishReason::ToolCalls);
+        assert!(matches!(
+            &choice.message.content[..],
+            [ContentPart::ToolCall { id, arguments, .. }]
+                if id == "call_2" && arguments["city"] == "Rome"
+        ));
+    }
+
+    #[test]
+    fn test_tool_choice() {
+        let provider = OpenAiProvider::new("test");
+        let tool_choice = |choice: Option<ToolChoice>| {
+            let mut req = ChatCompletionRequest::new("gpt-4o-mini", vec![Message::user("Hi")]);
+            req.tools = Some(vec![Tool::new(
+                "get_weather",
+                "Current weather",
+                serde_json::json!({"type": "object"}),
+            )]);
+            req.tool_choice = choice;
+            provider.build_body(&req, false).unwrap()["tool_choice"].clone()
+        };
+
+        assert_eq!(tool_choice(Some(ToolChoice::Auto)), "auto");
+        assert_eq!(tool_choice(Some(ToolChoice::None)), "none");
+        assert_eq!(tool_choice(Some(ToolChoice::Required)), "required");
+        assert_eq!(
+            tool_choice(Some(ToolChoice::named("get_weather"))),
+            serde_json::json!({"type": "function", "function": {"name": "get_weather"}})
+        );
+        assert_eq!(tool_choice(None), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_reasoning_content() {
+        let chunk = OpenAiProvider::convert_stream_chunk(serde_json::json!({
+            "id": "chunk-1",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "deepseek-reasoner",
+            "choices": [{
+                "index": 0,
+                "delta": {"role": "assistant", "content": null, "reasoning_content": "Let me think"},
+                "finish_reason": null
+            }]
+        }))
+        .unwrap();
+        assert_eq!(
+            chunk.choices[0].delta.reasoning.as_deref(),
+            Some("Let me think")
+        );
+
+        assert!(is_reasoning_model("o3-mini"));
+        assert!(is_reasoning_model("openai/gpt-5"));
+        assert!(!is_reasoning_model("gpt-4o"));
+    }
+
+    #[test]
+    fn test_usage_details() {
+        let usage = serde_json::json!({
+            "prompt_tokens": 2000,
+            "completion_tokens": 300,
+            "total_tokens": 2300,
+            "prompt_tokens_details": {"cached_tokens": 1536, "audio_tokens": 40},
+            "completion_tokens_details": {"reasoning_tokens": 256, "audio_tokens": 12}
+        });
+        let response = OpenAiProvider::new("test-key")
+            .convert_response(serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "o4-mini",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Done"},
+                    "finish_reason": "stop"
+                }],
+                "usage": usage
+            }))
+            .unwrap();
+
+        let assert_breakdown = |usage: &Usage| {
+            assert_eq!(usage.prompt_tokens, 2000);
+            assert_eq!(usage.completion_tokens, 300);
+            assert_eq!(
+                usage.prompt_tokens_details,
+                Some(PromptTokensDetails {
+                    cached_tokens: Some(1536),
+                    audio_tokens: Some(40),
+                })
+            );
+            assert_eq!(
+                usage.completion_tokens_details,
+                Some(CompletionTokensDetails {
+                    reasoning_tokens: Some(256),
+                    audio_tokens: Some(12),
+                })
+            );
+            assert_eq!(usage.cached_tokens(), 1536);
+            assert_eq!(usage.reasoning_tokens(), 256);
+        };
+        assert_breakdown(&response.usage);
+
+        // The final chunk of a stream carries the same breakdown
+        let chunk = OpenAiProvider::convert_stream_chunk(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "o4-mini",
+            "choices": [],
+            "usage": usage
+        }))
+        .unwrap();
+        assert_breakdown(chunk.usage.as_ref().unwrap());
+    }
+
+    #[test]
+    fn test_finish_metadata() {
+        let provider = OpenAiProvider::new("test-key");
+        let response = provider
+            .convert_response(serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "gpt-4o-2024-08-06",
+                "system_fingerprint": "fp_abc123",
+                "service_tier": "default",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "1, 2, 3"},
+                    "finish_reason": "stop",
+                    "stop_reason": "4"
+                }]
+            }))
+            .unwrap();
+        assert_eq!(response.system_fingerprint.as_deref(), Some("fp_abc123"));
+        assert_eq!(response.service_tier.as_deref(), Some("default"));
+        assert_eq!(response.choices[0].stop_sequence.as_deref(), Some("4"));
+
+        let response = provider
+            .convert_response(serde_json::json!({
+                "id": "chatcmpl-2",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "gpt-4o",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "I"},
+                    "finish_reason": "content_filter",
+                    "content_filter_results": {
+                        "hate": {"filtered": false, "severity": "safe"},
+                        "violence": {"filtered": true, "severity": "high"}
+                    }
+                }]
+            }))
+            .unwrap();
+        assert_eq!(
+            response.choices[0].finish_reason,
+            FinishReason::ContentFilter
+        );
+        assert_eq!(response.choices[0].filtered_categories, ["violence"]);
+    }
+
+    #[test]
+    fn test_convert_model_list() {
+        let models = convert_model_list(&serde_json::json!({
+            "object": "list",
+            "data": [
+                {"id": "gpt-4o", "object": "model", "created": 1715367049, "owned_by": "system"},
+                {"id": "llama3.2:latest", "object": "model"},
+                {"object": "model"}
+            ]
+        }));
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].owned_by.as_deref(), Some("system"));
+        assert_eq!(models[1], ModelInfo::new("llama3.2:latest"));
+    }
+
+    #[test]
+    fn test_map_openai_error() {
+        let err = map_openai_error(
+            "openai",
+            api_error(Some("requests"), Some("rate_limit_exceeded")),
+        );
+        assert!(matches!(err, AiError::RateLimit { .. }));
+        assert!(err.is_retryable());
+        assert_eq!(err.status(), Some(429));
+
+        let err = map_openai_error(
+            "openai",
+            api_error(Some("insufficient_quota"), Some("insufficient_quota")),
+        );
+        assert!(!err.is_retryable());
+        assert_eq!(
+            err.details().unwrap().code.as_deref(),
+            Some("insufficient_quota")
+        );
+
+        let err = map_openai_error(
+            "openai",
+            api_error(Some("invalid_request_error"), Some("model_not_found")),
+        );
+        assert!(matches!(err, AiError::ModelNotFound { .. }));
+
+        let err = map_openai_error("openai", api_error(None, None));
+        assert!(err.is_retryable());
+    }
+
+    /// Serve OpenAI-style completions on a local port, forwarding each raw
+    /// HTTP request to the returned channel
+    async fn capture_server() -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    let mut request = Vec::new();
+                    loop {
+                        let n = socket.read(&mut buf).await.unwrap_or(0);
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request).to_string();
+                        let Some(head_end) = text.find("\r\n\r\n") else {
+                            continue;
+                        };
+                        let length = text[..head_end]
+                            .lines()
+                            .find_map(|line| {
+                                let (name, value) = line.split_once(':')?;
+                                if !name.eq_ignore_ascii_case("content-length") {
+                                    return None;
+                                }
+                                value.trim().parse::<usize>().ok()
+                            })
+                            .unwrap_or(0);
+                        if request.len() < head_end + 4 + length {
+                            continue;
+                        }
+                        let _ = tx.send(text);
+                        request.clear();
+
+                        let body = r#"{"id":"c","object":"chat.completion","created":0,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"ok"},"finish_reason":"stop"}]}"#;
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (url, rx)
+    }
+
+    fn header_lines(request: &str) -> Vec<String> {
+        request
+            .split("\r\n\r\n")
+            .next()
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(str::to_ascii_lowercase)
+            .collect()
+    }
+
+    #[test]
+    fn test_header_config_merge() {
+        let provider = OpenAiProvider::builder()
+            .api_key("test")
+            .header("X-Tenant", "default")
+            .header("X-Team", "core")
+            .build()
+            .unwrap();
+
+        let mut request_headers = HashMap::new();
+        request_headers.insert("X-Tenant".to_string(), "acme".to_string());
+        request_headers.insert("X-Trace".to_string(), "t-1".to_string());
+        let headers = provider
+            .config
+            .with_headers(&request_headers)
+            .unwrap()
+            .headers();
+
+        assert_eq!(headers["x-tenant"], "acme");
+        assert_eq!(headers.get_all("x-tenant").iter().count(), 1);
+        assert_eq!(headers["x-team"], "core");
+        assert_eq!(headers["x-trace"], "t-1");
+        assert_eq!(headers["authorization"], "Bearer test");
+
+        // The provider's own config is untouched
+        assert_eq!(provider.config.headers()["x-tenant"], "default");
+
+        request_headers.insert("bad header".to_string(), "x".to_string());
+        assert!(matches!(
+            provider.config.with_headers(&request_headers),
+            Err(AiError::Configuration(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_headers_reach_wire() {
+        let (url, mut requests) = capture_server().await;
+        let provider = OpenAiProvider::builder()
+            .api_key("test")
+            .api_base(url)
+            .header("X-Tenant", "default")
+            .header("X-Team", "core")
+            .build()
+            .unwrap();
+
+        let req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("hi")])
+            .with_header("X-Tenant", "acme")
+            .with_header("X-Trace", "t-1");
+        let response = provider.chat_completion(req).await.unwrap();
+        assert_eq!(response.choices.len(), 1);
+
+        let request = requests.recv().await.unwrap();
+        assert!(request.starts_with("POST /chat/completions "));
+        let headers = header_lines(&request);
+        assert!(headers.contains(&"x-tenant: acme".to_string()));
+        assert!(!headers.contains(&"x-tenant: default".to_string()));
+        assert!(headers.contains(&"x-team: core".to_string()));
+        assert!(headers.contains(&"x-trace: t-1".to_string()));
+        assert!(headers.contains(&"authorization: bearer test".to_string()));
+
+        // Without per-request headers only the defaults are sent
+        let req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("hi")]);
+        provider.chat_completion(req).await.unwrap();
+
+        let headers = header_lines(&requests.recv().await.unwrap());
+        assert!(headers.contains(&"x-tenant: default".to_string()));
+        assert!(!headers.iter().any(|h| h.starts_with("x-trace:")));
+    }
+
+    #[tokio::test]
+    async fn test_builder_options() {
+        assert!(matches!(
+            OpenAiProvider::builder().build(),
+            Err(AiError::Configuration(_))
+        ));
+        assert!(matches!(
+            OpenAiProvider::builder()
+                .api_key("test")
+                .proxy("not a proxy url")
+                .build(),
+            Err(AiError::Configuration(_))
+        ));
+        assert!(OpenAiProvider::builder()
+            .api_key("test")
+            .danger_accept_invalid_certs(true)
+            .connect_timeout(Duration::from_secs(1))
+            .build()
+            .is_ok());
+
+        // Requests go through the configured proxy in absolute form
+        let (proxy, mut requests) = capture_server().await;
+        let provider = OpenAiProvider::builder()
+            .api_key("test")
+            .api_base("http://api.example.test/v1")
+            .proxy(proxy)
+            .build()
+            .unwrap();
+        let req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("hi")]);
+        provider.chat_completion(req).await.unwrap();
+        let request = requests.recv().await.unwrap();
+        assert!(request.starts_with("POST http://api.example.test/v1/chat/completions "));
+
+        // A custom client replaces the client options entirely
+        let (url, mut requests) = capture_server().await;
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert("x-client", HeaderValue::from_static("custom"));
+        let provider = OpenAiProvider::builder()
+            .api_key("test")
+            .api_base(url)
+            .proxy("not a proxy url")
+            .http_client(
+                reqwest::Client::builder()
+                    .default_headers(default_headers)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        let req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("hi")]);
+        provider.chat_completion(req).await.unwrap();
+        let headers = header_lines(&requests.recv().await.unwrap());
+        assert!(headers.contains(&"x-client: custom".to_string()));
+
+        // The total timeout applies to a server that never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        let provider = OpenAiProvider::builder()
+            .api_key("test")
+            .api_base(url)
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let req = ChatCompletionRequest::new("gpt-4o", vec![Message::user("hi")]);
+        let err = provider.chat_completion(req).await.unwrap_err();
+        assert!(matches!(err, AiError::Timeout { .. }), "{err:?}");
+    }
+}
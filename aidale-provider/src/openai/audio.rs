@@ -0,0 +1,176 @@
+//! OpenAI audio API: text-to-speech and Whisper transcription.
+
+use super::OpenAiProvider;
+use aidale_core::audio::{
+    AudioFileFormat, Speech, SpeechRequest, Transcription, TranscriptionRequest,
+    TranscriptionSegment,
+};
+use aidale_core::error::AiError;
+use async_openai::types::{
+    AudioInput, AudioResponseFormat, CreateSpeechRequest, CreateTranscriptionRequest,
+    CreateTranscriptionResponseVerboseJson, InputSource, SpeechModel, SpeechResponseFormat,
+    TimestampGranularity, Voice,
+};
+
+/// Speech formats the API can return
+fn speech_format(format: AudioFileFormat) -> Result<SpeechResponseFormat, AiError> {
+    match format {
+        AudioFileFormat::Mp3 => Ok(SpeechResponseFormat::Mp3),
+        AudioFileFormat::Opus => Ok(SpeechResponseFormat::Opus),
+        AudioFileFormat::Aac => Ok(SpeechResponseFormat::Aac),
+        AudioFileFormat::Flac => Ok(SpeechResponseFormat::Flac),
+        AudioFileFormat::Wav => Ok(SpeechResponseFormat::Wav),
+        AudioFileFormat::Pcm => Ok(SpeechResponseFormat::Pcm),
+        format => Err(AiError::invalid_request(format!(
+            "OpenAI can't synthesize speech as {}",
+            format
+        ))),
+    }
+}
+
+fn speech_request(req: SpeechRequest) -> Result<CreateSpeechRequest, AiError> {
+    let voice: Voice = serde_json::from_value(serde_json::Value::String(req.voice.clone()))
+        .map_err(|_| AiError::invalid_request(format!("Unknown OpenAI voice: {}", req.voice)))?;
+    Ok(CreateSpeechRequest {
+        input: req.input,
+        model: SpeechModel::Other(req.model),
+        voice,
+        instructions: req.instructions,
+        response_format: Some(speech_format(req.format)?),
+        speed: req.speed,
+    })
+}
+
+fn transcription_request(req: TranscriptionRequest) -> CreateTranscriptionRequest {
+    let filename = req.filename();
+    let timestamps = req.timestamps;
+    CreateTranscriptionRequest {
+        file: AudioInput {
+            source: InputSource::VecU8 {
+                filename,
+                vec: req.audio,
+            },
+        },
+        model: req.model,
+        prompt: req.prompt,
+        // Only the verbose format carries language, duration and segments
+        response_format: Some(if timestamps {
+            AudioResponseFormat::VerboseJson
+        } else {
+            AudioResponseFormat::Json
+        }),
+        temperature: req.temperature,
+        language: req.language,
+        timestamp_granularities: timestamps.then(|| vec![TimestampGranularity::Segment]),
+    }
+}
+
+fn convert_verbose(response: CreateTranscriptionResponseVerboseJson) -> Transcription {
+    Transcription {
+        text: response.text,
+        language: Some(response.language),
+        duration: Some(response.duration.into()),
+        segments: response
+            .segments
+            .unwrap_or_default()
+            .into_iter()
+            .map(|segment| TranscriptionSegment {
+                start: segment.start.into(),
+                end: segment.end.into(),
+                text: segment.text.trim().to_string(),
+            })
+            .collect(),
+    }
+}
+
+impl OpenAiProvider {
+    pub(super) async fn create_speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        let format = req.format;
+        let response = self
+            .client
+            .audio()
+            .speech(speech_request(req)?)
+            .await
+            .map_err(|e| self.map_error(e))?;
+        Ok(Speech {
+            audio: response.bytes.to_vec(),
+            format,
+        })
+    }
+
+    pub(super) async fn create_transcription(
+        &self,
+        req: TranscriptionRequest,
+    ) -> Result<Transcription, AiError> {
+        let language = req.language.clone();
+        let timestamps = req.timestamps;
+        let request = transcription_request(req);
+        let audio = self.client.audio();
+        if timestamps {
+            let response = audio
+                .transcribe_verbose_json(request)
+                .await
+                .map_err(|e| self.map_error(e))?;
+            return Ok(convert_verbose(response));
+        }
+        let response = audio
+            .transcribe(request)
+            .await
+            .map_err(|e| self.map_error(e))?;
+        Ok(Transcription {
+            text: response.text,
+            language,
+            ..Transcription::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_requests() {
+        let speech = speech_request(
+            SpeechRequest::new("tts-1", "Hello", "nova").with_format(AudioFileFormat::Opus),
+        )
+        .unwrap();
+        assert_eq!(speech.voice, Voice::Nova);
+        assert_eq!(speech.response_format, Some(SpeechResponseFormat::Opus));
+        assert_eq!(serde_json::to_value(&speech.model).unwrap(), "tts-1");
+        assert!(speech_request(SpeechRequest::new("tts-1", "Hello", "robot")).is_err());
+        assert!(speech_request(
+            SpeechRequest::new("tts-1", "Hello", "nova").with_format(AudioFileFormat::Webm)
+        )
+        .is_err());
+
+        let request = transcription_request(
+            TranscriptionRequest::new("whisper-1", vec![1, 2, 3], AudioFileFormat::M4a)
+                .with_timestamps(true),
+        );
+        assert!(matches!(
+            &request.file.source,
+            InputSource::VecU8 { filename, .. } if filename == "audio.m4a"
+        ));
+        assert_eq!(
+            request.response_format,
+            Some(AudioResponseFormat::VerboseJson)
+        );
+
+        let verbose: CreateTranscriptionResponseVerboseJson =
+            serde_json::from_value(serde_json::json!({
+                "language": "english",
+                "duration": 2.5,
+                "text": "Hello there.",
+                "segments": [{
+                    "id": 0, "seek": 0, "start": 0.0, "end": 2.5, "text": " Hello there.",
+                    "tokens": [1, 2], "temperature": 0.0, "avg_logprob": -0.2,
+                    "compression_ratio": 0.8, "no_speech_prob": 0.01
+                }]
+            }))
+            .unwrap();
+        let transcription = convert_verbose(verbose);
+        assert_eq!(transcription.duration, Some(2.5));
+        assert_eq!(transcription.segments[0].text, "Hello there.");
+    }
+}
@@ -0,0 +1,248 @@
+//! OpenAI Files API.
+//!
+//! Uploaded files are referenced by ID instead of being sent inline with
+//! every request: batch input files, documents for the Responses API, and
+//! assistants-style file search.
+//!
+//! ```no_run
+//! use aidale_core::types::{Message, Role};
+//! use aidale_provider::openai::files::FilePurpose;
+//! use aidale_provider::OpenAiProvider;
+//!
+//! # async fn run() -> Result<(), aidale_core::error::AiError> {
+//! let provider = OpenAiProvider::new("sk-...");
+//! let contract = provider
+//!     .files()
+//!     .upload_path("contract.pdf", FilePurpose::Assistants)
+//!     .await?;
+//! let message = Message::builder(Role::User)
+//!     .text("Summarize the termination clause.")
+//!     .part(contract.part())
+//!     .build();
+//! # Ok(())
+//! # }
+//! ```
+
+use super::OpenAiProvider;
+use aidale_core::error::AiError;
+use aidale_core::types::ContentPart;
+use async_openai::types::{
+    CreateFileRequest, FileInput, FilePurpose as OpenAIFilePurpose, InputSource, OpenAIFile,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What an uploaded file will be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilePurpose {
+    /// Documents for the Responses API, assistants and file search
+    Assistants,
+    /// JSONL input of a batch job
+    Batch,
+    /// Training data for fine-tuning
+    FineTune,
+    /// Images for vision input
+    Vision,
+}
+
+impl From<FilePurpose> for OpenAIFilePurpose {
+    fn from(purpose: FilePurpose) -> Self {
+        match purpose {
+            FilePurpose::Assistants => Self::Assistants,
+            FilePurpose::Batch => Self::Batch,
+            FilePurpose::FineTune => Self::FineTune,
+            FilePurpose::Vision => Self::Vision,
+        }
+    }
+}
+
+/// A file stored with OpenAI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHandle {
+    pub id: String,
+    pub filename: String,
+    /// Size in bytes
+    pub bytes: u64,
+    /// Purpose as reported by OpenAI, e.g. `batch` or `batch_output`
+    pub purpose: String,
+    /// Unix time in seconds
+    pub created_at: u64,
+    /// Unix time in seconds after which OpenAI deletes the file
+    pub expires_at: Option<u64>,
+}
+
+impl FileHandle {
+    /// Content part referencing this file in a request
+    pub fn part(&self) -> ContentPart {
+        ContentPart::FileRef {
+            id: self.id.clone(),
+        }
+    }
+}
+
+impl From<&FileHandle> for ContentPart {
+    fn from(file: &FileHandle) -> Self {
+        file.part()
+    }
+}
+
+impl From<OpenAIFile> for FileHandle {
+    fn from(file: OpenAIFile) -> Self {
+        let purpose = serde_json::to_value(&file.purpose)
+            .ok()
+            .and_then(|purpose| purpose.as_str().map(str::to_string))
+            .unwrap_or_default();
+        Self {
+            id: file.id,
+            filename: file.filename,
+            bytes: file.bytes.into(),
+            purpose,
+            created_at: file.created_at.into(),
+            expires_at: file.expires_at.map(Into::into),
+        }
+    }
+}
+
+/// Files API of an [`OpenAiProvider`]
+#[derive(Debug, Clone, Copy)]
+pub struct Files<'a> {
+    provider: &'a OpenAiProvider,
+}
+
+impl OpenAiProvider {
+    /// Access the Files API
+    pub fn files(&self) -> Files<'_> {
+        Files { provider: self }
+    }
+}
+
+impl Files<'_> {
+    /// Upload a file from memory
+    pub async fn upload(
+        &self,
+        filename: impl Into<String>,
+        bytes: Vec<u8>,
+        purpose: FilePurpose,
+    ) -> Result<FileHandle, AiError> {
+        self.create(
+            InputSource::VecU8 {
+                filename: filename.into(),
+                vec: bytes,
+            },
+            purpose,
+        )
+        .await
+    }
+
+    /// Upload a local file
+    pub async fn upload_path(
+        &self,
+        path: impl AsRef<Path>,
+        purpose: FilePurpose,
+    ) -> Result<FileHandle, AiError> {
+        self.create(
+            InputSource::Path {
+                path: path.as_ref().to_path_buf(),
+            },
+            purpose,
+        )
+        .await
+    }
+
+    async fn create(
+        &self,
+        source: InputSource,
+        purpose: FilePurpose,
+    ) -> Result<FileHandle, AiError> {
+        let request = CreateFileRequest {
+            file: FileInput { source },
+            purpose: purpose.into(),
+            expires_after: None,
+        };
+        let file = self
+            .provider
+            .client
+            .files()
+            .create(request)
+            .await
+            .map_err(|e| self.provider.map_error(e))?;
+        Ok(file.into())
+    }
+
+    /// List the files of the organization
+    pub async fn list(&self) -> Result<Vec<FileHandle>, AiError> {
+        let files = self
+            .provider
+            .client
+            .files()
+            .list(&[("limit", "10000")])
+            .await
+            .map_err(|e| self.provider.map_error(e))?;
+        Ok(files.data.into_iter().map(Into::into).collect())
+    }
+
+    /// Look up a file by ID
+    pub async fn retrieve(&self, id: &str) -> Result<FileHandle, AiError> {
+        let file = self
+            .provider
+            .client
+            .files()
+            .retrieve(id)
+            .await
+            .map_err(|e| self.provider.map_error(e))?;
+        Ok(file.into())
+    }
+
+    /// Download the contents of a file, such as a batch output
+    pub async fn content(&self, id: &str) -> Result<Vec<u8>, AiError> {
+        let bytes = self
+            .provider
+            .client
+            .files()
+            .content(id)
+            .await
+            .map_err(|e| self.provider.map_error(e))?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Delete a file
+    pub async fn delete(&self, id: &str) -> Result<(), AiError> {
+        let deleted = self
+            .provider
+            .client
+            .files()
+            .delete(id)
+            .await
+            .map_err(|e| self.provider.map_error(e))?;
+        if !deleted.deleted {
+            return Err(AiError::provider(format!("File {} was not deleted", id)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_handle() {
+        let file: OpenAIFile = serde_json::from_value(serde_json::json!({
+            "id": "file-abc123",
+            "object": "file",
+            "bytes": 120000,
+            "created_at": 1677610602,
+            "expires_at": null,
+            "filename": "contract.pdf",
+            "purpose": "assistants"
+        }))
+        .unwrap();
+        let handle = FileHandle::from(file);
+        assert_eq!(handle.purpose, "assistants");
+        assert_eq!(handle.bytes, 120000);
+        assert!(matches!(
+            ContentPart::from(&handle),
+            ContentPart::FileRef { id } if id == "file-abc123"
+        ));
+    }
+}
@@ -0,0 +1,347 @@
+//! OpenAI Realtime API provider.
+//!
+//! Sessions run over a WebSocket to `wss://api.openai.com/v1/realtime`. A
+//! background task translates [`RealtimeCommand`]s into client events and
+//! server events into [`RealtimeEvent`]s; audio travels base64-encoded in
+//! both directions.
+//!
+//! Interrupting a response cancels it and truncates the assistant's audio
+//! item to what was played, so the model's memory of the conversation
+//! matches what the user actually heard.
+
+use aidale_core::error::AiError;
+use aidale_core::realtime::{
+    AudioFormat, RealtimeCommand, RealtimeConfig, RealtimeEvent, RealtimeProvider, RealtimeSession,
+};
+use aidale_core::types::Usage;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures::channel::mpsc;
+use futures::{stream, SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{self, Message};
+
+const DEFAULT_URL: &str = "wss://api.openai.com/v1/realtime";
+
+/// OpenAI Realtime API provider
+#[derive(Clone)]
+pub struct OpenAiRealtimeProvider {
+    api_key: String,
+    url: String,
+}
+
+impl std::fmt::Debug for OpenAiRealtimeProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAiRealtimeProvider")
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+impl OpenAiRealtimeProvider {
+    /// Create a provider for the OpenAI endpoint
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            url: DEFAULT_URL.to_string(),
+        }
+    }
+
+    /// Connect to a different WebSocket endpoint, e.g. a proxy
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+}
+
+/// Map a connection error, keeping authentication failures distinguishable
+fn connect_error(e: tungstenite::Error) -> AiError {
+    match e {
+        tungstenite::Error::Http(response) => {
+            let status = response.status().as_u16();
+            let message = format!("Realtime connection rejected with HTTP {}", status);
+            match status {
+                401 | 403 => AiError::authentication(message),
+                429 => AiError::rate_limit(message),
+                _ => AiError::provider(message),
+            }
+        }
+        e => AiError::provider(format!("Realtime connection failed: {}", e)),
+    }
+}
+
+fn audio_format(format: AudioFormat) -> &'static str {
+    match format {
+        AudioFormat::Pcm16 => "pcm16",
+        AudioFormat::G711Ulaw => "g711_ulaw",
+        AudioFormat::G711Alaw => "g711_alaw",
+    }
+}
+
+/// `session.update` event for a configuration
+fn session_update(config: &RealtimeConfig) -> Value {
+    let format = audio_format(config.audio_format);
+    let mut session = json!({
+        "modalities": ["audio", "text"],
+        "input_audio_format": format,
+        "output_audio_format": format,
+        "turn_detection": if config.server_vad { json!({"type": "server_vad"}) } else { Value::Null },
+        "tools": config.tools.iter().map(|tool| json!({
+            "type": "function",
+            "name": tool.name,
+            "description": tool.description,
+            "parameters": tool.parameters,
+        })).collect::<Vec<_>>(),
+    });
+    if let Some(instructions) = &config.instructions {
+        session["instructions"] = json!(instructions);
+    }
+    if let Some(voice) = &config.voice {
+        session["voice"] = json!(voice);
+    }
+    if let Some(temperature) = config.temperature {
+        session["temperature"] = json!(temperature);
+    }
+    if config.transcribe_input {
+        session["input_audio_transcription"] = json!({"model": "whisper-1"});
+    }
+    json!({"type": "session.update", "session": session})
+}
+
+/// Client events for a command
+///
+/// `audio_item` is the assistant audio item currently being received,
+/// which an interruption truncates.
+fn client_events(command: RealtimeCommand, audio_item: Option<&str>) -> Vec<Value> {
+    match command {
+        RealtimeCommand::UpdateSession(config) => vec![session_update(&config)],
+        RealtimeCommand::Audio(audio) => vec![json!({
+            "type": "input_audio_buffer.append",
+            "audio": BASE64.encode(audio),
+        })],
+        RealtimeCommand::CommitAudio => vec![json!({"type": "input_audio_buffer.commit"})],
+        RealtimeCommand::Text(text) => vec![json!({
+            "type": "conversation.item.create",
+            "item": {
+                "type": "message",
+                "role": "user",
+                "content": [{"type": "input_text", "text": text}],
+            },
+        })],
+        RealtimeCommand::ToolResult { call_id, output } => vec![json!({
+            "type": "conversation.item.create",
+            "item": {
+                "type": "function_call_output",
+                "call_id": call_id,
+                "output": output.to_string(),
+            },
+        })],
+        RealtimeCommand::CreateResponse => vec![json!({"type": "response.create"})],
+        RealtimeCommand::Interrupt { played } => {
+            let mut events = vec![json!({"type": "response.cancel"})];
+            if let Some(item_id) = audio_item {
+                events.push(json!({
+                    "type": "conversation.item.truncate",
+                    "item_id": item_id,
+                    "content_index": 0,
+                    "audio_end_ms": played.as_millis() as u64,
+                }));
+            }
+            events
+        }
+    }
+}
+
+/// Convert a server event; events without a counterpart are skipped
+fn convert_event(event: &Value) -> Option<RealtimeEvent> {
+    let text = |field: &str| event[field].as_str().unwrap_or_default().to_string();
+    Some(match event["type"].as_str()? {
+        "session.created" => RealtimeEvent::SessionCreated {
+            id: event["session"]["id"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        },
+        "response.audio.delta" => RealtimeEvent::AudioDelta {
+            item_id: text("item_id"),
+            audio: BASE64.decode(event["delta"].as_str()?).ok()?,
+        },
+        "response.audio.done" => RealtimeEvent::AudioDone {
+            item_id: text("item_id"),
+        },
+        "response.audio_transcript.delta" => RealtimeEvent::TranscriptDelta {
+            text: text("delta"),
+        },
+        "response.text.delta" => RealtimeEvent::TextDelta {
+            text: text("delta"),
+        },
+        "conversation.item.input_audio_transcription.completed" => RealtimeEvent::InputTranscript {
+            text: text("transcript"),
+        },
+        "input_audio_buffer.speech_started" => RealtimeEvent::SpeechStarted,
+        "input_audio_buffer.speech_stopped" => RealtimeEvent::SpeechStopped,
+        "response.function_call_arguments.done" => RealtimeEvent::ToolCall {
+            call_id: text("call_id"),
+            name: text("name"),
+            arguments: serde_json::from_str(event["arguments"].as_str()?).unwrap_or(Value::Null),
+        },
+        "response.done" => {
+            let usage = &event["response"]["usage"];
+            RealtimeEvent::ResponseDone {
+                usage: usage.is_object().then(|| Usage {
+                    prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0) as u32,
+                    completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0) as u32,
+                    total_tokens: usage["total_tokens"].as_u64().unwrap_or(0) as u32,
+                    ..Usage::default()
+                }),
+            }
+        }
+        "error" => RealtimeEvent::Error {
+            message: event["error"]["message"]
+                .as_str()
+                .unwrap_or("Unknown error")
+                .to_string(),
+        },
+        _ => return None,
+    })
+}
+
+/// Input of the session task
+enum Input {
+    Command(RealtimeCommand),
+    /// The application closed the sender
+    Closed,
+    Message(Result<Message, tungstenite::Error>),
+}
+
+#[async_trait]
+impl RealtimeProvider for OpenAiRealtimeProvider {
+    async fn connect(&self, config: RealtimeConfig) -> Result<RealtimeSession, AiError> {
+        let url = format!("{}?model={}", self.url, config.model);
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| AiError::configuration(format!("Invalid realtime URL: {}", e)))?;
+        let headers = request.headers_mut();
+        let auth = format!("Bearer {}", self.api_key)
+            .parse()
+            .map_err(|_| AiError::configuration("Invalid API key"))?;
+        headers.insert("Authorization", auth);
+        headers.insert("OpenAI-Beta", "realtime=v1".parse().expect("valid header"));
+
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(connect_error)?;
+        let (mut sink, messages) = socket.split();
+        sink.send(Message::text(session_update(&config).to_string()))
+            .await
+            .map_err(|e| AiError::stream(e.to_string()))?;
+
+        let (command_tx, command_rx) = mpsc::unbounded();
+        let (event_tx, event_rx) = mpsc::unbounded();
+        let mut inputs = stream::select(
+            command_rx
+                .map(Input::Command)
+                .chain(stream::iter([Input::Closed])),
+            messages.map(Input::Message),
+        );
+
+        tokio::spawn(async move {
+            let mut audio_item: Option<String> = None;
+            while let Some(input) = inputs.next().await {
+                let sent = match input {
+                    Input::Command(command) => {
+                        let mut sent = Ok(());
+                        for event in client_events(command, audio_item.as_deref()) {
+                            sent = sink.send(Message::text(event.to_string())).await;
+                            if sent.is_err() {
+                                break;
+                            }
+                        }
+                        sent
+                    }
+                    Input::Closed => sink.close().await,
+                    Input::Message(Ok(Message::Text(text))) => {
+                        let Ok(event) = serde_json::from_str::<Value>(&text) else {
+                            continue;
+                        };
+                        let Some(event) = convert_event(&event) else {
+                            continue;
+                        };
+                        match &event {
+                            RealtimeEvent::AudioDelta { item_id, .. } => {
+                                audio_item = Some(item_id.clone())
+                            }
+                            RealtimeEvent::ResponseDone { .. } => audio_item = None,
+                            _ => {}
+                        }
+                        // Keep the connection open while the application
+                        // still holds the sender, even if it stopped reading
+                        let _ = event_tx.unbounded_send(Ok(event));
+                        Ok(())
+                    }
+                    Input::Message(Ok(Message::Close(_))) => break,
+                    Input::Message(Ok(_)) => Ok(()),
+                    Input::Message(Err(e)) => Err(e),
+                };
+                if let Err(e) = sent {
+                    if !matches!(e, tungstenite::Error::ConnectionClosed) {
+                        let _ = event_tx.unbounded_send(Err(AiError::stream(e.to_string())));
+                    }
+                    break;
+                }
+            }
+        });
+
+        Ok(RealtimeSession::new(command_tx, Box::new(event_rx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_event_mapping() {
+        let config = RealtimeConfig::new("gpt-4o-realtime-preview")
+            .with_voice("alloy")
+            .with_server_vad(false);
+        let update = session_update(&config);
+        assert_eq!(update["session"]["voice"], "alloy");
+        assert_eq!(update["session"]["turn_detection"], Value::Null);
+
+        let events = client_events(
+            RealtimeCommand::Interrupt {
+                played: Duration::from_millis(1500),
+            },
+            Some("item_1"),
+        );
+        assert_eq!(events[0]["type"], "response.cancel");
+        assert_eq!(events[1]["audio_end_ms"], 1500);
+        let audio = client_events(RealtimeCommand::Audio(vec![0, 1, 2]), None);
+        assert_eq!(audio[0]["audio"], "AAEC");
+
+        let delta = convert_event(&json!({
+            "type": "response.audio.delta",
+            "item_id": "item_1",
+            "delta": "AAEC"
+        }));
+        assert!(matches!(
+            delta,
+            Some(RealtimeEvent::AudioDelta { item_id, audio }) if item_id == "item_1" && audio == [0, 1, 2]
+        ));
+        let call = convert_event(&json!({
+            "type": "response.function_call_arguments.done",
+            "call_id": "call_1",
+            "name": "get_weather",
+            "arguments": "{\"city\":\"Paris\"}"
+        }));
+        assert!(matches!(
+            call,
+            Some(RealtimeEvent::ToolCall { arguments, .. }) if arguments == json!({"city": "Paris"})
+        ));
+        assert!(convert_event(&json!({"type": "rate_limits.updated"})).is_none());
+    }
+}
@@ -0,0 +1,714 @@
+//! OpenAI Responses API provider.
+//!
+//! Targets `/v1/responses` while mapping to the shared chat completion
+//! types, so it can be used anywhere an [`OpenAiProvider`] can. Reasoning
+//! summaries are surfaced as `ContentPart::Reasoning`, and the response ID is
+//! returned as `ChatCompletionResponse::id` so conversations can be chained.
+//!
+//! Responses-only parameters such as `previous_response_id`, `store` or
+//! built-in tools go through `extra`, which is merged into the request body
+//! (an `extra["tools"]` entry replaces the converted function tools).
+//!
+//! # Example
+//!
+//! ```ignore
+//! let req = ChatCompletionRequest::new("gpt-4.1", messages)
+//!     .with_extra("previous_response_id", serde_json::json!(previous.id))
+//!     .with_extra("store", serde_json::json!(true));
+//! ```
+//!
+//! [`OpenAiProvider`]: crate::OpenAiProvider
+
+use crate::http::{read_json, send, send_json, sse_events, Wire};
+use aidale_core::error::AiError;
+use aidale_core::http::{HttpClient, HttpRequest};
+use aidale_core::json_repair;
+use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use aidale_core::wire::WireObserver;
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+
+/// OpenAI provider using the Responses API
+#[derive(Clone)]
+pub struct OpenAiResponsesProvider {
+    http: Arc<dyn HttpClient>,
+    api_key: String,
+    api_base: String,
+    info: Arc<ProviderInfo>,
+    wire: Option<Wire>,
+}
+
+impl std::fmt::Debug for OpenAiResponsesProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAiResponsesProvider")
+            .field("api_base", &self.api_base)
+            .field("info", &self.info)
+            .finish()
+    }
+}
+
+impl OpenAiResponsesProvider {
+    /// Create a new Responses API provider with default configuration
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            http: Arc::new(reqwest::Client::new()),
+            api_key: api_key.into(),
+            api_base: OPENAI_API_BASE.to_string(),
+            info: Arc::new(ProviderInfo {
+                id: "openai".to_string(),
+                name: "OpenAI Responses".to_string(),
+            }),
+            wire: None,
+        }
+    }
+
+    /// Set API base URL
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
+    /// Use a preconfigured HTTP client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http = Arc::new(client);
+        self
+    }
+
+    /// Send requests through a custom HTTP backend, e.g. a runtime's `fetch`
+    pub fn with_http_backend(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Observe the JSON bodies sent to and received from the API
+    pub fn with_wire_observer(mut self, observer: Arc<dyn WireObserver>) -> Self {
+        self.wire = Some(Wire::new(self.info.id.clone(), observer));
+        self
+    }
+
+    /// Extract text content from a message
+    fn text_of(msg: &Message) -> String {
+        msg.content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Convert our Message type to Responses API input items
+    ///
+    /// Tool calls and tool results are separate items in the Responses API,
+    /// so one message may expand into several.
+    fn convert_message(msg: &Message) -> Vec<serde_json::Value> {
+        match msg.role {
+            Role::System => {
+                vec![serde_json::json!({"role": "system", "content": Self::text_of(msg)})]
+            }
+            Role::User => {
+                let content: Vec<_> = msg
+                    .content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text } => {
+                            Some(serde_json::json!({"type": "input_text", "text": text}))
+                        }
+                        ContentPart::File { name, mime, data } => Some(serde_json::json!({
+                            "type": "input_file",
+                            "filename": name,
+                            "file_data": format!("data:{};base64,{}", mime, data),
+                        })),
+                        ContentPart::FileRef { id } => {
+                            Some(serde_json::json!({"type": "input_file", "file_id": id}))
+                        }
+                        _ => part.image_url().map(
+                            |url| serde_json::json!({"type": "input_image", "image_url": url}),
+                        ),
+                    })
+                    .collect();
+                vec![serde_json::json!({"role": "user", "content": content})]
+            }
+            Role::Assistant => {
+                let mut items = Vec::new();
+                let text = Self::text_of(msg);
+                if !text.is_empty() {
+                    items.push(serde_json::json!({"role": "assistant", "content": text}));
+                }
+                items.extend(msg.content.iter().filter_map(|part| match part {
+                    ContentPart::ToolCall {
+                        id,
+                        name,
+                        arguments,
+                    } => Some(serde_json::json!({
+                        "type": "function_call",
+                        "call_id": id,
+                        "name": name,
+                        "arguments": arguments.to_string(),
+                    })),
+                    _ => None,
+                }));
+                items
+            }
+            Role::Tool => msg
+                .content
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::ToolResult { id, result } => Some(serde_json::json!({
+                        "type": "function_call_output",
+                        "call_id": id,
+                        "output": match result {
+                            serde_json::Value::String(text) => text.clone(),
+                            other => other.to_string(),
+                        },
+                    })),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Build the request body for the responses endpoint
+    fn build_body(req: &ChatCompletionRequest, stream: bool) -> serde_json::Value {
+        let input: Vec<_> = req
+            .messages
+            .iter()
+            .flat_map(Self::convert_message)
+            .collect();
+
+        let mut body = serde_json::json!({
+            "model": req.model,
+            "input": input,
+            "stream": stream,
+        });
+
+        if let Some(max_tokens) = req.max_tokens {
+            body["max_output_tokens"] = max_tokens.into();
+        }
+        if let Some(temperature) = req.temperature {
+            body["temperature"] = temperature.into();
+        }
+        if let Some(top_p) = req.top_p {
+            body["top_p"] = top_p.into();
+        }
+        if let Some(user) = &req.user {
+            body["user"] = user.clone().into();
+        }
+        if let Some(effort) = req.reasoning_effort {
+            body["reasoning"] = serde_json::json!({"effort": effort, "summary": "auto"});
+        }
+        if let Some(tools) = &req.tools {
+            body["tools"] = tools
+                .iter()
+                .map(|tool| {
+                    serde_json::json!({
+                        "type": "function",
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                        "strict": tool.strict,
+                    })
+                })
+                .collect();
+        }
+        if let Some(tool_choice) = &req.tool_choice {
+            body["tool_choice"] = match tool_choice {
+                ToolChoice::Auto => "auto".into(),
+                ToolChoice::None => "none".into(),
+                ToolChoice::Required => "required".into(),
+                ToolChoice::Named(name) => serde_json::json!({"type": "function", "name": name}),
+            };
+        }
+        if let Some(parallel) = req.parallel_tool_calls {
+            body["parallel_tool_calls"] = parallel.into();
+        }
+        match &req.response_format {
+            Some(ResponseFormat::JsonObject) => {
+                body["text"] = serde_json::json!({"format": {"type": "json_object"}});
+            }
+            Some(ResponseFormat::JsonSchema {
+                name,
+                schema,
+                strict,
+            }) => {
+                body["text"] = serde_json::json!({"format": {
+                    "type": "json_schema",
+                    "name": name,
+                    "schema": schema,
+                    "strict": strict,
+                }});
+            }
+            Some(ResponseFormat::Text) | None => {}
+        }
+
+        // Responses-only parameters such as `previous_response_id`
+        for (key, value) in &req.extra {
+            body[key] = value.clone();
+        }
+
+        body
+    }
+
+    /// Build an authenticated request to the responses endpoint
+    fn responses_request(&self, headers: &HashMap<String, String>) -> HttpRequest {
+        let mut request = HttpRequest::post(format!("{}/responses", self.api_base))
+            .with_bearer_auth(&self.api_key);
+        for (name, value) in headers {
+            request = request.with_header(name, value);
+        }
+        request
+    }
+
+    /// Finish reason from the response status
+    fn convert_finish_reason(
+        status: Option<&str>,
+        incomplete_reason: Option<&str>,
+        has_tool_calls: bool,
+    ) -> FinishReason {
+        match (status, incomplete_reason) {
+            (Some("incomplete"), Some("max_output_tokens")) => FinishReason::Length,
+            (Some("incomplete"), Some("content_filter")) => FinishReason::ContentFilter,
+            (Some("incomplete"), Some(other)) => FinishReason::Other(other.to_string()),
+            _ if has_tool_calls => FinishReason::ToolCalls,
+            _ => FinishReason::Stop,
+        }
+    }
+
+    /// Convert a Responses API response to our ChatCompletionResponse
+    fn convert_response(response: ResponsesResponse) -> Result<ChatCompletionResponse, AiError> {
+        let mut content = Vec::new();
+        let mut has_tool_calls = false;
+
+        for item in response.output {
+            match item {
+                OutputItem::Reasoning { summary } => {
+                    let text = summary
+                        .into_iter()
+                        .map(|part| part.text)
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+                    if !text.is_empty() {
+                        content.push(ContentPart::Reasoning { text });
+                    }
+                }
+                OutputItem::Message { content: parts } => {
+                    content.extend(
+                        parts
+                            .into_iter()
+                            .filter_map(|part| match part.kind.as_str() {
+                                "output_text" => Some(ContentPart::Text { text: part.text }),
+                                "refusal" => Some(ContentPart::Text { text: part.refusal }),
+                                _ => None,
+                            }),
+                    );
+                }
+                OutputItem::FunctionCall {
+                    call_id,
+                    name,
+                    arguments,
+                } => {
+                    has_tool_calls = true;
+                    content.push(ContentPart::ToolCall {
+                        id: call_id,
+                        name,
+                        arguments: json_repair::parse(&arguments)?,
+                    });
+                }
+                OutputItem::Other => {}
+            }
+        }
+
+        let finish_reason = Self::convert_finish_reason(
+            response.status.as_deref(),
+            response
+                .incomplete_details
+                .as_ref()
+                .and_then(|details| details.reason.as_deref()),
+            has_tool_calls,
+        );
+
+        Ok(ChatCompletionResponse {
+            id: response.id,
+            model: response.model,
+            choices: vec![Choice {
+                index: 0,
+                message: Message {
+                    role: Role::Assistant,
+                    content,
+                    name: None,
+                    cache_control: None,
+                },
+                finish_reason,
+                logprobs: None,
+                stop_sequence: None,
+                filtered_categories: Vec::new(),
+            }],
+            usage: response
+                .usage
+                .map(ResponsesUsage::into_usage)
+                .unwrap_or_default(),
+            created: response.created_at,
+            system_fingerprint: None,
+            service_tier: response.service_tier,
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAiResponsesProvider {
+    fn info(&self) -> Arc<ProviderInfo> {
+        self.info.clone()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let body = Self::build_body(&req, false);
+        let wire = self.wire.as_ref();
+        let response = send_json(
+            "OpenAI",
+            &*self.http,
+            self.responses_request(&req.headers),
+            &body,
+            wire,
+        )
+        .await?;
+        let response: ResponsesResponse = read_json(response, wire).await?;
+
+        Self::convert_response(response)
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let body = Self::build_body(&req, true);
+        let response = send_json(
+            "OpenAI",
+            &*self.http,
+            self.responses_request(&req.headers),
+            &body,
+            self.wire.as_ref(),
+        )
+        .await?;
+        let wire = self.wire.clone();
+        let mut model = req.model.clone();
+
+        let stream = async_stream::try_stream! {
+            let mut events = Box::pin(sse_events(response, wire));
+            let mut id = String::new();
+            let mut has_tool_calls = false;
+
+            while let Some(event) = events.next().await {
+                let event = event?;
+                let data: serde_json::Value = serde_json::from_str(&event.data)?;
+
+                let mut message_delta = MessageDelta {
+                    role: None,
+                    content: None,
+                    reasoning: None,
+                    tool_calls: None,
+                };
+                let mut finish_reason = None;
+                let mut usage = None;
+                let mut service_tier = None;
+
+                match data["type"].as_str().unwrap_or_default() {
+                    "response.created" => {
+                        id = data.pointer("/response/id")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        if let Some(name) = data.pointer("/response/model").and_then(|v| v.as_str()) {
+                            model = name.to_string();
+                        }
+                        message_delta.role = Some(Role::Assistant);
+                    }
+                    "response.output_text.delta" => {
+                        message_delta.content = data["delta"].as_str().map(str::to_string);
+                    }
+                    "response.reasoning_summary_text.delta" => {
+                        message_delta.reasoning = data["delta"].as_str().map(str::to_string);
+                    }
+                    "response.output_item.done" if data.pointer("/item/type")
+                        .and_then(|v| v.as_str()) == Some("function_call") =>
+                    {
+                        let item = &data["item"];
+                        let arguments = serde_json::from_str(
+                            item["arguments"].as_str().unwrap_or("{}"),
+                        )?;
+                        has_tool_calls = true;
+                        message_delta.tool_calls = Some(vec![ContentPart::ToolCall {
+                            id: item["call_id"].as_str().unwrap_or_default().to_string(),
+                            name: item["name"].as_str().unwrap_or_default().to_string(),
+                            arguments,
+                        }]);
+                    }
+                    "response.completed" | "response.incomplete" => {
+                        let response = &data["response"];
+                        finish_reason = Some(Self::convert_finish_reason(
+                            response["status"].as_str(),
+                            response.pointer("/incomplete_details/reason").and_then(|v| v.as_str()),
+                            has_tool_calls,
+                        ));
+                        usage = response
+                            .get("usage")
+                            .and_then(|u| serde_json::from_value::<ResponsesUsage>(u.clone()).ok())
+                            .map(ResponsesUsage::into_usage);
+                        service_tier = response["service_tier"].as_str().map(str::to_string);
+                    }
+                    "response.failed" | "error" => {
+                        let message = data
+                            .pointer("/response/error/message")
+                            .or_else(|| data.get("message"))
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("response failed")
+                            .to_string();
+                        Err(AiError::provider(format!("OpenAI API error: {}", message)))?;
+                    }
+                    _ => continue,
+                }
+
+                yield ChatCompletionChunk {
+                    id: id.clone(),
+                    model: model.clone(),
+                    choices: vec![ChoiceDelta {
+                        index: 0,
+                        delta: message_delta,
+                        finish_reason,
+                    }],
+                    usage,
+                    system_fingerprint: None,
+                    service_tier,
+                };
+            }
+        };
+
+        Ok(Box::new(Box::pin(stream)))
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        let wire = self.wire.as_ref();
+        let request = self.responses_request(&HashMap::new());
+        let response = send_json("OpenAI", &*self.http, request, &body, wire).await?;
+        read_json(response, wire).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        let wire = self.wire.as_ref();
+        let request =
+            HttpRequest::get(format!("{}/models", self.api_base)).with_bearer_auth(&self.api_key);
+        let response = send("OpenAI", &*self.http, request, wire).await?;
+        let response: serde_json::Value = read_json(response, wire).await?;
+        Ok(convert_model_list(&response))
+    }
+}
+
+/// Models of an OpenAI-style `/models` response. Only `id` is required, as
+/// compatible servers (vLLM, Ollama, LM Studio) leave out the other fields.
+pub(crate) fn convert_model_list(raw: &serde_json::Value) -> Vec<ModelInfo> {
+    raw["data"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|model| {
+                    let mut info = ModelInfo::new(model["id"].as_str()?);
+                    info.owned_by = model["owned_by"].as_str().map(str::to_string);
+                    info.created = model["created"].as_u64();
+                    Some(info)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// ============================================================================
+// Responses API wire types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct ResponsesResponse {
+    id: String,
+    model: String,
+    status: Option<String>,
+    incomplete_details: Option<IncompleteDetails>,
+    #[serde(default)]
+    output: Vec<OutputItem>,
+    usage: Option<ResponsesUsage>,
+    created_at: Option<u64>,
+    service_tier: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IncompleteDetails {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OutputItem {
+    Message {
+        #[serde(default)]
+        content: Vec<OutputContent>,
+    },
+    Reasoning {
+        #[serde(default)]
+        summary: Vec<SummaryPart>,
+    },
+    FunctionCall {
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+    /// Built-in tool calls (web search, file search, ...) and future items
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputContent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    refusal: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SummaryPart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponsesUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+    input_tokens_details: Option<ResponsesInputTokensDetails>,
+    output_tokens_details: Option<ResponsesOutputTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponsesInputTokensDetails {
+    cached_tokens: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponsesOutputTokensDetails {
+    reasoning_tokens: Option<u32>,
+}
+
+impl ResponsesUsage {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.input_tokens,
+            completion_tokens: self.output_tokens,
+            total_tokens: self.input_tokens + self.output_tokens,
+            prompt_tokens_details: self
+                .input_tokens_details
+                .map(|details| PromptTokensDetails {
+                    cached_tokens: details.cached_tokens,
+                    audio_tokens: None,
+                }),
+            completion_tokens_details: self.output_tokens_details.map(|details| {
+                CompletionTokensDetails {
+                    reasoning_tokens: details.reasoning_tokens,
+                    audio_tokens: None,
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strict_and_parallel_tools() {
+        let mut req = ChatCompletionRequest::new("o4-mini", vec![Message::user("Hi")]);
+        req.tools = Some(vec![
+            Tool::new(
+                "get_weather",
+                "Current weather",
+                serde_json::json!({"type": "object"}),
+            )
+            .with_strict(true),
+            Tool::new(
+                "get_time",
+                "Current time",
+                serde_json::json!({"type": "object"}),
+            ),
+        ]);
+
+        let body = OpenAiResponsesProvider::build_body(&req, false);
+        assert_eq!(body["tools"][0]["strict"], true);
+        assert_eq!(body["tools"][1]["strict"], false);
+        assert!(body.get("parallel_tool_calls").is_none());
+
+        req.parallel_tool_calls = Some(false);
+        let body = OpenAiResponsesProvider::build_body(&req, false);
+        assert_eq!(body["parallel_tool_calls"], false);
+    }
+
+    #[test]
+    fn test_convert_response() {
+        let response: ResponsesResponse = serde_json::from_value(serde_json::json!({
+            "id": "resp_123",
+            "object": "response",
+            "created_at": 1741476542,
+            "model": "o4-mini",
+            "status": "completed",
+            "output": [
+                {"type": "reasoning", "id": "rs_1", "summary": [{"type": "summary_text", "text": "Checking the weather tool."}]},
+                {"type": "web_search_call", "id": "ws_1", "status": "completed"},
+                {"type": "function_call", "id": "fc_1", "call_id": "call_1", "name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+            ],
+            "usage": {
+                "input_tokens": 12,
+                "output_tokens": 30,
+                "total_tokens": 42,
+                "input_tokens_details": {"cached_tokens": 8},
+                "output_tokens_details": {"reasoning_tokens": 20}
+            }
+        }))
+        .unwrap();
+
+        let converted = OpenAiResponsesProvider::convert_response(response).unwrap();
+        let choice = &converted.choices[0];
+        assert_eq!(converted.id, "resp_123");
+        assert_eq!(choice.finish_reason, FinishReason::ToolCalls);
+        assert_eq!(converted.usage.total_tokens, 42);
+        assert_eq!(converted.usage.cached_tokens(), 8);
+        assert_eq!(converted.usage.reasoning_tokens(), 20);
+        assert_eq!(
+            converted
+                .usage
+                .prompt_tokens_details
+                .as_ref()
+                .and_then(|details| details.audio_tokens),
+            None
+        );
+        assert!(
+            matches!(&choice.message.content[0], ContentPart::Reasoning { text } if text == "Checking the weather tool.")
+        );
+        assert!(
+            matches!(&choice.message.content[1], ContentPart::ToolCall { name, .. } if name == "get_weather")
+        );
+    }
+}
@@ -0,0 +1,656 @@
+//! Perplexity provider implementation.
+//!
+//! Talks to Perplexity's chat completions API for the `sonar` models, which
+//! search the web before answering. The answer cites search results with
+//! `[n]` markers; they are mapped to `ContentPart::Citation`s whose sources
+//! are the result URLs, so they end up in `TextResult::citations`. Only
+//! non-streaming completions carry citations.
+//!
+//! Search is tuned with [`PerplexitySearch`], either for every request via
+//! [`PerplexityProvider::with_search`] or for one request:
+//!
+//! ```ignore
+//! let req = PerplexitySearch::new()
+//!     .with_domain("docs.rs")
+//!     .with_recency(SearchRecency::Month)
+//!     .apply(ChatCompletionRequest::new("sonar-pro", messages));
+//! ```
+//!
+//! The reasoning models' `<think>` block becomes `ContentPart::Reasoning`.
+
+use crate::http::{read_json, send_json, sse_events, Wire};
+use aidale_core::error::AiError;
+use aidale_core::http::{HttpClient, HttpRequest};
+use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use aidale_core::wire::WireObserver;
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const PERPLEXITY_API_BASE: &str = "https://api.perplexity.ai";
+
+/// How far back web search results may date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchRecency {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// How much search context is retrieved; more costs more
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchContextSize {
+    Low,
+    Medium,
+    High,
+}
+
+/// Web search options for Perplexity requests
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PerplexitySearch {
+    /// Domains to search, or to skip when prefixed with `-`
+    pub domains: Vec<String>,
+    pub recency: Option<SearchRecency>,
+    pub context_size: Option<SearchContextSize>,
+}
+
+impl PerplexitySearch {
+    /// Create options searching the whole web
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only search `domain`
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domains.push(domain.into());
+        self
+    }
+
+    /// Never search `domain`
+    pub fn without_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domains.push(format!("-{}", domain.into()));
+        self
+    }
+
+    /// Only use results published within `recency`
+    pub fn with_recency(mut self, recency: SearchRecency) -> Self {
+        self.recency = Some(recency);
+        self
+    }
+
+    /// Set how much search context is retrieved
+    pub fn with_context_size(mut self, context_size: SearchContextSize) -> Self {
+        self.context_size = Some(context_size);
+        self
+    }
+
+    /// Request parameters for these options
+    fn params(&self) -> impl Iterator<Item = (&'static str, serde_json::Value)> {
+        let domains = (!self.domains.is_empty())
+            .then(|| ("search_domain_filter", serde_json::json!(self.domains)));
+        let recency = self
+            .recency
+            .map(|recency| ("search_recency_filter", serde_json::json!(recency)));
+        let context_size = self.context_size.map(|size| {
+            (
+                "web_search_options",
+                serde_json::json!({"search_context_size": size}),
+            )
+        });
+        domains.into_iter().chain(recency).chain(context_size)
+    }
+
+    /// Set these options on a request, replacing any set before
+    pub fn apply(&self, mut req: ChatCompletionRequest) -> ChatCompletionRequest {
+        for (key, value) in self.params() {
+            req.extra.insert(key.to_string(), value);
+        }
+        req
+    }
+}
+
+/// Perplexity provider
+#[derive(Clone)]
+pub struct PerplexityProvider {
+    http: Arc<dyn HttpClient>,
+    api_key: String,
+    api_base: String,
+    info: Arc<ProviderInfo>,
+    search: PerplexitySearch,
+    wire: Option<Wire>,
+}
+
+impl std::fmt::Debug for PerplexityProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PerplexityProvider")
+            .field("api_base", &self.api_base)
+            .field("info", &self.info)
+            .field("search", &self.search)
+            .finish()
+    }
+}
+
+impl PerplexityProvider {
+    /// Create a new Perplexity provider with default configuration
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            http: Arc::new(reqwest::Client::new()),
+            api_key: api_key.into(),
+            api_base: PERPLEXITY_API_BASE.to_string(),
+            info: Arc::new(ProviderInfo {
+                id: "perplexity".to_string(),
+                name: "Perplexity".to_string(),
+            }),
+            search: PerplexitySearch::default(),
+            wire: None,
+        }
+    }
+
+    /// Set API base URL
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
+    /// Set default search options; a request's own options take precedence
+    pub fn with_search(mut self, search: PerplexitySearch) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// Use a preconfigured HTTP client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http = Arc::new(client);
+        self
+    }
+
+    /// Send requests through a custom HTTP backend, e.g. a runtime's `fetch`
+    pub fn with_http_backend(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Observe the JSON bodies sent to and received from the API
+    pub fn with_wire_observer(mut self, observer: Arc<dyn WireObserver>) -> Self {
+        self.wire = Some(Wire::new(self.info.id.clone(), observer));
+        self
+    }
+
+    /// Convert our Message type to a Perplexity message
+    fn convert_message(msg: &Message) -> serde_json::Value {
+        let role = match msg.role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant | Role::Tool => "assistant",
+        };
+        if msg.content.iter().any(|part| part.image_url().is_some()) {
+            let content: Vec<_> = msg
+                .content
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => {
+                        Some(serde_json::json!({"type": "text", "text": text}))
+                    }
+                    _ => part.image_url().map(
+                        |url| serde_json::json!({"type": "image_url", "image_url": {"url": url}}),
+                    ),
+                })
+                .collect();
+            return serde_json::json!({"role": role, "content": content});
+        }
+        let text = msg
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        serde_json::json!({"role": role, "content": text})
+    }
+
+    /// Build the request body for the chat endpoint
+    fn build_body(
+        &self,
+        req: &ChatCompletionRequest,
+        stream: bool,
+    ) -> Result<serde_json::Value, AiError> {
+        if req.tools.as_ref().is_some_and(|tools| !tools.is_empty()) {
+            return Err(AiError::unsupported(
+                "Perplexity does not support tool calling",
+            ));
+        }
+        let unsupported = req
+            .messages
+            .iter()
+            .flat_map(|msg| &msg.content)
+            .any(|part| {
+                matches!(
+                    part,
+                    ContentPart::File { .. }
+                        | ContentPart::FileRef { .. }
+                        | ContentPart::ToolCall { .. }
+                        | ContentPart::ToolResult { .. }
+                )
+            });
+        if unsupported {
+            return Err(AiError::unsupported(
+                "Perplexity does not support file attachments or tool results",
+            ));
+        }
+
+        let messages: Vec<_> = req.messages.iter().map(Self::convert_message).collect();
+        let mut body = serde_json::json!({
+            "model": req.model,
+            "messages": messages,
+            "stream": stream,
+        });
+
+        if let Some(temperature) = req.temperature {
+            body["temperature"] = temperature.into();
+        }
+        if let Some(max_tokens) = req.max_tokens {
+            body["max_tokens"] = max_tokens.into();
+        }
+        if let Some(top_p) = req.top_p {
+            body["top_p"] = top_p.into();
+        }
+        if let Some(frequency_penalty) = req.frequency_penalty {
+            body["frequency_penalty"] = frequency_penalty.into();
+        }
+        if let Some(presence_penalty) = req.presence_penalty {
+            body["presence_penalty"] = presence_penalty.into();
+        }
+        match &req.response_format {
+            Some(ResponseFormat::JsonSchema { schema, .. }) => {
+                body["response_format"] =
+                    serde_json::json!({"type": "json_schema", "json_schema": {"schema": schema}});
+            }
+            Some(ResponseFormat::JsonObject) => {
+                return Err(AiError::unsupported(
+                    "Perplexity only supports JSON schema response formats",
+                ));
+            }
+            Some(ResponseFormat::Text) | None => {}
+        }
+
+        for (key, value) in self.search.params() {
+            body[key] = value;
+        }
+        // Per-request parameters, including search options
+        for (key, value) in &req.extra {
+            body[key] = value.clone();
+        }
+
+        Ok(body)
+    }
+
+    /// Build an authenticated request to the chat endpoint
+    fn chat_request(&self, headers: &HashMap<String, String>) -> HttpRequest {
+        let mut request = HttpRequest::post(format!("{}/chat/completions", self.api_base))
+            .with_bearer_auth(&self.api_key);
+        for (name, value) in headers {
+            request = request.with_header(name, value);
+        }
+        request
+    }
+
+    /// Convert a Perplexity finish reason
+    fn convert_finish_reason(reason: &str) -> FinishReason {
+        match reason {
+            "stop" => FinishReason::Stop,
+            "length" => FinishReason::Length,
+            other => FinishReason::Other(other.to_string()),
+        }
+    }
+
+    /// Split the reasoning models' leading `<think>` block from the answer
+    fn split_reasoning(content: &str) -> (Option<&str>, &str) {
+        let Some(rest) = content.trim_start().strip_prefix("<think>") else {
+            return (None, content);
+        };
+        match rest.split_once("</think>") {
+            Some((thinking, answer)) => (Some(thinking.trim()), answer.trim_start()),
+            None => (Some(rest.trim()), ""),
+        }
+    }
+
+    /// Citations of `text` for the search result URLs
+    ///
+    /// Answers without `[n]` markers are cited as a whole.
+    fn citations(text: &str, urls: &[String]) -> Vec<Citation> {
+        let citations = Citation::from_markers(text, |n| {
+            let url = urls.get(n.checked_sub(1)?)?;
+            Some((url.clone(), None))
+        });
+        if !citations.is_empty() || urls.is_empty() || text.is_empty() {
+            return citations;
+        }
+        vec![Citation {
+            start: 0,
+            end: text.chars().count(),
+            text: text.to_string(),
+            sources: urls.to_vec(),
+            confidence: None,
+        }]
+    }
+
+    /// Convert a Perplexity response to our ChatCompletionResponse
+    fn convert_response(response: PerplexityResponse) -> ChatCompletionResponse {
+        // Older responses only list URLs under `citations`
+        let urls: Vec<String> = if response.search_results.is_empty() {
+            response.citations
+        } else {
+            response
+                .search_results
+                .into_iter()
+                .map(|result| result.url)
+                .collect()
+        };
+
+        let choices = response
+            .choices
+            .into_iter()
+            .map(|choice| {
+                let (reasoning, text) = Self::split_reasoning(&choice.message.content);
+                let mut content = Vec::new();
+                if let Some(reasoning) = reasoning {
+                    content.push(ContentPart::Reasoning {
+                        text: reasoning.to_string(),
+                    });
+                }
+                content.push(ContentPart::Text {
+                    text: text.to_string(),
+                });
+                content.extend(
+                    Self::citations(text, &urls)
+                        .into_iter()
+                        .map(ContentPart::Citation),
+                );
+                Choice {
+                    index: choice.index,
+                    message: Message {
+                        role: Role::Assistant,
+                        content,
+                        name: None,
+                        cache_control: None,
+                    },
+                    finish_reason: choice
+                        .finish_reason
+                        .as_deref()
+                        .map_or(FinishReason::Stop, Self::convert_finish_reason),
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }
+            })
+            .collect();
+
+        ChatCompletionResponse {
+            id: response.id,
+            model: response.model,
+            choices,
+            usage: response
+                .usage
+                .map(PerplexityUsage::into_usage)
+                .unwrap_or_default(),
+            created: response.created,
+            system_fingerprint: None,
+            service_tier: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for PerplexityProvider {
+    fn info(&self) -> Arc<ProviderInfo> {
+        self.info.clone()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let body = self.build_body(&req, false)?;
+        let wire = self.wire.as_ref();
+        let response = send_json(
+            "Perplexity",
+            &*self.http,
+            self.chat_request(&req.headers),
+            &body,
+            wire,
+        )
+        .await?;
+        let response: PerplexityResponse = read_json(response, wire).await?;
+
+        Ok(Self::convert_response(response))
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let body = self.build_body(&req, true)?;
+        let response = send_json(
+            "Perplexity",
+            &*self.http,
+            self.chat_request(&req.headers),
+            &body,
+            self.wire.as_ref(),
+        )
+        .await?;
+        let wire = self.wire.clone();
+
+        let stream = async_stream::try_stream! {
+            let mut events = Box::pin(sse_events(response, wire));
+            let mut first = true;
+
+            while let Some(event) = events.next().await {
+                let event = event?;
+                if event.data == "[DONE]" {
+                    break;
+                }
+                let chunk: PerplexityChunk = serde_json::from_str(&event.data)?;
+                let choices = chunk
+                    .choices
+                    .into_iter()
+                    .map(|choice| ChoiceDelta {
+                        index: choice.index,
+                        delta: MessageDelta {
+                            role: first.then_some(Role::Assistant),
+                            content: choice.delta.content.filter(|content| !content.is_empty()),
+                            reasoning: None,
+                            tool_calls: None,
+                        },
+                        finish_reason: choice
+                            .finish_reason
+                            .as_deref()
+                            .map(Self::convert_finish_reason),
+                    })
+                    .collect();
+                first = false;
+
+                yield ChatCompletionChunk {
+                    id: chunk.id,
+                    model: chunk.model,
+                    choices,
+                    usage: chunk.usage.map(PerplexityUsage::into_usage),
+                    system_fingerprint: None,
+                    service_tier: None,
+                };
+            }
+        };
+
+        Ok(Box::new(Box::pin(stream)))
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        let wire = self.wire.as_ref();
+        let request = self.chat_request(&HashMap::new());
+        let response = send_json("Perplexity", &*self.http, request, &body, wire).await?;
+        read_json(response, wire).await
+    }
+}
+
+// ============================================================================
+// Perplexity wire types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct PerplexityResponse {
+    id: String,
+    model: String,
+    created: Option<u64>,
+    choices: Vec<PerplexityChoice>,
+    usage: Option<PerplexityUsage>,
+    #[serde(default)]
+    citations: Vec<String>,
+    #[serde(default)]
+    search_results: Vec<PerplexitySearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexityChoice {
+    #[serde(default)]
+    index: u32,
+    finish_reason: Option<String>,
+    message: PerplexityMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexityMessage {
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexitySearchResult {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexityChunk {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    choices: Vec<PerplexityChunkChoice>,
+    usage: Option<PerplexityUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexityChunkChoice {
+    #[serde(default)]
+    index: u32,
+    finish_reason: Option<String>,
+    #[serde(default)]
+    delta: PerplexityDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PerplexityDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexityUsage {
+    #[serde(default)]
+    prompt_tokens: u32,
+    #[serde(default)]
+    completion_tokens: u32,
+}
+
+impl PerplexityUsage {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            total_tokens: self.prompt_tokens + self.completion_tokens,
+            prompt_tokens_details: None,
+            completion_tokens_details: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_options_and_citations() {
+        let provider = PerplexityProvider::new("key").with_search(
+            PerplexitySearch::new()
+                .with_recency(SearchRecency::Week)
+                .with_context_size(SearchContextSize::High),
+        );
+        let req = PerplexitySearch::new()
+            .with_domain("docs.rs")
+            .without_domain("reddit.com")
+            .with_recency(SearchRecency::Day)
+            .apply(ChatCompletionRequest::new(
+                "sonar",
+                vec![Message::user("Latest tokio?")],
+            ));
+        let body = provider.build_body(&req, false).unwrap();
+        assert_eq!(
+            body["search_domain_filter"],
+            serde_json::json!(["docs.rs", "-reddit.com"])
+        );
+        assert_eq!(body["search_recency_filter"], "day");
+        assert_eq!(body["web_search_options"]["search_context_size"], "high");
+
+        let response: PerplexityResponse = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "model": "sonar-reasoning",
+            "created": 1700000000,
+            "choices": [{
+                "index": 0,
+                "finish_reason": "stop",
+                "message": {
+                    "role": "assistant",
+                    "content": "<think>Check the docs.</think>\nTokio 1.40 is out [2]. It adds APIs [1][2]."
+                }
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 20, "total_tokens": 30},
+            "search_results": [
+                {"title": "Docs", "url": "https://docs.rs/tokio"},
+                {"title": "Blog", "url": "https://tokio.rs/blog"}
+            ]
+        }))
+        .unwrap();
+        let converted = PerplexityProvider::convert_response(response);
+        let content = &converted.choices[0].message.content;
+        assert!(
+            matches!(&content[0], ContentPart::Reasoning { text } if text == "Check the docs.")
+        );
+        let citations: Vec<_> = content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Citation(citation) => Some(citation),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].text, "Tokio 1.40 is out");
+        assert_eq!(citations[0].sources, ["https://tokio.rs/blog"]);
+        assert_eq!(
+            citations[1].sources,
+            ["https://docs.rs/tokio", "https://tokio.rs/blog"]
+        );
+
+        let unmarked = PerplexityProvider::citations("Yes.", &["https://a.com".to_string()]);
+        assert_eq!((unmarked[0].start, unmarked[0].end), (0, 4));
+    }
+}
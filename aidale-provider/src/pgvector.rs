@@ -0,0 +1,300 @@
+//! Postgres vector store using the pgvector extension.
+//!
+//! Records live in a table with an `id text` primary key, an `embedding
+//! vector(n)` column with an HNSW index for cosine distance, and a `payload
+//! jsonb` column with a GIN index. Filters are sent as JSON containment
+//! (`payload @> '{"lang": "en"}'`), which the GIN index serves.
+//!
+//! No Postgres driver is bundled. The store sends SQL through a
+//! [`PgExecutor`] wrapping the application's connection or pool; all
+//! parameters and selected columns are text, so an adapter stays small:
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! struct Postgres(tokio_postgres::Client);
+//!
+//! #[async_trait]
+//! impl PgExecutor for Postgres {
+//!     async fn execute(&self, sql: &str, params: &[&str]) -> Result<(), AiError> {
+//!         let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|p| p as _).collect();
+//!         self.0.execute(sql, &params).await.map_err(|e| AiError::provider(e.to_string()))?;
+//!         Ok(())
+//!     }
+//!
+//!     async fn query(&self, sql: &str, params: &[&str]) -> Result<Vec<Vec<Option<String>>>, AiError> {
+//!         let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|p| p as _).collect();
+//!         let rows = self.0.query(sql, &params).await.map_err(|e| AiError::provider(e.to_string()))?;
+//!         Ok(rows.iter().map(|row| (0..row.len()).map(|i| row.get(i)).collect()).collect())
+//!     }
+//! }
+//!
+//! let store = PgVectorStore::new(Arc::new(Postgres(client)), "documents");
+//! store.create_table(1536).await?;
+//! ```
+
+use aidale_core::error::AiError;
+use aidale_core::vector_store::{ScoredRecord, VectorQuery, VectorRecord, VectorStore};
+use async_trait::async_trait;
+use serde_json::json;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Connection to a Postgres database
+///
+/// `$1`, `$2`, ... placeholders in the SQL are bound to `params` in order,
+/// as text.
+#[async_trait]
+pub trait PgExecutor: Send + Sync + Debug + 'static {
+    /// Execute a statement
+    async fn execute(&self, sql: &str, params: &[&str]) -> Result<(), AiError>;
+
+    /// Run a query, returning every column of every row as text
+    async fn query(&self, sql: &str, params: &[&str]) -> Result<Vec<Vec<Option<String>>>, AiError>;
+}
+
+/// Quote a possibly schema-qualified identifier, e.g. `rag.documents`
+fn quote_ident(name: &str) -> String {
+    name.split('.')
+        .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// pgvector's text form of a vector, e.g. `[0.1,0.2]`
+fn vector_literal(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(|value| value.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+/// Postgres-backed vector store for a single table
+#[derive(Debug, Clone)]
+pub struct PgVectorStore {
+    executor: Arc<dyn PgExecutor>,
+    table: String,
+    batch_size: usize,
+}
+
+impl PgVectorStore {
+    /// Create a store for `table`, which may be schema-qualified
+    pub fn new(executor: Arc<dyn PgExecutor>, table: impl Into<String>) -> Self {
+        Self {
+            executor,
+            table: table.into(),
+            batch_size: 500,
+        }
+    }
+
+    /// Set the number of records inserted per statement (default 500)
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Create the extension, the table and its indexes unless they exist
+    pub async fn create_table(&self, dimensions: usize) -> Result<(), AiError> {
+        let table = quote_ident(&self.table);
+        let name = self.table.rsplit('.').next().unwrap_or(&self.table);
+        let statements = [
+            "CREATE EXTENSION IF NOT EXISTS vector".to_string(),
+            format!(
+                "CREATE TABLE IF NOT EXISTS {} (id text PRIMARY KEY, embedding vector({}) NOT NULL, payload jsonb NOT NULL DEFAULT '{{}}')",
+                table, dimensions
+            ),
+            format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} USING hnsw (embedding vector_cosine_ops)",
+                quote_ident(&format!("{}_embedding_idx", name)),
+                table
+            ),
+            format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} USING gin (payload jsonb_path_ops)",
+                quote_ident(&format!("{}_payload_idx", name)),
+                table
+            ),
+        ];
+        for sql in &statements {
+            self.executor.execute(sql, &[]).await?;
+        }
+        Ok(())
+    }
+
+    /// `INSERT ... ON CONFLICT` statement for `rows` records
+    fn upsert_sql(&self, rows: usize) -> String {
+        let values: Vec<String> = (0..rows)
+            .map(|row| {
+                let n = row * 3;
+                format!(
+                    "(${}::text, ${}::text::vector, ${}::text::jsonb)",
+                    n + 1,
+                    n + 2,
+                    n + 3
+                )
+            })
+            .collect();
+        format!(
+            "INSERT INTO {} (id, embedding, payload) VALUES {} \
+             ON CONFLICT (id) DO UPDATE SET embedding = EXCLUDED.embedding, payload = EXCLUDED.payload",
+            quote_ident(&self.table),
+            values.join(", ")
+        )
+    }
+
+    /// Similarity query and its parameters
+    fn search_sql(&self, query: &VectorQuery) -> (String, Vec<String>) {
+        let mut params = vec![vector_literal(&query.vector)];
+        let mut conditions = Vec::new();
+        if !query.filter.is_empty() {
+            params.push(json!(query.filter).to_string());
+            conditions.push(format!("payload @> ${}::text::jsonb", params.len()));
+        }
+        if let Some(min_score) = query.min_score {
+            params.push(min_score.to_string());
+            conditions.push(format!(
+                "1 - (embedding <=> $1::text::vector) >= ${}::text::float8",
+                params.len()
+            ));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+        let sql = format!(
+            "SELECT id, embedding::text, payload::text, 1 - (embedding <=> $1::text::vector) \
+             FROM {}{} ORDER BY embedding <=> $1::text::vector LIMIT {}",
+            quote_ident(&self.table),
+            filter,
+            query.limit
+        );
+        (sql, params)
+    }
+
+    fn convert_row(row: Vec<Option<String>>) -> Result<ScoredRecord, AiError> {
+        let invalid = || AiError::provider("Unexpected row from pgvector query");
+        let [id, vector, payload, score]: [Option<String>; 4] =
+            row.try_into().map_err(|_| invalid())?;
+        Ok(ScoredRecord {
+            record: VectorRecord {
+                id: id.ok_or_else(invalid)?,
+                vector: serde_json::from_str(&vector.ok_or_else(invalid)?)?,
+                payload: serde_json::from_str(payload.as_deref().unwrap_or("{}"))?,
+            },
+            score: score
+                .and_then(|score| score.parse().ok())
+                .ok_or_else(invalid)?,
+        })
+    }
+}
+
+#[async_trait]
+impl VectorStore for PgVectorStore {
+    async fn upsert(&self, records: Vec<VectorRecord>) -> Result<(), AiError> {
+        for batch in records.chunks(self.batch_size) {
+            let params: Vec<String> = batch
+                .iter()
+                .flat_map(|record| {
+                    [
+                        record.id.clone(),
+                        vector_literal(&record.vector),
+                        record.payload.to_string(),
+                    ]
+                })
+                .collect();
+            let params: Vec<&str> = params.iter().map(String::as_str).collect();
+            self.executor
+                .execute(&self.upsert_sql(batch.len()), &params)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &VectorQuery) -> Result<Vec<ScoredRecord>, AiError> {
+        let (sql, params) = self.search_sql(query);
+        let params: Vec<&str> = params.iter().map(String::as_str).collect();
+        self.executor
+            .query(&sql, &params)
+            .await?
+            .into_iter()
+            .map(Self::convert_row)
+            .collect()
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<(), AiError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let sql = format!(
+            "DELETE FROM {} WHERE id IN (SELECT jsonb_array_elements_text($1::text::jsonb))",
+            quote_ident(&self.table)
+        );
+        self.executor
+            .execute(&sql, &[&json!(ids).to_string()])
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records statements and answers queries with a fixed row
+    #[derive(Debug, Default)]
+    struct RecordingExecutor {
+        statements: Mutex<Vec<(String, Vec<String>)>>,
+    }
+
+    #[async_trait]
+    impl PgExecutor for RecordingExecutor {
+        async fn execute(&self, sql: &str, params: &[&str]) -> Result<(), AiError> {
+            let params = params.iter().map(|p| p.to_string()).collect();
+            self.statements
+                .lock()
+                .unwrap()
+                .push((sql.to_string(), params));
+            Ok(())
+        }
+
+        async fn query(
+            &self,
+            sql: &str,
+            params: &[&str],
+        ) -> Result<Vec<Vec<Option<String>>>, AiError> {
+            self.execute(sql, params).await?;
+            Ok(vec![vec![
+                Some("a".to_string()),
+                Some("[1,0]".to_string()),
+                Some(r#"{"lang": "en"}"#.to_string()),
+                Some("0.93".to_string()),
+            ]])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pgvector_statements() {
+        let executor = Arc::new(RecordingExecutor::default());
+        let store = PgVectorStore::new(executor.clone(), "rag.docs").with_batch_size(2);
+
+        let records = (0..3)
+            .map(|i| VectorRecord::new(i.to_string(), vec![1.0, 0.5], json!({"lang": "en"})))
+            .collect();
+        store.upsert(records).await.unwrap();
+        let query = VectorQuery::new(vec![1.0, 0.0], 5)
+            .with_filter("lang", json!("en"))
+            .with_min_score(0.5);
+        let hits = store.search(&query).await.unwrap();
+        assert_eq!(hits[0].record.id, "a");
+        assert_eq!(hits[0].record.vector, vec![1.0, 0.0]);
+        assert_eq!(hits[0].score, 0.93);
+
+        let statements = executor.statements.lock().unwrap();
+        assert_eq!(statements.len(), 3);
+        assert!(statements[0].0.starts_with(r#"INSERT INTO "rag"."docs""#));
+        assert_eq!(statements[0].1.len(), 6);
+        assert_eq!(statements[0].1[1], "[1,0.5]");
+        assert_eq!(statements[1].1.len(), 3);
+        let (sql, params) = &statements[2];
+        assert!(sql.contains("payload @> $2::text::jsonb"));
+        assert!(sql.ends_with("LIMIT 5"));
+        assert_eq!(params[1], r#"{"lang":"en"}"#);
+    }
+}
@@ -0,0 +1,357 @@
+//! Qdrant vector store.
+//!
+//! Implements [`VectorStore`] over Qdrant's REST API, so a Qdrant collection
+//! can back retrieval and semantic caching. Qdrant only accepts unsigned
+//! integers and UUIDs as point IDs; other record IDs are mapped to a
+//! deterministic UUID and the original ID is kept in the payload.
+//!
+//! Upserts are split into batches so large ingestions stay below Qdrant's
+//! request size limit. Filtered searches need a payload index on each
+//! filtered field to stay fast on large collections.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let store = QdrantStore::new("http://localhost:6333", "docs");
+//! store.ensure_collection(1536).await?;
+//! store.create_payload_index("lang", PayloadSchema::Keyword).await?;
+//! ```
+
+use crate::http::{read_json, send, send_json};
+use aidale_core::error::AiError;
+use aidale_core::http::{HttpClient, HttpRequest};
+use aidale_core::vector_store::{ScoredRecord, VectorQuery, VectorRecord, VectorStore};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Payload field holding the original record ID
+const ID_FIELD: &str = "_aidale_id";
+
+/// Type of a payload field index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadSchema {
+    Keyword,
+    Integer,
+    Float,
+    Bool,
+}
+
+impl PayloadSchema {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Keyword => "keyword",
+            Self::Integer => "integer",
+            Self::Float => "float",
+            Self::Bool => "bool",
+        }
+    }
+}
+
+/// Qdrant-backed vector store for a single collection
+#[derive(Clone)]
+pub struct QdrantStore {
+    http: Arc<dyn HttpClient>,
+    url: String,
+    collection: String,
+    api_key: Option<String>,
+    batch_size: usize,
+}
+
+impl std::fmt::Debug for QdrantStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QdrantStore")
+            .field("url", &self.url)
+            .field("collection", &self.collection)
+            .finish()
+    }
+}
+
+impl QdrantStore {
+    /// Create a store for `collection` on the Qdrant server at `url`
+    pub fn new(url: impl Into<String>, collection: impl Into<String>) -> Self {
+        Self {
+            http: Arc::new(reqwest::Client::new()),
+            url: url.into().trim_end_matches('/').to_string(),
+            collection: collection.into(),
+            api_key: None,
+            batch_size: 256,
+        }
+    }
+
+    /// Set the API key (Qdrant Cloud)
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Set the number of points sent per upsert request (default 256)
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Use a preconfigured HTTP client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http = Arc::new(client);
+        self
+    }
+
+    /// Send requests through a custom HTTP backend, e.g. a runtime's `fetch`
+    pub fn with_http_backend(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Create the collection with cosine distance and `dimensions`-sized
+    /// vectors
+    pub async fn create_collection(&self, dimensions: usize) -> Result<(), AiError> {
+        let body = json!({"vectors": {"size": dimensions, "distance": "Cosine"}});
+        let url = format!("{}/collections/{}", self.url, self.collection);
+        send_json(
+            "Qdrant",
+            &*self.http,
+            self.request(HttpRequest::put(url)),
+            &body,
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Create the collection unless it already exists
+    pub async fn ensure_collection(&self, dimensions: usize) -> Result<(), AiError> {
+        let url = format!("{}/collections/{}/exists", self.url, self.collection);
+        let response = send(
+            "Qdrant",
+            &*self.http,
+            self.request(HttpRequest::get(url)),
+            None,
+        )
+        .await?;
+        let response: ExistsResponse = read_json(response, None).await?;
+        if response.result.exists {
+            return Ok(());
+        }
+        self.create_collection(dimensions).await
+    }
+
+    /// Index a payload field so filtered searches on it stay fast
+    pub async fn create_payload_index(
+        &self,
+        field: &str,
+        schema: PayloadSchema,
+    ) -> Result<(), AiError> {
+        let body = json!({"field_name": field, "field_schema": schema.as_str()});
+        let url = format!(
+            "{}/collections/{}/index?wait=true",
+            self.url, self.collection
+        );
+        send_json(
+            "Qdrant",
+            &*self.http,
+            self.request(HttpRequest::put(url)),
+            &body,
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn request(&self, request: HttpRequest) -> HttpRequest {
+        match &self.api_key {
+            Some(key) => request.with_header("api-key", key),
+            None => request,
+        }
+    }
+
+    fn points_url(&self, action: &str) -> String {
+        format!(
+            "{}/collections/{}/points{}",
+            self.url, self.collection, action
+        )
+    }
+
+    /// Map a record ID to a valid Qdrant point ID
+    fn point_id(id: &str) -> serde_json::Value {
+        if let Ok(number) = id.parse::<u64>() {
+            json!(number)
+        } else if uuid::Uuid::parse_str(id).is_ok() {
+            json!(id)
+        } else {
+            json!(uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, id.as_bytes()).to_string())
+        }
+    }
+
+    /// Convert a search hit back to a record, restoring the original ID
+    fn convert_point(point: QdrantPoint) -> ScoredRecord {
+        let mut payload = point.payload.unwrap_or_else(|| json!({}));
+        let id = payload
+            .as_object_mut()
+            .and_then(|fields| fields.remove(ID_FIELD))
+            .and_then(|id| id.as_str().map(str::to_string))
+            .unwrap_or_else(|| match point.id {
+                serde_json::Value::String(id) => id,
+                other => other.to_string(),
+            });
+
+        ScoredRecord {
+            record: VectorRecord {
+                id,
+                vector: point.vector.unwrap_or_default(),
+                payload,
+            },
+            score: point.score,
+        }
+    }
+}
+
+#[async_trait]
+impl VectorStore for QdrantStore {
+    async fn upsert(&self, records: Vec<VectorRecord>) -> Result<(), AiError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let points: Vec<_> = records
+            .into_iter()
+            .map(|record| {
+                let mut payload = match record.payload {
+                    serde_json::Value::Object(fields) => fields,
+                    serde_json::Value::Null => serde_json::Map::new(),
+                    other => serde_json::Map::from_iter([("value".to_string(), other)]),
+                };
+                payload.insert(ID_FIELD.to_string(), json!(record.id));
+                json!({
+                    "id": Self::point_id(&record.id),
+                    "vector": record.vector,
+                    "payload": payload,
+                })
+            })
+            .collect();
+
+        for batch in points.chunks(self.batch_size) {
+            let url = self.points_url("?wait=true");
+            let body = json!({"points": batch});
+            send_json(
+                "Qdrant",
+                &*self.http,
+                self.request(HttpRequest::put(url)),
+                &body,
+                None,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &VectorQuery) -> Result<Vec<ScoredRecord>, AiError> {
+        let mut body = json!({
+            "vector": query.vector,
+            "limit": query.limit,
+            "with_payload": true,
+            "with_vector": true,
+        });
+        if let Some(min_score) = query.min_score {
+            body["score_threshold"] = json!(min_score);
+        }
+        if !query.filter.is_empty() {
+            let must: Vec<_> = query
+                .filter
+                .iter()
+                .map(|(key, value)| json!({"key": key, "match": {"value": value}}))
+                .collect();
+            body["filter"] = json!({"must": must});
+        }
+
+        let url = self.points_url("/search");
+        let response = send_json(
+            "Qdrant",
+            &*self.http,
+            self.request(HttpRequest::post(url)),
+            &body,
+            None,
+        )
+        .await?;
+        let response: SearchResponse = read_json(response, None).await?;
+
+        Ok(response
+            .result
+            .into_iter()
+            .map(Self::convert_point)
+            .collect())
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<(), AiError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let points: Vec<_> = ids.iter().map(|id| Self::point_id(id)).collect();
+        let url = self.points_url("/delete?wait=true");
+        let body = json!({"points": points});
+        send_json(
+            "Qdrant",
+            &*self.http,
+            self.request(HttpRequest::post(url)),
+            &body,
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Qdrant API types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct ExistsResponse {
+    result: CollectionExists,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionExists {
+    exists: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    result: Vec<QdrantPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QdrantPoint {
+    id: serde_json::Value,
+    score: f32,
+    #[serde(default)]
+    payload: Option<serde_json::Value>,
+    #[serde(default)]
+    vector: Option<Vec<f32>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_ids_round_trip() {
+        assert_eq!(QdrantStore::point_id("42"), json!(42));
+        let uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        assert_eq!(QdrantStore::point_id(uuid), json!(uuid));
+
+        let mapped = QdrantStore::point_id("doc-1#3");
+        assert_eq!(mapped, QdrantStore::point_id("doc-1#3"));
+
+        let hit = QdrantStore::convert_point(QdrantPoint {
+            id: mapped,
+            score: 0.9,
+            payload: Some(json!({"text": "chunk", ID_FIELD: "doc-1#3"})),
+            vector: None,
+        });
+        assert_eq!(hit.record.id, "doc-1#3");
+        assert_eq!(hit.record.payload, json!({"text": "chunk"}));
+    }
+}
@@ -0,0 +1,89 @@
+//! Shared request and response handling for rerank APIs.
+//!
+//! Cohere and Jina accept the same body and return the same result list.
+
+use crate::http::{read_json, send_json, Wire};
+use aidale_core::error::AiError;
+use aidale_core::http::{HttpClient, HttpRequest};
+use aidale_core::rerank::{RerankRequest, RerankResult};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct RerankResponse {
+    results: Vec<RerankHit>,
+}
+
+#[derive(Deserialize)]
+struct RerankHit {
+    index: usize,
+    relevance_score: f32,
+}
+
+/// Request body for a rerank endpoint
+pub(crate) fn rerank_body(model: &str, req: &RerankRequest) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "model": model,
+        "query": req.query,
+        "documents": req.documents,
+    });
+    if let Some(top_n) = req.top_n {
+        body["top_n"] = serde_json::json!(top_n);
+    }
+    body
+}
+
+/// Send a rerank request and return results sorted by descending score
+pub(crate) async fn send_rerank(
+    provider: &str,
+    http: &dyn HttpClient,
+    request: HttpRequest,
+    model: &str,
+    req: &RerankRequest,
+    wire: Option<&Wire>,
+) -> Result<Vec<RerankResult>, AiError> {
+    if req.documents.is_empty() {
+        return Ok(Vec::new());
+    }
+    let body = rerank_body(model, req);
+    let response = send_json(provider, http, request, &body, wire).await?;
+    let response: RerankResponse = read_json(response, wire).await?;
+    Ok(convert_results(response))
+}
+
+fn convert_results(response: RerankResponse) -> Vec<RerankResult> {
+    let mut results: Vec<RerankResult> = response
+        .results
+        .into_iter()
+        .map(|hit| RerankResult {
+            index: hit.index,
+            score: hit.relevance_score,
+        })
+        .collect();
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rerank_body_and_results() {
+        let req = RerankRequest::new("capital of France", vec!["Paris".into(), "Berlin".into()])
+            .with_top_n(1);
+        let body = rerank_body("rerank-v3.5", &req);
+        assert_eq!(body["documents"][1], "Berlin");
+        assert_eq!(body["top_n"], 1);
+
+        let response: RerankResponse = serde_json::from_value(serde_json::json!({
+            "results": [
+                {"index": 1, "relevance_score": 0.02},
+                {"index": 0, "relevance_score": 0.97}
+            ]
+        }))
+        .unwrap();
+        let results = convert_results(response);
+        assert_eq!(results[0].index, 0);
+        assert_eq!(results[1].score, 0.02);
+    }
+}
@@ -0,0 +1,730 @@
+//! Vertex AI provider for Gemini models.
+//!
+//! Authenticates with OAuth access tokens from a
+//! [`TokenSource`](crate::google_auth::TokenSource) — Application Default
+//! Credentials by default — and talks to the regional `generateContent`
+//! endpoints of a GCP project.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use aidale_provider::VertexAiProvider;
+//!
+//! // Uses GOOGLE_APPLICATION_CREDENTIALS / gcloud / metadata server, and
+//! // GOOGLE_CLOUD_PROJECT unless the credentials name a project.
+//! let provider = VertexAiProvider::from_env()?.with_location("europe-west4");
+//! ```
+
+use crate::google_auth::{self, TokenCache, TokenSource};
+use crate::http::{read_json, send_json, sse_events, Wire};
+use aidale_core::error::AiError;
+use aidale_core::http::{HttpRequest, HttpResponse};
+use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use aidale_core::wire::WireObserver;
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const DEFAULT_LOCATION: &str = "us-central1";
+
+/// Vertex AI provider
+#[derive(Clone)]
+pub struct VertexAiProvider {
+    client: reqwest::Client,
+    tokens: Arc<TokenCache>,
+    project_id: String,
+    location: String,
+    api_base: Option<String>,
+    info: Arc<ProviderInfo>,
+    wire: Option<Wire>,
+}
+
+impl std::fmt::Debug for VertexAiProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VertexAiProvider")
+            .field("project_id", &self.project_id)
+            .field("location", &self.location)
+            .field("credentials", self.tokens.source())
+            .field("info", &self.info)
+            .finish()
+    }
+}
+
+impl VertexAiProvider {
+    /// Create a provider for a project using the given credentials
+    pub fn new(project_id: impl Into<String>, credentials: Arc<dyn TokenSource>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            tokens: Arc::new(TokenCache::new(credentials)),
+            project_id: project_id.into(),
+            location: DEFAULT_LOCATION.to_string(),
+            api_base: None,
+            info: Arc::new(ProviderInfo {
+                id: "vertex".to_string(),
+                name: "Vertex AI".to_string(),
+            }),
+            wire: None,
+        }
+    }
+
+    /// Create a provider from Application Default Credentials.
+    ///
+    /// The project comes from `GOOGLE_CLOUD_PROJECT` or the credentials file,
+    /// and the location from `GOOGLE_CLOUD_LOCATION` (default `us-central1`).
+    pub fn from_env() -> Result<Self, AiError> {
+        let credentials = google_auth::application_default()?;
+        let project_id = std::env::var("GOOGLE_CLOUD_PROJECT")
+            .ok()
+            .or_else(|| credentials.project_id())
+            .ok_or_else(|| {
+                AiError::configuration(
+                    "No GCP project: set GOOGLE_CLOUD_PROJECT or use credentials with a project_id",
+                )
+            })?;
+
+        let mut provider = Self::new(project_id, credentials);
+        if let Ok(location) = std::env::var("GOOGLE_CLOUD_LOCATION") {
+            provider.location = location;
+        }
+        Ok(provider)
+    }
+
+    /// Set the region (e.g. `europe-west4`, or `global`)
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = location.into();
+        self
+    }
+
+    /// Override the API base URL (defaults to the regional endpoint)
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = Some(api_base.into());
+        self
+    }
+
+    /// Use a preconfigured HTTP client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Observe the JSON bodies sent to and received from the API
+    pub fn with_wire_observer(mut self, observer: Arc<dyn WireObserver>) -> Self {
+        self.wire = Some(Wire::new(self.info.id.clone(), observer));
+        self
+    }
+
+    /// URL of a model method, e.g. `generateContent`
+    fn model_url(&self, model: &str, method: &str) -> String {
+        let api_base = match &self.api_base {
+            Some(api_base) => api_base.clone(),
+            None if self.location == "global" => "https://aiplatform.googleapis.com/v1".to_string(),
+            None => format!("https://{}-aiplatform.googleapis.com/v1", self.location),
+        };
+        format!(
+            "{}/projects/{}/locations/{}/publishers/google/models/{}:{}",
+            api_base, self.project_id, self.location, model, method
+        )
+    }
+
+    /// Send a request to a model method with a fresh access token
+    async fn send(
+        &self,
+        headers: &HashMap<String, String>,
+        url: String,
+        body: &serde_json::Value,
+    ) -> Result<HttpResponse, AiError> {
+        let token = self.tokens.token(&self.client).await?;
+        let mut request = HttpRequest::post(url).with_bearer_auth(token);
+        for (name, value) in headers {
+            request = request.with_header(name, value);
+        }
+
+        let result = send_json("Vertex", &self.client, request, body, self.wire.as_ref()).await;
+        if let Err(AiError::Authentication { .. }) = &result {
+            // The token may have been revoked; fetch a new one next time
+            self.tokens.invalidate().await;
+        }
+        result
+    }
+
+    /// Convert an image URL to an `inlineData` or `fileData` part
+    fn convert_image(url: &str) -> serde_json::Value {
+        if let Some((mime_type, data)) = url
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(";base64,"))
+        {
+            return serde_json::json!({"inlineData": {"mimeType": mime_type, "data": data}});
+        }
+
+        let extension = url.rsplit('.').next().unwrap_or_default().to_lowercase();
+        let mime_type = match extension.as_str() {
+            "png" => "image/png",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            _ => "image/jpeg",
+        };
+        serde_json::json!({"fileData": {"mimeType": mime_type, "fileUri": url}})
+    }
+
+    /// Build the request body for `generateContent`
+    ///
+    /// Gemini has no tool call IDs, so tool results are matched to their
+    /// function name through the preceding assistant tool calls.
+    fn build_body(req: &ChatCompletionRequest) -> serde_json::Value {
+        let mut system = Vec::new();
+        let mut contents = Vec::new();
+        let mut call_names: HashMap<&str, &str> = HashMap::new();
+
+        for msg in &req.messages {
+            let (role, parts): (&str, Vec<_>) = match msg.role {
+                Role::System => {
+                    system.extend(msg.content.iter().filter_map(|part| match part {
+                        ContentPart::Text { text } => Some(serde_json::json!({"text": text})),
+                        _ => None,
+                    }));
+                    continue;
+                }
+                Role::User => (
+                    "user",
+                    msg.content
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::Text { text } => Some(serde_json::json!({"text": text})),
+                            ContentPart::Image { url } => Some(Self::convert_image(url)),
+                            // Gemini reads PDFs and other documents inline like images
+                            ContentPart::ImageData {
+                                mime_type: mime,
+                                data,
+                            }
+                            | ContentPart::File { mime, data, .. } => Some(
+                                serde_json::json!({"inlineData": {"mimeType": mime, "data": data}}),
+                            ),
+                            _ => None,
+                        })
+                        .collect(),
+                ),
+                Role::Assistant => (
+                    "model",
+                    msg.content
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::Text { text } => Some(serde_json::json!({"text": text})),
+                            ContentPart::ToolCall {
+                                id,
+                                name,
+                                arguments,
+                            } => {
+                                call_names.insert(id, name);
+                                Some(serde_json::json!({
+                                    "functionCall": {"name": name, "args": arguments},
+                                }))
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                ),
+                Role::Tool => (
+                    "user",
+                    msg.content
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::ToolResult { id, result } => {
+                                let name = call_names.get(id.as_str()).copied().unwrap_or(id);
+                                // The response field must be a JSON object
+                                let response = match result {
+                                    serde_json::Value::Object(_) => result.clone(),
+                                    other => serde_json::json!({"content": other}),
+                                };
+                                Some(serde_json::json!({
+                                    "functionResponse": {"name": name, "response": response},
+                                }))
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                ),
+            };
+            if !parts.is_empty() {
+                contents.push(serde_json::json!({"role": role, "parts": parts}));
+            }
+        }
+
+        let mut body = serde_json::json!({ "contents": contents });
+        if !system.is_empty() {
+            body["systemInstruction"] = serde_json::json!({ "parts": system });
+        }
+
+        let mut config = serde_json::Map::new();
+        if let Some(temperature) = req.temperature {
+            config.insert("temperature".into(), temperature.into());
+        }
+        if let Some(top_p) = req.top_p {
+            config.insert("topP".into(), top_p.into());
+        }
+        if let Some(max_tokens) = req.max_tokens {
+            config.insert("maxOutputTokens".into(), max_tokens.into());
+        }
+        if let Some(frequency_penalty) = req.frequency_penalty {
+            config.insert("frequencyPenalty".into(), frequency_penalty.into());
+        }
+        if let Some(presence_penalty) = req.presence_penalty {
+            config.insert("presencePenalty".into(), presence_penalty.into());
+        }
+        if let Some(stop) = &req.stop {
+            config.insert("stopSequences".into(), serde_json::json!(stop));
+        }
+        if let Some(seed) = req.seed {
+            config.insert("seed".into(), seed.into());
+        }
+        if let Some(n) = req.n {
+            config.insert("candidateCount".into(), n.into());
+        }
+        if let Some(effort) = req.reasoning_effort {
+            let budget = match effort {
+                ReasoningEffort::Minimal => 0,
+                ReasoningEffort::Low => 1024,
+                ReasoningEffort::Medium => 8192,
+                ReasoningEffort::High => 24576,
+            };
+            config.insert(
+                "thinkingConfig".into(),
+                serde_json::json!({"thinkingBudget": budget, "includeThoughts": budget > 0}),
+            );
+        }
+        match &req.response_format {
+            Some(ResponseFormat::JsonObject) => {
+                config.insert("responseMimeType".into(), "application/json".into());
+            }
+            Some(ResponseFormat::JsonSchema { schema, .. }) => {
+                config.insert("responseMimeType".into(), "application/json".into());
+                config.insert("responseJsonSchema".into(), schema.clone());
+            }
+            Some(ResponseFormat::Text) | None => {}
+        }
+        if !config.is_empty() {
+            body["generationConfig"] = serde_json::Value::Object(config);
+        }
+
+        if let Some(tools) = &req.tools {
+            let declarations: Vec<_> = tools
+                .iter()
+                .map(|tool| {
+                    serde_json::json!({
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    })
+                })
+                .collect();
+            body["tools"] = serde_json::json!([{ "functionDeclarations": declarations }]);
+        }
+        if let Some(tool_choice) = &req.tool_choice {
+            let config = match tool_choice {
+                ToolChoice::Auto => serde_json::json!({"mode": "AUTO"}),
+                ToolChoice::None => serde_json::json!({"mode": "NONE"}),
+                ToolChoice::Required => serde_json::json!({"mode": "ANY"}),
+                ToolChoice::Named(name) => {
+                    serde_json::json!({"mode": "ANY", "allowedFunctionNames": [name]})
+                }
+            };
+            body["toolConfig"] = serde_json::json!({ "functionCallingConfig": config });
+        }
+
+        // Vertex-specific parameters such as `safetySettings` or `labels`
+        for (key, value) in &req.extra {
+            body[key] = value.clone();
+        }
+
+        body
+    }
+
+    /// Convert a Gemini finish reason
+    fn convert_finish_reason(reason: &str) -> FinishReason {
+        match reason {
+            "STOP" => FinishReason::Stop,
+            "MAX_TOKENS" => FinishReason::Length,
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => {
+                FinishReason::ContentFilter
+            }
+            other => FinishReason::Other(other.to_lowercase()),
+        }
+    }
+
+    /// Convert candidate parts to content parts
+    fn convert_parts(parts: Vec<GeminiPart>) -> Vec<ContentPart> {
+        parts
+            .into_iter()
+            .filter_map(|part| {
+                if let Some(call) = part.function_call {
+                    return Some(ContentPart::ToolCall {
+                        id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+                        name: call.name,
+                        arguments: call.args,
+                    });
+                }
+                match part.text {
+                    Some(text) if part.thought => Some(ContentPart::Reasoning { text }),
+                    Some(text) => Some(ContentPart::Text { text }),
+                    None => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Convert a Gemini response to our ChatCompletionResponse
+    fn convert_response(model: &str, response: GeminiResponse) -> ChatCompletionResponse {
+        let mut choices: Vec<Choice> = response
+            .candidates
+            .into_iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                let content = Self::convert_parts(candidate.content.parts);
+                let has_tool_calls = content
+                    .iter()
+                    .any(|part| matches!(part, ContentPart::ToolCall { .. }));
+                let finish_reason = match candidate.finish_reason.as_deref() {
+                    Some("STOP") | None if has_tool_calls => FinishReason::ToolCalls,
+                    Some(reason) => Self::convert_finish_reason(reason),
+                    None => FinishReason::Stop,
+                };
+                Choice {
+                    index: candidate.index.unwrap_or(i as u32),
+                    message: Message {
+                        role: Role::Assistant,
+                        content,
+                        name: None,
+                        cache_control: None,
+                    },
+                    finish_reason,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: blocked_categories(candidate.safety_ratings),
+                }
+            })
+            .collect();
+
+        // A blocked prompt comes back without candidates
+        let feedback = response.prompt_feedback.filter(|_| choices.is_empty());
+        if let Some(feedback) = feedback {
+            let ratings = serde_json::from_value(feedback["safetyRatings"].clone());
+            let mut categories = blocked_categories(ratings.unwrap_or_default());
+            if categories.is_empty() {
+                // Blocked for a reason other than the ratings, e.g. `BLOCKLIST`
+                categories.extend(feedback["blockReason"].as_str().map(str::to_string));
+            }
+            choices.push(Choice {
+                index: 0,
+                message: Message {
+                    role: Role::Assistant,
+                    content: Vec::new(),
+                    name: None,
+                    cache_control: None,
+                },
+                finish_reason: FinishReason::ContentFilter,
+                logprobs: None,
+                stop_sequence: None,
+                filtered_categories: categories,
+            });
+        }
+
+        ChatCompletionResponse {
+            id: response.response_id.unwrap_or_default(),
+            model: response.model_version.unwrap_or_else(|| model.to_string()),
+            choices,
+            usage: response
+                .usage_metadata
+                .map(GeminiUsage::into_usage)
+                .unwrap_or_default(),
+            created: None,
+            system_fingerprint: None,
+            service_tier: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for VertexAiProvider {
+    fn info(&self) -> Arc<ProviderInfo> {
+        self.info.clone()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let body = Self::build_body(&req);
+        let url = self.model_url(&req.model, "generateContent");
+        let response = self.send(&req.headers, url, &body).await?;
+        let response: GeminiResponse = read_json(response, self.wire.as_ref()).await?;
+
+        Ok(Self::convert_response(&req.model, response))
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        let body = Self::build_body(&req);
+        let url = format!(
+            "{}?alt=sse",
+            self.model_url(&req.model, "streamGenerateContent")
+        );
+        let response = self.send(&req.headers, url, &body).await?;
+        let wire = self.wire.clone();
+        let model = req.model.clone();
+
+        let stream = async_stream::try_stream! {
+            let mut events = Box::pin(sse_events(response, wire));
+            let mut started = false;
+            let mut saw_tool_calls = false;
+
+            while let Some(event) = events.next().await {
+                let event = event?;
+                let response: GeminiResponse = serde_json::from_str(&event.data)?;
+                // Usage is cumulative; only report it with the final chunk
+                let finished = response
+                    .candidates
+                    .iter()
+                    .any(|candidate| candidate.finish_reason.is_some());
+
+                let chunk = Self::convert_response(&model, response);
+                let choices = chunk
+                    .choices
+                    .into_iter()
+                    .map(|choice| {
+                        let mut delta = MessageDelta {
+                            role: (!started).then_some(Role::Assistant),
+                            content: None,
+                            reasoning: None,
+                            tool_calls: None,
+                        };
+                        let mut tool_calls = Vec::new();
+                        for part in choice.message.content {
+                            match part {
+                                ContentPart::Text { text } => {
+                                    delta.content.get_or_insert_with(String::new).push_str(&text)
+                                }
+                                ContentPart::Reasoning { text } => {
+                                    delta.reasoning.get_or_insert_with(String::new).push_str(&text)
+                                }
+                                call @ ContentPart::ToolCall { .. } => tool_calls.push(call),
+                                _ => {}
+                            }
+                        }
+                        if !tool_calls.is_empty() {
+                            saw_tool_calls = true;
+                            delta.tool_calls = Some(tool_calls);
+                        }
+                        // Tool calls may arrive before the chunk that finishes
+                        let finish_reason = match choice.finish_reason {
+                            FinishReason::Stop if saw_tool_calls => FinishReason::ToolCalls,
+                            reason => reason,
+                        };
+                        ChoiceDelta {
+                            index: choice.index,
+                            delta,
+                            finish_reason: finished.then_some(finish_reason),
+                        }
+                    })
+                    .collect();
+                started = true;
+
+                yield ChatCompletionChunk {
+                    id: chunk.id,
+                    model: chunk.model,
+                    choices,
+                    usage: if finished { Some(chunk.usage) } else { None },
+                    system_fingerprint: None,
+                    service_tier: None,
+                };
+            }
+        };
+
+        Ok(Box::new(Box::pin(stream)))
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        // Gemini bodies don't name the model, it is part of the URL
+        let mut body = body;
+        let model = body
+            .as_object_mut()
+            .and_then(|body| body.remove("model"))
+            .and_then(|model| model.as_str().map(str::to_string))
+            .ok_or_else(|| AiError::invalid_request("Raw Vertex requests need a 'model' field"))?;
+        let url = self.model_url(&model, "generateContent");
+        let response = self.send(&HashMap::new(), url, &body).await?;
+        read_json(response, self.wire.as_ref()).await
+    }
+}
+
+// ============================================================================
+// Gemini wire types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+    prompt_feedback: Option<serde_json::Value>,
+    usage_metadata: Option<GeminiUsage>,
+    model_version: Option<String>,
+    response_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiCandidate {
+    #[serde(default)]
+    content: GeminiContent,
+    finish_reason: Option<String>,
+    index: Option<u32>,
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiSafetyRating {
+    category: String,
+    #[serde(default)]
+    blocked: bool,
+}
+
+/// Categories of the ratings that blocked the content
+fn blocked_categories(ratings: Vec<GeminiSafetyRating>) -> Vec<String> {
+    ratings
+        .into_iter()
+        .filter(|rating| rating.blocked)
+        .map(|rating| rating.category)
+        .collect()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct GeminiContent {
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiPart {
+    text: Option<String>,
+    #[serde(default)]
+    thought: bool,
+    function_call: Option<GeminiFunctionCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiFunctionCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsage {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    thoughts_token_count: Option<u32>,
+    #[serde(default)]
+    total_token_count: u32,
+    cached_content_token_count: Option<u32>,
+}
+
+impl GeminiUsage {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_token_count,
+            completion_tokens: self.candidates_token_count + self.thoughts_token_count.unwrap_or(0),
+            total_tokens: self.total_token_count,
+            prompt_tokens_details: self.cached_content_token_count.map(|cached| {
+                PromptTokensDetails {
+                    cached_tokens: Some(cached),
+                    audio_tokens: None,
+                }
+            }),
+            completion_tokens_details: self.thoughts_token_count.map(|thoughts| {
+                CompletionTokensDetails {
+                    reasoning_tokens: Some(thoughts),
+                    audio_tokens: None,
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_round_trip() {
+        let messages = vec![
+            Message::system("Be brief."),
+            Message::user("Weather in Paris?"),
+            Message::builder(Role::Assistant)
+                .tool_call(
+                    "call_1",
+                    "get_weather",
+                    serde_json::json!({"city": "Paris"}),
+                )
+                .build(),
+            Message::tool_result("call_1", serde_json::json!("sunny")),
+        ];
+        let body =
+            VertexAiProvider::build_body(&ChatCompletionRequest::new("gemini-2.5-pro", messages));
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        assert_eq!(body["contents"][1]["role"], "model");
+        assert_eq!(
+            body["contents"][2]["parts"][0]["functionResponse"],
+            serde_json::json!({"name": "get_weather", "response": {"content": "sunny"}})
+        );
+
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "Looking it up.", "thought": true},
+                    {"functionCall": {"name": "get_weather", "args": {"city": "Paris"}}}
+                ]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 5, "thoughtsTokenCount": 3, "totalTokenCount": 18},
+            "modelVersion": "gemini-2.5-pro",
+            "responseId": "abc"
+        }))
+        .unwrap();
+        let converted = VertexAiProvider::convert_response("gemini-2.5-pro", response);
+        assert_eq!(converted.choices[0].finish_reason, FinishReason::ToolCalls);
+        assert_eq!(converted.usage.completion_tokens, 8);
+        assert_eq!(converted.usage.reasoning_tokens(), 3);
+        assert!(matches!(
+            &converted.choices[0].message.content[0],
+            ContentPart::Reasoning { .. }
+        ));
+    }
+
+    #[test]
+    fn test_file_attachment() {
+        let message = Message::builder(Role::User)
+            .text("Summarize the termination clause.")
+            .file("contract.pdf", "application/pdf", b"%PDF-1.7")
+            .build();
+        let body = VertexAiProvider::build_body(&ChatCompletionRequest::new(
+            "gemini-2.5-pro",
+            vec![message],
+        ));
+        assert_eq!(
+            body["contents"][0]["parts"][1],
+            serde_json::json!({"inlineData": {"mimeType": "application/pdf", "data": "JVBERi0xLjc="}})
+        );
+    }
+}
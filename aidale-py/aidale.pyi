@@ -0,0 +1,33 @@
+"""Type stubs for the aidale extension module."""
+
+from typing import Any, AsyncIterator, Optional
+
+__version__: str
+
+class AidaleError(Exception):
+    code: Optional[str]
+    status: Optional[int]
+
+class Provider:
+    @staticmethod
+    def openai(api_key: str, base_url: Optional[str] = None) -> Provider: ...
+    @staticmethod
+    def openai_responses(api_key: str, base_url: Optional[str] = None) -> Provider: ...
+    @staticmethod
+    def cohere(api_key: str, base_url: Optional[str] = None) -> Provider: ...
+    @staticmethod
+    def deepseek(api_key: str) -> Provider: ...
+    @property
+    def id(self) -> str: ...
+
+class ChatStream:
+    def __aiter__(self) -> AsyncIterator[dict[str, Any]]: ...
+    async def __anext__(self) -> dict[str, Any]: ...
+
+class Executor:
+    def __init__(
+        self, provider: Provider, layers: Optional[list[dict[str, Any]]] = None
+    ) -> None: ...
+    async def chat(self, request: dict[str, Any]) -> dict[str, Any]: ...
+    def stream(self, request: dict[str, Any]) -> ChatStream: ...
+    async def models(self) -> list[dict[str, Any]]: ...
@@ -0,0 +1,440 @@
+//! # Aidale Python bindings
+//!
+//! Exposes providers, layer stacks and the runtime executor to Python, so
+//! Python services go through the same layered gateway as Rust ones.
+//! Requests and responses use the OpenAI chat completion format as plain
+//! dicts, coroutines run on a shared Tokio runtime, and streams are async
+//! iterators:
+//!
+//! ```python
+//! import aidale
+//!
+//! provider = aidale.Provider.openai(os.environ["OPENAI_API_KEY"])
+//! executor = aidale.Executor(provider, layers=[{"type": "retry", "max_retries": 3}])
+//!
+//! response = await executor.chat({
+//!     "model": "gpt-4o-mini",
+//!     "messages": [{"role": "user", "content": "Hello"}],
+//! })
+//! print(response["choices"][0]["message"]["content"])
+//!
+//! async for chunk in executor.stream({"model": "gpt-4o-mini", "messages": messages}):
+//!     print(chunk["choices"][0]["delta"].get("content", ""), end="")
+//! ```
+//!
+//! Layers are given as descriptors (see `aidale_layer::build_layer_stack`).
+//! Failures raise `aidale.AidaleError`, carrying the provider's error `code`
+//! and HTTP `status` when known. Build the wheel with `maturin build`.
+
+use aidale_core::error::AiError;
+use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_core::types::ChatCompletionRequest;
+use aidale_layer::{build_layer_stack, LayerDescriptor};
+use aidale_provider::{CohereProvider, OpenAiProvider, OpenAiResponsesProvider};
+use aidale_serve::convert::{parse_request, response_json, ChunkEncoder, OpenAiRequest};
+use futures::StreamExt;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyStopAsyncIteration, PyValueError};
+use pyo3::ffi::c_str;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::PyCFunction;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+create_exception!(
+    aidale,
+    AidaleError,
+    PyException,
+    "Error raised by a provider, layer or the runtime"
+);
+
+/// Raise an [`AiError`] as `AidaleError` with `code` and `status` attributes
+fn py_err(err: AiError) -> PyErr {
+    let exception = AidaleError::new_err(err.to_string());
+    Python::with_gil(|py| {
+        let value = exception.value(py);
+        let _ = value.setattr("code", err.code());
+        let _ = value.setattr("status", err.status());
+    });
+    exception
+}
+
+/// Convert a JSON-serializable Python object
+fn to_json(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let text: String = obj
+        .py()
+        .import("json")?
+        .call_method1("dumps", (obj,))?
+        .extract()?;
+    serde_json::from_str(&text).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Convert a JSON value to Python dicts, lists and scalars
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    let obj = py
+        .import("json")?
+        .call_method1("loads", (value.to_string(),))?;
+    Ok(obj.unbind())
+}
+
+/// Runtime driving every request
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("aidale")
+            .build()
+            .expect("Failed to start the Tokio runtime")
+    })
+}
+
+/// Runtime tasks that will take the GIL to resolve their awaitable
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+struct InFlight;
+
+impl InFlight {
+    fn new() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Resolve an asyncio future unless it was cancelled
+fn resolver(py: Python<'_>) -> PyResult<&Bound<'_, PyAny>> {
+    static RESOLVE: GILOnceCell<PyObject> = GILOnceCell::new();
+    let resolve = RESOLVE.get_or_try_init(py, || {
+        let code = c_str!(
+            "def resolve(future, result, error):
+    if not future.done():
+        if error is None:
+            future.set_result(result)
+        else:
+            future.set_exception(error)
+"
+        );
+        let module =
+            PyModule::from_code(py, code, c_str!("aidale_resolve.py"), c_str!("_resolve"))?;
+        Ok::<_, PyErr>(module.getattr("resolve")?.unbind())
+    })?;
+    Ok(resolve.bind(py))
+}
+
+/// Run `future` on the runtime, returning an asyncio future of its result
+///
+/// The awaitable is resolved on the caller's event loop, and cancelling it
+/// aborts the task. Tasks are counted until they are done with the GIL, so
+/// the interpreter can wait for them at exit instead of finalizing while a
+/// runtime thread is inside it.
+fn spawn_awaitable<'py, F>(py: Python<'py>, future: F) -> PyResult<Bound<'py, PyAny>>
+where
+    F: Future<Output = PyResult<PyObject>> + Send + 'static,
+{
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    let awaitable = event_loop.call_method0("create_future")?;
+    let resolve = resolver(py)?.clone().unbind();
+    let target = awaitable.clone().unbind();
+    let event_loop = event_loop.unbind();
+
+    let in_flight = InFlight::new();
+    let task = runtime().spawn(async move {
+        let _in_flight = in_flight;
+        let result = future.await;
+        Python::with_gil(move |py| {
+            let (result, error) = match result {
+                Ok(result) => (result, py.None()),
+                Err(err) => (py.None(), err.into_value(py).into_any()),
+            };
+            // Fails only if the event loop was closed in the meantime
+            let _ = event_loop
+                .bind(py)
+                .call_method1("call_soon_threadsafe", (resolve, target, result, error));
+        });
+    });
+
+    let abort = task.abort_handle();
+    let on_done = PyCFunction::new_closure(py, None, None, move |args, _| {
+        if args.get_item(0)?.call_method0("cancelled")?.is_truthy()? {
+            abort.abort();
+        }
+        Ok::<_, PyErr>(())
+    })?;
+    awaitable.call_method1("add_done_callback", (on_done,))?;
+    Ok(awaitable)
+}
+
+/// Give runtime tasks up to a second to finish before the interpreter shuts
+/// down; registered with `atexit`
+#[pyfunction]
+fn drain(py: Python<'_>) {
+    py.allow_threads(|| {
+        let started = Instant::now();
+        while IN_FLIGHT.load(Ordering::SeqCst) > 0 && started.elapsed() < Duration::from_secs(1) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    });
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A model provider
+#[pyclass(name = "Provider", module = "aidale", frozen)]
+#[derive(Clone)]
+struct PyProvider {
+    inner: Arc<dyn Provider>,
+}
+
+#[pymethods]
+impl PyProvider {
+    /// OpenAI, or any OpenAI-compatible server at `base_url`
+    #[staticmethod]
+    #[pyo3(signature = (api_key, base_url = None))]
+    fn openai(api_key: String, base_url: Option<String>) -> PyResult<Self> {
+        let mut builder = OpenAiProvider::builder().api_key(api_key);
+        if let Some(base_url) = base_url {
+            builder = builder.api_base(base_url);
+        }
+        Ok(Self {
+            inner: Arc::new(builder.build().map_err(py_err)?),
+        })
+    }
+
+    /// OpenAI through the Responses API
+    #[staticmethod]
+    #[pyo3(signature = (api_key, base_url = None))]
+    fn openai_responses(api_key: String, base_url: Option<String>) -> Self {
+        let mut provider = OpenAiResponsesProvider::new(api_key);
+        if let Some(base_url) = base_url {
+            provider = provider.with_api_base(base_url);
+        }
+        Self {
+            inner: Arc::new(provider),
+        }
+    }
+
+    /// Cohere
+    #[staticmethod]
+    #[pyo3(signature = (api_key, base_url = None))]
+    fn cohere(api_key: String, base_url: Option<String>) -> Self {
+        let mut provider = CohereProvider::new(api_key);
+        if let Some(base_url) = base_url {
+            provider = provider.with_api_base(base_url);
+        }
+        Self {
+            inner: Arc::new(provider),
+        }
+    }
+
+    /// DeepSeek
+    #[staticmethod]
+    fn deepseek(api_key: String) -> PyResult<Self> {
+        Ok(Self {
+            inner: Arc::new(aidale_provider::deepseek(api_key).map_err(py_err)?),
+        })
+    }
+
+    /// Provider ID, e.g. `openai`
+    #[getter]
+    fn id(&self) -> String {
+        self.inner.info().id.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Provider({:?})", self.inner.info().id)
+    }
+}
+
+/// Runs requests against a provider wrapped in a layer stack
+#[pyclass(name = "Executor", module = "aidale", frozen)]
+struct PyExecutor {
+    inner: Arc<RuntimeExecutor>,
+}
+
+#[pymethods]
+impl PyExecutor {
+    #[new]
+    #[pyo3(signature = (provider, layers = None))]
+    fn new(provider: &PyProvider, layers: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let mut inner = provider.inner.clone();
+        if let Some(layers) = layers {
+            let layers: Vec<LayerDescriptor> = serde_json::from_value(to_json(layers)?)
+                .map_err(|e| PyValueError::new_err(format!("Invalid layers: {}", e)))?;
+            inner = build_layer_stack(inner, &layers).map_err(py_err)?;
+        }
+        Ok(Self {
+            inner: Arc::new(RuntimeExecutor::builder(inner).finish()),
+        })
+    }
+
+    /// Send a chat completion request, returning the `chat.completion` dict
+    fn chat<'py>(
+        &self,
+        py: Python<'py>,
+        request: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let OpenAiRequest { request, .. } = parse_request(to_json(request)?).map_err(py_err)?;
+        let executor = self.inner.clone();
+        spawn_awaitable(py, async move {
+            let response = executor.chat_completion(request).await.map_err(py_err)?;
+            let response = response_json(&response, unix_time());
+            Python::with_gil(|py| to_py(py, &response))
+        })
+    }
+
+    /// Stream a chat completion as `chat.completion.chunk` dicts
+    fn stream(&self, request: &Bound<'_, PyAny>) -> PyResult<PyChatStream> {
+        let OpenAiRequest {
+            request,
+            include_usage,
+            ..
+        } = parse_request(to_json(request)?).map_err(py_err)?;
+        let state = StreamState {
+            executor: self.inner.clone(),
+            request: Some(request),
+            chunks: None,
+            encoder: ChunkEncoder::new(unix_time(), include_usage),
+            ready: VecDeque::new(),
+        };
+        Ok(PyChatStream {
+            state: Arc::new(tokio::sync::Mutex::new(state)),
+        })
+    }
+
+    /// List the provider's models
+    fn models<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let executor = self.inner.clone();
+        spawn_awaitable(py, async move {
+            let models = executor.models().await.map_err(py_err)?;
+            let models =
+                serde_json::to_value(models).map_err(|e| py_err(AiError::Serialization(e)))?;
+            Python::with_gil(|py| to_py(py, &models))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Executor({:?})", self.inner.info().id)
+    }
+}
+
+struct StreamState {
+    executor: Arc<RuntimeExecutor>,
+    /// Request to send on the first `__anext__`
+    request: Option<ChatCompletionRequest>,
+    chunks: Option<Box<ChatCompletionStream>>,
+    encoder: ChunkEncoder,
+    /// Encoded chunks not yet returned
+    ready: VecDeque<Value>,
+}
+
+/// Async iterator over the chunks of a streamed chat completion
+///
+/// The request is sent when iteration starts.
+#[pyclass(name = "ChatStream", module = "aidale", frozen)]
+struct PyChatStream {
+    state: Arc<tokio::sync::Mutex<StreamState>>,
+}
+
+#[pymethods]
+impl PyChatStream {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let state = self.state.clone();
+        spawn_awaitable(py, async move {
+            let mut state = state.lock().await;
+            loop {
+                if let Some(chunk) = state.ready.pop_front() {
+                    return Python::with_gil(|py| to_py(py, &chunk));
+                }
+                if state.chunks.is_none() {
+                    let Some(request) = state.request.take() else {
+                        return Err(PyStopAsyncIteration::new_err(()));
+                    };
+                    let chunks = state.executor.stream(request).await.map_err(py_err)?;
+                    state.chunks = Some(chunks);
+                }
+
+                let next = match state.chunks.as_mut() {
+                    Some(chunks) => chunks.next().await,
+                    None => None,
+                };
+                match next {
+                    Some(Ok(chunk)) => {
+                        let encoded = state.encoder.encode(&chunk);
+                        state.ready.extend(encoded);
+                    }
+                    Some(Err(err)) => {
+                        state.chunks = None;
+                        return Err(py_err(err));
+                    }
+                    None => {
+                        state.chunks = None;
+                        return Err(PyStopAsyncIteration::new_err(()));
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "aidale")]
+fn aidale_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("AidaleError", m.py().get_type::<AidaleError>())?;
+    m.add_class::<PyProvider>()?;
+    m.add_class::<PyExecutor>()?;
+    m.add_class::<PyChatStream>()?;
+    m.py()
+        .import("atexit")?
+        .call_method1("register", (wrap_pyfunction!(drain, m)?,))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::error::ApiErrorDetails;
+    use serde_json::json;
+
+    #[test]
+    fn test_conversions() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let value =
+                json!({"model": "m", "messages": [{"role": "user", "content": "hi"}], "n": 1.5});
+            let obj = to_py(py, &value).unwrap();
+            assert_eq!(to_json(obj.bind(py)).unwrap(), value);
+
+            let details = ApiErrorDetails::new("openai")
+                .with_status(404)
+                .with_code("model_not_found");
+            let err = py_err(AiError::model_not_found("gpt-9").with_details(details));
+            assert!(err.is_instance_of::<AidaleError>(py));
+            let status: Option<u16> = err.value(py).getattr("status").unwrap().extract().unwrap();
+            let code: Option<String> = err.value(py).getattr("code").unwrap().extract().unwrap();
+            assert_eq!(status, Some(404));
+            assert_eq!(code.as_deref(), Some("model_not_found"));
+        });
+    }
+}
@@ -0,0 +1,435 @@
+//! Conversion between OpenAI chat completion JSON and Aidale types.
+
+use aidale_core::error::AiError;
+use aidale_core::json_repair;
+use aidale_core::types::*;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// A parsed `/v1/chat/completions` request body
+#[derive(Debug, Clone)]
+pub struct OpenAiRequest {
+    pub request: ChatCompletionRequest,
+    /// Whether the client asked for a stream
+    pub stream: bool,
+    /// Whether the client asked for a final usage chunk
+    /// (`stream_options.include_usage`)
+    pub include_usage: bool,
+}
+
+fn invalid(message: impl Into<String>) -> AiError {
+    AiError::invalid_request(message.into())
+}
+
+/// Remove a field from the body, treating `null` as absent
+fn take<T: DeserializeOwned>(
+    body: &mut Map<String, Value>,
+    key: &str,
+) -> Result<Option<T>, AiError> {
+    match body.remove(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| invalid(format!("Invalid '{}': {}", key, e))),
+    }
+}
+
+/// Parse an OpenAI chat completion request body
+///
+/// Fields without an Aidale counterpart are kept in
+/// [`ChatCompletionRequest::extra`] and passed on to the provider.
+pub fn parse_request(body: Value) -> Result<OpenAiRequest, AiError> {
+    let Value::Object(mut body) = body else {
+        return Err(invalid("Request body must be a JSON object"));
+    };
+
+    let model: String = take(&mut body, "model")?.ok_or_else(|| invalid("Missing 'model'"))?;
+    let messages: Vec<Value> =
+        take(&mut body, "messages")?.ok_or_else(|| invalid("Missing 'messages'"))?;
+    let messages = messages
+        .into_iter()
+        .map(parse_message)
+        .collect::<Result<_, _>>()?;
+
+    let mut request = ChatCompletionRequest::new(model, messages);
+    request.temperature = take(&mut body, "temperature")?;
+    let max_completion_tokens = take(&mut body, "max_completion_tokens")?;
+    request.max_tokens = max_completion_tokens.or(take(&mut body, "max_tokens")?);
+    request.top_p = take(&mut body, "top_p")?;
+    request.frequency_penalty = take(&mut body, "frequency_penalty")?;
+    request.presence_penalty = take(&mut body, "presence_penalty")?;
+    request.stop = match body.remove("stop") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(stop)) => Some(vec![stop]),
+        Some(stop) => Some(
+            serde_json::from_value(stop).map_err(|e| invalid(format!("Invalid 'stop': {}", e)))?,
+        ),
+    };
+    request.tools = take::<Vec<Value>>(&mut body, "tools")?
+        .map(|tools| tools.into_iter().map(parse_tool).collect())
+        .transpose()?;
+    request.tool_choice = body
+        .remove("tool_choice")
+        .filter(|choice| !choice.is_null())
+        .map(parse_tool_choice)
+        .transpose()?;
+    request.parallel_tool_calls = take(&mut body, "parallel_tool_calls")?;
+    request.response_format = body
+        .remove("response_format")
+        .filter(|format| !format.is_null())
+        .map(parse_response_format)
+        .transpose()?;
+    request.reasoning_effort = take(&mut body, "reasoning_effort")?;
+    request.seed = take(&mut body, "seed")?;
+    request.logprobs = take(&mut body, "logprobs")?;
+    request.top_logprobs = take(&mut body, "top_logprobs")?;
+    request.logit_bias = take(&mut body, "logit_bias")?;
+    request.n = take(&mut body, "n")?;
+    request.user = take(&mut body, "user")?;
+
+    let stream = take(&mut body, "stream")?.unwrap_or(false);
+    let include_usage = take::<Value>(&mut body, "stream_options")?
+        .and_then(|options| options.get("include_usage").and_then(Value::as_bool))
+        .unwrap_or(false);
+    request.extra = body.into_iter().collect::<HashMap<_, _>>();
+
+    Ok(OpenAiRequest {
+        request,
+        stream,
+        include_usage,
+    })
+}
+
+/// Text of a `content` field, which is a string or an array of parts
+fn parse_content(content: Option<Value>) -> Result<Vec<ContentPart>, AiError> {
+    let parts = match content {
+        None | Some(Value::Null) => return Ok(Vec::new()),
+        Some(Value::String(text)) => return Ok(vec![ContentPart::Text { text }]),
+        Some(Value::Array(parts)) => parts,
+        Some(_) => return Err(invalid("'content' must be a string or an array")),
+    };
+    parts
+        .into_iter()
+        .map(|part| match part["type"].as_str() {
+            Some("text") => Ok(ContentPart::Text {
+                text: part["text"].as_str().unwrap_or_default().to_string(),
+            }),
+            Some("image_url") => {
+                let url = part["image_url"]["url"]
+                    .as_str()
+                    .or(part["image_url"].as_str())
+                    .ok_or_else(|| invalid("Missing 'image_url.url'"))?;
+                Ok(ContentPart::Image {
+                    url: url.to_string(),
+                })
+            }
+            other => Err(AiError::unsupported(format!(
+                "Content part type {}",
+                other.unwrap_or("(none)")
+            ))),
+        })
+        .collect()
+}
+
+fn parse_message(message: Value) -> Result<Message, AiError> {
+    let Value::Object(mut message) = message else {
+        return Err(invalid("Messages must be JSON objects"));
+    };
+    let role: String = take(&mut message, "role")?.ok_or_else(|| invalid("Missing 'role'"))?;
+    let name: Option<String> = take(&mut message, "name")?;
+    let mut content = parse_content(message.remove("content"))?;
+
+    let role = match role.as_str() {
+        "system" | "developer" => Role::System,
+        "user" => Role::User,
+        "assistant" => {
+            let calls: Vec<Value> = take(&mut message, "tool_calls")?.unwrap_or_default();
+            for call in calls {
+                let arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+                content.push(ContentPart::ToolCall {
+                    id: call["id"].as_str().unwrap_or_default().to_string(),
+                    name: call["function"]["name"]
+                        .as_str()
+                        .ok_or_else(|| invalid("Missing 'function.name' in tool call"))?
+                        .to_string(),
+                    arguments: json_repair::parse(arguments)
+                        .unwrap_or_else(|_| Value::String(arguments.to_string())),
+                });
+            }
+            Role::Assistant
+        }
+        "tool" => {
+            let id: String = take(&mut message, "tool_call_id")?
+                .ok_or_else(|| invalid("Missing 'tool_call_id' in tool message"))?;
+            let text: String = content
+                .into_iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text),
+                    _ => None,
+                })
+                .collect();
+            content = vec![ContentPart::ToolResult {
+                id,
+                result: Value::String(text),
+            }];
+            Role::Tool
+        }
+        other => return Err(invalid(format!("Unknown role '{}'", other))),
+    };
+
+    Ok(Message {
+        role,
+        content,
+        name,
+        cache_control: None,
+    })
+}
+
+fn parse_tool(tool: Value) -> Result<Tool, AiError> {
+    if tool["type"].as_str() != Some("function") {
+        return Err(AiError::unsupported(format!("Tool type {}", tool["type"])));
+    }
+    let function = &tool["function"];
+    let name = function["name"]
+        .as_str()
+        .ok_or_else(|| invalid("Missing 'function.name' in tool"))?;
+    let parameters = match &function["parameters"] {
+        Value::Null => json!({"type": "object", "properties": {}}),
+        parameters => parameters.clone(),
+    };
+    Ok(Tool::new(
+        name,
+        function["description"].as_str().unwrap_or_default(),
+        parameters,
+    )
+    .with_strict(function["strict"].as_bool().unwrap_or(false)))
+}
+
+fn parse_tool_choice(choice: Value) -> Result<ToolChoice, AiError> {
+    match choice.as_str() {
+        Some("auto") => Ok(ToolChoice::Auto),
+        Some("none") => Ok(ToolChoice::None),
+        Some("required") => Ok(ToolChoice::Required),
+        _ => choice["function"]["name"]
+            .as_str()
+            .map(ToolChoice::named)
+            .ok_or_else(|| invalid(format!("Invalid 'tool_choice': {}", choice))),
+    }
+}
+
+fn parse_response_format(format: Value) -> Result<ResponseFormat, AiError> {
+    match format["type"].as_str() {
+        Some("text") => Ok(ResponseFormat::Text),
+        Some("json_object") => Ok(ResponseFormat::JsonObject),
+        Some("json_schema") => {
+            let schema = &format["json_schema"];
+            Ok(ResponseFormat::JsonSchema {
+                name: schema["name"].as_str().unwrap_or("response").to_string(),
+                schema: schema["schema"].clone(),
+                strict: schema["strict"].as_bool().unwrap_or(false),
+            })
+        }
+        _ => Err(invalid(format!("Invalid 'response_format': {}", format))),
+    }
+}
+
+fn finish_reason(reason: &FinishReason) -> &str {
+    match reason {
+        FinishReason::Stop => "stop",
+        FinishReason::Length => "length",
+        FinishReason::ToolCalls => "tool_calls",
+        FinishReason::ContentFilter => "content_filter",
+        FinishReason::Other(reason) => reason,
+    }
+}
+
+/// OpenAI `tool_calls` entries of the tool call parts, numbered from `index`
+fn tool_calls(parts: &[ContentPart], index: usize) -> Vec<Value> {
+    parts
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::ToolCall {
+                id,
+                name,
+                arguments,
+            } => Some((id, name, arguments)),
+            _ => None,
+        })
+        .enumerate()
+        .map(|(i, (id, name, arguments))| {
+            let arguments = match arguments {
+                Value::String(raw) => raw.clone(),
+                other => other.to_string(),
+            };
+            json!({
+                "index": index + i,
+                "id": id,
+                "type": "function",
+                "function": {"name": name, "arguments": arguments},
+            })
+        })
+        .collect()
+}
+
+/// Serialize a response as an OpenAI `chat.completion` object
+pub fn response_json(response: &ChatCompletionResponse, created: u64) -> Value {
+    let choices: Vec<Value> = response
+        .choices
+        .iter()
+        .map(|choice| {
+            let mut text = String::new();
+            let mut reasoning = String::new();
+            for part in &choice.message.content {
+                match part {
+                    ContentPart::Text { text: t } => text.push_str(t),
+                    ContentPart::Reasoning { text: t } => reasoning.push_str(t),
+                    _ => {}
+                }
+            }
+            let mut message = json!({
+                "role": "assistant",
+                "content": if text.is_empty() { Value::Null } else { Value::String(text) },
+            });
+            let calls = tool_calls(&choice.message.content, 0);
+            if !calls.is_empty() {
+                message["tool_calls"] = Value::Array(calls);
+            }
+            if !reasoning.is_empty() {
+                message["reasoning_content"] = Value::String(reasoning);
+            }
+            json!({
+                "index": choice.index,
+                "message": message,
+                "finish_reason": finish_reason(&choice.finish_reason),
+                "logprobs": choice.logprobs.as_ref().map(|content| json!({"content": content})),
+            })
+        })
+        .collect();
+
+    let mut body = json!({
+        "id": response.id,
+        "object": "chat.completion",
+        "created": response.created.unwrap_or(created),
+        "model": response.model,
+        "choices": choices,
+        "usage": response.usage,
+    });
+    if let Some(fingerprint) = &response.system_fingerprint {
+        body["system_fingerprint"] = json!(fingerprint);
+    }
+    if let Some(tier) = &response.service_tier {
+        body["service_tier"] = json!(tier);
+    }
+    body
+}
+
+/// Serializes stream chunks as OpenAI `chat.completion.chunk` objects
+///
+/// Aidale streams deliver tool calls complete; they are sent as single
+/// deltas numbered per choice, as OpenAI clients expect.
+#[derive(Debug)]
+pub struct ChunkEncoder {
+    created: u64,
+    include_usage: bool,
+    tool_calls: HashMap<u32, usize>,
+}
+
+impl ChunkEncoder {
+    /// Create an encoder; `include_usage` adds a final chunk with the usage
+    pub fn new(created: u64, include_usage: bool) -> Self {
+        Self {
+            created,
+            include_usage,
+            tool_calls: HashMap::new(),
+        }
+    }
+
+    /// Chunk objects to send for a stream chunk
+    pub fn encode(&mut self, chunk: &ChatCompletionChunk) -> Vec<Value> {
+        let mut out = Vec::new();
+        let base = |choices: Vec<Value>| {
+            let mut body = json!({
+                "id": chunk.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": chunk.model,
+                "choices": choices,
+            });
+            if let Some(fingerprint) = &chunk.system_fingerprint {
+                body["system_fingerprint"] = json!(fingerprint);
+            }
+            body
+        };
+
+        if !chunk.choices.is_empty() {
+            let mut choices = Vec::with_capacity(chunk.choices.len());
+            for choice in &chunk.choices {
+                let mut delta = Map::new();
+                if let Some(role) = &choice.delta.role {
+                    delta.insert("role".to_string(), json!(role));
+                }
+                if let Some(content) = &choice.delta.content {
+                    delta.insert("content".to_string(), json!(content));
+                }
+                if let Some(reasoning) = &choice.delta.reasoning {
+                    delta.insert("reasoning_content".to_string(), json!(reasoning));
+                }
+                if let Some(parts) = &choice.delta.tool_calls {
+                    let index = self.tool_calls.entry(choice.index).or_default();
+                    let calls = tool_calls(parts, *index);
+                    *index += calls.len();
+                    if !calls.is_empty() {
+                        delta.insert("tool_calls".to_string(), Value::Array(calls));
+                    }
+                }
+                choices.push(json!({
+                    "index": choice.index,
+                    "delta": delta,
+                    "finish_reason": choice.finish_reason.as_ref().map(finish_reason),
+                }));
+            }
+            out.push(base(choices));
+        }
+
+        if let (true, Some(usage)) = (self.include_usage, &chunk.usage) {
+            let mut body = base(Vec::new());
+            body["usage"] = json!(usage);
+            out.push(body);
+        }
+        out
+    }
+}
+
+/// HTTP status and OpenAI error body for an error
+pub fn error_json(error: &AiError) -> (u16, Value) {
+    let (status, kind) = match error {
+        AiError::InvalidRequest { .. }
+        | AiError::Serialization(_)
+        | AiError::ContentBlocked { .. }
+        | AiError::Unsupported(_) => (400, "invalid_request_error"),
+        AiError::Authentication { .. } => (401, "authentication_error"),
+        AiError::ModelNotFound { .. } => (404, "invalid_request_error"),
+        AiError::RateLimit { .. } | AiError::BudgetExhausted { .. } => (429, "rate_limit_error"),
+        AiError::Timeout { .. } | AiError::DeadlineExceeded(_) => (504, "timeout_error"),
+        AiError::Provider { .. } | AiError::Network(_) | AiError::Stream(_) => {
+            (502, "upstream_error")
+        }
+        _ => (500, "server_error"),
+    };
+    let code = error.code().map(str::to_string).or(match error {
+        AiError::ModelNotFound { .. } => Some("model_not_found".to_string()),
+        AiError::BudgetExhausted { .. } => Some("insufficient_quota".to_string()),
+        AiError::ContentBlocked { .. } => Some("content_filter".to_string()),
+        _ => None,
+    });
+    let body = json!({
+        "error": {
+            "message": error.to_string(),
+            "type": kind,
+            "param": null,
+            "code": code,
+        }
+    });
+    (status, body)
+}
@@ -0,0 +1,422 @@
+//! # Aidale Serve
+//!
+//! An OpenAI-compatible HTTP gateway in front of a [`RuntimeExecutor`].
+//!
+//! Clients written against the OpenAI API (the official SDKs, Open WebUI,
+//! LangChain, ...) can talk to an Aidale stack (layers, plugins, routing
+//! across providers) by pointing their base URL at the server:
+//!
+//! ```ignore
+//! let executor = RuntimeExecutor::builder(provider)
+//!     .layer(RetryLayer::new())
+//!     .plugin(Arc::new(ToolUsePlugin::new()))
+//!     .finish();
+//!
+//! Server::new(Arc::new(executor))
+//!     .with_api_key("sk-gateway")
+//!     .serve("0.0.0.0:8080")
+//!     .await?;
+//! ```
+//!
+//! Routes:
+//!
+//! - `POST /v1/chat/completions`, streaming included (`stream: true` answers
+//!   with `chat.completion.chunk` server-sent events ending in `[DONE]`)
+//! - `GET /v1/models`, listing [`RuntimeExecutor::models`]
+//!
+//! Requests go through [`RuntimeExecutor::chat_completion`] and
+//! [`RuntimeExecutor::stream`], so layers and the model-level plugin hooks
+//! apply. Errors are answered with OpenAI's error body and a matching status
+//! (see [`convert::error_json`]). [`Server::router`] returns the
+//! [`axum::Router`] for embedding the routes in an existing application.
+//!
+//! The `cli` feature builds the `aidale-serve` binary, a gateway to an
+//! OpenAI-compatible upstream configured through environment variables.
+
+pub mod convert;
+
+use aidale_core::error::AiError;
+use aidale_core::runtime::RuntimeExecutor;
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::{stream, StreamExt};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use convert::{parse_request, ChunkEncoder, OpenAiRequest};
+
+/// OpenAI-compatible server for a runtime executor
+#[derive(Clone)]
+pub struct Server {
+    executor: Arc<RuntimeExecutor>,
+    api_key: Option<Arc<str>>,
+}
+
+impl Server {
+    /// Serve `executor` without authentication
+    pub fn new(executor: Arc<RuntimeExecutor>) -> Self {
+        Self {
+            executor,
+            api_key: None,
+        }
+    }
+
+    /// Require clients to send `Authorization: Bearer <api_key>`
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into().into());
+        self
+    }
+
+    /// Routes of the server
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .route("/v1/models", get(models))
+            .with_state(self)
+    }
+
+    /// Listen on `addr` until the process is stopped
+    pub async fn serve(self, addr: impl tokio::net::ToSocketAddrs) -> Result<(), AiError> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| AiError::other(format!("Failed to bind: {}", e)))?;
+        if let Ok(addr) = listener.local_addr() {
+            tracing::info!("Serving OpenAI-compatible API on http://{}", addr);
+        }
+        axum::serve(listener, self.router())
+            .await
+            .map_err(|e| AiError::other(format!("Server error: {}", e)))
+    }
+
+    /// Check the bearer token, if one is configured
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(api_key) = &self.api_key else {
+            return true;
+        };
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        token == Some(&**api_key)
+    }
+}
+
+fn unauthorized() -> Response {
+    let body = json!({
+        "error": {
+            "message": "Incorrect API key provided",
+            "type": "invalid_request_error",
+            "param": null,
+            "code": "invalid_api_key",
+        }
+    });
+    (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+}
+
+impl std::fmt::Debug for Server {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Server")
+            .field("provider", &self.executor.info().id)
+            .field("api_key", &self.api_key.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn error_response(error: &AiError) -> Response {
+    let (status, body) = convert::error_json(error);
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    (status, Json(body)).into_response()
+}
+
+async fn chat_completions(
+    State(server): State<Server>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !server.is_authorized(&headers) {
+        return unauthorized();
+    }
+    let parsed = serde_json::from_slice::<Value>(&body)
+        .map_err(|e| AiError::invalid_request(format!("Invalid JSON body: {}", e)))
+        .and_then(parse_request);
+    let OpenAiRequest {
+        request,
+        stream,
+        include_usage,
+    } = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => return error_response(&err),
+    };
+    let created = unix_time();
+
+    if !stream {
+        return match server.executor.chat_completion(request).await {
+            Ok(response) => Json(convert::response_json(&response, created)).into_response(),
+            Err(err) => {
+                tracing::warn!("Chat completion failed: {}", err);
+                error_response(&err)
+            }
+        };
+    }
+
+    let chunks = match server.executor.stream(request).await {
+        Ok(chunks) => chunks,
+        Err(err) => {
+            tracing::warn!("Chat completion stream failed: {}", err);
+            return error_response(&err);
+        }
+    };
+    let mut encoder = ChunkEncoder::new(created, include_usage);
+    let events = chunks
+        .flat_map(move |chunk| {
+            let events = match chunk {
+                Ok(chunk) => encoder
+                    .encode(&chunk)
+                    .iter()
+                    .map(|body| aidale_http::sse::encode_event(None, &body.to_string()))
+                    .collect(),
+                // The status is already sent; report the error in-band
+                Err(err) => {
+                    let (_, body) = convert::error_json(&err);
+                    vec![aidale_http::sse::encode_event(None, &body.to_string())]
+                }
+            };
+            stream::iter(events)
+        })
+        .chain(stream::once(async {
+            aidale_http::sse::encode_event(None, aidale_http::sse::DONE)
+        }))
+        .map(Ok::<_, Infallible>);
+
+    let mut response = Response::new(Body::from_stream(events));
+    for (name, value) in aidale_http::SSE_HEADERS {
+        response.headers_mut().insert(
+            HeaderName::from_static(name),
+            HeaderValue::from_static(value),
+        );
+    }
+    response
+}
+
+async fn models(State(server): State<Server>, headers: HeaderMap) -> Response {
+    if !server.is_authorized(&headers) {
+        return unauthorized();
+    }
+    match server.executor.models().await {
+        Ok(models) => {
+            let owner = server.executor.info().id.clone();
+            let data: Vec<Value> = models
+                .into_iter()
+                .map(|model| {
+                    json!({
+                        "id": model.id,
+                        "object": "model",
+                        "created": model.created.unwrap_or(0),
+                        "owned_by": model.owned_by.unwrap_or_else(|| owner.clone()),
+                    })
+                })
+                .collect();
+            Json(json!({"object": "list", "data": data})).into_response()
+        }
+        Err(err) => error_response(&err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::provider::{ChatCompletionStream, Provider};
+    use aidale_core::types::*;
+    use async_trait::async_trait;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    /// Echoes the last user message, calling `lookup` when tools are given
+    #[derive(Debug)]
+    struct EchoProvider;
+
+    fn reply(req: &ChatCompletionRequest) -> Message {
+        let text = match req.messages.last().and_then(|m| m.content.first()) {
+            Some(ContentPart::Text { text }) => text.clone(),
+            _ => String::new(),
+        };
+        match &req.tools {
+            Some(_) => Message {
+                role: Role::Assistant,
+                content: vec![ContentPart::ToolCall {
+                    id: "call_1".to_string(),
+                    name: "lookup".to_string(),
+                    arguments: json!({"q": text}),
+                }],
+                name: None,
+                cache_control: None,
+            },
+            None => Message::assistant(text),
+        }
+    }
+
+    #[async_trait]
+    impl Provider for EchoProvider {
+        fn info(&self) -> Arc<ProviderInfo> {
+            Arc::new(ProviderInfo {
+                id: "echo".to_string(),
+                name: "Echo".to_string(),
+            })
+        }
+
+        async fn chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<ChatCompletionResponse, AiError> {
+            if req.model == "missing" {
+                return Err(AiError::model_not_found("missing"));
+            }
+            Ok(ChatCompletionResponse {
+                id: "resp_1".to_string(),
+                model: req.model.clone(),
+                choices: vec![Choice {
+                    index: 0,
+                    message: reply(&req),
+                    finish_reason: FinishReason::Stop,
+                    logprobs: None,
+                    stop_sequence: None,
+                    filtered_categories: Vec::new(),
+                }],
+                usage: Usage {
+                    prompt_tokens: 3,
+                    completion_tokens: 2,
+                    total_tokens: 5,
+                    ..Usage::default()
+                },
+                created: None,
+                system_fingerprint: None,
+                service_tier: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            req: ChatCompletionRequest,
+        ) -> Result<Box<ChatCompletionStream>, AiError> {
+            let message = reply(&req);
+            let chunk = |delta: MessageDelta, finish_reason, usage| {
+                Ok(ChatCompletionChunk {
+                    id: "resp_1".to_string(),
+                    model: req.model.clone(),
+                    choices: vec![ChoiceDelta {
+                        index: 0,
+                        delta,
+                        finish_reason,
+                    }],
+                    usage,
+                    system_fingerprint: None,
+                    service_tier: None,
+                })
+            };
+            let chunks = vec![
+                chunk(
+                    MessageDelta {
+                        role: Some(Role::Assistant),
+                        content: None,
+                        reasoning: None,
+                        tool_calls: Some(message.content),
+                    },
+                    None,
+                    None,
+                ),
+                chunk(
+                    MessageDelta {
+                        role: None,
+                        content: None,
+                        reasoning: None,
+                        tool_calls: None,
+                    },
+                    Some(FinishReason::ToolCalls),
+                    Some(Usage::default()),
+                ),
+            ];
+            Ok(Box::new(stream::iter(chunks)))
+        }
+    }
+
+    async fn post(router: &Router, body: Value, key: &str) -> (StatusCode, String) {
+        let request = Request::post("/v1/chat/completions")
+            .header("authorization", format!("Bearer {}", key))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_chat_completions() {
+        let executor = RuntimeExecutor::builder(EchoProvider).finish();
+        let router = Server::new(Arc::new(executor))
+            .with_api_key("secret")
+            .router();
+        let body = json!({
+            "model": "m",
+            "messages": [
+                {"role": "developer", "content": "Be brief."},
+                {"role": "user", "content": [{"type": "text", "text": "Hello"}]},
+            ],
+        });
+
+        let (status, _) = post(&router, body.clone(), "wrong").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, text) = post(&router, body.clone(), "secret").await;
+        assert_eq!(status, StatusCode::OK);
+        let response: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(response["object"], "chat.completion");
+        assert_eq!(response["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(response["choices"][0]["finish_reason"], "stop");
+        assert_eq!(response["usage"]["total_tokens"], 5);
+
+        let mut missing = body.clone();
+        missing["model"] = json!("missing");
+        let (status, text) = post(&router, missing, "secret").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(text.contains("model_not_found"));
+
+        let mut streamed = body;
+        streamed["stream"] = json!(true);
+        streamed["stream_options"] = json!({"include_usage": true});
+        streamed["tools"] = json!([{
+            "type": "function",
+            "function": {"name": "lookup", "parameters": {"type": "object"}},
+        }]);
+        let (status, text) = post(&router, streamed, "secret").await;
+        assert_eq!(status, StatusCode::OK);
+        let events: Vec<&str> = text
+            .split("\n\n")
+            .filter_map(|event| event.strip_prefix("data: "))
+            .collect();
+        assert_eq!(events.len(), 4);
+        let first: Value = serde_json::from_str(events[0]).unwrap();
+        let call = &first["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(call["index"], 0);
+        assert_eq!(call["function"]["arguments"], r#"{"q":"Hello"}"#);
+        let usage: Value = serde_json::from_str(events[2]).unwrap();
+        assert_eq!(usage["choices"], json!([]));
+        assert_eq!(events[3], "[DONE]");
+    }
+}
@@ -0,0 +1,56 @@
+//! `aidale-serve`: an OpenAI-compatible gateway to an OpenAI-compatible
+//! upstream.
+//!
+//! Configured through environment variables:
+//!
+//! - `OPENAI_API_KEY` (required) and `OPENAI_BASE_URL`: the upstream
+//! - `AIDALE_LAYERS`: path of a JSON file with a list of layer descriptors
+//!   (see `aidale_layer::build_layer_stack`)
+//! - `AIDALE_API_KEY`: bearer token clients must send
+//! - `AIDALE_ADDR`: listen address, `127.0.0.1:8080` by default
+
+use aidale_core::error::AiError;
+use aidale_core::provider::Provider;
+use aidale_core::runtime::RuntimeExecutor;
+use aidale_layer::{build_layer_stack, LayerDescriptor};
+use aidale_provider::OpenAiProvider;
+use aidale_serve::Server;
+use std::sync::Arc;
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+async fn run() -> Result<(), AiError> {
+    let api_key =
+        env("OPENAI_API_KEY").ok_or_else(|| AiError::configuration("OPENAI_API_KEY is not set"))?;
+    let mut builder = OpenAiProvider::builder().api_key(api_key);
+    if let Some(base) = env("OPENAI_BASE_URL") {
+        builder = builder.api_base(base);
+    }
+    let mut provider: Arc<dyn Provider> = Arc::new(builder.build()?);
+
+    if let Some(path) = env("AIDALE_LAYERS") {
+        let config = std::fs::read_to_string(&path)
+            .map_err(|e| AiError::configuration(format!("Failed to read {}: {}", path, e)))?;
+        let layers: Vec<LayerDescriptor> = serde_json::from_str(&config)?;
+        provider = build_layer_stack(provider, &layers)?;
+    }
+
+    let executor = RuntimeExecutor::builder(provider).finish();
+    let mut server = Server::new(Arc::new(executor));
+    if let Some(key) = env("AIDALE_API_KEY") {
+        server = server.with_api_key(key);
+    }
+    let addr = env("AIDALE_ADDR").unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    server.serve(addr).await
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+    if let Err(err) = run().await {
+        eprintln!("aidale-serve: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,123 @@
+//! Assertions over captured exchanges.
+//!
+//! All assertions panic with a description of what was captured, so a
+//! failing test shows the prompts or tool calls that were actually seen.
+
+use crate::capture::InteractionLog;
+use crate::golden::render_prompt;
+use aidale_core::types::*;
+
+/// Whether `actual` contains `expected`: objects may have extra fields,
+/// arrays and scalars must match exactly
+pub fn json_contains(actual: &serde_json::Value, expected: &serde_json::Value) -> bool {
+    match (actual, expected) {
+        (serde_json::Value::Object(actual), serde_json::Value::Object(expected)) => {
+            expected.iter().all(|(key, value)| {
+                actual
+                    .get(key)
+                    .is_some_and(|actual| json_contains(actual, value))
+            })
+        }
+        _ => actual == expected,
+    }
+}
+
+/// Assert that some captured request has a message containing `needle`
+#[track_caller]
+pub fn assert_prompt_contains(log: &InteractionLog, needle: &str) {
+    let requests = log.requests();
+    let found = requests.iter().any(|req| {
+        req.messages.iter().any(|msg| {
+            msg.content.iter().any(|part| match part {
+                ContentPart::Text { text } => text.contains(needle),
+                _ => false,
+            })
+        })
+    });
+
+    if !found {
+        let prompts: Vec<_> = requests.iter().map(render_prompt).collect();
+        panic!(
+            "no prompt contains {:?}; captured {} request(s):\n\n{}",
+            needle,
+            requests.len(),
+            prompts.join("\n========\n")
+        );
+    }
+}
+
+/// Assert that the model called tool `name` with arguments containing
+/// `expected` (see [`json_contains`])
+#[track_caller]
+pub fn assert_tool_called_with(log: &InteractionLog, name: &str, expected: serde_json::Value) {
+    let exchanges = log.exchanges();
+    let calls: Vec<_> = exchanges
+        .iter()
+        .flat_map(|exchange| exchange.tool_calls())
+        .filter_map(|part| match part {
+            ContentPart::ToolCall {
+                name, arguments, ..
+            } => Some((name.as_str(), arguments)),
+            _ => None,
+        })
+        .collect();
+
+    let found = calls
+        .iter()
+        .any(|(called, arguments)| *called == name && json_contains(arguments, &expected));
+
+    if !found {
+        let seen: Vec<_> = calls
+            .iter()
+            .map(|(called, arguments)| format!("  {}({})", called, arguments))
+            .collect();
+        panic!(
+            "tool {:?} was not called with {}; tool calls seen:\n{}",
+            name,
+            expected,
+            if seen.is_empty() {
+                "  (none)".to_string()
+            } else {
+                seen.join("\n")
+            }
+        );
+    }
+}
+
+/// Assert the number of captured requests
+#[track_caller]
+pub fn assert_request_count(log: &InteractionLog, expected: usize) {
+    let actual = log.len();
+    assert_eq!(
+        actual, expected,
+        "expected {} request(s), captured {}",
+        expected, actual
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockProvider;
+    use aidale_core::provider::Provider;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_assertions() {
+        let mock = MockProvider::new().with_tool_call(
+            "get_weather",
+            json!({"city": "Paris", "units": {"temp": "c", "wind": "kmh"}}),
+        );
+        let log = mock.log();
+        let req = ChatCompletionRequest::new("m", vec![Message::user("Weather in Paris?")]);
+        mock.chat_completion(req).await.unwrap();
+
+        assert_prompt_contains(&log, "in Paris");
+        assert_tool_called_with(&log, "get_weather", json!({"units": {"temp": "c"}}));
+        assert_request_count(&log, 1);
+
+        assert!(!json_contains(&json!({"a": [1, 2]}), &json!({"a": [1]})));
+        let missing = std::panic::catch_unwind(|| assert_prompt_contains(&log, "London"));
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! Exchange capture.
+//!
+//! [`InteractionLog`] is a shared handle: clone it before handing the
+//! provider to an executor and inspect it after the code under test ran.
+
+use aidale_core::audio::{Speech, SpeechRequest, Transcription, TranscriptionRequest};
+use aidale_core::error::AiError;
+use aidale_core::layer::{Layer, LayeredProvider};
+use aidale_core::provider::{ApiRequest, ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+
+/// What a captured request produced
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Response(ChatCompletionResponse),
+    /// Chunks received so far
+    Stream(Vec<ChatCompletionChunk>),
+    Error(String),
+}
+
+/// A request with its outcome
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub request: ChatCompletionRequest,
+    pub outcome: Outcome,
+}
+
+impl Exchange {
+    /// Tool calls returned by the model
+    pub fn tool_calls(&self) -> Vec<&ContentPart> {
+        let is_call = |part: &&ContentPart| matches!(part, ContentPart::ToolCall { .. });
+        match &self.outcome {
+            Outcome::Response(response) => response
+                .choices
+                .iter()
+                .flat_map(|choice| choice.message.content.iter().filter(is_call))
+                .collect(),
+            Outcome::Stream(chunks) => chunks
+                .iter()
+                .flat_map(|chunk| &chunk.choices)
+                .filter_map(|choice| choice.delta.tool_calls.as_ref())
+                .flat_map(|calls| calls.iter().filter(is_call))
+                .collect(),
+            Outcome::Error(_) => Vec::new(),
+        }
+    }
+}
+
+/// Shared, append-only log of exchanges
+#[derive(Debug, Clone, Default)]
+pub struct InteractionLog {
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl InteractionLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Snapshot of all exchanges, oldest first
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().unwrap().clone()
+    }
+
+    /// Snapshot of all requests, oldest first
+    pub fn requests(&self) -> Vec<ChatCompletionRequest> {
+        self.exchanges
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|exchange| exchange.request.clone())
+            .collect()
+    }
+
+    /// The most recent request
+    pub fn last_request(&self) -> Option<ChatCompletionRequest> {
+        self.exchanges
+            .lock()
+            .unwrap()
+            .last()
+            .map(|exchange| exchange.request.clone())
+    }
+
+    /// Number of captured exchanges
+    pub fn len(&self) -> usize {
+        self.exchanges.lock().unwrap().len()
+    }
+
+    /// Whether nothing was captured
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all exchanges
+    pub fn clear(&self) {
+        self.exchanges.lock().unwrap().clear();
+    }
+
+    /// Append an exchange, returning its index
+    pub(crate) fn push(&self, request: ChatCompletionRequest, outcome: Outcome) -> usize {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        exchanges.push(Exchange { request, outcome });
+        exchanges.len() - 1
+    }
+
+    /// Record the result of the stream at `index`
+    pub(crate) fn record_stream_item(
+        &self,
+        index: usize,
+        item: &Result<ChatCompletionChunk, AiError>,
+    ) {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let Some(exchange) = exchanges.get_mut(index) else {
+            return;
+        };
+        match (item, &mut exchange.outcome) {
+            (Ok(chunk), Outcome::Stream(chunks)) => chunks.push(chunk.clone()),
+            (Err(err), outcome) => *outcome = Outcome::Error(err.to_string()),
+            _ => {}
+        }
+    }
+
+    /// Wrap a stream so that its items are recorded at `index`
+    pub(crate) fn capture_stream(
+        &self,
+        index: usize,
+        mut stream: Box<ChatCompletionStream>,
+    ) -> Box<ChatCompletionStream> {
+        let log = self.clone();
+        let captured = async_stream::stream! {
+            while let Some(item) = stream.next().await {
+                log.record_stream_item(index, &item);
+                yield item;
+            }
+        };
+        Box::new(Box::pin(captured))
+    }
+}
+
+/// Layer that captures every exchange of the wrapped provider
+#[derive(Debug, Clone, Default)]
+pub struct CapturingLayer {
+    log: InteractionLog,
+}
+
+impl CapturingLayer {
+    /// Create a layer with a fresh log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle to the captured exchanges
+    pub fn log(&self) -> InteractionLog {
+        self.log.clone()
+    }
+}
+
+impl<P: Provider> Layer<P> for CapturingLayer {
+    type LayeredProvider = CapturingProvider<P>;
+
+    fn layer(&self, inner: P) -> Self::LayeredProvider {
+        CapturingProvider {
+            inner,
+            log: self.log.clone(),
+        }
+    }
+}
+
+/// Provider wrapped with exchange capture
+#[derive(Debug)]
+pub struct CapturingProvider<P> {
+    inner: P,
+    log: InteractionLog,
+}
+
+#[async_trait]
+impl<P: Provider> LayeredProvider for CapturingProvider<P> {
+    type Inner = P;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn layered_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let result = self.inner.chat_completion(req.clone()).await;
+        let outcome = match &result {
+            Ok(response) => Outcome::Response(response.clone()),
+            Err(err) => Outcome::Error(err.to_string()),
+        };
+        self.log.push(req, outcome);
+        result
+    }
+
+    async fn layered_stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        match self.inner.stream_chat_completion(req.clone()).await {
+            Ok(stream) => {
+                let index = self.log.push(req, Outcome::Stream(Vec::new()));
+                Ok(self.log.capture_stream(index, stream))
+            }
+            Err(err) => {
+                self.log.push(req, Outcome::Error(err.to_string()));
+                Err(err)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for CapturingProvider<P> {
+    fn info(&self) -> Arc<ProviderInfo> {
+        LayeredProvider::layered_info(self)
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        LayeredProvider::layered_chat_completion(self, req).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        LayeredProvider::layered_stream_chat_completion(self, req).await
+    }
+
+    async fn chat_completion_raw(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_chat_completion_raw(self, body).await
+    }
+
+    async fn api_request(&self, req: ApiRequest) -> Result<serde_json::Value, AiError> {
+        LayeredProvider::layered_api_request(self, req).await
+    }
+
+    async fn speech(&self, req: SpeechRequest) -> Result<Speech, AiError> {
+        LayeredProvider::layered_speech(self, req).await
+    }
+
+    async fn transcription(&self, req: TranscriptionRequest) -> Result<Transcription, AiError> {
+        LayeredProvider::layered_transcription(self, req).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AiError> {
+        LayeredProvider::layered_list_models(self).await
+    }
+
+    async fn health_check(&self) -> Result<(), AiError> {
+        LayeredProvider::layered_health_check(self).await
+    }
+}
@@ -0,0 +1,94 @@
+//! Fake clock for time-dependent layers.
+//!
+//! [`FakeClock`] pauses Tokio's clock: `tokio::time::sleep` returns as soon
+//! as the runtime is idle, with virtual time advanced by the sleep duration.
+//! Layers that measure time with `aidale_core::rt::now` (such as the retry
+//! layer's time budget) see the virtual time, so backoff schedules can be
+//! asserted exactly without slowing tests down.
+//!
+//! The clock can only be paused on a current-thread runtime, which is what
+//! `#[tokio::test]` uses by default.
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Paused Tokio clock with a fixed starting point
+#[derive(Debug, Clone, Copy)]
+pub struct FakeClock {
+    start: Instant,
+}
+
+impl FakeClock {
+    /// Pause the current runtime's clock
+    ///
+    /// # Panics
+    ///
+    /// Panics outside a current-thread Tokio runtime or if the clock is
+    /// already paused.
+    pub fn pause() -> Self {
+        tokio::time::pause();
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    /// Move virtual time forward, firing due timers
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::advance(duration).await;
+    }
+
+    /// Virtual time elapsed since the clock was paused
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Current virtual instant
+    pub fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Let time run in real time again
+    pub fn resume(self) {
+        tokio::time::resume();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assertions::assert_request_count;
+    use crate::capture::CapturingLayer;
+    use crate::mock::MockProvider;
+    use aidale_core::error::AiError;
+    use aidale_core::layer::Layer;
+    use aidale_core::provider::Provider;
+    use aidale_core::types::*;
+    use aidale_layer::RetryLayer;
+
+    #[tokio::test]
+    async fn test_retry_backoff_in_virtual_time() {
+        let clock = FakeClock::pause();
+        let capture = CapturingLayer::new();
+        let log = capture.log();
+
+        let mock = MockProvider::new()
+            .with_error(AiError::rate_limit("slow down"))
+            .with_error(AiError::rate_limit("slow down"))
+            .with_text("ok");
+        let provider = RetryLayer::new()
+            .with_initial_delay(Duration::from_secs(1))
+            .with_max_retries(3)
+            .layer(capture.layer(mock));
+
+        let req = ChatCompletionRequest::new("m", vec![Message::user("Hi")]);
+        provider.chat_completion(req).await.unwrap();
+
+        // 1s then 2s of exponential backoff, without waiting for real; Tokio
+        // rounds each sleep up to the next millisecond tick
+        assert_eq!(clock.elapsed().as_secs(), 3);
+        assert_request_count(&log, 3);
+
+        clock.advance(Duration::from_secs(7)).await;
+        assert_eq!(clock.elapsed().as_secs(), 10);
+    }
+}
@@ -0,0 +1,164 @@
+//! Golden-file prompt snapshots.
+//!
+//! [`render_prompt`] turns a request into stable, reviewable text. Tool call
+//! IDs are left out because several providers generate them randomly.
+//! Golden files are created on first run; afterwards a mismatch fails with a
+//! line diff unless [`UPDATE_ENV`] is set, in which case the file is
+//! rewritten.
+
+use crate::capture::InteractionLog;
+use aidale_core::types::*;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Environment variable that makes golden assertions rewrite their files
+pub const UPDATE_ENV: &str = "AIDALE_UPDATE_GOLDEN";
+
+/// Render a request as text for snapshots and failure messages
+pub fn render_prompt(req: &ChatCompletionRequest) -> String {
+    let mut out = format!("model: {}\n", req.model);
+    if let Some(tools) = &req.tools {
+        let names: Vec<_> = tools.iter().map(|tool| tool.name.as_str()).collect();
+        let _ = writeln!(out, "tools: {}", names.join(", "));
+    }
+
+    for msg in &req.messages {
+        let role = match msg.role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        };
+        let _ = writeln!(out, "\n--- {}", role);
+        for part in &msg.content {
+            match part {
+                ContentPart::Text { text } => {
+                    let _ = writeln!(out, "{}", text);
+                }
+                ContentPart::Image { url } => {
+                    let _ = writeln!(out, "[image {}]", url);
+                }
+                ContentPart::ImageData { mime_type, data } => {
+                    let _ = writeln!(out, "[image {} {} base64 chars]", mime_type, data.len());
+                }
+                ContentPart::FileRef { id } => {
+                    let _ = writeln!(out, "[file {}]", id);
+                }
+                ContentPart::File { name, mime, data } => {
+                    let _ = writeln!(out, "[file {} {} {} base64 chars]", name, mime, data.len());
+                }
+                ContentPart::ToolCall {
+                    name, arguments, ..
+                } => {
+                    let _ = writeln!(out, "[tool call {} {}]", name, arguments);
+                }
+                ContentPart::ToolResult { result, .. } => {
+                    let _ = writeln!(out, "[tool result {}]", result);
+                }
+                ContentPart::Reasoning { text } => {
+                    let _ = writeln!(out, "[reasoning {}]", text);
+                }
+                ContentPart::Citation(_) => {}
+            }
+        }
+    }
+    out
+}
+
+/// Compare `actual` with the golden file at `path`
+#[track_caller]
+pub fn assert_golden(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+    let update = std::env::var_os(UPDATE_ENV).is_some();
+
+    match std::fs::read_to_string(path) {
+        Ok(expected) if expected == actual => {}
+        Ok(expected) if !update => panic!(
+            "golden file {} does not match (set {}=1 to update):\n{}",
+            path.display(),
+            UPDATE_ENV,
+            line_diff(&expected, actual)
+        ),
+        _ => {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).expect("create golden file directory");
+            }
+            std::fs::write(path, actual).expect("write golden file");
+        }
+    }
+}
+
+/// Compare the most recent captured prompt with the golden file at `path`
+#[track_caller]
+pub fn assert_prompt_golden(log: &InteractionLog, path: impl AsRef<Path>) {
+    let req = log
+        .last_request()
+        .expect("no request was captured for the golden prompt");
+    assert_golden(path, &render_prompt(&req));
+}
+
+/// Line diff with `-` for expected-only and `+` for actual-only lines
+fn line_diff(expected: &str, actual: &str) -> String {
+    let a: Vec<_> = expected.lines().collect();
+    let b: Vec<_> = actual.lines().collect();
+
+    // Longest common subsequence lengths of the suffixes
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            let _ = writeln!(out, "  {}", a[i]);
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "- {}", a[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {}", b[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_diff() {
+        let req = ChatCompletionRequest::new(
+            "gpt-4o",
+            vec![
+                Message::system("Be brief."),
+                Message::builder(Role::Assistant)
+                    .tool_call("call_x", "search", serde_json::json!({"q": "rust"}))
+                    .build(),
+            ],
+        );
+        assert_eq!(
+            render_prompt(&req),
+            "model: gpt-4o\n\n--- system\nBe brief.\n\n--- assistant\n[tool call search {\"q\":\"rust\"}]\n"
+        );
+
+        assert_eq!(line_diff("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c\n");
+
+        let path = std::env::temp_dir().join(format!("golden-{}.txt", std::process::id()));
+        assert_golden(&path, "first");
+        assert_golden(&path, "first");
+        let changed = std::panic::catch_unwind(|| assert_golden(&path, "second"));
+        std::fs::remove_file(&path).unwrap();
+        assert!(changed.is_err() || std::env::var_os(UPDATE_ENV).is_some());
+    }
+}
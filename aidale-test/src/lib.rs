@@ -0,0 +1,43 @@
+//! # Aidale Test
+//!
+//! Deterministic testing utilities for code built on Aidale.
+//!
+//! - [`MockProvider`] answers with scripted responses, streamed or not
+//! - [`CapturingLayer`] records every exchange of any provider into an
+//!   [`InteractionLog`], for example around a
+//!   [`ReplayProvider`](https://docs.rs/aidale-layer) cassette
+//! - [`assert_prompt_contains`], [`assert_tool_called_with`] and
+//!   [`assert_request_count`] check what was sent and answered
+//! - [`assert_prompt_golden`] compares the rendered prompt with a golden
+//!   file (set `AIDALE_UPDATE_GOLDEN=1` to rewrite it)
+//! - [`FakeClock`] pauses Tokio's clock so retry and backoff delays elapse
+//!   instantly and can be asserted exactly
+//!
+//! ```ignore
+//! let mock = MockProvider::new()
+//!     .with_tool_call("get_weather", json!({"city": "Paris"}))
+//!     .with_text("Bring an umbrella.");
+//! let log = mock.log();
+//!
+//! let executor = RuntimeExecutor::builder(mock).finish();
+//! agent_under_test(&executor).await?;
+//!
+//! assert_prompt_contains(&log, "Paris");
+//! assert_tool_called_with(&log, "get_weather", json!({"city": "Paris"}));
+//! assert_prompt_golden(&log, "tests/golden/weather.txt");
+//! ```
+
+pub mod assertions;
+pub mod capture;
+pub mod clock;
+pub mod golden;
+pub mod mock;
+
+// Re-exports
+pub use assertions::{
+    assert_prompt_contains, assert_request_count, assert_tool_called_with, json_contains,
+};
+pub use capture::{CapturingLayer, CapturingProvider, Exchange, InteractionLog, Outcome};
+pub use clock::FakeClock;
+pub use golden::{assert_golden, assert_prompt_golden, render_prompt, UPDATE_ENV};
+pub use mock::MockProvider;
@@ -0,0 +1,241 @@
+//! Scripted provider.
+
+use crate::capture::{InteractionLog, Outcome};
+use aidale_core::error::AiError;
+use aidale_core::provider::{ChatCompletionStream, Provider};
+use aidale_core::types::*;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Provider answering with scripted responses, in order
+///
+/// Every request, streamed or not, consumes the next scripted response and
+/// is captured in [`Self::log`]. Streams deliver each choice as one chunk.
+/// Requests beyond the script fail with `AiError::Provider`.
+#[derive(Debug)]
+pub struct MockProvider {
+    info: Arc<ProviderInfo>,
+    responses: Mutex<VecDeque<Result<ChatCompletionResponse, AiError>>>,
+    served: AtomicUsize,
+    log: InteractionLog,
+}
+
+impl MockProvider {
+    /// Create a provider with ID `mock` and an empty script
+    pub fn new() -> Self {
+        Self {
+            info: Arc::new(ProviderInfo {
+                id: "mock".to_string(),
+                name: "Mock".to_string(),
+            }),
+            responses: Mutex::new(VecDeque::new()),
+            served: AtomicUsize::new(0),
+            log: InteractionLog::new(),
+        }
+    }
+
+    /// Report a different provider ID, e.g. to exercise provider-specific
+    /// JSON strategies
+    pub fn with_info(mut self, id: impl Into<String>, name: impl Into<String>) -> Self {
+        self.info = Arc::new(ProviderInfo {
+            id: id.into(),
+            name: name.into(),
+        });
+        self
+    }
+
+    /// Queue a full response
+    pub fn with_response(self, response: ChatCompletionResponse) -> Self {
+        self.responses.lock().unwrap().push_back(Ok(response));
+        self
+    }
+
+    /// Queue a text answer
+    pub fn with_text(self, text: impl Into<String>) -> Self {
+        self.with_message(Message::assistant(text), FinishReason::Stop)
+    }
+
+    /// Queue a single tool call
+    pub fn with_tool_call(self, name: impl Into<String>, arguments: serde_json::Value) -> Self {
+        let id = format!("call_{}", self.responses.lock().unwrap().len());
+        let message = Message::builder(Role::Assistant)
+            .tool_call(id, name, arguments)
+            .build();
+        self.with_message(message, FinishReason::ToolCalls)
+    }
+
+    /// Queue an error
+    pub fn with_error(self, error: AiError) -> Self {
+        self.responses.lock().unwrap().push_back(Err(error));
+        self
+    }
+
+    fn with_message(self, message: Message, finish_reason: FinishReason) -> Self {
+        self.with_response(ChatCompletionResponse {
+            id: String::new(),
+            model: String::new(),
+            choices: vec![Choice {
+                index: 0,
+                message,
+                finish_reason,
+                logprobs: None,
+                stop_sequence: None,
+                filtered_categories: Vec::new(),
+            }],
+            usage: Usage::default(),
+            created: None,
+            system_fingerprint: None,
+            service_tier: None,
+        })
+    }
+
+    /// Handle to the captured exchanges
+    pub fn log(&self) -> InteractionLog {
+        self.log.clone()
+    }
+
+    /// Number of scripted responses not yet served
+    pub fn remaining(&self) -> usize {
+        self.responses.lock().unwrap().len()
+    }
+
+    /// Next scripted response, filled in for `req`
+    fn next(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, AiError> {
+        let next = self.responses.lock().unwrap().pop_front();
+        let served = self.served.fetch_add(1, Ordering::SeqCst);
+        let mut response = next.unwrap_or_else(|| {
+            Err(AiError::provider(format!(
+                "MockProvider has no scripted response for request {}",
+                served + 1
+            )))
+        })?;
+
+        if response.id.is_empty() {
+            response.id = format!("mock-{}", served);
+        }
+        if response.model.is_empty() {
+            response.model = req.model.clone();
+        }
+        Ok(response)
+    }
+
+    /// Split a response into one chunk per choice, usage on the last one
+    fn to_chunks(response: ChatCompletionResponse) -> Vec<ChatCompletionChunk> {
+        let count = response.choices.len();
+        response
+            .choices
+            .into_iter()
+            .enumerate()
+            .map(|(i, choice)| {
+                let (text, tool_calls): (Vec<_>, Vec<_>) = choice
+                    .message
+                    .content
+                    .into_iter()
+                    .partition(|part| matches!(part, ContentPart::Text { .. }));
+                let text: String = text
+                    .into_iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text } => Some(text),
+                        _ => None,
+                    })
+                    .collect();
+                let tool_calls: Vec<_> = tool_calls
+                    .into_iter()
+                    .filter(|part| matches!(part, ContentPart::ToolCall { .. }))
+                    .collect();
+
+                ChatCompletionChunk {
+                    id: response.id.clone(),
+                    model: response.model.clone(),
+                    choices: vec![ChoiceDelta {
+                        index: choice.index,
+                        delta: MessageDelta {
+                            role: Some(Role::Assistant),
+                            content: (!text.is_empty()).then_some(text),
+                            reasoning: None,
+                            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                        },
+                        finish_reason: Some(choice.finish_reason),
+                    }],
+                    usage: (i + 1 == count).then(|| response.usage.clone()),
+                    system_fingerprint: None,
+                    service_tier: None,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    fn info(&self) -> Arc<ProviderInfo> {
+        self.info.clone()
+    }
+
+    async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let result = self.next(&req);
+        let outcome = match &result {
+            Ok(response) => Outcome::Response(response.clone()),
+            Err(err) => Outcome::Error(err.to_string()),
+        };
+        self.log.push(req, outcome);
+        result
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<Box<ChatCompletionStream>, AiError> {
+        match self.next(&req) {
+            Ok(response) => {
+                let chunks = Self::to_chunks(response);
+                self.log.push(req, Outcome::Stream(chunks.clone()));
+                Ok(Box::new(futures::stream::iter(chunks.into_iter().map(Ok))))
+            }
+            Err(err) => {
+                self.log.push(req, Outcome::Error(err.to_string()));
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aidale_core::RuntimeExecutor;
+
+    #[tokio::test]
+    async fn test_scripted_responses_are_logged() {
+        let mock = MockProvider::new()
+            .with_tool_call("lookup", serde_json::json!({"q": "rust"}))
+            .with_text("Done.");
+        let log = mock.log();
+        let executor = RuntimeExecutor::builder(mock).finish();
+
+        let params = TextParams::new(vec![Message::user("Look up rust")]);
+        let first = executor.generate_text("m", params.clone()).await.unwrap();
+        assert_eq!(first.finish_reason, FinishReason::ToolCalls);
+
+        let mut streamed = executor.stream_text("m", params.clone()).await.unwrap();
+        let text: Vec<_> = futures::StreamExt::collect(streamed.text_stream()).await;
+        assert_eq!(text.len(), 1);
+        assert_eq!(streamed.content(), "Done.");
+
+        assert!(executor.generate_text("m", params).await.is_err());
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.exchanges()[0].tool_calls().len(), 1);
+        assert!(matches!(log.exchanges()[2].outcome, Outcome::Error(_)));
+    }
+}
@@ -52,14 +52,14 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     // Use schemars to generate JSON Schema from Rust struct
     let person_schema = schema_for!(PersonInfo);
 
-    let person_params = ObjectParams {
-        messages: vec![Message::user(
+    let person_params = ObjectParams::new(
+        vec![Message::user(
             "Extract person information: Sarah Johnson is a 28-year-old data scientist who enjoys rock climbing, cooking, and playing piano.",
         )],
-        schema: serde_json::to_value(&person_schema)?,
-        max_tokens: Some(300),
-        temperature: Some(0.1),
-    };
+        serde_json::to_value(&person_schema)?,
+    )
+    .with_max_tokens(300)
+    .with_temperature(0.1);
 
     match executor
         .generate_object("deepseek-chat", person_params)
@@ -89,14 +89,14 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     // Use schemars to generate JSON Schema from Rust struct
     let product_schema = schema_for!(ProductAnalysis);
 
-    let product_params = ObjectParams {
-        messages: vec![Message::user(
+    let product_params = ObjectParams::new(
+        vec![Message::user(
             "Analyze the MacBook Pro M3 and provide a rating out of 5, list of pros and cons.",
         )],
-        schema: serde_json::to_value(&product_schema)?,
-        max_tokens: Some(400),
-        temperature: Some(0.2),
-    };
+        serde_json::to_value(&product_schema)?,
+    )
+    .with_max_tokens(400)
+    .with_temperature(0.2);
 
     match executor
         .generate_object("deepseek-chat", product_params)
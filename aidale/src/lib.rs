@@ -55,6 +55,8 @@
 //! - `providers`: All available providers
 //! - `layers`: Built-in layers (logging, retry, caching, etc.)
 //! - `plugins`: Built-in plugins (tool use, etc.)
+//! - `templates`: Jinja prompt templates with partials and typed variables
+//! - `language`: Output language enforcement with language identification
 //! - `full`: All features enabled
 
 // Re-export core types and traits
@@ -81,6 +83,34 @@ pub mod plugin {
     pub use aidale_plugin::*;
 }
 
+// Re-export agents under `agent` module
+#[cfg(feature = "aidale-agent")]
+pub mod agent {
+    //! Tool-using agents.
+    pub use aidale_agent::*;
+}
+
+// Re-export the evaluation framework under `eval` module
+#[cfg(feature = "aidale-eval")]
+pub mod eval {
+    //! Evaluation datasets, graders and reports.
+    pub use aidale_eval::*;
+}
+
+// Re-export HTTP streaming helpers under `http` module
+#[cfg(feature = "aidale-http")]
+pub mod http {
+    //! SSE and Vercel AI SDK stream encoding for web frameworks.
+    pub use aidale_http::*;
+}
+
+// Re-export the OpenAI-compatible gateway under `serve` module
+#[cfg(feature = "aidale-serve")]
+pub mod serve {
+    //! OpenAI-compatible `/v1/chat/completions` server for a runtime executor.
+    pub use aidale_serve::*;
+}
+
 // Re-export schemars when schema feature is enabled
 #[cfg(feature = "schema")]
 pub mod schemars {
@@ -103,7 +133,6 @@ pub use aidale_core::{
     Result,
 };
 
-/// Prelude module for convenient imports
 pub mod prelude {
     //! Prelude module containing the most commonly used types and traits.
     //!